@@ -0,0 +1,50 @@
+use std::{
+  error::Error,
+  fs::{self, File},
+  io::{BufWriter, Write},
+  path::PathBuf,
+};
+
+use structopt::StructOpt;
+
+use moclib::deser::fits::mef::{hdus_from_fits_ivoa, MocFitsWriter, MocHdu};
+
+#[derive(StructOpt, Debug)]
+pub struct Bundle {
+  #[structopt(parse(from_os_str))]
+  /// Path of the output multi-extension FITS file
+  output: PathBuf,
+  #[structopt(parse(from_os_str), required = true)]
+  /// Paths of the input FITS MOC files (all the MOCs of multi-extension input files are copied)
+  inputs: Vec<PathBuf>,
+}
+
+impl Bundle {
+  pub fn exec(self) -> Result<(), Box<dyn Error>> {
+    let file = File::create(self.output)?;
+    let mut writer = MocFitsWriter::new(BufWriter::new(file))?;
+    for path in self.inputs {
+      let bytes = fs::read(&path)?;
+      let hdus: Vec<MocHdu> = hdus_from_fits_ivoa(&bytes)?
+        .filter(|hdu| hdu.as_ref().map_or(true, |hdu| hdu.is_moc()))
+        .collect::<Result<_, _>>()?;
+      let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+      for hdu in &hdus {
+        let mut metadata = hdu.metadata().clone();
+        // Name the HDU after the input file, so that it can be selected by name
+        if hdu.name().is_none() && !stem.is_empty() {
+          metadata.extname = Some(if hdus.len() == 1 {
+            stem.clone()
+          } else {
+            format!("{}_{}", stem, hdu.index())
+          });
+        }
+        writer.write_moc(hdu.moc()?, &metadata)?;
+      }
+    }
+    writer.into_inner().flush().map_err(|e| e.into())
+  }
+}
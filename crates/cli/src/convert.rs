@@ -1,8 +1,8 @@
 use std::{
   error::Error,
-  fs::File,
+  fs::{self, File},
   io::{BufRead, BufReader},
-  path::PathBuf,
+  path::{Path, PathBuf},
   str::FromStr,
 };
 
@@ -11,14 +11,25 @@ use structopt::StructOpt;
 use moclib::{
   deser::{
     ascii::{from_ascii_ivoa, from_ascii_stream, moc2d_from_ascii_ivoa},
-    fits::{from_fits_ivoa, MocIdxType, MocQtyType, MocType as RMocType, STMocType},
-    json::{cellmoc2d_from_json_aladin, from_json_aladin},
+    fits::{
+      bintable::BinTableReader,
+      from_fits_ivoa_with_metadata,
+      keywords::CoordSys,
+      mef::{hdus_from_fits_ivoa, HduId},
+      multiordermap::mom_from_fits_multiordermap,
+      skymap::mom_from_fits_skymap,
+      MocIdxType, MocQtyType, MocType as RMocType, STMocType,
+    },
+    gz::{is_gz, uncompress},
+    json::{from_json_aladin, rangemoc2d_from_json_aladin},
+    packed::from_packed,
+    votable::{VOTable, VOTableMoc},
   },
   moc::{
     CellMOCIntoIterator, CellMOCIterator, CellOrCellRangeMOCIntoIterator,
-    CellOrCellRangeMOCIterator,
+    CellOrCellRangeMOCIterator, RangeMOCIntoIterator,
   },
-  moc2d::{CellMOC2IntoIterator, CellOrCellRangeMOC2IntoIterator, RangeMOC2IntoIterator},
+  moc2d::{CellOrCellRangeMOC2IntoIterator, RangeMOC2IntoIterator},
   qty::{Frequency, Hpx, Time},
 };
 
@@ -31,6 +42,11 @@ pub enum MocType {
   FMOC,
   STMOC,
   SFMOC,
+  TFMOC,
+  /// Multi-order map (FITS only)
+  MOM,
+  /// HEALPix skymap (FITS only)
+  Skymap,
 }
 impl FromStr for MocType {
   type Err = String;
@@ -42,8 +58,11 @@ impl FromStr for MocType {
       "fmoc" => Ok(MocType::FMOC),
       "stmoc" => Ok(MocType::STMOC),
       "sfmoc" => Ok(MocType::SFMOC),
+      "tfmoc" => Ok(MocType::TFMOC),
+      "mom" => Ok(MocType::MOM),
+      "skymap" => Ok(MocType::Skymap),
       _ => Err(format!(
-        "Unrecognized moc type. Actual: '{}'. Expected: 'moc (or smoc), 'tmoc', 'fmoc', 'stmoc' or 'sfmoc'",
+        "Unrecognized moc type. Actual: '{}'. Expected: 'moc (or smoc), 'tmoc', 'fmoc', 'stmoc', 'sfmoc', 'tfmoc', 'mom' or 'skymap'",
         s
       )),
     }
@@ -56,11 +75,14 @@ pub struct Convert {
   /// Path of the input MOC file (or stdin if equals "-")
   input: PathBuf,
   #[structopt(short = "t", long = "type")]
-  /// Input MOC type ('smoc', 'tmoc', 'fmoc' or 'stmoc') required for 'ascii', 'json' ans 'stream' inputs; ignored for 'fits'
+  /// Input MOC type ('smoc', 'tmoc', 'fmoc', 'stmoc', 'sfmoc' or 'tfmoc') required for 'ascii', 'json' ans 'stream' inputs; ignored for 'fits' except 'mom' and 'skymap' (FITS multi-order map or skymap input, to be written with the 'mom' or 'skymap' outputs)
   moc_type: Option<MocType>,
   #[structopt(short = "f", long = "format")]
-  /// Format of the input MOC ('ascii', 'json', 'fits' or 'stream') [default: guess from the file extension]
+  /// Format of the input MOC ('ascii', 'json', 'fits', 'stream', 'packed' or 'votable') [default: guess from the file extension]
   input_fmt: Option<InputFormat>,
+  #[structopt(long = "hdu")]
+  /// Index (starting at 1) or name (EXTNAME or MOCID) of the HDU to be read in a FITS file containing several MOCs [default: first HDU]
+  hdu: Option<HduId>,
   #[structopt(subcommand)]
   output: OutputFormat,
 }
@@ -81,6 +103,25 @@ impl Convert {
           .into(),
         )
       }
+    } else if let Some(hdu_id) = self.hdu {
+      let bytes = fs::read(path)?;
+      let hdu = hdus_from_fits_ivoa(&bytes)?
+        .find(|hdu| hdu.as_ref().map_or(true, |hdu| hdu.matches(&hdu_id)))
+        .ok_or_else(|| format!("HDU '{}' not found.", hdu_id))??;
+      let output = self.output.with_metadata(hdu.metadata().clone());
+      write_fits_moc(hdu.moc()?, output)
+    } else if let Some(MocType::MOM) = self.moc_type {
+      // Possibly gzipped FITS file, whatever the extension
+      let coordsys = coordsys_from_fits(&path)?;
+      let f = File::open(path)?;
+      let (depth_max, mom) = mom_from_fits_multiordermap(BufReader::new(f))?;
+      self.output.write_mom(depth_max, coordsys, mom)
+    } else if let Some(MocType::Skymap) = self.moc_type {
+      // Possibly gzipped FITS file, whatever the extension; we keep all non-blank values
+      let coordsys = coordsys_from_fits(&path)?;
+      let f = File::open(path)?;
+      let (depth_max, mom) = mom_from_fits_skymap(BufReader::new(f), None, f64::NEG_INFINITY)?;
+      self.output.write_mom(depth_max, coordsys, mom)
     } else {
       let input_fmt = match self.input_fmt {
         Some(input_fmt) => Ok(input_fmt),
@@ -92,6 +133,18 @@ impl Convert {
   }
 }
 
+/// Returns the coordinate system of the given (possibly gzipped) FITS multi-order map or skymap,
+/// ICRS if the `COORDSYS` keyword is missing.
+fn coordsys_from_fits(path: &Path) -> Result<CoordSys, Box<dyn Error>> {
+  let mut reader = BufReader::new(File::open(path)?);
+  let coordsys = if is_gz(&mut reader)? {
+    BinTableReader::open(uncompress(reader))?.coordsys()
+  } else {
+    BinTableReader::open(reader)?.coordsys()
+  };
+  Ok(coordsys.unwrap_or(CoordSys::ICRS))
+}
+
 pub fn exec<R: BufRead>(
   mut input: R,
   input_fmt: InputFormat,
@@ -99,6 +152,26 @@ pub fn exec<R: BufRead>(
   output: OutputFormat,
 ) -> Result<(), Box<dyn Error>> {
   match (moc_type, input_fmt) {
+    // VOTable embedding an ASCII MOC (in a PARAM) or a FITS MOC (in a STREAM)
+    (moc_type, InputFormat::VOTable) => {
+      let votable = VOTable::from_reader(input)?;
+      match votable.moc() {
+        Some(VOTableMoc::Ascii(ascii)) => {
+          // Without explicit type, try the name of the PARAM (e.g. 'smoc', as written by 'moc')
+          let moc_type = moc_type.or_else(|| {
+            votable
+              .moc_param()
+              .and_then(|param| param.name.to_lowercase().parse::<MocType>().ok())
+          });
+          exec(ascii.as_bytes(), InputFormat::Ascii, moc_type, output)
+        }
+        Some(VOTableMoc::Fits(fits)) => exec(fits, InputFormat::Fits, moc_type, output),
+        None => Err(
+          String::from("No MOC found in the VOTable (no 'moc' xtype PARAM, no FITS STREAM).")
+            .into(),
+        ),
+      }
+    }
     // SMOC
     (Some(MocType::SMOC), InputFormat::Ascii) => {
       let mut input_str = Default::default();
@@ -118,6 +191,10 @@ pub fn exec<R: BufRead>(
       let cellrange_it = from_ascii_stream::<u64, Hpx<u64>, _>(input)?;
       output.write_smoc_possibly_auto_converting_from_u64(cellrange_it.ranges())
     }
+    (Some(MocType::SMOC), InputFormat::Packed) => {
+      let moc = from_packed::<u64, Hpx<u64>, _>(input)?.try_into_range_moc()?;
+      output.write_smoc_possibly_auto_converting_from_u64(moc.into_range_moc_iter())
+    }
     // TMOC
     (Some(MocType::TMOC), InputFormat::Ascii) => {
       let mut input_str = String::new();
@@ -137,6 +214,10 @@ pub fn exec<R: BufRead>(
       let cellrange_it = from_ascii_stream::<u64, Time<u64>, _>(input)?;
       output.write_tmoc_possibly_auto_converting_from_u64(cellrange_it.ranges())
     }
+    (Some(MocType::TMOC), InputFormat::Packed) => {
+      let moc = from_packed::<u64, Time<u64>, _>(input)?.try_into_range_moc()?;
+      output.write_tmoc_possibly_auto_converting_from_u64(moc.into_range_moc_iter())
+    }
     // FMOC
     (Some(MocType::FMOC), InputFormat::Ascii) => {
       let mut input_str = String::new();
@@ -156,6 +237,10 @@ pub fn exec<R: BufRead>(
       let cellrange_it = from_ascii_stream::<u64, Frequency<u64>, _>(input)?;
       output.write_fmoc_possibly_auto_converting_from_u64(cellrange_it.ranges())
     }
+    (Some(MocType::FMOC), InputFormat::Packed) => {
+      let moc = from_packed::<u64, Frequency<u64>, _>(input)?.try_into_range_moc()?;
+      output.write_fmoc_possibly_auto_converting_from_u64(moc.into_range_moc_iter())
+    }
     // ST-MOC
     (Some(MocType::STMOC), InputFormat::Ascii) => {
       let mut input_str = String::new();
@@ -170,12 +255,15 @@ pub fn exec<R: BufRead>(
     (Some(MocType::STMOC), InputFormat::Json) => {
       let mut input_str = String::new();
       input.read_to_string(&mut input_str)?;
-      let cell2 = cellmoc2d_from_json_aladin::<u64, Time<u64>, u64, Hpx<u64>>(&input_str)?;
-      output.write_stmoc(cell2.into_cell_moc2_iter().into_range_moc2_iter())
+      let moc2 = rangemoc2d_from_json_aladin::<u64, Time<u64>, u64, Hpx<u64>>(&input_str)?;
+      output.write_stmoc(moc2.into_range_moc2_iter())
     }
     (Some(MocType::STMOC), InputFormat::Stream) => {
       Err(String::from("No stream format for ST-MOCs yet.").into())
     }
+    (Some(MocType::STMOC), InputFormat::Packed) => {
+      Err(String::from("No packed format for ST-MOCs yet.").into())
+    }
     // SF-MOC
     (Some(MocType::SFMOC), InputFormat::Ascii) => {
       let mut input_str = String::new();
@@ -190,98 +278,139 @@ pub fn exec<R: BufRead>(
     (Some(MocType::SFMOC), InputFormat::Json) => {
       let mut input_str = String::new();
       input.read_to_string(&mut input_str)?;
-      let cell2 = cellmoc2d_from_json_aladin::<u64, Frequency<u64>, u64, Hpx<u64>>(&input_str)?;
-      output.write_sfmoc(cell2.into_cell_moc2_iter().into_range_moc2_iter())
+      let moc2 = rangemoc2d_from_json_aladin::<u64, Frequency<u64>, u64, Hpx<u64>>(&input_str)?;
+      output.write_sfmoc(moc2.into_range_moc2_iter())
     }
     (Some(MocType::SFMOC), InputFormat::Stream) => {
       Err(String::from("No stream format for SF-MOCs yet.").into())
     }
-    // FITS file (SMOC or TMOC or FMOC, or ST-MOC)
+    (Some(MocType::SFMOC), InputFormat::Packed) => {
+      Err(String::from("No packed format for SF-MOCs yet.").into())
+    }
+    // TF-MOC
+    (Some(MocType::TFMOC), InputFormat::Ascii) => {
+      let mut input_str = String::new();
+      input.read_to_string(&mut input_str)?;
+      let cellrange2 = moc2d_from_ascii_ivoa::<u64, Time<u64>, u64, Frequency<u64>>(&input_str)?;
+      output.write_tfmoc(
+        cellrange2
+          .into_cellcellrange_moc2_iter()
+          .into_range_moc2_iter(),
+      )
+    }
+    (Some(MocType::TFMOC), InputFormat::Json) => {
+      let mut input_str = String::new();
+      input.read_to_string(&mut input_str)?;
+      let moc2 = rangemoc2d_from_json_aladin::<u64, Time<u64>, u64, Frequency<u64>>(&input_str)?;
+      output.write_tfmoc(moc2.into_range_moc2_iter())
+    }
+    (Some(MocType::TFMOC), InputFormat::Stream) => {
+      Err(String::from("No stream format for TF-MOCs yet.").into())
+    }
+    (Some(MocType::TFMOC), InputFormat::Packed) => {
+      Err(String::from("No packed format for TF-MOCs yet.").into())
+    }
+    // Multi-order map or skymap: only from a FITS file, see `Convert::exec`
+    (Some(MocType::MOM), _) | (Some(MocType::Skymap), _) => {
+      Err(String::from("Multi-order map and skymap inputs must be (non-stdin) FITS files.").into())
+    }
+    // FITS file (SMOC or TMOC or FMOC, or ST-MOC, SF-MOC or TF-MOC)
     (_, InputFormat::Fits) => {
-      let fits_res = from_fits_ivoa(input)?;
-      match fits_res {
-        MocIdxType::U16(moc) => match moc {
-          MocQtyType::Hpx(moc) => match moc {
-            RMocType::Ranges(moc) => output.write_smoc_possibly_converting_to_u64(moc),
-            RMocType::Cells(moc) => {
-              output.write_smoc_possibly_converting_to_u64(moc.into_cell_moc_iter().ranges())
-            }
-          },
-          MocQtyType::Time(moc) => match moc {
-            RMocType::Ranges(moc) => output.write_tmoc_possibly_converting_to_u64(moc),
-            RMocType::Cells(moc) => {
-              output.write_tmoc_possibly_converting_to_u64(moc.into_cell_moc_iter().ranges())
-            }
-          },
-          MocQtyType::Freq(moc) => match moc {
-            RMocType::Ranges(moc) => output.write_fmoc_possibly_converting_to_u64(moc),
-            RMocType::Cells(moc) => {
-              output.write_fmoc_possibly_converting_to_u64(moc.into_cell_moc_iter().ranges())
-            }
-          },
-          MocQtyType::TimeHpx(moc) => match moc {
-            STMocType::V2(moc) => output.write_stmoc(moc),
-            STMocType::PreV2(moc) => output.write_stmoc(moc),
-          },
-          MocQtyType::FreqHpx(moc) => output.write_sfmoc(moc),
-        },
-        MocIdxType::U32(moc) => match moc {
-          MocQtyType::Hpx(moc) => match moc {
-            RMocType::Ranges(moc) => output.write_smoc_possibly_converting_to_u64(moc),
-            RMocType::Cells(moc) => {
-              output.write_smoc_possibly_converting_to_u64(moc.into_cell_moc_iter().ranges())
-            }
-          },
-          MocQtyType::Time(moc) => match moc {
-            RMocType::Ranges(moc) => output.write_tmoc_possibly_converting_to_u64(moc),
-            RMocType::Cells(moc) => {
-              output.write_tmoc_possibly_converting_to_u64(moc.into_cell_moc_iter().ranges())
-            }
-          },
-          MocQtyType::Freq(moc) => match moc {
-            RMocType::Ranges(moc) => output.write_fmoc_possibly_converting_to_u64(moc),
-            RMocType::Cells(moc) => {
-              output.write_fmoc_possibly_converting_to_u64(moc.into_cell_moc_iter().ranges())
-            }
-          },
-          MocQtyType::TimeHpx(moc) => match moc {
-            STMocType::V2(moc) => output.write_stmoc(moc),
-            STMocType::PreV2(moc) => output.write_stmoc(moc),
-          },
-          MocQtyType::FreqHpx(moc) => output.write_sfmoc(moc),
-        },
-        MocIdxType::U64(moc) => match moc {
-          MocQtyType::Hpx(moc) => match moc {
-            RMocType::Ranges(moc) => output.write_smoc_possibly_converting_to_u64(moc),
-            RMocType::Cells(moc) => {
-              output.write_smoc_possibly_converting_to_u64(moc.into_cell_moc_iter().ranges())
-            }
-          },
-          MocQtyType::Time(moc) => match moc {
-            RMocType::Ranges(moc) => output.write_tmoc_possibly_converting_to_u64(moc),
-            RMocType::Cells(moc) => {
-              output.write_tmoc_possibly_converting_to_u64(moc.into_cell_moc_iter().ranges())
-            }
-          },
-          MocQtyType::Freq(moc) => match moc {
-            RMocType::Ranges(moc) => output.write_fmoc_possibly_converting_to_u64(moc),
-            RMocType::Cells(moc) => {
-              output.write_fmoc_possibly_converting_to_u64(moc.into_cell_moc_iter().ranges())
-            }
-          },
-          MocQtyType::TimeHpx(moc) => match moc {
-            STMocType::V2(moc) => output.write_stmoc(moc),
-            STMocType::PreV2(moc) => output.write_stmoc(moc),
-          },
-          MocQtyType::FreqHpx(moc) => output.write_sfmoc(moc),
-        },
-      }
+      let (moc, metadata) = from_fits_ivoa_with_metadata(input)?;
+      write_fits_moc(moc, output.with_metadata(metadata))
     }
     // MOC type required
     _ => Err(String::from("Input MOC type must be specified.").into()),
   }
 }
 
+/// Writes a MOC read from a FITS file (SMOC or TMOC or FMOC, or ST-MOC, SF-MOC or TF-MOC).
+fn write_fits_moc<R: BufRead>(
+  fits_res: MocIdxType<R>,
+  output: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+  match fits_res {
+    MocIdxType::U16(moc) => match moc {
+      MocQtyType::Hpx(moc) => match moc {
+        RMocType::Ranges(moc) => output.write_smoc_possibly_converting_to_u64(moc),
+        RMocType::Cells(moc) => {
+          output.write_smoc_possibly_converting_to_u64(moc.into_cell_moc_iter().ranges())
+        }
+      },
+      MocQtyType::Time(moc) => match moc {
+        RMocType::Ranges(moc) => output.write_tmoc_possibly_converting_to_u64(moc),
+        RMocType::Cells(moc) => {
+          output.write_tmoc_possibly_converting_to_u64(moc.into_cell_moc_iter().ranges())
+        }
+      },
+      MocQtyType::Freq(moc) => match moc {
+        RMocType::Ranges(moc) => output.write_fmoc_possibly_converting_to_u64(moc),
+        RMocType::Cells(moc) => {
+          output.write_fmoc_possibly_converting_to_u64(moc.into_cell_moc_iter().ranges())
+        }
+      },
+      MocQtyType::TimeHpx(moc) => match moc {
+        STMocType::V2(moc) => output.write_stmoc(moc),
+        STMocType::PreV2(moc) => output.write_stmoc(moc),
+      },
+      MocQtyType::FreqHpx(moc) => output.write_sfmoc(moc),
+      MocQtyType::TimeFreq(moc) => output.write_tfmoc(moc),
+    },
+    MocIdxType::U32(moc) => match moc {
+      MocQtyType::Hpx(moc) => match moc {
+        RMocType::Ranges(moc) => output.write_smoc_possibly_converting_to_u64(moc),
+        RMocType::Cells(moc) => {
+          output.write_smoc_possibly_converting_to_u64(moc.into_cell_moc_iter().ranges())
+        }
+      },
+      MocQtyType::Time(moc) => match moc {
+        RMocType::Ranges(moc) => output.write_tmoc_possibly_converting_to_u64(moc),
+        RMocType::Cells(moc) => {
+          output.write_tmoc_possibly_converting_to_u64(moc.into_cell_moc_iter().ranges())
+        }
+      },
+      MocQtyType::Freq(moc) => match moc {
+        RMocType::Ranges(moc) => output.write_fmoc_possibly_converting_to_u64(moc),
+        RMocType::Cells(moc) => {
+          output.write_fmoc_possibly_converting_to_u64(moc.into_cell_moc_iter().ranges())
+        }
+      },
+      MocQtyType::TimeHpx(moc) => match moc {
+        STMocType::V2(moc) => output.write_stmoc(moc),
+        STMocType::PreV2(moc) => output.write_stmoc(moc),
+      },
+      MocQtyType::FreqHpx(moc) => output.write_sfmoc(moc),
+      MocQtyType::TimeFreq(moc) => output.write_tfmoc(moc),
+    },
+    MocIdxType::U64(moc) => match moc {
+      MocQtyType::Hpx(moc) => match moc {
+        RMocType::Ranges(moc) => output.write_smoc_possibly_converting_to_u64(moc),
+        RMocType::Cells(moc) => {
+          output.write_smoc_possibly_converting_to_u64(moc.into_cell_moc_iter().ranges())
+        }
+      },
+      MocQtyType::Time(moc) => match moc {
+        RMocType::Ranges(moc) => output.write_tmoc_possibly_converting_to_u64(moc),
+        RMocType::Cells(moc) => {
+          output.write_tmoc_possibly_converting_to_u64(moc.into_cell_moc_iter().ranges())
+        }
+      },
+      MocQtyType::Freq(moc) => match moc {
+        RMocType::Ranges(moc) => output.write_fmoc_possibly_converting_to_u64(moc),
+        RMocType::Cells(moc) => {
+          output.write_fmoc_possibly_converting_to_u64(moc.into_cell_moc_iter().ranges())
+        }
+      },
+      MocQtyType::TimeHpx(moc) => match moc {
+        STMocType::V2(moc) => output.write_stmoc(moc),
+        STMocType::PreV2(moc) => output.write_stmoc(moc),
+      },
+      MocQtyType::FreqHpx(moc) => output.write_sfmoc(moc),
+      MocQtyType::TimeFreq(moc) => output.write_tfmoc(moc),
+    },
+  }
+}
+
 /*
 IN THIS PREVIOUS CODE, WE LOADED DATA (FROM JSON OR ASCII) WITH A GIVEN DATATYPE u16, u32 or u64.
 NOW, WE ALWAYS LOAD IN u64 AND CONVERT IF NECESSARY
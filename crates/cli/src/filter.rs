@@ -1,6 +1,6 @@
 use std::error::Error;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{stdout, BufRead, BufReader, BufWriter};
 use std::marker::Send;
 use std::path::PathBuf;
 
@@ -8,18 +8,27 @@ use num::PrimInt;
 use rayon::prelude::*;
 use structopt::StructOpt;
 
-use moclib::deser::fits::{MocIdxType, MocQtyType};
+use moclib::deser::{
+  fits::{bintable::BinTableReader, MocIdxType, MocQtyType},
+  gz::{is_gz, uncompress},
+  votable::{is_votable, VOTableReader},
+};
 use moclib::idx::Idx;
 use moclib::moc::range::RangeMOC;
 use moclib::qty::{Hpx, MocQty, Time};
 
-use super::input::from_fits_file;
+use super::input::{
+  bintable_column_reader, bintable_time, from_fits_file, is_fits, votable_column, votable_f64,
+  votable_time,
+};
 use super::InputTime;
 
 #[derive(StructOpt, Debug)]
 pub struct CsvArgs {
   #[structopt(parse(from_os_str))]
-  /// Path of the input CSV file to be filtered (or stdin if equals "-" or empty)
+  /// Path of the input CSV file to be filtered (or stdin if equals "-" or empty).
+  /// A (possibly gzipped) FITS BINTABLE input is detected automatically and filtered into a FITS
+  /// BINTABLE written on stdout; idem for a VOTable input, filtered into a TABLEDATA VOTable.
   input_csv: Option<PathBuf>,
   #[structopt(short = "h", long)]
   /// The input file contains a header line (the first non-commented line)
@@ -37,10 +46,14 @@ impl CsvArgs {
     let path = self.input_csv.clone().unwrap_or_else(|| PathBuf::from("-"));
     if path == PathBuf::from("-") {
       let stdin = std::io::stdin();
-      pos_filter.filter_from(BufReader::new(stdin), moc)
+      pos_filter.filter_from_any(BufReader::new(stdin), moc)
     } else {
-      let f = File::open(path)?;
-      pos_filter.filter_from(BufReader::new(f), moc)
+      let mut reader = BufReader::new(File::open(path)?);
+      if is_gz(&mut reader)? {
+        pos_filter.filter_from_any(uncompress(reader), moc)
+      } else {
+        pos_filter.filter_from_any(reader, moc)
+      }
     }
   }
 
@@ -52,10 +65,14 @@ impl CsvArgs {
     let path = self.input_csv.clone().unwrap_or_else(|| PathBuf::from("-"));
     if path == PathBuf::from("-") {
       let stdin = std::io::stdin();
-      time_filter.filter_from(BufReader::new(stdin), moc)
+      time_filter.filter_from_any(BufReader::new(stdin), moc)
     } else {
-      let f = File::open(path)?;
-      time_filter.filter_from(BufReader::new(f), moc)
+      let mut reader = BufReader::new(File::open(path)?);
+      if is_gz(&mut reader)? {
+        time_filter.filter_from_any(uncompress(reader), moc)
+      } else {
+        time_filter.filter_from_any(reader, moc)
+      }
     }
   }
 }
@@ -94,7 +111,7 @@ pub struct PositionFilter {
   /// Column name (or index starting at 0) of the decimal degrees latitude field
   lat: String,
   #[structopt(long = "--n-threads")]
-  /// Use multithreading with the given number of threads
+  /// Use multithreading with the given number of threads (CSV inputs only)
   n_threads: Option<u16>,
   #[structopt(long = "--chunk-size", default_value = "200000")]
   /// Number of rows to be processed in parallel (only with multi-threading on)
@@ -121,6 +138,76 @@ impl PositionFilter {
   fn filter<T: Idx>(&self, moc: RangeMOC<T, Hpx<T>>) -> Result<(), Box<dyn Error>> {
     self.csv_args.posfilter_input_dispatch(self, moc)
   }
+  fn filter_from_any<T: Idx, R: BufRead + Send>(
+    &self,
+    mut reader: R,
+    moc: RangeMOC<T, Hpx<T>>,
+  ) -> Result<(), Box<dyn Error>> {
+    if is_fits(&mut reader)? {
+      self.filter_from_fits(reader, moc)
+    } else if is_votable(reader.fill_buf()?) {
+      self.filter_from_votable(reader, moc)
+    } else {
+      self.filter_from(reader, moc)
+    }
+  }
+  fn filter_from_fits<T: Idx, R: BufRead>(
+    &self,
+    reader: R,
+    moc: RangeMOC<T, Hpx<T>>,
+  ) -> Result<(), Box<dyn Error>> {
+    let mut table = BinTableReader::open(reader)?;
+    let lon = bintable_column_reader(&table, &self.lon)?;
+    let lat = bintable_column_reader(&table, &self.lat)?;
+    let layer = healpix::nested::get(moc.depth_max());
+    // WARNING: THIS WILL NOT WORK IF MOC CONTAINS DEPTH > 29!!
+    let shift = Hpx::<u64>::shift_from_depth_max(moc.depth_max()) as u32;
+    let mut rows: Vec<u8> = Vec::new();
+    for row in &mut table {
+      let row = row?;
+      if let (Some(lon), Some(lat)) = (lon.read(&row, 0), lat.read(&row, 0)) {
+        let icell = T::from_u64_idx(
+          layer
+            .hash(lon.to_radians(), lat.to_radians())
+            .unsigned_shl(shift),
+        );
+        if moc.contains_val(&icell) {
+          rows.extend_from_slice(&row);
+        }
+      }
+    }
+    table
+      .write_rows(&rows, BufWriter::new(stdout().lock()))
+      .map_err(|e| e.into())
+  }
+  fn filter_from_votable<T: Idx, R: BufRead>(
+    &self,
+    reader: R,
+    moc: RangeMOC<T, Hpx<T>>,
+  ) -> Result<(), Box<dyn Error>> {
+    let rows = VOTableReader::new(reader)?;
+    let votable = rows.votable().clone();
+    let ilon = votable_column(&votable, &self.lon)?;
+    let ilat = votable_column(&votable, &self.lat)?;
+    let layer = healpix::nested::get(moc.depth_max());
+    // WARNING: THIS WILL NOT WORK IF MOC CONTAINS DEPTH > 29!!
+    let shift = Hpx::<u64>::shift_from_depth_max(moc.depth_max()) as u32;
+    // Errors are kept to be returned by the writer
+    let rows = rows.filter(|row| match row {
+      Ok(row) => match (votable_f64(row, ilon), votable_f64(row, ilat)) {
+        (Some(lon), Some(lat)) => moc.contains_val(&T::from_u64_idx(
+          layer
+            .hash(lon.to_radians(), lat.to_radians())
+            .unsigned_shl(shift),
+        )),
+        _ => false,
+      },
+      Err(_) => true,
+    });
+    votable
+      .write(rows, BufWriter::new(stdout().lock()))
+      .map_err(|e| e.into())
+  }
   fn filter_from<T: Idx, R: BufRead + Send>(
     &self,
     reader: R,
@@ -324,6 +411,60 @@ impl TimeFilter {
   fn filter<T: Idx>(&self, moc: RangeMOC<T, Time<T>>) -> Result<(), Box<dyn Error>> {
     self.csv_args.timefilter_input_dispatch(self, moc)
   }
+  fn filter_from_any<T: Idx, R: BufRead + Send>(
+    &self,
+    mut reader: R,
+    moc: RangeMOC<T, Time<T>>,
+  ) -> Result<(), Box<dyn Error>> {
+    if is_fits(&mut reader)? {
+      self.filter_from_fits(reader, moc)
+    } else if is_votable(reader.fill_buf()?) {
+      self.filter_from_votable(reader, moc)
+    } else {
+      self.filter_from(reader, moc)
+    }
+  }
+  fn filter_from_fits<T: Idx, R: BufRead>(
+    &self,
+    reader: R,
+    moc: RangeMOC<T, Time<T>>,
+  ) -> Result<(), Box<dyn Error>> {
+    let mut table = BinTableReader::open(reader)?;
+    let time = bintable_column_reader(&table, &self.time)?;
+    let mut rows: Vec<u8> = Vec::new();
+    for row in &mut table {
+      let row = row?;
+      if let Some(usec) = bintable_time(&self.time_type, &time, &row) {
+        // Times in microseconds are indices at the maximum depth
+        if moc.contains_val(&T::from_u64_idx(usec)) {
+          rows.extend_from_slice(&row);
+        }
+      }
+    }
+    table
+      .write_rows(&rows, BufWriter::new(stdout().lock()))
+      .map_err(|e| e.into())
+  }
+  fn filter_from_votable<T: Idx, R: BufRead>(
+    &self,
+    reader: R,
+    moc: RangeMOC<T, Time<T>>,
+  ) -> Result<(), Box<dyn Error>> {
+    let rows = VOTableReader::new(reader)?;
+    let votable = rows.votable().clone();
+    let itime = votable_column(&votable, &self.time)?;
+    // Times in microseconds are indices at the maximum depth; errors are kept to be returned by
+    // the writer
+    let rows = rows.filter(|row| match row {
+      Ok(row) => votable_time(&self.time_type, row, itime)
+        .map(|usec| moc.contains_val(&T::from_u64_idx(usec)))
+        .unwrap_or(false),
+      Err(_) => true,
+    });
+    votable
+      .write(rows, BufWriter::new(stdout().lock()))
+      .map_err(|e| e.into())
+  }
   fn filter_from<T: Idx, R: BufRead + Send>(
     &self,
     reader: R,
@@ -360,15 +501,14 @@ impl TimeFilter {
       self.time.parse::<usize>()?
     };
     // We can start the job
-    // WARNING: THIS WILL NOT WORK IF MOC CONTAINS DEPTH > 29!!
-    let shift = Time::<u64>::shift_from_depth_max(moc.depth_max()) as u32;
+    // Times in microseconds are indices at the maximum depth
     for line in it {
       let line = line?;
       let mut split_it = line.split(sep);
       let icell = split_it
         .nth(itime)
         .and_then(|s| self.time_type.parse(s).ok())
-        .map(|tcell| T::from_u64_idx(tcell).unsigned_shl(shift));
+        .map(T::from_u64_idx);
       if let Some(icell) = icell {
         if moc.contains_val(&icell) {
           println!("{}", line);
@@ -9,7 +9,7 @@ use std::{
   str::{self, FromStr},
 };
 
-use log::error;
+use log::{error, warn};
 use rayon::{
   iter::{IntoParallelIterator, ParallelBridge, ParallelIterator},
   prelude::ParallelString,
@@ -19,8 +19,16 @@ use structopt::StructOpt;
 use healpix::nested::Layer;
 use moclib::{
   deser::{
-    fits::{multiordermap::from_fits_multiordermap, skymap::from_fits_skymap},
-    stcs::stcs2moc,
+    ds9::{from_crtf, from_ds9},
+    fits::{
+      bintable::{BinTableReader, ValueReader},
+      error::FitsError,
+      multiordermap::{credible_regions_from_fits_multiordermap, from_fits_multiordermap},
+      skymap::from_fits_skymap,
+    },
+    geojson::from_geojson,
+    gz::{is_gz, uncompress},
+    stcs::{stcs2mocs, StcMoc},
   },
   elem::valuedcell::valued_cells_to_moc_with_opt,
   elemset::range::HpxRanges,
@@ -32,7 +40,13 @@ use moclib::{
   qty::{Frequency, Hpx, MocQty, Time},
 };
 
-use super::{output::OutputFormat, InputTime};
+use super::{
+  input::{
+    bintable_column_reader, bintable_time, votable_columns, votable_from_input, votable_lines,
+  },
+  output::OutputFormat,
+  InputTime,
+};
 
 const HALF_PI: f64 = 0.5 * std::f64::consts::PI;
 const PI: f64 = std::f64::consts::PI;
@@ -70,6 +84,28 @@ pub enum MultiOrderInputFormat {
   #[structopt(name = "multires")]
   /// Possibly gzipped Fits file supporting (a so far limited sub-set of) the multi-resolution format
   FitsMultiRes {
+    #[structopt(short = "c", long = "column")]
+    /// Name of the density column [default: PROBDENSITY]
+    column: Option<String>,
+    #[structopt(
+      short = "l",
+      long = "levels",
+      use_delimiter = true,
+      require_delimiter = true
+    )]
+    /// Comma separated list of credible levels in ]0, 1] (e.g. '0.5,0.9'), replacing the
+    /// 'from'/'to' thresholds: one MOC per level is written, the level percentage being added
+    /// before the output file extension, and the area and value of each MOC are printed on stderr
+    levels: Vec<f64>,
+    #[structopt(
+      long = "position",
+      use_delimiter = true,
+      require_delimiter = true,
+      allow_hyphen_values = true
+    )]
+    /// With 'levels' only: comma separated 'lon,lat' position, in degrees, at which the
+    /// searched area and value are printed on stderr
+    position: Vec<f64>,
     #[structopt(parse(from_os_str))]
     /// The input multi-order-map FITS file
     input: PathBuf,
@@ -82,6 +118,9 @@ pub enum MultiOrderInputFormat {
     #[structopt(short = "s", long = "skip", default_value = "0.0")]
     /// Skip cells having values lower or equals to the provided value
     skip_vals_le_than: f64,
+    #[structopt(short = "c", long = "column")]
+    /// Name of the value column [default: first column, or first non-PIXEL column in partial skymaps]
+    column: Option<String>,
     #[structopt(parse(from_os_str))]
     /// The input multi-order-map FITS file
     input: PathBuf,
@@ -133,11 +172,16 @@ pub enum From {
     /// Use the lots of small cones (few cells each) algo instead of few large cones
     small: bool,
     #[structopt(parse(from_os_str))]
-    /// The input file containing one cone per line, use '-' for stdin
+    /// The input file containing one cone per line (or a VOTable), use '-' for stdin
     input: PathBuf,
     #[structopt(short = "s", long = "separator", default_value = " ")]
     /// File separator (default = ' ')
     separator: String,
+    #[structopt(short = "c", long = "columns")]
+    /// VOTable input only: comma separated names (or indices starting at 0) of the
+    /// longitude, latitude and radius columns
+    /// [default: columns having the UCDs 'pos.eq.ra', 'pos.eq.dec' and 'phys.angSize', else the first columns]
+    columns: Option<String>,
     #[structopt(subcommand)]
     out: OutputFormat,
   },
@@ -250,27 +294,75 @@ pub enum From {
     out: OutputFormat,
   },
   #[structopt(name = "stcs")]
-  /// Create a Spatial MOC from a STC-S input.
+  /// Create a MOC from a STC-S input: a S-MOC, T-MOC, F-MOC, ST-MOC, SF-MOC or TF-MOC depending
+  /// on the space, time and spectral sub-phrases of the STC-S string.
   StcS {
     /// Depth of the created MOC, in `[0, 29]`.
     depth: u8,
+    #[structopt(long = "tdepth", default_value = "35")]
+    /// Depth on the time, in `[0, 61]`, if the STC-S contains a time sub-phrase.
+    tdepth: u8,
+    #[structopt(long = "fdepth", default_value = "30")]
+    /// Depth on the frequency, in `[0, 59]`, if the STC-S contains a spectral sub-phrase.
+    fdepth: u8,
     #[structopt(parse(from_os_str))]
     /// The input file containing the STC-S string, use '-' to read from stdin
     input: PathBuf,
     #[structopt(subcommand)]
     out: OutputFormat,
   },
+  #[structopt(name = "geojson")]
+  /// Create a Spatial MOC from a GeoJSON Polygon, MultiPolygon, Feature or FeatureCollection.
+  GeoJson {
+    /// Depth of the created MOC, in `[0, 29]`.
+    depth: u8,
+    #[structopt(parse(from_os_str))]
+    /// The input GeoJSON file, use '-' to read from stdin
+    input: PathBuf,
+    #[structopt(subcommand)]
+    out: OutputFormat,
+  },
+  #[structopt(name = "ds9")]
+  /// Create a Spatial MOC from a DS9 region file (circle, ellipse, box, polygon and annulus
+  /// shapes, in fk5/icrs), exclude shapes being removed from the union of the include shapes.
+  Ds9 {
+    /// Depth of the created MOC, in `[0, 29]`.
+    depth: u8,
+    #[structopt(parse(from_os_str))]
+    /// The input region file, use '-' to read from stdin
+    input: PathBuf,
+    #[structopt(subcommand)]
+    out: OutputFormat,
+  },
+  #[structopt(name = "crtf")]
+  /// Create a Spatial MOC from a CASA region file (CRTF: circle, ellipse, box, centerbox, rotbox,
+  /// poly and annulus shapes, in J2000/ICRS), exclude shapes being removed from the union of the
+  /// include shapes.
+  Crtf {
+    /// Depth of the created MOC, in `[0, 29]`.
+    depth: u8,
+    #[structopt(parse(from_os_str))]
+    /// The input region file, use '-' to read from stdin
+    input: PathBuf,
+    #[structopt(subcommand)]
+    out: OutputFormat,
+  },
   #[structopt(name = "pos")]
   /// Create a Spatial MOC from a list of positions in decimal degrees (one pair per line, longitude first, then latitude).
   Positions {
     /// Depth of the created MOC, in `[0, 29]`.
     depth: u8,
     #[structopt(parse(from_os_str))]
-    /// The input file, use '-' for stdin
+    /// The input file (or VOTable), use '-' for stdin
     input: PathBuf,
     #[structopt(short = "s", long = "separator", default_value = " ")]
     /// Separator between both coordinates (default = ' ')
     separator: String,
+    #[structopt(short = "c", long = "columns")]
+    /// VOTable input only: comma separated names (or indices starting at 0) of the
+    /// longitude and latitude columns
+    /// [default: columns having the UCDs 'pos.eq.ra' and 'pos.eq.dec', else the first columns]
+    columns: Option<String>,
     #[structopt(subcommand)]
     out: OutputFormat,
   },
@@ -348,11 +440,16 @@ pub enum From {
     /// or 'isosimple' (Gregorian date, 'YYYY-MM-DDTHH:MM:SS' WARNING: no conversion to TCB)
     time: InputTime,
     #[structopt(parse(from_os_str))]
-    /// The input file, use '-' for stdin
+    /// The input file (or VOTable), use '-' for stdin
     input: PathBuf,
     #[structopt(short = "s", long = "separator", default_value = " ")]
     /// Separator between time lower and upper bounds (default = ' ')
     separator: String,
+    #[structopt(short = "c", long = "columns")]
+    /// VOTable input only: comma separated names (or indices starting at 0) of the
+    /// time, longitude and latitude columns
+    /// [default: columns having the UCDs 'time.epoch', 'pos.eq.ra' and 'pos.eq.dec', else the first columns]
+    columns: Option<String>,
     #[structopt(subcommand)]
     out: OutputFormat,
   },
@@ -438,6 +535,42 @@ pub enum From {
     #[structopt(subcommand)]
     out: OutputFormat,
   }, // TODO: from file containing a list of FreqRange,S-MOC path
+  #[structopt(name = "bintable")]
+  /// Create a Space, Time, Frequency, Space-Time or Space-Frequency MOC from the rows of a
+  /// (possibly gzipped) FITS BINTABLE, e.g. a catalogue, depending on the provided columns.
+  BinTable {
+    #[structopt(parse(from_os_str))]
+    /// The input FITS file
+    input: PathBuf,
+    #[structopt(short = "l", long = "lon")]
+    /// Column name (or index starting at 0) of the decimal degrees longitude field
+    lon: Option<String>,
+    #[structopt(short = "b", long = "lat")]
+    /// Column name (or index starting at 0) of the decimal degrees latitude field
+    lat: Option<String>,
+    #[structopt(long = "sdepth")]
+    /// Depth on the position, in `[0, 29]` (required with '--lon' and '--lat').
+    sdepth: Option<u8>,
+    #[structopt(short = "t", long = "time")]
+    /// Column name (or index starting at 0) of the time field
+    time: Option<String>,
+    #[structopt(long = "time-type", default_value = "jd")]
+    /// Time type: 'jd' (julian date), 'mjd' (modified julian date), 'usec' (microsec since JD=0),
+    /// 'isorfc' (Gregorian date-time, Rfc3339, WARNING: no conversion to TCB),
+    /// or 'isosimple' (Gregorian date, 'YYYY-MM-DDTHH:MM:SS' WARNING: no conversion to TCB)
+    time_type: InputTime,
+    #[structopt(long = "tdepth")]
+    /// Depth on the time, in `[0, 61]` (required with '--time').
+    tdepth: Option<u8>,
+    #[structopt(short = "f", long = "freq")]
+    /// Column name (or index starting at 0) of the frequency field, in Hz
+    freq: Option<String>,
+    #[structopt(long = "fdepth")]
+    /// Depth on the frequency, in `[0, 59]` (required with '--freq').
+    fdepth: Option<u8>,
+    #[structopt(subcommand)]
+    out: OutputFormat,
+  },
 }
 
 impl From {
@@ -459,6 +592,7 @@ impl From {
         small,
         input,
         separator,
+        columns,
         out,
       } => {
         fn line2cone_params(
@@ -482,6 +616,7 @@ impl From {
           let radius = radius.to_radians();
           Ok((lon, lat, radius))
         }
+        let votable_sep = separator.clone();
         let line2cone =
           move |line: std::io::Result<String>| match line2cone_params(&separator, line) {
             Ok(lonlatrad) => Some(lonlatrad),
@@ -490,7 +625,19 @@ impl From {
               None
             }
           };
-        let moc: RangeMOC<u64, Hpx<u64>> = if input == PathBuf::from(r"-") {
+        let moc: RangeMOC<u64, Hpx<u64>> = if let Some(vot) = votable_from_input(&input)? {
+          let icols = votable_columns(
+            vot.votable(),
+            columns.as_deref(),
+            &["pos.eq.ra", "pos.eq.dec", "phys.angSize"],
+          )?;
+          let cones = votable_lines(vot, &icols, &votable_sep).filter_map(line2cone);
+          if small {
+            RangeMOC::from_small_cones(depth, 2, cones, None)
+          } else {
+            RangeMOC::from_large_cones(depth, 2, CellSelection::All, cones)
+          }
+        } else if input.as_os_str() == "-" {
           let stdin = std::io::stdin();
           if small {
             RangeMOC::from_small_cones(depth, 2, stdin.lock().lines().filter_map(line2cone), None)
@@ -869,19 +1016,65 @@ impl From {
         };
         out.write_smoc_possibly_auto_converting_from_u64(moc.into_range_moc_iter())
       }
-      From::StcS { depth, input, out } => {
+      From::StcS {
+        depth,
+        tdepth,
+        fdepth,
+        input,
+        out,
+      } => {
         let ascii_stc = if input == PathBuf::from(r"-") {
           std::io::read_to_string(std::io::stdin())
         } else {
           std::fs::read_to_string(input)
         }?;
-        let moc = stcs2moc(depth, None, ascii_stc.as_ref())?;
+        match stcs2mocs(depth, None, tdepth, fdepth, ascii_stc.as_ref())? {
+          StcMoc::Space(moc) => {
+            out.write_smoc_possibly_auto_converting_from_u64(moc.into_range_moc_iter())
+          }
+          StcMoc::Time(moc) => {
+            out.write_tmoc_possibly_auto_converting_from_u64(moc.into_range_moc_iter())
+          }
+          StcMoc::Frequency(moc) => {
+            out.write_fmoc_possibly_auto_converting_from_u64(moc.into_range_moc_iter())
+          }
+          StcMoc::TimeSpace(moc) => out.write_stmoc(moc.into_range_moc2_iter()),
+          StcMoc::FrequencySpace(moc) => out.write_sfmoc(moc.into_range_moc2_iter()),
+          StcMoc::TimeFrequency(moc) => out.write_tfmoc(moc.into_range_moc2_iter()),
+        }
+      }
+      From::GeoJson { depth, input, out } => {
+        let geojson = if input.as_os_str() == "-" {
+          std::io::read_to_string(std::io::stdin())
+        } else {
+          std::fs::read_to_string(input)
+        }?;
+        let moc = from_geojson(&geojson, depth, CellSelection::All)?;
+        out.write_smoc_possibly_auto_converting_from_u64(moc.into_range_moc_iter())
+      }
+      From::Ds9 { depth, input, out } => {
+        let regions = if input.as_os_str() == "-" {
+          std::io::read_to_string(std::io::stdin())
+        } else {
+          std::fs::read_to_string(input)
+        }?;
+        let moc = from_ds9(&regions, depth, 2, CellSelection::All)?;
+        out.write_smoc_possibly_auto_converting_from_u64(moc.into_range_moc_iter())
+      }
+      From::Crtf { depth, input, out } => {
+        let regions = if input.as_os_str() == "-" {
+          std::io::read_to_string(std::io::stdin())
+        } else {
+          std::fs::read_to_string(input)
+        }?;
+        let moc = from_crtf(&regions, depth, 2, CellSelection::All)?;
         out.write_smoc_possibly_auto_converting_from_u64(moc.into_range_moc_iter())
       }
       From::Positions {
         depth,
         input,
         separator,
+        columns,
         out,
       } => {
         fn line2coos(
@@ -899,6 +1092,7 @@ impl From {
           let lat = lat_deg2rad(lat_deg)?;
           Ok((lon, lat))
         }
+        let votable_sep = separator.clone();
         let line2pos = move |line: std::io::Result<String>| match line2coos(&separator, line) {
           Ok(lonlat) => Some(lonlat),
           Err(e) => {
@@ -906,7 +1100,15 @@ impl From {
             None
           }
         };
-        let moc: RangeMOC<u64, Hpx<u64>> = if input == PathBuf::from(r"-") {
+        let moc: RangeMOC<u64, Hpx<u64>> = if let Some(vot) = votable_from_input(&input)? {
+          let icols = votable_columns(
+            vot.votable(),
+            columns.as_deref(),
+            &["pos.eq.ra", "pos.eq.dec"],
+          )?;
+          let coos = votable_lines(vot, &icols, &votable_sep).filter_map(line2pos);
+          RangeMOC::from_coos(depth, coos, None)
+        } else if input.as_os_str() == "-" {
           let stdin = std::io::stdin();
           RangeMOC::from_coos(depth, stdin.lock().lines().filter_map(line2pos), None)
         } else {
@@ -1051,6 +1253,71 @@ impl From {
         let moc = RangeMOC::new(depth, ranges);
         out.write_smoc_possibly_auto_converting_from_u64(moc.into_range_moc_iter())
       }
+      From::ValuedCells {
+        not_strict,
+        split,
+        revese_recursive_descent,
+        input_type:
+          MultiOrderInputFormat::FitsMultiRes {
+            column,
+            levels,
+            position,
+            input,
+            out,
+          },
+        ..
+      } if !levels.is_empty() => {
+        let position = match position.as_slice() {
+          [] => None,
+          [lon, lat] => Some((lon.to_radians(), lat.to_radians())),
+          _ => return Err(String::from("Position must be of the form 'lon,lat'.").into()),
+        };
+        // The level percentage is added to the output file names: it must be unique
+        let mut numbers: Vec<(usize, f64)> = Vec::with_capacity(levels.len());
+        for &level in &levels {
+          if !(level > 0.0 && level <= 1.0) {
+            return Err(format!("Credible level {} not in ]0, 1].", level).into());
+          }
+          let num = (level * 100.0).round() as usize;
+          if let Some((_, other)) = numbers.iter().find(|(n, _)| *n == num) {
+            return Err(
+              format!(
+                "Credible levels {} and {} lead to the same output file number {}.",
+                other, level, num
+              )
+              .into(),
+            );
+          }
+          numbers.push((num, level));
+        }
+        let f = File::open(input)?;
+        let reader = BufReader::new(f);
+        let (regions, searched) = credible_regions_from_fits_multiordermap(
+          reader,
+          column.as_deref(),
+          &levels,
+          !not_strict,
+          split,
+          revese_recursive_descent,
+          position,
+        )?;
+        // Regions are returned in the order of the input levels
+        for ((num, _), region) in numbers.into_iter().zip(regions) {
+          eprintln!(
+            "level: {}; area: {} deg2; value: {}",
+            region.level, region.area_deg2, region.value
+          );
+          let nout = out.clone_with_number(num);
+          nout.write_smoc_possibly_auto_converting_from_u64(region.moc.into_range_moc_iter())?;
+        }
+        if let Some(searched) = searched {
+          eprintln!(
+            "searched area: {} deg2; searched value: {}",
+            searched.area_deg2, searched.value
+          );
+        }
+        Ok(())
+      }
       From::ValuedCells {
         from_threshold,
         to_threshold,
@@ -1058,7 +1325,9 @@ impl From {
         not_strict,
         split,
         revese_recursive_descent,
-        input_type: MultiOrderInputFormat::FitsMultiRes { input, out },
+        input_type: MultiOrderInputFormat::FitsMultiRes {
+          column, input, out, ..
+        },
       } => {
         let from_threshold = from_threshold.parse::<f64>()?;
         let to_threshold = to_threshold.parse::<f64>()?;
@@ -1066,6 +1335,7 @@ impl From {
         let reader = BufReader::new(f);
         let moc = from_fits_multiordermap(
           reader,
+          column.as_deref(),
           from_threshold,
           to_threshold,
           asc,
@@ -1085,6 +1355,7 @@ impl From {
         input_type:
           MultiOrderInputFormat::FitsSkymap {
             skip_vals_le_than,
+            column,
             input,
             out,
           },
@@ -1095,6 +1366,7 @@ impl From {
         let reader = BufReader::new(f);
         let moc = from_fits_skymap(
           reader,
+          column.as_deref(),
           skip_vals_le_than,
           from_threshold,
           to_threshold,
@@ -1202,6 +1474,7 @@ impl From {
         time,
         input,
         separator,
+        columns,
         out,
       } => {
         let time_shift = Time::<u64>::shift_from_depth_max(tdepth) as u32;
@@ -1230,6 +1503,7 @@ impl From {
           let hpx = layer.hash(lon, lat);
           Ok((time_idx, hpx))
         }
+        let votable_sep = separator.clone();
         let line2tpos = move |line: std::io::Result<String>| match line2tscoos(
           &separator, &time, layer, time_shift, line,
         ) {
@@ -1239,24 +1513,33 @@ impl From {
             None
           }
         };
-        let moc2: RangeMOC2<u64, Time<u64>, u64, Hpx<u64>> = if input == PathBuf::from(r"-") {
-          let stdin = std::io::stdin();
-          RangeMOC2::from_fixed_depth_cells(
-            tdepth,
-            sdepth,
-            stdin.lock().lines().filter_map(line2tpos),
-            None,
-          )
-        } else {
-          let f = File::open(input)?;
-          let reader = BufReader::new(f);
-          RangeMOC2::from_fixed_depth_cells(
-            tdepth,
-            sdepth,
-            reader.lines().filter_map(line2tpos),
-            None,
-          )
-        };
+        let moc2: RangeMOC2<u64, Time<u64>, u64, Hpx<u64>> =
+          if let Some(vot) = votable_from_input(&input)? {
+            let icols = votable_columns(
+              vot.votable(),
+              columns.as_deref(),
+              &["time.epoch", "pos.eq.ra", "pos.eq.dec"],
+            )?;
+            let cells = votable_lines(vot, &icols, &votable_sep).filter_map(line2tpos);
+            RangeMOC2::from_fixed_depth_cells(tdepth, sdepth, cells, None)
+          } else if input.as_os_str() == "-" {
+            let stdin = std::io::stdin();
+            RangeMOC2::from_fixed_depth_cells(
+              tdepth,
+              sdepth,
+              stdin.lock().lines().filter_map(line2tpos),
+              None,
+            )
+          } else {
+            let f = File::open(input)?;
+            let reader = BufReader::new(f);
+            RangeMOC2::from_fixed_depth_cells(
+              tdepth,
+              sdepth,
+              reader.lines().filter_map(line2tpos),
+              None,
+            )
+          };
         out.write_stmoc(moc2.into_range_moc2_iter())
       }
       From::TimerangePos {
@@ -1523,6 +1806,35 @@ impl From {
           )
         };
         out.write_sfmoc(moc2.into_range_moc2_iter())
+      }
+      From::BinTable {
+        input,
+        lon,
+        lat,
+        sdepth,
+        time,
+        time_type,
+        tdepth,
+        freq,
+        fdepth,
+        out,
+      } => {
+        let columns = BinTableColumns {
+          lon,
+          lat,
+          sdepth,
+          time,
+          time_type,
+          tdepth,
+          freq,
+          fdepth,
+        };
+        let mut reader = BufReader::new(File::open(input)?);
+        if is_gz(&mut reader)? {
+          columns.bintable2moc(uncompress(reader), out)
+        } else {
+          columns.bintable2moc(reader, out)
+        }
       } // ST-MOC from t-moc + s-moc (we can then create a complex ST-MOC by union of elementary ST-MOCs)
         // - e.g. multiple observation of the same area of the sky
         // - XMM ST-MOC (from list of observations)?
@@ -1530,6 +1842,158 @@ impl From {
   }
 }
 
+/// Columns (and associated depths) of a FITS BINTABLE used to build a MOC.
+struct BinTableColumns {
+  lon: Option<String>,
+  lat: Option<String>,
+  sdepth: Option<u8>,
+  time: Option<String>,
+  time_type: InputTime,
+  tdepth: Option<u8>,
+  freq: Option<String>,
+  fdepth: Option<u8>,
+}
+
+impl BinTableColumns {
+  fn bintable2moc<R: BufRead>(self, reader: R, out: OutputFormat) -> Result<(), Box<dyn Error>> {
+    fn depth(depth: Option<u8>, param: &str) -> Result<u8, Box<dyn Error>> {
+      depth.ok_or_else(|| format!("Option '--{}' is required.", param).into())
+    }
+    let table = BinTableReader::open(reader)?;
+    let lonlat = match (&self.lon, &self.lat) {
+      (Some(lon), Some(lat)) => Some((
+        bintable_column_reader(&table, lon)?,
+        bintable_column_reader(&table, lat)?,
+        depth(self.sdepth, "sdepth")?,
+      )),
+      (None, None) => None,
+      _ => return Err(String::from("Both '--lon' and '--lat' must be provided.").into()),
+    };
+    let time = match &self.time {
+      Some(time) => Some((
+        bintable_column_reader(&table, time)?,
+        depth(self.tdepth, "tdepth")?,
+      )),
+      None => None,
+    };
+    let freq = match &self.freq {
+      Some(freq) => Some((
+        bintable_column_reader(&table, freq)?,
+        depth(self.fdepth, "fdepth")?,
+      )),
+      None => None,
+    };
+    // Rows reading error, if any, and number of rows skipped because of blank or invalid values
+    let mut io_err = None;
+    let mut n_skipped = 0_u64;
+    let rows = table.map_while(|row| match row {
+      Ok(row) => Some(row),
+      Err(e) => {
+        io_err = Some(e);
+        None
+      }
+    });
+    let coos = |lon: &ValueReader, lat: &ValueReader, row: &[u8]| match (
+      lon.read(row, 0).map(lon_deg2rad),
+      lat.read(row, 0).map(lat_deg2rad),
+    ) {
+      (Some(Ok(lon)), Some(Ok(lat))) => Some((lon, lat)),
+      _ => None,
+    };
+    match (lonlat, time, freq) {
+      (Some((lon, lat, sdepth)), None, None) => {
+        let moc: RangeMOC<u64, Hpx<u64>> = RangeMOC::from_coos(
+          sdepth,
+          rows.filter_map(|row| coos(&lon, &lat, &row).or_else(|| skip(&mut n_skipped))),
+          None,
+        );
+        check_rows(io_err, n_skipped)?;
+        out.write_smoc_possibly_auto_converting_from_u64(moc.into_range_moc_iter())
+      }
+      (None, Some((time, tdepth)), None) => {
+        let moc = RangeMOC::<u64, Time<u64>>::from_microsec_since_jd0(
+          tdepth,
+          rows.filter_map(|row| {
+            bintable_time(&self.time_type, &time, &row).or_else(|| skip(&mut n_skipped))
+          }),
+          None,
+        );
+        check_rows(io_err, n_skipped)?;
+        out.write_tmoc_possibly_auto_converting_from_u64(moc.into_range_moc_iter())
+      }
+      (None, None, Some((freq, fdepth))) => {
+        let moc = RangeMOC::<u64, Frequency<u64>>::from_freq_in_hz(
+          fdepth,
+          rows.filter_map(|row| freq.read(&row, 0).or_else(|| skip(&mut n_skipped))),
+          None,
+        );
+        check_rows(io_err, n_skipped)?;
+        out.write_fmoc_possibly_auto_converting_from_u64(moc.into_range_moc_iter())
+      }
+      (Some((lon, lat, sdepth)), Some((time, tdepth)), None) => {
+        let time_shift = Time::<u64>::shift_from_depth_max(tdepth) as u32;
+        let layer = healpix::nested::get(sdepth);
+        let moc2: RangeMOC2<u64, Time<u64>, u64, Hpx<u64>> = RangeMOC2::from_fixed_depth_cells(
+          tdepth,
+          sdepth,
+          rows.filter_map(|row| {
+            match (
+              bintable_time(&self.time_type, &time, &row),
+              coos(&lon, &lat, &row),
+            ) {
+              (Some(time_us), Some((lon, lat))) => {
+                Some((time_us >> time_shift, layer.hash(lon, lat)))
+              }
+              _ => skip(&mut n_skipped),
+            }
+          }),
+          None,
+        );
+        check_rows(io_err, n_skipped)?;
+        out.write_stmoc(moc2.into_range_moc2_iter())
+      }
+      (Some((lon, lat, sdepth)), None, Some((freq, fdepth))) => {
+        let moc2: RangeMOC2<u64, Frequency<u64>, u64, Hpx<u64>> =
+          RangeMOC2::from_freq_in_hz_and_coos(
+            fdepth,
+            sdepth,
+            rows.filter_map(|row| match (freq.read(&row, 0), coos(&lon, &lat, &row)) {
+              (Some(freq_hz), Some(lonlat)) => Some((freq_hz, lonlat)),
+              _ => skip(&mut n_skipped),
+            }),
+            None,
+          );
+        check_rows(io_err, n_skipped)?;
+        out.write_sfmoc(moc2.into_range_moc2_iter())
+      }
+      _ => Err(
+        String::from(
+          "Expected columns: '--lon' and '--lat' (S-MOC), '--time' (T-MOC), '--freq' (F-MOC), \
+           '--lon', '--lat' and '--time' (ST-MOC) or '--lon', '--lat' and '--freq' (SF-MOC).",
+        )
+        .into(),
+      ),
+    }
+  }
+}
+
+/// Count a row skipped because of a blank or invalid value.
+fn skip<T>(n_skipped: &mut u64) -> Option<T> {
+  *n_skipped += 1;
+  None
+}
+
+/// Returns the error which occurred while reading the rows, if any, and warn about skipped rows.
+fn check_rows(io_err: Option<FitsError>, n_skipped: u64) -> Result<(), Box<dyn Error>> {
+  if n_skipped > 0 {
+    warn!(
+      "{} rows skipped because of blank or invalid values.",
+      n_skipped
+    );
+  }
+  io_err.map_or(Ok(()), |e| Err(e.into()))
+}
+
 fn lon_deg2rad(lon_deg: f64) -> Result<f64, Box<dyn Error>> {
   let mut lon = lon_deg.to_radians();
   if lon == TWICE_PI {
@@ -1895,7 +2359,9 @@ mod tests {
         force_v1: false,
         moc_id: None,
         moc_type: None,
+        checksum: false,
         file: PathBuf::from("test/resources/xmmlog.range.stmoc.fits"),
+        metadata: Default::default(),
       },
     };
     from.exec().unwrap();
@@ -1912,6 +2378,7 @@ mod tests {
       time: InputTime::JD,
       input: PathBuf::from("test/resources/xmmlog1.csv"),
       separator: String::from(","),
+      columns: None,
       out: OutputFormat::Ascii {
         fold: Some(80),
         range_len: false,
@@ -1941,6 +2408,7 @@ mod tests {
       time: InputTime::JD,
       input: PathBuf::from("test/resources/xmmlog1.csv"),
       separator: String::from(","),
+      columns: None,
       out: OutputFormat::Ascii {
         fold: Some(80),
         range_len: false,
@@ -1958,4 +2426,29 @@ mod tests {
     let expected = fs::read_to_string(expected).unwrap();
     assert_eq!(actual, expected);
   }
+
+  #[test]
+  fn st_moc_logxmm_val_votable() {
+    // xmmlog1.vot: same content as xmmlog1.csv, in BINARY2
+    let expected = "test/resources/xmmlog.stmoc.t24.s7.expected.txt";
+    let actual = "test/resources/xmmlog.stmoc.t24.s7.vot.actual.txt";
+    let from = From::TimestampPos {
+      tdepth: 24,
+      sdepth: 7,
+      time: InputTime::JD,
+      input: PathBuf::from("test/resources/xmmlog1.vot"),
+      separator: String::from(" "),
+      columns: Some(String::from("obs_jd,RA,DEC")),
+      out: OutputFormat::Ascii {
+        fold: Some(80),
+        range_len: false,
+        opt_file: Some(actual.into()),
+      },
+    };
+    from.exec().unwrap();
+    // Check results
+    let actual = fs::read_to_string(actual).unwrap();
+    let expected = fs::read_to_string(expected).unwrap();
+    assert_eq!(actual, expected);
+  }
 }
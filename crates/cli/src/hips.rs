@@ -0,0 +1,174 @@
+use std::{
+  error::Error,
+  fs::File,
+  io::{self, BufReader, BufWriter, Write},
+  path::PathBuf,
+};
+
+use structopt::StructOpt;
+
+use moclib::{
+  deser::fits::hpx_cells_to_fits_ivoa,
+  moc::{range::RangeMOC, RangeMOCIntoIterator, RangeMOCIterator},
+  qty::{Hpx, MocQty},
+};
+
+use crate::{input::InputFormat, view::read_smoc};
+
+/// Lists the HiPS tiles (`NorderK/DirD/NpixN` paths) covered by an S-MOC, from the HiPS min order
+/// to the HiPS max order, and (optionally) writes the HiPS `Moc.fits` file.
+#[derive(StructOpt, Debug)]
+pub struct Hips {
+  #[structopt(parse(from_os_str))]
+  /// Path of the input S-MOC file (or stdin if equals "-")
+  input: PathBuf,
+  #[structopt(short = "f", long = "format")]
+  /// Format of the input MOC ('ascii', 'json', 'fits', 'stream', 'packed' or 'votable') [default: guess from the file extension]
+  input_fmt: Option<InputFormat>,
+  #[structopt(short = "o", long = "order")]
+  /// HiPS max order, i.e. order of the deepest tiles [default: the MOC depth minus log2 of the tile width]
+  order: Option<u8>,
+  #[structopt(short = "w", long = "tile-width", default_value = "512")]
+  /// Width of the HiPS tiles, in pixels (must be a power of 2)
+  tile_width: u32,
+  #[structopt(long = "min-order", default_value = "0")]
+  /// HiPS min order, i.e. order of the shallowest listed tiles
+  min_order: u8,
+  #[structopt(short = "e", long = "ext")]
+  /// Extension added to each tile path (e.g. 'png', 'jpg' or 'fits')
+  ext: Option<String>,
+  #[structopt(short = "m", long = "moc", parse(from_os_str))]
+  /// Path of the HiPS MOC to be written (usually '<hips_dir>/Moc.fits'), in FITS NUNIQ
+  moc: Option<PathBuf>,
+  #[structopt(long = "moc-order")]
+  /// Depth of the written HiPS MOC [default: the HiPS max order]
+  moc_order: Option<u8>,
+  #[structopt(short = "n", long = "no-tiles")]
+  /// Do not print the list of tiles (e.g. to only write the HiPS MOC)
+  no_tiles: bool,
+}
+
+impl Hips {
+  pub fn exec(self) -> Result<(), Box<dyn Error>> {
+    let smoc = if self.input.as_os_str() == "-" {
+      match self.input_fmt {
+        Some(input_fmt) => read_smoc(io::stdin().lock(), input_fmt),
+        None => Err(
+          String::from(
+            "Using stdin, the MOC format ('ascii', 'json', ...) must be provided, see options.",
+          )
+          .into(),
+        ),
+      }
+    } else {
+      let input_fmt = match self.input_fmt {
+        Some(input_fmt) => Ok(input_fmt),
+        None => InputFormat::from_extension(&self.input),
+      }?;
+      read_smoc(BufReader::new(File::open(&self.input)?), input_fmt)
+    }?;
+    if !self.tile_width.is_power_of_two() {
+      return Err(
+        format!(
+          "Tile width must be a power of 2. Actual: {}.",
+          self.tile_width
+        )
+        .into(),
+      );
+    }
+    let tile_order = self.tile_width.trailing_zeros() as u8;
+    let order = self
+      .order
+      .unwrap_or_else(|| smoc.depth_max().saturating_sub(tile_order));
+    if order > Hpx::<u64>::MAX_DEPTH {
+      return Err(
+        format!(
+          "HiPS order must be <= {}. Actual: {}.",
+          Hpx::<u64>::MAX_DEPTH,
+          order
+        )
+        .into(),
+      );
+    }
+    if self.min_order > order {
+      return Err(
+        format!(
+          "HiPS min order ({}) larger than the HiPS max order ({}).",
+          self.min_order, order
+        )
+        .into(),
+      );
+    }
+    if let Some(path) = &self.moc {
+      let moc_order = self.moc_order.unwrap_or(order).min(Hpx::<u64>::MAX_DEPTH);
+      let hips_moc = at_depth(&smoc, moc_order);
+      hpx_cells_to_fits_ivoa(
+        hips_moc.into_range_moc_iter().cells(),
+        None,
+        None,
+        BufWriter::new(File::create(path)?),
+      )?;
+    }
+    if !self.no_tiles {
+      let ext = self
+        .ext
+        .map(|ext| format!(".{}", ext.trim_start_matches('.')))
+        .unwrap_or_default();
+      let stdout = io::stdout();
+      let mut handle = BufWriter::new(stdout.lock());
+      for norder in self.min_order..=order {
+        for npix in at_depth(&smoc, norder).flatten_to_fixed_depth_cells() {
+          writeln!(handle, "{}{}", tile_path(norder, npix), ext)?;
+        }
+      }
+      handle.flush()?;
+    }
+    Ok(())
+  }
+}
+
+/// Returns the given MOC at the given depth: degraded if the depth is smaller than the MOC depth,
+/// and with the same ranges otherwise.
+fn at_depth(moc: &RangeMOC<u64, Hpx<u64>>, depth: u8) -> RangeMOC<u64, Hpx<u64>> {
+  if depth < moc.depth_max() {
+    moc.degraded(depth)
+  } else {
+    RangeMOC::new(depth, moc.moc_ranges().clone())
+  }
+}
+
+/// Returns the path, relative to the HiPS root directory, of the tile of given order and index,
+/// without extension (tiles are grouped by directories of 10 000 tiles).
+fn tile_path(norder: u8, npix: u64) -> String {
+  format!(
+    "Norder{}/Dir{}/Npix{}",
+    norder,
+    (npix / 10_000) * 10_000,
+    npix
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_tile_path() {
+    assert_eq!(tile_path(3, 12), "Norder3/Dir0/Npix12");
+    assert_eq!(tile_path(8, 123_456), "Norder8/Dir120000/Npix123456");
+  }
+
+  #[test]
+  fn test_at_depth() {
+    let moc = RangeMOC::<u64, Hpx<u64>>::from_cells(5, [(5_u8, 4_u64), (3, 1)].into_iter(), None);
+    let tiles = |depth: u8| {
+      at_depth(&moc, depth)
+        .flatten_to_fixed_depth_cells()
+        .collect::<Vec<u64>>()
+    };
+    assert_eq!(tiles(0), vec![0]);
+    assert_eq!(tiles(3), vec![0, 1]);
+    assert_eq!(tiles(4), vec![1, 4, 5, 6, 7]);
+    assert_eq!(at_depth(&moc, 7).depth_max(), 7);
+  }
+}
@@ -14,11 +14,12 @@ use moclib::{
     ascii::{from_ascii_ivoa, from_ascii_stream},
     fits::{from_fits_ivoa, MocIdxType, MocQtyType, MocType as RMocType},
     json::from_json_aladin,
+    packed::from_packed,
   },
   idx::Idx,
   moc::{
     CellMOCIntoIterator, CellMOCIterator, CellOrCellRangeMOCIntoIterator,
-    CellOrCellRangeMOCIterator, RangeMOCIterator,
+    CellOrCellRangeMOCIterator, RangeMOCIntoIterator, RangeMOCIterator,
   },
   qty::{Frequency, Time},
 };
@@ -114,6 +115,10 @@ pub fn exec<R: BufRead>(
       let cellrange_it = from_ascii_stream::<u64, Time<u64>, _>(input)?;
       print_tmoc(print_header, cellrange_it.ranges())
     }
+    (Some(MocType::TMOC), InputFormat::Packed) => {
+      let moc = from_packed::<u64, Time<u64>, _>(input)?.try_into_range_moc()?;
+      print_tmoc(print_header, moc.into_range_moc_iter())
+    }
     // FMOC
     (Some(MocType::FMOC), InputFormat::Ascii) => {
       let mut input_str = String::new();
@@ -134,6 +139,10 @@ pub fn exec<R: BufRead>(
       let cellrange_it = from_ascii_stream::<u64, Frequency<u64>, _>(input)?;
       print_fmoc(print_header, cellrange_it.ranges())
     }
+    (Some(MocType::FMOC), InputFormat::Packed) => {
+      let moc = from_packed::<u64, Frequency<u64>, _>(input)?.try_into_range_moc()?;
+      print_fmoc(print_header, moc.into_range_moc_iter())
+    }
     // FITS file (SMOC or TMOC or FMOC, or ST-MOC)
     (_, InputFormat::Fits) => {
       let fits_res = from_fits_ivoa(input)?;
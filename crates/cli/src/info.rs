@@ -1,31 +1,87 @@
-use std::{error::Error, fs::File, io::BufReader, path::PathBuf};
+use std::{error::Error, fs, io::BufRead, path::PathBuf};
 
 use structopt::StructOpt;
 
 use moclib::{
-  deser::fits::{MocIdxType, MocQtyType, MocType, STMocType},
+  deser::fits::{
+    keywords,
+    mef::{hdus_from_fits_ivoa, MocHdu},
+    metadata::MocMetadata,
+    MocIdxType, MocQtyType, MocType, STMocType,
+  },
   idx::Idx,
   moc::{range::RangeMocIter, CellMOCIntoIterator, CellMOCIterator, RangeMOCIterator},
   moc2d::{range::RangeMOC2Elem, RangeMOC2Iterator},
   qty::MocQty,
 };
 
-use super::input::from_fits_file;
-
 #[derive(StructOpt, Debug)]
 pub struct Info {
   #[structopt(parse(from_os_str))]
-  /// Path of the FITS file containing a MOC
+  /// Path of the FITS file containing a MOC (or several MOCs, one per HDU)
   file: PathBuf,
 }
 
 impl Info {
   pub fn exec(self) -> Result<(), Box<dyn Error>> {
-    print_info(from_fits_file(self.file)?)
+    let bytes = fs::read(self.file)?;
+    let hdus: Vec<MocHdu> = hdus_from_fits_ivoa(&bytes)?.collect::<Result<_, _>>()?;
+    match hdus.as_slice() {
+      [] => Err(String::from("No extension HDU found.").into()),
+      [hdu] => {
+        print_info(hdu.moc()?)?;
+        print_metadata(hdu.metadata());
+        Ok(())
+      }
+      hdus => {
+        println!("Number of HDUs: {}", hdus.len());
+        for hdu in hdus {
+          println!();
+          match hdu.name() {
+            Some(name) => println!("HDU {}: {}", hdu.index(), name),
+            None => println!("HDU {}", hdu.index()),
+          }
+          if hdu.is_moc() {
+            print_info(hdu.moc()?)?;
+          } else {
+            println!("Not a MOC");
+          }
+          print_metadata(hdu.metadata());
+        }
+        Ok(())
+      }
+    }
+  }
+}
+
+fn print_metadata(metadata: &MocMetadata) {
+  if let Some(moc_id) = &metadata.moc_id {
+    println!("MOCID: {}", moc_id);
+  }
+  if let Some(moc_tool) = &metadata.moc_tool {
+    println!("MOCTOOL: {}", moc_tool);
+  }
+  match metadata.moc_type {
+    Some(keywords::MocType::Image) => println!("MOCTYPE: IMAGE"),
+    Some(keywords::MocType::Catalog) => println!("MOCTYPE: CATALOG"),
+    None => {}
+  }
+  if let Some(extname) = &metadata.extname {
+    println!("EXTNAME: {}", extname);
+  }
+  if let Some(date) = &metadata.date {
+    println!("DATE: {}", date);
+  }
+  let mut cards = metadata.cards().peekable();
+  if cards.peek().is_some() {
+    println!("Other header cards:");
+    for card in cards {
+      println!("  {}", card);
+    }
   }
 }
 
-fn print_info(moc: MocIdxType<BufReader<File>>) -> Result<(), Box<dyn Error>> {
+fn print_info<R: BufRead>(moc: MocIdxType<R>) -> Result<(), Box<dyn Error>> {
   match moc {
     // MocIdxType::U8(moc) => print_info_qty("u8", moc),
     MocIdxType::U16(moc) => print_info_qty("u16", moc),
@@ -35,9 +91,9 @@ fn print_info(moc: MocIdxType<BufReader<File>>) -> Result<(), Box<dyn Error>> {
   }
 }
 
-fn print_info_qty<T: Idx>(
+fn print_info_qty<T: Idx, R: BufRead>(
   idx_type: &str,
-  moc: MocQtyType<T, BufReader<File>>,
+  moc: MocQtyType<T, R>,
 ) -> Result<(), Box<dyn Error>> {
   match moc {
     MocQtyType::Hpx(moc) => print_moc_info_type(idx_type, "SPACE", moc),
@@ -45,13 +101,14 @@ fn print_info_qty<T: Idx>(
     MocQtyType::Freq(moc) => print_moc_info_type(idx_type, "FREQUENCY", moc),
     MocQtyType::TimeHpx(moc) => print_moc2_info_type(idx_type, "TIME-SPACE", moc),
     MocQtyType::FreqHpx(moc) => print_moc2_info(idx_type, "FREQUENCY-SPACE", moc),
+    MocQtyType::TimeFreq(moc) => print_moc2_info(idx_type, "TIME-FREQUENCY", moc),
   }
 }
 
-fn print_moc_info_type<T: Idx, Q: MocQty<T>>(
+fn print_moc_info_type<T: Idx, Q: MocQty<T>, R: BufRead>(
   idx_type: &str,
   qty_type: &str,
-  moc: MocType<T, Q, BufReader<File>>,
+  moc: MocType<T, Q, R>,
 ) -> Result<(), Box<dyn Error>> {
   match moc {
     MocType::Ranges(moc) => print_moc_info(idx_type, qty_type, moc),
@@ -73,10 +130,10 @@ fn print_moc_info<T: Idx, Q: MocQty<T>, R: RangeMOCIterator<T, Qty = Q>>(
   Ok(())
 }
 
-fn print_moc2_info_type<T: Idx>(
+fn print_moc2_info_type<T: Idx, R: BufRead>(
   idx_type: &str,
   qty_type: &str,
-  moc2: STMocType<T, BufReader<File>>,
+  moc2: STMocType<T, R>,
 ) -> Result<(), Box<dyn Error>> {
   match moc2 {
     STMocType::V2(moc) => print_moc2_info(idx_type, qty_type, moc),
@@ -1,10 +1,20 @@
 use std::error::Error;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufRead, BufReader};
 use std::path::{Path, PathBuf};
 use std::str::{self, FromStr};
 
-use moclib::deser::fits::{from_fits_ivoa, MocIdxType};
+use log::warn;
+
+use moclib::deser::{
+  fits::{
+    bintable::{BinTableReader, ValueReader},
+    from_fits_ivoa, MocIdxType,
+  },
+  votable::{is_votable, VOTable, VOTableReader},
+};
+
+use super::InputTime;
 
 #[derive(Debug)]
 pub enum ReducedInputFormat {
@@ -32,6 +42,8 @@ pub enum InputFormat {
   Json,
   Fits,
   Stream,
+  Packed,
+  VOTable,
 }
 impl FromStr for InputFormat {
   type Err = String;
@@ -42,8 +54,10 @@ impl FromStr for InputFormat {
       "json" => Ok(Self::Json),
       "fits" => Ok(Self::Fits),
       "stream" => Ok(Self::Stream),
+      "packed" => Ok(Self::Packed),
+      "votable" => Ok(Self::VOTable),
       _ => Err(format!(
-        "Unrecognized format '{}'. Expected: 'ascii, 'json', 'fits' or 'stream'",
+        "Unrecognized format '{}'. Expected: 'ascii, 'json', 'fits', 'stream', 'packed' or 'votable'",
         s
       )),
     }
@@ -57,6 +71,8 @@ impl InputFormat {
       Some("json") => Ok(Self::Json),
       Some("ascii") | Some("txt") => Ok(Self::Ascii),
       Some("stream") => Ok(Self::Stream),
+      Some("mocp") => Ok(Self::Packed),
+      Some("vot") | Some("votable") | Some("xml") => Ok(Self::VOTable),
       _ => Err(String::from(
         "Unable to guess the MOC format from the file extension, see options.",
       )),
@@ -99,3 +115,208 @@ pub fn from_fits_file(path: PathBuf) -> Result<MocIdxType<BufReader<File>>, Box<
   let reader = BufReader::new(file);
   from_fits_ivoa(reader).map_err(|e| e.into())
 }
+
+/// Returns `true` if the (possibly uncompressed) content of the given reader starts like a FITS file.
+pub fn is_fits<R: BufRead>(reader: &mut R) -> Result<bool, std::io::Error> {
+  reader.fill_buf().map(|buf| buf.starts_with(b"SIMPLE  ="))
+}
+
+/// Returns the reader of the FITS BINTABLE column of given name or, if no column has such a name,
+/// of given index (starting at 0).
+pub fn bintable_column_reader<R: BufRead>(
+  table: &BinTableReader<R>,
+  column: &str,
+) -> Result<ValueReader, Box<dyn Error>> {
+  let icol = match table.column_index(column) {
+    Some(icol) => icol,
+    None => column.parse::<usize>().map_err(|_| {
+      format!(
+        "Column '{}' not found. Available columns: {:?}",
+        column,
+        table.column_names()
+      )
+    })?,
+  };
+  table.column_reader(icol).map_err(|e| e.into())
+}
+
+/// Returns the time, in microseconds since JD=0, stored in the given FITS BINTABLE column
+/// (either a string or a number), or `None` if the value is blank or can't be parsed.
+pub fn bintable_time(time_type: &InputTime, column: &ValueReader, row: &[u8]) -> Option<u64> {
+  match column.read_str(row) {
+    Some(time_str) => time_type.parse(time_str.trim()).ok(),
+    None => column
+      .read(row, 0)
+      .and_then(|time| time_type.from_f64(time).ok()),
+  }
+}
+
+/// Returns a reader of the VOTable stored in the given file (or in stdin if the path is '-'),
+/// or `None` if the input does not start like a VOTable.
+#[allow(clippy::type_complexity)]
+pub fn votable_from_input(
+  input: &Path,
+) -> Result<Option<VOTableReader<Box<dyn BufRead>>>, Box<dyn Error>> {
+  let mut reader: Box<dyn BufRead> = if input.as_os_str() == "-" {
+    Box::new(std::io::stdin().lock())
+  } else {
+    Box::new(BufReader::new(File::open(input)?))
+  };
+  if is_votable(reader.fill_buf()?) {
+    VOTableReader::new(reader).map(Some).map_err(|e| e.into())
+  } else {
+    Ok(None)
+  }
+}
+
+/// Returns the indices of the VOTable columns given by name (or index starting at 0), in a
+/// comma separated list. If no column is given, looks for the columns having the given UCDs, and falls back on
+/// the first columns.
+pub fn votable_columns(
+  votable: &VOTable,
+  columns: Option<&str>,
+  default_ucds: &[&str],
+) -> Result<Vec<usize>, Box<dyn Error>> {
+  let names = || {
+    votable
+      .fields
+      .iter()
+      .map(|f| f.name.as_str())
+      .collect::<Vec<&str>>()
+  };
+  let columns: Vec<&str> = columns
+    .map(|c| c.split(',').map(str::trim).collect())
+    .unwrap_or_default();
+  if columns.is_empty() {
+    match default_ucds
+      .iter()
+      .map(|ucd| votable.column_index_by_ucd(ucd))
+      .collect::<Option<Vec<usize>>>()
+    {
+      Some(icols) => Ok(icols),
+      None if votable.fields.len() >= default_ucds.len() => {
+        warn!(
+          "No columns having the UCDs {:?}: using the {} first columns of {:?}.",
+          default_ucds,
+          default_ucds.len(),
+          names()
+        );
+        Ok((0..default_ucds.len()).collect())
+      }
+      None => Err(
+        format!(
+          "Not enough columns in the VOTable: {} expected, columns: {:?}",
+          default_ucds.len(),
+          names()
+        )
+        .into(),
+      ),
+    }
+  } else if columns.len() != default_ucds.len() {
+    Err(
+      format!(
+        "{} columns expected, {} given.",
+        default_ucds.len(),
+        columns.len()
+      )
+      .into(),
+    )
+  } else {
+    columns
+      .iter()
+      .map(|column| votable_column(votable, column))
+      .collect()
+  }
+}
+
+/// Returns the index of the VOTable column of given name or, if no column has such a name,
+/// of given index (starting at 0).
+pub fn votable_column(votable: &VOTable, column: &str) -> Result<usize, Box<dyn Error>> {
+  votable
+    .column_index(column)
+    .or_else(|| {
+      column
+        .parse::<usize>()
+        .ok()
+        .filter(|i| *i < votable.fields.len())
+    })
+    .ok_or_else(|| {
+      format!(
+        "Column '{}' not found. Available columns: {:?}",
+        column,
+        votable
+          .fields
+          .iter()
+          .map(|f| f.name.as_str())
+          .collect::<Vec<&str>>()
+      )
+      .into()
+    })
+}
+
+/// Returns the value of the given column of a VOTable row, parsed as a `f64`.
+pub fn votable_f64(row: &[Option<String>], icol: usize) -> Option<f64> {
+  row
+    .get(icol)
+    .and_then(|v| v.as_deref())
+    .and_then(|v| v.parse::<f64>().ok())
+}
+
+/// Returns the time, in microseconds since JD=0, stored in the given column of a VOTable row.
+pub fn votable_time(time_type: &InputTime, row: &[Option<String>], icol: usize) -> Option<u64> {
+  row
+    .get(icol)
+    .and_then(|v| v.as_deref())
+    .and_then(|v| time_type.parse(v).ok())
+}
+
+/// Returns, for each VOTable row having no null value in the given columns, a line made of the
+/// values of the given columns separated by the given separator.
+pub fn votable_lines<'a, R: BufRead + 'a>(
+  rows: VOTableReader<R>,
+  icols: &'a [usize],
+  separator: &'a str,
+) -> impl Iterator<Item = std::io::Result<String>> + 'a {
+  VOTableLines {
+    rows,
+    icols,
+    separator,
+    n_skipped: 0,
+  }
+}
+
+/// Iterator on the lines built from the rows of a VOTable, see [votable_lines].
+struct VOTableLines<'a, R: BufRead> {
+  rows: VOTableReader<R>,
+  icols: &'a [usize],
+  separator: &'a str,
+  /// Number of rows skipped because of null values
+  n_skipped: usize,
+}
+
+impl<R: BufRead> Iterator for VOTableLines<'_, R> {
+  type Item = std::io::Result<String>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    for row in &mut self.rows {
+      let row = match row {
+        Ok(row) => row,
+        Err(e) => return Some(Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e))),
+      };
+      let values = self
+        .icols
+        .iter()
+        .map(|i| row.get(*i).and_then(|v| v.as_deref()))
+        .collect::<Option<Vec<&str>>>();
+      match values {
+        Some(values) => return Some(Ok(values.join(self.separator))),
+        None => self.n_skipped += 1,
+      }
+    }
+    if self.n_skipped > 0 {
+      warn!("{} rows with null values skipped.", self.n_skipped);
+      self.n_skipped = 0;
+    }
+    None
+  }
+}
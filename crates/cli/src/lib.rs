@@ -6,15 +6,19 @@ use time::{
 };
 // use chrono::prelude::*;
 
+pub mod bundle;
 pub mod constants;
 pub mod convert;
 pub mod filter;
 pub mod from;
+pub mod hips;
 pub mod hprint;
 pub mod info;
 pub mod input;
 pub mod op;
 pub mod output;
+pub mod repair;
+pub mod validate;
 pub mod view;
 
 // See https://www.ivoa.net/rdf/timescale/2019-03-15/timescale.html
@@ -76,6 +80,18 @@ impl InputTime {
       }
     }
   }
+
+  /// Same as [Self::parse] for a value already parsed as a number (e.g. read from a FITS column).
+  pub fn from_f64(&self, value: f64) -> Result<u64, Box<dyn Error>> {
+    match self {
+      InputTime::JD => Ok((value * N_MICROSEC_IN_DAY) as u64),
+      InputTime::MJD => Ok((mjd2jd(value) * N_MICROSEC_IN_DAY) as u64),
+      InputTime::MicroSecSinceJD0 => Ok(value as u64),
+      InputTime::IsoRfc | InputTime::IsoSimple => {
+        Err(String::from("ISO times must be provided as strings, not as numbers.").into())
+      }
+    }
+  }
 }
 
 impl FromStr for InputTime {
@@ -4,8 +4,8 @@ use structopt::clap::AppSettings;
 use structopt::StructOpt;
 
 use moc_cli::{
-  constants::Constants, convert::Convert, filter::Filter, from::From, hprint::HumanPrint,
-  info::Info, op::Op, view::View,
+  bundle::Bundle, constants::Constants, convert::Convert, filter::Filter, from::From, hips::Hips,
+  hprint::HumanPrint, info::Info, op::Op, repair::Repair, validate::Validate, view::View,
 };
 
 #[derive(Debug, StructOpt)]
@@ -20,9 +20,18 @@ enum Args {
   #[structopt(name = "info")]
   /// Prints information on the given MOC
   Info(Info),
+  #[structopt(name = "validate")]
+  /// Strictly validates a FITS MOC (structure, checksums, keywords and data), listing the issues
+  Validate(Validate),
+  #[structopt(name = "repair")]
+  /// Repairs a malformed FITS MOC (sorting, merging, degrading too deep cells), listing the fixes
+  Repair(Repair),
   #[structopt(name = "convert")]
   /// Converts an input format to the (most recent versions of) an output format
   Convert(Convert),
+  #[structopt(name = "bundle")]
+  /// Bundles several FITS MOCs (possibly of different types) into a single multi-extension FITS file
+  Bundle(Bundle),
   #[structopt(name = "from")]
   /// Create a MOC from given parameters
   From(From),
@@ -38,6 +47,9 @@ enum Args {
   #[structopt(name = "view")]
   /// Save a PNG of a S-MOC and visualize it.
   View(View),
+  #[structopt(name = "hips")]
+  /// List the HiPS tiles covered by a S-MOC and write the HiPS MOC
+  Hips(Hips),
 }
 
 impl Args {
@@ -45,12 +57,16 @@ impl Args {
     match self {
       Args::Constants(cst) => cst.exec(),
       Args::Info(info) => info.exec(),
+      Args::Validate(validate) => validate.exec(),
+      Args::Repair(repair) => repair.exec(),
       Args::Convert(convert) => convert.exec(),
+      Args::Bundle(bundle) => bundle.exec(),
       Args::From(from) => from.exec(),
       Args::Op(op) => op.exec(),
       Args::Filter(filter) => filter.exec(),
       Args::HumanPrint(hprint) => hprint.exec(),
       Args::View(view) => view.exec(),
+      Args::Hips(hips) => hips.exec(),
     }
   }
 }
@@ -1,4 +1,10 @@
-use std::{error::Error, fs::File, io::BufReader, path::PathBuf};
+use std::{
+  error::Error,
+  f64::consts::PI,
+  fs::{self, File},
+  io::{BufRead, BufReader},
+  path::PathBuf,
+};
 
 use structopt::StructOpt;
 
@@ -9,18 +15,30 @@ use crate::{
 use moclib::hpxranges2d::HpxRanges2D;
 use moclib::{
   deser::fits::{
-    from_fits_ivoa, multiordermap::sum_from_fits_multiordermap, MocIdxType, MocQtyType, MocType,
-    RangeMoc2DIterFromFits, STMocType,
+    from_fits_ivoa,
+    mef::{hdus_from_fits_ivoa, HduId},
+    multiordermap::{columns_from_fits_multiordermap, sum_from_fits_multiordermap},
+    MocIdxType, MocQtyType, MocType, RangeMoc2DIterFromFits, STMocType,
   },
   elemset::range::MocRanges,
   hpxranges2d::{FreqSpaceMoc, TimeSpaceMoc},
   idx::Idx,
   moc::{
-    range::{RangeMOC, RangeMocIter},
+    range::{op::convert::convert_to_u64, RangeMOC, RangeMocIter},
     CellMOCIntoIterator, CellMOCIterator, RangeMOCIntoIterator, RangeMOCIterator,
   },
-  moc2d::{range::RangeMOC2Elem, RangeMOC2Iterator},
+  moc2d::{
+    range::{RangeMOC2, RangeMOC2Elem},
+    RangeMOC2ElemIt, RangeMOC2IntoIterator, RangeMOC2Iterator,
+  },
+  mocranges2d::Moc2DRanges,
+  mom::{
+    cell_area,
+    stats::{mom_stats_per_component, MomStats},
+    HpxMOMIterator, HpxMomIter,
+  },
   qty::{Frequency, Hpx, MocQty, Time},
+  ranges::ranges2d::SNORanges2D,
 };
 
 #[derive(StructOpt, Debug)]
@@ -94,7 +112,18 @@ pub enum Op {
   #[structopt(name = "minus")]
   /// Performs the logical operation 'AND(left, NOT(right))' between 2 MOCs (= left minus right)
   Minus(Op2Args),
-  // MultiUnion OpNArgs (only FITS files?)
+  #[structopt(name = "multiunion")]
+  /// Performs the union of the MOCs stored in the HDUs of a multi-extension FITS file. All (selected) MOCs must be of the same type (S-, T-, F- or ST-MOC).
+  MultiUnion {
+    #[structopt(parse(from_os_str))]
+    /// Input FITS file containing several MOCs
+    input: PathBuf,
+    #[structopt(long = "hdu", number_of_values = 1)]
+    /// Index (starting at 1) or name (EXTNAME or MOCID) of an HDU to be included in the union (can be repeated) [default: all HDUs]
+    hdus: Vec<HduId>,
+    #[structopt(subcommand)]
+    output: OutputFormat,
+  },
   #[structopt(name = "sfold")]
   /// Returns the union of the T- or F-MOCs associated to S-MOCs intersecting the given S-MOC. Left: S-MOC, right: ST-MOC or SF-MOC, res: T-MOC or F-MOC.
   SpaceFold(Op2Args),
@@ -113,6 +142,41 @@ pub enum Op {
     #[structopt(parse(from_os_str))]
     /// Input MOC FITS file.
     moc: PathBuf,
+  },
+  #[structopt(name = "momstats")]
+  /// Prints, as a table, statistics (area, sum, mean, min, max, median and quantiles, area weighted) of the densities of the given Multi-Order Map which are in the given MOC, possibly for each connected component of the MOC.
+  MultiOrderMapStats {
+    #[structopt(parse(from_os_str))]
+    /// Input Multi-Order healpix Map FITS file in which the value is a density (e.g. of probability).
+    mom: PathBuf,
+    #[structopt(parse(from_os_str))]
+    /// Input MOC FITS file.
+    moc: PathBuf,
+    #[structopt(short = "-c", long = "--column")]
+    /// Name of the density column [default: PROBDENSITY]
+    column: Option<String>,
+    #[structopt(short = "-s", long = "--split")]
+    /// Also print the statistics of each disjoint part of the MOC
+    split: bool,
+    #[structopt(short = "-i", long = "--8neigh")]
+    /// With 'split', account for indirect neighbours (8-neigh) instead of direct neighbours (4-neigh) only.
+    indirect_neigh: bool,
+    #[structopt(
+      short = "-q",
+      long = "--quantiles",
+      use_delimiter = true,
+      require_delimiter = true
+    )]
+    /// Comma separated list of quantiles to be printed (e.g. '0.05,0.95')
+    quantiles: Vec<f64>,
+    #[structopt(
+      long = "--position",
+      use_delimiter = true,
+      require_delimiter = true,
+      allow_hyphen_values = true
+    )]
+    /// Comma separated 'lon,lat' position, in degrees, at which the density is printed (if in the MOC)
+    position: Vec<f64>,
   }, // Add (?):
      // * moc contains (exit code=0 + output="true", else exit code=1 + output="false")
      // * moc overlaps
@@ -145,30 +209,118 @@ impl Op {
       Op::Union(op) => op.exec(Op2::Union),
       Op::SymmetricDifference(op) => op.exec(Op2::SymmetricDifference),
       Op::Minus(op) => op.exec(Op2::Minus),
+      Op::MultiUnion {
+        input,
+        hdus,
+        output,
+      } => multi_union_exec(input, hdus, output),
       Op::SpaceFold(op) => op.exec(Op2::SpaceFold),
       Op::TimeFold(op) => op.exec(Op2::TimeFold),
       Op::FreqFold(op) => op.exec(Op2::FreqFold),
-      Op::MultiOrderMapSum { mom, moc } => input::from_fits_file(moc).and_then(|moc| {
-        let moc = match moc {
-          MocIdxType::U16(moc) => match moc {
-            MocQtyType::Hpx(moc) => moc.collect_to_u64::<Hpx<u64>>(),
-            _ => return Err("Input MOC is not a S-MOC!".to_string().into()),
-          },
-          MocIdxType::U32(moc) => match moc {
-            MocQtyType::Hpx(moc) => moc.collect_to_u64::<Hpx<u64>>(),
-            _ => return Err("Input MOC is not a S-MOC!".to_string().into()),
-          },
-          MocIdxType::U64(moc) => match moc {
-            MocQtyType::Hpx(moc) => moc.collect(),
-            _ => return Err("Input MOC is not a S-MOC!".to_string().into()),
-          },
-        };
+      Op::MultiOrderMapSum { mom, moc } => {
+        let moc = smoc_from_fits_file(moc)?;
         let f = File::open(mom)?;
         let sum = sum_from_fits_multiordermap(BufReader::new(f), &moc)?;
         println!("{}", sum);
         Ok(())
-      }),
+      }
+      Op::MultiOrderMapStats {
+        mom,
+        moc,
+        column,
+        split,
+        indirect_neigh,
+        quantiles,
+        position,
+      } => {
+        let moc = smoc_from_fits_file(moc)?;
+        let position = match position.as_slice() {
+          [] => None,
+          [lon, lat] => Some((lon.to_radians(), lat.to_radians())),
+          _ => return Err(String::from("Position must be of the form 'lon,lat'.").into()),
+        };
+        let column = column.as_deref().unwrap_or("PROBDENSITY");
+        let f = File::open(mom)?;
+        let columns = columns_from_fits_multiordermap(BufReader::new(f), None, Some(&[column]))?;
+        let mom: Vec<(u64, f64)> = columns.mom_iter(column, true)?.collect();
+        // Header
+        let mut header = String::from("moc\tn_cells\tarea_deg2\tsum\tmean\tmin\tmax\tmedian");
+        for q in &quantiles {
+          header.push_str(&format!("\tq{}", q));
+        }
+        println!("{}", header);
+        let mom_it = || HpxMomIter::<u64, Hpx<u64>, f64, _>::new(mom.iter().copied());
+        print_mom_stats(
+          "all",
+          MomStats::from_mom_in_moc(mom_it(), &moc, &quantiles),
+          quantiles.len(),
+        );
+        if split {
+          let components = mom_stats_per_component(mom_it(), &moc, indirect_neigh, &quantiles);
+          for (i, (_, stats)) in components.into_iter().enumerate() {
+            print_mom_stats(&i.to_string(), stats, quantiles.len());
+          }
+        }
+        if let Some((lon, lat)) = position {
+          match mom_it().value_at(lon, lat) {
+            Some((uniq, value)) if moc.is_in(lon, lat) => {
+              let depth = Hpx::<u64>::from_uniq_hpx(uniq).0;
+              println!(
+                "density at position: {} (uniq: {})",
+                value / cell_area(depth),
+                uniq
+              );
+            }
+            _ => println!("density at position: none (position not in the MOC or in the MOM)"),
+          }
+        }
+        Ok(())
+      }
+    }
+  }
+}
+
+/// Load a S-MOC from a FITS file, converting its indices to `u64`.
+fn smoc_from_fits_file(path: PathBuf) -> Result<RangeMOC<u64, Hpx<u64>>, Box<dyn Error>> {
+  input::from_fits_file(path).and_then(|moc| {
+    Ok(match moc {
+      MocIdxType::U16(moc) => match moc {
+        MocQtyType::Hpx(moc) => moc.collect_to_u64::<Hpx<u64>>(),
+        _ => return Err("Input MOC is not a S-MOC!".to_string().into()),
+      },
+      MocIdxType::U32(moc) => match moc {
+        MocQtyType::Hpx(moc) => moc.collect_to_u64::<Hpx<u64>>(),
+        _ => return Err("Input MOC is not a S-MOC!".to_string().into()),
+      },
+      MocIdxType::U64(moc) => match moc {
+        MocQtyType::Hpx(moc) => moc.collect(),
+        _ => return Err("Input MOC is not a S-MOC!".to_string().into()),
+      },
+    })
+  })
+}
+
+/// Print a row of the `momstats` table.
+fn print_mom_stats(name: &str, stats: Option<MomStats>, n_quantiles: usize) {
+  match stats {
+    Some(stats) => {
+      let mut row = format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+        name,
+        stats.n_cells,
+        stats.area * (180.0 / PI) * (180.0 / PI),
+        stats.sum,
+        stats.mean,
+        stats.min,
+        stats.max,
+        stats.median
+      );
+      for (_, value) in stats.quantiles {
+        row.push_str(&format!("\t{}", value));
+      }
+      println!("{}", row);
     }
+    None => println!("{}\t0{}", name, "\t-".repeat(6 + n_quantiles)),
   }
 }
 
@@ -224,6 +376,7 @@ fn op1_exec_on_fits_qty<T: Idx>(
     MocQtyType::Freq(moc) => op1_exec_on_fits_freq(op1, moc, output),
     MocQtyType::TimeHpx(moc) => op1_exec_on_fits_timehpx(op1, moc, output),
     MocQtyType::FreqHpx(moc) => op1_exec_on_fits_freqhpx(op1, moc, output),
+    MocQtyType::TimeFreq(moc) => op1_exec_on_fits_timefreq(op1, moc, output),
   }
 }
 
@@ -287,6 +440,14 @@ fn op1_exec_on_fits_freqhpx<T: Idx>(
   op1.perform_op_on_2drangemoc_iter(moc, output)
 }
 
+fn op1_exec_on_fits_timefreq<T: Idx>(
+  op1: Op1,
+  moc: RangeMoc2DIterFromFits<T, BufReader<File>, Time<T>, Frequency<T>>,
+  output: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+  op1.perform_op_on_2drangemoc_iter(moc, output)
+}
+
 pub enum Op1 {
   Complement,
   Degrade { new_depth: u8 },
@@ -632,6 +793,14 @@ fn op2_exec_on_fits_qty<T: Idx>(
     (MocQtyType::FreqHpx(_), MocQtyType::TimeHpx(_)) => {
       Err(String::from("Incompatible MOCs. Left: SF-MOC. Right: ST-MOC.").into())
     }
+    // TF-MOC vs TF-MOC
+    (MocQtyType::TimeFreq(left_moc), MocQtyType::TimeFreq(right_moc)) => {
+      op2.perform_op_on_tfrangemoc_iter(left_moc, right_moc, output)
+    }
+    // TF-MOC vs Others
+    (MocQtyType::TimeFreq(_), _) | (_, MocQtyType::TimeFreq(_)) => Err(
+      String::from("Incompatible MOCs. Operations on TF-MOCs only possible with TF-MOCs.").into(),
+    ),
   }
 }
 fn op2_exec_on_fits_moc<T: Idx, Q: MocQty<T>>(
@@ -746,6 +915,12 @@ fn op2_exec_on_fits_qty_with_lconv<TL: Idx, TR: Idx + From<TL>>(
     (MocQtyType::FreqHpx(_), MocQtyType::TimeHpx(_)) => {
       Err(String::from("Incompatible MOCs. Left: SF-MOC. Right: ST-MOC.").into())
     }
+    (MocQtyType::TimeFreq(_), MocQtyType::TimeFreq(_)) => {
+      Err(String::from("Unable to convert a TF-MOCs datatype so far.").into())
+    }
+    (MocQtyType::TimeFreq(_), _) | (_, MocQtyType::TimeFreq(_)) => Err(
+      String::from("Incompatible MOCs. Operations on TF-MOCs only possible with TF-MOCs.").into(),
+    ),
   }
 }
 
@@ -865,6 +1040,12 @@ fn op2_exec_on_fits_qty_with_rconv<TL: Idx + From<TR>, TR: Idx>(
     (MocQtyType::FreqHpx(_), MocQtyType::TimeHpx(_)) => {
       Err(String::from("Incompatible MOCs. Left: SF-MOC. Right: ST-MOC.").into())
     }
+    (MocQtyType::TimeFreq(_), MocQtyType::TimeFreq(_)) => {
+      Err(String::from("Unable to convert a TF-MOCs datatype so far.").into())
+    }
+    (MocQtyType::TimeFreq(_), _) | (_, MocQtyType::TimeFreq(_)) => Err(
+      String::from("Incompatible MOCs. Operations on TF-MOCs only possible with TF-MOCs.").into(),
+    ),
   }
 }
 fn op2_exec_on_fits_moc_rconv<TL: Idx + From<TR>, QL: MocQty<TL>, TR: Idx, QR: MocQty<TR>>(
@@ -1440,6 +1621,196 @@ impl Op2 {
       Op2::FreqFold => Err(String::from("Operation must involves either a F-MOC").into()),
     }
   }
+
+  fn perform_op_on_tfrangemoc_iter<T: Idx, L, R>(
+    self,
+    left_tfmoc: L,
+    right_tfmoc: R,
+    output: OutputFormat,
+  ) -> Result<(), Box<dyn Error>>
+  where
+    L: RangeMOC2Iterator<
+      T,
+      Time<T>,
+      RangeMocIter<T, Time<T>>,
+      T,
+      Frequency<T>,
+      RangeMocIter<T, Frequency<T>>,
+      RangeMOC2Elem<T, Time<T>, T, Frequency<T>>,
+    >,
+    R: RangeMOC2Iterator<
+      T,
+      Time<T>,
+      RangeMocIter<T, Time<T>>,
+      T,
+      Frequency<T>,
+      RangeMocIter<T, Frequency<T>>,
+      RangeMOC2Elem<T, Time<T>, T, Frequency<T>>,
+    >,
+  {
+    // Same as for ST-MOCs and SF-MOCs, but using the generic 2D ranges
+    let time_depth = left_tfmoc.depth_max_1().max(right_tfmoc.depth_max_1());
+    let freq_depth = left_tfmoc.depth_max_2().max(right_tfmoc.depth_max_2());
+    match self {
+      Op2::Union => output.write_tfmoc(left_tfmoc.or(right_tfmoc)),
+      Op2::Intersection => {
+        let left_tfmoc = Moc2DRanges::from_ranges_it(left_tfmoc);
+        let right_tfmoc = Moc2DRanges::from_ranges_it(right_tfmoc);
+        output.write_tfmoc(
+          left_tfmoc
+            .intersection(&right_tfmoc)
+            .range_moc2_iter(time_depth, freq_depth),
+        )
+      }
+      Op2::SymmetricDifference => {
+        let left_tfmoc = Moc2DRanges::from_ranges_it(left_tfmoc);
+        let right_tfmoc = Moc2DRanges::from_ranges_it(right_tfmoc);
+        output.write_tfmoc(
+          left_tfmoc
+            .difference(&right_tfmoc)
+            .union(&right_tfmoc.difference(&left_tfmoc))
+            .range_moc2_iter(time_depth, freq_depth),
+        )
+      }
+      Op2::Minus => {
+        let left_tfmoc = Moc2DRanges::from_ranges_it(left_tfmoc);
+        let right_tfmoc = Moc2DRanges::from_ranges_it(right_tfmoc);
+        output.write_tfmoc(
+          left_tfmoc
+            .difference(&right_tfmoc)
+            .range_moc2_iter(time_depth, freq_depth),
+        )
+      }
+      Op2::SpaceFold => Err(String::from("Operation must involves either a S-MOC").into()),
+      Op2::TimeFold => Err(String::from("Operation must involves either a T-MOC").into()),
+      Op2::FreqFold => Err(String::from("Operation must involves either a F-MOC").into()),
+    }
+  }
+}
+
+/// Union of the MOCs read from the HDUs of a FITS file, on u64 indices.
+enum UnionMoc {
+  Space(RangeMOC<u64, Hpx<u64>>),
+  Time(RangeMOC<u64, Time<u64>>),
+  Freq(RangeMOC<u64, Frequency<u64>>),
+  TimeSpace(RangeMOC2<u64, Time<u64>, u64, Hpx<u64>>),
+}
+impl UnionMoc {
+  fn from_fits<R: BufRead>(moc: MocIdxType<R>) -> Result<Self, Box<dyn Error>> {
+    match moc {
+      MocIdxType::U16(moc) => Self::from_fits_qty(moc),
+      MocIdxType::U32(moc) => Self::from_fits_qty(moc),
+      MocIdxType::U64(moc) => Self::from_fits_qty(moc),
+    }
+  }
+
+  fn from_fits_qty<T: Idx, R: BufRead>(moc: MocQtyType<T, R>) -> Result<Self, Box<dyn Error>> {
+    match moc {
+      MocQtyType::Hpx(moc) => Ok(UnionMoc::Space(moc.collect_to_u64::<Hpx<u64>>())),
+      MocQtyType::Time(moc) => Ok(UnionMoc::Time(moc.collect_to_u64::<Time<u64>>())),
+      MocQtyType::Freq(moc) => Ok(UnionMoc::Freq(moc.collect_to_u64::<Frequency<u64>>())),
+      MocQtyType::TimeHpx(STMocType::V2(moc)) => Ok(UnionMoc::TimeSpace(stmoc_to_u64(moc))),
+      MocQtyType::TimeHpx(STMocType::PreV2(moc)) => Ok(UnionMoc::TimeSpace(stmoc_to_u64(moc))),
+      MocQtyType::FreqHpx(_) | MocQtyType::TimeFreq(_) => {
+        Err(String::from("Union of SF-MOCs or TF-MOCs not supported yet.").into())
+      }
+    }
+  }
+
+  fn type_name(&self) -> &'static str {
+    match self {
+      UnionMoc::Space(_) => "S-MOC",
+      UnionMoc::Time(_) => "T-MOC",
+      UnionMoc::Freq(_) => "F-MOC",
+      UnionMoc::TimeSpace(_) => "ST-MOC",
+    }
+  }
+
+  fn or(self, rhs: Self) -> Result<Self, Box<dyn Error>> {
+    match (self, rhs) {
+      (UnionMoc::Space(l), UnionMoc::Space(r)) => Ok(UnionMoc::Space(l.or(&r))),
+      (UnionMoc::Time(l), UnionMoc::Time(r)) => Ok(UnionMoc::Time(l.or(&r))),
+      (UnionMoc::Freq(l), UnionMoc::Freq(r)) => Ok(UnionMoc::Freq(l.or(&r))),
+      (UnionMoc::TimeSpace(l), UnionMoc::TimeSpace(r)) => Ok(UnionMoc::TimeSpace(l.into_or(r))),
+      (l, r) => Err(
+        format!(
+          "Unable to perform the union of a {} and a {}.",
+          l.type_name(),
+          r.type_name()
+        )
+        .into(),
+      ),
+    }
+  }
+
+  fn write(self, output: OutputFormat) -> Result<(), Box<dyn Error>> {
+    match self {
+      UnionMoc::Space(moc) => {
+        output.write_smoc_possibly_auto_converting_from_u64(moc.into_range_moc_iter())
+      }
+      UnionMoc::Time(moc) => {
+        output.write_tmoc_possibly_auto_converting_from_u64(moc.into_range_moc_iter())
+      }
+      UnionMoc::Freq(moc) => {
+        output.write_fmoc_possibly_auto_converting_from_u64(moc.into_range_moc_iter())
+      }
+      UnionMoc::TimeSpace(moc) => output.write_stmoc(moc.into_range_moc2_iter()),
+    }
+  }
+}
+
+fn stmoc_to_u64<T, R>(stmoc: R) -> RangeMOC2<u64, Time<u64>, u64, Hpx<u64>>
+where
+  T: Idx,
+  R: RangeMOC2Iterator<
+    T,
+    Time<T>,
+    RangeMocIter<T, Time<T>>,
+    T,
+    Hpx<T>,
+    RangeMocIter<T, Hpx<T>>,
+    RangeMOC2Elem<T, Time<T>, T, Hpx<T>>,
+  >,
+{
+  let (depth_time, depth_hpx) = (stmoc.depth_max_1(), stmoc.depth_max_2());
+  let elems = stmoc
+    .map(|elem| {
+      let (tmoc, smoc) = elem.range_mocs_it();
+      RangeMOC2Elem::new(
+        convert_to_u64::<T, Time<T>, _, Time<u64>>(tmoc).into_range_moc(),
+        convert_to_u64::<T, Hpx<T>, _, Hpx<u64>>(smoc).into_range_moc(),
+      )
+    })
+    .collect();
+  RangeMOC2::new(depth_time, depth_hpx, elems)
+}
+
+/// Unions, on the fly, the MOCs of the given HDUs (all MOC HDUs if empty).
+fn multi_union_exec(
+  input: PathBuf,
+  hdu_ids: Vec<HduId>,
+  output: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+  let bytes = fs::read(input)?;
+  let mut union: Option<UnionMoc> = None;
+  for hdu in hdus_from_fits_ivoa(&bytes)? {
+    let hdu = hdu?;
+    let selected = if hdu_ids.is_empty() {
+      hdu.is_moc()
+    } else {
+      hdu_ids.iter().any(|id| hdu.matches(id))
+    };
+    if selected {
+      let moc = UnionMoc::from_fits(hdu.moc()?)?;
+      union = Some(match union {
+        None => moc,
+        Some(union) => union.or(moc)?,
+      });
+    }
+  }
+  union
+    .ok_or_else(|| String::from("No MOC HDU selected.").into())
+    .and_then(|union| union.write(output))
 }
 
 #[cfg(test)]
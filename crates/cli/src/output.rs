@@ -1,7 +1,7 @@
 use std::{
   error::Error,
   fs::File,
-  io::{self, BufWriter},
+  io::{self, BufWriter, Write},
   path::PathBuf,
 };
 
@@ -10,18 +10,33 @@ use structopt::StructOpt;
 use moclib::{
   deser::{
     ascii::{moc2d_to_ascii_ivoa, to_ascii_ivoa, to_ascii_stream},
-    fits::{self, ranges_sf_to_fits_ivoa, ranges_st_to_fits_ivoa, ranges_to_fits_ivoa},
-    json::{cellmoc2d_to_json_aladin, to_json_aladin},
+    ds9::{to_crtf, to_ds9},
+    fits::{
+      self, hpx_cells_to_fits_ivoa_with_metadata, keywords::CoordSys, metadata::MocMetadata,
+      multiordermap::hpxmom_to_fits_multiordermap, ranges2d_to_fits_ivoa,
+      ranges2d_to_fits_ivoa_with_metadata, ranges_to_fits_ivoa, ranges_to_fits_ivoa_with_metadata,
+      skymap::hpxmom_to_fits_skymap,
+    },
+    geojson::to_geojson,
+    json::{cellmoc2d_to_json_aladin, rangemoc2d_to_json_aladin, to_json_aladin},
+    packed::to_packed,
+    stcs::{moc2stcs, moc2stcs_moc, tmoc2stcs},
+    votable::{moc_to_votable, VOTableMoc},
   },
+  elemset::range::MocRanges,
   idx::Idx,
   moc::{
-    range::op::convert::{convert_from_u64, convert_to_u64},
-    CellHpxMOCIterator, CellMOCIterator, RangeMOCIterator,
+    range::{
+      op::convert::{convert_from_u64, convert_to_u64},
+      RangeMOC,
+    },
+    CellMOCIterator, RangeMOCIterator,
   },
   moc2d::{
     CellMOC2IntoIterator, CellOrCellRangeMOC2IntoIterator, RangeMOC2ElemIt, RangeMOC2Iterator,
   },
-  qty::{Frequency, Hpx, MocQty, Time},
+  mom::{HpxMomIter, SKYMAP_DEPTH_MAX},
+  qty::{Frequency, Hpx, MocQty, MocableQty, Time},
 };
 
 #[derive(StructOpt, Clone, Debug)]
@@ -44,6 +59,9 @@ pub enum OutputFormat {
     #[structopt(short = "-w", long = "--fold")]
     /// Width of a cheep fold formatting
     fold: Option<usize>,
+    #[structopt(short = "-r", long = "--ranges")]
+    /// Write the ranges of 2D-MOCs as [first, last] cell arrays at the depth max, in streaming (not Aladin compatible)
+    ranges: bool,
     /// Path of the output file (stdout if empty)
     opt_file: Option<PathBuf>,
   },
@@ -62,15 +80,97 @@ pub enum OutputFormat {
     #[structopt(short = "-y", long = "--moc-type")]
     /// MOC Type to be written in the FITS header (IMAGE or CATALOG)
     moc_type: Option<fits::keywords::MocType>,
+    #[structopt(short = "-c", long = "--checksum")]
+    /// Write the CHECKSUM and DATASUM keywords (the data is then first written in memory; always set if the input FITS MOC has a CHECKSUM)
+    checksum: bool,
     /// Path of the output file
     file: PathBuf,
+    #[structopt(skip)]
+    /// Metadata of the input FITS MOC, preserved in the output header
+    metadata: MocMetadata,
   },
   #[structopt(name = "stream")]
   /// Output a streamed MOC (not yet implemented!)
   Stream,
+  #[structopt(name = "packed")]
+  /// Output a packed MOC (compact delta + varint encoding, 1D MOCs only)
+  Packed {
+    #[structopt(short = "-z", long = "--deflate")]
+    /// Compress the encoded ranges using deflate
+    deflate: bool,
+    /// Path of the output file (stdout if empty)
+    opt_file: Option<PathBuf>,
+  },
+  #[structopt(name = "geojson")]
+  /// Output the S-MOC contours as a GeoJSON MultiPolygon (S-MOCs only)
+  GeoJson {
+    /// Path of the output file (stdout if empty)
+    opt_file: Option<PathBuf>,
+  },
+  #[structopt(name = "ds9")]
+  /// Output the S-MOC contours as DS9 regions (S-MOCs only)
+  Ds9 {
+    /// Path of the output file (stdout if empty)
+    opt_file: Option<PathBuf>,
+  },
+  #[structopt(name = "crtf")]
+  /// Output the S-MOC contours as CASA regions, CRTF (S-MOCs only)
+  Crtf {
+    /// Path of the output file (stdout if empty)
+    opt_file: Option<PathBuf>,
+  },
+  #[structopt(name = "stcs")]
+  /// Output an STC-S string: union of polygons for S-MOCs, TimeInterval for T-MOCs
+  StcS {
+    #[structopt(long = "moc")]
+    /// Output a S-MOC as an STC-S 'MOC' expression instead of a union of polygons
+    moc: bool,
+    /// Path of the output file (stdout if empty)
+    opt_file: Option<PathBuf>,
+  },
+  #[structopt(name = "votable")]
+  /// Output a VOTable embedding the MOC, as an ASCII MOC in a PARAM (xtype 'moc')
+  /// or as a FITS MOC in a base64 encoded STREAM
+  VOTable {
+    #[structopt(long = "--fits")]
+    /// Embed the FITS serialization of the MOC instead of the ASCII one
+    fits: bool,
+    /// Path of the output file (stdout if empty)
+    opt_file: Option<PathBuf>,
+  },
+  #[structopt(name = "mom")]
+  /// Output a FITS multi-order map, UNIQ + PROBDENSITY columns (multi-order map inputs only)
+  Mom {
+    #[structopt(short = "-z", long = "--gzip")]
+    /// Compress the output file using gzip
+    gz: bool,
+    /// Path of the output file
+    file: PathBuf,
+  },
+  #[structopt(name = "skymap")]
+  /// Output a FITS implicit NESTED skymap, PROB column (multi-order map inputs only)
+  Skymap {
+    #[structopt(short = "-d", long = "--depth")]
+    /// Depth of the skymap, at most 13 [default: depth of the input multi-order map]
+    depth: Option<u8>,
+    #[structopt(short = "-z", long = "--gzip")]
+    /// Compress the output file using gzip
+    gz: bool,
+    /// Path of the output file
+    file: PathBuf,
+  },
 }
 
 impl OutputFormat {
+  /// Set the metadata (read from an input FITS MOC) to be preserved in a FITS output.
+  /// The `--moc-id` and `--moc-type` options, if any, take precedence over the metadata.
+  pub fn with_metadata(mut self, input_metadata: MocMetadata) -> Self {
+    if let OutputFormat::Fits { metadata, .. } = &mut self {
+      *metadata = input_metadata;
+    }
+    self
+  }
+
   /// Clone this output format, providing a number to possibly change the name
   pub fn clone_with_number(&self, num: usize) -> Self {
     let mut new = self.clone();
@@ -84,6 +184,29 @@ impl OutputFormat {
         ..
       } => add_number_before_extension(num, path),
       OutputFormat::Fits { file, .. } => add_number_before_extension(num, file),
+      OutputFormat::Mom { file, .. } => add_number_before_extension(num, file),
+      OutputFormat::Skymap { file, .. } => add_number_before_extension(num, file),
+      OutputFormat::Packed {
+        opt_file: Some(path),
+        ..
+      } => add_number_before_extension(num, path),
+      OutputFormat::GeoJson {
+        opt_file: Some(path),
+      } => add_number_before_extension(num, path),
+      OutputFormat::Ds9 {
+        opt_file: Some(path),
+      } => add_number_before_extension(num, path),
+      OutputFormat::Crtf {
+        opt_file: Some(path),
+      } => add_number_before_extension(num, path),
+      OutputFormat::StcS {
+        opt_file: Some(path),
+        ..
+      } => add_number_before_extension(num, path),
+      OutputFormat::VOTable {
+        opt_file: Some(path),
+        ..
+      } => add_number_before_extension(num, path),
       _ => {}
     };
     new
@@ -276,6 +399,7 @@ impl OutputFormat {
       OutputFormat::Json {
         fold,
         opt_file: None,
+        ..
       } => {
         let stdout = io::stdout();
         to_json_aladin(it.cells(), &fold, "", stdout.lock()).map_err(|e| e.into())
@@ -283,6 +407,7 @@ impl OutputFormat {
       OutputFormat::Json {
         fold,
         opt_file: Some(path),
+        ..
       } => {
         let file = File::create(path)?;
         to_json_aladin(it.cells(), &fold, "", BufWriter::new(file)).map_err(|e| e.into())
@@ -292,16 +417,95 @@ impl OutputFormat {
         force_v1: _,
         moc_id,
         moc_type,
+        checksum,
         file,
+        metadata,
       } => {
+        let metadata = fits_metadata(moc_id, moc_type, checksum, metadata);
         // Here I don't know how to convert the generic qty MocQty<T> into MocQty<u64>...
         let file = File::create(file)?;
-        ranges_to_fits_ivoa(it, moc_id, moc_type, BufWriter::new(file)).map_err(|e| e.into())
+        ranges_to_fits_ivoa_with_metadata(it, &metadata, BufWriter::new(file)).map_err(|e| e.into())
       }
       OutputFormat::Stream => {
         let stdout = io::stdout();
         to_ascii_stream(it.cells().cellranges(), true, stdout.lock()).map_err(|e| e.into())
       }
+      OutputFormat::Packed {
+        deflate,
+        opt_file: None,
+      } => {
+        let stdout = io::stdout();
+        to_packed(it, deflate, stdout.lock()).map_err(|e| e.into())
+      }
+      OutputFormat::Packed {
+        deflate,
+        opt_file: Some(path),
+      } => {
+        let file = File::create(path)?;
+        to_packed(it, deflate, BufWriter::new(file)).map_err(|e| e.into())
+      }
+      OutputFormat::GeoJson { opt_file } => {
+        let moc = to_smoc(it, "GeoJSON")?;
+        match opt_file {
+          None => to_geojson(&moc, io::stdout().lock()),
+          Some(path) => to_geojson(&moc, BufWriter::new(File::create(path)?)),
+        }
+        .map_err(|e| e.into())
+      }
+      OutputFormat::Ds9 { opt_file } => {
+        let moc = to_smoc(it, "DS9")?;
+        match opt_file {
+          None => to_ds9(&moc, io::stdout().lock()),
+          Some(path) => to_ds9(&moc, BufWriter::new(File::create(path)?)),
+        }
+        .map_err(|e| e.into())
+      }
+      OutputFormat::Crtf { opt_file } => {
+        let moc = to_smoc(it, "CRTF")?;
+        match opt_file {
+          None => to_crtf(&moc, io::stdout().lock()),
+          Some(path) => to_crtf(&moc, BufWriter::new(File::create(path)?)),
+        }
+        .map_err(|e| e.into())
+      }
+      OutputFormat::StcS { moc, opt_file } => {
+        let stcs = if Q::PREFIX == Time::<T>::PREFIX {
+          // Q is Time<T> since both quantities have the same prefix
+          let tmoc: RangeMOC<T, Time<T>> =
+            RangeMOC::new(it.depth_max(), MocRanges::new_unchecked(it.collect()));
+          tmoc2stcs(&tmoc)?
+        } else {
+          let smoc = to_smoc(it, "STC-S")?;
+          if moc {
+            moc2stcs_moc(&smoc)
+          } else {
+            moc2stcs(&smoc)
+          }
+        };
+        match opt_file {
+          None => writeln!(io::stdout().lock(), "{}", stcs),
+          Some(path) => writeln!(File::create(path)?, "{}", stcs),
+        }
+        .map_err(|e| e.into())
+      }
+      OutputFormat::VOTable { fits, opt_file } => {
+        let mut buf: Vec<u8> = Vec::new();
+        let moc = if fits {
+          ranges_to_fits_ivoa(it, None, None, &mut buf)?;
+          VOTableMoc::Fits(&buf)
+        } else {
+          to_ascii_ivoa(it.cells().cellranges(), &None, false, &mut buf)?;
+          VOTableMoc::Ascii(std::str::from_utf8(&buf)?)
+        };
+        write_votable(&format!("{}moc", Q::PREFIX), moc, opt_file)
+      }
+      OutputFormat::Mom { .. } | OutputFormat::Skymap { .. } => Err(
+        format!(
+          "Multi-order map outputs (mom, skymap) not available for {}-MOCs.",
+          Q::PREFIX.to_uppercase()
+        )
+        .into(),
+      ),
     }
   }
 
@@ -324,11 +528,14 @@ impl OutputFormat {
         force_v1: _,
         moc_id,
         moc_type,
+        checksum,
         file,
+        metadata,
       } => {
+        let metadata = fits_metadata(moc_id, moc_type, checksum, metadata);
         // Here I don't know how to convert the generic qty MocQty<T> into MocQty<u64>...
         let file = File::create(file)?;
-        it.hpx_cells_to_fits_ivoa(moc_id, moc_type, BufWriter::new(file))
+        hpx_cells_to_fits_ivoa_with_metadata(it, &metadata, BufWriter::new(file))
           .map_err(|e| e.into())
       }
       _ => unreachable!(),
@@ -361,6 +568,7 @@ impl OutputFormat {
       OutputFormat::Json {
         fold,
         opt_file: None,
+        ..
       } => {
         let stdout = io::stdout();
         to_json_aladin(it, &fold, "", stdout.lock()).map_err(|e| e.into())
@@ -368,6 +576,7 @@ impl OutputFormat {
       OutputFormat::Json {
         fold,
         opt_file: Some(path),
+        ..
       } => {
         let file = File::create(path)?;
         to_json_aladin(it, &fold, "", BufWriter::new(file)).map_err(|e| e.into())
@@ -377,17 +586,41 @@ impl OutputFormat {
         force_v1: _,
         moc_id,
         moc_type,
+        checksum,
         file,
+        metadata,
       } => {
+        let metadata = fits_metadata(moc_id, moc_type, checksum, metadata);
         // Here I don't know how to convert the generic qty MocQty<T> into MocQty<u64>...
         let file = File::create(file)?;
-        ranges_to_fits_ivoa(it.ranges(), moc_id, moc_type, BufWriter::new(file))
+        ranges_to_fits_ivoa_with_metadata(it.ranges(), &metadata, BufWriter::new(file))
           .map_err(|e| e.into())
       }
       OutputFormat::Stream => {
         let stdout = io::stdout();
         to_ascii_stream(it.cellranges(), true, stdout.lock()).map_err(|e| e.into())
       }
+      OutputFormat::Packed {
+        deflate,
+        opt_file: None,
+      } => {
+        let stdout = io::stdout();
+        to_packed(it.ranges(), deflate, stdout.lock()).map_err(|e| e.into())
+      }
+      OutputFormat::Packed {
+        deflate,
+        opt_file: Some(path),
+      } => {
+        let file = File::create(path)?;
+        to_packed(it.ranges(), deflate, BufWriter::new(file)).map_err(|e| e.into())
+      }
+      OutputFormat::GeoJson { .. }
+      | OutputFormat::Ds9 { .. }
+      | OutputFormat::Crtf { .. }
+      | OutputFormat::StcS { .. }
+      | OutputFormat::VOTable { .. }
+      | OutputFormat::Mom { .. }
+      | OutputFormat::Skymap { .. } => self.write_moc(it.ranges()),
     }
   }
 
@@ -399,71 +632,7 @@ impl OutputFormat {
     K: RangeMOC2ElemIt<T, I::Qty, T, J::Qty, It1 = I, It2 = J>,
     L: RangeMOC2Iterator<T, I::Qty, I, T, J::Qty, J, K>,
   {
-    // In case of ascii or json inputs, we perform useless conversions:
-    //            cell -> range -> cell
-    //   cellcellrange -> range -> cellcellrange
-    // We could make 2 other `write_stmoc` methods (taking different iterators) to avoid this
-    match self {
-      OutputFormat::Ascii {
-        fold,
-        range_len,
-        opt_file: None,
-      } => {
-        let stdout = io::stdout();
-        moc2d_to_ascii_ivoa(
-          stmoc.into_cellcellrange_moc2_iter(),
-          &fold,
-          range_len,
-          stdout.lock(),
-        )
-        .map_err(|e| e.into())
-      }
-      OutputFormat::Ascii {
-        fold,
-        range_len,
-        opt_file: Some(path),
-      } => {
-        let file = File::create(path)?;
-        moc2d_to_ascii_ivoa(
-          stmoc.into_cellcellrange_moc2_iter(),
-          &fold,
-          range_len,
-          BufWriter::new(file),
-        )
-        .map_err(|e| e.into())
-      }
-      OutputFormat::Json {
-        fold,
-        opt_file: None,
-      } => {
-        let stdout = io::stdout();
-        cellmoc2d_to_json_aladin(stmoc.into_cell_moc2_iter(), &fold, stdout.lock())
-          .map_err(|e| e.into())
-      }
-      OutputFormat::Json {
-        fold,
-        opt_file: Some(path),
-      } => {
-        let file = File::create(path)?;
-        cellmoc2d_to_json_aladin(stmoc.into_cell_moc2_iter(), &fold, BufWriter::new(file))
-          .map_err(|e| e.into())
-      }
-      OutputFormat::Fits {
-        force_u64: _,
-        force_v1: _,
-        moc_id,
-        moc_type,
-        file,
-      } => {
-        // TODO handle the forced to u64??
-        let file = File::create(file)?;
-        ranges_st_to_fits_ivoa(stmoc, moc_id, moc_type, BufWriter::new(file)).map_err(|e| e.into())
-      }
-      OutputFormat::Stream => {
-        // let stdout = io::stdout();
-        Err(String::from("No stream format for ST-MOCs yet.").into())
-      }
-    }
+    self.write_moc2d(stmoc)
   }
 
   pub fn write_sfmoc<T, I, J, K, L>(self, sfmoc: L) -> Result<(), Box<dyn Error>>
@@ -473,11 +642,34 @@ impl OutputFormat {
     J: RangeMOCIterator<T, Qty = Hpx<T>>,
     K: RangeMOC2ElemIt<T, I::Qty, T, J::Qty, It1 = I, It2 = J>,
     L: RangeMOC2Iterator<T, I::Qty, I, T, J::Qty, J, K>,
+  {
+    self.write_moc2d(sfmoc)
+  }
+
+  pub fn write_tfmoc<T, I, J, K, L>(self, tfmoc: L) -> Result<(), Box<dyn Error>>
+  where
+    T: Idx,
+    I: RangeMOCIterator<T, Qty = Time<T>>,
+    J: RangeMOCIterator<T, Qty = Frequency<T>>,
+    K: RangeMOC2ElemIt<T, I::Qty, T, J::Qty, It1 = I, It2 = J>,
+    L: RangeMOC2Iterator<T, I::Qty, I, T, J::Qty, J, K>,
+  {
+    self.write_moc2d(tfmoc)
+  }
+
+  /// Write any 2D-MOC (ST-MOC, SF-MOC, TF-MOC, ...).
+  pub fn write_moc2d<T, I, J, K, L>(self, moc2: L) -> Result<(), Box<dyn Error>>
+  where
+    T: Idx,
+    I: RangeMOCIterator<T>,
+    J: RangeMOCIterator<T>,
+    K: RangeMOC2ElemIt<T, I::Qty, T, J::Qty, It1 = I, It2 = J>,
+    L: RangeMOC2Iterator<T, I::Qty, I, T, J::Qty, J, K>,
   {
     // In case of ascii or json inputs, we perform useless conversions:
     //            cell -> range -> cell
     //   cellcellrange -> range -> cellcellrange
-    // We could make 2 other `write_stmoc` methods (taking different iterators) to avoid this
+    // We could make 2 other `write_moc2d` methods (taking different iterators) to avoid this
     match self {
       OutputFormat::Ascii {
         fold,
@@ -486,7 +678,7 @@ impl OutputFormat {
       } => {
         let stdout = io::stdout();
         moc2d_to_ascii_ivoa(
-          sfmoc.into_cellcellrange_moc2_iter(),
+          moc2.into_cellcellrange_moc2_iter(),
           &fold,
           range_len,
           stdout.lock(),
@@ -500,7 +692,7 @@ impl OutputFormat {
       } => {
         let file = File::create(path)?;
         moc2d_to_ascii_ivoa(
-          sfmoc.into_cellcellrange_moc2_iter(),
+          moc2.into_cellcellrange_moc2_iter(),
           &fold,
           range_len,
           BufWriter::new(file),
@@ -509,39 +701,195 @@ impl OutputFormat {
       }
       OutputFormat::Json {
         fold,
+        ranges,
         opt_file: None,
       } => {
         let stdout = io::stdout();
-        cellmoc2d_to_json_aladin(sfmoc.into_cell_moc2_iter(), &fold, stdout.lock())
-          .map_err(|e| e.into())
+        if ranges {
+          rangemoc2d_to_json_aladin(moc2, &fold, stdout.lock()).map_err(|e| e.into())
+        } else {
+          cellmoc2d_to_json_aladin(moc2.into_cell_moc2_iter(), &fold, stdout.lock())
+            .map_err(|e| e.into())
+        }
       }
       OutputFormat::Json {
         fold,
+        ranges,
         opt_file: Some(path),
       } => {
         let file = File::create(path)?;
-        cellmoc2d_to_json_aladin(sfmoc.into_cell_moc2_iter(), &fold, BufWriter::new(file))
-          .map_err(|e| e.into())
+        if ranges {
+          rangemoc2d_to_json_aladin(moc2, &fold, BufWriter::new(file)).map_err(|e| e.into())
+        } else {
+          cellmoc2d_to_json_aladin(moc2.into_cell_moc2_iter(), &fold, BufWriter::new(file))
+            .map_err(|e| e.into())
+        }
       }
       OutputFormat::Fits {
         force_u64: _,
         force_v1: _,
         moc_id,
         moc_type,
+        checksum,
         file,
+        metadata,
       } => {
+        let metadata = fits_metadata(moc_id, moc_type, checksum, metadata);
         // TODO handle the forced to u64??
         let file = File::create(file)?;
-        ranges_sf_to_fits_ivoa(sfmoc, moc_id, moc_type, BufWriter::new(file)).map_err(|e| e.into())
+        ranges2d_to_fits_ivoa_with_metadata(moc2, &metadata, BufWriter::new(file))
+          .map_err(|e| e.into())
+      }
+      OutputFormat::VOTable { fits, opt_file } => {
+        let mut buf: Vec<u8> = Vec::new();
+        let moc = if fits {
+          ranges2d_to_fits_ivoa(moc2, None, None, &mut buf)?;
+          VOTableMoc::Fits(&buf)
+        } else {
+          moc2d_to_ascii_ivoa(moc2.into_cellcellrange_moc2_iter(), &None, false, &mut buf)?;
+          VOTableMoc::Ascii(std::str::from_utf8(&buf)?)
+        };
+        // Same names as the MOC types of the CLI: 'stmoc', 'sfmoc' and 'tfmoc'
+        let name = if J::Qty::PREFIX == Hpx::<T>::PREFIX {
+          format!("s{}moc", I::Qty::PREFIX)
+        } else {
+          format!("{}{}moc", I::Qty::PREFIX, J::Qty::PREFIX)
+        };
+        write_votable(&name, moc, opt_file)
       }
       OutputFormat::Stream => {
         // let stdout = io::stdout();
-        Err(String::from("No stream format for SF-MOCs yet.").into())
+        Err(
+          format!(
+            "No stream format for {}{}-MOCs yet.",
+            I::Qty::PREFIX.to_uppercase(),
+            J::Qty::PREFIX.to_uppercase()
+          )
+          .into(),
+        )
+      }
+      OutputFormat::GeoJson { .. }
+      | OutputFormat::Ds9 { .. }
+      | OutputFormat::Crtf { .. }
+      | OutputFormat::StcS { .. } => Err(
+        format!(
+          "No polygon based format (GeoJSON, DS9, CRTF, STC-S) for {}{}-MOCs.",
+          I::Qty::PREFIX.to_uppercase(),
+          J::Qty::PREFIX.to_uppercase()
+        )
+        .into(),
+      ),
+      OutputFormat::Packed { .. } => Err(
+        format!(
+          "No packed format for {}{}-MOCs yet.",
+          I::Qty::PREFIX.to_uppercase(),
+          J::Qty::PREFIX.to_uppercase()
+        )
+        .into(),
+      ),
+      OutputFormat::Mom { .. } | OutputFormat::Skymap { .. } => Err(
+        format!(
+          "Multi-order map outputs (mom, skymap) not available for {}{}-MOCs.",
+          I::Qty::PREFIX.to_uppercase(),
+          J::Qty::PREFIX.to_uppercase()
+        )
+        .into(),
+      ),
+    }
+  }
+
+  /// Write a multi-order map, i.e. a list of `(uniq, value)` pairs in which values are
+  /// proportional to the cells area, in the given coordinate system.
+  pub fn write_mom(
+    self,
+    depth_max: u8,
+    coordsys: CoordSys,
+    mom: Vec<(u64, f64)>,
+  ) -> Result<(), Box<dyn Error>> {
+    let mom_it = HpxMomIter::<u64, Hpx<u64>, f64, _>::new(mom.into_iter());
+    match self {
+      OutputFormat::Mom { gz, file } => {
+        let file = File::create(file)?;
+        hpxmom_to_fits_multiordermap(depth_max, coordsys, mom_it, gz, BufWriter::new(file))
+          .map_err(|e| e.into())
       }
+      OutputFormat::Skymap { depth, gz, file } => {
+        let depth = depth.unwrap_or(depth_max);
+        if depth > SKYMAP_DEPTH_MAX {
+          return Err(
+            format!(
+              "Skymap depth {} larger than the maximum depth {}.",
+              depth, SKYMAP_DEPTH_MAX
+            )
+            .into(),
+          );
+        }
+        let file = File::create(file)?;
+        hpxmom_to_fits_skymap(depth, coordsys, mom_it, gz, BufWriter::new(file))
+          .map_err(|e| e.into())
+      }
+      _ => Err(
+        String::from("Only 'mom' and 'skymap' outputs are available for multi-order maps.").into(),
+      ),
     }
   }
 }
 
+/// Returns the metadata to be written in a FITS MOC header, the `--moc-id` and `--moc-type`
+/// options overriding the values possibly coming from the input MOC.
+fn fits_metadata(
+  moc_id: Option<String>,
+  moc_type: Option<fits::keywords::MocType>,
+  checksum: bool,
+  mut metadata: MocMetadata,
+) -> MocMetadata {
+  metadata.checksum |= checksum;
+  if moc_id.is_some() {
+    metadata.moc_id = moc_id;
+  }
+  if moc_type.is_some() {
+    metadata.moc_type = moc_type;
+  }
+  metadata
+}
+
+/// Write the given MOC serialization in a VOTable, the name being the MOC type (e.g. `smoc`).
+fn write_votable(
+  name: &str,
+  moc: VOTableMoc,
+  opt_file: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+  match opt_file {
+    None => moc_to_votable(name, moc, io::stdout().lock()),
+    Some(path) => moc_to_votable(name, moc, BufWriter::new(File::create(path)?)),
+  }
+  .map_err(|e| e.into())
+}
+
+/// Collect the given S-MOC iterator into a S-MOC, returning an error if the MOC is not a S-MOC.
+fn to_smoc<T, Q, R>(it: R, format_name: &str) -> Result<RangeMOC<T, Hpx<T>>, Box<dyn Error>>
+where
+  T: Idx,
+  Q: MocQty<T>,
+  R: RangeMOCIterator<T, Qty = Q>,
+{
+  if Q::PREFIX != Hpx::<T>::PREFIX {
+    return Err(
+      format!(
+        "{} output not available for {}-MOCs.",
+        format_name,
+        Q::PREFIX.to_uppercase()
+      )
+      .into(),
+    );
+  }
+  // Q is Hpx<T> since both quantities have the same prefix
+  Ok(RangeMOC::new(
+    it.depth_max(),
+    MocRanges::new_unchecked(it.collect()),
+  ))
+}
+
 fn add_number_before_extension(num: usize, path: &mut PathBuf) {
   match path.extension().and_then(|s| s.to_str()).map(String::from) {
     Some(ext) => path.set_extension(format!("{}.{}", num, ext)),
@@ -0,0 +1,42 @@
+use std::{
+  error::Error,
+  fs::File,
+  io::{BufReader, BufWriter},
+  path::PathBuf,
+};
+
+use structopt::StructOpt;
+
+use moclib::deser::fits::from_fits_ivoa_repair;
+
+/// Loads a possibly malformed FITS MOC (unsorted, overlapping or redundant cells, cells deeper
+/// than `MOCORDER`, ...), prints what has been fixed and (optionally) writes the repaired MOC.
+#[derive(StructOpt, Debug)]
+pub struct Repair {
+  #[structopt(parse(from_os_str))]
+  /// Path of the FITS file containing a MOC
+  input: PathBuf,
+  #[structopt(parse(from_os_str))]
+  /// Path of the repaired FITS MOC (only the report is printed if empty)
+  output: Option<PathBuf>,
+  #[structopt(short = "-p", long = "--coosys-permissive")]
+  /// Do not fail if the S-MOC COORDSYS is not ICRS
+  coosys_permissive: bool,
+}
+
+impl Repair {
+  pub fn exec(self) -> Result<(), Box<dyn Error>> {
+    let reader = BufReader::new(File::open(&self.input)?);
+    let (moc, metadata, report) = from_fits_ivoa_repair(reader, self.coosys_permissive)?;
+    println!("{}", report);
+    match self.output {
+      Some(path) => {
+        let writer = BufWriter::new(File::create(path)?);
+        moc
+          .to_fits_ivoa_with_metadata(&metadata, writer)
+          .map_err(|e| e.into())
+      }
+      None => Ok(()),
+    }
+  }
+}
@@ -0,0 +1,36 @@
+use std::{error::Error, fs, path::PathBuf};
+
+use structopt::StructOpt;
+
+use moclib::deser::fits::validate::{validate_fits_ivoa, Severity};
+
+#[derive(StructOpt, Debug)]
+pub struct Validate {
+  #[structopt(parse(from_os_str))]
+  /// Path of the FITS file containing a MOC
+  file: PathBuf,
+  #[structopt(short = "-s", long = "--strict")]
+  /// Also fail if warnings are found (e.g. no CHECKSUM or non-merged ranges)
+  strict: bool,
+}
+
+impl Validate {
+  pub fn exec(self) -> Result<(), Box<dyn Error>> {
+    let bytes = fs::read(&self.file)?;
+    let issues = validate_fits_ivoa(&bytes);
+    for issue in &issues {
+      println!("{}", issue);
+    }
+    let n_errors = issues
+      .iter()
+      .filter(|issue| issue.severity == Severity::Error)
+      .count();
+    let n_warnings = issues.len() - n_errors;
+    if n_errors > 0 || (self.strict && n_warnings > 0) {
+      Err(format!("{} error(s) and {} warning(s) found.", n_errors, n_warnings).into())
+    } else {
+      println!("Valid MOC ({} warning(s)).", n_warnings);
+      Ok(())
+    }
+  }
+}
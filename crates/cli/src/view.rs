@@ -23,6 +23,8 @@ use moclib::{
     fits::{from_fits_ivoa, MocIdxType, MocQtyType, MocType},
     img::{to_png_file, to_png_file_auto},
     json::from_json_aladin,
+    packed::from_packed,
+    votable::{VOTable, VOTableMoc},
   },
   moc::{
     range::RangeMOC, CellMOCIntoIterator, CellMOCIterator, CellOrCellRangeMOCIntoIterator,
@@ -99,7 +101,7 @@ pub struct View {
   /// Path of the input MOC file (or stdin if equals "-")
   input: PathBuf,
   #[structopt(short = "f", long = "format")]
-  /// Format of the input MOC ('ascii', 'json', 'fits' or 'stream') [default: guess from the file extension]
+  /// Format of the input MOC ('ascii', 'json', 'fits', 'stream', 'packed' or 'votable') [default: guess from the file extension]
   input_fmt: Option<InputFormat>,
 
   #[structopt(parse(from_os_str))]
@@ -159,75 +161,14 @@ impl View {
 }
 
 pub(crate) fn exec<R: BufRead>(
-  mut input: R,
+  input: R,
   input_fmt: InputFormat,
   output: PathBuf,
   galactic: bool,
   mode: Mode,
   view: bool,
 ) -> Result<(), Box<dyn Error>> {
-  let smoc = match input_fmt {
-    // SMOC
-    InputFormat::Ascii => {
-      let mut input_str = Default::default();
-      input.read_to_string(&mut input_str)?;
-      let cellcellranges = from_ascii_ivoa::<u64, Hpx<u64>>(&input_str)?;
-      Ok::<RangeMOC<u64, Hpx<u64>>, String>(
-        cellcellranges
-          .into_cellcellrange_moc_iter()
-          .ranges()
-          .into_range_moc(),
-      )
-    }
-    InputFormat::Json => {
-      let mut input_str = String::new();
-      input.read_to_string(&mut input_str)?;
-      let cells = from_json_aladin::<u64, Hpx<u64>>(&input_str)?;
-      Ok(cells.into_cell_moc_iter().ranges().into_range_moc())
-    }
-    InputFormat::Stream => {
-      let cellrange_it = from_ascii_stream::<u64, Hpx<u64>, _>(input)?;
-      Ok(cellrange_it.ranges().into_range_moc())
-    }
-    InputFormat::Fits => {
-      let fits_res = from_fits_ivoa(input)?;
-      match fits_res {
-        MocIdxType::U16(moc) => match moc {
-          MocQtyType::Hpx(moc) => match moc {
-            MocType::Ranges(moc) => Ok(moc.convert::<u64, Hpx<u64>>().into_range_moc()),
-            MocType::Cells(moc) => Ok(
-              moc
-                .into_cell_moc_iter()
-                .ranges()
-                .convert::<u64, Hpx<u64>>()
-                .into_range_moc(),
-            ),
-          },
-          _ => Err(String::from("Input MOC type must be SMOC.")),
-        },
-        MocIdxType::U32(moc) => match moc {
-          MocQtyType::Hpx(moc) => match moc {
-            MocType::Ranges(moc) => Ok(moc.convert::<u64, Hpx<u64>>().into_range_moc()),
-            MocType::Cells(moc) => Ok(
-              moc
-                .into_cell_moc_iter()
-                .ranges()
-                .convert::<u64, Hpx<u64>>()
-                .into_range_moc(),
-            ),
-          },
-          _ => Err(String::from("Input MOC type must be SMOC.")),
-        },
-        MocIdxType::U64(moc) => match moc {
-          MocQtyType::Hpx(moc) => match moc {
-            MocType::Ranges(moc) => Ok(moc.into_range_moc()),
-            MocType::Cells(moc) => Ok(moc.into_cell_moc_iter().ranges().into_range_moc()),
-          },
-          _ => Err(String::from("Input MOC type must be SMOC.")),
-        },
-      }
-    }
-  }?;
+  let smoc = read_smoc(input, input_fmt)?;
   let pos_conv = if galactic {
     Some(PosConversion::EqMap2GalImg)
   } else {
@@ -451,3 +392,85 @@ pub(crate) fn exec<R: BufRead>(
     }
   }
 }
+
+/// Reads an S-MOC from the given input, in the given format.
+pub(crate) fn read_smoc<R: BufRead>(
+  mut input: R,
+  input_fmt: InputFormat,
+) -> Result<RangeMOC<u64, Hpx<u64>>, Box<dyn Error>> {
+  match input_fmt {
+    // SMOC
+    InputFormat::Ascii => {
+      let mut input_str = Default::default();
+      input.read_to_string(&mut input_str)?;
+      let cellcellranges = from_ascii_ivoa::<u64, Hpx<u64>>(&input_str)?;
+      Ok::<RangeMOC<u64, Hpx<u64>>, String>(
+        cellcellranges
+          .into_cellcellrange_moc_iter()
+          .ranges()
+          .into_range_moc(),
+      )
+    }
+    InputFormat::Json => {
+      let mut input_str = String::new();
+      input.read_to_string(&mut input_str)?;
+      let cells = from_json_aladin::<u64, Hpx<u64>>(&input_str)?;
+      Ok(cells.into_cell_moc_iter().ranges().into_range_moc())
+    }
+    InputFormat::Stream => {
+      let cellrange_it = from_ascii_stream::<u64, Hpx<u64>, _>(input)?;
+      Ok(cellrange_it.ranges().into_range_moc())
+    }
+    InputFormat::Packed => Ok(from_packed::<u64, Hpx<u64>, _>(input)?.try_into_range_moc()?),
+    InputFormat::VOTable => {
+      let votable = VOTable::from_reader(input)?;
+      return match votable.moc() {
+        Some(VOTableMoc::Ascii(ascii)) => read_smoc(ascii.as_bytes(), InputFormat::Ascii),
+        Some(VOTableMoc::Fits(fits)) => read_smoc(fits, InputFormat::Fits),
+        None => Err(
+          String::from("No MOC found in the VOTable (no 'moc' xtype PARAM, no FITS STREAM).")
+            .into(),
+        ),
+      };
+    }
+    InputFormat::Fits => {
+      let fits_res = from_fits_ivoa(input)?;
+      match fits_res {
+        MocIdxType::U16(moc) => match moc {
+          MocQtyType::Hpx(moc) => match moc {
+            MocType::Ranges(moc) => Ok(moc.convert::<u64, Hpx<u64>>().into_range_moc()),
+            MocType::Cells(moc) => Ok(
+              moc
+                .into_cell_moc_iter()
+                .ranges()
+                .convert::<u64, Hpx<u64>>()
+                .into_range_moc(),
+            ),
+          },
+          _ => Err(String::from("Input MOC type must be SMOC.")),
+        },
+        MocIdxType::U32(moc) => match moc {
+          MocQtyType::Hpx(moc) => match moc {
+            MocType::Ranges(moc) => Ok(moc.convert::<u64, Hpx<u64>>().into_range_moc()),
+            MocType::Cells(moc) => Ok(
+              moc
+                .into_cell_moc_iter()
+                .ranges()
+                .convert::<u64, Hpx<u64>>()
+                .into_range_moc(),
+            ),
+          },
+          _ => Err(String::from("Input MOC type must be SMOC.")),
+        },
+        MocIdxType::U64(moc) => match moc {
+          MocQtyType::Hpx(moc) => match moc {
+            MocType::Ranges(moc) => Ok(moc.into_range_moc()),
+            MocType::Cells(moc) => Ok(moc.into_cell_moc_iter().ranges().into_range_moc()),
+          },
+          _ => Err(String::from("Input MOC type must be SMOC.")),
+        },
+      }
+    }
+  }
+  .map_err(|e| e.into())
+}
@@ -0,0 +1,663 @@
+//! Apache Arrow (and Parquet) serialization of MOCs and of multi-order maps, available with the
+//! `arrow` feature.
+//!
+//! # Layouts
+//! * 1D MOCs are stored either as a single `uniq` column (S-MOC only, HEALPix `NUNIQ` cells) or
+//!   as a pair of `start` and `end` columns (one range per row, at the MOC depth max);
+//! * 2D MOCs (e.g. ST-MOCs) are stored as 4 lists columns, one row per 2D-MOC element:
+//!   `t_start`, `t_end`, `s_start` and `s_end` for a ST-MOC (the prefix of each quantity
+//!   being used, i.e. `f` for frequencies);
+//! * multi-order maps are stored as a `uniq` column followed by `f64` value columns (see
+//!   [mom_to_arrow]).
+//!
+//! All indices are stored as `UInt64`.
+//! As in FITS files, the MOC dimension (`MOCDIM`) and depth (`MOCORD_S`, `MOCORD_T` or
+//! `MOCORD_F`, `MOCORDER` for multi-order maps) are stored in the schema metadata.
+//! Units of multi-order map value columns are stored in the `unit` metadata of their field.
+
+use std::{collections::HashMap, io::Write, ops::Range, sync::Arc};
+
+use arrow_array::{
+  types::UInt64Type, Array, ArrayRef, Float32Array, Float64Array, ListArray, RecordBatch,
+  UInt64Array,
+};
+use arrow_schema::{ArrowError as ArrowLibError, DataType, Field, Schema};
+use parquet::{
+  arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ArrowWriter},
+  errors::ParquetError,
+  file::reader::ChunkReader,
+};
+use quick_error::quick_error;
+
+use crate::{
+  deser::{
+    bin::check_ranges,
+    fits::multiordermap::{MultiOrderMapColumns, ValueColumn},
+  },
+  elemset::range::MocRanges,
+  moc::{range::RangeMOC, CellMOCIterator, RangeMOCIterator},
+  moc2d::{
+    range::{RangeMOC2, RangeMOC2Elem},
+    HasTwoMaxDepth,
+  },
+  mom::HpxMOMIterator,
+  qty::{Hpx, MocQty},
+};
+
+/// Schema metadata key of the MOC dimension (`SPACE`, `TIME`, `TIME.SPACE`, ...).
+pub const MOCDIM: &str = "MOCDIM";
+/// Schema metadata key of the depth of a multi-order map.
+pub const MOCORDER: &str = "MOCORDER";
+/// Field metadata key of the unit of a multi-order map value column.
+pub const UNIT: &str = "unit";
+
+/// Name of the column containing HEALPix `NUNIQ` indices.
+pub const UNIQ_COLUMN: &str = "uniq";
+/// Name of the column containing the (inclusive) lower bound of 1D MOC ranges.
+pub const START_COLUMN: &str = "start";
+/// Name of the column containing the (exclusive) upper bound of 1D MOC ranges.
+pub const END_COLUMN: &str = "end";
+
+quick_error! {
+  #[derive(Debug)]
+  pub enum ArrowError {
+    /// Error from the Arrow library
+    Arrow(err: ArrowLibError) {
+      from()
+      display("Arrow error: {}", err)
+    }
+    /// Error from the Parquet library
+    Parquet(err: ParquetError) {
+      from()
+      display("Parquet error: {}", err)
+    }
+    MissingColumn(name: String) {
+      display("Missing column '{}'", name)
+    }
+    WrongColumnType(name: String, expected: String, actual: String) {
+      display("Wrong type for column '{}'. Expected: {}. Actual: {}", name, expected, actual)
+    }
+    NullValues(name: String) {
+      display("Column '{}' contains null values", name)
+    }
+    MissingMetadata(key: &'static str) {
+      display("Missing schema metadata '{}'", key)
+    }
+    WrongMetadata(key: String, expected: String, actual: String) {
+      display("Wrong schema metadata '{}'. Expected: {}. Actual: {}", key, expected, actual)
+    }
+    InvalidData(msg: String) {
+      display("Invalid data: {}", msg)
+    }
+  }
+}
+
+/// Returns the `MOCDIM` value of the quantity of given prefix.
+fn mocdim(prefix: char) -> &'static str {
+  match prefix {
+    's' => "SPACE",
+    't' => "TIME",
+    'f' => "FREQUENCY",
+    _ => unreachable!(),
+  }
+}
+
+/// Returns the schema metadata key of the depth of the quantity of given prefix, i.e.
+/// `MOCORD_S`, `MOCORD_T` or `MOCORD_F`.
+fn mocord(prefix: char) -> String {
+  format!("MOCORD_{}", prefix.to_ascii_uppercase())
+}
+
+/// Write the given range MOC as an Arrow table made of a `start` and an `end` columns.
+pub fn ranges_to_arrow<Q, I>(moc_it: I) -> Result<RecordBatch, ArrowError>
+where
+  Q: MocQty<u64>,
+  I: RangeMOCIterator<u64, Qty = Q>,
+{
+  let depth_max = moc_it.depth_max();
+  let (starts, ends): (Vec<u64>, Vec<u64>) = moc_it.map(|range| (range.start, range.end)).unzip();
+  let schema = Schema::new_with_metadata(
+    vec![
+      Field::new(START_COLUMN, DataType::UInt64, false),
+      Field::new(END_COLUMN, DataType::UInt64, false),
+    ],
+    moc_metadata::<Q>(depth_max),
+  );
+  RecordBatch::try_new(
+    Arc::new(schema),
+    vec![
+      Arc::new(UInt64Array::from(starts)),
+      Arc::new(UInt64Array::from(ends)),
+    ],
+  )
+  .map_err(ArrowError::Arrow)
+}
+
+/// Write the given S-MOC as an Arrow table made of a single `uniq` column containing the
+/// HEALPix `NUNIQ` index of each cell.
+pub fn hpx_cells_to_arrow<I>(moc_it: I) -> Result<RecordBatch, ArrowError>
+where
+  I: CellMOCIterator<u64, Qty = Hpx<u64>>,
+{
+  let depth_max = moc_it.depth_max();
+  let uniqs: Vec<u64> = moc_it.map(|cell| cell.uniq_hpx()).collect();
+  let schema = Schema::new_with_metadata(
+    vec![Field::new(UNIQ_COLUMN, DataType::UInt64, false)],
+    moc_metadata::<Hpx<u64>>(depth_max),
+  );
+  RecordBatch::try_new(Arc::new(schema), vec![Arc::new(UInt64Array::from(uniqs))])
+    .map_err(ArrowError::Arrow)
+}
+
+fn moc_metadata<Q: MocQty<u64>>(depth_max: u8) -> HashMap<String, String> {
+  HashMap::from([
+    (String::from(MOCDIM), String::from(mocdim(Q::PREFIX))),
+    (mocord(Q::PREFIX), depth_max.to_string()),
+  ])
+}
+
+/// Read a 1D MOC from an Arrow table written by [ranges_to_arrow] or by [hpx_cells_to_arrow].
+/// The MOC dimension stored in the schema metadata must match the given quantity.
+pub fn range_moc_from_arrow<Q: MocQty<u64>>(
+  batch: &RecordBatch,
+) -> Result<RangeMOC<u64, Q>, ArrowError> {
+  check_mocdim(batch, mocdim(Q::PREFIX))?;
+  let depth_max = get_depth::<Q>(batch, &mocord(Q::PREFIX))?;
+  if batch.column_by_name(UNIQ_COLUMN).is_some() && Q::PREFIX == 's' {
+    let uniqs = get_u64_column(batch, UNIQ_COLUMN)?;
+    let mut ranges: Vec<Range<u64>> = Vec::with_capacity(uniqs.len());
+    for uniq in uniqs.values().iter() {
+      let (depth, idx) = Hpx::<u64>::from_uniq_hpx(*uniq);
+      if *uniq < 4 || depth > depth_max {
+        return Err(ArrowError::InvalidData(format!(
+          "NUNIQ {} not valid for a MOC of depth {}",
+          uniq, depth_max
+        )));
+      }
+      let shift = Q::shift_from_depth_max(depth) as u32;
+      ranges.push(idx << shift..(idx + 1) << shift);
+    }
+    Ok(RangeMOC::new(depth_max, MocRanges::new_from(ranges)))
+  } else {
+    let starts = get_u64_column(batch, START_COLUMN)?;
+    let ends = get_u64_column(batch, END_COLUMN)?;
+    let ranges: Vec<Range<u64>> = starts
+      .values()
+      .iter()
+      .zip(ends.values().iter())
+      .map(|(start, end)| *start..*end)
+      .collect();
+    check_ranges::<u64, Q>(&ranges, depth_max)
+      .map_err(|e| ArrowError::InvalidData(e.to_string()))?;
+    Ok(RangeMOC::new(depth_max, MocRanges::new_unchecked(ranges)))
+  }
+}
+
+/// Write the given 2D-MOC as an Arrow table made of 4 lists columns, one row per element of
+/// the 2D-MOC: the starts and the ends of the ranges of the first quantity, then the starts and
+/// the ends of the ranges of the second quantity (e.g. `t_start`, `t_end`, `s_start` and `s_end`
+/// for a ST-MOC).
+pub fn ranges2d_to_arrow<Q, R>(moc2: &RangeMOC2<u64, Q, u64, R>) -> Result<RecordBatch, ArrowError>
+where
+  Q: MocQty<u64>,
+  R: MocQty<u64>,
+{
+  let (l_starts, l_ends) = list_columns(moc2.elems().iter().map(|elem| elem.moc_l()));
+  let (r_starts, r_ends) = list_columns(moc2.elems().iter().map(|elem| elem.moc_r()));
+  let columns: Vec<ArrayRef> = vec![
+    Arc::new(l_starts),
+    Arc::new(l_ends),
+    Arc::new(r_starts),
+    Arc::new(r_ends),
+  ];
+  let names = list_column_names::<Q, R>();
+  let fields: Vec<Field> = names
+    .iter()
+    .zip(columns.iter())
+    .map(|(name, col)| Field::new(name, col.data_type().clone(), false))
+    .collect();
+  let metadata = HashMap::from([
+    (
+      String::from(MOCDIM),
+      format!("{}.{}", mocdim(Q::PREFIX), mocdim(R::PREFIX)),
+    ),
+    (mocord(Q::PREFIX), moc2.depth_max_1().to_string()),
+    (mocord(R::PREFIX), moc2.depth_max_2().to_string()),
+  ]);
+  RecordBatch::try_new(
+    Arc::new(Schema::new_with_metadata(fields, metadata)),
+    columns,
+  )
+  .map_err(ArrowError::Arrow)
+}
+
+fn list_column_names<Q: MocQty<u64>, R: MocQty<u64>>() -> [String; 4] {
+  [
+    format!("{}_{}", Q::PREFIX, START_COLUMN),
+    format!("{}_{}", Q::PREFIX, END_COLUMN),
+    format!("{}_{}", R::PREFIX, START_COLUMN),
+    format!("{}_{}", R::PREFIX, END_COLUMN),
+  ]
+}
+
+/// Returns the list arrays of the range starts and of the range ends of the given MOCs.
+fn list_columns<'a, Q, I>(mocs: I) -> (ListArray, ListArray)
+where
+  Q: MocQty<u64>,
+  I: Iterator<Item = &'a RangeMOC<u64, Q>> + Clone,
+{
+  let starts = ListArray::from_iter_primitive::<UInt64Type, _, _>(mocs.clone().map(|moc| {
+    Some(
+      moc
+        .moc_ranges()
+        .iter()
+        .map(|range| Some(range.start))
+        .collect::<Vec<_>>(),
+    )
+  }));
+  let ends = ListArray::from_iter_primitive::<UInt64Type, _, _>(mocs.map(|moc| {
+    Some(
+      moc
+        .moc_ranges()
+        .iter()
+        .map(|range| Some(range.end))
+        .collect::<Vec<_>>(),
+    )
+  }));
+  (starts, ends)
+}
+
+/// Read a 2D-MOC (e.g. a ST-MOC) from an Arrow table written by [ranges2d_to_arrow].
+/// The MOC dimension stored in the schema metadata must match the given quantities.
+pub fn range_moc2_from_arrow<Q, R>(
+  batch: &RecordBatch,
+) -> Result<RangeMOC2<u64, Q, u64, R>, ArrowError>
+where
+  Q: MocQty<u64>,
+  R: MocQty<u64>,
+{
+  check_mocdim(
+    batch,
+    &format!("{}.{}", mocdim(Q::PREFIX), mocdim(R::PREFIX)),
+  )?;
+  let depth_max_l = get_depth::<Q>(batch, &mocord(Q::PREFIX))?;
+  let depth_max_r = get_depth::<R>(batch, &mocord(R::PREFIX))?;
+  let [l_start, l_end, r_start, r_end] = list_column_names::<Q, R>();
+  let l_starts = get_list_column(batch, &l_start)?;
+  let l_ends = get_list_column(batch, &l_end)?;
+  let r_starts = get_list_column(batch, &r_start)?;
+  let r_ends = get_list_column(batch, &r_end)?;
+  let mut elems = Vec::with_capacity(batch.num_rows());
+  for irow in 0..batch.num_rows() {
+    let moc_l = list_row_to_moc::<Q>(l_starts, l_ends, irow, depth_max_l)?;
+    let moc_r = list_row_to_moc::<R>(r_starts, r_ends, irow, depth_max_r)?;
+    elems.push(RangeMOC2Elem::new(moc_l, moc_r));
+  }
+  Ok(RangeMOC2::new(depth_max_l, depth_max_r, elems))
+}
+
+fn list_row_to_moc<Q: MocQty<u64>>(
+  starts: &ListArray,
+  ends: &ListArray,
+  irow: usize,
+  depth_max: u8,
+) -> Result<RangeMOC<u64, Q>, ArrowError> {
+  let starts = starts.value(irow);
+  let ends = ends.value(irow);
+  let (starts, ends) = match (
+    starts.as_any().downcast_ref::<UInt64Array>(),
+    ends.as_any().downcast_ref::<UInt64Array>(),
+  ) {
+    (Some(starts), Some(ends)) if starts.len() == ends.len() => (starts, ends),
+    _ => {
+      return Err(ArrowError::InvalidData(format!(
+        "row {}: range starts and ends must be lists of UInt64 of same length",
+        irow
+      )))
+    }
+  };
+  let ranges: Vec<Range<u64>> = starts
+    .values()
+    .iter()
+    .zip(ends.values().iter())
+    .map(|(start, end)| *start..*end)
+    .collect();
+  check_ranges::<u64, Q>(&ranges, depth_max)
+    .map_err(|e| ArrowError::InvalidData(format!("row {}: {}", irow, e)))?;
+  Ok(RangeMOC::new(depth_max, MocRanges::new_unchecked(ranges)))
+}
+
+/// Write a multi-order map as an Arrow table made of a `uniq` column followed by the given
+/// value columns (stored as `Float64`), i.e. the Arrow counterpart of
+/// [to_fits_multiordermap](crate::deser::fits::multiordermap::to_fits_multiordermap).
+///
+/// # Params
+/// * `depth_max`: the multi-order map depth (`MOCORDER`)
+/// * `columns`: name and (optional) unit of each value column
+/// * `rows`: the `(uniq, values)` rows, each one having as many values as the number of columns
+pub fn mom_to_arrow<I, V>(
+  depth_max: u8,
+  columns: &[ValueColumn],
+  rows: I,
+) -> Result<RecordBatch, ArrowError>
+where
+  I: Iterator<Item = (u64, V)>,
+  V: AsRef<[f64]>,
+{
+  let mut uniqs: Vec<u64> = Vec::with_capacity(rows.size_hint().0);
+  let mut values: Vec<Vec<f64>> = vec![Vec::with_capacity(rows.size_hint().0); columns.len()];
+  for (irow, (uniq, row_values)) in rows.enumerate() {
+    let (depth, _ipix) = Hpx::<u64>::from_uniq_hpx(uniq);
+    if uniq < 4 || depth > depth_max {
+      return Err(ArrowError::InvalidData(format!(
+        "NUNIQ {} not valid for a multi-order map of depth {}",
+        uniq, depth_max
+      )));
+    }
+    let row_values = row_values.as_ref();
+    if row_values.len() != columns.len() {
+      return Err(ArrowError::InvalidData(format!(
+        "Wrong number of values in row {}. Expected: {}. Actual: {}.",
+        irow,
+        columns.len(),
+        row_values.len()
+      )));
+    }
+    uniqs.push(uniq);
+    for (col, value) in values.iter_mut().zip(row_values) {
+      col.push(*value);
+    }
+  }
+  let mut fields = Vec::with_capacity(1 + columns.len());
+  fields.push(Field::new(UNIQ_COLUMN, DataType::UInt64, false));
+  for column in columns {
+    let field = Field::new(&column.name, DataType::Float64, false);
+    fields.push(match &column.unit {
+      Some(unit) => field.with_metadata(HashMap::from([(String::from(UNIT), unit.clone())])),
+      None => field,
+    });
+  }
+  let mut arrays: Vec<ArrayRef> = Vec::with_capacity(1 + columns.len());
+  arrays.push(Arc::new(UInt64Array::from(uniqs)));
+  for col in values {
+    arrays.push(Arc::new(Float64Array::from(col)));
+  }
+  let metadata = HashMap::from([(String::from(MOCORDER), depth_max.to_string())]);
+  RecordBatch::try_new(
+    Arc::new(Schema::new_with_metadata(fields, metadata)),
+    arrays,
+  )
+  .map_err(ArrowError::Arrow)
+}
+
+/// Write the given multi-order map as an Arrow table made of a `uniq` column and of a single
+/// value column of given name.
+/// Contrary to [hpxmom_to_fits_multiordermap](crate::deser::fits::multiordermap::hpxmom_to_fits_multiordermap),
+/// values are written as is (i.e. they are not converted into densities).
+pub fn hpxmom_to_arrow<I>(depth_max: u8, name: &str, mom: I) -> Result<RecordBatch, ArrowError>
+where
+  I: HpxMOMIterator<u64, f64>,
+{
+  let columns = [ValueColumn::new(name, None)];
+  mom_to_arrow(
+    depth_max,
+    &columns,
+    mom.map(|(uniq, value)| (uniq, [value])),
+  )
+}
+
+/// Read a multi-order map from an Arrow table made of a `uniq` column (of type `UInt64`) and
+/// of value columns of type `Float64` or `Float32` (other columns are ignored).
+/// Null values are read as `NaN`.
+/// Use [MultiOrderMapColumns::mom_iter] to get a MOM iterator on a given column.
+///
+/// # Info
+/// If the `MOCORDER` schema metadata is missing, the MOM depth is the largest depth of the
+/// `uniq` cells.
+pub fn columns_from_arrow(batch: &RecordBatch) -> Result<MultiOrderMapColumns, ArrowError> {
+  let uniqs = get_u64_column(batch, UNIQ_COLUMN)?.values().to_vec();
+  let mut data_depth_max = 0;
+  for uniq in &uniqs {
+    if *uniq < 4 {
+      return Err(ArrowError::InvalidData(format!("NUNIQ {} not valid", uniq)));
+    }
+    data_depth_max = data_depth_max.max(Hpx::<u64>::from_uniq_hpx(*uniq).0);
+  }
+  let depth_max = match batch.schema().metadata().get(MOCORDER) {
+    Some(depth) => {
+      let depth = parse_depth::<Hpx<u64>>(MOCORDER, depth)?;
+      if data_depth_max > depth {
+        return Err(ArrowError::InvalidData(format!(
+          "cell depth {} larger than the multi-order map depth {}",
+          data_depth_max, depth
+        )));
+      }
+      depth
+    }
+    None => data_depth_max,
+  };
+  let schema = batch.schema();
+  let mut columns = Vec::new();
+  let mut values = Vec::new();
+  for (field, array) in schema.fields().iter().zip(batch.columns()) {
+    let col_values: Vec<f64> = match field.data_type() {
+      _ if field.name() == UNIQ_COLUMN => continue,
+      DataType::Float64 => {
+        // Unwrap ok since we checked the type
+        let array = array.as_any().downcast_ref::<Float64Array>().unwrap();
+        array
+          .iter()
+          .map(|value| value.unwrap_or(f64::NAN))
+          .collect()
+      }
+      DataType::Float32 => {
+        // Unwrap ok since we checked the type
+        let array = array.as_any().downcast_ref::<Float32Array>().unwrap();
+        array
+          .iter()
+          .map(|value| value.map(|v| v as f64).unwrap_or(f64::NAN))
+          .collect()
+      }
+      _ => continue,
+    };
+    columns.push(ValueColumn {
+      name: field.name().clone(),
+      unit: field.metadata().get(UNIT).cloned(),
+    });
+    values.push(col_values);
+  }
+  Ok(MultiOrderMapColumns::new(
+    depth_max,
+    String::from(UNIQ_COLUMN),
+    columns,
+    uniqs,
+    values,
+  ))
+}
+
+/// Write the given Arrow table (e.g. a MOC or a multi-order map) in Parquet.
+/// The schema metadata (MOC dimension and depth) are preserved.
+pub fn to_parquet<W: Write + Send>(batch: &RecordBatch, writer: W) -> Result<(), ArrowError> {
+  let mut writer = ArrowWriter::try_new(writer, batch.schema(), None)?;
+  writer.write(batch)?;
+  writer.close()?;
+  Ok(())
+}
+
+/// Read a full Parquet file (e.g. written by [to_parquet]) into a single Arrow table.
+pub fn from_parquet<R: ChunkReader + 'static>(reader: R) -> Result<RecordBatch, ArrowError> {
+  let builder = ParquetRecordBatchReaderBuilder::try_new(reader)?;
+  let schema = builder.schema().clone();
+  let n_rows = builder.metadata().file_metadata().num_rows().max(1) as usize;
+  let mut reader = builder.with_batch_size(n_rows).build()?;
+  match reader.next() {
+    // The batches returned by the reader do not contain the schema metadata
+    Some(batch) => batch
+      .and_then(|batch| batch.with_schema(schema))
+      .map_err(ArrowError::Arrow),
+    None => Ok(RecordBatch::new_empty(schema)),
+  }
+}
+
+fn check_mocdim(batch: &RecordBatch, expected: &str) -> Result<(), ArrowError> {
+  match batch.schema().metadata().get(MOCDIM) {
+    Some(actual) if actual == expected => Ok(()),
+    Some(actual) => Err(ArrowError::WrongMetadata(
+      String::from(MOCDIM),
+      String::from(expected),
+      actual.clone(),
+    )),
+    None => Err(ArrowError::MissingMetadata(MOCDIM)),
+  }
+}
+
+fn get_depth<Q: MocQty<u64>>(batch: &RecordBatch, key: &str) -> Result<u8, ArrowError> {
+  let schema = batch.schema();
+  let depth = schema
+    .metadata()
+    .get(key)
+    .ok_or_else(|| ArrowError::InvalidData(format!("missing schema metadata '{}'", key)))?;
+  parse_depth::<Q>(key, depth)
+}
+
+fn parse_depth<Q: MocQty<u64>>(key: &str, depth: &str) -> Result<u8, ArrowError> {
+  match depth.parse::<u8>() {
+    Ok(depth) if depth <= Q::MAX_DEPTH => Ok(depth),
+    _ => Err(ArrowError::WrongMetadata(
+      String::from(key),
+      format!("a depth <= {}", Q::MAX_DEPTH),
+      String::from(depth),
+    )),
+  }
+}
+
+fn get_column<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a ArrayRef, ArrowError> {
+  let array = batch
+    .column_by_name(name)
+    .ok_or_else(|| ArrowError::MissingColumn(String::from(name)))?;
+  if array.null_count() > 0 {
+    Err(ArrowError::NullValues(String::from(name)))
+  } else {
+    Ok(array)
+  }
+}
+
+fn get_u64_column<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a UInt64Array, ArrowError> {
+  let array = get_column(batch, name)?;
+  array.as_any().downcast_ref::<UInt64Array>().ok_or_else(|| {
+    ArrowError::WrongColumnType(
+      String::from(name),
+      DataType::UInt64.to_string(),
+      array.data_type().to_string(),
+    )
+  })
+}
+
+fn get_list_column<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a ListArray, ArrowError> {
+  let array = get_column(batch, name)?;
+  array.as_any().downcast_ref::<ListArray>().ok_or_else(|| {
+    ArrowError::WrongColumnType(
+      String::from(name),
+      String::from("List(UInt64)"),
+      array.data_type().to_string(),
+    )
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use bytes::Bytes;
+
+  use super::*;
+  use crate::{elemset::range::MocRanges, moc::RangeMOCIntoIterator, mom::HpxMomIter, qty::Time};
+
+  fn smoc() -> RangeMOC<u64, Hpx<u64>> {
+    RangeMOC::new(
+      10,
+      MocRanges::new_from(vec![
+        Hpx::<u64>::uniq_hpx_to_range(Hpx::<u64>::uniq_hpx(3, 12)),
+        Hpx::<u64>::uniq_hpx_to_range(Hpx::<u64>::uniq_hpx(7, 600)),
+        Hpx::<u64>::uniq_hpx_to_range(Hpx::<u64>::uniq_hpx(10, 1_000_000)),
+      ]),
+    )
+  }
+
+  #[test]
+  fn test_smoc_ranges_and_uniq() {
+    let moc = smoc();
+    let batch = ranges_to_arrow((&moc).into_range_moc_iter()).unwrap();
+    assert_eq!(batch.num_rows(), 3);
+    assert_eq!(batch.schema().metadata().get(MOCDIM).unwrap(), "SPACE");
+    assert_eq!(batch.schema().metadata().get("MOCORD_S").unwrap(), "10");
+    assert_eq!(range_moc_from_arrow::<Hpx<u64>>(&batch).unwrap(), moc);
+    assert!(matches!(
+      range_moc_from_arrow::<Time<u64>>(&batch),
+      Err(ArrowError::WrongMetadata(..))
+    ));
+
+    let batch = hpx_cells_to_arrow((&moc).into_range_moc_iter().cells()).unwrap();
+    assert_eq!(batch.num_columns(), 1);
+    assert_eq!(range_moc_from_arrow::<Hpx<u64>>(&batch).unwrap(), moc);
+  }
+
+  #[test]
+  fn test_stmoc() {
+    let stmoc = RangeMOC2::<u64, Time<u64>, u64, Hpx<u64>>::from_time_and_coos(
+      20,
+      8,
+      [(1_000_000_u64, 0.5, 0.2), (5_000_000_000, 3.0, -0.5)].into_iter(),
+      None,
+    );
+    let batch = ranges2d_to_arrow(&stmoc).unwrap();
+    assert_eq!(batch.schema().metadata().get(MOCDIM).unwrap(), "TIME.SPACE");
+    assert!(batch.column_by_name("t_start").is_some());
+    assert!(batch.column_by_name("s_end").is_some());
+    let actual = range_moc2_from_arrow::<Time<u64>, Hpx<u64>>(&batch).unwrap();
+    assert_eq!(actual, stmoc);
+  }
+
+  #[test]
+  fn test_mom_and_parquet() {
+    let columns = [
+      ValueColumn::new("PROBDENSITY", Some("sr-1")),
+      ValueColumn::new("DISTMU", None),
+    ];
+    let rows = vec![
+      (Hpx::<u64>::uniq_hpx(1, 3), [1.5, 10.0]),
+      (Hpx::<u64>::uniq_hpx(2, 0), [0.5, f64::NAN]),
+    ];
+    let batch = mom_to_arrow(2, &columns, rows.clone().into_iter()).unwrap();
+
+    let mut buf: Vec<u8> = Vec::new();
+    to_parquet(&batch, &mut buf).unwrap();
+    let read_batch = from_parquet(Bytes::from(buf)).unwrap();
+    assert_eq!(read_batch.schema().metadata().get(MOCORDER).unwrap(), "2");
+
+    let mom = columns_from_arrow(&read_batch).unwrap();
+    assert_eq!(mom.depth_max(), 2);
+    assert_eq!(mom.uniqs(), &[rows[0].0, rows[1].0]);
+    assert_eq!(mom.columns()[0].unit.as_deref(), Some("sr-1"));
+    assert_eq!(mom.values("PROBDENSITY").unwrap(), &[1.5, 0.5]);
+    let mom_it = mom.mom_iter("DISTMU", false).unwrap();
+    assert_eq!(mom_it.collect::<Vec<_>>(), vec![(rows[0].0, 10.0)]);
+
+    let batch = hpxmom_to_arrow(
+      2,
+      "value",
+      HpxMomIter::<u64, Hpx<u64>, f64, _>::new(vec![(rows[1].0, 2.0)].into_iter()),
+    )
+    .unwrap();
+    let mom = columns_from_arrow(&batch).unwrap();
+    assert_eq!(mom.rows().collect::<Vec<_>>(), vec![(rows[1].0, vec![2.0])]);
+  }
+
+  #[test]
+  fn test_parquet_moc() {
+    let moc = smoc();
+    let batch = ranges_to_arrow((&moc).into_range_moc_iter()).unwrap();
+    let mut buf: Vec<u8> = Vec::new();
+    to_parquet(&batch, &mut buf).unwrap();
+    let read_batch = from_parquet(Bytes::from(buf)).unwrap();
+    assert_eq!(range_moc_from_arrow::<Hpx<u64>>(&read_batch).unwrap(), moc);
+  }
+}
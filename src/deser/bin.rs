@@ -0,0 +1,742 @@
+//! Native, versioned, binary serialization of range MOCs.
+//!
+//! The format stores the ranges as they are stored in memory, so that a MOC can be used directly
+//! from a byte slice (e.g. a memory mapped file), without any copy or decoding step.
+//!
+//! # Format (version 1)
+//!
+//! Header (32 bytes), multi-bytes numbers are stored in little-endian:
+//!
+//! | offset | size | content                                                            |
+//! |--------|------|--------------------------------------------------------------------|
+//! |      0 |    4 | magic number: `MOCR`                                               |
+//! |      4 |    1 | version of the format: `1`                                         |
+//! |      5 |    1 | endianness of the payload: `l` (little-endian) or `b` (big-endian) |
+//! |      6 |    1 | number of bytes of an index: `1`, `2`, `4`, `8` or `16`            |
+//! |      7 |    1 | number of dimensions: `1` or `2`                                   |
+//! |      8 |    1 | prefix of the first quantity: `s`, `t` or `f`                      |
+//! |      9 |    1 | depth max of the first quantity                                    |
+//! |     10 |    1 | prefix of the second quantity (`0` for a 1D MOC)                   |
+//! |     11 |    1 | depth max of the second quantity (`0` for a 1D MOC)                |
+//! |     12 |    4 | CRC32 of the payload                                               |
+//! |     16 |    8 | total number of ranges                                             |
+//! |     24 |    8 | number of 2D elements (`0` for a 1D MOC)                           |
+//!
+//! Payload:
+//! * 1D MOC: the sorted list of ranges, each range being a `(start, end)` tuple of indices.
+//! * 2D MOC: the `2 * n_elems + 1` cumulative offsets (`u64`, in number of ranges) defining
+//!   the elements (see [BorrowedRangeMOC2](crate::moc2d::range::borrowed::BorrowedRangeMOC2)),
+//!   padded with zeros to a multiple of 16 bytes, followed by the list of ranges.
+//!
+//! The writers always use the endianness of the platform.
+//! Zero-copy loading requires:
+//! * the payload endianness to be the platform endianness;
+//! * the input slice to be aligned on the index type (16 bytes is enough for all types,
+//!   memory mapped files are page aligned).
+//!
+//! The reader based methods (`*_from_bin_reader`) copy the data and support both endiannesses.
+
+use std::{
+  io::{self, Read, Write},
+  mem,
+  ops::Range,
+  slice,
+};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use flate2::Crc;
+use quick_error::quick_error;
+
+use crate::{
+  elemset::range::MocRanges,
+  idx::Idx,
+  moc::range::{borrowed::BorrowedRangeMOC, RangeMOC},
+  moc2d::{
+    range::{borrowed::BorrowedRangeMOC2, RangeMOC2},
+    HasTwoMaxDepth,
+  },
+  qty::MocQty,
+  ranges::{BorrowedRanges, Ranges},
+};
+
+/// Magic number starting each binary MOC.
+pub const MAGIC: &[u8; 4] = b"MOCR";
+/// Current version of the format.
+pub const VERSION: u8 = 1;
+/// Size, in bytes, of the header.
+pub const HEADER_SIZE: usize = 32;
+
+const LITTLE_ENDIAN: u8 = b'l';
+const BIG_ENDIAN: u8 = b'b';
+#[cfg(target_endian = "little")]
+const NATIVE_ENDIAN: u8 = LITTLE_ENDIAN;
+#[cfg(target_endian = "big")]
+const NATIVE_ENDIAN: u8 = BIG_ENDIAN;
+
+quick_error! {
+  #[derive(Debug)]
+  pub enum BinError {
+    /// IO error
+    Io(err: io::Error) {
+      from()
+      display("I/O error: {}", err)
+    }
+    WrongMagicNumber {
+      display("Wrong magic number, not a binary MOC")
+    }
+    UnsupportedVersion(version: u8) {
+      display("Unsupported binary MOC version: {}. Supported version: {}", version, VERSION)
+    }
+    UnexpectedValue(field: &'static str, expected: String, actual: String) {
+      display("Unexpected value for '{}'. Expected: {}. Actual: {}", field, expected, actual)
+    }
+    Truncated(expected: usize, actual: usize) {
+      display("Truncated data. Expected: {} bytes. Actual: {} bytes", expected, actual)
+    }
+    WrongChecksum(expected: u32, actual: u32) {
+      display("Wrong checksum. Expected: {}. Actual: {}", expected, actual)
+    }
+    NotAligned(align: usize) {
+      display("Data not aligned on {} bytes, zero-copy loading impossible", align)
+    }
+    NonNativeEndianness {
+      display("Data endianness differs from the platform one, zero-copy loading impossible")
+    }
+    InvalidRanges(msg: String) {
+      display("Invalid ranges: {}", msg)
+    }
+  }
+}
+
+/// Header of a binary MOC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinHeader {
+  pub version: u8,
+  /// `b'l'` for little-endian, `b'b'` for big-endian.
+  pub endianness: u8,
+  pub n_bytes: u8,
+  pub n_dim: u8,
+  pub qty_prefix_1: u8,
+  pub depth_max_1: u8,
+  pub qty_prefix_2: u8,
+  pub depth_max_2: u8,
+  pub checksum: u32,
+  pub n_ranges: u64,
+  pub n_elems: u64,
+}
+
+impl BinHeader {
+  fn new_1d<T: Idx, Q: MocQty<T>>(depth_max: u8, n_ranges: u64, checksum: u32) -> Self {
+    Self {
+      version: VERSION,
+      endianness: NATIVE_ENDIAN,
+      n_bytes: T::N_BYTES,
+      n_dim: 1,
+      qty_prefix_1: Q::PREFIX as u8,
+      depth_max_1: depth_max,
+      qty_prefix_2: 0,
+      depth_max_2: 0,
+      checksum,
+      n_ranges,
+      n_elems: 0,
+    }
+  }
+
+  fn new_2d<T: Idx, Q: MocQty<T>, R: MocQty<T>>(
+    depth_max_1: u8,
+    depth_max_2: u8,
+    n_ranges: u64,
+    n_elems: u64,
+    checksum: u32,
+  ) -> Self {
+    Self {
+      version: VERSION,
+      endianness: NATIVE_ENDIAN,
+      n_bytes: T::N_BYTES,
+      n_dim: 2,
+      qty_prefix_1: Q::PREFIX as u8,
+      depth_max_1,
+      qty_prefix_2: R::PREFIX as u8,
+      depth_max_2,
+      checksum,
+      n_ranges,
+      n_elems,
+    }
+  }
+
+  /// Parses the header at the beginning of the given slice, checking the magic number and the
+  /// version. Useful to decide which type to use before calling the loading methods.
+  pub fn from_bytes(bytes: &[u8]) -> Result<Self, BinError> {
+    if bytes.len() < HEADER_SIZE {
+      return Err(BinError::Truncated(HEADER_SIZE, bytes.len()));
+    }
+    if &bytes[0..4] != MAGIC {
+      return Err(BinError::WrongMagicNumber);
+    }
+    if bytes[4] != VERSION {
+      return Err(BinError::UnsupportedVersion(bytes[4]));
+    }
+    if bytes[5] != LITTLE_ENDIAN && bytes[5] != BIG_ENDIAN {
+      return Err(BinError::UnexpectedValue(
+        "endianness",
+        "'l' or 'b'".to_string(),
+        (bytes[5] as char).to_string(),
+      ));
+    }
+    Ok(Self {
+      version: bytes[4],
+      endianness: bytes[5],
+      n_bytes: bytes[6],
+      n_dim: bytes[7],
+      qty_prefix_1: bytes[8],
+      depth_max_1: bytes[9],
+      qty_prefix_2: bytes[10],
+      depth_max_2: bytes[11],
+      checksum: LittleEndian::read_u32(&bytes[12..16]),
+      n_ranges: LittleEndian::read_u64(&bytes[16..24]),
+      n_elems: LittleEndian::read_u64(&bytes[24..32]),
+    })
+  }
+
+  /// Reads and parses the header from the given reader.
+  pub fn read<R: Read>(mut reader: R) -> Result<Self, BinError> {
+    let mut bytes = [0_u8; HEADER_SIZE];
+    reader.read_exact(&mut bytes)?;
+    Self::from_bytes(&bytes)
+  }
+
+  pub fn write<W: Write>(&self, mut writer: W) -> Result<(), BinError> {
+    let mut bytes = [0_u8; HEADER_SIZE];
+    bytes[0..4].copy_from_slice(MAGIC);
+    bytes[4] = self.version;
+    bytes[5] = self.endianness;
+    bytes[6] = self.n_bytes;
+    bytes[7] = self.n_dim;
+    bytes[8] = self.qty_prefix_1;
+    bytes[9] = self.depth_max_1;
+    bytes[10] = self.qty_prefix_2;
+    bytes[11] = self.depth_max_2;
+    LittleEndian::write_u32(&mut bytes[12..16], self.checksum);
+    LittleEndian::write_u64(&mut bytes[16..24], self.n_ranges);
+    LittleEndian::write_u64(&mut bytes[24..32], self.n_elems);
+    writer.write_all(&bytes).map_err(BinError::Io)
+  }
+
+  /// Size, in bytes, of the payload (i.e. of the data following the header).
+  pub fn payload_size(&self) -> usize {
+    (self.n_ranges as usize)
+      .saturating_mul(self.n_bytes as usize * 2)
+      .saturating_add(self.offsets_size())
+  }
+
+  /// Size, in bytes, of the 2D offsets, including the padding (`0` for a 1D MOC).
+  fn offsets_size(&self) -> usize {
+    if self.n_dim == 2 {
+      offsets_size(self.n_elems as usize)
+    } else {
+      0
+    }
+  }
+
+  fn check_1d<T: Idx, Q: MocQty<T>>(&self) -> Result<(), BinError> {
+    check_field("number of dimensions", 1, self.n_dim)?;
+    check_field("number of bytes", T::N_BYTES, self.n_bytes)?;
+    check_qty::<T, Q>(self.qty_prefix_1, self.depth_max_1)
+  }
+
+  fn check_2d<T: Idx, Q: MocQty<T>, R: MocQty<T>>(&self) -> Result<(), BinError> {
+    check_field("number of dimensions", 2, self.n_dim)?;
+    check_field("number of bytes", T::N_BYTES, self.n_bytes)?;
+    check_qty::<T, Q>(self.qty_prefix_1, self.depth_max_1)?;
+    check_qty::<T, R>(self.qty_prefix_2, self.depth_max_2)
+  }
+}
+
+/// Writes the given MOC in the native binary format.
+pub fn range_moc_to_bin<T: Idx, Q: MocQty<T>, W: Write>(
+  moc: &RangeMOC<T, Q>,
+  mut writer: W,
+) -> Result<(), BinError> {
+  let bytes = moc.moc_ranges().ranges().as_bytes();
+  BinHeader::new_1d::<T, Q>(moc.depth_max(), moc.len() as u64, checksum(&[bytes]))
+    .write(&mut writer)?;
+  writer.write_all(bytes).map_err(BinError::Io)
+}
+
+/// Writes the given 2D MOC in the native binary format.
+pub fn range_moc2_to_bin<T: Idx, Q: MocQty<T>, R: MocQty<T>, W: Write>(
+  moc: &RangeMOC2<T, Q, T, R>,
+  mut writer: W,
+) -> Result<(), BinError> {
+  let elems = moc.elems();
+  let mut offsets: Vec<u8> = Vec::with_capacity(offsets_size(elems.len()));
+  let mut n_ranges = 0_u64;
+  offsets.extend_from_slice(&n_ranges.to_ne_bytes());
+  for elem in elems {
+    n_ranges += elem.n_ranges_1() as u64;
+    offsets.extend_from_slice(&n_ranges.to_ne_bytes());
+    n_ranges += elem.n_ranges_2() as u64;
+    offsets.extend_from_slice(&n_ranges.to_ne_bytes());
+  }
+  offsets.resize(offsets_size(elems.len()), 0);
+  let mut chunks: Vec<&[u8]> = Vec::with_capacity(1 + 2 * elems.len());
+  chunks.push(&offsets);
+  for elem in elems {
+    chunks.push(elem.moc_l().moc_ranges().ranges().as_bytes());
+    chunks.push(elem.moc_r().moc_ranges().ranges().as_bytes());
+  }
+  BinHeader::new_2d::<T, Q, R>(
+    moc.depth_max_1(),
+    moc.depth_max_2(),
+    n_ranges,
+    elems.len() as u64,
+    checksum(&chunks),
+  )
+  .write(&mut writer)?;
+  for chunk in chunks {
+    writer.write_all(chunk)?;
+  }
+  Ok(())
+}
+
+/// Returns a view on the MOC stored in the given binary data, without copying it.
+/// The header, the checksum and the ranges are checked, so that the returned MOC is valid.
+/// # Params
+/// * `bytes`: the binary MOC, starting by the header, e.g. a memory mapped file. It must be
+///   aligned on the index type (see the module documentation).
+pub fn range_moc_from_bin<T: Idx, Q: MocQty<T>>(
+  bytes: &[u8],
+) -> Result<BorrowedRangeMOC<'_, T, Q>, BinError> {
+  let header = BinHeader::from_bytes(bytes)?;
+  header.check_1d::<T, Q>()?;
+  let payload = zero_copy_payload(&header, bytes)?;
+  let ranges: &[Range<T>] = cast_slice(payload)?;
+  check_ranges::<T, Q>(ranges, header.depth_max_1)?;
+  Ok(BorrowedRangeMOC::new(
+    header.depth_max_1,
+    BorrowedRanges(ranges).into(),
+  ))
+}
+
+/// Returns a view on the 2D MOC stored in the given binary data, without copying it.
+/// The header, the checksum, the offsets and the ranges are checked, so that the returned
+/// MOC is valid.
+/// # Params
+/// * `bytes`: the binary MOC, starting by the header, e.g. a memory mapped file. It must be
+///   aligned on the index type (see the module documentation).
+pub fn range_moc2_from_bin<T: Idx, Q: MocQty<T>, R: MocQty<T>>(
+  bytes: &[u8],
+) -> Result<BorrowedRangeMOC2<'_, T, Q, R>, BinError> {
+  let header = BinHeader::from_bytes(bytes)?;
+  header.check_2d::<T, Q, R>()?;
+  let payload = zero_copy_payload(&header, bytes)?;
+  let (offsets, ranges) = payload.split_at(header.offsets_size());
+  let offsets: &[u64] = cast_slice(offsets)?;
+  let offsets = &offsets[..2 * header.n_elems as usize + 1];
+  let ranges: &[Range<T>] = cast_slice(ranges)?;
+  check_moc2_ranges::<T, Q, R>(offsets, ranges, header.depth_max_1, header.depth_max_2)?;
+  Ok(BorrowedRangeMOC2::new(
+    header.depth_max_1,
+    header.depth_max_2,
+    offsets,
+    ranges,
+  ))
+}
+
+/// Reads (and copies) a MOC stored in the binary format, whatever the payload endianness is.
+pub fn range_moc_from_bin_reader<T: Idx, Q: MocQty<T>, R: Read>(
+  mut reader: R,
+) -> Result<RangeMOC<T, Q>, BinError> {
+  let header = BinHeader::read(&mut reader)?;
+  header.check_1d::<T, Q>()?;
+  let payload = read_payload(&header, reader)?;
+  let ranges = match header.endianness {
+    LITTLE_ENDIAN => read_ranges::<T, LittleEndian>(&payload, header.n_ranges as usize),
+    _ => read_ranges::<T, BigEndian>(&payload, header.n_ranges as usize),
+  }?;
+  check_ranges::<T, Q>(&ranges, header.depth_max_1)?;
+  Ok(RangeMOC::new(
+    header.depth_max_1,
+    MocRanges::from(Ranges::new_unchecked(ranges)),
+  ))
+}
+
+/// Reads (and copies) a 2D MOC stored in the binary format, whatever the payload endianness is.
+pub fn range_moc2_from_bin_reader<T: Idx, Q: MocQty<T>, R: MocQty<T>, S: Read>(
+  mut reader: S,
+) -> Result<RangeMOC2<T, Q, T, R>, BinError> {
+  let header = BinHeader::read(&mut reader)?;
+  header.check_2d::<T, Q, R>()?;
+  let payload = read_payload(&header, reader)?;
+  let (offsets, ranges) = payload.split_at(header.offsets_size());
+  let n_offsets = 2 * header.n_elems as usize + 1;
+  let (offsets, ranges) = match header.endianness {
+    LITTLE_ENDIAN => (
+      read_offsets::<LittleEndian>(offsets, n_offsets),
+      read_ranges::<T, LittleEndian>(ranges, header.n_ranges as usize)?,
+    ),
+    _ => (
+      read_offsets::<BigEndian>(offsets, n_offsets),
+      read_ranges::<T, BigEndian>(ranges, header.n_ranges as usize)?,
+    ),
+  };
+  check_moc2_ranges::<T, Q, R>(&offsets, &ranges, header.depth_max_1, header.depth_max_2)?;
+  Ok(
+    BorrowedRangeMOC2::<T, Q, R>::new(header.depth_max_1, header.depth_max_2, &offsets, &ranges)
+      .to_range_moc2(),
+  )
+}
+
+/// Size, in bytes, of the offsets of a 2D MOC of `n_elems` elements, including the padding.
+fn offsets_size(n_elems: usize) -> usize {
+  n_elems
+    .saturating_mul(2 * mem::size_of::<u64>())
+    .saturating_add(mem::size_of::<u64>() + 15)
+    & !15
+}
+
+fn checksum(chunks: &[&[u8]]) -> u32 {
+  let mut crc = Crc::new();
+  for chunk in chunks {
+    crc.update(chunk);
+  }
+  crc.sum()
+}
+
+fn check_field(field: &'static str, expected: u8, actual: u8) -> Result<(), BinError> {
+  if expected == actual {
+    Ok(())
+  } else {
+    Err(BinError::UnexpectedValue(
+      field,
+      expected.to_string(),
+      actual.to_string(),
+    ))
+  }
+}
+
+fn check_qty<T: Idx, Q: MocQty<T>>(prefix: u8, depth_max: u8) -> Result<(), BinError> {
+  if prefix != Q::PREFIX as u8 {
+    Err(BinError::UnexpectedValue(
+      "quantity",
+      Q::PREFIX.to_string(),
+      (prefix as char).to_string(),
+    ))
+  } else if depth_max > Q::MAX_DEPTH {
+    Err(BinError::UnexpectedValue(
+      "depth max",
+      format!("<= {}", Q::MAX_DEPTH),
+      depth_max.to_string(),
+    ))
+  } else {
+    Ok(())
+  }
+}
+
+/// Returns the payload of the given binary data after having checked its length,
+/// its endianness and its checksum.
+fn zero_copy_payload<'a>(header: &BinHeader, bytes: &'a [u8]) -> Result<&'a [u8], BinError> {
+  if header.endianness != NATIVE_ENDIAN {
+    return Err(BinError::NonNativeEndianness);
+  }
+  let expected_len = HEADER_SIZE.saturating_add(header.payload_size());
+  if bytes.len() < expected_len {
+    return Err(BinError::Truncated(expected_len, bytes.len()));
+  }
+  let payload = &bytes[HEADER_SIZE..expected_len];
+  check_checksum(header, payload)?;
+  Ok(payload)
+}
+
+fn read_payload<R: Read>(header: &BinHeader, reader: R) -> Result<Vec<u8>, BinError> {
+  let expected_len = header.payload_size();
+  let mut payload = Vec::new();
+  reader.take(expected_len as u64).read_to_end(&mut payload)?;
+  if payload.len() < expected_len {
+    return Err(BinError::Truncated(
+      HEADER_SIZE.saturating_add(expected_len),
+      HEADER_SIZE + payload.len(),
+    ));
+  }
+  check_checksum(header, &payload)?;
+  Ok(payload)
+}
+
+fn check_checksum(header: &BinHeader, payload: &[u8]) -> Result<(), BinError> {
+  let actual = checksum(&[payload]);
+  if actual == header.checksum {
+    Ok(())
+  } else {
+    Err(BinError::WrongChecksum(header.checksum, actual))
+  }
+}
+
+/// Re-interprets the given bytes as a slice of `U`.
+/// Must only be used with `U` a primitive integer (or a `Range` of primitive integers),
+/// for which any bit pattern is valid.
+fn cast_slice<U>(bytes: &[u8]) -> Result<&[U], BinError> {
+  let align = mem::align_of::<U>();
+  if bytes.as_ptr().align_offset(align) != 0 {
+    return Err(BinError::NotAligned(align));
+  }
+  let len = bytes.len() / mem::size_of::<U>();
+  debug_assert_eq!(bytes.len(), len * mem::size_of::<U>());
+  Ok(unsafe { slice::from_raw_parts(bytes.as_ptr() as *const U, len) })
+}
+
+fn read_offsets<B: ByteOrder>(bytes: &[u8], n_offsets: usize) -> Vec<u64> {
+  bytes
+    .chunks_exact(mem::size_of::<u64>())
+    .take(n_offsets)
+    .map(B::read_u64)
+    .collect()
+}
+
+fn read_ranges<T: Idx, B: ByteOrder>(
+  mut bytes: &[u8],
+  n_ranges: usize,
+) -> Result<Vec<Range<T>>, BinError> {
+  let mut ranges = Vec::with_capacity(n_ranges);
+  for _ in 0..n_ranges {
+    let start = T::read::<_, B>(&mut bytes)?;
+    let end = T::read::<_, B>(&mut bytes)?;
+    ranges.push(start..end);
+  }
+  Ok(ranges)
+}
+
+/// Checks that the ranges are valid, i.e. non-empty, sorted, non-overlapping,
+/// non-consecutive, in the quantity domain, and with bounds compatible with the given depth.
+pub(crate) fn check_ranges<T: Idx, Q: MocQty<T>>(
+  ranges: &[Range<T>],
+  depth_max: u8,
+) -> Result<(), BinError> {
+  let mask = T::one().unsigned_shl(Q::shift_from_depth_max(depth_max) as u32) - T::one();
+  let upper_bound = Q::n_cells_max();
+  let mut prev_end: Option<T> = None;
+  for range in ranges {
+    if range.start >= range.end {
+      return Err(BinError::InvalidRanges(format!("empty range {:?}", range)));
+    }
+    if range.end > upper_bound {
+      return Err(BinError::InvalidRanges(format!(
+        "range {:?} out of the domain upper bound {}",
+        range, upper_bound
+      )));
+    }
+    if (range.start & mask) != T::zero() || (range.end & mask) != T::zero() {
+      return Err(BinError::InvalidRanges(format!(
+        "range {:?} bounds incompatible with depth max {}",
+        range, depth_max
+      )));
+    }
+    if let Some(prev_end) = prev_end {
+      if prev_end >= range.start {
+        return Err(BinError::InvalidRanges(format!(
+          "range {:?} not sorted, overlapping or consecutive with previous range",
+          range
+        )));
+      }
+    }
+    prev_end = Some(range.end);
+  }
+  Ok(())
+}
+
+/// Checks the offsets and the ranges of a 2D MOC.
+fn check_moc2_ranges<T: Idx, Q: MocQty<T>, R: MocQty<T>>(
+  offsets: &[u64],
+  ranges: &[Range<T>],
+  depth_max_1: u8,
+  depth_max_2: u8,
+) -> Result<(), BinError> {
+  if offsets.first() != Some(&0) || offsets.last() != Some(&(ranges.len() as u64)) {
+    return Err(BinError::InvalidRanges(String::from(
+      "first offset must be 0 and last offset must be the number of ranges",
+    )));
+  }
+  if offsets.windows(2).any(|w| w[0] > w[1]) {
+    return Err(BinError::InvalidRanges(String::from(
+      "offsets must be increasing",
+    )));
+  }
+  let mut prev_end: Option<T> = None;
+  for bounds in offsets.windows(3).step_by(2) {
+    let ranges_1 = &ranges[bounds[0] as usize..bounds[1] as usize];
+    let ranges_2 = &ranges[bounds[1] as usize..bounds[2] as usize];
+    check_ranges::<T, Q>(ranges_1, depth_max_1)?;
+    check_ranges::<T, R>(ranges_2, depth_max_2)?;
+    if let (Some(prev_end), Some(first)) = (prev_end, ranges_1.first()) {
+      if prev_end > first.start {
+        return Err(BinError::InvalidRanges(String::from(
+          "first dimension ranges of the elements not sorted or overlapping",
+        )));
+      }
+    }
+    if let Some(last) = ranges_1.last() {
+      prev_end = Some(last.end);
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::{
+    moc::{range::CellSelection, RangeMOCIntoIterator, RangeMOCIterator},
+    qty::{Hpx, Time},
+  };
+
+  /// Copies the given bytes in a 16 bytes aligned buffer (like a memory mapped file would be).
+  fn aligned(bytes: &[u8]) -> Vec<u128> {
+    let mut buf = vec![0_u128; bytes.len().div_ceil(16)];
+    let ptr = buf.as_mut_ptr() as *mut u8;
+    unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, bytes.len()) };
+    buf
+  }
+
+  fn as_bytes(buf: &[u128], len: usize) -> &[u8] {
+    unsafe { slice::from_raw_parts(buf.as_ptr() as *const u8, len) }
+  }
+
+  fn smoc() -> RangeMOC<u64, Hpx<u64>> {
+    RangeMOC::from_cone(0.5, 0.2, 0.1, 10, 2, CellSelection::All)
+  }
+
+  fn stmoc() -> RangeMOC2<u64, Time<u64>, u64, Hpx<u64>> {
+    RangeMOC2::<u64, Time<u64>, u64, Hpx<u64>>::from_time_and_coos(
+      20,
+      8,
+      vec![
+        (1_000_000_u64, 0.1, 0.2),
+        (10_000_000_000_000, 1.1, -0.2),
+        (1_000_000_000_000_000, 3.0, 0.7),
+      ]
+      .into_iter(),
+      None,
+    )
+  }
+
+  #[test]
+  fn test_bin_smoc_zero_copy() {
+    let moc = smoc();
+    let mut buf: Vec<u8> = Default::default();
+    range_moc_to_bin(&moc, &mut buf).unwrap();
+    assert_eq!(buf.len(), HEADER_SIZE + moc.len() * 16);
+    let header = BinHeader::from_bytes(&buf).unwrap();
+    assert_eq!(header.n_dim, 1);
+    assert_eq!(header.qty_prefix_1, b's');
+    assert_eq!(header.depth_max_1, 10);
+
+    let aligned_buf = aligned(&buf);
+    let bytes = as_bytes(&aligned_buf, buf.len());
+    let view = range_moc_from_bin::<u64, Hpx<u64>>(bytes).unwrap();
+    assert_eq!(view.len(), moc.len());
+    assert_eq!(view.to_range_moc(), moc);
+    assert_eq!(
+      view.into_range_moc_iter().cells().count(),
+      (&moc).into_range_moc_iter().cells().count()
+    );
+    // Wrong quantity
+    assert!(range_moc_from_bin::<u64, Time<u64>>(bytes).is_err());
+    // Wrong index type
+    assert!(range_moc_from_bin::<u32, Hpx<u32>>(bytes).is_err());
+    // Misaligned
+    let mut shifted = vec![0_u8];
+    shifted.extend_from_slice(bytes);
+    let shifted_aligned = aligned(&shifted);
+    assert!(matches!(
+      range_moc_from_bin::<u64, Hpx<u64>>(&as_bytes(&shifted_aligned, shifted.len())[1..]),
+      Err(BinError::NotAligned(_))
+    ));
+  }
+
+  #[test]
+  fn test_bin_smoc_reader() {
+    let moc = smoc();
+    let mut buf: Vec<u8> = Default::default();
+    range_moc_to_bin(&moc, &mut buf).unwrap();
+    let moc2 = range_moc_from_bin_reader::<u64, Hpx<u64>, _>(buf.as_slice()).unwrap();
+    assert_eq!(moc2, moc);
+    // Non-native endianness: zero-copy impossible but readable
+    let mut swapped = buf.clone();
+    swapped[5] = if NATIVE_ENDIAN == LITTLE_ENDIAN {
+      BIG_ENDIAN
+    } else {
+      LITTLE_ENDIAN
+    };
+    for chunk in swapped[HEADER_SIZE..].chunks_exact_mut(8) {
+      chunk.reverse();
+    }
+    let crc = checksum(&[&swapped[HEADER_SIZE..]]);
+    LittleEndian::write_u32(&mut swapped[12..16], crc);
+    let aligned_buf = aligned(&swapped);
+    assert!(matches!(
+      range_moc_from_bin::<u64, Hpx<u64>>(as_bytes(&aligned_buf, swapped.len())),
+      Err(BinError::NonNativeEndianness)
+    ));
+    let moc3 = range_moc_from_bin_reader::<u64, Hpx<u64>, _>(swapped.as_slice()).unwrap();
+    assert_eq!(moc3, moc);
+    // Truncated
+    assert!(matches!(
+      range_moc_from_bin_reader::<u64, Hpx<u64>, _>(&buf[..buf.len() - 1]),
+      Err(BinError::Truncated(_, _))
+    ));
+    // Corrupted
+    let n = buf.len();
+    buf[n - 3] ^= 0x01;
+    assert!(matches!(
+      range_moc_from_bin_reader::<u64, Hpx<u64>, _>(buf.as_slice()),
+      Err(BinError::WrongChecksum(_, _))
+    ));
+  }
+
+  #[test]
+  fn test_bin_smoc_invalid_ranges() {
+    // Consecutive ranges, with a valid checksum
+    let ranges: Vec<Range<u64>> = vec![0..16, 16..32];
+    let ranges = BorrowedRanges(&ranges);
+    let bytes = ranges.as_bytes();
+    let mut buf: Vec<u8> = Default::default();
+    BinHeader::new_1d::<u64, Hpx<u64>>(29, 2, checksum(&[bytes]))
+      .write(&mut buf)
+      .unwrap();
+    buf.extend_from_slice(bytes);
+    let aligned_buf = aligned(&buf);
+    assert!(matches!(
+      range_moc_from_bin::<u64, Hpx<u64>>(as_bytes(&aligned_buf, buf.len())),
+      Err(BinError::InvalidRanges(_))
+    ));
+  }
+
+  #[test]
+  fn test_bin_stmoc() {
+    let moc = stmoc();
+    let mut buf: Vec<u8> = Default::default();
+    range_moc2_to_bin(&moc, &mut buf).unwrap();
+    let header = BinHeader::from_bytes(&buf).unwrap();
+    assert_eq!(header.n_dim, 2);
+    assert_eq!(header.n_elems, 3);
+    assert_eq!(header.n_ranges, moc.compute_n_ranges());
+    // Zero-copy
+    let aligned_buf = aligned(&buf);
+    let bytes = as_bytes(&aligned_buf, buf.len());
+    let view = range_moc2_from_bin::<u64, Time<u64>, Hpx<u64>>(bytes).unwrap();
+    assert_eq!(view.n_elems(), 3);
+    assert_eq!(view.to_range_moc2(), moc);
+    let t = 10_000_000_000_000_u64;
+    let (moc_t, moc_s) = view.elem(1);
+    let s = moc_s.moc_ranges().0 .0[0].start;
+    assert!(!moc_t.is_empty());
+    assert!(view.contains_val(&t, &s));
+    assert!(!view.contains_val(&(t + (1 << 50)), &s));
+    // Wrong qties
+    assert!(range_moc2_from_bin::<u64, Hpx<u64>, Time<u64>>(bytes).is_err());
+    assert!(range_moc_from_bin::<u64, Time<u64>>(bytes).is_err());
+    // Copy
+    let moc2 = range_moc2_from_bin_reader::<u64, Time<u64>, Hpx<u64>, _>(buf.as_slice()).unwrap();
+    assert_eq!(moc2, moc);
+  }
+}
@@ -0,0 +1,986 @@
+//! DS9 and CASA (CRTF) region files serialization and deserialization of S-MOCs.
+//!
+//! Only regions in celestial coordinates are supported (`fk5`, `icrs` or `j2000` in DS9,
+//! `J2000` or `ICRS` in CRTF, the default when no coordinate system is given).
+//! When reading a region file, the resulting MOC is the union (`or`) of all include shapes minus
+//! (`minus`) the union of all exclude shapes (prefixed by `-`), whatever their order.
+//! When writing a S-MOC, each connected component is written as a polygon (`polygon` in DS9,
+//! `poly` in CRTF) followed by its holes written as exclude polygons (prefixed by `-`).
+
+use std::{f64::consts::PI, io::Write};
+
+use thiserror::Error;
+
+use crate::{
+  idx::Idx,
+  moc::range::{
+    polygons::{contains, polygon_coverage_deg, MocPolygon},
+    CellSelection, RangeMOC,
+  },
+  qty::{Hpx, MocQty},
+};
+
+#[derive(Error, Debug)]
+pub enum Ds9Error {
+  #[error("I/O error: {0}")]
+  Io(#[from] std::io::Error),
+  #[error("Depth {depth} larger than the max depth {depth_max}")]
+  WrongDepth { depth: u8, depth_max: u8 },
+  #[error("Line {line}: coordinate system '{found}' not supported (only fk5, icrs or j2000)")]
+  UnsupportedFrame { line: usize, found: String },
+  #[error("Line {line}: shape '{found}' not supported")]
+  UnsupportedShape { line: usize, found: String },
+  #[error("Line {line}: wrong region '{region}': {msg}")]
+  WrongRegion {
+    line: usize,
+    region: String,
+    msg: String,
+  },
+}
+
+/// Supported shapes.
+const SHAPES: [&str; 5] = ["circle", "ellipse", "box", "polygon", "annulus"];
+/// Shapes having no area, ignored when building a MOC.
+const NO_AREA_SHAPES: [&str; 7] = [
+  "point",
+  "line",
+  "vector",
+  "text",
+  "ruler",
+  "compass",
+  "projection",
+];
+
+/// Supported CRTF shapes.
+const CRTF_SHAPES: [&str; 7] = [
+  "circle",
+  "ellipse",
+  "box",
+  "centerbox",
+  "rotbox",
+  "poly",
+  "annulus",
+];
+/// CRTF shapes having no area, ignored when building a MOC.
+const CRTF_NO_AREA_SHAPES: [&str; 4] = ["symbol", "line", "vector", "text"];
+
+/// Write the given S-MOC as a DS9 region file, in `fk5`, each connected component of the MOC
+/// being a `polygon` followed by its holes (`-polygon`), see [RangeMOC::to_polygons].
+/// Exclude shapes being removed from the union of all include shapes, a component having a hole
+/// which contains other components is first split along a meridian crossing that hole
+/// (see [split_at_meridians](crate::moc::range::polygons::MocPolygon::split_at_meridians)).
+///
+/// # Remark
+/// To follow the MOC contours, the number of vertices is large: you may want to degrade the MOC
+/// first. Longitudes are continuous along a polygon and may thus be lower than 0 or larger than
+/// 360 degrees.
+pub fn to_ds9<T: Idx, W: Write>(moc: &RangeMOC<T, Hpx<T>>, mut writer: W) -> Result<(), Ds9Error> {
+  writeln!(writer, "# Region file format: DS9 version 4.1")?;
+  writeln!(writer, "# S-MOC of depth {}", moc.depth_max())?;
+  writeln!(writer, "fk5")?;
+  for polygon in region_polygons(moc) {
+    write_polygon(&mut writer, "", &polygon.exterior)?;
+    for hole in &polygon.holes {
+      write_polygon(&mut writer, "-", hole)?;
+    }
+  }
+  Ok(())
+}
+
+/// Write the given S-MOC as a CASA region file (CRTF), in `J2000`, each connected component of
+/// the MOC being a `poly` followed by its holes (`-poly`), see [to_ds9].
+pub fn to_crtf<T: Idx, W: Write>(moc: &RangeMOC<T, Hpx<T>>, mut writer: W) -> Result<(), Ds9Error> {
+  writeln!(writer, "#CRTFv0 CASA Region Text Format version 0")?;
+  writeln!(writer, "# S-MOC of depth {}", moc.depth_max())?;
+  writeln!(writer, "global coord=J2000")?;
+  for polygon in region_polygons(moc) {
+    write_crtf_poly(&mut writer, "", &polygon.exterior)?;
+    for hole in &polygon.holes {
+      write_crtf_poly(&mut writer, "-", hole)?;
+    }
+  }
+  Ok(())
+}
+
+/// Returns the polygons (with holes) of the given S-MOC, a component having a hole which
+/// contains other components being split along a meridian crossing that hole.
+fn region_polygons<T: Idx>(moc: &RangeMOC<T, Hpx<T>>) -> Vec<MocPolygon> {
+  let polygons = moc.to_polygons();
+  let mut split_polygons = Vec::with_capacity(polygons.len());
+  for (i, polygon) in polygons.iter().enumerate() {
+    let cut_lons: Vec<f64> = polygon
+      .holes
+      .iter()
+      .filter(|hole| {
+        polygons.iter().enumerate().any(|(j, other)| {
+          let (lon, lat) = other.exterior[0];
+          j != i
+            && [0.0, -2.0 * PI, 2.0 * PI]
+              .iter()
+              .any(|shift| contains(hole, (lon + shift, lat)))
+        })
+      })
+      .filter_map(|hole| {
+        // Middle of a non meridian edge
+        (0..hole.len())
+          .map(|k| (hole[k].0, hole[(k + 1) % hole.len()].0))
+          .find(|(lon1, lon2)| lon1 != lon2)
+          .map(|(lon1, lon2)| 0.5 * (lon1 + lon2))
+      })
+      .collect();
+    split_polygons.extend(polygon.split_at_meridians(&cut_lons));
+  }
+  split_polygons
+}
+
+fn write_polygon<W: Write>(
+  writer: &mut W,
+  prefix: &str,
+  ring: &[(f64, f64)],
+) -> Result<(), Ds9Error> {
+  write!(writer, "{}polygon(", prefix)?;
+  for (i, (lon, lat)) in ring.iter().enumerate() {
+    if i > 0 {
+      write!(writer, ",")?;
+    }
+    write!(writer, "{},{}", lon.to_degrees(), lat.to_degrees())?;
+  }
+  writeln!(writer, ")").map_err(Ds9Error::Io)
+}
+
+fn write_crtf_poly<W: Write>(
+  writer: &mut W,
+  prefix: &str,
+  ring: &[(f64, f64)],
+) -> Result<(), Ds9Error> {
+  write!(writer, "{}poly [", prefix)?;
+  for (i, (lon, lat)) in ring.iter().enumerate() {
+    if i > 0 {
+      write!(writer, ", ")?;
+    }
+    write!(writer, "[{}deg, {}deg]", lon.to_degrees(), lat.to_degrees())?;
+  }
+  writeln!(writer, "]").map_err(Ds9Error::Io)
+}
+
+/// Build a S-MOC of given depth from a DS9 region file content.
+///
+/// # Input
+/// - `input`: the region file content
+/// - `depth`: the MOC depth
+/// - `delta_depth`: the difference between the MOC depth and the depth at which the computations
+///   are made for circles, ellipses and annuli (should remain quite small).
+/// - `selection`: select BMOC cells to keep in the MOC (exclude shapes use the dual selection,
+///   e.g. cells partially overlapping an exclude shape are kept when selecting all cells)
+///
+/// # Supported shapes
+/// * `circle(lon, lat, radius)`
+/// * `ellipse(lon, lat, r1, r2, angle)`
+/// * `box(lon, lat, width, height, angle)`
+/// * `polygon(lon1, lat1, lon2, lat2, ...)`
+/// * `annulus(lon, lat, r1, r2, ..., rn)`
+///
+/// Shapes without area (`point`, `text`, `line`, ...) are ignored.
+/// Positions are either decimal degrees or sexagesimal (`hh:mm:ss`, `dd:mm:ss`, `12h34m56s`,
+/// `+12d34m56s`). Sizes are in degrees, unless followed by `"` (arcsec), `'` (arcmin), `d` (degrees)
+/// or `r` (radians). Angles are in degrees, counterclockwise from the longitude axis.
+pub fn from_ds9(
+  input: &str,
+  depth: u8,
+  delta_depth: u8,
+  selection: CellSelection,
+) -> Result<RangeMOC<u64, Hpx<u64>>, Ds9Error> {
+  if depth > Hpx::<u64>::MAX_DEPTH {
+    return Err(Ds9Error::WrongDepth {
+      depth,
+      depth_max: Hpx::<u64>::MAX_DEPTH,
+    });
+  }
+  if let CellSelection::Border = selection {
+    let all = from_ds9(input, depth, delta_depth, CellSelection::All)?;
+    let inside = from_ds9(input, depth, delta_depth, CellSelection::Inside)?;
+    return Ok(all.minus(&inside));
+  }
+  let mut include = RangeMOC::new_empty(depth);
+  let mut exclude = RangeMOC::new_empty(depth);
+  let mut unsupported_frame: Option<&str> = None;
+  for (line_num, line) in input.lines().enumerate() {
+    let line_num = line_num + 1;
+    // Remove comments and properties
+    let line = line.split('#').next().unwrap_or_default();
+    for region in line.split(';').map(str::trim).filter(|r| !r.is_empty()) {
+      let lowercase = region.to_lowercase();
+      match lowercase.as_str() {
+        "fk5" | "icrs" | "j2000" => unsupported_frame = None,
+        "fk4" | "b1950" | "galactic" | "ecliptic" | "image" | "physical" | "linear"
+        | "amplifier" | "detector" | "wcs" => unsupported_frame = Some(region),
+        _ if lowercase.starts_with("global") => (),
+        _ => {
+          if let Some(frame) = unsupported_frame {
+            return Err(Ds9Error::UnsupportedFrame {
+              line: line_num,
+              found: frame.to_string(),
+            });
+          }
+          let (is_exclude, shape) = match region.strip_prefix('-') {
+            Some(shape) => (true, shape),
+            None => (false, region.strip_prefix('+').unwrap_or(region)),
+          };
+          let shape_selection = match (is_exclude, selection) {
+            (true, CellSelection::All) => CellSelection::Inside,
+            (true, CellSelection::Inside) => CellSelection::All,
+            (_, s) => s,
+          };
+          let name = shape
+            .split('(')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+          if NO_AREA_SHAPES.contains(&name.as_str()) {
+            continue;
+          }
+          if !SHAPES.contains(&name.as_str()) {
+            return Err(Ds9Error::UnsupportedShape {
+              line: line_num,
+              found: name,
+            });
+          }
+          let shape_moc =
+            shape_to_moc(shape, depth, delta_depth, shape_selection).map_err(|msg| {
+              Ds9Error::WrongRegion {
+                line: line_num,
+                region: region.to_string(),
+                msg,
+              }
+            })?;
+          if is_exclude {
+            exclude = exclude.or(&shape_moc);
+          } else {
+            include = include.or(&shape_moc);
+          }
+        }
+      }
+    }
+  }
+  Ok(include.minus(&exclude))
+}
+
+/// Build a S-MOC of given depth from a CASA region file (CRTF) content.
+///
+/// # Input
+/// Same as [from_ds9].
+///
+/// # Supported shapes
+/// * `circle [[lon, lat], r]`
+/// * `ellipse [[lon, lat], [b1, b2], pa]`, `b1` and `b2` being semi-axes, `b1` at the position
+///   angle `pa`
+/// * `box [[lon1, lat1], [lon2, lat2]]`, the two corners of the box
+/// * `centerbox [[lon, lat], [width, height]]`
+/// * `rotbox [[lon, lat], [width, height], pa]`, the box height being at the position angle `pa`
+/// * `poly [[lon1, lat1], [lon2, lat2], ...]`
+/// * `annulus [[lon, lat], [r1, r2]]`
+///
+/// Shapes without area (`symbol`, `text`, `line`, `vector`) and annotations (`ann` prefix)
+/// are ignored.
+/// Positions are either quantities (`10.5deg`, `0.2rad`) or sexagesimal (`12h34m56.7s`,
+/// `12:34:56.7`, `+12d34m56.7s`, `+12.34.56.7`). Sizes and angles are quantities (`deg`, `rad`,
+/// `arcmin`, `arcsec`, `mas`, `'` or `"`), values without unit being in degrees.
+/// Position angles are east of north.
+pub fn from_crtf(
+  input: &str,
+  depth: u8,
+  delta_depth: u8,
+  selection: CellSelection,
+) -> Result<RangeMOC<u64, Hpx<u64>>, Ds9Error> {
+  if depth > Hpx::<u64>::MAX_DEPTH {
+    return Err(Ds9Error::WrongDepth {
+      depth,
+      depth_max: Hpx::<u64>::MAX_DEPTH,
+    });
+  }
+  if let CellSelection::Border = selection {
+    let all = from_crtf(input, depth, delta_depth, CellSelection::All)?;
+    let inside = from_crtf(input, depth, delta_depth, CellSelection::Inside)?;
+    return Ok(all.minus(&inside));
+  }
+  let mut include = RangeMOC::new_empty(depth);
+  let mut exclude = RangeMOC::new_empty(depth);
+  let mut global_frame: Option<&str> = None;
+  for (line_num, line) in input.lines().enumerate() {
+    let line_num = line_num + 1;
+    let region = line.trim();
+    if region.is_empty() || region.starts_with('#') {
+      continue;
+    }
+    if let Some(props) = region.strip_prefix("global") {
+      if let Some(frame) = crtf_coord(props) {
+        global_frame = Some(frame);
+      }
+      continue;
+    }
+    let (is_exclude, shape) = match region.strip_prefix('-') {
+      Some(shape) => (true, shape),
+      None => (false, region.strip_prefix('+').unwrap_or(region)),
+    };
+    if shape
+      .strip_prefix("ann")
+      .is_some_and(|s| s.starts_with(char::is_whitespace))
+    {
+      continue;
+    }
+    let name = shape
+      .split(|c: char| c == '[' || c.is_whitespace())
+      .next()
+      .unwrap_or_default()
+      .to_lowercase();
+    if CRTF_NO_AREA_SHAPES.contains(&name.as_str()) {
+      continue;
+    }
+    if !CRTF_SHAPES.contains(&name.as_str()) {
+      return Err(Ds9Error::UnsupportedShape {
+        line: line_num,
+        found: name,
+      });
+    }
+    let wrong_region = |msg| Ds9Error::WrongRegion {
+      line: line_num,
+      region: region.to_string(),
+      msg,
+    };
+    let (params, props) = shape[name.len()..]
+      .trim_start()
+      .strip_prefix('[')
+      .ok_or_else(|| String::from("'[' not found"))
+      .and_then(parse_crtf_params)
+      .map_err(wrong_region)?;
+    if let Some(frame) = crtf_coord(props).or(global_frame) {
+      if !frame.eq_ignore_ascii_case("j2000") && !frame.eq_ignore_ascii_case("icrs") {
+        return Err(Ds9Error::UnsupportedFrame {
+          line: line_num,
+          found: frame.to_string(),
+        });
+      }
+    }
+    let shape_selection = match (is_exclude, selection) {
+      (true, CellSelection::All) => CellSelection::Inside,
+      (true, CellSelection::Inside) => CellSelection::All,
+      (_, s) => s,
+    };
+    let shape_moc = crtf_shape_to_moc(&name, &params, depth, delta_depth, shape_selection)
+      .map_err(wrong_region)?;
+    if is_exclude {
+      exclude = exclude.or(&shape_moc);
+    } else {
+      include = include.or(&shape_moc);
+    }
+  }
+  Ok(include.minus(&exclude))
+}
+
+fn shape_to_moc(
+  shape: &str,
+  depth: u8,
+  delta_depth: u8,
+  selection: CellSelection,
+) -> Result<RangeMOC<u64, Hpx<u64>>, String> {
+  let (name, params) = shape
+    .split_once('(')
+    .and_then(|(name, rest)| rest.split_once(')').map(|(params, _)| (name, params)))
+    .ok_or_else(|| String::from("parenthesis not found"))?;
+  let name = name.trim().to_lowercase();
+  let params: Vec<&str> = params
+    .split(|c: char| c == ',' || c.is_whitespace())
+    .filter(|p| !p.is_empty())
+    .collect();
+  let delta_depth = delta_depth.min(Hpx::<u64>::MAX_DEPTH - depth);
+  Ok(match name.as_str() {
+    "circle" => {
+      let [lon, lat, r] = n_params(&params)?;
+      let (lon, lat) = parse_position(lon, lat)?;
+      let r = parse_size(r)?;
+      check_range("radius", r, 0.0, PI)?;
+      RangeMOC::from_cone(lon, lat, r, depth, delta_depth, selection)
+    }
+    "ellipse" => {
+      let [lon, lat, r1, r2, angle] = n_params_with_angle(&params)?;
+      let (lon, lat) = parse_position(lon, lat)?;
+      let (a, b, pa) = major_minor_pa(parse_size(r1)?, parse_size(r2)?, parse_angle(angle)?);
+      check_range("semi-major axis", a, 0.0, 0.5 * PI)?;
+      check_range("semi-minor axis", b, 0.0, a)?;
+      RangeMOC::from_elliptical_cone(lon, lat, a, b, pa, depth, delta_depth, selection)
+    }
+    "box" => {
+      let [lon, lat, w, h, angle] = n_params_with_angle(&params)?;
+      let (lon, lat) = parse_position(lon, lat)?;
+      let (a, b, pa) = major_minor_pa(
+        0.5 * parse_size(w)?,
+        0.5 * parse_size(h)?,
+        parse_angle(angle)?,
+      );
+      check_range("half width", a, 0.0, 0.5 * PI)?;
+      check_range("half height", b, 0.0, a)?;
+      RangeMOC::from_box(lon, lat, a, b, pa, depth, selection)
+    }
+    "polygon" => {
+      if params.len() < 6 || params.len() % 2 == 1 {
+        return Err(String::from(
+          "a polygon needs an even number (>= 6) of parameters",
+        ));
+      }
+      let vertices = params
+        .chunks_exact(2)
+        .map(|lonlat| {
+          parse_position(lonlat[0], lonlat[1])
+            .map(|(lon, lat)| (lon.to_degrees(), lat.to_degrees()))
+        })
+        .collect::<Result<Vec<(f64, f64)>, String>>()?;
+      polygon_coverage_deg(&vertices, &[], depth, selection)?
+    }
+    "annulus" => {
+      if params.len() < 4 {
+        return Err(String::from("an annulus needs at least 4 parameters"));
+      }
+      let (lon, lat) = parse_position(params[0], params[1])?;
+      let r_int = parse_size(params[2])?;
+      let r_ext = parse_size(params[params.len() - 1])?;
+      check_range("outer radius", r_ext, 0.0, PI)?;
+      check_range("inner radius", r_int, 0.0, r_ext)?;
+      RangeMOC::from_ring(lon, lat, r_int, r_ext, depth, delta_depth, selection)
+    }
+    _ => return Err(format!("unsupported shape '{}'", name)),
+  })
+}
+
+fn n_params<'a, const N: usize>(params: &[&'a str]) -> Result<[&'a str; N], String> {
+  <[&str; N]>::try_from(params)
+    .map_err(|_| format!("{} parameters expected, found {}", N, params.len()))
+}
+
+/// Like [n_params] for shapes which last parameter is an optional angle (default `0`).
+fn n_params_with_angle<'a, const N: usize>(params: &[&'a str]) -> Result<[&'a str; N], String> {
+  if params.len() == N - 1 {
+    let mut with_angle = params.to_vec();
+    with_angle.push("0");
+    n_params(&with_angle)
+  } else {
+    n_params(params)
+  }
+}
+
+/// Check that `value` is in `]min, max]`.
+fn check_range(label: &str, value: f64, min: f64, max: f64) -> Result<(), String> {
+  if min < value && value <= max {
+    Ok(())
+  } else {
+    Err(format!(
+      "{} must be in ]{}, {}] deg. Actual: {} deg.",
+      label,
+      min.to_degrees(),
+      max.to_degrees(),
+      value.to_degrees()
+    ))
+  }
+}
+
+/// From the two semi-axes (the first one being along the longitude axis when the angle is `0`)
+/// and the DS9 angle (counterclockwise, from the longitude axis), returns the semi-major axis,
+/// the semi-minor axis and the position angle (east of north) of the semi-major axis, in `[0, pi[`.
+fn major_minor_pa(r_lon: f64, r_lat: f64, angle: f64) -> (f64, f64, f64) {
+  if r_lon >= r_lat {
+    (r_lon, r_lat, (angle + 0.5 * PI).rem_euclid(PI))
+  } else {
+    (r_lat, r_lon, angle.rem_euclid(PI))
+  }
+}
+
+/// Returns the position, in radians.
+fn parse_position(lon: &str, lat: &str) -> Result<(f64, f64), String> {
+  let lon = if lon.contains([':', 'h']) {
+    15.0 * parse_sexagesimal(lon)?
+  } else {
+    parse_deg(lon)?
+  };
+  let lat = if lat.contains(':') || lat.trim_end_matches('d').contains('d') {
+    parse_sexagesimal(lat)?
+  } else {
+    parse_deg(lat)?
+  };
+  check_position(lon, lat)
+}
+
+/// Check the position, in degrees, and returns it in radians.
+fn check_position(lon: f64, lat: f64) -> Result<(f64, f64), String> {
+  if !lon.is_finite() || !(-90.0..=90.0).contains(&lat) {
+    return Err(format!("wrong position: ({}, {})", lon, lat));
+  }
+  Ok((lon.to_radians().rem_euclid(2.0 * PI), lat.to_radians()))
+}
+
+/// Parse a decimal value in degrees, possibly followed by `d`.
+fn parse_deg(value: &str) -> Result<f64, String> {
+  value
+    .trim_end_matches('d')
+    .parse::<f64>()
+    .map_err(|e| format!("wrong value '{}': {}", value, e))
+}
+
+/// Parse `[+-]a:b:c` or `[+-]aXbYc[Z]`, with `X`, `Y` and `Z` in `h`, `d`, `m`, `s`, returning
+/// `a + b/60 + c/3600` (with the sign).
+fn parse_sexagesimal(value: &str) -> Result<f64, String> {
+  let (sign, unsigned) = match value.strip_prefix('-') {
+    Some(v) => (-1.0, v),
+    None => (1.0, value.strip_prefix('+').unwrap_or(value)),
+  };
+  let fields: Vec<&str> = unsigned
+    .split([':', 'h', 'd', 'm', 's'])
+    .filter(|f| !f.is_empty())
+    .collect();
+  if fields.is_empty() || fields.len() > 3 {
+    return Err(format!("wrong sexagesimal value '{}'", value));
+  }
+  fields
+    .iter()
+    .zip([1.0, 60.0, 3600.0])
+    .try_fold(0.0, |acc, (field, div)| {
+      field
+        .parse::<f64>()
+        .map(|v| acc + v / div)
+        .map_err(|e| format!("wrong sexagesimal value '{}': {}", value, e))
+    })
+    .map(|v| sign * v)
+}
+
+/// Returns the size, in radians.
+fn parse_size(value: &str) -> Result<f64, String> {
+  let parse = |v: &str| {
+    v.parse::<f64>()
+      .map_err(|e| format!("wrong size '{}': {}", value, e))
+  };
+  if let Some(v) = value.strip_suffix('"') {
+    parse(v).map(|v| (v / 3600.0).to_radians())
+  } else if let Some(v) = value.strip_suffix('\'') {
+    parse(v).map(|v| (v / 60.0).to_radians())
+  } else if let Some(v) = value.strip_suffix('r') {
+    parse(v)
+  } else {
+    parse(value.strip_suffix('d').unwrap_or(value)).map(f64::to_radians)
+  }
+}
+
+/// Returns the angle, in radians.
+fn parse_angle(value: &str) -> Result<f64, String> {
+  parse_deg(value).map(f64::to_radians)
+}
+
+/// A CRTF shape parameter: either a single value or a `[a, b]` pair.
+#[derive(Debug)]
+enum CrtfParam<'a> {
+  Value(&'a str),
+  Pair(&'a str, &'a str),
+}
+
+/// Parse the CRTF parameters following the opening `[` of the parameters list, returning the
+/// parameters and the remaining properties (e.g. `coord=J2000, color=red`).
+fn parse_crtf_params(mut input: &str) -> Result<(Vec<CrtfParam<'_>>, &str), String> {
+  let mut params = Vec::new();
+  loop {
+    input = input.trim_start();
+    let end = if let Some(pair) = input.strip_prefix('[') {
+      let (pair, rest) = pair
+        .split_once(']')
+        .ok_or_else(|| String::from("']' not found"))?;
+      let (a, b) = pair
+        .split_once(',')
+        .ok_or_else(|| format!("wrong pair '[{}]'", pair))?;
+      params.push(CrtfParam::Pair(a.trim(), b.trim()));
+      input = rest.trim_start();
+      input
+        .chars()
+        .next()
+        .ok_or_else(|| String::from("']' not found"))?
+    } else {
+      let i = input
+        .find([',', ']'])
+        .ok_or_else(|| String::from("']' not found"))?;
+      params.push(CrtfParam::Value(input[..i].trim()));
+      input = &input[i..];
+      input.chars().next().unwrap()
+    };
+    input = &input[1..];
+    match end {
+      ',' => continue,
+      ']' => return Ok((params, input.trim_start_matches([' ', '\t', ',']))),
+      c => return Err(format!("unexpected character '{}'", c)),
+    }
+  }
+}
+
+/// Returns the value of the `coord` property, if any.
+fn crtf_coord(props: &str) -> Option<&str> {
+  props
+    .split(',')
+    .filter_map(|prop| prop.split_once('='))
+    .find(|(key, _)| key.trim().eq_ignore_ascii_case("coord"))
+    .map(|(_, value)| value.trim())
+}
+
+fn crtf_shape_to_moc(
+  name: &str,
+  params: &[CrtfParam],
+  depth: u8,
+  delta_depth: u8,
+  selection: CellSelection,
+) -> Result<RangeMOC<u64, Hpx<u64>>, String> {
+  use CrtfParam::{Pair, Value};
+  let delta_depth = delta_depth.min(Hpx::<u64>::MAX_DEPTH - depth);
+  Ok(match (name, params) {
+    ("circle", [Pair(lon, lat), Value(r)]) => {
+      let (lon, lat) = parse_crtf_position(lon, lat)?;
+      let r = parse_crtf_quantity(r)?;
+      check_range("radius", r, 0.0, PI)?;
+      RangeMOC::from_cone(lon, lat, r, depth, delta_depth, selection)
+    }
+    ("ellipse", [Pair(lon, lat), Pair(b1, b2), Value(pa)]) => {
+      let (lon, lat) = parse_crtf_position(lon, lat)?;
+      let (a, b, pa) = major_minor_pa(
+        parse_crtf_quantity(b2)?,
+        parse_crtf_quantity(b1)?,
+        parse_crtf_quantity(pa)?,
+      );
+      check_range("semi-major axis", a, 0.0, 0.5 * PI)?;
+      check_range("semi-minor axis", b, 0.0, a)?;
+      RangeMOC::from_elliptical_cone(lon, lat, a, b, pa, depth, delta_depth, selection)
+    }
+    ("box", [Pair(lon1, lat1), Pair(lon2, lat2)]) => {
+      let (lon1, lat1) = parse_crtf_position(lon1, lat1)?;
+      let (lon2, lat2) = parse_crtf_position(lon2, lat2)?;
+      // Longitude difference in [-pi, pi[
+      let dlon = (lon2 - lon1 + PI).rem_euclid(2.0 * PI) - PI;
+      let lat = 0.5 * (lat1 + lat2);
+      let lon = (lon1 + 0.5 * dlon).rem_euclid(2.0 * PI);
+      crtf_box(
+        lon,
+        lat,
+        dlon.abs() * lat.cos(),
+        (lat2 - lat1).abs(),
+        0.0,
+        depth,
+        selection,
+      )?
+    }
+    ("centerbox", [Pair(lon, lat), Pair(w, h)]) => {
+      let (lon, lat) = parse_crtf_position(lon, lat)?;
+      let (w, h) = (parse_crtf_quantity(w)?, parse_crtf_quantity(h)?);
+      crtf_box(lon, lat, w, h, 0.0, depth, selection)?
+    }
+    ("rotbox", [Pair(lon, lat), Pair(w, h), Value(pa)]) => {
+      let (lon, lat) = parse_crtf_position(lon, lat)?;
+      let (w, h) = (parse_crtf_quantity(w)?, parse_crtf_quantity(h)?);
+      crtf_box(lon, lat, w, h, parse_crtf_quantity(pa)?, depth, selection)?
+    }
+    ("poly", _) => {
+      let vertices = params
+        .iter()
+        .map(|param| match param {
+          Pair(lon, lat) => {
+            parse_crtf_position(lon, lat).map(|(lon, lat)| (lon.to_degrees(), lat.to_degrees()))
+          }
+          Value(v) => Err(format!("'[lon, lat]' vertex expected, found '{}'", v)),
+        })
+        .collect::<Result<Vec<(f64, f64)>, String>>()?;
+      if vertices.len() < 3 {
+        return Err(String::from("a polygon needs at least 3 vertices"));
+      }
+      polygon_coverage_deg(&vertices, &[], depth, selection)?
+    }
+    ("annulus", [Pair(lon, lat), Pair(r_int, r_ext)]) => {
+      let (lon, lat) = parse_crtf_position(lon, lat)?;
+      let (r_int, r_ext) = (parse_crtf_quantity(r_int)?, parse_crtf_quantity(r_ext)?);
+      check_range("outer radius", r_ext, 0.0, PI)?;
+      check_range("inner radius", r_int, 0.0, r_ext)?;
+      RangeMOC::from_ring(lon, lat, r_int, r_ext, depth, delta_depth, selection)
+    }
+    _ => return Err(format!("wrong parameters for shape '{}'", name)),
+  })
+}
+
+/// Box of given full width (along the longitude axis when `pa = 0`) and height, the height being
+/// at the position angle `pa`.
+fn crtf_box(
+  lon: f64,
+  lat: f64,
+  width: f64,
+  height: f64,
+  pa: f64,
+  depth: u8,
+  selection: CellSelection,
+) -> Result<RangeMOC<u64, Hpx<u64>>, String> {
+  let (a, b, pa) = major_minor_pa(0.5 * width, 0.5 * height, pa);
+  check_range("half width", a, 0.0, 0.5 * PI)?;
+  check_range("half height", b, 0.0, a)?;
+  Ok(RangeMOC::from_box(lon, lat, a, b, pa, depth, selection))
+}
+
+/// Returns the CRTF position, in radians.
+fn parse_crtf_position(lon: &str, lat: &str) -> Result<(f64, f64), String> {
+  let lon = if lon.contains([':', 'h']) {
+    15.0 * parse_sexagesimal(lon)?
+  } else {
+    parse_crtf_quantity(lon)?.to_degrees()
+  };
+  let lat = if lat.matches('.').count() >= 2 {
+    // +dd.mm.ss.s
+    parse_sexagesimal(&lat.replacen('.', ":", 2))?
+  } else if let Ok(lat) = parse_crtf_quantity(lat) {
+    lat.to_degrees()
+  } else {
+    parse_sexagesimal(lat)?
+  };
+  check_position(lon, lat)
+}
+
+/// Returns the CRTF angular quantity (size or angle), in radians, values without unit being in
+/// degrees.
+fn parse_crtf_quantity(value: &str) -> Result<f64, String> {
+  const UNITS: [(&str, f64); 7] = [
+    ("arcsec", 1.0 / 3600.0),
+    ("arcmin", 1.0 / 60.0),
+    ("mas", 1.0 / 3_600_000.0),
+    ("deg", 1.0),
+    ("rad", 180.0 / PI),
+    ("\"", 1.0 / 3600.0),
+    ("'", 1.0 / 60.0),
+  ];
+  let (v, deg_factor) = UNITS
+    .iter()
+    .find_map(|(unit, factor)| value.strip_suffix(unit).map(|v| (v, *factor)))
+    .unwrap_or((value, 1.0));
+  v.trim()
+    .parse::<f64>()
+    .map(|v| (v * deg_factor).to_radians())
+    .map_err(|e| format!("wrong quantity '{}': {}", value, e))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{
+    from_crtf, from_ds9, parse_crtf_position, parse_crtf_quantity, parse_position, parse_size,
+    to_crtf, to_ds9,
+  };
+  use crate::{
+    moc::range::{CellSelection, RangeMOC},
+    qty::Hpx,
+  };
+
+  #[test]
+  fn test_ds9_parse_coos() {
+    let (lon, lat) = parse_position("01:00:00", "-30:30:00").unwrap();
+    assert!((lon.to_degrees() - 15.0).abs() < 1e-12);
+    assert!((lat.to_degrees() + 30.5).abs() < 1e-12);
+    let (lon, lat) = parse_position("1h30m", "+10d30m36s").unwrap();
+    assert!((lon.to_degrees() - 22.5).abs() < 1e-12);
+    assert!((lat.to_degrees() - 10.51).abs() < 1e-12);
+    let (lon, lat) = parse_position("-10.5d", "20").unwrap();
+    assert!((lon.to_degrees() - 349.5).abs() < 1e-12);
+    assert!((lat.to_degrees() - 20.0).abs() < 1e-12);
+    assert!((parse_size("36\"").unwrap().to_degrees() - 0.01).abs() < 1e-12);
+    assert!((parse_size("6'").unwrap().to_degrees() - 0.1).abs() < 1e-12);
+    assert!((parse_size("0.5").unwrap().to_degrees() - 0.5).abs() < 1e-12);
+    assert!(parse_position("10", "95").is_err());
+  }
+
+  #[test]
+  fn test_ds9_shapes() {
+    let depth = 8;
+    let input = r#"# Region file format: DS9 version 4.1
+global color=green dashlist=8 3 width=1 font="helvetica 10 normal roman"
+fk5
+circle(10,20,1) # color=red text={my # circle}
+-circle(10,20,0.5")
+ellipse(100,-20,2,1,30); box(200,30,2,1,45)
+polygon(300,0,302,0,301,2)
+annulus(50,50,1,2,3)
+point(0,0) # point=circle
+"#;
+    let moc = from_ds9(input, depth, 2, CellSelection::All).unwrap();
+    let circle = RangeMOC::from_cone(
+      10_f64.to_radians(),
+      20_f64.to_radians(),
+      1_f64.to_radians(),
+      depth,
+      2,
+      CellSelection::All,
+    );
+    let ring = RangeMOC::from_ring(
+      50_f64.to_radians(),
+      50_f64.to_radians(),
+      1_f64.to_radians(),
+      3_f64.to_radians(),
+      depth,
+      2,
+      CellSelection::All,
+    );
+    // Exclusion smaller than a cell: nothing removed when selecting all cells
+    assert!(circle.minus(&moc).is_empty());
+    assert!(ring.minus(&moc).is_empty());
+    assert!(moc.is_in(100_f64.to_radians(), -20_f64.to_radians()));
+    assert!(moc.is_in(200_f64.to_radians(), 30_f64.to_radians()));
+    assert!(moc.is_in(301_f64.to_radians(), 1_f64.to_radians()));
+    assert!(!moc.is_in(50_f64.to_radians(), 50_f64.to_radians()));
+    assert!(!moc.is_in(0.0, 0.0));
+    // Ellipse angle: major axis along the longitude axis, 2 deg x 1 deg
+    let ellipse = from_ds9("ellipse(100,0,2,1,0)", depth, 2, CellSelection::Inside).unwrap();
+    assert!(ellipse.is_in(101.8_f64.to_radians(), 0.0));
+    assert!(!ellipse.is_in(100_f64.to_radians(), 1.8_f64.to_radians()));
+    let ellipse = from_ds9("ellipse(100,0,2,1,90)", depth, 2, CellSelection::Inside).unwrap();
+    assert!(!ellipse.is_in(101.8_f64.to_radians(), 0.0));
+    assert!(ellipse.is_in(100_f64.to_radians(), 1.8_f64.to_radians()));
+    // Exclusions are removed from the union of all includes, whatever their order
+    for input in [
+      "circle(0,0,1);-circle(0,0,0.5)",
+      "-circle(0,0,0.5);circle(0,0,1)",
+      "circle(0,0,1);-circle(0,0,0.5);circle(0,0,0.2)",
+    ] {
+      let moc = from_ds9(input, depth, 2, CellSelection::All).unwrap();
+      assert!(!moc.is_in(0.0, 0.0));
+      assert!(moc.is_in(0.75_f64.to_radians(), 0.0));
+    }
+    // Errors
+    assert!(from_ds9("galactic;circle(0,0,1)", depth, 2, CellSelection::All).is_err());
+    assert!(from_ds9("circle(0,0)", depth, 2, CellSelection::All).is_err());
+    assert!(from_ds9("panda(0,0,0,360,4,0,1,2)", depth, 2, CellSelection::All).is_err());
+  }
+
+  #[test]
+  fn test_ds9_round_trip() {
+    let depth = 6;
+    let cone = RangeMOC::<u64, Hpx<u64>>::from_cone(0.5, 0.3, 0.2, depth, 2, CellSelection::All);
+    let hole = RangeMOC::from_cone(0.5, 0.3, 0.12, depth, 2, CellSelection::All);
+    let island = RangeMOC::from_cone(0.5, 0.3, 0.03, depth, 2, CellSelection::All);
+    let cap = RangeMOC::from_cone(0.0, 1.5, 0.3, depth, 2, CellSelection::All);
+    let moc = cone.minus(&hole).or(&island).or(&cap);
+    let mut buff = Vec::new();
+    to_ds9(&moc, &mut buff).unwrap();
+    let ds9 = String::from_utf8(buff).unwrap();
+    // The component having a hole containing the island is split in two polygons
+    assert_eq!(ds9.lines().filter(|l| l.starts_with("-polygon")).count(), 0);
+    assert_eq!(ds9.lines().filter(|l| l.starts_with("polygon")).count(), 4);
+    // Great-circle edges slightly differ from the HEALPix cell edges
+    let all = from_ds9(&ds9, depth, 2, CellSelection::All).unwrap();
+    let inside = from_ds9(&ds9, depth, 2, CellSelection::Inside).unwrap();
+    assert!(moc.minus(&all).is_empty());
+    assert!(inside.minus(&moc).is_empty());
+    assert!(inside.is_in(0.5, 0.3));
+    assert!(inside.is_in(0.0, 1.5));
+    // Point between the island and the hole border
+    assert!(!all.is_in(0.5 + 0.075 / 0.3_f64.cos(), 0.3));
+    // Hole without island written as an exclusion
+    let mut buff = Vec::new();
+    to_ds9(&cone.minus(&hole), &mut buff).unwrap();
+    let ds9 = String::from_utf8(buff).unwrap();
+    assert_eq!(ds9.lines().filter(|l| l.starts_with("-polygon")).count(), 1);
+    let all = from_ds9(&ds9, depth, 2, CellSelection::All).unwrap();
+    assert!(!all.is_in(0.5, 0.3));
+  }
+
+  #[test]
+  fn test_crtf_parse_coos() {
+    let (lon, lat) = parse_crtf_position("01:00:00", "-30.30.00").unwrap();
+    assert!((lon.to_degrees() - 15.0).abs() < 1e-12);
+    assert!((lat.to_degrees() + 30.5).abs() < 1e-12);
+    let (lon, lat) = parse_crtf_position("1h30m00s", "+10d30m36s").unwrap();
+    assert!((lon.to_degrees() - 22.5).abs() < 1e-12);
+    assert!((lat.to_degrees() - 10.51).abs() < 1e-12);
+    let (lon, lat) = parse_crtf_position("-10.5deg", "0.5rad").unwrap();
+    assert!((lon.to_degrees() - 349.5).abs() < 1e-12);
+    assert!((lat - 0.5).abs() < 1e-12);
+    assert!((parse_crtf_quantity("36arcsec").unwrap().to_degrees() - 0.01).abs() < 1e-12);
+    assert!((parse_crtf_quantity("6'").unwrap().to_degrees() - 0.1).abs() < 1e-12);
+    assert!((parse_crtf_quantity("0.5").unwrap().to_degrees() - 0.5).abs() < 1e-12);
+    assert!(parse_crtf_position("10deg", "95deg").is_err());
+  }
+
+  #[test]
+  fn test_crtf_shapes() {
+    let depth = 8;
+    let input = r#"#CRTFv0 CASA Region Text Format version 0
+global coord=J2000, color=green
+circle [[10deg, 20deg], 1deg] coord=ICRS, color=red
+-circle [[10deg, 20deg], 0.5arcsec]
+ellipse [[100deg, -20deg], [1deg, 2deg], 30deg]
+centerbox [[200deg, 30deg], [2deg, 1deg]]
+rotbox [[250deg, 30deg], [2deg, 1deg], 45deg]
+box [[149deg, -1deg], [151deg, 1deg]]
+poly [[300deg, 0deg], [302deg, 0deg], [301deg, 2deg]]
+annulus [[50deg, 50deg], [1deg, 3deg]]
+symbol [[0deg, 0deg], .]
+ann circle [[0deg, 0deg], 1deg]
+"#;
+    let moc = from_crtf(input, depth, 2, CellSelection::All).unwrap();
+    let circle = RangeMOC::from_cone(
+      10_f64.to_radians(),
+      20_f64.to_radians(),
+      1_f64.to_radians(),
+      depth,
+      2,
+      CellSelection::All,
+    );
+    assert!(circle.minus(&moc).is_empty());
+    assert!(moc.is_in(100_f64.to_radians(), -20_f64.to_radians()));
+    assert!(moc.is_in(200_f64.to_radians(), 30_f64.to_radians()));
+    assert!(moc.is_in(250_f64.to_radians(), 30_f64.to_radians()));
+    assert!(moc.is_in(150.9_f64.to_radians(), 0.9_f64.to_radians()));
+    assert!(moc.is_in(301_f64.to_radians(), 1_f64.to_radians()));
+    assert!(moc.is_in(52_f64.to_radians(), 50_f64.to_radians()));
+    assert!(!moc.is_in(50_f64.to_radians(), 50_f64.to_radians()));
+    assert!(!moc.is_in(0.0, 0.0));
+    // Ellipse: first semi-axis at the position angle (east of north)
+    let ellipse = "ellipse [[100deg, 0deg], [2deg, 1deg], 0deg]";
+    let ellipse = from_crtf(ellipse, depth, 2, CellSelection::Inside).unwrap();
+    assert!(!ellipse.is_in(101.8_f64.to_radians(), 0.0));
+    assert!(ellipse.is_in(100_f64.to_radians(), 1.8_f64.to_radians()));
+    let ellipse = "ellipse [[100deg, 0deg], [2deg, 1deg], 90deg]";
+    let ellipse = from_crtf(ellipse, depth, 2, CellSelection::Inside).unwrap();
+    assert!(ellipse.is_in(101.8_f64.to_radians(), 0.0));
+    assert!(!ellipse.is_in(100_f64.to_radians(), 1.8_f64.to_radians()));
+    // Exclusions are removed from the union of all includes, whatever their order
+    let moc = from_crtf(
+      "-circle [[0deg, 0deg], 0.5deg]\ncircle [[0deg, 0deg], 1deg]",
+      depth,
+      2,
+      CellSelection::All,
+    )
+    .unwrap();
+    assert!(!moc.is_in(0.0, 0.0));
+    assert!(moc.is_in(0.75_f64.to_radians(), 0.0));
+    // Errors
+    for input in [
+      "global coord=GALACTIC\ncircle [[0deg, 0deg], 1deg]",
+      "circle [[0deg, 0deg], 1deg] coord=B1950",
+      "circle [[0deg, 0deg]]",
+      "circle [[0deg, 0deg], 1deg",
+      "rectangle [[0deg, 0deg], [1deg, 1deg]]",
+    ] {
+      assert!(from_crtf(input, depth, 2, CellSelection::All).is_err());
+    }
+  }
+
+  #[test]
+  fn test_crtf_round_trip() {
+    let depth = 6;
+    let cone = RangeMOC::<u64, Hpx<u64>>::from_cone(0.5, 0.3, 0.2, depth, 2, CellSelection::All);
+    let hole = RangeMOC::from_cone(0.5, 0.3, 0.12, depth, 2, CellSelection::All);
+    let moc = cone.minus(&hole);
+    let mut buff = Vec::new();
+    to_crtf(&moc, &mut buff).unwrap();
+    let crtf = String::from_utf8(buff).unwrap();
+    assert_eq!(crtf.lines().filter(|l| l.starts_with("poly")).count(), 1);
+    assert_eq!(crtf.lines().filter(|l| l.starts_with("-poly")).count(), 1);
+    let all = from_crtf(&crtf, depth, 2, CellSelection::All).unwrap();
+    let inside = from_crtf(&crtf, depth, 2, CellSelection::Inside).unwrap();
+    assert!(moc.minus(&all).is_empty());
+    assert!(inside.minus(&moc).is_empty());
+    assert!(!all.is_in(0.5, 0.3));
+  }
+}
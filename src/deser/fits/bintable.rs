@@ -0,0 +1,658 @@
+//! Generic reading of the columns of a FITS BINTABLE extension (skymaps, multi-order maps,
+//! catalogues, ...).
+
+use std::{
+  io::{BufRead, Write},
+  str::{self, FromStr},
+};
+
+use byteorder::{BigEndian, ByteOrder};
+use log::warn;
+
+use super::{
+  checksum::{checksum_add, set_checksum, CHECKSUM_INIT},
+  common::{
+    bintable_header, check_keyword_and_parse_uint_val, check_keyword_and_val, consume_primary_hdu,
+    get_keyword, get_left_trimmed_value, get_str_val_no_quote, next_36_chunks_of_80_bytes,
+    str_keyword_record, write_data_padding, write_primary_hdu,
+  },
+  error::FitsError,
+  keywords::{CoordSys, MocKeywords, MocKeywordsMap},
+  skymap::UNSEEN,
+};
+
+/// Header of a BINTABLE extension: columns description, blank values and MOC keywords.
+pub(super) struct BinTableHeader {
+  pub(super) n_bytes_per_row: u64,
+  pub(super) n_rows: u64,
+  pub(super) columns: Vec<Column>,
+  /// Offset, in bytes, of each column in a row
+  offsets: Vec<usize>,
+  /// Blank value for floating point columns
+  bad_data: Option<f64>,
+  /// Blank value for integer columns not having a `TNULLn` keyword
+  blank: Option<i64>,
+  pub(super) moc_kws: MocKeywordsMap,
+  /// All keyword records following `TFIELDS` (but `END`), to be able to write back the table
+  cards: Vec<[u8; 80]>,
+}
+
+impl BinTableHeader {
+  /// Consume the primary HDU and parse the header of the BINTABLE extension following it.
+  pub(super) fn parse<R: BufRead>(mut reader: R) -> Result<Self, FitsError> {
+    let mut header_block = [b' '; 2880];
+    consume_primary_hdu(&mut reader, &mut header_block)?;
+    // Read the extention HDU
+    let mut it80 = next_36_chunks_of_80_bytes(&mut reader, &mut header_block)?;
+    // See Table 10 and 17 in https://fits.gsfc.nasa.gov/standard40/fits_standard40aa-le.pdf
+    check_keyword_and_val(it80.next().unwrap(), b"XTENSION", b"'BINTABLE'")?;
+    check_keyword_and_val(it80.next().unwrap(), b"BITPIX  ", b"8")?;
+    check_keyword_and_val(it80.next().unwrap(), b"NAXIS  ", b"2")?;
+    let n_bytes_per_row =
+      check_keyword_and_parse_uint_val::<u64>(it80.next().unwrap(), b"NAXIS1  ")?;
+    let n_rows = check_keyword_and_parse_uint_val::<u64>(it80.next().unwrap(), b"NAXIS2 ")?;
+    check_keyword_and_val(it80.next().unwrap(), b"PCOUNT  ", b"0")?;
+    check_keyword_and_val(it80.next().unwrap(), b"GCOUNT  ", b"1")?;
+    let n_cols = check_keyword_and_parse_uint_val::<u64>(it80.next().unwrap(), b"TFIELDS ")?;
+    // Read columns and MOC keywords
+    let mut columns = vec![Column::default(); n_cols as usize];
+    let mut bad_data: Option<f64> = None;
+    let mut blank: Option<i64> = None;
+    let mut moc_kws = MocKeywordsMap::new();
+    let mut cards: Vec<[u8; 80]> = Vec::new();
+    'hr: loop {
+      for kw_record in &mut it80 {
+        if &kw_record[0..4] != b"END " {
+          cards.push(kw_record.try_into().unwrap());
+        }
+        let keyword = get_keyword(kw_record);
+        // Column keywords first, since 'TTYPE1' and 'TFORM1' are also MOC keywords
+        if let Some(i) = column_index(keyword, b"TTYPE", &columns) {
+          columns[i].name = String::from_utf8_lossy(get_str_val_no_quote(kw_record)?).to_string();
+        } else if let Some(i) = column_index(keyword, b"TUNIT", &columns) {
+          columns[i].unit =
+            Some(String::from_utf8_lossy(get_str_val_no_quote(kw_record)?).to_string());
+        } else if let Some(i) = column_index(keyword, b"TFORM", &columns) {
+          columns[i].tform = Some(TForm::parse(get_str_val_no_quote(kw_record)?)?);
+        } else if let Some(i) = column_index(keyword, b"TNULL", &columns) {
+          columns[i].tnull = Some(parse_num_val::<i64>(kw_record)?);
+        } else if keyword == b"BAD_DATA" {
+          bad_data = Some(parse_num_val::<f64>(kw_record)?);
+        } else if keyword == b"BLANK   " {
+          blank = Some(parse_num_val::<i64>(kw_record)?);
+        } else if let Some(mkw) = MocKeywords::is_moc_kw(kw_record) {
+          // Parse only MOC related keywords and ignore others
+          if let Some(previous_mkw) = moc_kws.insert(mkw?) {
+            // A FITS keyword MUST BE uniq (I may be more relax here, taking the last one and not complaining)
+            // return Err(FitsError::MultipleKeyword(previous_mkw.keyword_str().to_string()))
+            warn!(
+              "Keyword '{}' found more than once in a same HDU! We use the first occurrence.",
+              previous_mkw.keyword_str()
+            );
+            moc_kws.insert(previous_mkw);
+          }
+          // else keyword added without error
+        } else if &kw_record[0..4] == b"END " {
+          break 'hr;
+        }
+      }
+      // Read next 2880 bytes
+      it80 = next_36_chunks_of_80_bytes(&mut reader, &mut header_block)?;
+    }
+    // Check columns
+    let mut offsets = Vec::with_capacity(columns.len());
+    let mut n_bytes_sum = 0_u64;
+    for (i, col) in columns.iter().enumerate() {
+      offsets.push(n_bytes_sum as usize);
+      n_bytes_sum += col.tform(i)?.n_bytes();
+    }
+    if n_bytes_sum != n_bytes_per_row {
+      return Err(FitsError::Custom(format!(
+        "Sum of the columns sizes {} do not match NAXIS1 {}",
+        n_bytes_sum, n_bytes_per_row
+      )));
+    }
+    Ok(Self {
+      n_bytes_per_row,
+      n_rows,
+      columns,
+      offsets,
+      bad_data,
+      blank,
+      moc_kws,
+      cards,
+    })
+  }
+
+  /// Returns the index of the column of given name (case insensitive), if any.
+  pub(super) fn position(&self, name: &str) -> Option<usize> {
+    self
+      .columns
+      .iter()
+      .position(|col| col.name.eq_ignore_ascii_case(name))
+  }
+
+  /// Returns the index of the column of given name (case insensitive), or an error listing the
+  /// available columns.
+  pub(super) fn find_column(&self, name: &str) -> Result<usize, FitsError> {
+    self.position(name).ok_or_else(|| {
+      FitsError::Custom(format!(
+        "Column '{}' not found. Available columns: {:?}",
+        name,
+        self
+          .columns
+          .iter()
+          .map(|col| col.name.as_str())
+          .collect::<Vec<&str>>()
+      ))
+    })
+  }
+
+  /// Returns a reader of the values of the column of index `icol`, checking that the column
+  /// type is one of the `expected` types.
+  pub(super) fn value_reader(
+    &self,
+    icol: usize,
+    expected: &[u8],
+    expected_str: &str,
+  ) -> Result<ValueReader, FitsError> {
+    let tform = self
+      .columns
+      .get(icol)
+      .ok_or_else(|| {
+        FitsError::Custom(format!(
+          "Column index {} out of range: the table has {} columns.",
+          icol,
+          self.columns.len()
+        ))
+      })?
+      .tform(icol)?;
+    tform.check_type(icol, expected, expected_str)?;
+    Ok(ValueReader {
+      offset: self.offsets[icol],
+      tform,
+      null: self.columns[icol].tnull.or(self.blank),
+      bad_data: self.bad_data.unwrap_or(UNSEEN),
+    })
+  }
+}
+
+/// Streaming reader of the rows of a FITS BINTABLE extension (e.g. a catalogue) following a
+/// primary HDU containing no data.
+/// Rows are returned as raw bytes, from which column values are extracted using
+/// [ValueReader]s. Selected rows can be written back in a new FITS file with [Self::write_rows].
+pub struct BinTableReader<R: BufRead> {
+  /// Reader
+  reader: R,
+  /// Table header
+  header: BinTableHeader,
+  /// Number of rows already returned
+  n_rows_consumed: u64,
+}
+
+impl<R: BufRead> BinTableReader<R> {
+  /// Reads the primary HDU and the BINTABLE header, the reader is then ready to read the rows.
+  pub fn open(mut reader: R) -> Result<Self, FitsError> {
+    let header = BinTableHeader::parse(&mut reader)?;
+    Ok(Self {
+      reader,
+      header,
+      n_rows_consumed: 0,
+    })
+  }
+
+  /// Number of rows in the table (`NAXIS2`)
+  pub fn n_rows(&self) -> u64 {
+    self.header.n_rows
+  }
+
+  /// Number of bytes in a row (`NAXIS1`)
+  pub fn n_bytes_per_row(&self) -> usize {
+    self.header.n_bytes_per_row as usize
+  }
+
+  /// Coordinate system (`COORDSYS`) of the table, if any.
+  pub fn coordsys(&self) -> Option<CoordSys> {
+    match self.header.moc_kws.get::<CoordSys>() {
+      Some(MocKeywords::CoordSys(coordsys)) => Some(*coordsys),
+      _ => None,
+    }
+  }
+
+  /// Name (`TTYPEn`) of each column
+  pub fn column_names(&self) -> Vec<&str> {
+    self
+      .header
+      .columns
+      .iter()
+      .map(|col| col.name.as_str())
+      .collect()
+  }
+
+  /// Returns the index of the column of given name (case insensitive), if any.
+  pub fn column_index(&self, name: &str) -> Option<usize> {
+    self.header.position(name)
+  }
+
+  /// Returns a reader of the values of the column of index `icol` (starting at 0).
+  /// The column must be of type 'B', 'I', 'J', 'K', 'E', 'D' or 'A' (text).
+  pub fn column_reader(&self, icol: usize) -> Result<ValueReader, FitsError> {
+    self
+      .header
+      .value_reader(icol, b"BIJKEDA", "['B', 'I', 'J', 'K', 'E', 'D' or 'A']")
+  }
+
+  /// Same as [Self::column_reader], the column being given by its name (case insensitive).
+  pub fn column_reader_by_name(&self, name: &str) -> Result<ValueReader, FitsError> {
+    self
+      .header
+      .find_column(name)
+      .and_then(|icol| self.column_reader(icol))
+  }
+
+  /// Writes a FITS file made of an empty primary HDU followed by a BINTABLE having the header
+  /// of the table being read, but the number of rows.
+  /// The `CHECKSUM` and `DATASUM` keywords of the table being read, if any, are recomputed from
+  /// the written rows.
+  ///
+  /// # Params
+  /// * `rows`: the concatenated bytes of the rows to be written, e.g. filtered rows
+  /// * `writer`: the output stream
+  pub fn write_rows<W: Write>(&self, rows: &[u8], mut writer: W) -> Result<(), FitsError> {
+    let n_bytes_per_row = self.header.n_bytes_per_row;
+    let n_rows = rows.len() as u64 / n_bytes_per_row.max(1);
+    if n_bytes_per_row == 0 || n_rows * n_bytes_per_row != rows.len() as u64 {
+      return Err(FitsError::Custom(format!(
+        "Number of bytes {} not compatible with the number of bytes per row {}.",
+        rows.len(),
+        n_bytes_per_row
+      )));
+    }
+    // Remove the checksum keywords, no more valid since the data changes
+    let is_checksum_card = |card: &[u8; 80]| matches!(get_keyword(card), b"CHECKSUM" | b"DATASUM ");
+    let cards: Vec<[u8; 80]> = self
+      .header
+      .cards
+      .iter()
+      .filter(|card| !is_checksum_card(card))
+      .cloned()
+      .collect();
+    let with_checksum = cards.len() != self.header.cards.len();
+    // The padding zeros do not change the sum
+    let datasum = checksum_add(0, rows);
+    let checksum_cards = if with_checksum {
+      vec![
+        str_keyword_record(b"DATASUM ", &datasum.to_string())?,
+        str_keyword_record(b"CHECKSUM", CHECKSUM_INIT)?,
+      ]
+    } else {
+      vec![]
+    };
+    let mut header = bintable_header(
+      n_bytes_per_row,
+      n_rows,
+      self.header.columns.len() as u64,
+      &cards,
+      MocKeywordsMap::new(),
+      &checksum_cards,
+    )?;
+    if with_checksum {
+      set_checksum(&mut header, datasum)?;
+    }
+    write_primary_hdu(&mut writer)?;
+    writer.write_all(&header)?;
+    writer.write_all(rows)?;
+    write_data_padding(&mut writer, rows.len() as u64)
+  }
+}
+
+impl<R: BufRead> Iterator for BinTableReader<R> {
+  type Item = Result<Vec<u8>, FitsError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.n_rows_consumed < self.header.n_rows {
+      self.n_rows_consumed += 1;
+      let mut row = vec![0_u8; self.header.n_bytes_per_row as usize];
+      Some(
+        self
+          .reader
+          .read_exact(&mut row)
+          .map(|()| row)
+          .map_err(FitsError::Io),
+      )
+    } else {
+      None
+    }
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    let n_rows_remaining = (self.header.n_rows - self.n_rows_consumed) as usize;
+    (n_rows_remaining, Some(n_rows_remaining))
+  }
+}
+
+/// Returns the index, starting at 0, of the column the given indexed keyword (e.g. `TTYPE2`)
+/// refers to, or `None` if the keyword does not start with the given prefix or if the index
+/// is out of bounds.
+fn column_index(keyword: &[u8], prefix: &[u8], columns: &[Column]) -> Option<usize> {
+  keyword
+    .strip_prefix(prefix)
+    .and_then(|n| str::from_utf8(n).ok())
+    .and_then(|n| n.trim_end().parse::<usize>().ok())
+    .filter(|n| (1..=columns.len()).contains(n))
+    .map(|n| n - 1)
+}
+
+/// Parse a (possibly signed and/or floating point) numerical value.
+fn parse_num_val<T: FromStr>(keyword_record: &[u8]) -> Result<T, FitsError> {
+  let value = String::from_utf8_lossy(get_left_trimmed_value(keyword_record)).to_string();
+  // Remove the possible comment; and FITS allows 'D' as exponent character
+  let value = value
+    .split('/')
+    .next()
+    .unwrap_or("")
+    .trim()
+    .replace('D', "E");
+  value.parse::<T>().map_err(|_| {
+    FitsError::Custom(format!(
+      "Numerical value not found in keyword record '{}'",
+      String::from_utf8_lossy(keyword_record).trim_end()
+    ))
+  })
+}
+
+/// BINTABLE column, as described by its `TTYPEn`, `TUNITn`, `TFORMn` and `TNULLn` keywords.
+#[derive(Debug, Default, Clone)]
+pub(super) struct Column {
+  pub(super) name: String,
+  pub(super) unit: Option<String>,
+  tform: Option<TForm>,
+  tnull: Option<i64>,
+}
+impl Column {
+  pub(super) fn tform(&self, icol: usize) -> Result<TForm, FitsError> {
+    self
+      .tform
+      .ok_or_else(|| FitsError::MissingKeyword(format!("TFORM{}", icol + 1)))
+  }
+}
+
+/// Content of a `TFORMn` keyword: a repeat count followed by a data type character.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct TForm {
+  pub(super) repeat: u64,
+  pub(super) dtype: u8,
+}
+impl TForm {
+  fn parse(tform: &[u8]) -> Result<Self, FitsError> {
+    let i = tform
+      .iter()
+      .position(|c| !c.is_ascii_digit())
+      .ok_or_else(|| {
+        FitsError::Custom(format!(
+          "No type in TFORM '{}'",
+          String::from_utf8_lossy(tform)
+        ))
+      })?;
+    let repeat = if i == 0 {
+      1
+    } else {
+      String::from_utf8_lossy(&tform[..i])
+        .parse::<u64>()
+        .map_err(|e| {
+          FitsError::WrongUintValue(
+            format!("TFORM '{}' repeat count", String::from_utf8_lossy(tform)),
+            e,
+          )
+        })?
+    };
+    Ok(Self {
+      repeat,
+      dtype: tform[i],
+    })
+  }
+
+  /// Number of bytes of a single element
+  fn elem_n_bytes(&self) -> u64 {
+    match self.dtype {
+      b'L' | b'B' | b'A' => 1,
+      b'I' => 2,
+      b'J' | b'E' => 4,
+      b'K' | b'D' | b'C' | b'P' => 8,
+      b'M' | b'Q' => 16,
+      _ => 0,
+    }
+  }
+
+  /// Number of bytes of the column
+  fn n_bytes(&self) -> u64 {
+    match self.dtype {
+      b'X' => self.repeat.div_ceil(8),
+      _ => self.repeat * self.elem_n_bytes(),
+    }
+  }
+
+  pub(super) fn check_type(
+    &self,
+    icol: usize,
+    expected: &[u8],
+    expected_str: &str,
+  ) -> Result<(), FitsError> {
+    if expected.contains(&self.dtype) {
+      Ok(())
+    } else {
+      Err(FitsError::UnexpectedValue(
+        format!("TFORM{}", icol + 1),
+        String::from(expected_str),
+        String::from(self.dtype as char),
+      ))
+    }
+  }
+}
+
+/// Read values of a given column in a row, taking care of blank values.
+pub struct ValueReader {
+  /// Offset of the column in the row, in bytes
+  offset: usize,
+  pub(super) tform: TForm,
+  /// Blank value for integer columns
+  null: Option<i64>,
+  /// Blank value for floating point columns (in addition to `NaN` and `UNSEEN`)
+  bad_data: f64,
+}
+impl ValueReader {
+  /// Returns the integer value of index `k` in the column (the column must be of type 'B', 'I', 'J' or 'K').
+  pub(super) fn read_int(&self, row: &[u8], k: usize) -> i64 {
+    let n = self.tform.elem_n_bytes() as usize;
+    let from = self.offset + k * n;
+    let bytes = &row[from..from + n];
+    match self.tform.dtype {
+      b'B' => bytes[0] as i64,
+      b'I' => BigEndian::read_i16(bytes) as i64,
+      b'J' => BigEndian::read_i32(bytes) as i64,
+      _ => BigEndian::read_i64(bytes),
+    }
+  }
+
+  /// Returns the value of index `k` in the column, or `None` if it is a blank value.
+  /// The value of a text ('A') column is the parsed (whole) text, if it is a number.
+  pub fn read(&self, row: &[u8], k: usize) -> Option<f64> {
+    let n = self.tform.elem_n_bytes() as usize;
+    let from = self.offset + k * n;
+    let bytes = &row[from..from + n];
+    match self.tform.dtype {
+      b'A' => self
+        .read_str(row)
+        .and_then(|s| s.trim().parse::<f64>().ok()),
+      b'E' => Some(BigEndian::read_f32(bytes) as f64).filter(|val| !self.is_float_blank(*val)),
+      b'D' => Some(BigEndian::read_f64(bytes)).filter(|val| !self.is_float_blank(*val)),
+      _ => {
+        let val = self.read_int(row, k);
+        if self.null == Some(val) {
+          None
+        } else {
+          Some(val as f64)
+        }
+      }
+    }
+  }
+
+  /// Returns the text of a text ('A') column, without trailing spaces (or `NUL` characters),
+  /// or `None` if the column is not a text column or is not valid UTF-8.
+  pub fn read_str<'a>(&self, row: &'a [u8]) -> Option<&'a str> {
+    if self.tform.dtype == b'A' {
+      let bytes = &row[self.offset..self.offset + self.tform.repeat as usize];
+      str::from_utf8(bytes)
+        .ok()
+        .map(|s| s.trim_end_matches([' ', '\0']))
+    } else {
+      None
+    }
+  }
+
+  /// Blank values in `f32` columns are compared to blank values converted to `f32`.
+  fn is_float_blank(&self, val: f64) -> bool {
+    val.is_nan()
+      || [self.bad_data, UNSEEN]
+        .iter()
+        .any(|blank| val == *blank || val == (*blank as f32) as f64)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use std::io::{BufReader, Cursor};
+
+  use byteorder::{BigEndian, WriteBytesExt};
+
+  use super::{BinTableReader, TForm};
+  use crate::deser::fits::{
+    checksum::{checksum_add, CHECKSUM_OK},
+    common::{str_keyword_record, write_bintable_header, write_data_padding, write_primary_hdu},
+    keywords::MocKeywordsMap,
+  };
+
+  #[test]
+  fn test_bintable_read_filter_write() {
+    let col_kws = [
+      str_keyword_record(b"TTYPE1  ", "NAME").unwrap(),
+      str_keyword_record(b"TFORM1  ", "6A").unwrap(),
+      str_keyword_record(b"TTYPE2  ", "RA").unwrap(),
+      str_keyword_record(b"TFORM2  ", "D").unwrap(),
+      str_keyword_record(b"TUNIT2  ", "deg").unwrap(),
+      str_keyword_record(b"TTYPE3  ", "DEC").unwrap(),
+      str_keyword_record(b"TFORM3  ", "E").unwrap(),
+      str_keyword_record(b"TTYPE4  ", "FLAG").unwrap(),
+      str_keyword_record(b"TFORM4  ", "L").unwrap(),
+      str_keyword_record(b"TTYPE5  ", "MAG").unwrap(),
+      str_keyword_record(b"TFORM5  ", "J").unwrap(),
+      *b"TNULL5  =                  -99                                                  ",
+    ];
+    let mut data: Vec<u8> = Vec::new();
+    for (name, ra, dec, mag) in [
+      (b"star1 ", 10.0_f64, -5.0_f32, 12_i32),
+      (b"star2 ", 20.0, 5.0, -99),
+      (b"gal3  ", 30.0, 15.0, 18),
+    ] {
+      data.extend_from_slice(name);
+      data.write_f64::<BigEndian>(ra).unwrap();
+      data.write_f32::<BigEndian>(dec).unwrap();
+      data.push(b'T');
+      data.write_i32::<BigEndian>(mag).unwrap();
+    }
+    let mut buf: Vec<u8> = Vec::new();
+    write_primary_hdu(&mut buf).unwrap();
+    write_bintable_header(&mut buf, 23, 3, 5, &col_kws, MocKeywordsMap::new(), &[]).unwrap();
+    buf.extend_from_slice(&data);
+    write_data_padding(&mut buf, data.len() as u64).unwrap();
+
+    let reader = BinTableReader::open(BufReader::new(Cursor::new(buf))).unwrap();
+    assert_eq!(reader.n_rows(), 3);
+    assert_eq!(
+      reader.column_names(),
+      vec!["NAME", "RA", "DEC", "FLAG", "MAG"]
+    );
+    assert_eq!(reader.column_index("dec"), Some(2));
+    assert!(reader.column_reader(3).is_err()); // Logical column
+    assert!(reader.column_reader(5).is_err()); // Out of range
+    assert!(reader.column_reader_by_name("PMRA").is_err());
+    let name = reader.column_reader(0).unwrap();
+    let dec = reader.column_reader_by_name("DEC").unwrap();
+    let mag = reader.column_reader_by_name("MAG").unwrap();
+    let mut filtered: Vec<u8> = Vec::new();
+    let mut mags: Vec<Option<f64>> = Vec::new();
+    let mut names: Vec<String> = Vec::new();
+    let mut rows_reader = reader;
+    for row in &mut rows_reader {
+      let row = row.unwrap();
+      mags.push(mag.read(&row, 0));
+      names.push(name.read_str(&row).unwrap().to_string());
+      if dec.read(&row, 0).unwrap() > 0.0 {
+        filtered.extend_from_slice(&row);
+      }
+    }
+    assert_eq!(mags, vec![Some(12.0), None, Some(18.0)]);
+    assert_eq!(names, vec!["star1", "star2", "gal3"]);
+    // Write filtered rows and read them back
+    let mut out: Vec<u8> = Vec::new();
+    rows_reader.write_rows(&filtered, &mut out).unwrap();
+    assert_eq!(out.len() % 2880, 0);
+    assert!(rows_reader.write_rows(&filtered[1..], Vec::new()).is_err());
+    let reader = BinTableReader::open(BufReader::new(Cursor::new(out))).unwrap();
+    assert_eq!(reader.n_rows(), 2);
+    let ra = reader.column_reader_by_name("RA").unwrap();
+    let ras: Vec<f64> = reader
+      .map(|row| ra.read(&row.unwrap(), 0).unwrap())
+      .collect();
+    assert_eq!(ras, vec![20.0, 30.0]);
+  }
+
+  #[test]
+  fn test_bintable_write_rows_checksum() {
+    let col_kws = [
+      str_keyword_record(b"TTYPE1  ", "MAG").unwrap(),
+      str_keyword_record(b"TFORM1  ", "D").unwrap(),
+    ];
+    // Checksum keywords of the original table (values not checked when reading)
+    let checksum_kws = [
+      str_keyword_record(b"DATASUM ", "123").unwrap(),
+      str_keyword_record(b"CHECKSUM", "ZZZZZZZZZZZZZZZZ").unwrap(),
+    ];
+    let mut data: Vec<u8> = Vec::new();
+    for mag in [12.0_f64, 15.0, 18.0] {
+      data.write_f64::<BigEndian>(mag).unwrap();
+    }
+    for kws in [&checksum_kws[..], &[]] {
+      let mut buf: Vec<u8> = Vec::new();
+      write_primary_hdu(&mut buf).unwrap();
+      write_bintable_header(&mut buf, 8, 3, 1, &col_kws, MocKeywordsMap::new(), kws).unwrap();
+      buf.extend_from_slice(&data);
+      write_data_padding(&mut buf, data.len() as u64).unwrap();
+      let reader = BinTableReader::open(BufReader::new(Cursor::new(buf))).unwrap();
+      let mut out: Vec<u8> = Vec::new();
+      reader.write_rows(&data[8..], &mut out).unwrap();
+      let header = String::from_utf8_lossy(&out[2880..5760]).to_string();
+      assert!(header.contains("NAXIS2  =                    2"));
+      assert!(!header.contains("ZZZZ"));
+      assert_eq!(header.matches("CHECKSUM").count(), kws.len() / 2);
+      assert_eq!(header.matches("DATASUM").count(), kws.len() / 2);
+      if !kws.is_empty() {
+        // The 1's complement sum of the whole HDU is -0
+        assert_eq!(checksum_add(0, &out[2880..]), CHECKSUM_OK);
+        let datasum = checksum_add(0, &data[8..]);
+        assert!(header.contains(&format!("DATASUM = '{}'", datasum)));
+      }
+    }
+  }
+  #[test]
+  fn test_tform_parse() {
+    let tform = TForm::parse(b"16E").unwrap();
+    assert_eq!((tform.repeat, tform.dtype), (16, b'E'));
+    let tform = TForm::parse(b"K").unwrap();
+    assert_eq!((tform.repeat, tform.dtype), (1, b'K'));
+    assert!(TForm::parse(b"16").is_err());
+    // Repeat count overflowing a u64
+    assert!(TForm::parse(b"99999999999999999999E").is_err());
+  }
+}
@@ -0,0 +1,232 @@
+//! Computation and verification of the FITS `CHECKSUM` and `DATASUM` keywords, following
+//! Appendix J of the [FITS standard](https://fits.gsfc.nasa.gov/standard40/fits_standard40aa-le.pdf)
+//! (32-bit 1's complement checksum, encoded on 16 ASCII characters).
+
+use std::{io::Write, str};
+
+use crate::deser::fits::{
+  common::{get_keyword, get_str_val_no_quote},
+  error::FitsError,
+};
+
+/// Value of the `CHECKSUM` keyword while computing the checksum of an HDU.
+pub const CHECKSUM_INIT: &str = "0000000000000000";
+
+/// Value of the 32-bit 1's complement sum of an HDU having a valid `CHECKSUM` keyword
+/// (i.e. negative zero).
+pub const CHECKSUM_OK: u32 = 0xFFFFFFFF;
+
+/// Adds the given bytes, interpreted as big-endian 32-bit words, to the given 32-bit 1's
+/// complement sum.
+/// The number of bytes must be a multiple of 4 (which is the case of 2880 bytes FITS blocks),
+/// else the last incomplete word is completed with zeros.
+pub fn checksum_add(sum: u32, bytes: &[u8]) -> u32 {
+  let mut chunks = bytes.chunks_exact(4);
+  let mut sum = sum as u64;
+  for word in &mut chunks {
+    sum += u32::from_be_bytes([word[0], word[1], word[2], word[3]]) as u64;
+    // Fold the carry (never larger than 1 since both terms are < 2^32)
+    sum = (sum & 0xFFFFFFFF) + (sum >> 32);
+  }
+  let remainder = chunks.remainder();
+  if !remainder.is_empty() {
+    let mut word = [0_u8; 4];
+    word[..remainder.len()].copy_from_slice(remainder);
+    sum += u32::from_be_bytes(word) as u64;
+    sum = (sum & 0xFFFFFFFF) + (sum >> 32);
+  }
+  sum as u32
+}
+
+/// Writer computing the 32-bit 1's complement sum of the bytes written in the wrapped writer,
+/// e.g. to compute a `DATASUM` while streaming a data unit (or in a first pass using
+/// `std::io::sink()`).
+pub(super) struct ChecksumWriter<W: Write> {
+  writer: W,
+  sum: u32,
+  /// Bytes of the last incomplete 32-bit word
+  word: [u8; 4],
+  word_len: usize,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+  pub(super) fn new(writer: W) -> Self {
+    Self {
+      writer,
+      sum: 0,
+      word: [0_u8; 4],
+      word_len: 0,
+    }
+  }
+
+  /// Returns the sum of the bytes written so far, the last incomplete word being completed with
+  /// zeros (like the padding of a data unit).
+  pub(super) fn sum(&self) -> u32 {
+    checksum_add(self.sum, &self.word[..self.word_len])
+  }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    let n = self.writer.write(buf)?;
+    let mut bytes = &buf[..n];
+    if self.word_len > 0 {
+      let n_missing = (4 - self.word_len).min(bytes.len());
+      self.word[self.word_len..self.word_len + n_missing].copy_from_slice(&bytes[..n_missing]);
+      self.word_len += n_missing;
+      bytes = &bytes[n_missing..];
+      if self.word_len < 4 {
+        return Ok(n);
+      }
+      self.sum = checksum_add(self.sum, &self.word);
+      self.word_len = 0;
+    }
+    let n_complete = bytes.len() & !3;
+    self.sum = checksum_add(self.sum, &bytes[..n_complete]);
+    self.word_len = bytes.len() - n_complete;
+    self.word[..self.word_len].copy_from_slice(&bytes[n_complete..]);
+    Ok(n)
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    self.writer.flush()
+  }
+}
+
+/// Encodes the 1's complement of the given sum into 16 ASCII characters, to be used as the
+/// value of the `CHECKSUM` keyword.
+/// The sum must have been computed with the `CHECKSUM` keyword value set to [CHECKSUM_INIT].
+pub fn encode_checksum(sum: u32) -> String {
+  const EXCLUDE: [u8; 13] = [
+    0x3a, 0x3b, 0x3c, 0x3d, 0x3e, 0x3f, 0x40, 0x5b, 0x5c, 0x5d, 0x5e, 0x5f, 0x60,
+  ];
+  const OFFSET: u8 = 0x30; // '0'
+  let value = !sum;
+  let mut asc = [0_u8; 16];
+  for i in 0..4 {
+    let byte = ((value >> (24 - 8 * i)) & 0xFF) as u8;
+    let quotient = byte / 4 + OFFSET;
+    let remainder = byte % 4;
+    let mut ch = [quotient; 4];
+    ch[0] += remainder;
+    // Avoid ASCII punctuation, keeping the sum unchanged
+    let mut check = true;
+    while check {
+      check = false;
+      for j in (0..4).step_by(2) {
+        if EXCLUDE.contains(&ch[j]) || EXCLUDE.contains(&ch[j + 1]) {
+          ch[j] += 1;
+          ch[j + 1] -= 1;
+          check = true;
+        }
+      }
+    }
+    for (j, c) in ch.into_iter().enumerate() {
+      asc[4 * j + i] = c;
+    }
+  }
+  // Rotate right by one byte, to compensate for the FITS records starting on 4 bytes boundaries
+  let mut res = [0_u8; 16];
+  for (i, c) in res.iter_mut().enumerate() {
+    *c = asc[(i + 15) % 16];
+  }
+  // Made of ASCII digits and letters only
+  String::from_utf8_lossy(&res).to_string()
+}
+
+/// Replaces the value of the `CHECKSUM` keyword record of the given header (initially set to
+/// [CHECKSUM_INIT]) by the checksum of the HDU made of this header and of a data unit of given
+/// `datasum`.
+pub(super) fn set_checksum(header: &mut [u8], datasum: u32) -> Result<(), FitsError> {
+  let checksum = encode_checksum(checksum_add(datasum, header));
+  let keyword_record = header
+    .chunks_exact_mut(80)
+    .find(|keyword_record| get_keyword(keyword_record) == b"CHECKSUM")
+    .ok_or_else(|| FitsError::MissingKeyword(String::from("CHECKSUM")))?;
+  // The value starts at the 12th character, just after the opening quote: "CHECKSUM= '"
+  keyword_record[11..27].copy_from_slice(checksum.as_bytes());
+  Ok(())
+}
+
+/// Parses the value of a `DATASUM` keyword record, i.e. an unsigned 32-bit integer stored in a
+/// string.
+pub(super) fn parse_datasum(keyword_record: &[u8]) -> Result<u32, FitsError> {
+  let value = get_str_val_no_quote(keyword_record)?;
+  str::from_utf8(value)
+    .ok()
+    .and_then(|value| value.trim().parse::<u32>().ok())
+    .ok_or_else(|| {
+      FitsError::Custom(format!(
+        "DATASUM value is not a 32-bit unsigned integer: '{}'",
+        String::from_utf8_lossy(value)
+      ))
+    })
+}
+
+/// Verifies the `CHECKSUM` of an HDU from the sum of its header and from the value of its
+/// `DATASUM` keyword, i.e. without reading the data unit.
+/// Nothing is verified if the given keyword records do not contain both a `CHECKSUM` and a
+/// `DATASUM` keyword records.
+pub(super) fn verify_header_checksum(
+  header_sum: u32,
+  keyword_records: &[[u8; 80]],
+) -> Result<(), FitsError> {
+  let find = |keyword: &[u8]| {
+    keyword_records
+      .iter()
+      .find(|keyword_record| get_keyword(&keyword_record[..]) == keyword)
+  };
+  match (find(b"CHECKSUM"), find(b"DATASUM ")) {
+    (Some(_), Some(datasum_record)) => {
+      let datasum = parse_datasum(datasum_record)?;
+      if checksum_add(header_sum, &datasum.to_be_bytes()) == CHECKSUM_OK {
+        Ok(())
+      } else {
+        Err(FitsError::WrongChecksum(String::from("CHECKSUM")))
+      }
+    }
+    _ => Ok(()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_checksum() {
+    // Header made of a keyword record, the CHECKSUM keyword record and spaces
+    let mut header = [b' '; 2880];
+    header[0..30].copy_from_slice(b"SIMPLE  =                    T");
+    header[80..108].copy_from_slice(format!("CHECKSUM= '{}'", CHECKSUM_INIT).as_bytes());
+    header[160..163].copy_from_slice(b"END");
+    let data = [7_u8; 2880];
+    let datasum = checksum_add(0, &data);
+    let checksum = encode_checksum(checksum_add(datasum, &header));
+    assert_eq!(checksum.len(), 16);
+    assert!(checksum.bytes().all(|c| c.is_ascii_alphanumeric()));
+    header[91..107].copy_from_slice(checksum.as_bytes());
+    assert_eq!(checksum_add(datasum, &header), CHECKSUM_OK);
+    // A single bit change is detected
+    header[0] = b'T';
+    assert_ne!(checksum_add(datasum, &header), CHECKSUM_OK);
+  }
+
+  #[test]
+  fn test_checksum_writer() {
+    let data: Vec<u8> = (0..=255_u8).cycle().take(1001).collect();
+    let mut writer = ChecksumWriter::new(Vec::new());
+    // Writes not aligned on 32-bit words
+    for chunk in data.chunks(3) {
+      writer.write_all(chunk).unwrap();
+    }
+    assert_eq!(writer.sum(), checksum_add(0, &data));
+    assert_eq!(writer.writer, data);
+  }
+
+  #[test]
+  fn test_checksum_add_carry() {
+    assert_eq!(checksum_add(0xFFFFFFFF, &[0, 0, 0, 1]), 1);
+    assert_eq!(checksum_add(0, &[0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 2]), 2);
+  }
+}
@@ -9,7 +9,7 @@ use std::{
 
 use quick_error::ResultExt;
 
-use crate::deser::fits::error::FitsError;
+use crate::deser::fits::{error::FitsError, keywords::MocKeywordsMap};
 
 const VALUE_INDICATOR: &[u8; 2] = b"= ";
 
@@ -58,6 +58,91 @@ pub(super) fn write_uint_mandatory_keyword_record(dest: &mut [u8], keyword: &[u8
   dest[30 - val_bytes.len()..30].copy_from_slice(val_bytes);
 }
 
+/// Returns a keyword record containing the given string value.
+/// The value is padded with spaces to contain at least 8 characters, as recommended by the
+/// FITS standard.
+pub(super) fn str_keyword_record(keyword: &[u8; 8], val: &str) -> Result<[u8; 80], FitsError> {
+  let value_part = format!("'{:<8}'", val.replace('\'', "''"));
+  if !value_part.is_ascii() || value_part.len() > 70 {
+    return Err(FitsError::Custom(format!(
+      "String value of keyword '{}' not ASCII or too long: '{}'",
+      unsafe { str::from_utf8_unchecked(keyword) }.trim_end(),
+      val
+    )));
+  }
+  let mut keyword_record = [b' '; 80];
+  write_keyword_record(&mut keyword_record, keyword, &value_part);
+  Ok(keyword_record)
+}
+
+/// Writes the header of a BINTABLE extension made of `n_rows` rows of `n_bytes_per_row` bytes,
+/// and containing `n_cols` columns.
+/// The mandatory keyword records are followed by the columns keyword records (`TTYPEn`,
+/// `TFORMn`, ...), the MOC keyword records and by the given additional keyword records.
+/// The header may span over several 2880 bytes blocks.
+pub(super) fn write_bintable_header<W: Write>(
+  mut writer: W,
+  n_bytes_per_row: u64,
+  n_rows: u64,
+  n_cols: u64,
+  col_keyword_records: &[[u8; 80]],
+  moc_kw_map: MocKeywordsMap,
+  other_keyword_records: &[[u8; 80]],
+) -> Result<(), FitsError> {
+  let header = bintable_header(
+    n_bytes_per_row,
+    n_rows,
+    n_cols,
+    col_keyword_records,
+    moc_kw_map,
+    other_keyword_records,
+  )?;
+  writer.write_all(&header).map_err(FitsError::Io)
+}
+
+/// Same as [write_bintable_header], but returns the header bytes instead of writing them.
+pub(super) fn bintable_header(
+  n_bytes_per_row: u64,
+  n_rows: u64,
+  n_cols: u64,
+  col_keyword_records: &[[u8; 80]],
+  moc_kw_map: MocKeywordsMap,
+  other_keyword_records: &[[u8; 80]],
+) -> Result<Vec<u8>, FitsError> {
+  let n_records =
+    8 + col_keyword_records.len() + moc_kw_map.n_entries() + other_keyword_records.len() + 1;
+  let n_blocks = n_records.div_ceil(36);
+  let mut header = vec![b' '; n_blocks * 2880];
+  let mut it = header.chunks_mut(80);
+  it.next().unwrap()[0..20].copy_from_slice(b"XTENSION= 'BINTABLE'");
+  it.next().unwrap()[0..30].copy_from_slice(b"BITPIX  =                    8");
+  it.next().unwrap()[0..30].copy_from_slice(b"NAXIS   =                    2");
+  write_uint_mandatory_keyword_record(it.next().unwrap(), b"NAXIS1  ", n_bytes_per_row);
+  write_uint_mandatory_keyword_record(it.next().unwrap(), b"NAXIS2  ", n_rows);
+  it.next().unwrap()[0..30].copy_from_slice(b"PCOUNT  =                    0");
+  it.next().unwrap()[0..30].copy_from_slice(b"GCOUNT  =                    1");
+  write_uint_mandatory_keyword_record(it.next().unwrap(), b"TFIELDS ", n_cols);
+  for keyword_record in col_keyword_records {
+    it.next().unwrap().copy_from_slice(keyword_record);
+  }
+  moc_kw_map.write_all(&mut it)?;
+  for keyword_record in other_keyword_records {
+    it.next().unwrap().copy_from_slice(keyword_record);
+  }
+  it.next().unwrap()[0..3].copy_from_slice(b"END");
+  Ok(header)
+}
+
+/// Writes the zeros needed to complete the last 2880 bytes block of a data unit containing
+/// `n_bytes` bytes.
+pub(super) fn write_data_padding<W: Write>(mut writer: W, n_bytes: u64) -> Result<(), FitsError> {
+  let mod2880 = (n_bytes % 2880) as usize;
+  if mod2880 != 0 {
+    writer.write_all(&vec![0_u8; 2880 - mod2880])?;
+  }
+  Ok(())
+}
+
 // READ PART
 
 /// # Params
@@ -122,18 +207,6 @@ where
     .and_then(|()| parse_uint_val::<T>(keyword_record))
 }
 
-pub(super) fn check_keyword_and_get_str_val<'a>(
-  keyword_record: &'a [u8],
-  expected_kw: &[u8],
-) -> Result<&'a str, FitsError> {
-  check_expected_keyword(keyword_record, expected_kw)
-    .and_then(|()| check_for_value_indicator(keyword_record))
-    .and_then(|()| {
-      // We go unsafe because FITS headers are not supposed to contain non-ASCII chars
-      get_str_val_no_quote(keyword_record).map(|bytes| unsafe { str::from_utf8_unchecked(bytes) })
-    })
-}
-
 pub(super) fn check_expected_keyword(
   keyword_record: &[u8],
   expected: &[u8],
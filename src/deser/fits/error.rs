@@ -54,6 +54,9 @@ quick_error! {
     UnexpectedDepth(depth: u8, depth_max: u8) {
       display("unexpected depth. Max expected: {}. Actual: {}", depth_max, depth)
     }
+    WrongChecksum(keyword: String) {
+      display("Wrong {} value: the FITS file is probably corrupted.", keyword)
+    }
     Custom(msg: String) {
       display("FITS not valid: '{}'", msg)
     }
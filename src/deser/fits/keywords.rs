@@ -95,6 +95,30 @@ pub enum MocDim {
   TimeSpace,
   Frequency,
   FrequencySpace,
+  TimeFrequency,
+}
+impl MocDim {
+  /// Returns the `MOCDIM` of a 2D-MOC from the `MOCDIM` of each of its dimension,
+  /// or `None` if the given pair of dimensions is not supported.
+  pub fn from_2d(dim1: MocDim, dim2: MocDim) -> Option<MocDim> {
+    match (dim1, dim2) {
+      (MocDim::Time, MocDim::Space) => Some(MocDim::TimeSpace),
+      (MocDim::Frequency, MocDim::Space) => Some(MocDim::FrequencySpace),
+      (MocDim::Time, MocDim::Frequency) => Some(MocDim::TimeFrequency),
+      _ => None,
+    }
+  }
+
+  /// Returns the `MOCDIM` of each of the two dimensions of a 2D-MOC,
+  /// or `None` if `self` is a 1D-MOC dimension.
+  pub fn to_2d(&self) -> Option<(MocDim, MocDim)> {
+    match self {
+      MocDim::TimeSpace => Some((MocDim::Time, MocDim::Space)),
+      MocDim::FrequencySpace => Some((MocDim::Frequency, MocDim::Space)),
+      MocDim::TimeFrequency => Some((MocDim::Time, MocDim::Frequency)),
+      _ => None,
+    }
+  }
 }
 impl FitsCard for MocDim {
   const KEYWORD: &'static [u8; 8] = b"MOCDIM  ";
@@ -106,6 +130,7 @@ impl FitsCard for MocDim {
       b"TIME.SPACE" => Ok(MocDim::TimeSpace),
       b"FREQUENCY" => Ok(MocDim::Frequency),
       b"FREQUENCY.SPACE" => Ok(MocDim::FrequencySpace),
+      b"TIME.FREQUENCY" => Ok(MocDim::TimeFrequency),
       parsed_val => Err(Self::predefine_val_err(
         parsed_val,
         &[
@@ -114,6 +139,7 @@ impl FitsCard for MocDim {
           b"TIME.SPACE",
           b"FREQUENCY",
           b"FREQUENCY.SPACE",
+          b"TIME.FREQUENCY",
         ],
       )),
     }
@@ -126,6 +152,7 @@ impl FitsCard for MocDim {
       MocDim::TimeSpace => "'TIME.SPACE'",
       MocDim::Frequency => "'FREQUENCY'",
       MocDim::FrequencySpace => "'FREQUENCY.SPACE'",
+      MocDim::TimeFrequency => "'TIME.FREQUENCY'",
     })
   }
 }
@@ -169,6 +196,9 @@ impl FitsCard for Ordering {
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum CoordSys {
   ICRS, // C
+  // The two following values are not allowed in MOCs, only in HEALPix maps
+  GAL, // G
+  ECL, // E
 }
 impl FitsCard for CoordSys {
   const KEYWORD: &'static [u8; 8] = b"COORDSYS";
@@ -176,12 +206,18 @@ impl FitsCard for CoordSys {
   fn specific_parse_value(keyword_record: &[u8]) -> Result<Self, FitsError> {
     match get_str_val_no_quote(keyword_record)? {
       b"C" => Ok(CoordSys::ICRS),
-      parsed_val => Err(Self::predefine_val_err(parsed_val, &[b"C"])),
+      b"G" => Ok(CoordSys::GAL),
+      b"E" => Ok(CoordSys::ECL),
+      parsed_val => Err(Self::predefine_val_err(parsed_val, &[b"C", b"G", b"E"])),
     }
   }
 
   fn to_fits_value(&self) -> String {
-    String::from("'C'")
+    String::from(match self {
+      CoordSys::ICRS => "'C'",
+      CoordSys::GAL => "'G'",
+      CoordSys::ECL => "'E'",
+    })
   }
 }
 
@@ -541,6 +577,11 @@ impl MocKeywordsMap {
     self.entries[entry.index()].replace(entry)
   }
 
+  /// Returns the number of keywords in the map.
+  pub(super) fn n_entries(&self) -> usize {
+    self.entries.iter().filter(|v| v.is_some()).count()
+  }
+
   pub(super) fn get<T: MocCard>(&self /*, _phantom: PhantomData<T>*/) -> Option<&MocKeywords> {
     self.entries[T::INDEX as usize].as_ref()
   }
@@ -563,8 +604,13 @@ impl MocKeywordsMap {
   pub(super) fn check_coordsys(&self) -> Result<(), FitsError> {
     match self.get::<CoordSys>() {
       Some(MocKeywords::CoordSys(CoordSys::ICRS)) => Ok(()),
+      Some(MocKeywords::CoordSys(actual)) => Err(FitsError::UnexpectedValue(
+        CoordSys::keyword_string(),
+        String::from("C"),
+        actual.to_fits_value(),
+      )),
       None => Err(FitsError::MissingKeyword(CoordSys::keyword_string())),
-      _ => unreachable!(), // since there is only one elem in CoorSys enum
+      _ => unreachable!(), // since the key is CoordSys
     }
   }
 
@@ -584,23 +630,6 @@ impl MocKeywordsMap {
       _ => Err(FitsError::MissingKeyword(Ordering::keyword_string())),
     }
   }
-
-  pub(super) fn check_index_schema(&self, expected: IndexSchema) -> Result<(), FitsError> {
-    match self.get::<IndexSchema>() {
-      Some(MocKeywords::IndexSchema(actual)) => {
-        if *actual == expected {
-          Ok(())
-        } else {
-          Err(FitsError::UnexpectedValue(
-            IndexSchema::keyword_string(), // keyword
-            expected.to_fits_value(),      // expected
-            actual.to_fits_value(),        // actual
-          ))
-        }
-      }
-      _ => Err(FitsError::MissingKeyword(Ordering::keyword_string())),
-    }
-  }
 }
 
 #[derive(Debug)]
@@ -0,0 +1,467 @@
+//! Multi-Extension FITS (MEF) files containing several MOCs, possibly of different types
+//! (e.g. one S-MOC per CCD, one T-MOC per filter, ...).
+//! Each MOC is stored in its own BINTABLE HDU, usually identified by its `EXTNAME` or its `MOCID`.
+
+use std::{
+  convert::Infallible,
+  fmt::{self, Display},
+  io::{BufRead, Write},
+  str::{self, FromStr},
+};
+
+use crate::{
+  deser::fits::{
+    common::{
+      check_expected_value, consume_primary_hdu, get_keyword, get_left_trimmed_value,
+      index_of_last_digit, parse_uint_val, write_primary_hdu,
+    },
+    error::FitsError,
+    keywords::{MocKeywords, MocKeywordsMap},
+    metadata::MocMetadata,
+    read_moc_hdu, write_hpx_cells_fits_hdu, write_ranges2d_fits_hdu, write_ranges_fits_hdu,
+    MocIdxType,
+  },
+  idx::Idx,
+  moc::{CellMOCIterator, RangeMOCIterator},
+  moc2d::{RangeMOC2ElemIt, RangeMOC2Iterator},
+  qty::{Hpx, MocQty},
+};
+
+// WRITE PART
+
+/// Writes several MOCs in a same FITS file, each MOC being written in its own BINTABLE HDU.
+///
+/// # Example
+/// ```rust
+/// use moc::deser::fits::{mef::MocFitsWriter, metadata::MocMetadata};
+/// use moc::moc::{range::{CellSelection, RangeMOC}, RangeMOCIntoIterator};
+/// use moc::qty::{Hpx, Time};
+///
+/// let smoc = RangeMOC::<u64, Hpx<u64>>::from_cone(0.0, 0.0, 0.1, 8, 2, CellSelection::All);
+/// let tmoc = RangeMOC::<u64, Time<u64>>::new_empty(20);
+/// let mut metadata = MocMetadata::default();
+/// let mut writer = MocFitsWriter::new(Vec::<u8>::new()).unwrap();
+/// metadata.extname = Some(String::from("CCD1"));
+/// writer.write_ranges((&smoc).into_range_moc_iter(), &metadata).unwrap();
+/// metadata.extname = Some(String::from("EPOCHS"));
+/// writer.write_ranges((&tmoc).into_range_moc_iter(), &metadata).unwrap();
+/// assert_eq!(writer.n_hdus(), 2);
+/// let bytes = writer.into_inner();
+/// assert_eq!(bytes.len() % 2880, 0);
+/// ```
+pub struct MocFitsWriter<W: Write> {
+  writer: W,
+  n_hdus: usize,
+}
+
+impl<W: Write> MocFitsWriter<W> {
+  /// Writes the primary HDU, each MOC being then written in an extension HDU by the `write_*`
+  /// methods.
+  /// For best performances when writing in a file, use a `BufWriter` in input.
+  pub fn new(mut writer: W) -> Result<Self, FitsError> {
+    write_primary_hdu(&mut writer)?;
+    Ok(Self { writer, n_hdus: 0 })
+  }
+
+  /// Returns the number of MOC HDUs written so far.
+  pub fn n_hdus(&self) -> usize {
+    self.n_hdus
+  }
+
+  /// Writes a 1D MOC (S-MOC, T-MOC or F-MOC) in a new HDU, see
+  /// [ranges_to_fits_ivoa_with_metadata](super::ranges_to_fits_ivoa_with_metadata).
+  pub fn write_ranges<T, Q, I>(
+    &mut self,
+    moc_it: I,
+    metadata: &MocMetadata,
+  ) -> Result<(), FitsError>
+  where
+    T: Idx,
+    Q: MocQty<T>,
+    I: RangeMOCIterator<T, Qty = Q>,
+  {
+    write_ranges_fits_hdu(moc_it, metadata, &mut self.writer)?;
+    self.n_hdus += 1;
+    Ok(())
+  }
+
+  /// Writes a S-MOC in a new HDU using the `NUNIQ` ordering, see
+  /// [hpx_cells_to_fits_ivoa_with_metadata](super::hpx_cells_to_fits_ivoa_with_metadata).
+  pub fn write_hpx_cells<T, I>(
+    &mut self,
+    moc_it: I,
+    metadata: &MocMetadata,
+  ) -> Result<(), FitsError>
+  where
+    T: Idx,
+    I: CellMOCIterator<T, Qty = Hpx<T>>,
+  {
+    write_hpx_cells_fits_hdu(moc_it, metadata, &mut self.writer)?;
+    self.n_hdus += 1;
+    Ok(())
+  }
+
+  /// Writes a 2D-MOC (ST-MOC, SF-MOC or TF-MOC) in a new HDU, see
+  /// [ranges2d_to_fits_ivoa_with_metadata](super::ranges2d_to_fits_ivoa_with_metadata).
+  pub fn write_ranges2d<T, I, J, K, L>(
+    &mut self,
+    moc_it: L,
+    metadata: &MocMetadata,
+  ) -> Result<(), FitsError>
+  where
+    T: Idx,
+    I: RangeMOCIterator<T>,
+    J: RangeMOCIterator<T>,
+    K: RangeMOC2ElemIt<T, I::Qty, T, J::Qty, It1 = I, It2 = J>,
+    L: RangeMOC2Iterator<T, I::Qty, I, T, J::Qty, J, K>,
+  {
+    write_ranges2d_fits_hdu(moc_it, metadata, &mut self.writer)?;
+    self.n_hdus += 1;
+    Ok(())
+  }
+
+  /// Writes a MOC read from a FITS file (e.g. from another HDU, see [MocHdu::moc]) in a new HDU.
+  pub fn write_moc<R: BufRead>(
+    &mut self,
+    moc: MocIdxType<R>,
+    metadata: &MocMetadata,
+  ) -> Result<(), FitsError> {
+    moc.write_fits_hdu(metadata, &mut self.writer)?;
+    self.n_hdus += 1;
+    Ok(())
+  }
+
+  /// Returns the underlying writer.
+  pub fn into_inner(self) -> W {
+    self.writer
+  }
+}
+
+// READ PART
+
+/// Identifier of an HDU: either its index (the primary HDU having the index 0, the first
+/// extension the index 1, ...) or its name (value of its `EXTNAME` or `MOCID` keyword).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HduId {
+  Index(usize),
+  Name(String),
+}
+
+impl FromStr for HduId {
+  type Err = Infallible;
+
+  /// A string made of digits only is an index, any other string is a name.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Ok(match s.parse::<usize>() {
+      Ok(index) => HduId::Index(index),
+      Err(_) => HduId::Name(String::from(s)),
+    })
+  }
+}
+
+impl Display for HduId {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      HduId::Index(index) => write!(f, "{}", index),
+      HduId::Name(name) => write!(f, "{}", name),
+    }
+  }
+}
+
+/// An extension HDU of a FITS file, possibly containing a MOC.
+/// The header is parsed eagerly, but the MOC itself is read only when calling [MocHdu::moc].
+#[derive(Debug)]
+pub struct MocHdu<'a> {
+  index: usize,
+  is_moc: bool,
+  metadata: MocMetadata,
+  /// Bytes starting at the beginning of the HDU header
+  bytes: &'a [u8],
+}
+
+impl<'a> MocHdu<'a> {
+  /// Index of the HDU in the file (the first extension having the index 1).
+  pub fn index(&self) -> usize {
+    self.index
+  }
+
+  /// Returns `true` if the HDU is a BINTABLE containing MOC keywords.
+  pub fn is_moc(&self) -> bool {
+    self.is_moc
+  }
+
+  /// Returns the metadata found in the HDU header.
+  pub fn metadata(&self) -> &MocMetadata {
+    &self.metadata
+  }
+
+  /// Returns the value of the `EXTNAME` keyword, or of the `MOCID` keyword if `EXTNAME` is
+  /// missing.
+  pub fn name(&self) -> Option<&str> {
+    self
+      .metadata
+      .extname
+      .as_deref()
+      .or(self.metadata.moc_id.as_deref())
+  }
+
+  /// Returns `true` if the HDU is the one identified by the given id.
+  pub fn matches(&self, id: &HduId) -> bool {
+    match id {
+      HduId::Index(index) => self.index == *index,
+      HduId::Name(name) => {
+        self.metadata.extname.as_deref() == Some(name)
+          || self.metadata.moc_id.as_deref() == Some(name)
+      }
+    }
+  }
+
+  /// Reads the MOC stored in the HDU, see [from_fits_ivoa](super::from_fits_ivoa).
+  pub fn moc(&self) -> Result<MocIdxType<&'a [u8]>, FitsError> {
+    self.moc_custom(false)
+  }
+
+  /// Reads the MOC stored in the HDU, see [from_fits_ivoa_custom](super::from_fits_ivoa_custom).
+  pub fn moc_custom(&self, coosys_permissive: bool) -> Result<MocIdxType<&'a [u8]>, FitsError> {
+    if !self.is_moc {
+      return Err(FitsError::Custom(format!(
+        "HDU {} does not contain a MOC.",
+        self.index
+      )));
+    }
+    read_moc_hdu(self.bytes, coosys_permissive, true, false).map(|(moc, _)| moc)
+  }
+}
+
+/// Iterates over the extension HDUs of a FITS file, see [hdus_from_fits_ivoa].
+pub struct MocHduIter<'a> {
+  /// Remaining bytes, starting at the beginning of the next HDU
+  bytes: &'a [u8],
+  index: usize,
+}
+
+impl<'a> Iterator for MocHduIter<'a> {
+  type Item = Result<MocHdu<'a>, FitsError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    // Ignore possible blocks (e.g. of zeros) following the last HDU
+    if !self.bytes.starts_with(b"XTENSION") {
+      return None;
+    }
+    match parse_hdu(self.bytes, self.index) {
+      Ok((hdu, hdu_len)) => {
+        self.bytes = &self.bytes[hdu_len..];
+        self.index += 1;
+        Some(Ok(hdu))
+      }
+      Err(e) => {
+        self.bytes = &[];
+        Some(Err(e))
+      }
+    }
+  }
+}
+
+/// Iterates over the extension HDUs of a multi-extension FITS file containing MOCs.
+///
+/// # Params
+/// * `bytes`: the full content of the FITS file (e.g. read in memory or memory mapped)
+///
+/// # Info
+/// * only the headers are parsed while iterating, each MOC being read by [MocHdu::moc]
+/// * HDUs not containing a MOC are also returned (see [MocHdu::is_moc])
+pub fn hdus_from_fits_ivoa(bytes: &[u8]) -> Result<MocHduIter<'_>, FitsError> {
+  let mut reader = bytes;
+  consume_primary_hdu(&mut reader, &mut [b' '; 2880])?;
+  Ok(MocHduIter {
+    bytes: reader,
+    index: 1,
+  })
+}
+
+/// Returns the HDU starting the given bytes, together with its length in bytes (header and
+/// padded data unit).
+fn parse_hdu(bytes: &[u8], index: usize) -> Result<(MocHdu<'_>, usize), FitsError> {
+  let mut is_bintable = false;
+  let mut is_moc = false;
+  let mut bitpix = 8_u64;
+  let mut naxis = 0_u64;
+  let mut naxisn: Vec<u64> = Vec::new();
+  let mut pcount = 0_u64;
+  let mut gcount = 1_u64;
+  let mut moc_kws = MocKeywordsMap::new();
+  let mut other_kw_records: Vec<[u8; 80]> = Vec::new();
+  let mut header_len = None;
+  for (i, kw_record) in bytes.chunks_exact(80).enumerate() {
+    match get_keyword(kw_record) {
+      b"END     " => {
+        header_len = Some((i * 80 / 2880 + 1) * 2880);
+        break;
+      }
+      b"XTENSION" => is_bintable = check_expected_value(kw_record, b"'BINTABLE'").is_ok(),
+      b"BITPIX  " => bitpix = parse_abs_int_val(kw_record)?,
+      b"NAXIS   " => naxis = parse_uint_val::<u64>(kw_record)?,
+      b"PCOUNT  " => pcount = parse_uint_val::<u64>(kw_record)?,
+      b"GCOUNT  " => gcount = parse_uint_val::<u64>(kw_record)?,
+      kw if kw.starts_with(b"NAXIS") => naxisn.push(parse_uint_val::<u64>(kw_record)?),
+      kw => {
+        if matches!(
+          kw,
+          b"MOCDIM  " | b"MOCORDER" | b"MOCORD_S" | b"MOCORD_T" | b"MOCORD_F" | b"MOCORD_1"
+        ) {
+          is_moc = true;
+        }
+        match MocKeywords::is_moc_kw(kw_record) {
+          Some(Ok(moc_kw)) => {
+            moc_kws.insert(moc_kw);
+          }
+          Some(Err(_)) => (),
+          None => {
+            if let Ok(kw_record) = <[u8; 80]>::try_from(kw_record) {
+              other_kw_records.push(kw_record);
+            }
+          }
+        }
+      }
+    }
+  }
+  let header_len = header_len.ok_or_else(|| {
+    FitsError::Custom(format!(
+      "'END' keyword not found in the header of HDU {}.",
+      index
+    ))
+  })?;
+  // See Eq. 2 in the FITS standard
+  let data_len = if naxis == 0 {
+    0
+  } else {
+    (bitpix / 8) * gcount * (pcount + naxisn.iter().take(naxis as usize).product::<u64>())
+  };
+  if (bytes.len() as u64) < header_len as u64 + data_len {
+    return Err(FitsError::PrematureEndOfData);
+  }
+  let hdu_len = (header_len + data_len.div_ceil(2880) as usize * 2880).min(bytes.len());
+  let hdu = MocHdu {
+    index,
+    is_moc: is_bintable && is_moc,
+    metadata: MocMetadata::from_header(&moc_kws, &other_kw_records),
+    bytes: &bytes[..hdu_len],
+  };
+  Ok((hdu, hdu_len))
+}
+
+/// Parses the absolute value of an integer value (`BITPIX` being negative for floats).
+fn parse_abs_int_val(keyword_record: &[u8]) -> Result<u64, FitsError> {
+  let value = get_left_trimmed_value(keyword_record);
+  let value = value.strip_prefix(b"-").unwrap_or(value);
+  str::from_utf8(&value[..index_of_last_digit(value)])
+    .ok()
+    .and_then(|value| value.parse::<u64>().ok())
+    .ok_or_else(|| {
+      FitsError::UintValueNotFound(
+        String::from_utf8_lossy(keyword_record)
+          .trim_end()
+          .to_string(),
+      )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::io::BufReader;
+
+  use crate::{
+    deser::fits::{from_fits_ivoa, MocQtyType, MocType, STMocType},
+    elemset::range::{HpxRanges, TimeRanges},
+    moc::{range::RangeMOC, RangeMOCIntoIterator},
+    moc2d::{
+      range::{RangeMOC2, RangeMOC2Elem},
+      RangeMOC2IntoIterator,
+    },
+    qty::Time,
+  };
+
+  #[test]
+  fn test_write_read_mef() {
+    let smoc: RangeMOC<u64, Hpx<u64>> = RangeMOC::new(
+      10,
+      HpxRanges::new_unchecked(vec![1_u64 << 40..3 << 40, 5 << 42..6 << 42]),
+    );
+    let tmoc: RangeMOC<u64, Time<u64>> =
+      RangeMOC::new(20, TimeRanges::new_unchecked(vec![7_u64 << 41..9 << 41]));
+    let stmoc = RangeMOC2::<u64, Time<u64>, u64, Hpx<u64>>::from_time_and_coos(
+      20,
+      8,
+      vec![(2_451_545_u64 * 86_400_000_000, 0.1, 0.2)].into_iter(),
+      None,
+    );
+    let mut writer = MocFitsWriter::new(Vec::<u8>::new()).unwrap();
+    let mut metadata = MocMetadata::default();
+    metadata.extname = Some(String::from("CCD1"));
+    writer
+      .write_ranges((&smoc).into_range_moc_iter(), &metadata)
+      .unwrap();
+    metadata.extname = Some(String::from("FILTER_R"));
+    writer
+      .write_ranges((&tmoc).into_range_moc_iter(), &metadata)
+      .unwrap();
+    let metadata = MocMetadata::new(Some(String::from("ivo://CDS/stmoc")), None);
+    writer
+      .write_ranges2d((&stmoc).into_range_moc2_iter(), &metadata)
+      .unwrap();
+    assert_eq!(writer.n_hdus(), 3);
+    let bytes = writer.into_inner();
+    assert_eq!(bytes.len() % 2880, 0);
+
+    let hdus: Vec<MocHdu> = hdus_from_fits_ivoa(&bytes)
+      .unwrap()
+      .collect::<Result<_, _>>()
+      .unwrap();
+    assert_eq!(hdus.len(), 3);
+    assert!(hdus.iter().all(|hdu| hdu.is_moc()));
+    assert_eq!(
+      hdus.iter().map(|hdu| hdu.name()).collect::<Vec<_>>(),
+      vec![Some("CCD1"), Some("FILTER_R"), Some("ivo://CDS/stmoc")]
+    );
+    // Selection by name or by index, reading the HDUs in any order
+    let id: HduId = "ivo://CDS/stmoc".parse().unwrap();
+    let hdu = hdus.iter().find(|hdu| hdu.matches(&id)).unwrap();
+    assert_eq!(hdu.index(), 3);
+    match hdu.moc().unwrap() {
+      MocIdxType::U64(MocQtyType::TimeHpx(STMocType::V2(it))) => {
+        let elems: Vec<RangeMOC2Elem<u64, Time<u64>, u64, Hpx<u64>>> = it.collect();
+        assert!(RangeMOC2::new(20, 8, elems).eq_without_depth(&stmoc));
+      }
+      _ => panic!("ST-MOC expected"),
+    }
+    let hdu = hdus
+      .iter()
+      .find(|hdu| hdu.matches(&HduId::Index(1)))
+      .unwrap();
+    match hdu.moc().unwrap() {
+      MocIdxType::U64(MocQtyType::Hpx(MocType::Ranges(it))) => {
+        assert_eq!(it.into_range_moc(), smoc)
+      }
+      _ => panic!("S-MOC expected"),
+    }
+    let hdu = hdus
+      .iter()
+      .find(|hdu| hdu.matches(&"FILTER_R".parse().unwrap()))
+      .unwrap();
+    match hdu.moc().unwrap() {
+      MocIdxType::U64(MocQtyType::Time(MocType::Ranges(it))) => {
+        assert_eq!(it.into_range_moc(), tmoc)
+      }
+      _ => panic!("T-MOC expected"),
+    }
+    // The single MOC reader reads the first HDU
+    match from_fits_ivoa(BufReader::new(&bytes[..])).unwrap() {
+      MocIdxType::U64(MocQtyType::Hpx(MocType::Ranges(it))) => {
+        assert_eq!(it.into_range_moc(), smoc)
+      }
+      _ => panic!("S-MOC expected"),
+    }
+  }
+}
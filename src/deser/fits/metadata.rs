@@ -0,0 +1,242 @@
+//! Metadata (identity, provenance and additional keyword records) stored in the header of a
+//! FITS MOC, so that it can be preserved when a MOC is read and written back.
+
+use std::str;
+
+use crate::deser::fits::{
+  common::{get_keyword, get_left_trimmed_value, str_keyword_record},
+  error::FitsError,
+  keywords::{self, MocId, MocKeywords, MocKeywordsMap, MocTool},
+};
+
+/// Value of the `MOCTOOL` keyword when not provided in the metadata.
+pub const DEFAULT_MOC_TOOL: &str = "CDS MOC Rust lib";
+
+/// Metadata of a FITS MOC, i.e. the header keyword records not describing the MOC
+/// structure itself.
+///
+/// # Info
+/// * `MOCID`, `MOCTOOL`, `MOCTYPE`, `EXTNAME` and `DATE` are exposed as fields;
+/// * any other (non-structural) keyword record, like `ORIGIN`, `HISTORY` or `COMMENT`, is
+///   kept verbatim in the list of additional cards (see [MocMetadata::cards]).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MocMetadata {
+  /// Value of the `MOCID` keyword
+  pub moc_id: Option<String>,
+  /// Value of the `MOCTOOL` keyword; set to [DEFAULT_MOC_TOOL] on write if `None`
+  pub moc_tool: Option<String>,
+  /// Value of the `MOCTYPE` keyword
+  pub moc_type: Option<keywords::MocType>,
+  /// Value of the `EXTNAME` keyword
+  pub extname: Option<String>,
+  /// Value of the `DATE` keyword
+  pub date: Option<String>,
+  /// Write the `CHECKSUM` and `DATASUM` keywords (set when read from a header containing a
+  /// `CHECKSUM` keyword). Since the `DATASUM` is written before the data unit, the data unit of
+  /// a MOC given as an iterator is then first written in memory.
+  pub checksum: bool,
+  /// Additional keyword records, always 80 ASCII characters long
+  cards: Vec<[u8; 80]>,
+}
+
+impl MocMetadata {
+  /// Metadata containing only the (optional) `MOCID` and `MOCTYPE` keywords.
+  pub fn new(moc_id: Option<String>, moc_type: Option<keywords::MocType>) -> Self {
+    Self {
+      moc_id,
+      moc_type,
+      ..Default::default()
+    }
+  }
+
+  /// Returns the additional keyword records, right-trimmed.
+  pub fn cards(&self) -> impl Iterator<Item = &str> {
+    self
+      .cards
+      .iter()
+      // Cards are ASCII by construction
+      .map(|card| unsafe { str::from_utf8_unchecked(card) }.trim_end())
+  }
+
+  /// Adds an additional keyword record, e.g. `"ORIGIN  = 'CDS'"` or `"HISTORY made by me"`.
+  ///
+  /// # Errors
+  /// If the card is not made of at most 80 ASCII characters, or if its keyword is reserved,
+  /// i.e. if it is a MOC keyword or a keyword describing the BINTABLE structure.
+  pub fn push_card(&mut self, card: &str) -> Result<(), FitsError> {
+    if !card.is_ascii() || card.len() > 80 {
+      return Err(FitsError::Custom(format!(
+        "Keyword record not ASCII or longer than 80 characters: '{}'",
+        card
+      )));
+    }
+    let mut keyword_record = [b' '; 80];
+    keyword_record[..card.len()].copy_from_slice(card.as_bytes());
+    if is_reserved(&keyword_record) {
+      return Err(FitsError::Custom(format!(
+        "Reserved keyword in keyword record: '{}'",
+        card
+      )));
+    }
+    self.cards.push(keyword_record);
+    Ok(())
+  }
+
+  /// Adds an additional keyword record containing a string value.
+  pub fn push_str_card(&mut self, keyword: &str, value: &str) -> Result<(), FitsError> {
+    if !keyword.is_ascii() || keyword.is_empty() || keyword.len() > 8 {
+      return Err(FitsError::Custom(format!(
+        "Keyword not ASCII or longer than 8 characters: '{}'",
+        keyword
+      )));
+    }
+    let mut kw = [b' '; 8];
+    kw[..keyword.len()].copy_from_slice(keyword.to_ascii_uppercase().as_bytes());
+    let keyword_record = str_keyword_record(&kw, value)?;
+    // Safe since made of ASCII characters only
+    self.push_card(unsafe { str::from_utf8_unchecked(&keyword_record) })
+  }
+
+  /// Builds the metadata from the MOC keywords and from the other keyword records of a header.
+  /// Structural keyword records are ignored.
+  pub(super) fn from_header(moc_kws: &MocKeywordsMap, other_keyword_records: &[[u8; 80]]) -> Self {
+    let mut metadata = Self::default();
+    if let Some(MocKeywords::MOCId(MocId { id })) = moc_kws.get::<MocId>() {
+      metadata.moc_id = Some(id.clone());
+    }
+    if let Some(MocKeywords::MOCTool(MocTool { tool })) = moc_kws.get::<MocTool>() {
+      metadata.moc_tool = Some(tool.clone());
+    }
+    if let Some(MocKeywords::MOCType(mtype)) = moc_kws.get::<keywords::MocType>() {
+      metadata.moc_type = Some(*mtype);
+    }
+    for keyword_record in other_keyword_records {
+      match get_keyword(keyword_record) {
+        b"EXTNAME " => metadata.extname = parse_str_value(keyword_record),
+        b"DATE    " => metadata.date = parse_str_value(keyword_record),
+        b"CHECKSUM" => metadata.checksum = true,
+        _ if is_reserved(keyword_record) || !keyword_record.is_ascii() => (),
+        _ if keyword_record.iter().all(|b| *b == b' ') => (),
+        _ => metadata.cards.push(*keyword_record),
+      }
+    }
+    metadata
+  }
+
+  /// Insert the `MOCID`, `MOCTOOL` and `MOCTYPE` keywords in the given map.
+  pub(super) fn insert_moc_keywords(&self, moc_kws: &mut MocKeywordsMap) {
+    if let Some(id) = &self.moc_id {
+      moc_kws.insert(MocKeywords::MOCId(MocId { id: id.clone() }));
+    }
+    moc_kws.insert(MocKeywords::MOCTool(MocTool {
+      tool: self
+        .moc_tool
+        .clone()
+        .unwrap_or_else(|| String::from(DEFAULT_MOC_TOOL)),
+    }));
+    if let Some(mtype) = self.moc_type {
+      moc_kws.insert(MocKeywords::MOCType(mtype));
+    }
+  }
+
+  /// Returns the keyword records to be written after the MOC keywords: `EXTNAME`, `DATE` and
+  /// the additional cards.
+  pub(super) fn keyword_records(&self) -> Result<Vec<[u8; 80]>, FitsError> {
+    let mut keyword_records = Vec::with_capacity(2 + self.cards.len());
+    if let Some(extname) = &self.extname {
+      keyword_records.push(str_keyword_record(b"EXTNAME ", extname)?);
+    }
+    if let Some(date) = &self.date {
+      keyword_records.push(str_keyword_record(b"DATE    ", date)?);
+    }
+    keyword_records.extend_from_slice(&self.cards);
+    Ok(keyword_records)
+  }
+}
+
+/// Returns `true` if the keyword of the given record is either a MOC keyword, a keyword
+/// describing the BINTABLE structure, a checksum keyword (computed on write) or a keyword
+/// exposed as a [MocMetadata] field.
+fn is_reserved(keyword_record: &[u8; 80]) -> bool {
+  let keyword = get_keyword(keyword_record);
+  MocKeywords::is_moc_kw(keyword_record).is_some()
+    || matches!(
+      keyword,
+      b"SIMPLE  "
+        | b"XTENSION"
+        | b"BITPIX  "
+        | b"NAXIS   "
+        | b"NAXIS1  "
+        | b"NAXIS2  "
+        | b"PCOUNT  "
+        | b"GCOUNT  "
+        | b"TFIELDS "
+        | b"THEAP   "
+        | b"EXTEND  "
+        | b"EXTNAME "
+        | b"DATE    "
+        | b"CHECKSUM"
+        | b"DATASUM "
+        | b"END     "
+    )
+    || [
+      &b"TFORM"[..],
+      b"TTYPE",
+      b"TUNIT",
+      b"TNULL",
+      b"TSCAL",
+      b"TZERO",
+      b"TDISP",
+      b"TDIM",
+    ]
+    .iter()
+    .any(|prefix| keyword.starts_with(prefix))
+}
+
+/// Parse a FITS string value, replacing the escaped simple quotes (`''`) by a simple quote and
+/// removing the trailing spaces.
+fn parse_str_value(keyword_record: &[u8]) -> Option<String> {
+  let value = get_left_trimmed_value(keyword_record).strip_prefix(b"'")?;
+  let mut res = Vec::with_capacity(value.len());
+  let mut it = value.iter().peekable();
+  while let Some(c) = it.next() {
+    if *c == b'\'' {
+      if it.peek() == Some(&&b'\'') {
+        it.next();
+      } else {
+        return Some(String::from_utf8_lossy(res.trim_ascii_end()).to_string());
+      }
+    }
+    res.push(*c);
+  }
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_push_card() {
+    let mut metadata = MocMetadata::default();
+    assert!(metadata.push_card("ORIGIN  = 'CDS'").is_ok());
+    assert!(metadata.push_card("HISTORY Made by hand").is_ok());
+    assert!(metadata.push_str_card("observer", "L'Astronome").is_ok());
+    assert!(metadata.push_card("MOCORD_S= 5").is_err());
+    assert!(metadata.push_card("TTYPE1  = 'RANGE'").is_err());
+    assert!(metadata.push_card("EXTNAME = 'MOC'").is_err());
+    assert!(metadata.push_card(&"X".repeat(81)).is_err());
+    assert_eq!(
+      metadata.cards().collect::<Vec<&str>>(),
+      vec![
+        "ORIGIN  = 'CDS'",
+        "HISTORY Made by hand",
+        "OBSERVER= 'L''Astronome'"
+      ]
+    );
+    assert_eq!(
+      parse_str_value(&metadata.cards[2]),
+      Some(String::from("L'Astronome"))
+    );
+  }
+}
@@ -2,7 +2,7 @@
 //! [FITS standard](https://fits.gsfc.nasa.gov/standard40/fits_standard40aa-le.pdf).
 
 use std::{
-  io::{BufRead, Cursor, Write},
+  io::{self, BufRead, Cursor, Write},
   marker::PhantomData,
   ops::Range,
 };
@@ -12,15 +12,17 @@ use log::warn;
 
 use crate::{
   deser::fits::{
+    checksum::{checksum_add, set_checksum, verify_header_checksum, ChecksumWriter, CHECKSUM_INIT},
     common::{
-      check_keyword_and_parse_uint_val, check_keyword_and_val, consume_primary_hdu,
-      next_36_chunks_of_80_bytes, write_primary_hdu, write_uint_mandatory_keyword_record,
+      bintable_header, check_keyword_and_parse_uint_val, check_keyword_and_val,
+      consume_primary_hdu, str_keyword_record, write_data_padding, write_primary_hdu,
     },
     error::FitsError,
     keywords::{
-      CoordSys, FitsCard, MocDim, MocId, MocKeywords, MocKeywordsMap, MocOrdF, MocOrdS, MocOrdT,
-      MocOrder, MocTool, MocVers, Ordering, TForm1, TType1, TimeSys,
+      CoordSys, FitsCard, MocDim, MocKeywords, MocKeywordsMap, MocOrdF, MocOrdS, MocOrdT, MocOrder,
+      MocVers, Ordering, TForm1, TType1, TimeSys,
     },
+    metadata::MocMetadata,
   },
   elem::cell::Cell,
   elemset::{
@@ -30,9 +32,15 @@ use crate::{
   idx::Idx,
   moc::{
     cell::CellMOC,
-    range::{op::convert::convert_to_u64, RangeMOC, RangeMocIter},
+    range::{
+      op::{
+        convert::convert_to_u64,
+        repair::{repair_cells, repair_ranges, RepairReport},
+      },
+      RangeMOC, RangeMocIter,
+    },
     CellMOCIntoIterator, CellMOCIterator, HasMaxDepth, MOCProperties, NonOverlapping,
-    RangeMOCIterator, ZSorted,
+    RangeMOCIntoIterator, RangeMOCIterator, ZSorted,
   },
   moc2d::{
     range::{RangeMOC2, RangeMOC2Elem},
@@ -41,11 +49,16 @@ use crate::{
   qty::{Frequency, Hpx, MocQty, MocableQty, Time},
 };
 
+pub mod bintable;
+pub mod checksum;
 pub mod common;
 pub mod error;
 pub mod keywords;
+pub mod mef;
+pub mod metadata;
 pub mod multiordermap;
 pub mod skymap;
+pub mod validate;
 
 #[derive(Debug)]
 pub enum MocIdxType<R: BufRead> {
@@ -57,12 +70,42 @@ pub enum MocIdxType<R: BufRead> {
 }
 impl<R: BufRead> MocIdxType<R> {
   pub fn to_fits_ivoa<W: Write>(self, write: W) -> Result<(), FitsError> {
+    self.to_fits_ivoa_with_metadata(&MocMetadata::default(), write)
+  }
+  /// Same as [MocIdxType::to_fits_ivoa], but writing the given metadata in the header.
+  pub fn to_fits_ivoa_with_metadata<W: Write>(
+    self,
+    metadata: &MocMetadata,
+    mut write: W,
+  ) -> Result<(), FitsError> {
+    write_primary_hdu(&mut write)?;
+    self.write_fits_hdu(metadata, write)
+  }
+  /// Writes the MOC BINTABLE HDU only (i.e. without the primary HDU).
+  fn write_fits_hdu<W: Write>(self, metadata: &MocMetadata, write: W) -> Result<(), FitsError> {
+    match self {
+      //MocIdxType::U8(moc_qty_type) => moc_qty_type.write_fits_hdu(metadata, write),
+      MocIdxType::U16(moc_qty_type) => moc_qty_type.write_fits_hdu(metadata, write),
+      MocIdxType::U32(moc_qty_type) => moc_qty_type.write_fits_hdu(metadata, write),
+      MocIdxType::U64(moc_qty_type) => moc_qty_type.write_fits_hdu(metadata, write),
+      // MocIdxType::U128(moc_qty_type) => moc_qty_type.write_fits_hdu(metadata, write),
+    }
+  }
+  /// Normalises the MOC, see [MocType::repair].
+  pub fn repair(self) -> (Self, RepairReport) {
     match self {
-      //MocIdxType::U8(moc_qty_type) => moc_qty_type.to_fits_ivoa(write),
-      MocIdxType::U16(moc_qty_type) => moc_qty_type.to_fits_ivoa(write),
-      MocIdxType::U32(moc_qty_type) => moc_qty_type.to_fits_ivoa(write),
-      MocIdxType::U64(moc_qty_type) => moc_qty_type.to_fits_ivoa(write),
-      // MocIdxType::U128(moc_qty_type) => moc_qty_type.to_fits_ivoa(write),
+      MocIdxType::U16(moc_qty_type) => {
+        let (moc, report) = moc_qty_type.repair();
+        (MocIdxType::U16(moc), report)
+      }
+      MocIdxType::U32(moc_qty_type) => {
+        let (moc, report) = moc_qty_type.repair();
+        (MocIdxType::U32(moc), report)
+      }
+      MocIdxType::U64(moc_qty_type) => {
+        let (moc, report) = moc_qty_type.repair();
+        (MocIdxType::U64(moc), report)
+      }
     }
   }
 }
@@ -74,15 +117,48 @@ pub enum MocQtyType<T: Idx, R: BufRead> {
   TimeHpx(STMocType<T, R>),
   FreqHpx(RangeMoc2DIterFromFits<T, R, Frequency<T>, Hpx<T>>),
   Freq(MocType<T, Frequency<T>, R>),
+  TimeFreq(RangeMoc2DIterFromFits<T, R, Time<T>, Frequency<T>>),
 }
 impl<T: Idx, R: BufRead> MocQtyType<T, R> {
   pub fn to_fits_ivoa<W: Write>(self, write: W) -> Result<(), FitsError> {
+    self.to_fits_ivoa_with_metadata(&MocMetadata::default(), write)
+  }
+  /// Same as [MocQtyType::to_fits_ivoa], but writing the given metadata in the header.
+  pub fn to_fits_ivoa_with_metadata<W: Write>(
+    self,
+    metadata: &MocMetadata,
+    mut write: W,
+  ) -> Result<(), FitsError> {
+    write_primary_hdu(&mut write)?;
+    self.write_fits_hdu(metadata, write)
+  }
+  /// Writes the MOC BINTABLE HDU only (i.e. without the primary HDU).
+  fn write_fits_hdu<W: Write>(self, metadata: &MocMetadata, write: W) -> Result<(), FitsError> {
     match self {
-      MocQtyType::Hpx(moc_type) => moc_type.to_fits_ivoa(write),
-      MocQtyType::Time(moc_type) => moc_type.to_fits_ivoa(write),
-      MocQtyType::TimeHpx(moc_type) => moc_type.to_fits_ivoa(write),
-      MocQtyType::FreqHpx(moc2d) => ranges_sf_to_fits_ivoa(moc2d, None, None, write),
-      MocQtyType::Freq(moc_type) => moc_type.to_fits_ivoa(write),
+      MocQtyType::Hpx(moc_type) => moc_type.write_fits_hdu(metadata, write),
+      MocQtyType::Time(moc_type) => moc_type.write_fits_hdu(metadata, write),
+      MocQtyType::TimeHpx(moc_type) => moc_type.write_fits_hdu(metadata, write),
+      MocQtyType::FreqHpx(moc2d) => write_ranges2d_fits_hdu(moc2d, metadata, write),
+      MocQtyType::Freq(moc_type) => moc_type.write_fits_hdu(metadata, write),
+      MocQtyType::TimeFreq(moc2d) => write_ranges2d_fits_hdu(moc2d, metadata, write),
+    }
+  }
+  /// Normalises 1D-MOCs, see [MocType::repair]. 2D-MOCs are returned unchanged.
+  pub fn repair(self) -> (Self, RepairReport) {
+    match self {
+      MocQtyType::Hpx(moc) => {
+        let (moc, report) = moc.repair();
+        (MocQtyType::Hpx(moc), report)
+      }
+      MocQtyType::Time(moc) => {
+        let (moc, report) = moc.repair();
+        (MocQtyType::Time(moc), report)
+      }
+      MocQtyType::Freq(moc) => {
+        let (moc, report) = moc.repair();
+        (MocQtyType::Freq(moc), report)
+      }
+      moc2d => (moc2d, RepairReport::default()),
     }
   }
 }
@@ -97,13 +173,41 @@ pub enum MocType<T: Idx, Q: MocQty<T>, R: BufRead> {
 }
 impl<T: Idx, Q: MocQty<T>, R: BufRead> MocType<T, Q, R> {
   pub fn to_fits_ivoa<W: Write>(self, write: W) -> Result<(), FitsError> {
+    self.to_fits_ivoa_with_metadata(&MocMetadata::default(), write)
+  }
+  /// Same as [MocType::to_fits_ivoa], but writing the given metadata in the header.
+  pub fn to_fits_ivoa_with_metadata<W: Write>(
+    self,
+    metadata: &MocMetadata,
+    mut write: W,
+  ) -> Result<(), FitsError> {
+    write_primary_hdu(&mut write)?;
+    self.write_fits_hdu(metadata, write)
+  }
+  /// Writes the MOC BINTABLE HDU only (i.e. without the primary HDU).
+  fn write_fits_hdu<W: Write>(self, metadata: &MocMetadata, write: W) -> Result<(), FitsError> {
     match self {
-      MocType::Ranges(ranges) => ranges_to_fits_ivoa(ranges, None, None, write),
+      MocType::Ranges(ranges) => write_ranges_fits_hdu(ranges, metadata, write),
       MocType::Cells(cells) => {
-        ranges_to_fits_ivoa(cells.into_cell_moc_iter().ranges(), None, None, write)
+        write_ranges_fits_hdu(cells.into_cell_moc_iter().ranges(), metadata, write)
       }
     }
   }
+  /// Normalises a possibly malformed MOC (unsorted, overlapping or redundant elements,
+  /// cells deeper than the MOC depth, ...), see [repair_ranges] and [repair_cells].
+  /// The repaired MOC is returned as a [MocType::Cells].
+  pub fn repair(self) -> (Self, RepairReport) {
+    let (moc, report): (RangeMOC<T, Q>, RepairReport) = match self {
+      MocType::Ranges(ranges) => repair_ranges(ranges.depth_max(), ranges),
+      MocType::Cells(cells) => repair_cells(cells.depth_max(), cells.into_cell_moc_iter()),
+    };
+    let depth_max = moc.depth_max();
+    let cells = moc.into_range_moc_iter().cells().collect();
+    (
+      MocType::Cells(CellMOC::new(depth_max, MocCells::new(Cells::new(cells)))),
+      report,
+    )
+  }
   pub fn collect(self) -> RangeMOC<T, Q> {
     match self {
       MocType::Ranges(ranges) => ranges.into_range_moc(),
@@ -128,9 +232,22 @@ pub enum STMocType<T: Idx, R: BufRead> {
 }
 impl<T: Idx, R: BufRead> STMocType<T, R> {
   pub fn to_fits_ivoa<W: Write>(self, write: W) -> Result<(), FitsError> {
+    self.to_fits_ivoa_with_metadata(&MocMetadata::default(), write)
+  }
+  /// Same as [STMocType::to_fits_ivoa], but writing the given metadata in the header.
+  pub fn to_fits_ivoa_with_metadata<W: Write>(
+    self,
+    metadata: &MocMetadata,
+    mut write: W,
+  ) -> Result<(), FitsError> {
+    write_primary_hdu(&mut write)?;
+    self.write_fits_hdu(metadata, write)
+  }
+  /// Writes the MOC BINTABLE HDU only (i.e. without the primary HDU).
+  fn write_fits_hdu<W: Write>(self, metadata: &MocMetadata, write: W) -> Result<(), FitsError> {
     match self {
-      STMocType::V2(ranges2) => ranges_st_to_fits_ivoa(ranges2, None, None, write),
-      STMocType::PreV2(ranges2) => ranges_st_to_fits_ivoa(ranges2, None, None, write),
+      STMocType::V2(ranges2) => write_ranges2d_fits_hdu(ranges2, metadata, write),
+      STMocType::PreV2(ranges2) => write_ranges2d_fits_hdu(ranges2, metadata, write),
     }
   }
 }
@@ -142,15 +259,46 @@ pub fn hpx_cells_to_fits_ivoa<T, I, W>(
   moc_it: I,
   moc_id: Option<String>,
   moc_type: Option<keywords::MocType>,
+  writer: W,
+) -> Result<(), FitsError>
+where
+  T: Idx,
+  I: CellMOCIterator<T, Qty = Hpx<T>>,
+  W: Write,
+{
+  hpx_cells_to_fits_ivoa_with_metadata(moc_it, &MocMetadata::new(moc_id, moc_type), writer)
+}
+
+/// Same as [hpx_cells_to_fits_ivoa], but writing the given metadata in the header.
+/// The `CHECKSUM` and `DATASUM` keywords are written if [MocMetadata::checksum] is set.
+pub fn hpx_cells_to_fits_ivoa_with_metadata<T, I, W>(
+  moc_it: I,
+  metadata: &MocMetadata,
   mut writer: W,
 ) -> Result<(), FitsError>
+where
+  T: Idx,
+  I: CellMOCIterator<T, Qty = Hpx<T>>,
+  W: Write,
+{
+  write_primary_hdu(&mut writer)?;
+  write_hpx_cells_fits_hdu(moc_it, metadata, writer)
+}
+
+/// Writes the BINTABLE HDU of [hpx_cells_to_fits_ivoa_with_metadata] (i.e. without the primary
+/// HDU).
+fn write_hpx_cells_fits_hdu<T, I, W>(
+  moc_it: I,
+  metadata: &MocMetadata,
+  writer: W,
+) -> Result<(), FitsError>
 where
   T: Idx,
   I: CellMOCIterator<T, Qty = Hpx<T>>,
   W: Write,
 {
   let depth_max = moc_it.depth_max();
-  let moc_kw_map = build_hpx_uniq_moc_keywords(depth_max, moc_id, moc_type, PhantomData::<T>);
+  let moc_kw_map = build_hpx_uniq_moc_keywords(depth_max, metadata, PhantomData::<T>);
   let mut buffers: Vec<Cursor<Vec<u8>>> = Vec::with_capacity((depth_max + 1) as usize);
   let n_cells_guess = moc_it.size_hint().0.max(10_000);
   for d in 0..=depth_max {
@@ -168,25 +316,16 @@ where
       .write::<_, BigEndian>(&mut buffers[d as usize])?;
     n_cells += 1;
   }
-  write_fits_header(&mut writer, T::N_BYTES, n_cells, moc_kw_map)?;
-  let mut len = 0;
-  for buf in &mut buffers {
-    // buf.seek(SeekFrom::Start(0))?; // or buf.set_position(0); ?
-    let buf_ref = buf.get_ref();
-    len += buf_ref.len();
-    writer.write_all(buf_ref)?;
-  }
-  let mod2880 = len % 2880;
-  if mod2880 != 0 {
-    writer.write_all(&vec![0_u8; 2880 - mod2880])?;
-  }
-  Ok(())
+  let data: Vec<u8> = buffers
+    .into_iter()
+    .flat_map(|buf| buf.into_inner())
+    .collect();
+  write_bintable_hdu(writer, T::N_BYTES, n_cells, moc_kw_map, metadata, &data)
 }
 
 fn build_hpx_uniq_moc_keywords<T: Idx>(
   depth_max: u8,
-  moc_id: Option<String>,
-  moc_type: Option<keywords::MocType>,
+  metadata: &MocMetadata,
   _t_type: PhantomData<T>,
 ) -> MocKeywordsMap {
   let mut moc_kws = MocKeywordsMap::new();
@@ -196,15 +335,7 @@ fn build_hpx_uniq_moc_keywords<T: Idx>(
   moc_kws.insert(MocKeywords::CoordSys(CoordSys::ICRS));
   moc_kws.insert(MocKeywords::MOCOrdS(MocOrdS { depth: depth_max }));
   moc_kws.insert(MocKeywords::MOCOrder(MocOrder { depth: depth_max })); // For compatibility with v1
-  if let Some(id) = moc_id {
-    moc_kws.insert(MocKeywords::MOCId(MocId { id }));
-  }
-  moc_kws.insert(MocKeywords::MOCTool(MocTool {
-    tool: String::from("CDS MOC Rust lib"),
-  }));
-  if let Some(mtype) = moc_type {
-    moc_kws.insert(MocKeywords::MOCType(mtype));
-  }
+  metadata.insert_moc_keywords(&mut moc_kws);
   // BINTABLE specific
   moc_kws.insert(MocKeywords::TForm1(T::TFORM));
   moc_kws.insert(MocKeywords::TType1(TType1 {
@@ -213,30 +344,58 @@ fn build_hpx_uniq_moc_keywords<T: Idx>(
   moc_kws
 }
 
-fn write_fits_header<R: Write>(
+/// Writes the BINTABLE HDU containing the given data, computing its `DATASUM` and `CHECKSUM`
+/// keywords if required by the metadata.
+fn write_bintable_hdu<R: Write>(
   mut writer: R,
   naxis1_n_bytes: u8,
   naxis2_n_elems: u64,
   moc_kw_map: MocKeywordsMap,
+  metadata: &MocMetadata,
+  data: &[u8],
 ) -> Result<(), FitsError> {
-  write_primary_hdu(&mut writer)?;
-  let mut header_block = [b' '; 2880];
-  let mut it = header_block.chunks_mut(80);
-  // Write BINTABLE specific keywords in the buffer
-  it.next().unwrap()[0..20].copy_from_slice(b"XTENSION= 'BINTABLE'");
-  it.next().unwrap()[0..30].copy_from_slice(b"BITPIX  =                    8");
-  it.next().unwrap()[0..30].copy_from_slice(b"NAXIS   =                    2");
-  write_uint_mandatory_keyword_record(it.next().unwrap(), b"NAXIS1  ", naxis1_n_bytes as u64);
-  write_uint_mandatory_keyword_record(it.next().unwrap(), b"NAXIS2  ", naxis2_n_elems);
-  it.next().unwrap()[0..30].copy_from_slice(b"PCOUNT  =                    0");
-  it.next().unwrap()[0..30].copy_from_slice(b"GCOUNT  =                    1");
-  it.next().unwrap()[0..30].copy_from_slice(b"TFIELDS =                    1");
-  // Write MOC (+BINTABLE) specific keywords in the buffer
-  moc_kw_map.write_all(&mut it)?;
-  it.next().unwrap()[0..3].copy_from_slice(b"END");
-  // Do write the header
-  writer.write_all(&header_block[..])?;
-  Ok(())
+  // The padding zeros do not change the sum
+  let datasum = metadata.checksum.then(|| checksum_add(0, data));
+  write_bintable_hdu_header(
+    &mut writer,
+    naxis1_n_bytes,
+    naxis2_n_elems,
+    moc_kw_map,
+    metadata,
+    datasum,
+  )?;
+  writer.write_all(data)?;
+  write_data_padding(writer, data.len() as u64)
+}
+
+/// Writes the header of a BINTABLE HDU, adding the `DATASUM` and `CHECKSUM` keywords if the
+/// `datasum` of the data unit is provided.
+fn write_bintable_hdu_header<R: Write>(
+  mut writer: R,
+  naxis1_n_bytes: u8,
+  naxis2_n_elems: u64,
+  moc_kw_map: MocKeywordsMap,
+  metadata: &MocMetadata,
+  datasum: Option<u32>,
+) -> Result<(), FitsError> {
+  let mut keyword_records = metadata.keyword_records()?;
+  if let Some(datasum) = datasum {
+    keyword_records.push(str_keyword_record(b"DATASUM ", &datasum.to_string())?);
+    keyword_records.push(str_keyword_record(b"CHECKSUM", CHECKSUM_INIT)?);
+  }
+  // TTYPE1 and TFORM1 are part of the MOC keywords
+  let mut header = bintable_header(
+    naxis1_n_bytes as u64,
+    naxis2_n_elems,
+    1,
+    &[],
+    moc_kw_map,
+    &keyword_records,
+  )?;
+  if let Some(datasum) = datasum {
+    set_checksum(&mut header, datasum)?;
+  }
+  writer.write_all(&header).map_err(FitsError::Io)
 }
 
 /*
@@ -301,6 +460,39 @@ pub fn ranges_to_fits_ivoa<T, Q, I, W>(
   moc_type: Option<keywords::MocType>,
   writer: W,
 ) -> Result<(), FitsError>
+where
+  T: Idx,
+  Q: MocQty<T>,
+  I: RangeMOCIterator<T, Qty = Q>,
+  W: Write,
+{
+  ranges_to_fits_ivoa_with_metadata(moc_it, &MocMetadata::new(moc_id, moc_type), writer)
+}
+
+/// Same as [ranges_to_fits_ivoa], but writing the given metadata in the header.
+/// The `CHECKSUM` and `DATASUM` keywords are written if [MocMetadata::checksum] is set, the data
+/// being then first written in memory (it is else streamed if the number of ranges is known).
+pub fn ranges_to_fits_ivoa_with_metadata<T, Q, I, W>(
+  moc_it: I,
+  metadata: &MocMetadata,
+  mut writer: W,
+) -> Result<(), FitsError>
+where
+  T: Idx,
+  Q: MocQty<T>,
+  I: RangeMOCIterator<T, Qty = Q>,
+  W: Write,
+{
+  write_primary_hdu(&mut writer)?;
+  write_ranges_fits_hdu(moc_it, metadata, writer)
+}
+
+/// Writes the BINTABLE HDU of [ranges_to_fits_ivoa_with_metadata] (i.e. without the primary HDU).
+fn write_ranges_fits_hdu<T, Q, I, W>(
+  moc_it: I,
+  metadata: &MocMetadata,
+  writer: W,
+) -> Result<(), FitsError>
 where
   T: Idx,
   Q: MocQty<T>,
@@ -308,31 +500,31 @@ where
   W: Write,
 {
   let depth_max = moc_it.depth_max();
-  let moc_kw_map = build_range_moc_keywords(
-    depth_max,
-    moc_id,
-    moc_type,
-    PhantomData::<T>,
-    PhantomData::<Q>,
-  );
+  let moc_kw_map =
+    build_range_moc_keywords(depth_max, metadata, PhantomData::<T>, PhantomData::<Q>);
   match moc_it.size_hint() {
     (len_min, Some(len_max)) if len_min == len_max => {
-      ranges_to_fits_ivoa_internal(len_max, moc_it, moc_kw_map, writer)
+      ranges_to_fits_ivoa_internal(len_max, moc_it, moc_kw_map, metadata, writer)
     }
     _ => {
       // We don't know the size, so we can't stream (since the size must be known in advance in FITS)
       // Another solution would have been to write the stream in memory (counting the number of
       // elements written), and then to copy in the writer.
       let ranges: Vec<Range<T>> = moc_it.collect();
-      ranges_to_fits_ivoa_internal(ranges.len(), ranges.into_iter(), moc_kw_map, writer)
+      ranges_to_fits_ivoa_internal(
+        ranges.len(),
+        ranges.into_iter(),
+        moc_kw_map,
+        metadata,
+        writer,
+      )
     }
   }
 }
 
 fn build_range_moc_keywords<T: Idx, Q: MocQty<T>>(
   depth_max: u8,
-  moc_id: Option<String>,
-  moc_type: Option<keywords::MocType>,
+  metadata: &MocMetadata,
   // moc_version: MocVers,
   _t_type: PhantomData<T>,
   _q_type: PhantomData<Q>,
@@ -342,26 +534,8 @@ fn build_range_moc_keywords<T: Idx, Q: MocQty<T>>(
   // moc_kws.insert(MocKeywords::MOCVers(moc_version));
   moc_kws.insert(MocKeywords::MOCDim(Q::MOC_DIM));
   moc_kws.insert(MocKeywords::Ordering(Ordering::Range));
-  if Q::HAS_COOSYS {
-    moc_kws.insert(MocKeywords::CoordSys(CoordSys::ICRS));
-    moc_kws.insert(MocKeywords::MOCOrdS(MocOrdS { depth: depth_max }));
-  }
-  if Q::HAS_TIMESYS {
-    moc_kws.insert(MocKeywords::TimeSys(TimeSys::TCB));
-    moc_kws.insert(MocKeywords::MOCOrdT(MocOrdT { depth: depth_max }));
-  }
-  if Q::HAS_FREQSYS {
-    moc_kws.insert(MocKeywords::MOCOrdF(MocOrdF { depth: depth_max }));
-  }
-  if let Some(id) = moc_id {
-    moc_kws.insert(MocKeywords::MOCId(MocId { id }));
-  }
-  moc_kws.insert(MocKeywords::MOCTool(MocTool {
-    tool: String::from("CDS MOC Rust lib"),
-  }));
-  if let Some(mtype) = moc_type {
-    moc_kws.insert(MocKeywords::MOCType(mtype));
-  }
+  insert_qty_keywords::<T, Q>(&mut moc_kws, depth_max);
+  metadata.insert_moc_keywords(&mut moc_kws);
   // BINTABLE specific
   moc_kws.insert(MocKeywords::TForm1(T::TFORM));
   moc_kws.insert(MocKeywords::TType1(TType1 {
@@ -372,8 +546,46 @@ fn build_range_moc_keywords<T: Idx, Q: MocQty<T>>(
 
 fn ranges_to_fits_ivoa_internal<T, I, W>(
   n_ranges: usize,
-  mut range_it: I,
+  range_it: I,
   moc_kw_map: MocKeywordsMap,
+  metadata: &MocMetadata,
+  mut writer: W,
+) -> Result<(), FitsError>
+where
+  T: Idx,
+  I: Iterator<Item = Range<T>>,
+  W: Write,
+{
+  if metadata.checksum {
+    // Write data part in memory first, to compute the DATASUM
+    let mut data: Vec<u8> = Vec::with_capacity((n_ranges << 1) * T::N_BYTES as usize);
+    write_ranges_data(n_ranges, range_it, &mut data)?;
+    write_bintable_hdu(
+      writer,
+      T::N_BYTES,
+      (n_ranges as u64) << 1,
+      moc_kw_map,
+      metadata,
+      &data,
+    )
+  } else {
+    write_bintable_hdu_header(
+      &mut writer,
+      T::N_BYTES,
+      (n_ranges as u64) << 1,
+      moc_kw_map,
+      metadata,
+      None,
+    )?;
+    write_ranges_data(n_ranges, range_it, &mut writer)?;
+    write_data_padding(writer, ((n_ranges as u64) << 1) * T::N_BYTES as u64)
+  }
+}
+
+/// Writes exactly `n_ranges` ranges of the given iterator.
+fn write_ranges_data<T, I, W>(
+  n_ranges: usize,
+  mut range_it: I,
   mut writer: W,
 ) -> Result<(), FitsError>
 where
@@ -381,8 +593,6 @@ where
   I: Iterator<Item = Range<T>>,
   W: Write,
 {
-  write_fits_header(&mut writer, T::N_BYTES, (n_ranges as u64) << 1, moc_kw_map)?;
-  // Write data part
   for _ in 0..n_ranges {
     if let Some(Range { start, end }) = range_it.next() {
       start.write::<_, BigEndian>(&mut writer)?;
@@ -391,11 +601,6 @@ where
       return Err(FitsError::PrematureEndOfData);
     }
   }
-  // Complete FITS block of 2880 bytes
-  let mod2880 = ((n_ranges << 1) * T::N_BYTES as usize) % 2880;
-  if mod2880 != 0 {
-    writer.write_all(&vec![0_u8; 2880 - mod2880])?;
-  }
   // Ensure no more data in the iterator
   if range_it.next().is_some() {
     Err(FitsError::RemainingData)
@@ -408,50 +613,81 @@ pub fn range_stmoc_to_fits_ivoa<T: Idx, W: Write>(
   moc: &RangeMOC2<T, Time<T>, T, Hpx<T>>,
   moc_id: Option<String>,
   moc_type: Option<keywords::MocType>,
-  mut writer: W,
+  writer: W,
 ) -> Result<(), FitsError> {
-  let depth_max_time = moc.depth_max_1();
-  let depth_max_hpx = moc.depth_max_2();
-  let n_ranges = moc.compute_n_ranges();
-  let moc_kw_map = build_range_stmoc_keywords(
-    depth_max_time,
-    depth_max_hpx,
-    moc_id,
-    moc_type,
-    PhantomData::<T>,
-  );
-  write_fits_header(&mut writer, T::N_BYTES, n_ranges << 1, moc_kw_map)?;
-  let n_ranges_written = write_ranges2d_data(moc.into_range_moc2_iter(), writer)?;
-  if n_ranges != n_ranges_written as u64 {
-    Err(FitsError::UnexpectedWrittenSize)
-  } else {
-    Ok(())
-  }
+  range_moc2d_to_fits_ivoa(moc, moc_id, moc_type, writer)
 }
 
 pub fn range_sfmoc_to_fits_ivoa<T: Idx, W: Write>(
   moc: &RangeMOC2<T, Frequency<T>, T, Hpx<T>>,
   moc_id: Option<String>,
   moc_type: Option<keywords::MocType>,
-  mut writer: W,
+  writer: W,
 ) -> Result<(), FitsError> {
-  let depth_max_freq = moc.depth_max_1();
-  let depth_max_hpx = moc.depth_max_2();
-  let n_ranges = moc.compute_n_ranges();
-  let moc_kw_map = build_range_sfmoc_keywords(
-    depth_max_freq,
-    depth_max_hpx,
-    moc_id,
-    moc_type,
+  range_moc2d_to_fits_ivoa(moc, moc_id, moc_type, writer)
+}
+
+/// Write a 2D-MOC made of any pair of quantities supported by the `MOCDIM` keyword
+/// (see [MocDim::from_2d]), e.g. a ST-MOC, a SF-MOC or a TF-MOC.
+pub fn range_moc2d_to_fits_ivoa<T, Q1, Q2, W>(
+  moc: &RangeMOC2<T, Q1, T, Q2>,
+  moc_id: Option<String>,
+  moc_type: Option<keywords::MocType>,
+  writer: W,
+) -> Result<(), FitsError>
+where
+  T: Idx,
+  Q1: MocQty<T>,
+  Q2: MocQty<T>,
+  W: Write,
+{
+  range_moc2d_to_fits_ivoa_with_metadata(moc, &MocMetadata::new(moc_id, moc_type), writer)
+}
+
+/// Same as [range_moc2d_to_fits_ivoa], but writing the given metadata in the header.
+/// If [MocMetadata::checksum] is set, the `DATASUM` is computed in a first pass over the MOC.
+pub fn range_moc2d_to_fits_ivoa_with_metadata<T, Q1, Q2, W>(
+  moc: &RangeMOC2<T, Q1, T, Q2>,
+  metadata: &MocMetadata,
+  mut writer: W,
+) -> Result<(), FitsError>
+where
+  T: Idx,
+  Q1: MocQty<T>,
+  Q2: MocQty<T>,
+  W: Write,
+{
+  let moc_kw_map = build_range_moc2d_keywords(
+    moc.depth_max_1(),
+    moc.depth_max_2(),
+    metadata,
     PhantomData::<T>,
-  );
-  write_fits_header(&mut writer, T::N_BYTES, n_ranges << 1, moc_kw_map)?;
-  let n_ranges_written = write_ranges2d_data(moc.into_range_moc2_iter(), writer)?;
-  if n_ranges != n_ranges_written as u64 {
-    Err(FitsError::UnexpectedWrittenSize)
+    PhantomData::<Q1>,
+    PhantomData::<Q2>,
+  )?;
+  let n_ranges = moc.compute_n_ranges();
+  let datasum = if metadata.checksum {
+    // First pass computing the DATASUM, without keeping the data in memory
+    let mut checksum_writer = ChecksumWriter::new(io::sink());
+    write_ranges2d_data(moc.into_range_moc2_iter(), &mut checksum_writer)?;
+    Some(checksum_writer.sum())
   } else {
-    Ok(())
+    None
+  };
+  write_primary_hdu(&mut writer)?;
+  write_bintable_hdu_header(
+    &mut writer,
+    T::N_BYTES,
+    n_ranges << 1,
+    moc_kw_map,
+    metadata,
+    datasum,
+  )?;
+  let n_ranges_written = write_ranges2d_data(moc.into_range_moc2_iter(), &mut writer)?;
+  if n_ranges != n_ranges_written as u64 {
+    return Err(FitsError::UnexpectedWrittenSize);
   }
+  write_data_padding(writer, (n_ranges << 1) * T::N_BYTES as u64)
 }
 
 ///
@@ -463,7 +699,7 @@ pub fn ranges_st_to_fits_ivoa<T, I, J, K, L, W>(
   moc_it: L,
   moc_id: Option<String>,
   moc_type: Option<keywords::MocType>,
-  mut writer: W,
+  writer: W,
 ) -> Result<(), FitsError>
 where
   T: Idx,
@@ -473,25 +709,7 @@ where
   L: RangeMOC2Iterator<T, I::Qty, I, T, J::Qty, J, K>,
   W: Write,
 {
-  let depth_max_time = moc_it.depth_max_1();
-  let depth_max_hpx = moc_it.depth_max_2();
-  let moc_kw_map = build_range_stmoc_keywords(
-    depth_max_time,
-    depth_max_hpx,
-    moc_id,
-    moc_type,
-    PhantomData::<T>,
-  );
-  let mut mem_writter: Vec<u8> = Vec::with_capacity(1024); // 1kB
-  let n_ranges_written = write_ranges2d_data(moc_it, &mut mem_writter)?;
-  write_fits_header(
-    &mut writer,
-    T::N_BYTES,
-    (n_ranges_written as u64) << 1,
-    moc_kw_map,
-  )?;
-  writer.write_all(&mem_writter)?;
-  Ok(())
+  ranges2d_to_fits_ivoa(moc_it, moc_id, moc_type, writer)
 }
 
 ///
@@ -503,7 +721,7 @@ pub fn ranges_sf_to_fits_ivoa<T, I, J, K, L, W>(
   moc_it: L,
   moc_id: Option<String>,
   moc_type: Option<keywords::MocType>,
-  mut writer: W,
+  writer: W,
 ) -> Result<(), FitsError>
 where
   T: Idx,
@@ -513,25 +731,84 @@ where
   L: RangeMOC2Iterator<T, I::Qty, I, T, J::Qty, J, K>,
   W: Write,
 {
-  let depth_max_freq = moc_it.depth_max_1();
-  let depth_max_hpx = moc_it.depth_max_2();
-  let moc_kw_map = build_range_sfmoc_keywords(
-    depth_max_freq,
-    depth_max_hpx,
-    moc_id,
-    moc_type,
+  ranges2d_to_fits_ivoa(moc_it, moc_id, moc_type, writer)
+}
+
+/// Generic version of [ranges_st_to_fits_ivoa] and [ranges_sf_to_fits_ivoa], accepting any
+/// pair of quantities supported by the `MOCDIM` keyword (see [MocDim::from_2d]).
+///
+/// # Info
+/// * The data is first written in memory since we do not know in advance the number of ranges
+/// * For best performances when writing in a file, use a `BufWriter` in input.
+pub fn ranges2d_to_fits_ivoa<T, I, J, K, L, W>(
+  moc_it: L,
+  moc_id: Option<String>,
+  moc_type: Option<keywords::MocType>,
+  writer: W,
+) -> Result<(), FitsError>
+where
+  T: Idx,
+  I: RangeMOCIterator<T>,
+  J: RangeMOCIterator<T>,
+  K: RangeMOC2ElemIt<T, I::Qty, T, J::Qty, It1 = I, It2 = J>,
+  L: RangeMOC2Iterator<T, I::Qty, I, T, J::Qty, J, K>,
+  W: Write,
+{
+  ranges2d_to_fits_ivoa_with_metadata(moc_it, &MocMetadata::new(moc_id, moc_type), writer)
+}
+
+/// Same as [ranges2d_to_fits_ivoa], but writing the given metadata in the header.
+/// The `CHECKSUM` and `DATASUM` keywords are written if [MocMetadata::checksum] is set.
+pub fn ranges2d_to_fits_ivoa_with_metadata<T, I, J, K, L, W>(
+  moc_it: L,
+  metadata: &MocMetadata,
+  mut writer: W,
+) -> Result<(), FitsError>
+where
+  T: Idx,
+  I: RangeMOCIterator<T>,
+  J: RangeMOCIterator<T>,
+  K: RangeMOC2ElemIt<T, I::Qty, T, J::Qty, It1 = I, It2 = J>,
+  L: RangeMOC2Iterator<T, I::Qty, I, T, J::Qty, J, K>,
+  W: Write,
+{
+  write_primary_hdu(&mut writer)?;
+  write_ranges2d_fits_hdu(moc_it, metadata, writer)
+}
+
+/// Writes the BINTABLE HDU of [ranges2d_to_fits_ivoa_with_metadata] (i.e. without the primary
+/// HDU).
+fn write_ranges2d_fits_hdu<T, I, J, K, L, W>(
+  moc_it: L,
+  metadata: &MocMetadata,
+  writer: W,
+) -> Result<(), FitsError>
+where
+  T: Idx,
+  I: RangeMOCIterator<T>,
+  J: RangeMOCIterator<T>,
+  K: RangeMOC2ElemIt<T, I::Qty, T, J::Qty, It1 = I, It2 = J>,
+  L: RangeMOC2Iterator<T, I::Qty, I, T, J::Qty, J, K>,
+  W: Write,
+{
+  let moc_kw_map = build_range_moc2d_keywords(
+    moc_it.depth_max_1(),
+    moc_it.depth_max_2(),
+    metadata,
     PhantomData::<T>,
-  );
+    PhantomData::<I::Qty>,
+    PhantomData::<J::Qty>,
+  )?;
   let mut mem_writter: Vec<u8> = Vec::with_capacity(1024); // 1kB
   let n_ranges_written = write_ranges2d_data(moc_it, &mut mem_writter)?;
-  write_fits_header(
-    &mut writer,
+  write_bintable_hdu(
+    writer,
     T::N_BYTES,
     (n_ranges_written as u64) << 1,
     moc_kw_map,
-  )?;
-  writer.write_all(&mem_writter)?;
-  Ok(())
+    metadata,
+    &mem_writter,
+  )
 }
 
 // Returns the number of elements written
@@ -560,105 +837,87 @@ where
       n_ranges_written += 1;
     }
   }
-  // Complete FITS block of 2880 bytes
-  let mod2880 = ((n_ranges_written << 1) * T::N_BYTES as usize) % 2880;
-  if mod2880 != 0 {
-    writer.write_all(&vec![0_u8; 2880 - mod2880])?;
-  }
   Ok(n_ranges_written)
 }
 
-// Only implemented for ST-MOC so far but easy to generalize.
-// Same Idx type for both Time and Space.
-fn build_range_stmoc_keywords<T: Idx>(
-  depth_max_time: u8,
-  depth_max_hpx: u8,
-  moc_id: Option<String>,
-  moc_type: Option<keywords::MocType>,
+/// Same Idx type for both dimensions.
+fn build_range_moc2d_keywords<T: Idx, Q1: MocQty<T>, Q2: MocQty<T>>(
+  depth_max_1: u8,
+  depth_max_2: u8,
+  metadata: &MocMetadata,
   _t_type: PhantomData<T>,
-) -> MocKeywordsMap {
+  _q1_type: PhantomData<Q1>,
+  _q2_type: PhantomData<Q2>,
+) -> Result<MocKeywordsMap, FitsError> {
+  let moc_dim = MocDim::from_2d(Q1::MOC_DIM, Q2::MOC_DIM).ok_or_else(|| {
+    FitsError::Custom(format!(
+      "2D-MOC of {} and {} not supported.",
+      Q1::NAME,
+      Q2::NAME
+    ))
+  })?;
   let mut moc_kws = MocKeywordsMap::new();
   moc_kws.insert(MocKeywords::MOCVers(MocVers::V2_0));
-  moc_kws.insert(MocKeywords::MOCDim(MocDim::TimeSpace)); // To be changed for generalization
+  moc_kws.insert(MocKeywords::MOCDim(moc_dim));
   moc_kws.insert(MocKeywords::Ordering(Ordering::Range));
-  moc_kws.insert(MocKeywords::CoordSys(CoordSys::ICRS));
-  moc_kws.insert(MocKeywords::MOCOrdS(MocOrdS {
-    depth: depth_max_hpx,
-  }));
-  moc_kws.insert(MocKeywords::TimeSys(TimeSys::TCB));
-  moc_kws.insert(MocKeywords::MOCOrdT(MocOrdT {
-    depth: depth_max_time,
-  }));
-  if let Some(id) = moc_id {
-    moc_kws.insert(MocKeywords::MOCId(MocId { id }));
-  }
-  moc_kws.insert(MocKeywords::MOCTool(MocTool {
-    tool: String::from("CDS MOC Rust lib"),
-  }));
-  if let Some(mtype) = moc_type {
-    moc_kws.insert(MocKeywords::MOCType(mtype));
-  }
+  insert_qty_keywords::<T, Q1>(&mut moc_kws, depth_max_1);
+  insert_qty_keywords::<T, Q2>(&mut moc_kws, depth_max_2);
+  metadata.insert_moc_keywords(&mut moc_kws);
   // BINTABLE specific
   moc_kws.insert(MocKeywords::TForm1(T::TFORM));
   moc_kws.insert(MocKeywords::TType1(TType1 {
     ttype: String::from("INTERLEAVED_SETS_OF_FLAGGED_RANGES"),
   }));
-  moc_kws
+  Ok(moc_kws)
 }
 
-fn build_range_sfmoc_keywords<T: Idx>(
-  depth_max_freq: u8,
-  depth_max_hpx: u8,
-  moc_id: Option<String>,
-  moc_type: Option<keywords::MocType>,
-  _t_type: PhantomData<T>,
-) -> MocKeywordsMap {
-  let mut moc_kws = MocKeywordsMap::new();
-  moc_kws.insert(MocKeywords::MOCVers(MocVers::V2_0));
-  moc_kws.insert(MocKeywords::MOCDim(MocDim::FrequencySpace)); // To be changed for generalization
-  moc_kws.insert(MocKeywords::Ordering(Ordering::Range));
-  moc_kws.insert(MocKeywords::CoordSys(CoordSys::ICRS));
-  moc_kws.insert(MocKeywords::MOCOrdS(MocOrdS {
-    depth: depth_max_hpx,
-  }));
-  moc_kws.insert(MocKeywords::MOCOrdF(MocOrdF {
-    depth: depth_max_freq,
-  }));
-  if let Some(id) = moc_id {
-    moc_kws.insert(MocKeywords::MOCId(MocId { id }));
+/// Insert the keywords specific to the given quantity (system and depth).
+fn insert_qty_keywords<T: Idx, Q: MocQty<T>>(moc_kws: &mut MocKeywordsMap, depth_max: u8) {
+  if Q::HAS_COOSYS {
+    moc_kws.insert(MocKeywords::CoordSys(CoordSys::ICRS));
+    moc_kws.insert(MocKeywords::MOCOrdS(MocOrdS { depth: depth_max }));
   }
-  moc_kws.insert(MocKeywords::MOCTool(MocTool {
-    tool: String::from("CDS MOC Rust lib"),
-  }));
-  if let Some(mtype) = moc_type {
-    moc_kws.insert(MocKeywords::MOCType(mtype));
+  if Q::HAS_TIMESYS {
+    moc_kws.insert(MocKeywords::TimeSys(TimeSys::TCB));
+    moc_kws.insert(MocKeywords::MOCOrdT(MocOrdT { depth: depth_max }));
+  }
+  if Q::HAS_FREQSYS {
+    moc_kws.insert(MocKeywords::MOCOrdF(MocOrdF { depth: depth_max }));
   }
-  // BINTABLE specific
-  moc_kws.insert(MocKeywords::TForm1(T::TFORM));
-  moc_kws.insert(MocKeywords::TType1(TType1 {
-    ttype: String::from("INTERLEAVED_SETS_OF_FLAGGED_RANGES"),
-  }));
-  moc_kws
 }
 
 // FROM FITS
 
 /// Load a MOC stored in a FITS file implementing the IVOA MOC standard.
+/// The `CHECKSUM` of the header is verified, but not the `DATASUM`
+/// (see [from_fits_ivoa_custom_with_metadata]).
 /// # Params
 /// * `reader`: the FITS file bytes reader
 pub fn from_fits_ivoa<R: BufRead>(reader: R) -> Result<MocIdxType<R>, FitsError> {
-  from_fits_ivoa_custom(reader, false)
+  from_fits_ivoa_custom(reader, false, false)
+}
+
+/// Same as [from_fits_ivoa], but also returns the metadata found in the header
+/// (`MOCID`, `MOCTOOL`, `MOCTYPE`, `EXTNAME`, `DATE` and other non-structural keyword records).
+pub fn from_fits_ivoa_with_metadata<R: BufRead>(
+  reader: R,
+) -> Result<(MocIdxType<R>, MocMetadata), FitsError> {
+  from_fits_ivoa_custom_with_metadata(reader, false, false)
 }
 
 // We do not support compressed MOCs
-/// Load a MOC stored in a FITS file implementing the IVOA MOC standard, with a permissive
-/// option for more flexibility (see WARNING).
+/// Load a MOC stored in a FITS file implementing the IVOA MOC standard, with permissive
+/// options for more flexibility (see WARNING).
 ///
 /// # Params
 /// * `reader`: the FITS file bytes reader
 /// * `coosys_permissive`: if set to true, do not fail if COORDSYS != C
 ///   (made for Aladin Lite v3, to load MOCs associated to Galactic HiPS and to possibly
 ///   allow for planetary MOCs).
+/// * `repair`: if set to true, the MOC is normalised while loading: cells or ranges are sorted,
+///   overlapping or contiguous elements are merged, cells deeper than `MOCORDER` are degraded
+///   and out of bounds elements are clipped, the fixes being logged (see [from_fits_ivoa_repair]
+///   to get them as a [RepairReport]). Only 1D-MOCs are repaired.
 /// # WARNING
 ///   Spatial MOCs are supposed to be defined in the ICRS coordinate system only, see Tab. 3
 ///   of the MOC standard (https://www.ivoa.net/documents/MOC/20220317/REC-moc-2.0-20220317.pdf).
@@ -670,13 +929,78 @@ pub fn from_fits_ivoa<R: BufRead>(reader: R) -> Result<MocIdxType<R>, FitsError>
 ///   the coordinate system in MOC objects, so we cannot prevent operations between MOC based on
 ///   different coosys).
 pub fn from_fits_ivoa_custom<R: BufRead>(
-  mut reader: R,
+  reader: R,
   coosys_permissive: bool,
+  repair: bool,
 ) -> Result<MocIdxType<R>, FitsError> {
+  from_fits_ivoa_custom_with_metadata(reader, coosys_permissive, repair).map(|(moc, _)| moc)
+}
+
+/// Same as [from_fits_ivoa_custom], but also returns the metadata found in the header.
+///
+/// # Info
+/// If the header contains both the `CHECKSUM` and the `DATASUM` keywords, only the header is
+/// verified (i.e. the `CHECKSUM` against the `DATASUM` value): the data unit is lazily read, so
+/// its sum is not compared with `DATASUM`. To check the integrity of the whole file, use
+/// [validate::validate_fits_ivoa].
+pub fn from_fits_ivoa_custom_with_metadata<R: BufRead>(
+  reader: R,
+  coosys_permissive: bool,
+  repair: bool,
+) -> Result<(MocIdxType<R>, MocMetadata), FitsError> {
+  if repair {
+    from_fits_ivoa_repair(reader, coosys_permissive).map(|(moc, metadata, report)| {
+      if !report.is_clean() {
+        warn!("MOC repaired:\n{}", report);
+      }
+      (moc, metadata)
+    })
+  } else {
+    read_fits_ivoa(reader, coosys_permissive, true, false)
+  }
+}
+
+/// Load a possibly malformed MOC stored in a FITS file, repairing it (see the `repair` option of
+/// [from_fits_ivoa_custom]) and returning the report of what has been fixed together with the
+/// MOC and the header metadata.
+pub fn from_fits_ivoa_repair<R: BufRead>(
+  reader: R,
+  coosys_permissive: bool,
+) -> Result<(MocIdxType<R>, MocMetadata, RepairReport), FitsError> {
+  read_fits_ivoa(reader, coosys_permissive, true, true).map(|(moc, metadata)| {
+    let (moc, report) = moc.repair();
+    (moc, metadata, report)
+  })
+}
+
+/// Generic version of [from_fits_ivoa_custom_with_metadata], possibly ignoring the `CHECKSUM`
+/// keyword (to report checksum errors independently of the other errors when validating a file).
+fn read_fits_ivoa<R: BufRead>(
+  mut reader: R,
+  coosys_permissive: bool,
+  verify_checksum: bool,
+  keep_too_deep: bool,
+) -> Result<(MocIdxType<R>, MocMetadata), FitsError> {
   let mut header_block = [b' '; 2880];
   consume_primary_hdu(&mut reader, &mut header_block)?;
-  // Read the extention HDU
-  let mut it80 = next_36_chunks_of_80_bytes(&mut reader, &mut header_block)?;
+  read_moc_hdu(reader, coosys_permissive, verify_checksum, keep_too_deep)
+}
+
+/// Reads the MOC stored in the BINTABLE HDU starting at the current position of the reader
+/// (i.e. once the primary HDU or the previous HDUs have been consumed).
+/// If `keep_too_deep` is true, NUNIQ cells deeper than the MOC depth are kept (instead of being
+/// ignored) and are not sorted, so that they can be repaired.
+fn read_moc_hdu<R: BufRead>(
+  mut reader: R,
+  coosys_permissive: bool,
+  verify_checksum: bool,
+  keep_too_deep: bool,
+) -> Result<(MocIdxType<R>, MocMetadata), FitsError> {
+  let mut header_block = [b' '; 2880];
+  // Read the extention HDU, computing the header checksum
+  reader.read_exact(&mut header_block)?;
+  let mut header_sum = checksum_add(0, &header_block);
+  let mut it80 = header_block.chunks_exact(80);
   // See Table 10 and 17 in https://fits.gsfc.nasa.gov/standard40/fits_standard40aa-le.pdf
   check_keyword_and_val(it80.next().unwrap(), b"XTENSION", b"'BINTABLE'")?;
   check_keyword_and_val(it80.next().unwrap(), b"BITPIX  ", b"8")?;
@@ -691,11 +1015,15 @@ pub fn from_fits_ivoa_custom<R: BufRead>(
   // let data_size n_bytes as usize * n_cells as usize; // N_BYTES ok since BITPIX = 8
   // Read MOC keywords
   let mut moc_kws = MocKeywordsMap::new();
+  let mut other_kw_records: Vec<[u8; 80]> = Vec::new();
   'hr: loop {
     for kw_record in &mut it80 {
       // Parse only MOC related keywords and ignore others
       if let Some(mkw) = MocKeywords::is_moc_kw(kw_record) {
-        if mkw.is_err() && common::get_keyword(kw_record) == b"COORDSYS" && coosys_permissive {
+        if coosys_permissive
+          && common::get_keyword(kw_record) == b"COORDSYS"
+          && !matches!(mkw, Ok(MocKeywords::CoordSys(CoordSys::ICRS)))
+        {
           continue;
         }
         if let Some(previous_mkw) = moc_kws.insert(mkw?) {
@@ -710,10 +1038,17 @@ pub fn from_fits_ivoa_custom<R: BufRead>(
         // else keyword added without error
       } else if &kw_record[0..4] == b"END " {
         break 'hr;
+      } else if let Ok(kw_record) = <[u8; 80]>::try_from(kw_record) {
+        other_kw_records.push(kw_record);
       }
     }
     // Read next 2880 bytes
-    it80 = next_36_chunks_of_80_bytes(&mut reader, &mut header_block)?;
+    reader.read_exact(&mut header_block)?;
+    header_sum = checksum_add(header_sum, &header_block);
+    it80 = header_block.chunks_exact(80);
+  }
+  if verify_checksum {
+    verify_header_checksum(header_sum, &other_kw_records)?;
   }
   // CREATE A GUNIQ => General UNIQ in which the order is the order at the maximum depth
   // (and does not depends on the depth).
@@ -723,7 +1058,8 @@ pub fn from_fits_ivoa_custom<R: BufRead>(
   // CREATE RMIXED
   // 0: runiq 1: borne inf (max depht) 2: borne sup (max depth) => very fast binary search :)
   // println!("{:?}", &moc_kws);
-  match moc_kws.get::<MocVers>() {
+  let metadata = MocMetadata::from_header(&moc_kws, &other_kw_records);
+  let moc = match moc_kws.get::<MocVers>() {
     Some(MocKeywords::MOCVers(MocVers::V2_0)) | Some(MocKeywords::MOCVers(MocVers::V2_1)) => {
       match moc_kws.get::<MocDim>() {
         Some(MocKeywords::MOCDim(MocDim::Space)) => {
@@ -740,7 +1076,7 @@ pub fn from_fits_ivoa_custom<R: BufRead>(
           moc_kws.check_coordsys()?;
           match moc_kws.get::<Ordering>() {
             Some(MocKeywords::Ordering(Ordering::Nuniq)) => {
-              load_s_moc_nuniq(reader, n_bytes, n_elems, depth_max, &moc_kws)
+              load_s_moc_nuniq(reader, n_bytes, n_elems, depth_max, keep_too_deep, &moc_kws)
             }
             Some(MocKeywords::Ordering(Ordering::Range)) => {
               load_s_moc_range(reader, n_bytes, n_elems, depth_max, &moc_kws)
@@ -873,6 +1209,39 @@ pub fn from_fits_ivoa_custom<R: BufRead>(
             _ => Err(FitsError::MissingKeyword(Ordering::keyword_string())),
           }
         }
+        Some(MocKeywords::MOCDim(MocDim::TimeFrequency)) => {
+          let depth_max_time = match moc_kws.get::<MocOrdT>() {
+            Some(MocKeywords::MOCOrdT(MocOrdT { depth })) => *depth,
+            _ => return Err(FitsError::MissingKeyword(MocOrdT::keyword_string())),
+          };
+          let depth_max_freq = match moc_kws.get::<MocOrdF>() {
+            Some(MocKeywords::MOCOrdF(MocOrdF { depth })) => *depth,
+            _ => return Err(FitsError::MissingKeyword(MocOrdF::keyword_string())),
+          };
+          match moc_kws.get::<Ordering>() {
+            Some(MocKeywords::Ordering(Ordering::Nuniq)) => {
+              Err(FitsError::UncompatibleKeywordContent(
+                String::from("MOCDIM  = 'TIME.FREQUENCY'"),
+                String::from("ORDERING= 'NUNIQ'"),
+              ))
+            }
+            Some(MocKeywords::Ordering(Ordering::Range)) => load_tf_moc_range(
+              reader,
+              n_bytes,
+              n_elems,
+              depth_max_time,
+              depth_max_freq,
+              &moc_kws,
+            ),
+            Some(MocKeywords::Ordering(Ordering::Range29)) => {
+              Err(FitsError::UncompatibleKeywordContent(
+                String::from("ORDERING  = 'RANGE29'"),
+                String::from("MOCVERS= '2.x'"),
+              ))
+            }
+            _ => Err(FitsError::MissingKeyword(Ordering::keyword_string())),
+          }
+        }
         _ => Err(FitsError::MissingKeyword(MocDim::keyword_string())),
       }
     }
@@ -884,7 +1253,7 @@ pub fn from_fits_ivoa_custom<R: BufRead>(
       };
       match moc_kws.get::<Ordering>() {
         Some(MocKeywords::Ordering(Ordering::Nuniq)) => {
-          load_s_moc_nuniq(reader, n_bytes, n_elems, depth_max, &moc_kws)
+          load_s_moc_nuniq(reader, n_bytes, n_elems, depth_max, keep_too_deep, &moc_kws)
         }
         Some(MocKeywords::Ordering(Ordering::Range)) => {
           load_s_moc_range(reader, n_bytes, n_elems, depth_max, &moc_kws)
@@ -916,7 +1285,8 @@ pub fn from_fits_ivoa_custom<R: BufRead>(
         _ => Err(FitsError::MissingKeyword(Ordering::keyword_string())),
       }
     }
-  }
+  };
+  moc.map(|moc| (moc, metadata))
 }
 
 fn load_s_moc_nuniq<R: BufRead>(
@@ -924,22 +1294,23 @@ fn load_s_moc_nuniq<R: BufRead>(
   n_bytes: u8,
   n_elems: u64,
   depth_max: u8,
+  keep_too_deep: bool,
   moc_kws: &MocKeywordsMap,
 ) -> Result<MocIdxType<R>, FitsError> {
   match (moc_kws.get::<TForm1>(), n_bytes) {
     (Some(MocKeywords::TForm1(TForm1::OneI)), u16::N_BYTES) => {
       Ok(MocIdxType::U16(MocQtyType::Hpx(MocType::Cells(
-        from_fits_nuniq::<u16, R>(reader, depth_max, n_elems as usize)?,
+        from_fits_nuniq::<u16, R>(reader, depth_max, n_elems as usize, keep_too_deep)?,
       ))))
     }
     (Some(MocKeywords::TForm1(TForm1::OneJ)), u32::N_BYTES) => {
       Ok(MocIdxType::U32(MocQtyType::Hpx(MocType::Cells(
-        from_fits_nuniq::<u32, R>(reader, depth_max, n_elems as usize)?,
+        from_fits_nuniq::<u32, R>(reader, depth_max, n_elems as usize, keep_too_deep)?,
       ))))
     }
     (Some(MocKeywords::TForm1(TForm1::OneK)), u64::N_BYTES) => {
       Ok(MocIdxType::U64(MocQtyType::Hpx(MocType::Cells(
-        from_fits_nuniq::<u64, R>(reader, depth_max, n_elems as usize)?,
+        from_fits_nuniq::<u64, R>(reader, depth_max, n_elems as usize, keep_too_deep)?,
       ))))
     }
     (Some(MocKeywords::TForm1(tform)), nb) => Err(FitsError::UncompatibleKeywordContent(
@@ -1198,12 +1569,47 @@ fn load_sf_moc_range<R: BufRead>(
   }
 }
 
+fn load_tf_moc_range<R: BufRead>(
+  reader: R,
+  n_bytes: u8,
+  n_elems: u64,
+  depth_max_time: u8,
+  depth_max_freq: u8,
+  moc_kws: &MocKeywordsMap,
+) -> Result<MocIdxType<R>, FitsError> {
+  let n_ranges = n_elems >> 1;
+  match (moc_kws.get::<TForm1>(), n_bytes) {
+    (Some(MocKeywords::TForm1(TForm1::OneI)), u16::N_BYTES) => {
+      Ok(MocIdxType::U16(MocQtyType::TimeFreq(
+        RangeMoc2DIterFromFits::new_time_freq(depth_max_time, depth_max_freq, reader, n_ranges),
+      )))
+    }
+    (Some(MocKeywords::TForm1(TForm1::OneJ)), u32::N_BYTES) => {
+      Ok(MocIdxType::U32(MocQtyType::TimeFreq(
+        RangeMoc2DIterFromFits::new_time_freq(depth_max_time, depth_max_freq, reader, n_ranges),
+      )))
+    }
+    (Some(MocKeywords::TForm1(TForm1::OneK)), u64::N_BYTES) => {
+      Ok(MocIdxType::U64(MocQtyType::TimeFreq(
+        RangeMoc2DIterFromFits::new_time_freq(depth_max_time, depth_max_freq, reader, n_ranges),
+      )))
+    }
+    (Some(MocKeywords::TForm1(tform)), nb) => Err(FitsError::UncompatibleKeywordContent(
+      format!("NAXIS1  = {}", nb),
+      tform.to_string(),
+    )),
+    (None, _) => Err(FitsError::MissingKeyword(TForm1::keyword_string())),
+    _ => unreachable!(),
+  }
+}
+
 /// Official HEALPix Uniq numbering.
 /// The file is sorted first by depth and then by cell number.
 fn from_fits_nuniq<T, R>(
   mut reader: R,
   mut depth_max: u8,
   n_elems: usize,
+  keep_too_deep: bool,
 ) -> Result<CellMOC<T, Hpx<T>>, FitsError>
 where
   T: Idx,
@@ -1224,7 +1630,7 @@ where
       // Bug in Aladin writing extra uniq of values set to 0!!
       let cell = Cell::from_uniq_hpx(uniq);
       // Bug in old versions of the Java MOC lib writing values deeper than depth_max
-      if cell.depth > depth_max {
+      if cell.depth > depth_max && !keep_too_deep {
         warn!(
           "Wrong NUNIQ: depth {} larger than the MOC depth '{}': the NUNIQ {} is ignored!",
           cell.depth, depth_max, uniq
@@ -1234,7 +1640,9 @@ where
       }
     }
   }
-  v.sort_by(|a, b| a.flat_cmp::<Hpx<T>>(b));
+  if !keep_too_deep {
+    v.sort_by(|a, b| a.flat_cmp::<Hpx<T>>(b));
+  }
   Ok(CellMOC::new(
     depth_max,
     MocCells::<T, Hpx<T>>::new(Cells::new(v)),
@@ -1384,6 +1792,12 @@ impl<T: Idx, R: BufRead> RangeMoc2DIterFromFits<T, R, Frequency<T>, Hpx<T>> {
   }
 }
 
+impl<T: Idx, R: BufRead> RangeMoc2DIterFromFits<T, R, Time<T>, Frequency<T>> {
+  pub fn new_time_freq(depth_max_time: u8, depth_max_freq: u8, reader: R, n_ranges: u64) -> Self {
+    Self::new(depth_max_time, depth_max_freq, reader, n_ranges)
+  }
+}
+
 impl<T: Idx, R: BufRead, Q1: MocQty<T>, Q2: MocQty<T>> RangeMoc2DIterFromFits<T, R, Q1, Q2> {
   fn new(depth_max_dim1: u8, depth_max_dim2: u8, reader: R, n_ranges: u64) -> Self {
     RangeMoc2DIterFromFits {
@@ -1630,16 +2044,14 @@ impl<T: Idx, R: BufRead>
 #[cfg(test)]
 mod tests {
 
-  use std::{
-    fs::File,
-    io::{BufReader, BufWriter},
-    ops::Range,
-    path::PathBuf,
-  };
+  use std::{fs::File, io::BufReader, ops::Range, path::PathBuf};
 
   use crate::deser::fits::{
-    from_fits_ivoa, hpx_cells_to_fits_ivoa, range_stmoc_to_fits_ivoa, ranges_to_fits_ivoa,
-    FitsError, MocIdxType, MocQtyType, MocType, STMocType,
+    from_fits_ivoa, from_fits_ivoa_repair, from_fits_ivoa_with_metadata, hpx_cells_to_fits_ivoa,
+    keywords, metadata::MocMetadata, range_moc2d_to_fits_ivoa,
+    range_moc2d_to_fits_ivoa_with_metadata, range_stmoc_to_fits_ivoa,
+    ranges2d_to_fits_ivoa_with_metadata, ranges_to_fits_ivoa, ranges_to_fits_ivoa_with_metadata,
+    validate::validate_fits_ivoa, FitsError, MocIdxType, MocQtyType, MocType, STMocType,
   };
   use crate::elem::cell::Cell;
   use crate::elemset::{
@@ -1648,12 +2060,13 @@ mod tests {
   };
   use crate::moc::{
     cell::CellMOC, range::RangeMOC, CellMOCIntoIterator, HasMaxDepth, RangeMOCIntoIterator,
+    RangeMOCIterator,
   };
   use crate::moc2d::{
     range::{RangeMOC2, RangeMOC2Elem},
-    HasTwoMaxDepth, RangeMOC2ElemIt,
+    HasTwoMaxDepth, RangeMOC2ElemIt, RangeMOC2IntoIterator,
   };
-  use crate::qty::{Hpx, Time};
+  use crate::qty::{Frequency, Hpx, Time};
 
   #[test]
   fn test_err() {
@@ -1773,14 +2186,15 @@ mod tests {
       end: 2684354560_u64,
     }];
     let moc: RangeMOC<u64, Time<u64>> = RangeMOC::new(35, MocRanges::new_unchecked(ranges));
-    let path_buf1 = PathBuf::from("resources/MOC2.0/TMOC_test.fits");
-    let path_buf2 = PathBuf::from("../resources/MOC2.0/TMOC_test.fits");
-    let file = File::create(&path_buf1)
-      .or_else(|_| File::create(&path_buf2))
-      .unwrap();
-    let writer = BufWriter::new(file);
-    // write: it only tests that no error occur while writing
-    ranges_to_fits_ivoa(moc.into_range_moc_iter(), None, None, writer).unwrap();
+    let mut buff: Vec<u8> = Default::default();
+    ranges_to_fits_ivoa((&moc).into_range_moc_iter(), None, None, &mut buff).unwrap();
+    assert_eq!(buff.len() % 2880, 0);
+    match from_fits_ivoa(BufReader::new(&buff[..])).unwrap() {
+      MocIdxType::U64(MocQtyType::Time(MocType::Ranges(it))) => {
+        assert_eq!(it.into_range_moc(), moc);
+      }
+      _ => panic!("T-MOC expected"),
+    }
   }
 
   #[test]
@@ -1803,14 +2217,15 @@ mod tests {
         .into_boxed_slice(),
       )),
     );
-    let path_buf1 = PathBuf::from("resources/MOC2.0/SMOC_test.fits");
-    let path_buf2 = PathBuf::from("../resources/MOC2.0/SMOC_test.fits");
-    let file = File::create(&path_buf1)
-      .or_else(|_| File::create(&path_buf2))
-      .unwrap();
-    let writer = BufWriter::new(file);
-    // write: it only tests that no error occur while writing
-    hpx_cells_to_fits_ivoa(moc.into_cell_moc_iter(), None, None, writer).unwrap();
+    let mut buff: Vec<u8> = Default::default();
+    hpx_cells_to_fits_ivoa((&moc).into_cell_moc_iter(), None, None, &mut buff).unwrap();
+    assert_eq!(buff.len() % 2880, 0);
+    match from_fits_ivoa(BufReader::new(&buff[..])).unwrap() {
+      MocIdxType::U64(MocQtyType::Hpx(MocType::Cells(it))) => {
+        assert_eq!(it.len(), moc.len());
+      }
+      _ => panic!("S-MOC expected"),
+    }
   }
 
   #[test]
@@ -1895,14 +2310,143 @@ mod tests {
       ),
     ));
     let moc2 = RangeMOC2::new(61, 4, elems);
-    // Open file
-    let path_buf1 = PathBuf::from("resources/MOC2.0/STMOC_test.fits");
-    let path_buf2 = PathBuf::from("../resources/MOC2.0/STMOC_test.fits");
-    let file = File::create(&path_buf1)
-      .or_else(|_| File::create(&path_buf2))
+    let mut buff: Vec<u8> = Default::default();
+    range_stmoc_to_fits_ivoa(&moc2, None, None, &mut buff).unwrap();
+    assert_eq!(buff.len() % 2880, 0);
+    match from_fits_ivoa(BufReader::new(&buff[..])).unwrap() {
+      MocIdxType::U64(MocQtyType::TimeHpx(STMocType::V2(it))) => {
+        let elems: Vec<RangeMOC2Elem<u64, Time<u64>, u64, Hpx<u64>>> = it.collect();
+        assert!(RangeMOC2::new(61, 4, elems).eq_without_depth(&moc2));
+      }
+      _ => panic!("ST-MOC expected"),
+    }
+    // With checksum, the DATASUM being computed in a first pass on the MOC
+    let mut metadata = MocMetadata::default();
+    metadata.checksum = true;
+    let mut buff: Vec<u8> = Default::default();
+    range_moc2d_to_fits_ivoa_with_metadata(&moc2, &metadata, &mut buff).unwrap();
+    assert_eq!(validate_fits_ivoa(&buff), vec![]);
+    // Same result than when writing from an iterator, the data being first written in memory
+    let mut buff2: Vec<u8> = Default::default();
+    ranges2d_to_fits_ivoa_with_metadata((&moc2).into_range_moc2_iter(), &metadata, &mut buff2)
       .unwrap();
-    let writer = BufWriter::new(file);
-    // write: it only tests that no error occur while writing
-    range_stmoc_to_fits_ivoa(&moc2, None, None, writer).unwrap();
+    assert_eq!(buff, buff2);
+    // A modified header is detected when reading
+    let moctool = buff[2880..5760]
+      .chunks_exact(80)
+      .position(|record| record.starts_with(b"MOCTOOL "))
+      .unwrap();
+    buff[2880 + moctool * 80 + 11] ^= 0x20;
+    assert!(matches!(
+      from_fits_ivoa(BufReader::new(&buff[..])),
+      Err(FitsError::WrongChecksum(_))
+    ));
+  }
+
+  #[test]
+  fn test_write_read_tfmoc_fits() {
+    let moc2 = RangeMOC2::<u64, Time<u64>, u64, Frequency<u64>>::from_time_and_freq_in_hz(
+      20,
+      10,
+      vec![
+        (2_451_545_u64 * 86_400_000_000, 1.0e9),
+        (2_451_545_u64 * 86_400_000_000, 2.0e9),
+        (2_460_000_u64 * 86_400_000_000, 1.4e9),
+      ]
+      .into_iter(),
+      None,
+    );
+    let mut buff: Vec<u8> = Default::default();
+    range_moc2d_to_fits_ivoa(&moc2, None, None, &mut buff).unwrap();
+    assert_eq!(buff.len() % 2880, 0);
+    match from_fits_ivoa(BufReader::new(&buff[..])).unwrap() {
+      MocIdxType::U64(MocQtyType::TimeFreq(it)) => {
+        assert_eq!(it.depth_max_1(), 20);
+        assert_eq!(it.depth_max_2(), 10);
+        let elems: Vec<RangeMOC2Elem<u64, Time<u64>, u64, Frequency<u64>>> = it.collect();
+        let actual = RangeMOC2::new(20, 10, elems);
+        assert!(actual.eq_without_depth(&moc2));
+      }
+      _ => panic!("TF-MOC expected"),
+    }
+  }
+
+  #[test]
+  fn test_write_read_metadata_fits() {
+    let moc: RangeMOC<u64, Hpx<u64>> = RangeMOC::new(
+      10,
+      HpxRanges::new_unchecked(vec![1_u64 << 40..3 << 40, 5 << 42..6 << 42]),
+    );
+    let mut metadata = MocMetadata::new(
+      Some(String::from("ivo://CDS/test")),
+      Some(keywords::MocType::Catalog),
+    );
+    metadata.moc_tool = Some(String::from("Handmade"));
+    metadata.extname = Some(String::from("MOC"));
+    metadata.date = Some(String::from("2024-01-01T00:00:00"));
+    metadata.checksum = true;
+    metadata.push_str_card("ORIGIN", "CDS").unwrap();
+    // Force the header to span over several blocks
+    for i in 0..40 {
+      metadata.push_card(&format!("HISTORY line {}", i)).unwrap();
+    }
+    let mut buff: Vec<u8> = Default::default();
+    ranges_to_fits_ivoa_with_metadata((&moc).into_range_moc_iter(), &metadata, &mut buff).unwrap();
+    assert_eq!(buff.len() % 2880, 0);
+    let (moc_idx_type, actual_metadata) =
+      from_fits_ivoa_with_metadata(BufReader::new(&buff[..])).unwrap();
+    assert_eq!(actual_metadata, metadata);
+    // Re-writing the MOC with its metadata leads to the same file
+    let mut buff2: Vec<u8> = Default::default();
+    moc_idx_type
+      .to_fits_ivoa_with_metadata(&actual_metadata, &mut buff2)
+      .unwrap();
+    assert_eq!(buff, buff2);
+    match from_fits_ivoa(BufReader::new(&buff2[..])).unwrap() {
+      MocIdxType::U64(MocQtyType::Hpx(MocType::Ranges(it))) => {
+        assert_eq!(it.into_range_moc(), moc);
+      }
+      _ => panic!("S-MOC expected"),
+    }
+  }
+
+  #[test]
+  fn test_read_repair_fits() {
+    // Redundant cells (the writer sorts the cells by depth only)
+    let moc = CellMOC::<u64, Hpx<u64>>::new(
+      5,
+      MocCells::new(Cells(
+        vec![
+          Cell::new(3, 17),
+          Cell::new(1, 3),
+          Cell::new(5, 50 * 16),
+          Cell::new(5, 17 * 16 + 1),
+          Cell::new(3, 17),
+        ]
+        .into_boxed_slice(),
+      )),
+    );
+    let mut buff: Vec<u8> = Default::default();
+    hpx_cells_to_fits_ivoa(moc.into_cell_moc_iter(), None, None, &mut buff).unwrap();
+    // Set the MOC depth to 3 (so that depth 5 cells are too deep)
+    for record in buff[2880..5760].chunks_exact_mut(80) {
+      if let b"MOCORD_S" | b"MOCORDER" = &record[0..8] {
+        record[10] = b'3';
+      }
+    }
+    assert!(from_fits_ivoa(BufReader::new(&buff[..])).is_ok());
+    let (moc, _, report) = from_fits_ivoa_repair(BufReader::new(&buff[..]), false).unwrap();
+    assert_eq!(report.n_too_deep, 2);
+    assert_eq!(report.n_overlapping, 3);
+    match moc {
+      MocIdxType::U64(MocQtyType::Hpx(MocType::Cells(moc))) => {
+        assert_eq!(moc.depth_max(), 3);
+        assert_eq!(
+          moc.into_cell_moc_iter().collect::<Vec<_>>(),
+          vec![Cell::new(3, 17), Cell::new(1, 3)]
+        );
+      }
+      _ => panic!("S-MOC expected"),
+    }
   }
 }
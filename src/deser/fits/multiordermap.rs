@@ -1,33 +1,34 @@
 use std::{
-  f64::consts::PI,
-  io::{BufRead, BufReader, Read, Seek},
+  io::{BufRead, BufReader, Read, Seek, Write},
+  mem::size_of,
 };
 
-use byteorder::{BigEndian, ReadBytesExt};
+use byteorder::{BigEndian, WriteBytesExt};
 use log::warn;
 
 use crate::{
   deser::{
     fits::{
-      common::{
-        check_keyword_and_parse_uint_val, check_keyword_and_val, consume_primary_hdu,
-        next_36_chunks_of_80_bytes,
-      },
+      bintable::BinTableHeader,
+      common::{str_keyword_record, write_bintable_header, write_data_padding, write_primary_hdu},
       error::FitsError,
-      keywords::{FitsCard, MocKeywords, MocKeywordsMap, MocOrder, Ordering},
+      keywords::{
+        CoordSys, FitsCard, IndexSchema, MocKeywords, MocKeywordsMap, MocOrder, Ordering, PixType,
+      },
     },
-    gz::{is_gz, uncompress},
+    gz::{compress, is_gz, uncompress},
+  },
+  elem::valuedcell::{
+    valued_cells_to_credible_regions, valued_cells_to_moc_with_opt, CredibleRegion, SearchedRegion,
   },
-  elem::valuedcell::valued_cells_to_moc_with_opt,
-  idx::Idx,
   moc::range::RangeMOC,
-  mom::{HpxMOMIterator, HpxMomIter},
-  qty::Hpx,
+  mom::{cell_area, HpxMOMIterator, HpxMomIter},
+  qty::{Hpx, MocQty},
 };
 
 /// We expect the FITS file to be a BINTABLE containing a multi-order map.
-/// To be fast (in execution and development), we start by a non-flexible approach in which we
-/// expect the BINTABLE extension to contains:
+/// The `UNIQ` and `PROBDENSITY` columns are looked for by name (other columns are ignored), see
+/// [columns_from_fits_multiordermap] to read other columns. A typical header is:
 /// ```bash
 /// XTENSION= 'BINTABLE'           / binary table extension                         
 /// BITPIX  =                    8 / array data type                                
@@ -54,6 +55,7 @@ use crate::{
 ///
 /// # Params
 /// * `reader`: the reader over the FITS content
+/// * `column`: name of the density column, by default `PROBDENSITY`
 /// * `cumul_from`: the cumulative value from which cells are put in the MOC
 /// * `cumul_to`: the cumulative value to which cells are put in the MOC
 /// * `asc`: cumulative value computed from lower to highest densities instead of from highest to lowest
@@ -64,8 +66,10 @@ use crate::{
 /// # Info
 ///   Supports gz input stream
 ///
+#[allow(clippy::too_many_arguments)]
 pub fn from_fits_multiordermap<R: Read + Seek>(
   mut reader: BufReader<R>,
+  column: Option<&str>,
   cumul_from: f64,
   cumul_to: f64,
   asc: bool,
@@ -77,6 +81,7 @@ pub fn from_fits_multiordermap<R: Read + Seek>(
     let reader = uncompress(reader);
     from_fits_multiordermap_internal(
       reader,
+      column,
       cumul_from,
       cumul_to,
       asc,
@@ -87,6 +92,7 @@ pub fn from_fits_multiordermap<R: Read + Seek>(
   } else {
     from_fits_multiordermap_internal(
       reader,
+      column,
       cumul_from,
       cumul_to,
       asc,
@@ -97,8 +103,10 @@ pub fn from_fits_multiordermap<R: Read + Seek>(
   }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn from_fits_multiordermap_internal<R: BufRead>(
   reader: R,
+  column: Option<&str>,
   cumul_from: f64,
   cumul_to: f64,
   asc: bool,
@@ -106,19 +114,19 @@ fn from_fits_multiordermap_internal<R: BufRead>(
   no_split: bool,
   reverse_decent: bool,
 ) -> Result<RangeMOC<u64, Hpx<u64>>, FitsError> {
-  let data_it = MultiOrderMapIterator::open(reader)?;
-  let depth_max = data_it.depth_max;
-  let area_per_cell = data_it.area_per_cell;
-  let uniq_val_dens = data_it
-    .map(|res_uniq_dens| {
-      res_uniq_dens.map(|(uniq, dens)| {
-        let (cdepth, _ipix) = Hpx::<u64>::from_uniq_hpx(uniq);
-        let n_sub_cells = (1_u64 << (((depth_max - cdepth) << 1) as u32)) as f64;
-        let value = dens * n_sub_cells * area_per_cell;
-        (uniq, value, dens)
-      })
+  let column = column.unwrap_or("PROBDENSITY");
+  let mom = columns_from_fits_multiordermap_internal(reader, None, Some(&[column]))?;
+  let depth_max = mom.depth_max();
+  let uniq_val_dens = mom
+    .uniqs()
+    .iter()
+    .zip(mom.values[0].iter())
+    .filter(|(_, dens)| !dens.is_nan())
+    .map(|(uniq, dens)| {
+      let (cdepth, _ipix) = Hpx::<u64>::from_uniq_hpx(*uniq);
+      (*uniq, dens * cell_area(cdepth), *dens)
     })
-    .collect::<Result<Vec<(u64, f64, f64)>, FitsError>>()?;
+    .collect::<Vec<(u64, f64, f64)>>();
   // Build the MOC
   let ranges = valued_cells_to_moc_with_opt(
     depth_max,
@@ -133,6 +141,90 @@ fn from_fits_multiordermap_internal<R: BufRead>(
   Ok(RangeMOC::new(depth_max, ranges))
 }
 
+/// Computes, in one pass, the credible regions (MOCs, areas and actual cumulative values) of a
+/// multi-order map for all the given levels, and optionally the searched area and value at
+/// a given position (see [valued_cells_to_credible_regions]).
+///
+/// # Params
+/// * `reader`: the reader over the FITS content
+/// * `column`: name of the density column, by default `PROBDENSITY`
+/// * `levels`: the cumulative values of the credible regions (e.g. `[0.5, 0.9]`)
+/// * `strict`: (sub-)cells overlapping the level values are not added
+/// * `no_split`: cells overlapping the level values are not recursively split
+/// * `reverse_decent`: perform the recursive decent from the highest cell number to the lowest (to be compatible with Aladin)
+/// * `position`: optional `(lon, lat)` position, in radians, at which the searched region is computed
+///
+/// # Info
+///   Supports gz input stream
+///
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn credible_regions_from_fits_multiordermap<R: Read + Seek>(
+  mut reader: BufReader<R>,
+  column: Option<&str>,
+  levels: &[f64],
+  strict: bool,
+  no_split: bool,
+  reverse_decent: bool,
+  position: Option<(f64, f64)>,
+) -> Result<(Vec<CredibleRegion>, Option<SearchedRegion>), FitsError> {
+  if is_gz(&mut reader)? {
+    let reader = uncompress(reader);
+    credible_regions_from_fits_multiordermap_internal(
+      reader,
+      column,
+      levels,
+      strict,
+      no_split,
+      reverse_decent,
+      position,
+    )
+  } else {
+    credible_regions_from_fits_multiordermap_internal(
+      reader,
+      column,
+      levels,
+      strict,
+      no_split,
+      reverse_decent,
+      position,
+    )
+  }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn credible_regions_from_fits_multiordermap_internal<R: BufRead>(
+  reader: R,
+  column: Option<&str>,
+  levels: &[f64],
+  strict: bool,
+  no_split: bool,
+  reverse_decent: bool,
+  position: Option<(f64, f64)>,
+) -> Result<(Vec<CredibleRegion>, Option<SearchedRegion>), FitsError> {
+  let column = column.unwrap_or("PROBDENSITY");
+  let mom = columns_from_fits_multiordermap_internal(reader, None, Some(&[column]))?;
+  let depth_max = mom.depth_max();
+  let uniq_val_dens = mom
+    .uniqs()
+    .iter()
+    .zip(mom.values[0].iter())
+    .filter(|(_, dens)| !dens.is_nan())
+    .map(|(uniq, dens)| {
+      let (cdepth, _ipix) = Hpx::<u64>::from_uniq_hpx(*uniq);
+      (*uniq, dens * cell_area(cdepth), *dens)
+    })
+    .collect::<Vec<(u64, f64, f64)>>();
+  Ok(valued_cells_to_credible_regions(
+    depth_max,
+    uniq_val_dens,
+    levels,
+    strict,
+    no_split,
+    reverse_decent,
+    position,
+  ))
+}
+
 /// Returns the sum of the multi-order map values associated with cells inside the given MOC.
 /// If a cell is partially covered by the MOC, we apply on the value a factor equals to the ratio
 /// of the cell area covered by the MOC over the total cell area.  
@@ -152,133 +244,486 @@ fn sum_from_fits_multiordermap_internal<R: BufRead>(
   reader: R,
   moc: &RangeMOC<u64, Hpx<u64>>,
 ) -> Result<f64, FitsError> {
-  let data_it = MultiOrderMapIterator::open(reader)?;
-  let depth_max = data_it.depth_max;
-  let area_per_cell = data_it.area_per_cell;
-  let mom = data_it
-    .map(|res_uniq_dens| {
-      res_uniq_dens.map(|(uniq, dens)| {
-        let (cdepth, _ipix) = Hpx::<u64>::from_uniq_hpx(uniq);
-        let n_sub_cells = (1_u64 << (((depth_max - cdepth) << 1) as u32)) as f64;
-        let value = dens * n_sub_cells * area_per_cell;
-        (uniq, value)
-      })
-    })
-    .collect::<Result<Vec<(u64, f64)>, FitsError>>()?;
+  let (_depth_max, mom) = mom_from_fits_multiordermap_internal(reader)?;
   let mom_it = HpxMomIter::<u64, Hpx<u64>, f64, _>::new(mom.into_iter());
   Ok(mom_it.sum_values_in_hpxmoc(moc))
 }
 
-struct MultiOrderMapIterator<R: BufRead> {
-  /// Reader
+/// Returns the depth and the `(uniq, value)` pairs of a multi-order map, the values being
+/// the `PROBDENSITY` column values multiplied by the area of their cell (i.e. values are
+/// proportional to the area of the cell, see [crate::mom]).
+///
+/// # Info
+///   Supports gz input stream
+pub fn mom_from_fits_multiordermap<R: Read + Seek>(
+  mut reader: BufReader<R>,
+) -> Result<(u8, Vec<(u64, f64)>), FitsError> {
+  if is_gz(&mut reader)? {
+    mom_from_fits_multiordermap_internal(uncompress(reader))
+  } else {
+    mom_from_fits_multiordermap_internal(reader)
+  }
+}
+
+fn mom_from_fits_multiordermap_internal<R: BufRead>(
   reader: R,
+) -> Result<(u8, Vec<(u64, f64)>), FitsError> {
+  let mom = columns_from_fits_multiordermap_internal(reader, None, Some(&["PROBDENSITY"]))?;
+  let depth_max = mom.depth_max();
+  mom
+    .mom_iter("PROBDENSITY", true)
+    .map(|mom_it| (depth_max, mom_it.collect()))
+}
+
+/// Description of a value column of a multi-order map (or of a skymap) to be written in a
+/// FITS BINTABLE.
+#[derive(Debug, Clone)]
+pub struct ValueColumn {
+  /// Column name (`TTYPEn`), e.g. `PROBDENSITY`
+  pub name: String,
+  /// Column unit (`TUNITn`), e.g. `sr-1`
+  pub unit: Option<String>,
+}
+impl ValueColumn {
+  pub fn new<S: Into<String>>(name: S, unit: Option<S>) -> Self {
+    Self {
+      name: name.into(),
+      unit: unit.map(|u| u.into()),
+    }
+  }
+
+  /// Push the `TTYPEn`, `TFORMn` and (optional) `TUNITn` keyword records of the column
+  /// of index `n` (starting at 1) storing `f64` values.
+  pub(super) fn push_f64_keyword_records(
+    &self,
+    n: usize,
+    keyword_records: &mut Vec<[u8; 80]>,
+  ) -> Result<(), FitsError> {
+    keyword_records.push(str_keyword_record(
+      &indexed_keyword("TTYPE", n)?,
+      &self.name,
+    )?);
+    keyword_records.push(str_keyword_record(&indexed_keyword("TFORM", n)?, "D")?);
+    if let Some(unit) = &self.unit {
+      keyword_records.push(str_keyword_record(&indexed_keyword("TUNIT", n)?, unit)?);
+    }
+    Ok(())
+  }
+}
+
+/// Returns the 8 bytes FITS keyword made of the given prefix followed by the given index.
+fn indexed_keyword(prefix: &str, n: usize) -> Result<[u8; 8], FitsError> {
+  let keyword = format!("{}{:<w$}", prefix, n, w = 8 - prefix.len());
+  keyword
+    .as_bytes()
+    .try_into()
+    .map_err(|_| FitsError::Custom(format!("Keyword too long: '{}'", keyword)))
+}
+
+/// Write a multi-order map in a FITS BINTABLE made of a `UNIQ` column followed by the given
+/// value columns (stored as `f64`).
+/// The written file can be read back by [from_fits_multiordermap] and
+/// [sum_from_fits_multiordermap] if the first value column is a `PROBDENSITY` column.
+///
+/// # Params
+/// * `depth_max`: the multi-order map depth (`MOCORDER`)
+/// * `coordsys`: the coordinate system (`COORDSYS`) of the multi-order map
+/// * `columns`: name and (optional) unit of each value column
+/// * `rows`: the `(uniq, values)` rows, each one having as many values as the number of columns
+/// * `gz`: compress the output stream using gzip
+/// * `writer`: the output stream
+///
+/// # Info
+///   Since the number of rows must be known before writing the header, the rows are buffered.
+pub fn to_fits_multiordermap<W, I, V>(
+  depth_max: u8,
+  coordsys: CoordSys,
+  columns: &[ValueColumn],
+  rows: I,
+  gz: bool,
+  writer: W,
+) -> Result<(), FitsError>
+where
+  W: Write,
+  I: Iterator<Item = (u64, V)>,
+  V: AsRef<[f64]>,
+{
+  if gz {
+    let mut encoder = compress(writer);
+    to_fits_multiordermap_internal(depth_max, coordsys, columns, rows, &mut encoder)?;
+    encoder.finish().map(|_| ()).map_err(FitsError::Io)
+  } else {
+    to_fits_multiordermap_internal(depth_max, coordsys, columns, rows, writer)
+  }
+}
+
+fn to_fits_multiordermap_internal<W, I, V>(
+  depth_max: u8,
+  coordsys: CoordSys,
+  columns: &[ValueColumn],
+  rows: I,
+  mut writer: W,
+) -> Result<(), FitsError>
+where
+  W: Write,
+  I: Iterator<Item = (u64, V)>,
+  V: AsRef<[f64]>,
+{
+  let n_bytes_per_row = (size_of::<u64>() + columns.len() * size_of::<f64>()) as u64;
+  // Buffer the data part
+  let mut data: Vec<u8> = Vec::with_capacity(rows.size_hint().0 * n_bytes_per_row as usize);
+  let mut n_rows = 0_u64;
+  for (uniq, values) in rows {
+    let (depth, _ipix) = Hpx::<u64>::from_uniq_hpx(uniq);
+    if depth > depth_max {
+      return Err(FitsError::UnexpectedDepth(depth, depth_max));
+    }
+    let values = values.as_ref();
+    if values.len() != columns.len() {
+      return Err(FitsError::Custom(format!(
+        "Wrong number of values in row {}. Expected: {}. Actual: {}.",
+        n_rows,
+        columns.len(),
+        values.len()
+      )));
+    }
+    data.write_u64::<BigEndian>(uniq)?;
+    for value in values {
+      data.write_f64::<BigEndian>(*value)?;
+    }
+    n_rows += 1;
+  }
+  // Build the header
+  let mut col_kws = vec![
+    str_keyword_record(b"TTYPE1  ", "UNIQ")?,
+    str_keyword_record(b"TFORM1  ", "K")?,
+  ];
+  for (i, column) in columns.iter().enumerate() {
+    column.push_f64_keyword_records(i + 2, &mut col_kws)?;
+  }
+  let mut moc_kws = MocKeywordsMap::new();
+  moc_kws.insert(MocKeywords::PixType(PixType::Healpix));
+  moc_kws.insert(MocKeywords::Ordering(Ordering::Nuniq));
+  moc_kws.insert(MocKeywords::CoordSys(coordsys));
+  moc_kws.insert(MocKeywords::MOCOrder(MocOrder { depth: depth_max }));
+  moc_kws.insert(MocKeywords::IndexSchema(IndexSchema::Explicit));
+  // Write the primary HDU, the header and the data
+  write_primary_hdu(&mut writer)?;
+  write_bintable_header(
+    &mut writer,
+    n_bytes_per_row,
+    n_rows,
+    1 + columns.len() as u64,
+    &col_kws,
+    moc_kws,
+    &[],
+  )?;
+  writer.write_all(&data)?;
+  write_data_padding(&mut writer, data.len() as u64)
+}
+
+/// Write the given multi-order map in a FITS BINTABLE containing a `UNIQ` column and a
+/// `PROBDENSITY` column (in `sr-1`), i.e. in the format read by [from_fits_multiordermap] and
+/// [sum_from_fits_multiordermap].
+/// Values in the MOM being proportional to the cells area, they are divided by the area
+/// (in steradians) of their cell to get a density.
+///
+/// # Params
+/// * `depth_max`: the multi-order map depth (`MOCORDER`)
+/// * `coordsys`: the coordinate system (`COORDSYS`) of the multi-order map
+/// * `mom`: the multi-order map
+/// * `gz`: compress the output stream using gzip
+/// * `writer`: the output stream
+pub fn hpxmom_to_fits_multiordermap<I, W>(
+  depth_max: u8,
+  coordsys: CoordSys,
+  mom: I,
+  gz: bool,
+  writer: W,
+) -> Result<(), FitsError>
+where
+  I: HpxMOMIterator<u64, f64>,
+  W: Write,
+{
+  let columns = [ValueColumn::new("PROBDENSITY", Some("sr-1"))];
+  let rows = mom.map(|(uniq, value)| {
+    let (depth, _ipix) = Hpx::<u64>::from_uniq_hpx(uniq);
+    (uniq, [value / cell_area(depth)])
+  });
+  to_fits_multiordermap(depth_max, coordsys, &columns, rows, gz, writer)
+}
+
+/// Multi-order map read from a FITS BINTABLE: a column of HEALPix `UNIQ` indices plus any
+/// number of scalar value columns (e.g. `PROBDENSITY`, `DISTMU`, `DISTSIGMA` and `DISTNORM`
+/// in LIGO/Virgo maps).
+/// Blank values (`NaN`, `BAD_DATA`, `TNULLn`, ...) are stored as `NaN`.
+#[derive(Debug, Clone)]
+pub struct MultiOrderMapColumns {
   /// MOM depth
   depth_max: u8,
-  /// Area of a cell at the max MOM depth
-  area_per_cell: f64,
-  /// Number of rows to be read
-  n_rows: u64,
-  /// Number of rows already returned
-  n_rows_consumed: u64,
-  /// Used to consume row bytes we are not interested in
-  sink: Vec<u8>,
+  /// Name of the `UNIQ` column
+  uniq_column: String,
+  /// Name and unit of the value columns
+  columns: Vec<ValueColumn>,
+  /// `UNIQ` index of each row
+  uniqs: Vec<u64>,
+  /// Values of each column (`values[icol][irow]`)
+  values: Vec<Vec<f64>>,
 }
 
-impl<R: BufRead> MultiOrderMapIterator<R> {
-  fn open(mut reader: R) -> Result<Self, FitsError> {
-    let mut header_block = [b' '; 2880];
-    consume_primary_hdu(&mut reader, &mut header_block)?;
-    // Read the extention HDU
-    let mut it80 = next_36_chunks_of_80_bytes(&mut reader, &mut header_block)?;
-    // See Table 10 and 17 in https://fits.gsfc.nasa.gov/standard40/fits_standard40aa-le.pdf
-    check_keyword_and_val(it80.next().unwrap(), b"XTENSION", b"'BINTABLE'")?;
-    check_keyword_and_val(it80.next().unwrap(), b"BITPIX  ", b"8")?;
-    check_keyword_and_val(it80.next().unwrap(), b"NAXIS  ", b"2")?;
-    let n_bytes_per_row =
-      check_keyword_and_parse_uint_val::<u64>(it80.next().unwrap(), b"NAXIS1  ")?;
-    let n_rows = check_keyword_and_parse_uint_val::<u64>(it80.next().unwrap(), b"NAXIS2 ")?;
-    check_keyword_and_val(it80.next().unwrap(), b"PCOUNT  ", b"0")?;
-    check_keyword_and_val(it80.next().unwrap(), b"GCOUNT  ", b"1")?;
-    let _n_cols = check_keyword_and_parse_uint_val::<u64>(it80.next().unwrap(), b"TFIELDS ")?;
-    check_keyword_and_val(it80.next().unwrap(), b"TTYPE1 ", b"'UNIQ    '")?;
-    check_keyword_and_val(it80.next().unwrap(), b"TFORM1 ", b"'K       '")?;
-    check_keyword_and_val(it80.next().unwrap(), b"TTYPE2 ", b"'PROBDENSITY'")?;
-    check_keyword_and_val(it80.next().unwrap(), b"TFORM2 ", b"'D       '")?;
-    // nbits = |BITPIX|xGCOUNTx(PCOUNT+NAXIS1xNAXIS2x...xNAXISn)
-    // In our case (bitpix = 8, GCOUNT = 1, PCOUNT = 0) => nbytes = n_cells * size_of(T)
-    // let data_size n_bytes as usize * n_cells as usize; // N_BYTES ok since BITPIX = 8
-    // Read MOC keywords
-    let mut moc_kws = MocKeywordsMap::new();
-    'hr: loop {
-      for kw_record in &mut it80 {
-        // Parse only MOC related keywords and ignore others
-        if let Some(mkw) = MocKeywords::is_moc_kw(kw_record) {
-          if let Some(previous_mkw) = moc_kws.insert(mkw?) {
-            // A FITS keyword MUST BE uniq (I may be more relax here, taking the last one and not complaining)
-            // return Err(FitsError::MultipleKeyword(previous_mkw.keyword_str().to_string()))
+impl MultiOrderMapColumns {
+  /// Assumes (without checking!) that there is one vector of values per column, each one
+  /// having as many values as the number of `uniqs`.
+  pub(crate) fn new(
+    depth_max: u8,
+    uniq_column: String,
+    columns: Vec<ValueColumn>,
+    uniqs: Vec<u64>,
+    values: Vec<Vec<f64>>,
+  ) -> Self {
+    Self {
+      depth_max,
+      uniq_column,
+      columns,
+      uniqs,
+      values,
+    }
+  }
+
+  pub fn depth_max(&self) -> u8 {
+    self.depth_max
+  }
+
+  pub fn uniq_column(&self) -> &str {
+    &self.uniq_column
+  }
+
+  pub fn columns(&self) -> &[ValueColumn] {
+    &self.columns
+  }
+
+  pub fn n_rows(&self) -> usize {
+    self.uniqs.len()
+  }
+
+  pub fn uniqs(&self) -> &[u64] {
+    &self.uniqs
+  }
+
+  /// Returns the index of the value column of given name (case insensitive), if any.
+  pub fn column_index(&self, name: &str) -> Option<usize> {
+    self
+      .columns
+      .iter()
+      .position(|col| col.name.eq_ignore_ascii_case(name))
+  }
+
+  /// Returns the values, one per row, of the column of given name (case insensitive), if any.
+  pub fn values(&self, name: &str) -> Option<&[f64]> {
+    self
+      .column_index(name)
+      .map(|icol| self.values[icol].as_slice())
+  }
+
+  /// Returns an iterator over the `(uniq, values)` rows, values being in the columns order.
+  pub fn rows(&self) -> impl Iterator<Item = (u64, Vec<f64>)> + '_ {
+    self.uniqs.iter().enumerate().map(move |(irow, uniq)| {
+      (
+        *uniq,
+        self
+          .values
+          .iter()
+          .map(|col| col[irow])
+          .collect::<Vec<f64>>(),
+      )
+    })
+  }
+
+  /// Returns the multi-order map made of the `(uniq, value)` pairs of the column of given name,
+  /// skipping blank values.
+  ///
+  /// # Params
+  /// * `name`: name of the value column (case insensitive)
+  /// * `density`: the column contains densities (e.g. `PROBDENSITY` in `sr-1`) which are
+  ///   multiplied by the area of their cell (in steradians) to get values proportional
+  ///   to the cells area (see [crate::mom])
+  #[allow(clippy::type_complexity)]
+  pub fn mom_iter(
+    &self,
+    name: &str,
+    density: bool,
+  ) -> Result<HpxMomIter<u64, Hpx<u64>, f64, impl Iterator<Item = (u64, f64)> + '_>, FitsError> {
+    let icol = self.column_index(name).ok_or_else(|| {
+      FitsError::Custom(format!(
+        "Column '{}' not found. Available columns: {:?}",
+        name,
+        self
+          .columns
+          .iter()
+          .map(|col| col.name.as_str())
+          .collect::<Vec<&str>>()
+      ))
+    })?;
+    let it = self
+      .uniqs
+      .iter()
+      .zip(self.values[icol].iter())
+      .filter(|(_, val)| !val.is_nan())
+      .map(move |(uniq, val)| {
+        if density {
+          let (depth, _ipix) = Hpx::<u64>::from_uniq_hpx(*uniq);
+          (*uniq, val * cell_area(depth))
+        } else {
+          (*uniq, *val)
+        }
+      });
+    Ok(HpxMomIter::new(it))
+  }
+}
+
+/// Read a multi-order map stored in a FITS BINTABLE, selecting the `UNIQ` column and the value
+/// columns by name.
+/// The `UNIQ` column may be of type `J`, `K`, `E` or `D` (floating point indices must be
+/// integers) and value columns of type `B`, `I`, `J`, `K`, `E` or `D`.
+/// If the `MOCORDER` keyword is missing, the MOM depth is the largest depth of the `UNIQ` cells.
+///
+/// # Params
+/// * `reader`: the reader over the FITS content
+/// * `uniq_column`: name of the `UNIQ` column, by default `UNIQ` (or the first column if no
+///   column is named `UNIQ`)
+/// * `value_columns`: names of the value columns to be read, by default all scalar columns
+///   (but the `UNIQ` one)
+///
+/// # Info
+///   Supports gz input stream
+pub fn columns_from_fits_multiordermap<R: Read + Seek>(
+  mut reader: BufReader<R>,
+  uniq_column: Option<&str>,
+  value_columns: Option<&[&str]>,
+) -> Result<MultiOrderMapColumns, FitsError> {
+  if is_gz(&mut reader)? {
+    columns_from_fits_multiordermap_internal(uncompress(reader), uniq_column, value_columns)
+  } else {
+    columns_from_fits_multiordermap_internal(reader, uniq_column, value_columns)
+  }
+}
+
+fn columns_from_fits_multiordermap_internal<R: BufRead>(
+  mut reader: R,
+  uniq_column: Option<&str>,
+  value_columns: Option<&[&str]>,
+) -> Result<MultiOrderMapColumns, FitsError> {
+  const VALUE_TYPES: &[u8] = b"BIJKED";
+  const VALUE_TYPES_STR: &str = "['B', 'I', 'J', 'K', 'E' or 'D']";
+  let header = BinTableHeader::parse(&mut reader)?;
+  // Check header params
+  let moc_kws = &header.moc_kws;
+  moc_kws.check_pixtype()?;
+  moc_kws.check_ordering(Ordering::Nuniq)?;
+  if let Err(e) = moc_kws.check_coordsys() {
+    warn!("{}", e);
+  }
+  // Select columns
+  let uniq_icol = match uniq_column {
+    Some(name) => header.find_column(name)?,
+    None => header.position("UNIQ").unwrap_or(0),
+  };
+  let uniq_reader = header.value_reader(uniq_icol, b"JKED", "['J', 'K', 'E' or 'D']")?;
+  let value_icols = match value_columns {
+    Some(names) => names
+      .iter()
+      .map(|name| header.find_column(name))
+      .collect::<Result<Vec<usize>, FitsError>>()?,
+    None => (0..header.columns.len())
+      .filter(|icol| {
+        *icol != uniq_icol && {
+          let supported = header
+            .value_reader(*icol, VALUE_TYPES, VALUE_TYPES_STR)
+            .map(|reader| reader.tform.repeat == 1)
+            .unwrap_or(false);
+          if !supported {
             warn!(
-              "Keyword '{}' found more than once in a same HDU! We use the first occurrence.",
-              previous_mkw.keyword_str()
+              "Column '{}' ignored: not a scalar numeric column.",
+              header.columns[*icol].name
             );
-            moc_kws.insert(previous_mkw);
           }
-          // else keyword added without error
-        } else if &kw_record[0..4] == b"END " {
-          break 'hr;
+          supported
         }
-      }
-      // Read next 2880 bytes
-      it80 = next_36_chunks_of_80_bytes(&mut reader, &mut header_block)?;
+      })
+      .collect(),
+  };
+  let value_readers = value_icols
+    .iter()
+    .map(|icol| header.value_reader(*icol, VALUE_TYPES, VALUE_TYPES_STR))
+    .collect::<Result<Vec<_>, FitsError>>()?;
+  for (icol, reader) in std::iter::once((uniq_icol, &uniq_reader))
+    .chain(value_icols.iter().cloned().zip(&value_readers))
+  {
+    if reader.tform.repeat != 1 {
+      return Err(FitsError::Custom(format!(
+        "Column '{}' is a vector column (repeat = {}), only scalar columns are supported.",
+        header.columns[icol].name, reader.tform.repeat
+      )));
     }
-    // Check header params
-    moc_kws.check_pixtype()?;
-    moc_kws.check_ordering(Ordering::Nuniq)?;
-    moc_kws.check_coordsys()?;
-    // - get MOC depth
-    let depth_max = match moc_kws.get::<MocOrder>() {
-      Some(MocKeywords::MOCOrder(MocOrder { depth })) => Ok(*depth),
-      _ => Err(FitsError::MissingKeyword(MocOrder::keyword_string())),
-    }?;
-    let n_byte_skip = (n_bytes_per_row - 16) as usize;
-    let sink = vec![0; n_byte_skip];
-    let area_per_cell = (PI / 3.0) / (1_u64 << (depth_max << 1) as u32) as f64; // = 4pi / (12*4^depth)
-    Ok(Self {
-      reader,
-      depth_max,
-      area_per_cell,
-      n_rows,
-      n_rows_consumed: 0,
-      sink,
-    })
   }
-}
-impl<R: BufRead> Iterator for MultiOrderMapIterator<R> {
-  type Item = Result<(u64, f64), FitsError>;
-
-  fn next(&mut self) -> Option<Self::Item> {
-    if self.n_rows_consumed < self.n_rows {
-      self.n_rows_consumed += 1;
-      Some(
-        u64::read::<_, BigEndian>(&mut self.reader)
-          .and_then(|uniq| {
-            self.reader.read_f64::<BigEndian>().and_then(|dens| {
-              self
-                .reader
-                .read_exact(&mut self.sink)
-                .map(|()| (uniq, dens))
-            })
-          })
-          .map_err(FitsError::Io),
-      )
-    } else {
-      None
+  // Read data
+  let n_rows = header.n_rows as usize;
+  let mut row = vec![0_u8; header.n_bytes_per_row as usize];
+  let mut uniqs = Vec::with_capacity(n_rows);
+  let mut values = vec![Vec::with_capacity(n_rows); value_readers.len()];
+  let mut data_depth_max = 0_u8;
+  for irow in 0..n_rows {
+    reader.read_exact(&mut row)?;
+    let uniq = match uniq_reader.tform.dtype {
+      b'J' | b'K' => Some(uniq_reader.read_int(&row, 0))
+        .filter(|uniq| *uniq >= 4)
+        .map(|uniq| uniq as u64),
+      _ => uniq_reader
+        .read(&row, 0)
+        .filter(|uniq| *uniq >= 4.0 && *uniq < u64::MAX as f64 && uniq.fract() == 0.0)
+        .map(|uniq| uniq as u64),
+    }
+    .ok_or_else(|| FitsError::Custom(format!("Wrong UNIQ value in row {}.", irow)))?;
+    let (depth, _ipix) = Hpx::<u64>::from_uniq_hpx(uniq);
+    if depth > Hpx::<u64>::MAX_DEPTH {
+      return Err(FitsError::UnexpectedDepth(depth, Hpx::<u64>::MAX_DEPTH));
+    }
+    data_depth_max = data_depth_max.max(depth);
+    uniqs.push(uniq);
+    for (col_values, value_reader) in values.iter_mut().zip(&value_readers) {
+      col_values.push(value_reader.read(&row, 0).unwrap_or(f64::NAN));
     }
   }
-
-  fn size_hint(&self) -> (usize, Option<usize>) {
-    let n_rows_remaining = (self.n_rows - self.n_rows_consumed) as usize;
-    (n_rows_remaining, Some(n_rows_remaining))
-  }
+  // - get MOC depth
+  let depth_max = match moc_kws.get::<MocOrder>() {
+    Some(MocKeywords::MOCOrder(MocOrder { depth })) => {
+      if data_depth_max > *depth {
+        return Err(FitsError::UnexpectedDepth(data_depth_max, *depth));
+      }
+      *depth
+    }
+    _ => {
+      warn!(
+        "Keyword '{}' not found, we use the largest cell depth: {}.",
+        MocOrder::keyword_string(),
+        data_depth_max
+      );
+      data_depth_max
+    }
+  };
+  Ok(MultiOrderMapColumns::new(
+    depth_max,
+    header.columns[uniq_icol].name.clone(),
+    value_icols
+      .iter()
+      .map(|icol| ValueColumn {
+        name: header.columns[*icol].name.clone(),
+        unit: header.columns[*icol].unit.clone(),
+      })
+      .collect(),
+    uniqs,
+    values,
+  ))
 }
 
 #[cfg(test)]
@@ -286,7 +731,23 @@ mod tests {
 
   use std::{fs::File, io::BufReader, path::PathBuf};
 
-  use super::{from_fits_multiordermap, sum_from_fits_multiordermap};
+  use std::io::Cursor;
+
+  use byteorder::{BigEndian, WriteBytesExt};
+
+  use super::{
+    columns_from_fits_multiordermap, credible_regions_from_fits_multiordermap,
+    from_fits_multiordermap, hpxmom_to_fits_multiordermap, mom_from_fits_multiordermap,
+    sum_from_fits_multiordermap, to_fits_multiordermap, ValueColumn,
+  };
+  use crate::{
+    deser::fits::{
+      common::{str_keyword_record, write_bintable_header, write_data_padding, write_primary_hdu},
+      keywords::{CoordSys, MocKeywords, MocKeywordsMap, Ordering, PixType},
+    },
+    mom::HpxMomIter,
+    qty::Hpx,
+  };
 
   #[test]
   fn test_mutliordermap() {
@@ -296,7 +757,7 @@ mod tests {
       .or_else(|_| File::open(&path_buf2))
       .unwrap();
     let reader = BufReader::new(file);
-    let res = from_fits_multiordermap(reader, 0.0, 0.9, false, true, true, false);
+    let res = from_fits_multiordermap(reader, None, 0.0, 0.9, false, true, true, false);
     match res {
       Ok(o) => {
         print!("{:?}", o);
@@ -318,7 +779,7 @@ mod tests {
       .unwrap();
     let reader = BufReader::new(file);
     // First create MOC
-    let moc = from_fits_multiordermap(reader, 0.0, 0.9, false, true, true, false).unwrap();
+    let moc = from_fits_multiordermap(reader, None, 0.0, 0.9, false, true, true, false).unwrap();
 
     // Then compute the sum inside the MOC (should be 90%, i.e, 0.9).
     let file = File::open(&path_buf1)
@@ -329,4 +790,197 @@ mod tests {
     println!("value: {}", sum);
     assert!((0.8999..0.9001).contains(&sum));
   }
+
+  #[test]
+  fn test_mutliordermap_credible_regions() {
+    let path_buf1 = PathBuf::from("resources/LALInference.multiorder.fits");
+    let path_buf2 = PathBuf::from("../resources/LALInference.multiorder.fits");
+    let file = File::open(&path_buf1)
+      .or_else(|_| File::open(&path_buf2))
+      .unwrap();
+    let reader = BufReader::new(file);
+    let (regions, searched) = credible_regions_from_fits_multiordermap(
+      reader,
+      None,
+      &[0.5, 0.9],
+      true,
+      false,
+      false,
+      Some((0.0, 0.0)),
+    )
+    .unwrap();
+    assert_eq!(regions.len(), 2);
+    assert!(regions[0].area_deg2 < regions[1].area_deg2);
+    assert!(regions[0].moc.minus(&regions[1].moc).is_empty());
+    let area_90 = regions[1].area_deg2;
+    // Same MOCs as the ones built level by level
+    for region in regions {
+      let file = File::open(&path_buf1)
+        .or_else(|_| File::open(&path_buf2))
+        .unwrap();
+      let reader = BufReader::new(file);
+      let moc = from_fits_multiordermap(reader, None, 0.0, region.level, false, true, false, false)
+        .unwrap();
+      assert_eq!(region.moc, moc);
+      assert!((region.value - region.level).abs() < 1e-4);
+    }
+    let searched = searched.unwrap();
+    // The position (0, 0) is outside the 90% credible region
+    assert!(searched.value > 0.9 && searched.value < 1.0 + 1e-9);
+    assert!(searched.area_deg2 > area_90);
+  }
+
+  #[test]
+  fn test_mutliordermap_write_read() {
+    let path_buf1 = PathBuf::from("resources/LALInference.multiorder.fits");
+    let path_buf2 = PathBuf::from("../resources/LALInference.multiorder.fits");
+    let file = File::open(&path_buf1)
+      .or_else(|_| File::open(&path_buf2))
+      .unwrap();
+    let (depth_max, mom) = mom_from_fits_multiordermap(BufReader::new(file)).unwrap();
+    for gz in [false, true] {
+      let mut buf: Vec<u8> = Vec::new();
+      let mom_it = HpxMomIter::<u64, Hpx<u64>, f64, _>::new(mom.iter().cloned());
+      hpxmom_to_fits_multiordermap(depth_max, CoordSys::ICRS, mom_it, gz, &mut buf).unwrap();
+      if !gz {
+        assert_eq!(buf.len() % 2880, 0);
+      }
+      let (depth_max2, mom2) =
+        mom_from_fits_multiordermap(BufReader::new(Cursor::new(buf))).unwrap();
+      assert_eq!(depth_max, depth_max2);
+      assert_eq!(mom.len(), mom2.len());
+      for ((uniq, value), (uniq2, value2)) in mom.iter().zip(mom2.iter()) {
+        assert_eq!(uniq, uniq2);
+        assert!((value - value2).abs() <= 1e-12 * value.abs());
+      }
+    }
+  }
+
+  #[test]
+  fn test_mutliordermap_write_columns() {
+    let columns = [
+      ValueColumn::new("PROBDENSITY", Some("sr-1")),
+      ValueColumn::new("DISTMU", Some("Mpc")),
+    ];
+    // Cells 0 and 1 at depth 1 (uniq = 16 + ipix) and cell 8 at depth 2 (uniq = 64 + ipix)
+    let rows = vec![(16_u64, [1.0, 10.0]), (17, [2.0, 20.0]), (72, [3.0, 30.0])];
+    let mut buf: Vec<u8> = Vec::new();
+    to_fits_multiordermap(
+      2,
+      CoordSys::GAL,
+      &columns,
+      rows.into_iter(),
+      false,
+      &mut buf,
+    )
+    .unwrap();
+    let header = String::from_utf8_lossy(&buf[2880..5760]).to_string();
+    assert!(header.contains("COORDSYS= 'G'"));
+    assert!(header.contains("TTYPE3  = 'DISTMU  '"));
+    assert!(header.contains("TUNIT3  = 'Mpc     '"));
+    assert!(header.contains("NAXIS1  =                   24"));
+    let (depth_max, mom) = mom_from_fits_multiordermap(BufReader::new(Cursor::new(buf))).unwrap();
+    assert_eq!(depth_max, 2);
+    assert_eq!(
+      mom.iter().map(|(uniq, _)| *uniq).collect::<Vec<u64>>(),
+      vec![16, 17, 72]
+    );
+    // Wrong number of values
+    let rows = vec![(16_u64, vec![1.0])];
+    assert!(to_fits_multiordermap(
+      2,
+      CoordSys::ICRS,
+      &columns,
+      rows.into_iter(),
+      false,
+      Vec::new()
+    )
+    .is_err());
+  }
+
+  #[test]
+  fn test_mutliordermap_columns() {
+    let path_buf1 = PathBuf::from("resources/LALInference.multiorder.fits");
+    let path_buf2 = PathBuf::from("../resources/LALInference.multiorder.fits");
+    let file = File::open(&path_buf1)
+      .or_else(|_| File::open(&path_buf2))
+      .unwrap();
+    let mom = columns_from_fits_multiordermap(BufReader::new(file), None, None).unwrap();
+    assert_eq!(mom.depth_max(), 10);
+    assert_eq!(mom.uniq_column(), "UNIQ");
+    assert_eq!(
+      mom
+        .columns()
+        .iter()
+        .map(|col| (col.name.as_str(), col.unit.as_deref()))
+        .collect::<Vec<(&str, Option<&str>)>>(),
+      vec![
+        ("PROBDENSITY", Some("sr-1")),
+        ("DISTMU", Some("Mpc")),
+        ("DISTSIGMA", Some("Mpc")),
+        ("DISTNORM", Some("Mpc-2")),
+      ]
+    );
+    assert_eq!(mom.values("distmu").unwrap().len(), mom.n_rows());
+    assert_eq!(mom.rows().next().unwrap().1.len(), 4);
+    // The PROBDENSITY MOM is the one returned by `mom_from_fits_multiordermap`
+    let file = File::open(&path_buf1)
+      .or_else(|_| File::open(&path_buf2))
+      .unwrap();
+    let (_, expected) = mom_from_fits_multiordermap(BufReader::new(file)).unwrap();
+    let actual: Vec<(u64, f64)> = mom.mom_iter("PROBDENSITY", true).unwrap().collect();
+    assert_eq!(actual, expected);
+    assert!(mom.mom_iter("DISTMEAN", false).is_err());
+    // Select a single column
+    let file = File::open(&path_buf1)
+      .or_else(|_| File::open(&path_buf2))
+      .unwrap();
+    let mom =
+      columns_from_fits_multiordermap(BufReader::new(file), Some("uniq"), Some(&["DISTSIGMA"]))
+        .unwrap();
+    assert_eq!(mom.columns().len(), 1);
+    assert!(mom.values("DISTMU").is_none());
+  }
+
+  #[test]
+  fn test_mutliordermap_float_uniq() {
+    let col_kws = [
+      str_keyword_record(b"TTYPE1  ", "PROB").unwrap(),
+      str_keyword_record(b"TFORM1  ", "E").unwrap(),
+      str_keyword_record(b"TTYPE2  ", "UNIQ").unwrap(),
+      str_keyword_record(b"TFORM2  ", "E").unwrap(),
+      str_keyword_record(b"TTYPE3  ", "NPIX").unwrap(),
+      str_keyword_record(b"TFORM3  ", "J").unwrap(),
+      *b"TNULL3  =                   -1                                                  ",
+    ];
+    let mut moc_kws = MocKeywordsMap::new();
+    moc_kws.insert(MocKeywords::PixType(PixType::Healpix));
+    moc_kws.insert(MocKeywords::Ordering(Ordering::Nuniq));
+    moc_kws.insert(MocKeywords::CoordSys(CoordSys::ICRS));
+    // No MOCORDER: the depth is the largest depth of the cells
+    let mut data: Vec<u8> = Vec::new();
+    for (prob, uniq, npix) in [
+      (0.5_f32, 16_f32, 1_i32),
+      (f32::NAN, 17.0, -1),
+      (0.25, 72.0, 4),
+    ] {
+      data.write_f32::<BigEndian>(prob).unwrap();
+      data.write_f32::<BigEndian>(uniq).unwrap();
+      data.write_i32::<BigEndian>(npix).unwrap();
+    }
+    let mut buf: Vec<u8> = Vec::new();
+    write_primary_hdu(&mut buf).unwrap();
+    write_bintable_header(&mut buf, 12, 3, 3, &col_kws, moc_kws, &[]).unwrap();
+    buf.extend_from_slice(&data);
+    write_data_padding(&mut buf, data.len() as u64).unwrap();
+    let mom =
+      columns_from_fits_multiordermap(BufReader::new(Cursor::new(buf)), None, None).unwrap();
+    assert_eq!(mom.depth_max(), 2);
+    assert_eq!(mom.uniqs(), &[16, 17, 72]);
+    let npix = mom.values("NPIX").unwrap();
+    assert_eq!(npix[0], 1.0);
+    assert!(npix[1].is_nan());
+    let prob: Vec<(u64, f64)> = mom.mom_iter("PROB", false).unwrap().collect();
+    assert_eq!(prob, vec![(16, 0.5), (72, 0.25)]);
+  }
 }
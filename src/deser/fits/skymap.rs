@@ -1,9 +1,11 @@
 use std::{
-  io::{self, BufRead, BufReader, Read, Seek},
+  io::{BufRead, BufReader, Read, Seek, Write},
   mem::size_of,
+  ops::Range,
+  str,
 };
 
-use byteorder::{BigEndian, ReadBytesExt};
+use byteorder::{BigEndian, WriteBytesExt};
 use log::warn;
 
 use healpix::depth;
@@ -11,52 +13,68 @@ use healpix::depth;
 use crate::{
   deser::{
     fits::{
+      bintable::BinTableHeader,
       common::{
-        check_keyword_and_get_str_val, check_keyword_and_parse_uint_val, check_keyword_and_val,
-        consume_primary_hdu, next_36_chunks_of_80_bytes,
+        str_keyword_record, write_bintable_header, write_data_padding, write_primary_hdu,
+        write_uint_mandatory_keyword_record,
       },
       error::FitsError,
-      keywords::{FitsCard, IndexSchema, MocKeywords, MocKeywordsMap, MocOrder, Nside, Ordering},
+      keywords::{
+        CoordSys, FitsCard, IndexSchema, MocKeywords, MocKeywordsMap, MocOrder, Nside, Ordering,
+        PixType,
+      },
+      multiordermap::ValueColumn,
     },
-    gz::{is_gz, uncompress},
+    gz::{compress, is_gz, uncompress},
   },
   elem::{
     cell::Cell, cellrange::CellRange, range::MocRange, valuedcell::valued_cells_to_moc_with_opt,
   },
   moc::range::RangeMOC,
-  qty::Hpx,
+  mom::{map::MultiOrderMap, HpxMOMIterator},
+  qty::{Hpx, MocQty},
 };
 
+/// Value used in HEALPix skymaps (e.g. by healpy) to flag pixels having no data.
+pub const UNSEEN: f64 = -1.6375e30;
+
 /// We expect the FITS file to be a BINTABLE containing a skymap.
 /// [Here](https://gamma-astro-data-formats.readthedocs.io/en/latest/skymaps/healpix/index.html)
 /// a description of the format.
-/// We so far implemented a subset of the format only:
-/// * `INDXSCHM= 'IMPLICIT'`
-/// * `ORDERING= 'NESTED  '`
-/// To be fast (in execution and development), we start by a non-flexible approach in which we
-/// expect the BINTABLE extension to contains:
+/// We support:
+/// * `INDXSCHM= 'IMPLICIT'`: full-sky maps, possibly with vector columns (e.g. `TFORM1 = '1024E'`
+///   as written by healpy)
+/// * `INDXSCHM= 'EXPLICIT'`: partial-sky maps, the pixel indices being stored in the `PIXEL`
+///   column (an error is returned if no column is named `PIXEL`)
+/// * `ORDERING= 'NESTED  '` and `ORDERING= 'RING    '` (converted to NESTED, so `NSIDE` must be a
+///   power of 2)
+/// * value columns of type `D`, `E`, `B`, `I`, `J` or `K`
+/// * blank values: `NaN`, `UNSEEN` or the `BAD_DATA` keyword value for floating point columns,
+///   the `TNULLn` (or `BLANK`) keyword value for integer columns. Blank pixels are ignored.
+///
+/// Example of header:
 /// ```bash
-/// XTENSION= 'BINTABLE'           / binary table extension                         
-/// BITPIX  =                    8 / array data type                                
+/// XTENSION= 'BINTABLE'           / binary table extension
+/// BITPIX  =                    8 / array data type
 /// NAXIS   =                    2 / number of array dimensions
-/// NAXIS1  =                    ?? / length of dimension 1                          
-/// NAXIS2  =                   ?? / length of dimension 2                          
-/// PCOUNT  =                    0 / number of group parameters                     
-/// GCOUNT  =                    1 / number of groups                               
+/// NAXIS1  =                    ?? / length of dimension 1
+/// NAXIS2  =                   ?? / length of dimension 2
+/// PCOUNT  =                    0 / number of group parameters
+/// GCOUNT  =                    1 / number of groups
 /// TFIELDS =                   ?? / number of table fields
-/// TTYPE1  = 'XXX'       // SHOULD STARS WITH 'PROB', else WARNING                                                            
-/// TFORM1  = 'XXX'       // MUST CONTAINS D (f64) or E (f32)                                                            
+/// TTYPE1  = 'XXX'       // By default, the first (non-PIXEL) column SHOULD STARS WITH 'PROB', else WARNING
+/// TFORM1  = 'XXX'       // MUST CONTAINS D (f64), E (f32), B (u8), I (i16), J (i32) or K (i64)
 /// TUNIT1  = 'pix-1    '
-/// TTYPE2  = ???                                                         
-/// TFORM2  = ???                                                            
+/// TTYPE2  = ???
+/// TFORM2  = ???
 /// ...
-/// MOC     =                    T                                                  
-/// PIXTYPE = 'HEALPIX '           / HEALPIX pixelisation                           
-/// ORDERING= 'NESTED  '           / Pixel ordering scheme: RING, NESTED, or NUNIQ  
+/// MOC     =                    T
+/// PIXTYPE = 'HEALPIX '           / HEALPIX pixelisation
+/// ORDERING= 'NESTED  '           / Pixel ordering scheme: RING, NESTED, or NUNIQ
 /// COORDSYS= 'C       '  // WARNING if not found
 /// NSIDE    =                  ?? / MOC resolution (best nside)
 ///  or
-/// ORDER    =                  ?? / MOC resolution (best order), superseded by NSIDE
+/// MOCORDER =                  ?? / MOC resolution (best order), superseded by NSIDE
 ///                                / (because NSIDE which are not power of 2 are possible in RING)
 /// INDXSCHM= 'IMPLICIT'           / Indexing: IMPLICIT or EXPLICIT
 /// ...
@@ -65,6 +83,8 @@ use crate::{
 ///
 /// # Params
 /// * `reader`: the reader over the FITS content
+/// * `column`: name (case insensitive) of the value column, `None` for the first value column
+/// * `skip_value_le_this`: pixels having a value lower or equal to this value are skipped
 /// * `cumul_from`: the cumulative value from which cells are put in the MOC
 /// * `cumul_to`: the cumulative value to which cells are put in the MOC
 /// * `asc`: cumulative value computed from lower to highest densities instead of from highest to lowest
@@ -77,7 +97,8 @@ use crate::{
 ///
 #[allow(clippy::too_many_arguments)]
 pub fn from_fits_skymap<R: Read + Seek>(
-  mut reader: BufReader<R>,
+  reader: BufReader<R>,
+  column: Option<&str>,
   skip_value_le_this: f64,
   cumul_from: f64,
   cumul_to: f64,
@@ -86,117 +107,100 @@ pub fn from_fits_skymap<R: Read + Seek>(
   no_split: bool,
   reverse_decent: bool,
 ) -> Result<RangeMOC<u64, Hpx<u64>>, FitsError> {
+  let (depth_max, uniq_val_dens, cumul_skipped) = load_skymap(reader, column, skip_value_le_this)?;
+  // Build the MOC
+  let ranges = valued_cells_to_moc_with_opt(
+    depth_max,
+    uniq_val_dens,
+    cumul_from - cumul_skipped,
+    cumul_to - cumul_skipped,
+    asc,
+    strict,
+    no_split,
+    reverse_decent,
+  );
+  Ok(RangeMOC::new(depth_max, ranges))
+}
+
+/// Returns the depth and the `(uniq, value)` pairs of a skymap (see [from_fits_skymap] for the
+/// supported skymaps), to be e.g. wrapped in a [HpxMomIter](crate::mom::HpxMomIter).
+/// Consecutive NESTED pixels having the same value are merged into larger cells, the value of a
+/// cell being the sum of the values of its pixels.
+///
+/// # Params
+/// * `reader`: the reader over the FITS content
+/// * `column`: name (case insensitive) of the value column, `None` for the first value column
+/// * `skip_value_le_this`: pixels having a value lower or equal to this value are skipped
+///
+/// # Info
+///   Supports gz input stream
+pub fn mom_from_fits_skymap<R: Read + Seek>(
+  reader: BufReader<R>,
+  column: Option<&str>,
+  skip_value_le_this: f64,
+) -> Result<(u8, Vec<(u64, f64)>), FitsError> {
+  load_skymap(reader, column, skip_value_le_this).map(|(depth_max, uniq_val_dens, _)| {
+    let mom = uniq_val_dens
+      .into_iter()
+      .map(|(uniq, value, _dens)| (uniq, value))
+      .collect();
+    (depth_max, mom)
+  })
+}
+
+/// Reads a skymap (see [from_fits_skymap] for the supported skymaps) as a [MultiOrderMap],
+/// adaptively coarsening its uniform regions (see [MultiOrderMap::coarsen]).
+///
+/// # Params
+/// * `reader`: the reader over the FITS content
+/// * `column`: name (case insensitive) of the value column, `None` for the first value column
+/// * `skip_value_le_this`: pixels having a value lower or equal to this value are skipped
+/// * `rel_tol`: relative tolerance under which 4 sibling cells are considered uniform and merged
+///
+/// # Info
+///   Supports gz input stream
+pub fn multiordermap_from_fits_skymap<R: Read + Seek>(
+  reader: BufReader<R>,
+  column: Option<&str>,
+  skip_value_le_this: f64,
+  rel_tol: f64,
+) -> Result<MultiOrderMap<u64, f64>, FitsError> {
+  mom_from_fits_skymap(reader, column, skip_value_le_this)
+    .map(|(depth_max, mom)| MultiOrderMap::new(depth_max, mom).coarsen(rel_tol))
+}
+
+/// Returns the skymap depth, its `(uniq, value, density)` tuples and the sum of the skipped values.
+#[allow(clippy::type_complexity)]
+fn load_skymap<R: Read + Seek>(
+  mut reader: BufReader<R>,
+  column: Option<&str>,
+  skip_value_le_this: f64,
+) -> Result<(u8, Vec<(u64, f64, f64)>, f64), FitsError> {
   if is_gz(&mut reader)? {
-    from_fits_skymap_internal(
-      uncompress(reader),
-      skip_value_le_this,
-      cumul_from,
-      cumul_to,
-      asc,
-      strict,
-      no_split,
-      reverse_decent,
-    )
+    load_skymap_internal(uncompress(reader), column, skip_value_le_this)
   } else {
-    from_fits_skymap_internal(
-      reader,
-      skip_value_le_this,
-      cumul_from,
-      cumul_to,
-      asc,
-      strict,
-      no_split,
-      reverse_decent,
-    )
+    load_skymap_internal(reader, column, skip_value_le_this)
   }
 }
 
-#[allow(clippy::too_many_arguments)]
-fn from_fits_skymap_internal<R: BufRead>(
+#[allow(clippy::type_complexity)]
+fn load_skymap_internal<R: BufRead>(
   mut reader: R,
+  column: Option<&str>,
   skip_value_le_this: f64,
-  cumul_from: f64,
-  cumul_to: f64,
-  asc: bool,
-  strict: bool,
-  no_split: bool,
-  reverse_decent: bool,
-) -> Result<RangeMOC<u64, Hpx<u64>>, FitsError> {
-  let mut header_block = [b' '; 2880];
-  consume_primary_hdu(&mut reader, &mut header_block)?;
-  // Read the extention HDU
-  let mut it80 = next_36_chunks_of_80_bytes(&mut reader, &mut header_block)?;
-  // See Table 10 and 17 in https://fits.gsfc.nasa.gov/standard40/fits_standard40aa-le.pdf
-  check_keyword_and_val(it80.next().unwrap(), b"XTENSION", b"'BINTABLE'")?;
-  check_keyword_and_val(it80.next().unwrap(), b"BITPIX  ", b"8")?;
-  check_keyword_and_val(it80.next().unwrap(), b"NAXIS  ", b"2")?;
-  let n_bytes_per_row = check_keyword_and_parse_uint_val::<u64>(it80.next().unwrap(), b"NAXIS1  ")?;
-  let n_rows = check_keyword_and_parse_uint_val::<u64>(it80.next().unwrap(), b"NAXIS2 ")?;
-  check_keyword_and_val(it80.next().unwrap(), b"PCOUNT  ", b"0")?;
-  check_keyword_and_val(it80.next().unwrap(), b"GCOUNT  ", b"1")?;
-  let _n_cols = check_keyword_and_parse_uint_val::<u64>(it80.next().unwrap(), b"TFIELDS ")?;
-  // check_keyword_and_val(it80.next().unwrap(), b"TTYPE1 ", b"'PROB    '")?;
-  // check_keyword_and_val(it80.next().unwrap(), b"TFORM1 ", b"'D       '")?;
-  let ttype1 = check_keyword_and_get_str_val(it80.next().unwrap(), b"TTYPE1 ")?;
-  if !ttype1.to_uppercase().starts_with("PROB") {
-    let err = FitsError::UnexpectedValue(
-      String::from("TTYPE1"),
-      String::from("starts with 'PROB'"),
-      String::from(ttype1),
-    );
-    warn!("{}", err);
-  }
-  let tform1 = check_keyword_and_get_str_val(it80.next().unwrap(), b"TFORM1 ")?;
-  let (is_f64, n_pack) = if tform1 == "D" || tform1 == "1D" {
-    Ok((true, 1_u64))
-  } else if tform1 == "1024D" {
-    Ok((true, 1024_u64))
-  } else if tform1 == "E" || tform1 == "1E" {
-    Ok((false, 1_u64))
-  } else if tform1 == "1024E" {
-    Ok((false, 1024_u64))
-  } else {
-    // TODO: ALSO SUPPORT B or 1B and TTYPE = M (for MASK)!!
-    Err(FitsError::UnexpectedValue(
-      String::from("TFORM1"),
-      String::from("['D', '1D', '1024D', 'E', '1E' or '1024E']"),
-      String::from(tform1),
-    ))
-  }?;
-
-  // nbits = |BITPIX|xGCOUNTx(PCOUNT+NAXIS1xNAXIS2x...xNAXISn)
-  // In our case (bitpix = 8, GCOUNT = 1, PCOUNT = 0) => nbytes = n_cells * size_of(T)
-  // let data_size n_bytes as usize * n_cells as usize; // N_BYTES ok since BITPIX = 8
-  // Read MOC keywords
-  let mut moc_kws = MocKeywordsMap::new();
-  'hr: loop {
-    for kw_record in &mut it80 {
-      // Parse only MOC related keywords and ignore others
-      if let Some(mkw) = MocKeywords::is_moc_kw(kw_record) {
-        if let Some(previous_mkw) = moc_kws.insert(mkw?) {
-          // A FITS keyword MUST BE uniq (I may be more relax here, taking the last one and not complaining)
-          // return Err(FitsError::MultipleKeyword(previous_mkw.keyword_str().to_string()))
-          warn!(
-            "Keyword '{}' found more than once in a same HDU! We use the first occurrence.",
-            previous_mkw.keyword_str()
-          );
-          moc_kws.insert(previous_mkw);
-        }
-        // else keyword added without error
-      } else if &kw_record[0..4] == b"END " {
-        break 'hr;
-      }
-    }
-    // Read next 2880 bytes
-    it80 = next_36_chunks_of_80_bytes(&mut reader, &mut header_block)?;
-  }
+) -> Result<(u8, Vec<(u64, f64, f64)>, f64), FitsError> {
+  let header = BinTableHeader::parse(&mut reader)?;
+  let moc_kws = &header.moc_kws;
   // Check header params
   moc_kws.check_pixtype()?;
-  // moc_kws.check_ordering(Ordering::Nested)?;
   if let Err(e) = moc_kws.check_coordsys() {
     warn!("{}", e);
   }
-  moc_kws.check_index_schema(IndexSchema::Implicit)?;
+  let explicit = match moc_kws.get::<IndexSchema>() {
+    Some(MocKeywords::IndexSchema(IndexSchema::Implicit)) => false,
+    Some(MocKeywords::IndexSchema(IndexSchema::Explicit)) => true,
+    _ => return Err(FitsError::MissingKeyword(IndexSchema::keyword_string())),
+  };
   // - get MOC depth
   let depth_max = match moc_kws.get::<MocOrder>() {
     Some(MocKeywords::MOCOrder(MocOrder { depth })) => *depth,
@@ -214,69 +218,9 @@ fn from_fits_skymap_internal<R: BufRead>(
       _ => return Err(FitsError::MissingKeyword(MocOrder::keyword_string())),
     },
   };
-  if n_rows * n_pack != healpix::n_hash(depth_max) {
-    return Err(FitsError::Custom(format!(
-      "Number of elements {} do not match number of HEALPix cells {}",
-      n_rows * n_pack,
-      healpix::n_hash(depth_max)
-    )));
-  }
-  // Read data
-  let (uniq_val_dens, cumul_skipped) = match moc_kws.get::<Ordering>() {
-    Some(MocKeywords::Ordering(Ordering::Nested)) => {
-      if is_f64 {
-        let first_elem_byte_size = size_of::<f64>() * n_pack as usize;
-        let n_byte_skip = n_bytes_per_row as usize - first_elem_byte_size;
-        load_from_nested(
-          reader,
-          |r| r.read_f64::<BigEndian>(),
-          skip_value_le_this,
-          depth_max,
-          n_pack,
-          n_byte_skip,
-          n_rows,
-        )
-      } else {
-        let first_elem_byte_size = size_of::<f32>() * n_pack as usize;
-        let n_byte_skip = n_bytes_per_row as usize - first_elem_byte_size;
-        load_from_nested(
-          reader,
-          |r| r.read_f32::<BigEndian>().map(|v| v as f64),
-          skip_value_le_this,
-          depth_max,
-          n_pack,
-          n_byte_skip,
-          n_rows,
-        )
-      }?
-    }
-    Some(MocKeywords::Ordering(Ordering::Ring)) => {
-      if is_f64 {
-        let first_elem_byte_size = size_of::<f64>() * n_pack as usize;
-        let n_byte_skip = n_bytes_per_row as usize - first_elem_byte_size;
-        load_from_ring(
-          reader,
-          |r| r.read_f64::<BigEndian>(),
-          skip_value_le_this,
-          depth_max,
-          n_pack,
-          n_byte_skip,
-          n_rows,
-        )
-      } else {
-        let first_elem_byte_size = size_of::<f32>() * n_pack as usize;
-        let n_byte_skip = n_bytes_per_row as usize - first_elem_byte_size;
-        load_from_ring(
-          reader,
-          |r| r.read_f32::<BigEndian>().map(|v| v as f64),
-          skip_value_le_this,
-          depth_max,
-          n_pack,
-          n_byte_skip,
-          n_rows,
-        )
-      }?
-    }
+  let nested = match moc_kws.get::<Ordering>() {
+    Some(MocKeywords::Ordering(Ordering::Nested)) => true,
+    Some(MocKeywords::Ordering(Ordering::Ring)) => false,
     Some(MocKeywords::Ordering(other_ordering)) => {
       return Err(FitsError::UnexpectedValue(
         Ordering::keyword_string(),
@@ -291,175 +235,295 @@ fn from_fits_skymap_internal<R: BufRead>(
     Some(_other_keyword) => unreachable!(),
     None => return Err(FitsError::MissingKeyword(Ordering::keyword_string())),
   };
-  // Build the MOC
-  let ranges = valued_cells_to_moc_with_opt(
-    depth_max,
-    uniq_val_dens,
-    cumul_from - cumul_skipped,
-    cumul_to - cumul_skipped,
-    asc,
-    strict,
-    no_split,
-    reverse_decent,
-  );
-  Ok(RangeMOC::new(depth_max, ranges))
-}
-
-#[allow(clippy::type_complexity)]
-fn load_from_nested<R, F>(
-  mut reader: R,
-  read_f64: F,
-  skip_value_le_this: f64,
-  depth_max: u8,
-  n_pack: u64,
-  n_byte_skip: usize,
-  n_rows: u64,
-) -> io::Result<(Vec<(u64, f64, f64)>, f64)>
-where
-  R: BufRead,
-  F: Fn(&mut R) -> io::Result<f64>,
-{
-  let mut sink = vec![0; n_byte_skip];
-  let mut prev_range = 0..0;
-  let mut prev_val = 0.0;
-  let mut uniq_val_dens: Vec<(u64, f64, f64)> = Vec::with_capacity(10_240);
-  let mut cumul_skipped = 0_f64;
-  if n_pack == 1 {
-    for ipix in 0..n_rows {
-      let val = read_f64(&mut reader)?;
-      // - we skip too low value (e.g. all cells set to 0)
-      // - we pack together, in a same range, consecutive cells having the same value
-      //   and we build a multi resolution map to reuse existing code
-      if val > skip_value_le_this {
-        if val == prev_val && ipix == prev_range.end {
-          prev_range.end = ipix + 1;
-        } else {
-          if prev_range.start != prev_range.end {
-            let moc_range: MocRange<u64, Hpx<u64>> =
-              CellRange::from_depth_range(depth_max, prev_range.clone()).into();
-            for moc_cell in moc_range {
-              let n_cells = 1_u64 << ((depth_max - moc_cell.depth()) << 1);
-              let uniq = Cell::<u64>::from(moc_cell).uniq_hpx();
-              let uval = prev_val * (n_cells as f64);
-              uniq_val_dens.push((uniq, uval, prev_val))
-            }
-          }
-          prev_val = val;
-          prev_range = ipix..ipix + 1;
-        }
+  let pixel_icol = if explicit {
+    Some(header.find_column("PIXEL")?)
+  } else {
+    None
+  };
+  let value_icol = match column {
+    Some(name) => header.find_column(name)?,
+    None => {
+      let icol = (0..header.columns.len())
+        .find(|icol| Some(*icol) != pixel_icol)
+        .ok_or_else(|| FitsError::Custom(String::from("No value column found")))?;
+      if !header.columns[icol].name.to_uppercase().starts_with("PROB") {
+        let err = FitsError::UnexpectedValue(
+          format!("TTYPE{}", icol + 1),
+          String::from("starts with 'PROB'"),
+          header.columns[icol].name.clone(),
+        );
+        warn!("{}", err);
+      }
+      icol
+    }
+  };
+  let value_reader =
+    header.value_reader(value_icol, b"BIJKED", "['B', 'I', 'J', 'K', 'E' or 'D']")?;
+  let value_tform = value_reader.tform;
+  // Read data
+  let n_hash = healpix::n_hash(depth_max);
+  let nested_layer = healpix::nested::get(depth_max);
+  let n_rows = header.n_rows;
+  let mut row = vec![0_u8; header.n_bytes_per_row as usize];
+  // Merging consecutive pixels requires pixels in the NESTED order
+  let mut cells = SkymapCells::new(depth_max, skip_value_le_this, nested && !explicit);
+  if let Some(pixel_icol) = pixel_icol {
+    let pixel_reader = header.value_reader(pixel_icol, b"IJK", "['I', 'J' or 'K']")?;
+    if pixel_reader.tform.repeat != 1 || value_tform.repeat != 1 {
+      return Err(FitsError::Custom(String::from(
+        "Vector columns not supported in EXPLICIT skymaps",
+      )));
+    }
+    for _ in 0..n_rows {
+      reader.read_exact(&mut row)?;
+      let ipix = pixel_reader.read_int(&row, 0);
+      if ipix < 0 || ipix as u64 >= n_hash {
+        return Err(FitsError::Custom(format!(
+          "Pixel index {} out of range [0, {})",
+          ipix, n_hash
+        )));
+      }
+      let ipix = if nested {
+        ipix as u64
       } else {
-        cumul_skipped += val;
+        nested_layer.from_ring(ipix as u64)
+      };
+      if let Some(val) = value_reader.read(&row, 0) {
+        cells.push(ipix, val);
       }
-      // Skip other columns bits
-      reader.read_exact(&mut sink)?;
     }
   } else {
+    let n_pack = value_tform.repeat;
+    if n_rows * n_pack != n_hash {
+      return Err(FitsError::Custom(format!(
+        "Number of elements {} do not match number of HEALPix cells {}",
+        n_rows * n_pack,
+        n_hash
+      )));
+    }
     for i_row in 0..n_rows {
+      reader.read_exact(&mut row)?;
       let start = i_row * n_pack;
-      for ipix in start..start + n_pack {
-        let val = read_f64(&mut reader)?;
-        // - we skip too low value (e.g. all cells set to 0)
-        // - we pack together, in a same range, consecutive cells having the same value
-        //   and we build a multi resolution map to reuse existing code
-        if val > skip_value_le_this {
-          if val == prev_val && ipix == prev_range.end {
-            prev_range.end = ipix + 1;
-          } else {
-            if prev_range.start != prev_range.end {
-              let moc_range: MocRange<u64, Hpx<u64>> =
-                CellRange::from_depth_range(depth_max, prev_range.clone()).into();
-              for moc_cell in moc_range {
-                let n_cells = 1_u64 << ((depth_max - moc_cell.depth()) << 1);
-                let uniq = Cell::<u64>::from(moc_cell).uniq_hpx();
-                let uval = prev_val * (n_cells as f64);
-                uniq_val_dens.push((uniq, uval, prev_val))
-              }
-            }
-            prev_val = val;
-            prev_range = ipix..ipix + 1;
-          }
+      for (k, ipix) in (start..start + n_pack).enumerate() {
+        let ipix = if nested {
+          ipix
         } else {
-          cumul_skipped += val;
+          nested_layer.from_ring(ipix)
+        };
+        if let Some(val) = value_reader.read(&row, k) {
+          cells.push(ipix, val);
         }
       }
-      // Skip other columns bits
-      reader.read_exact(&mut sink)?;
-    }
-  }
-  if prev_range.start != prev_range.end {
-    let moc_range: MocRange<u64, Hpx<u64>> =
-      CellRange::from_depth_range(depth_max, prev_range).into();
-    for moc_cell in moc_range {
-      let n_cells = 1_u64 << ((depth_max - moc_cell.depth()) << 1);
-      let uniq = Cell::<u64>::from(moc_cell).uniq_hpx();
-      let uval = prev_val * (n_cells as f64);
-      uniq_val_dens.push((uniq, uval, prev_val))
     }
   }
-  Ok((uniq_val_dens, cumul_skipped))
+  let (uniq_val_dens, cumul_skipped) = cells.into_parts();
+  Ok((depth_max, uniq_val_dens, cumul_skipped))
 }
 
-#[allow(clippy::type_complexity)]
-fn load_from_ring<R, F>(
-  mut reader: R,
-  read_f64: F,
+/// Build the `(uniq, value, density)` tuples from the skymap pixels values.
+struct SkymapCells {
+  depth: u8,
   skip_value_le_this: f64,
-  depth_max: u8,
-  n_pack: u64,
-  n_byte_skip: usize,
-  n_rows: u64,
-) -> io::Result<(Vec<(u64, f64, f64)>, f64)>
-where
-  R: BufRead,
-  F: Fn(&mut R) -> io::Result<f64>,
-{
-  let nested_layer = healpix::nested::get(depth_max);
-  let mut sink = vec![0; n_byte_skip];
-  let mut uniq_val_dens: Vec<(u64, f64, f64)> = Vec::with_capacity(10_240);
-  let mut cumul_skipped = 0_f64;
-  if n_pack == 1 {
-    for ipix_ring in 0..n_rows {
-      let val = read_f64(&mut reader)?;
-      // - we skip too low value (e.g. all cells set to 0)
-      if val > skip_value_le_this {
-        let ipix_nested = nested_layer.from_ring(ipix_ring);
-        let uniq = Hpx::<u64>::uniq_hpx(depth_max, ipix_nested);
-        uniq_val_dens.push((uniq, val, val))
+  /// Pack together, in a same range, consecutive pixels having the same value
+  /// (pixels must be pushed in the NESTED order)
+  merge: bool,
+  prev_range: Range<u64>,
+  prev_val: f64,
+  uniq_val_dens: Vec<(u64, f64, f64)>,
+  cumul_skipped: f64,
+}
+impl SkymapCells {
+  fn new(depth: u8, skip_value_le_this: f64, merge: bool) -> Self {
+    Self {
+      depth,
+      skip_value_le_this,
+      merge,
+      prev_range: 0..0,
+      prev_val: 0.0,
+      uniq_val_dens: Vec::with_capacity(10_240),
+      cumul_skipped: 0.0,
+    }
+  }
+
+  fn push(&mut self, ipix: u64, val: f64) {
+    // - we skip too low value (e.g. all cells set to 0)
+    // - we pack together, in a same range, consecutive cells having the same value
+    //   and we build a multi resolution map to reuse existing code
+    if val > self.skip_value_le_this {
+      if !self.merge {
+        let uniq = Hpx::<u64>::uniq_hpx(self.depth, ipix);
+        self.uniq_val_dens.push((uniq, val, val));
+      } else if val == self.prev_val && ipix == self.prev_range.end {
+        self.prev_range.end = ipix + 1;
       } else {
-        cumul_skipped += val;
+        self.flush();
+        self.prev_val = val;
+        self.prev_range = ipix..ipix + 1;
       }
-      // Skip other columns bits
-      reader.read_exact(&mut sink)?;
+    } else {
+      self.cumul_skipped += val;
     }
-  } else {
-    for i_row in 0..n_rows {
-      let start = i_row * n_pack;
-      for ipix_ring in start..start + n_pack {
-        let val = read_f64(&mut reader)?;
-        // - we skip too low value (e.g. all cells set to 0)
-        if val > skip_value_le_this {
-          let ipix_nested = nested_layer.from_ring(ipix_ring);
-          let uniq = Hpx::<u64>::uniq_hpx(depth_max, ipix_nested);
-          uniq_val_dens.push((uniq, val, val))
-        } else {
-          cumul_skipped += val;
-        }
+  }
+
+  fn flush(&mut self) {
+    if self.prev_range.start != self.prev_range.end {
+      let moc_range: MocRange<u64, Hpx<u64>> =
+        CellRange::from_depth_range(self.depth, self.prev_range.clone()).into();
+      for moc_cell in moc_range {
+        let n_cells = 1_u64 << ((self.depth - moc_cell.depth()) << 1);
+        let uniq = Cell::<u64>::from(moc_cell).uniq_hpx();
+        let uval = self.prev_val * (n_cells as f64);
+        self.uniq_val_dens.push((uniq, uval, self.prev_val))
       }
-      // Skip other columns bits
-      reader.read_exact(&mut sink)?;
     }
   }
-  Ok((uniq_val_dens, cumul_skipped))
+
+  fn into_parts(mut self) -> (Vec<(u64, f64, f64)>, f64) {
+    self.flush();
+    (self.uniq_val_dens, self.cumul_skipped)
+  }
+}
+
+/// Write an implicit, `NESTED` ordered, all-sky HEALPix skymap in a FITS BINTABLE containing a
+/// single value column (stored as `f64`).
+/// The written file can be read back by [from_fits_skymap].
+///
+/// # Params
+/// * `depth`: the skymap depth (i.e. HEALPix order)
+/// * `coordsys`: the coordinate system (`COORDSYS`) of the skymap
+/// * `column`: name and (optional) unit of the value column
+/// * `values`: the `12 * 4^depth` values, ordered by increasing NESTED cell index
+/// * `gz`: compress the output stream using gzip
+/// * `writer`: the output stream
+pub fn to_fits_skymap<W, I>(
+  depth: u8,
+  coordsys: CoordSys,
+  column: &ValueColumn,
+  values: I,
+  gz: bool,
+  writer: W,
+) -> Result<(), FitsError>
+where
+  W: Write,
+  I: Iterator<Item = f64>,
+{
+  if gz {
+    let mut encoder = compress(writer);
+    to_fits_skymap_internal(depth, coordsys, column, values, &mut encoder)?;
+    encoder.finish().map(|_| ()).map_err(FitsError::Io)
+  } else {
+    to_fits_skymap_internal(depth, coordsys, column, values, writer)
+  }
+}
+
+fn to_fits_skymap_internal<W, I>(
+  depth: u8,
+  coordsys: CoordSys,
+  column: &ValueColumn,
+  values: I,
+  mut writer: W,
+) -> Result<(), FitsError>
+where
+  W: Write,
+  I: Iterator<Item = f64>,
+{
+  if depth > Hpx::<u64>::MAX_DEPTH {
+    return Err(FitsError::Custom(format!(
+      "Skymap depth {} larger than the maximum HEALPix depth {}.",
+      depth,
+      Hpx::<u64>::MAX_DEPTH
+    )));
+  }
+  let n_rows = healpix::n_hash(depth);
+  // Build the header
+  let mut col_kws = Vec::with_capacity(3);
+  column.push_f64_keyword_records(1, &mut col_kws)?;
+  let mut moc_kws = MocKeywordsMap::new();
+  moc_kws.insert(MocKeywords::PixType(PixType::Healpix));
+  moc_kws.insert(MocKeywords::Ordering(Ordering::Nested));
+  moc_kws.insert(MocKeywords::CoordSys(coordsys));
+  moc_kws.insert(MocKeywords::Nside(Nside {
+    nside: healpix::nside(depth),
+  }));
+  moc_kws.insert(MocKeywords::IndexSchema(IndexSchema::Implicit));
+  let mut other_kws = [[b' '; 80]; 3];
+  other_kws[0] = str_keyword_record(b"OBJECT  ", "FULLSKY")?;
+  write_uint_mandatory_keyword_record(&mut other_kws[1], b"FIRSTPIX", 0);
+  write_uint_mandatory_keyword_record(&mut other_kws[2], b"LASTPIX ", n_rows - 1);
+  // Write the primary HDU, the header and the data
+  write_primary_hdu(&mut writer)?;
+  write_bintable_header(
+    &mut writer,
+    size_of::<f64>() as u64,
+    n_rows,
+    1,
+    &col_kws,
+    moc_kws,
+    &other_kws,
+  )?;
+  let mut n_values = 0_u64;
+  for value in values {
+    if n_values == n_rows {
+      return Err(FitsError::RemainingData);
+    }
+    writer.write_f64::<BigEndian>(value)?;
+    n_values += 1;
+  }
+  if n_values != n_rows {
+    return Err(FitsError::PrematureEndOfData);
+  }
+  write_data_padding(&mut writer, n_rows * size_of::<f64>() as u64)
+}
+
+/// Write the given multi-order map as an implicit, `NESTED` ordered, all-sky HEALPix skymap
+/// of the given depth, in a FITS BINTABLE containing a single `PROB` column (in `pix-1`).
+/// The value of a MOM cell of depth lower than `depth` is evenly distributed among its sub-cells
+/// while the values of MOM cells of depth larger than `depth` are summed in their super-cell.
+/// Cells not in the MOM are set to 0.
+///
+/// # Params
+/// * `depth`: the skymap depth (i.e. HEALPix order), at most
+///   [SKYMAP_DEPTH_MAX](crate::mom::SKYMAP_DEPTH_MAX)
+/// * `coordsys`: the coordinate system (`COORDSYS`) of the skymap
+/// * `mom`: the multi-order map
+/// * `gz`: compress the output stream using gzip
+/// * `writer`: the output stream
+pub fn hpxmom_to_fits_skymap<I, W>(
+  depth: u8,
+  coordsys: CoordSys,
+  mom: I,
+  gz: bool,
+  writer: W,
+) -> Result<(), FitsError>
+where
+  I: HpxMOMIterator<u64, f64>,
+  W: Write,
+{
+  let values = mom.to_skymap(depth).map_err(FitsError::Custom)?;
+  let column = ValueColumn::new("PROB", Some("pix-1"));
+  to_fits_skymap(depth, coordsys, &column, values.into_iter(), gz, writer)
 }
 
 #[cfg(test)]
 mod tests {
 
-  use super::from_fits_skymap;
-  use crate::deser::fits::ranges_to_fits_ivoa;
+  use super::{
+    from_fits_skymap, hpxmom_to_fits_skymap, mom_from_fits_skymap, multiordermap_from_fits_skymap,
+    to_fits_skymap, UNSEEN,
+  };
+  use crate::deser::fits::{
+    common::{str_keyword_record, write_bintable_header, write_data_padding, write_primary_hdu},
+    keywords::{CoordSys, IndexSchema, MocKeywords, MocKeywordsMap, Nside, Ordering, PixType},
+    multiordermap::ValueColumn,
+    ranges_to_fits_ivoa,
+  };
   use crate::moc::RangeMOCIntoIterator;
+  use crate::{
+    mom::{HpxMomIter, SKYMAP_DEPTH_MAX},
+    qty::Hpx,
+  };
   use std::fs::File;
-  use std::io::{BufReader, BufWriter};
+  use std::io::{BufReader, Cursor};
   use std::path::PathBuf;
 
   // Perform only in release mode (else slow: the decompressed fits files is 1.6GB large)!
@@ -473,15 +537,10 @@ mod tests {
       .unwrap();
     let reader = BufReader::new(file);
 
-    let res = from_fits_skymap(reader, 0.0, 0.0, 0.9, false, true, true, false);
+    let res = from_fits_skymap(reader, None, 0.0, 0.0, 0.9, false, true, true, false);
     match res {
       Ok(o) => {
-        let path_buf1 = PathBuf::from("resources/Skymap/bayestar.moc.out.fits");
-        let path_buf2 = PathBuf::from("../resources/Skymap/bayestar.moc.out.fits");
-        let file = File::create(&path_buf1)
-          .or_else(|_| File::create(&path_buf2))
-          .unwrap();
-        let writer = BufWriter::new(file);
+        let writer: Vec<u8> = Vec::new();
         print!("{:?}", &o);
         ranges_to_fits_ivoa(o.into_range_moc_iter(), None, None, writer).unwrap();
         assert!(true)
@@ -503,15 +562,10 @@ mod tests {
       .unwrap();
     let reader = BufReader::new(file);
 
-    let res = from_fits_skymap(reader, 0.0, 0.0, 0.9, false, true, true, false);
+    let res = from_fits_skymap(reader, None, 0.0, 0.0, 0.9, false, true, true, false);
     match res {
       Ok(o) => {
-        let path_buf1 = PathBuf::from("resources/Skymap/gbuts_healpix_systematic.moc.out.fits");
-        let path_buf2 = PathBuf::from("../resources/Skymap/gbuts_healpix_systematic.moc.out.fits");
-        let file = File::create(&path_buf1)
-          .or_else(|_| File::create(&path_buf2))
-          .unwrap();
-        let writer = BufWriter::new(file);
+        let writer: Vec<u8> = Vec::new();
         print!("{:?}", &o);
         ranges_to_fits_ivoa(o.into_range_moc_iter(), None, None, writer).unwrap();
         assert!(true)
@@ -534,7 +588,7 @@ mod tests {
       .unwrap();
     let reader = BufReader::new(file);
 
-    let res = from_fits_skymap(reader, 0.0, 0.0, 0.9, false, true, true, false);
+    let res = from_fits_skymap(reader, None, 0.0, 0.0, 0.9, false, true, true, false);
     match res {
       Ok(o) => {
         print!("{:?}", o);
@@ -561,7 +615,7 @@ mod tests {
       .unwrap();
     let reader = BufReader::new(file);
 
-    let res = from_fits_skymap(reader, 0.0, 0.0, 0.9, false, true, true, false);
+    let res = from_fits_skymap(reader, None, 0.0, 0.0, 0.9, false, true, true, false);
     match res {
       Ok(o) => {
         // print!("{:?}", o);
@@ -587,7 +641,7 @@ mod tests {
       .unwrap();
     let reader = BufReader::new(file);
 
-    let res = from_fits_skymap(reader, 0.0, 0.0, 0.9, false, true, true, false);
+    let res = from_fits_skymap(reader, None, 0.0, 0.0, 0.9, false, true, true, false);
     match res {
       Ok(o) => {
         // print!("{:?}", o);
@@ -600,4 +654,263 @@ mod tests {
       }
     }
   }
+
+  #[test]
+  fn test_skymap_write_read() {
+    // Depth 1 skymap: cell 0 at depth 0 (covering cells 0-3 at depth 1) and cells 4 and 5 at depth 1
+    let mom = vec![(4_u64, 0.4_f64), (4 * 4 + 4, 0.3), (4 * 4 + 5, 0.3)];
+    for gz in [false, true] {
+      let mut buf: Vec<u8> = Vec::new();
+      hpxmom_to_fits_skymap(
+        1,
+        CoordSys::ICRS,
+        HpxMomIter::<u64, Hpx<u64>, f64, _>::new(mom.clone().into_iter()),
+        gz,
+        &mut buf,
+      )
+      .unwrap();
+      let moc = from_fits_skymap(
+        BufReader::new(Cursor::new(buf)),
+        None,
+        0.0,
+        0.0,
+        1.0,
+        false,
+        false,
+        false,
+        false,
+      )
+      .unwrap();
+      assert_eq!(moc.depth_max(), 1);
+      assert_eq!(moc.moc_ranges().0 .0.len(), 1);
+      assert_eq!(moc.moc_ranges().0 .0[0], 0..(6_u64 << 56));
+    }
+    // Wrong number of values
+    let column = ValueColumn::new("PROB", None);
+    let coosys = CoordSys::ICRS;
+    assert!(to_fits_skymap(
+      0,
+      coosys,
+      &column,
+      vec![0.0; 11].into_iter(),
+      false,
+      Vec::new()
+    )
+    .is_err());
+    assert!(to_fits_skymap(
+      0,
+      coosys,
+      &column,
+      vec![0.0; 13].into_iter(),
+      false,
+      Vec::new()
+    )
+    .is_err());
+    // Too large depths
+    assert!(to_fits_skymap(30, coosys, &column, vec![].into_iter(), false, Vec::new()).is_err());
+    let mom_it = HpxMomIter::<u64, Hpx<u64>, f64, _>::new(mom.into_iter());
+    assert!(
+      hpxmom_to_fits_skymap(SKYMAP_DEPTH_MAX + 1, coosys, mom_it, false, Vec::new()).is_err()
+    );
+  }
+
+  #[test]
+  fn test_skymap_to_multiordermap() {
+    // Depth 1 skymap: uniform base cells but base cells 1 (almost uniform) and 2
+    let mut values = vec![0.02_f64; 48];
+    values[4] = 0.021;
+    values[8] = 0.04;
+    let column = ValueColumn::new("PROB", None);
+    let mut buf: Vec<u8> = Vec::new();
+    to_fits_skymap(
+      1,
+      CoordSys::GAL,
+      &column,
+      values.into_iter(),
+      false,
+      &mut buf,
+    )
+    .unwrap();
+    for (rel_tol, n_cells) in [(0.0, 18), (0.1, 15), (1.0, 12)] {
+      let mom = multiordermap_from_fits_skymap(
+        BufReader::new(Cursor::new(buf.clone())),
+        None,
+        0.0,
+        rel_tol,
+      )
+      .unwrap();
+      assert_eq!(mom.depth_max(), 1);
+      assert_eq!(mom.len(), n_cells);
+      let sum: f64 = mom.iter().map(|(_, v)| v).sum();
+      assert!((sum - 0.981).abs() < 1e-12);
+    }
+  }
+
+  /// Build a skymap FITS file from its column keyword records and its rows.
+  fn build_skymap(
+    depth: u8,
+    ordering: Ordering,
+    schema: IndexSchema,
+    col_kws: &[[u8; 80]],
+    n_cols: u64,
+    other_kws: &[[u8; 80]],
+    rows: &[Vec<u8>],
+  ) -> Vec<u8> {
+    let mut moc_kws = MocKeywordsMap::new();
+    moc_kws.insert(MocKeywords::PixType(PixType::Healpix));
+    moc_kws.insert(MocKeywords::Ordering(ordering));
+    moc_kws.insert(MocKeywords::CoordSys(CoordSys::ICRS));
+    moc_kws.insert(MocKeywords::Nside(Nside {
+      nside: healpix::nside(depth),
+    }));
+    moc_kws.insert(MocKeywords::IndexSchema(schema));
+    let mut buf: Vec<u8> = Vec::new();
+    write_primary_hdu(&mut buf).unwrap();
+    write_bintable_header(
+      &mut buf,
+      rows[0].len() as u64,
+      rows.len() as u64,
+      n_cols,
+      col_kws,
+      moc_kws,
+      other_kws,
+    )
+    .unwrap();
+    let data = rows.concat();
+    buf.extend_from_slice(&data);
+    write_data_padding(&mut buf, data.len() as u64).unwrap();
+    buf
+  }
+
+  #[test]
+  fn test_skymap_explicit_ring() {
+    let col_kws = [
+      str_keyword_record(b"TTYPE1  ", "PIXEL").unwrap(),
+      str_keyword_record(b"TFORM1  ", "J").unwrap(),
+      str_keyword_record(b"TTYPE2  ", "PROB").unwrap(),
+      str_keyword_record(b"TFORM2  ", "E").unwrap(),
+      str_keyword_record(b"TTYPE3  ", "DISTMU").unwrap(),
+      str_keyword_record(b"TFORM3  ", "D").unwrap(),
+    ];
+    let row = |ipix: i32, prob: f32, dist: f64| {
+      [
+        &ipix.to_be_bytes()[..],
+        &prob.to_be_bytes()[..],
+        &dist.to_be_bytes()[..],
+      ]
+      .concat()
+    };
+    let rows = [
+      row(0, 0.25, 1.0),
+      row(1, 0.25, UNSEEN),
+      row(2, 0.25, f64::NAN),
+      row(3, 0.25, 2.0),
+      row(10, UNSEEN as f32, 5.0),
+    ];
+    let fits = build_skymap(
+      1,
+      Ordering::Ring,
+      IndexSchema::Explicit,
+      &col_kws,
+      3,
+      &[],
+      &rows,
+    );
+    // Default column: 'PROB'
+    let (depth, mom) =
+      mom_from_fits_skymap(BufReader::new(Cursor::new(fits.clone())), None, 0.0).unwrap();
+    assert_eq!(depth, 1);
+    let layer = healpix::nested::get(1);
+    let mut expected: Vec<u64> = (0..4)
+      .map(|ipix_ring| Hpx::<u64>::uniq_hpx(1, layer.from_ring(ipix_ring)))
+      .collect();
+    expected.sort_unstable();
+    let mut actual: Vec<u64> = mom.iter().map(|(uniq, _)| *uniq).collect();
+    actual.sort_unstable();
+    assert_eq!(actual, expected);
+    assert_eq!(mom.iter().map(|(_, v)| *v).sum::<f64>(), 1.0);
+    // Column selected by name (case insensitive), blank values being removed
+    let (_, mom) = mom_from_fits_skymap(
+      BufReader::new(Cursor::new(fits.clone())),
+      Some("distmu"),
+      0.0,
+    )
+    .unwrap();
+    assert_eq!(mom.iter().map(|(_, v)| *v).sum::<f64>(), 8.0);
+    // Unknown column
+    assert!(mom_from_fits_skymap(
+      BufReader::new(Cursor::new(fits.clone())),
+      Some("DISTSIGMA"),
+      0.0
+    )
+    .is_err());
+    // MOC
+    let moc = from_fits_skymap(
+      BufReader::new(Cursor::new(fits)),
+      None,
+      0.0,
+      0.0,
+      1.0,
+      false,
+      false,
+      false,
+      false,
+    )
+    .unwrap();
+    assert_eq!(moc.moc_ranges().0 .0.len(), expected.len());
+    // Explicit skymap without a 'PIXEL' column
+    let mut col_kws = col_kws;
+    col_kws[0] = str_keyword_record(b"TTYPE1  ", "IPIX").unwrap();
+    let fits = build_skymap(
+      1,
+      Ordering::Ring,
+      IndexSchema::Explicit,
+      &col_kws,
+      3,
+      &[],
+      &rows,
+    );
+    assert!(mom_from_fits_skymap(BufReader::new(Cursor::new(fits)), None, 0.0).is_err());
+  }
+
+  #[test]
+  fn test_skymap_implicit_vector_columns() {
+    let col_kws = [
+      str_keyword_record(b"TTYPE1  ", "PROBABILITY").unwrap(),
+      str_keyword_record(b"TFORM1  ", "4E").unwrap(),
+      str_keyword_record(b"TTYPE2  ", "MASK").unwrap(),
+      str_keyword_record(b"TFORM2  ", "4I").unwrap(),
+      *b"TNULL2  =                   -1                                                  ",
+    ];
+    let other_kws =
+      [*b"BAD_DATA=    -1.0000000000E+00                                                  "];
+    let row = |probs: [f32; 4], mask: [i16; 4]| {
+      let mut row: Vec<u8> = probs.iter().flat_map(|p| p.to_be_bytes()).collect();
+      row.extend(mask.iter().flat_map(|m| m.to_be_bytes()));
+      row
+    };
+    let rows = [
+      row([0.1, 0.1, 0.1, 0.1], [1, 1, -1, 1]),
+      row([-1.0, -1.0, 0.0, 0.0], [0, 0, 0, 0]),
+      row([0.2, 0.2, 0.1, f32::NAN], [-1, 2, 2, 2]),
+    ];
+    let fits = build_skymap(
+      0,
+      Ordering::Nested,
+      IndexSchema::Implicit,
+      &col_kws,
+      2,
+      &other_kws,
+      &rows,
+    );
+    let (depth, mom) =
+      mom_from_fits_skymap(BufReader::new(Cursor::new(fits.clone())), None, 0.0).unwrap();
+    assert_eq!(depth, 0);
+    assert_eq!(mom.len(), 7);
+    assert!((mom.iter().map(|(_, v)| *v).sum::<f64>() - 0.9).abs() < 1e-6);
+    let (_, mom) =
+      mom_from_fits_skymap(BufReader::new(Cursor::new(fits)), Some("MASK"), -1.0).unwrap();
+    assert_eq!(mom.len(), 10);
+    assert_eq!(mom.iter().map(|(_, v)| *v).sum::<f64>(), 9.0);
+  }
 }
@@ -0,0 +1,509 @@
+//! Strict validation of a FITS MOC file: HDU structure, `NAXIS2` vs data length, `CHECKSUM`
+//! and `DATASUM` keywords, MOC keywords consistency, depth bounds and sortedness of the ranges.
+//! Contrary to the reader, the validator does not stop at the first problem: it returns the list
+//! of all the issues found.
+
+use std::{
+  collections::HashMap,
+  fmt::{self, Display},
+  io::BufRead,
+};
+
+use crate::{
+  deser::fits::{
+    checksum::{checksum_add, parse_datasum, CHECKSUM_OK},
+    common::{get_keyword, parse_uint_val},
+    read_moc_hdu, MocIdxType, MocQtyType, MocType, STMocType,
+  },
+  idx::Idx,
+  moc::{
+    range::op::check::{check_issues, RangeIssue},
+    RangeMOCIntoIterator, RangeMOCIterator,
+  },
+  moc2d::{range::RangeMOC2Elem, HasTwoMaxDepth},
+  qty::MocQty,
+};
+
+/// Maximum number of issues reported on the MOC data (ranges or cells).
+const MAX_DATA_ISSUES: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+  /// The file does not comply with the FITS or the MOC standard, or is corrupted
+  Error,
+  /// The file is readable but not normalized or not complete (e.g. no checksum)
+  Warning,
+}
+
+/// Issue found while validating a FITS MOC file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+  pub severity: Severity,
+  pub message: String,
+}
+
+impl Issue {
+  fn error(message: String) -> Self {
+    Self {
+      severity: Severity::Error,
+      message,
+    }
+  }
+
+  fn warning(message: String) -> Self {
+    Self {
+      severity: Severity::Warning,
+      message,
+    }
+  }
+}
+
+impl Display for Issue {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.severity {
+      Severity::Error => write!(f, "ERROR: {}", self.message),
+      Severity::Warning => write!(f, "WARNING: {}", self.message),
+    }
+  }
+}
+
+/// Validates the content of a FITS MOC file, returning the list of issues found (an empty list
+/// meaning that the file is valid).
+///
+/// # Params
+/// * `bytes`: the full content of the FITS file (the checksum being computed on the whole data)
+pub fn validate_fits_ivoa(bytes: &[u8]) -> Vec<Issue> {
+  let mut issues = Vec::new();
+  if !bytes.len().is_multiple_of(2880) {
+    issues.push(Issue::error(format!(
+      "File size ({} bytes) is not a multiple of 2880 bytes.",
+      bytes.len()
+    )));
+  }
+  // Primary HDU
+  if !bytes.starts_with(b"SIMPLE  =") {
+    issues.push(Issue::error(String::from(
+      "Not a FITS file: 'SIMPLE' keyword not found.",
+    )));
+    return issues;
+  }
+  let Some(primary_header_len) = header_len(bytes) else {
+    issues.push(Issue::error(String::from(
+      "'END' keyword not found in the primary header.",
+    )));
+    return issues;
+  };
+  let primary_header = &bytes[..primary_header_len];
+  if find_record(primary_header, b"CHECKSUM").is_some()
+    && checksum_add(0, primary_header) != CHECKSUM_OK
+  {
+    issues.push(Issue::error(String::from(
+      "Wrong CHECKSUM in the primary HDU.",
+    )));
+  }
+  // Extension HDUs, several MOCs possibly being stored in a same file
+  let mut ext = &bytes[primary_header_len..];
+  let mut index = 1;
+  loop {
+    let mut hdu_issues = Vec::new();
+    let next = validate_hdu(ext, &mut hdu_issues).map(|hdu_len| &ext[hdu_len..]);
+    let has_next_hdu = next.is_some_and(|next| next.starts_with(b"XTENSION"));
+    if index > 1 || has_next_hdu {
+      for issue in &mut hdu_issues {
+        issue.message = format!("HDU {}: {}", index, issue.message);
+      }
+    }
+    issues.append(&mut hdu_issues);
+    match next {
+      Some(next) if has_next_hdu => {
+        ext = next;
+        index += 1;
+      }
+      Some(next) if !next.is_empty() => {
+        issues.push(Issue::warning(format!(
+          "{} bytes after the last HDU are ignored.",
+          next.len()
+        )));
+        break;
+      }
+      _ => break,
+    }
+  }
+  issues
+}
+
+/// Validates the extension HDU starting the given bytes, returning the length of the HDU
+/// (header plus padded data unit) or `None` if the length cannot be determined.
+fn validate_hdu(ext: &[u8], issues: &mut Vec<Issue>) -> Option<usize> {
+  let Some(header_len) = header_len(ext) else {
+    issues.push(Issue::error(String::from(
+      "No extension HDU, or 'END' keyword not found in the extension header.",
+    )));
+    return None;
+  };
+  let header = &ext[..header_len];
+  check_duplicated_keywords(header, issues);
+  let (naxis1, naxis2, pcount) = match (
+    parse_uint_record(header, b"NAXIS1  "),
+    parse_uint_record(header, b"NAXIS2  "),
+    parse_uint_record(header, b"PCOUNT  "),
+  ) {
+    (Ok(naxis1), Ok(naxis2), Ok(pcount)) => (naxis1, naxis2, pcount),
+    (naxis1, naxis2, pcount) => {
+      for err in [naxis1.err(), naxis2.err(), pcount.err()]
+        .into_iter()
+        .flatten()
+      {
+        issues.push(Issue::error(err));
+      }
+      return None;
+    }
+  };
+  // Data length
+  let data = &ext[header_len..];
+  let data_len = naxis1.saturating_mul(naxis2).saturating_add(pcount);
+  let padded_data_len = data_len.div_ceil(2880) * 2880;
+  let data_unit = &data[..data.len().min(padded_data_len as usize)];
+  if (data.len() as u64) < data_len {
+    issues.push(Issue::error(format!(
+      "NAXIS1 x NAXIS2 = {} x {} = {} bytes of data expected, but only {} bytes available.",
+      naxis1,
+      naxis2,
+      data_len,
+      data.len()
+    )));
+  } else if (data.len() as u64) < padded_data_len {
+    issues.push(Issue::warning(String::from(
+      "Data unit not completed to a multiple of 2880 bytes.",
+    )));
+  }
+  check_checksums(header, data_unit, issues);
+  // MOC keywords and data (the checksum being already checked)
+  match read_moc_hdu(ext, false, false, false) {
+    Ok((moc, _)) => match moc {
+      MocIdxType::U16(moc) => check_moc_data(moc, naxis2, issues),
+      MocIdxType::U32(moc) => check_moc_data(moc, naxis2, issues),
+      MocIdxType::U64(moc) => check_moc_data(moc, naxis2, issues),
+    },
+    Err(err) => issues.push(Issue::error(format!("Unable to read the MOC: {}", err))),
+  }
+  Some(header_len + data_unit.len())
+}
+
+/// Returns the length of the header (multiple of 2880) starting the given bytes, or `None` if no
+/// `END` keyword is found.
+fn header_len(bytes: &[u8]) -> Option<usize> {
+  bytes
+    .chunks_exact(80)
+    .position(|keyword_record| get_keyword(keyword_record) == b"END     ")
+    // The header may be truncated, which is reported as a wrong file size
+    .map(|i| ((i * 80 / 2880 + 1) * 2880).min(bytes.len()))
+}
+
+/// Returns the keyword records of the given header, stopping at the `END` keyword.
+fn records(header: &[u8]) -> impl Iterator<Item = &[u8]> {
+  header
+    .chunks_exact(80)
+    .take_while(|keyword_record| get_keyword(keyword_record) != b"END     ")
+}
+
+fn find_record<'a>(header: &'a [u8], keyword: &[u8; 8]) -> Option<&'a [u8]> {
+  records(header).find(|keyword_record| get_keyword(keyword_record) == keyword)
+}
+
+fn parse_uint_record(header: &[u8], keyword: &[u8; 8]) -> Result<u64, String> {
+  let keyword_str = String::from_utf8_lossy(keyword).trim_end().to_string();
+  find_record(header, keyword)
+    .ok_or_else(|| format!("Missing keyword '{}'.", keyword_str))
+    .and_then(|keyword_record| {
+      parse_uint_val::<u64>(keyword_record).map_err(|e| format!("{}: {}", keyword_str, e))
+    })
+}
+
+fn check_duplicated_keywords(header: &[u8], issues: &mut Vec<Issue>) {
+  let mut counts: HashMap<&[u8], usize> = HashMap::new();
+  for keyword_record in records(header) {
+    let keyword = get_keyword(keyword_record);
+    if !matches!(keyword, b"HISTORY " | b"COMMENT " | b"        ") {
+      *counts.entry(keyword).or_default() += 1;
+    }
+  }
+  let mut duplicates: Vec<(&[u8], usize)> = counts.into_iter().filter(|(_, n)| *n > 1).collect();
+  duplicates.sort();
+  for (keyword, n) in duplicates {
+    issues.push(Issue::warning(format!(
+      "Keyword '{}' found {} times in the MOC HDU header.",
+      String::from_utf8_lossy(keyword).trim_end(),
+      n
+    )));
+  }
+}
+
+fn check_checksums(header: &[u8], data_unit: &[u8], issues: &mut Vec<Issue>) {
+  let datasum = checksum_add(0, data_unit);
+  let datasum_record = find_record(header, b"DATASUM ");
+  let checksum_record = find_record(header, b"CHECKSUM");
+  if let Some(datasum_record) = datasum_record {
+    match parse_datasum(datasum_record) {
+      Ok(expected) if expected != datasum => issues.push(Issue::error(format!(
+        "Wrong DATASUM: {} in the header, {} computed from the data.",
+        expected, datasum
+      ))),
+      Ok(_) => (),
+      Err(err) => issues.push(Issue::error(err.to_string())),
+    }
+  }
+  if checksum_record.is_some() && checksum_add(datasum, header) != CHECKSUM_OK {
+    issues.push(Issue::error(String::from("Wrong CHECKSUM in the MOC HDU.")));
+  }
+  if datasum_record.is_none() && checksum_record.is_none() {
+    issues.push(Issue::warning(String::from(
+      "No CHECKSUM nor DATASUM keyword: the file integrity cannot be verified.",
+    )));
+  }
+}
+
+fn count_errors(issues: &[Issue]) -> usize {
+  issues
+    .iter()
+    .filter(|issue| issue.severity == Severity::Error)
+    .count()
+}
+
+fn check_moc_data<T: Idx, R: BufRead>(moc: MocQtyType<T, R>, naxis2: u64, issues: &mut Vec<Issue>) {
+  match moc {
+    MocQtyType::Hpx(moc) => check_moc_type(moc, naxis2, issues),
+    MocQtyType::Time(moc) => check_moc_type(moc, naxis2, issues),
+    MocQtyType::Freq(moc) => check_moc_type(moc, naxis2, issues),
+    MocQtyType::TimeHpx(STMocType::V2(moc2)) => check_moc2_ranges(moc2, issues),
+    MocQtyType::TimeHpx(STMocType::PreV2(moc2)) => {
+      issues.push(Issue::warning(String::from(
+        "ST-MOC written following a pre-version 2.0 of the MOC standard.",
+      )));
+      check_moc2_ranges(moc2, issues)
+    }
+    MocQtyType::FreqHpx(moc2) => check_moc2_ranges(moc2, issues),
+    MocQtyType::TimeFreq(moc2) => check_moc2_ranges(moc2, issues),
+  }
+}
+
+fn check_moc_type<T: Idx, Q: MocQty<T>, R: BufRead>(
+  moc: MocType<T, Q, R>,
+  naxis2: u64,
+  issues: &mut Vec<Issue>,
+) {
+  match moc {
+    MocType::Ranges(moc) => check_ranges(moc, "", issues),
+    MocType::Cells(moc) => {
+      // The reader ignores the null NUNIQ and the NUNIQ deeper than the MOC depth
+      let n_cells = moc.len() as u64;
+      if n_cells < naxis2 {
+        issues.push(Issue::warning(format!(
+          "{} NUNIQ values ignored (null or deeper than the MOC depth).",
+          naxis2 - n_cells
+        )));
+      }
+    }
+  }
+}
+
+fn check_ranges<T, Q, I>(moc: I, context: &str, issues: &mut Vec<Issue>)
+where
+  T: Idx,
+  Q: MocQty<T>,
+  I: RangeMOCIterator<T, Qty = Q>,
+{
+  if !check_depth::<T, Q>(moc.depth_max(), issues) {
+    return;
+  }
+  let range_issues = check_issues(moc);
+  let n_issues = range_issues.len();
+  for range_issue in range_issues.into_iter().take(MAX_DATA_ISSUES) {
+    let message = format!("{}{}.", context, range_issue);
+    issues.push(match range_issue {
+      RangeIssue::NotMerged { .. } => Issue::warning(message),
+      _ => Issue::error(message),
+    });
+  }
+  if n_issues > MAX_DATA_ISSUES {
+    issues.push(Issue::error(format!(
+      "{}{} more issues not reported.",
+      context,
+      n_issues - MAX_DATA_ISSUES
+    )));
+  }
+}
+
+/// Returns `false` if the given depth is larger than the maximum depth of the quantity (for
+/// the index type).
+fn check_depth<T: Idx, Q: MocQty<T>>(depth: u8, issues: &mut Vec<Issue>) -> bool {
+  if depth > Q::MAX_DEPTH {
+    issues.push(Issue::error(format!(
+      "{} depth {} larger than the maximum depth {} for {}-bytes indices.",
+      Q::NAME,
+      depth,
+      Q::MAX_DEPTH,
+      T::N_BYTES
+    )));
+    false
+  } else {
+    true
+  }
+}
+
+fn check_moc2_ranges<T, Q1, Q2, I>(moc2: I, issues: &mut Vec<Issue>)
+where
+  T: Idx,
+  Q1: MocQty<T>,
+  Q2: MocQty<T>,
+  I: HasTwoMaxDepth + Iterator<Item = RangeMOC2Elem<T, Q1, T, Q2>>,
+{
+  let depth_ok_1 = check_depth::<T, Q1>(moc2.depth_max_1(), issues);
+  let depth_ok_2 = check_depth::<T, Q2>(moc2.depth_max_2(), issues);
+  if !(depth_ok_1 && depth_ok_2) {
+    return;
+  }
+  let n_errors = count_errors(issues);
+  let mut prev_end: Option<T> = None;
+  for (i, elem) in moc2.enumerate() {
+    if count_errors(issues) - n_errors > MAX_DATA_ISSUES {
+      issues.push(Issue::error(String::from(
+        "Too many issues, stop checking the data.",
+      )));
+      return;
+    }
+    let (moc_1, moc_2) = elem.mocs();
+    if moc_1.is_empty() || moc_2.is_empty() {
+      issues.push(Issue::error(format!(
+        "Element {}: empty {} or {} MOC.",
+        i,
+        Q1::NAME,
+        Q2::NAME
+      )));
+      continue;
+    }
+    let first = moc_1.moc_ranges().iter().next().map(|r| r.start);
+    let last = moc_1.moc_ranges().iter().last().map(|r| r.end);
+    if let (Some(prev_end), Some(first)) = (prev_end, first) {
+      if first < prev_end {
+        issues.push(Issue::error(format!(
+          "Element {}: {} ranges not sorted or overlapping the previous element ones.",
+          i,
+          Q1::NAME
+        )));
+      }
+    }
+    prev_end = last;
+    check_ranges(
+      (&moc_1).into_range_moc_iter(),
+      &format!("Element {}, {} MOC: ", i, Q1::NAME),
+      issues,
+    );
+    check_ranges(
+      (&moc_2).into_range_moc_iter(),
+      &format!("Element {}, {} MOC: ", i, Q2::NAME),
+      issues,
+    );
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::{
+    deser::fits::{metadata::MocMetadata, ranges_to_fits_ivoa_with_metadata},
+    elemset::range::MocRanges,
+    moc::range::RangeMOC,
+    qty::{Hpx, Time},
+  };
+
+  fn checksum_metadata() -> MocMetadata {
+    let mut metadata = MocMetadata::default();
+    metadata.checksum = true;
+    metadata
+  }
+
+  fn tmoc_fits_with_metadata(metadata: &MocMetadata) -> Vec<u8> {
+    let moc: RangeMOC<u64, Time<u64>> = RangeMOC::new(
+      35,
+      MocRanges::new_unchecked(vec![1073741824..2684354560, 4294967296..5368709120]),
+    );
+    let mut buff: Vec<u8> = Default::default();
+    ranges_to_fits_ivoa_with_metadata(moc.into_range_moc_iter(), metadata, &mut buff).unwrap();
+    buff
+  }
+
+  fn tmoc_fits() -> Vec<u8> {
+    tmoc_fits_with_metadata(&checksum_metadata())
+  }
+
+  #[test]
+  fn test_validate_valid() {
+    assert_eq!(validate_fits_ivoa(&tmoc_fits()), vec![]);
+    // No checksum by default
+    let issues = validate_fits_ivoa(&tmoc_fits_with_metadata(&MocMetadata::default()));
+    assert_eq!(issues.len(), 1, "{:?}", issues);
+    assert_eq!(issues[0].severity, Severity::Warning);
+    assert!(issues[0].message.starts_with("No CHECKSUM"));
+  }
+
+  #[test]
+  fn test_validate_corrupted() {
+    let mut buff = tmoc_fits();
+    // Swap the two ranges: data not sorted anymore, DATASUM unchanged but CHECKSUM wrong
+    let data_start = 2 * 2880;
+    buff[data_start..data_start + 32].rotate_left(16);
+    let issues = validate_fits_ivoa(&buff);
+    assert!(issues.iter().all(|issue| issue.severity == Severity::Error));
+    assert_eq!(issues.len(), 1, "{:?}", issues);
+    assert!(issues[0].message.starts_with("Range 1 not sorted"));
+    // Change a value: wrong DATASUM and wrong CHECKSUM
+    buff[data_start + 7] ^= 1;
+    let issues = validate_fits_ivoa(&buff);
+    assert!(issues[0].message.starts_with("Wrong DATASUM"));
+    assert!(issues[1].message.starts_with("Wrong CHECKSUM"));
+    // Truncated data
+    let issues = validate_fits_ivoa(&buff[..2 * 2880 + 16]);
+    assert!(issues
+      .iter()
+      .any(|issue| issue.message.contains("bytes of data expected")));
+  }
+
+  #[test]
+  fn test_validate_mef() {
+    // Primary HDU followed by twice the same T-MOC HDU
+    let single = tmoc_fits();
+    let mut buff = single.clone();
+    buff.extend_from_slice(&single[2880..]);
+    assert_eq!(validate_fits_ivoa(&buff), vec![]);
+    // Change a value in the second HDU (keeping it at depth 35)
+    buff[single.len() + 2880 + 4] ^= 0x08;
+    let issues = validate_fits_ivoa(&buff);
+    assert_eq!(issues.len(), 2, "{:?}", issues);
+    assert!(issues[0].message.starts_with("HDU 2: Wrong DATASUM"));
+    assert!(issues[1].message.starts_with("HDU 2: Wrong CHECKSUM"));
+    // Block of spaces after the last HDU
+    buff.extend_from_slice(&[b' '; 2880]);
+    let issues = validate_fits_ivoa(&buff);
+    assert_eq!(issues.len(), 3, "{:?}", issues);
+    assert_eq!(
+      issues[2],
+      Issue::warning(String::from("2880 bytes after the last HDU are ignored."))
+    );
+  }
+
+  #[test]
+  #[allow(clippy::single_range_in_vec_init)]
+  fn test_validate_smoc_depth() {
+    // A range not aligned at depth 2
+    let moc: RangeMOC<u64, Hpx<u64>> =
+      RangeMOC::new(2, MocRanges::new_unchecked(vec![0..(1 << 54) + 1]));
+    let mut buff: Vec<u8> = Default::default();
+    ranges_to_fits_ivoa_with_metadata(moc.into_range_moc_iter(), &checksum_metadata(), &mut buff)
+      .unwrap();
+    let issues = validate_fits_ivoa(&buff);
+    assert_eq!(issues.len(), 1, "{:?}", issues);
+    assert!(issues[0].message.contains("not aligned on depth 2"));
+  }
+}
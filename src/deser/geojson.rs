@@ -0,0 +1,596 @@
+//! GeoJSON (RFC 7946) serialization and deserialization of S-MOCs.
+//!
+//! A S-MOC is written as a `FeatureCollection` containing a single `Feature` which geometry is
+//! a `MultiPolygon`, each polygon being a connected component of the MOC (with its possible holes).
+//! Coordinates are `[lon, lat]` in degrees.
+//! When reading a GeoJSON, all `Polygon` and `MultiPolygon` geometries (possibly in `Feature`s,
+//! `FeatureCollection`s or `GeometryCollection`s) are merged into a single S-MOC.
+
+use std::io::Write;
+
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use crate::{
+  idx::Idx,
+  moc::range::{
+    polygons::{contains, polygon_coverage_deg, remove_spikes, MocPolygon},
+    CellSelection, RangeMOC,
+  },
+  qty::{Hpx, MocQty},
+};
+
+#[derive(Error, Debug)]
+pub enum GeoJsonError {
+  #[error("I/O error: {0}")]
+  Io(#[from] std::io::Error),
+  #[error("JSON error: {0}")]
+  Json(#[from] serde_json::Error),
+  #[error("Depth {depth} larger than the max depth {depth_max}")]
+  WrongDepth { depth: u8, depth_max: u8 },
+  #[error("GeoJSON type not supported: {found:?}")]
+  UnsupportedType { found: String },
+  #[error("Wrong GeoJSON object: {msg}")]
+  WrongObject { msg: String },
+  #[error("Wrong GeoJSON coordinates: {msg}")]
+  WrongCoordinates { msg: String },
+}
+
+/// Write the given S-MOC as a GeoJSON `FeatureCollection` containing a single `MultiPolygon`
+/// feature, each polygon being a connected component of the MOC (see [RangeMOC::to_polygons]).
+/// The MOC depth is written in the `moc_depth` property of the feature.
+///
+/// Longitudes are written in `[-180, 180]` and polygons crossing the antimeridian are split
+/// into several polygons (RFC 7946, section 3.1.9).
+///
+/// # Remark
+/// To follow the MOC contours, the number of vertices is large: you may want to degrade the MOC
+/// first.
+pub fn to_geojson<T: Idx, W: Write>(
+  moc: &RangeMOC<T, Hpx<T>>,
+  mut writer: W,
+) -> Result<(), GeoJsonError> {
+  let coordinates: Vec<Value> = moc
+    .to_polygons()
+    .iter()
+    .flat_map(split_at_antimeridian)
+    .map(|(exterior, holes)| polygon_to_json(&exterior, &holes))
+    .collect();
+  let root = json!({
+    "type": "FeatureCollection",
+    "features": [{
+      "type": "Feature",
+      "properties": { "moc_depth": moc.depth_max() },
+      "geometry": {
+        "type": "MultiPolygon",
+        "coordinates": coordinates
+      }
+    }]
+  });
+  serde_json::to_writer(&mut writer, &root)?;
+  writer.write_all(b"\n").map_err(GeoJsonError::Io)
+}
+
+/// Ring vertices, `(lon, lat)` in degrees, not explicitly closed.
+type Ring = Vec<(f64, f64)>;
+
+fn polygon_to_json(exterior: &Ring, holes: &[Ring]) -> Value {
+  Value::Array(
+    std::iter::once(exterior)
+      .chain(holes.iter())
+      .map(|ring| {
+        // GeoJSON rings are explicitly closed
+        Value::Array(
+          ring
+            .iter()
+            .chain(ring.first())
+            .map(|(lon, lat)| json!([lon, lat]))
+            .collect(),
+        )
+      })
+      .collect(),
+  )
+}
+
+/// Converts the polygon coordinates in degrees and splits it along the antimeridian, returning
+/// polygons having their longitudes in `[-180, 180]`.
+///
+/// The longitudes of a [MocPolygon] being continuous along a ring, each ring is seen as a closed
+/// curve on the `(lon, lat)` cylinder, and is cut at each antimeridian crossing. The resulting
+/// chains start and end on the `lon = +-180` sides of the `[-180, 180] x [-90, 90]` rectangle.
+/// The MOC being on the left of the rings, the chains are joined following the rectangle border
+/// counterclockwise.
+fn split_at_antimeridian(polygon: &MocPolygon) -> Vec<(Ring, Vec<Ring>)> {
+  let mut chains: Vec<Ring> = Vec::new();
+  let mut exteriors: Vec<Ring> = Vec::new();
+  let mut holes: Vec<Ring> = Vec::new();
+  let mut full_sky = false;
+  for (i, ring) in std::iter::once(&polygon.exterior)
+    .chain(polygon.holes.iter())
+    .enumerate()
+  {
+    let ring = cylinder_ring(ring);
+    if ring.len() < 3 {
+      // Only the full sky exterior ring is made of artificial edges along the poles
+      full_sky |= i == 0;
+      continue;
+    }
+    let ring_chains = cut_at_antimeridian(&ring);
+    if !ring_chains.is_empty() {
+      chains.extend(ring_chains);
+    } else {
+      // Ring in a single `]180 + 360 (k - 1), 180 + 360 k]` longitude range
+      let shift = 360.0 * period(ring[0].0) as f64;
+      let ring: Ring = ring
+        .into_iter()
+        .map(|(lon, lat)| (lon - shift, lat))
+        .collect();
+      if i == 0 {
+        exteriors.push(ring);
+      } else {
+        holes.push(ring);
+      }
+    }
+  }
+  if full_sky && chains.is_empty() {
+    exteriors.push(CORNERS.iter().map(|(_, corner)| *corner).collect());
+  }
+  exteriors.extend(join_chains(&chains));
+  let mut polygons: Vec<(Ring, Vec<Ring>)> = exteriors
+    .into_iter()
+    .map(|exterior| (exterior, Vec::new()))
+    .collect();
+  for hole in holes {
+    if let Some((_, holes)) = polygons
+      .iter_mut()
+      .find(|(exterior, _)| contains(exterior, hole[0]))
+    {
+      holes.push(hole);
+    }
+  }
+  polygons
+}
+
+/// Converts the ring in degrees, removing the artificial edges closing along a pole the rings
+/// going around that pole (see [MocPolygon]).
+fn cylinder_ring(ring: &[(f64, f64)]) -> Ring {
+  let is_pole = |lat: f64| 90.0 - lat.abs() < 1e-9;
+  let mut ring: Ring = ring
+    .iter()
+    .map(|(lon, lat)| (lon.to_degrees(), lat.to_degrees()))
+    .collect();
+  // Edges along a pole linking two cells are at most 270 degrees wide
+  while let Some(i) = (0..ring.len()).find(|i| {
+    let (lon1, lat1) = ring[*i];
+    let (lon2, lat2) = ring[(*i + 1) % ring.len()];
+    is_pole(lat1) && is_pole(lat2) && (lon2 - lon1).abs() > 315.0
+  }) {
+    let j = (i + 1) % ring.len();
+    ring.remove(i.max(j));
+    ring.remove(i.min(j));
+  }
+  // The ring was closed by a vertex equal, modulo 360, to the first one
+  while ring.len() > 1 && {
+    let ((lon1, lat1), (lon2, lat2)) = (ring[0], ring[ring.len() - 1]);
+    let dlon = (lon2 - lon1).rem_euclid(360.0);
+    (lat2 - lat1).abs() < 1e-9 && (dlon < 1e-9 || 360.0 - dlon < 1e-9)
+  } {
+    ring.pop();
+  }
+  ring
+}
+
+/// Index `k` of the `]180 + 360 (k - 1), 180 + 360 k]` longitude range containing `lon`.
+fn period(lon: f64) -> i64 {
+  ((lon - 180.0) / 360.0).ceil() as i64
+}
+
+/// Cuts the ring at each antimeridian crossing, returning the chains (with longitudes in
+/// `[-180, 180]`) starting and ending on the antimeridian. Returns no chain if the ring does not
+/// cross the antimeridian.
+fn cut_at_antimeridian(ring: &Ring) -> Vec<Ring> {
+  let n = ring.len();
+  // The edge closing a ring going around a pole goes back to the first vertex shifted by 360
+  let close_shift = 360.0 * ((ring[n - 1].0 - ring[0].0) / 360.0).round();
+  let edge = |i: usize| {
+    let from = ring[i];
+    let to = if i + 1 == n {
+      (ring[0].0 + close_shift, ring[0].1)
+    } else {
+      ring[i + 1]
+    };
+    (from, to)
+  };
+  let Some(first) = (0..n).find(|i| {
+    let ((lon1, _), (lon2, _)) = edge(*i);
+    period(lon1) != period(lon2)
+  }) else {
+    return Vec::new();
+  };
+  let push = |chain: &mut Ring, vertex: (f64, f64)| {
+    if chain.last() != Some(&vertex) {
+      chain.push(vertex);
+    }
+  };
+  let mut chains = Vec::new();
+  let mut chain: Option<Ring> = None;
+  // The first crossing edge is visited twice, to close the last chain
+  for k in 0..=n {
+    let ((lon1, lat1), (lon2, lat2)) = edge((first + k) % n);
+    let (k1, k2) = (period(lon1), period(lon2));
+    let crossings: Vec<i64> = if k2 > k1 {
+      (k1..k2).collect()
+    } else {
+      (k2..k1).rev().collect()
+    };
+    for line in crossings {
+      let lon = 180.0 + 360.0 * line as f64;
+      let lat = lat1 + (lon - lon1) / (lon2 - lon1) * (lat2 - lat1);
+      let (end, start) = if k2 > k1 {
+        ((180.0, lat), (-180.0, lat))
+      } else {
+        ((-180.0, lat), (180.0, lat))
+      };
+      if let Some(mut ended) = chain.take() {
+        push(&mut ended, end);
+        chains.push(ended);
+      }
+      if k == n {
+        return chains;
+      }
+      chain = Some(vec![start]);
+    }
+    if let Some(chain) = chain.as_mut() {
+      push(chain, (lon2 - 360.0 * k2 as f64, lat2));
+    }
+  }
+  chains
+}
+
+/// Positions, along the `[-180, 180] x [-90, 90]` rectangle border followed counterclockwise
+/// from its south-east corner, of the rectangle corners.
+const CORNERS: [(f64, (f64, f64)); 4] = [
+  (180.0, (180.0, 90.0)),
+  (540.0, (-180.0, 90.0)),
+  (720.0, (-180.0, -90.0)),
+  (1080.0, (180.0, -90.0)),
+];
+
+/// Position of a chain end (on the `lon = +-180` sides) along the rectangle border.
+fn border_pos((lon, lat): (f64, f64)) -> f64 {
+  if lon > 0.0 {
+    lat + 90.0
+  } else {
+    540.0 + (90.0 - lat)
+  }
+}
+
+/// Joins the chains into rings, going from the end of a chain to the start of the next chain
+/// following the rectangle border counterclockwise.
+fn join_chains(chains: &[Ring]) -> Vec<Ring> {
+  let mut rings = Vec::new();
+  let mut used = vec![false; chains.len()];
+  for start in 0..chains.len() {
+    if used[start] {
+      continue;
+    }
+    let mut ring = Vec::new();
+    let mut curr = start;
+    loop {
+      used[curr] = true;
+      ring.extend_from_slice(&chains[curr]);
+      let end_pos = border_pos(chains[curr][chains[curr].len() - 1]);
+      let dist = |j: usize| (border_pos(chains[j][0]) - end_pos).rem_euclid(1080.0);
+      let Some(next) = (0..chains.len())
+        .filter(|j| *j != curr || dist(*j) > 0.0)
+        .min_by(|a, b| dist(*a).total_cmp(&dist(*b)))
+      else {
+        break;
+      };
+      let next_dist = dist(next);
+      let mut corners: Vec<(f64, (f64, f64))> = CORNERS
+        .iter()
+        .map(|(pos, corner)| ((pos - end_pos).rem_euclid(1080.0), *corner))
+        .filter(|(d, _)| *d > 0.0 && *d < next_dist)
+        .collect();
+      corners.sort_by(|(d1, _), (d2, _)| d1.total_cmp(d2));
+      ring.extend(corners.into_iter().map(|(_, corner)| corner));
+      if used[next] {
+        break;
+      }
+      curr = next;
+    }
+    let ring = remove_spikes(ring);
+    if ring.len() >= 3 {
+      rings.push(ring);
+    }
+  }
+  rings
+}
+
+/// Build a S-MOC of given depth from a GeoJSON input, merging all the `Polygon` and `MultiPolygon`
+/// geometries it contains.
+///
+/// # Input
+/// - `input`: the GeoJSON string, its root being either a `FeatureCollection`, a `Feature`,
+///   a `GeometryCollection`, a `Polygon` or a `MultiPolygon`
+/// - `depth`: the MOC depth
+/// - `selection`: select BMOC cells to keep in the MOC
+///
+/// # Remark
+/// Polygon edges are great-circle arcs. A ring having an edge along a pole latitude (`+-90`)
+/// and spanning 360 degrees in longitude is considered as containing that pole.
+pub fn from_geojson(
+  input: &str,
+  depth: u8,
+  selection: CellSelection,
+) -> Result<RangeMOC<u64, Hpx<u64>>, GeoJsonError> {
+  if depth > Hpx::<u64>::MAX_DEPTH {
+    return Err(GeoJsonError::WrongDepth {
+      depth,
+      depth_max: Hpx::<u64>::MAX_DEPTH,
+    });
+  }
+  let root: Value = serde_json::from_str(input)?;
+  let mut moc = RangeMOC::new_empty(depth);
+  add_geojson_object(&root, depth, selection, &mut moc)?;
+  Ok(moc)
+}
+
+fn add_geojson_object(
+  value: &Value,
+  depth: u8,
+  selection: CellSelection,
+  moc: &mut RangeMOC<u64, Hpx<u64>>,
+) -> Result<(), GeoJsonError> {
+  let get_array = |key: &str| {
+    value
+      .get(key)
+      .and_then(|v| v.as_array())
+      .ok_or_else(|| GeoJsonError::WrongObject {
+        msg: format!("array '{}' not found in {}", key, value),
+      })
+  };
+  match value.get("type").and_then(|t| t.as_str()) {
+    Some("FeatureCollection") => {
+      for feature in get_array("features")? {
+        add_geojson_object(feature, depth, selection, moc)?;
+      }
+    }
+    Some("Feature") => match value.get("geometry") {
+      Some(Value::Null) => {}
+      Some(geometry) => add_geojson_object(geometry, depth, selection, moc)?,
+      None => {
+        return Err(GeoJsonError::WrongObject {
+          msg: String::from("Feature without geometry member"),
+        })
+      }
+    },
+    Some("GeometryCollection") => {
+      for geometry in get_array("geometries")? {
+        add_geojson_object(geometry, depth, selection, moc)?;
+      }
+    }
+    Some("Polygon") => {
+      *moc = moc.or(&polygon_to_moc(
+        get_array("coordinates")?,
+        depth,
+        selection,
+      )?);
+    }
+    Some("MultiPolygon") => {
+      for polygon in get_array("coordinates")? {
+        let rings = polygon
+          .as_array()
+          .ok_or_else(|| GeoJsonError::WrongCoordinates {
+            msg: format!("polygon is not an array: {}", polygon),
+          })?;
+        *moc = moc.or(&polygon_to_moc(rings, depth, selection)?);
+      }
+    }
+    Some(other) => {
+      return Err(GeoJsonError::UnsupportedType {
+        found: other.to_string(),
+      })
+    }
+    None => {
+      return Err(GeoJsonError::WrongObject {
+        msg: String::from("no 'type' member found"),
+      })
+    }
+  }
+  Ok(())
+}
+
+/// The first ring is the exterior ring, the other ones are holes.
+fn polygon_to_moc(
+  rings: &[Value],
+  depth: u8,
+  selection: CellSelection,
+) -> Result<RangeMOC<u64, Hpx<u64>>, GeoJsonError> {
+  let mut rings = rings.iter().map(parse_ring);
+  let exterior = rings
+    .next()
+    .ok_or_else(|| GeoJsonError::WrongCoordinates {
+      msg: String::from("polygon without ring"),
+    })??;
+  let holes = rings.collect::<Result<Vec<Vec<(f64, f64)>>, GeoJsonError>>()?;
+  polygon_coverage_deg(&exterior, &holes, depth, selection)
+    .map_err(|msg| GeoJsonError::WrongCoordinates { msg })
+}
+
+/// Parse the positions of a ring, removing the closing position.
+fn parse_ring(ring: &Value) -> Result<Vec<(f64, f64)>, GeoJsonError> {
+  let wrong_coos = |msg: String| GeoJsonError::WrongCoordinates { msg };
+  let mut vertices = ring
+    .as_array()
+    .ok_or_else(|| wrong_coos(format!("ring is not an array: {}", ring)))?
+    .iter()
+    .map(|pos| match pos.as_array().map(|a| a.as_slice()) {
+      Some([lon, lat, ..]) => match (lon.as_f64(), lat.as_f64()) {
+        (Some(lon), Some(lat)) if lon.is_finite() && (-90.0..=90.0).contains(&lat) => {
+          Ok((lon, lat))
+        }
+        _ => Err(wrong_coos(format!("wrong position: {}", pos))),
+      },
+      _ => Err(wrong_coos(format!("wrong position: {}", pos))),
+    })
+    .collect::<Result<Vec<(f64, f64)>, GeoJsonError>>()?;
+  if vertices.len() > 1 && vertices.first() == vertices.last() {
+    vertices.pop();
+  }
+  Ok(vertices)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::f64::consts::{FRAC_PI_2, PI};
+
+  use serde_json::Value;
+
+  use super::{from_geojson, to_geojson};
+  use crate::{
+    elemset::range::MocRanges,
+    moc::range::{CellSelection, RangeMOC},
+    qty::Hpx,
+  };
+
+  fn check_round_trip(moc: &RangeMOC<u64, Hpx<u64>>) -> Value {
+    let mut buff = Vec::new();
+    to_geojson(moc, &mut buff).unwrap();
+    let geojson = String::from_utf8(buff).unwrap();
+    let depth = moc.depth_max();
+    // Great-circle edges slightly differ from the HEALPix cell edges
+    let all = from_geojson(&geojson, depth, CellSelection::All).unwrap();
+    let inside = from_geojson(&geojson, depth, CellSelection::Inside).unwrap();
+    assert!(moc.minus(&all).is_empty());
+    assert!(inside.minus(moc).is_empty());
+    assert!(!inside.is_empty());
+    let json: Value = serde_json::from_str(&geojson).unwrap();
+    // RFC 7946 longitudes
+    for polygon in json["features"][0]["geometry"]["coordinates"]
+      .as_array()
+      .unwrap()
+    {
+      for ring in polygon.as_array().unwrap() {
+        for pos in ring.as_array().unwrap() {
+          assert!((-180.0..=180.0).contains(&pos[0].as_f64().unwrap()));
+        }
+      }
+    }
+    json
+  }
+
+  #[test]
+  fn test_geojson_cone() {
+    let moc = RangeMOC::from_cone(1.0, 0.5, 0.1, 8, 2, CellSelection::All);
+    let json = check_round_trip(&moc);
+    let feature = &json["features"][0];
+    assert_eq!(feature["properties"]["moc_depth"], 8);
+    assert_eq!(feature["geometry"]["type"], "MultiPolygon");
+    let polygons = feature["geometry"]["coordinates"].as_array().unwrap();
+    assert_eq!(polygons.len(), 1);
+    let exterior = polygons[0][0].as_array().unwrap();
+    assert_eq!(exterior.first(), exterior.last());
+  }
+
+  #[test]
+  fn test_geojson_antimeridian() {
+    // Cone crossing the antimeridian, with a hole also crossing it
+    let outer: RangeMOC<u64, Hpx<u64>> =
+      RangeMOC::from_cone(PI, 0.5, 0.1, 8, 2, CellSelection::All);
+    let inner = RangeMOC::from_cone(PI, 0.5, 0.03, 8, 2, CellSelection::All);
+    let json = check_round_trip(&outer);
+    let polygons = json["features"][0]["geometry"]["coordinates"]
+      .as_array()
+      .unwrap();
+    assert_eq!(polygons.len(), 2);
+    let json = check_round_trip(&outer.minus(&inner));
+    let polygons = json["features"][0]["geometry"]["coordinates"]
+      .as_array()
+      .unwrap();
+    assert_eq!(polygons.len(), 2);
+    assert!(polygons.iter().all(|p| p.as_array().unwrap().len() == 1));
+    // Full sky, and full sky minus a cone crossing the antimeridian
+    let full = RangeMOC::new_full_domain(3);
+    let json = check_round_trip(&full);
+    assert_eq!(
+      json["features"][0]["geometry"]["coordinates"][0][0]
+        .as_array()
+        .unwrap()
+        .len(),
+      5
+    );
+    check_round_trip(&outer.not());
+  }
+
+  #[test]
+  fn test_geojson_holes_and_components() {
+    let outer: RangeMOC<u64, Hpx<u64>> =
+      RangeMOC::from_cone(1.0, 0.5, 0.1, 8, 2, CellSelection::All);
+    let inner = RangeMOC::from_cone(1.0, 0.5, 0.03, 8, 2, CellSelection::All);
+    let other = RangeMOC::from_cone(4.0, -0.5, 0.05, 8, 2, CellSelection::All);
+    let moc = outer.minus(&inner).or(&other);
+    let json = check_round_trip(&moc);
+    let polygons = json["features"][0]["geometry"]["coordinates"]
+      .as_array()
+      .unwrap();
+    assert_eq!(polygons.len(), 2);
+  }
+
+  #[test]
+  #[allow(clippy::single_range_in_vec_init)]
+  fn test_geojson_polar_cap() {
+    let moc = RangeMOC::from_cone(0.0, FRAC_PI_2, 0.2, 6, 2, CellSelection::All);
+    check_round_trip(&moc);
+    check_round_trip(&moc.not());
+    // Band around the equator and its complement
+    let south = RangeMOC::from_cone(0.0, -FRAC_PI_2, 1.0, 5, 2, CellSelection::All);
+    let north = RangeMOC::from_cone(0.0, FRAC_PI_2, 1.0, 5, 2, CellSelection::All);
+    let caps = north.or(&south);
+    check_round_trip(&caps);
+    check_round_trip(&caps.not());
+    // Base cells touching a pole
+    check_round_trip(&RangeMOC::new(
+      3,
+      MocRanges::new_unchecked(vec![0..(2_u64 << 58)]),
+    ));
+  }
+
+  #[test]
+  fn test_geojson_input() {
+    let polygon = r#"{"type": "Polygon", "coordinates": [
+      [[10, 10], [20, 10], [20, 20], [10, 20], [10, 10]],
+      [[14, 14], [16, 14], [16, 16], [14, 16], [14, 14]]
+    ]}"#;
+    let moc = from_geojson(polygon, 8, CellSelection::All).unwrap();
+    assert!(moc.is_in(12_f64.to_radians(), 12_f64.to_radians()));
+    assert!(!moc.is_in(15_f64.to_radians(), 15_f64.to_radians()));
+    assert!(!moc.is_in(25_f64.to_radians(), 15_f64.to_radians()));
+    let feature = format!(
+      r#"{{"type": "FeatureCollection", "features": [
+        {{"type": "Feature", "properties": {{}}, "geometry": {}}},
+        {{"type": "Feature", "properties": {{}}, "geometry": {{"type": "MultiPolygon", "coordinates": [
+          [[[-30, -10], [-20, -10], [-20, 0], [-30, 0], [-30, -10]]]
+        ]}}}}
+      ]}}"#,
+      polygon
+    );
+    let moc2 = from_geojson(&feature, 8, CellSelection::All).unwrap();
+    assert!(moc.minus(&moc2).is_empty());
+    assert!(moc2.is_in(335_f64.to_radians(), -5_f64.to_radians()));
+    // Unsupported geometry
+    assert!(from_geojson(
+      r#"{"type": "Point", "coordinates": [0, 0]}"#,
+      8,
+      CellSelection::All
+    )
+    .is_err());
+    // Full sky
+    let full = r#"{"type": "Polygon", "coordinates": [[[0, -90], [360, -90], [360, 90], [0, 90], [0, -90]]]}"#;
+    assert_eq!(
+      from_geojson(full, 3, CellSelection::All).unwrap(),
+      RangeMOC::new_full_domain(3)
+    );
+  }
+}
@@ -1,6 +1,6 @@
-use std::io::{BufRead, BufReader, Read, Seek};
+use std::io::{BufRead, BufReader, Read, Seek, Write};
 
-use flate2::read::GzDecoder;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 
 const GZ_MAGIC_NUM: [u8; 2] = [0x1F, 0x8B];
 const GZ_MAGIC_NUM_LEN: usize = GZ_MAGIC_NUM.len();
@@ -17,6 +17,12 @@ pub fn uncompress<R: BufRead>(reader: R) -> BufReader<GzDecoder<R>> {
   BufReader::new(GzDecoder::new(reader))
 }
 
+/// Returns an object implementing `Write` and compressing on-the-fly into the given `Write`.
+/// Call `finish` on the returned object to write the gzip footer and get back the inner `Write`.
+pub fn compress<W: Write>(writer: W) -> GzEncoder<W> {
+  GzEncoder::new(writer, Compression::default())
+}
+
 /*pub fn uncompress_if_needed<R, O, F>(
   mut reader: BufReader<R>,
   op: F
@@ -341,11 +341,7 @@ fn show_with_default_app(path: &str) -> Result<(), io::Error> {
 #[cfg(test)]
 mod tests {
 
-  use std::{
-    fs::File,
-    io::BufReader,
-    path::{Path, PathBuf},
-  };
+  use std::{env::temp_dir, fs::File, io::BufReader, path::PathBuf};
 
   use mapproj::{
     conic::{cod::Cod, coe::Coe, coo::Coo, cop::Cop},
@@ -391,7 +387,7 @@ mod tests {
           None,
           None,
           None,
-          &Path::new("sdss_mol.png"),
+          &temp_dir().join("sdss_mol.png"),
           view,
         )
         .unwrap();
@@ -402,7 +398,7 @@ mod tests {
           None,
           None,
           None,
-          &Path::new("sdss_ait.png"),
+          &temp_dir().join("sdss_ait.png"),
           view,
         )
         .unwrap();
@@ -413,7 +409,7 @@ mod tests {
           None,
           None,
           None,
-          &Path::new("sdss_par.png"),
+          &temp_dir().join("sdss_par.png"),
           view,
         )
         .unwrap();
@@ -424,7 +420,7 @@ mod tests {
           None,
           None,
           None,
-          &Path::new("sdss_sfl.png"),
+          &temp_dir().join("sdss_sfl.png"),
           view,
         )
         .unwrap();
@@ -436,7 +432,7 @@ mod tests {
           None,
           None,
           None,
-          &Path::new("sdss_car.png"),
+          &temp_dir().join("sdss_car.png"),
           view,
         )
         .unwrap();
@@ -447,7 +443,7 @@ mod tests {
           None,
           None,
           None,
-          &Path::new("sdss_cea.png"),
+          &temp_dir().join("sdss_cea.png"),
           view,
         )
         .unwrap();
@@ -458,7 +454,7 @@ mod tests {
           None,
           None,
           None,
-          &Path::new("sdss_cyp.png"),
+          &temp_dir().join("sdss_cyp.png"),
           view,
         )
         .unwrap();
@@ -469,7 +465,7 @@ mod tests {
           None,
           None,
           None,
-          &Path::new("sdss_mer.png"),
+          &temp_dir().join("sdss_mer.png"),
           view,
         )
         .unwrap();
@@ -481,7 +477,7 @@ mod tests {
           None,
           None,
           None,
-          &Path::new("sdss_cod.png"),
+          &temp_dir().join("sdss_cod.png"),
           view,
         )
         .unwrap();
@@ -492,7 +488,7 @@ mod tests {
           None,
           None,
           None,
-          &Path::new("sdss_coe.png"),
+          &temp_dir().join("sdss_coe.png"),
           view,
         )
         .unwrap();
@@ -503,7 +499,7 @@ mod tests {
           None,
           None,
           None,
-          &Path::new("sdss_coo.png"),
+          &temp_dir().join("sdss_coo.png"),
           view,
         )
         .unwrap();
@@ -514,7 +510,7 @@ mod tests {
           None,
           None,
           None,
-          &Path::new("sdss_cop.png"),
+          &temp_dir().join("sdss_cop.png"),
           view,
         )
         .unwrap();
@@ -526,7 +522,7 @@ mod tests {
           None,
           None,
           None,
-          &Path::new("sdss_hpx.png"),
+          &temp_dir().join("sdss_hpx.png"),
           view,
         )
         .unwrap();
@@ -538,7 +534,7 @@ mod tests {
           None,
           None,
           None,
-          &Path::new("sdss_air.png"),
+          &temp_dir().join("sdss_air.png"),
           view,
         )
         .unwrap();
@@ -549,7 +545,7 @@ mod tests {
           None,
           None,
           None,
-          &Path::new("sdss_arc.png"),
+          &temp_dir().join("sdss_arc.png"),
           view,
         )
         .unwrap();
@@ -560,7 +556,7 @@ mod tests {
           None,
           Some((-3.0..=3.0, -3.0..=3.0)),
           None,
-          &Path::new("sdss_azp.png"),
+          &temp_dir().join("sdss_azp.png"),
           view,
         )
         .unwrap();
@@ -571,7 +567,7 @@ mod tests {
           None,
           None,
           None,
-          &Path::new("sdss_feye_front.png"),
+          &temp_dir().join("sdss_feye_front.png"),
           view,
         )
         .unwrap();
@@ -582,7 +578,7 @@ mod tests {
           Some((180.0, 0.0)),
           None,
           None,
-          &Path::new("sdss_feye_back.png"),
+          &temp_dir().join("sdss_feye_back.png"),
           view,
         )
         .unwrap();
@@ -594,7 +590,7 @@ mod tests {
           None,
           None,
           None,
-          &Path::new("sdss_ncp_front.png"),
+          &temp_dir().join("sdss_ncp_front.png"),
           view,
         )
         .unwrap();
@@ -605,7 +601,7 @@ mod tests {
           Some((180.0, 0.0)),
           None,
           None,
-          &Path::new("sdss_ncp_back.png"),
+          &temp_dir().join("sdss_ncp_back.png"),
           view,
         )
         .unwrap();
@@ -617,7 +613,7 @@ mod tests {
           None,
           None,
           None,
-          &Path::new("sdss_sin_front.png"),
+          &temp_dir().join("sdss_sin_front.png"),
           view,
         )
         .unwrap();
@@ -628,7 +624,7 @@ mod tests {
           Some((180.0, 0.0)),
           None,
           None,
-          &Path::new("sdss_sin_back.png"),
+          &temp_dir().join("sdss_sin_back.png"),
           view,
         )
         .unwrap();
@@ -640,7 +636,7 @@ mod tests {
           None,
           None,
           None,
-          &Path::new("sdss_stg.png"),
+          &temp_dir().join("sdss_stg.png"),
           view,
         )
         .unwrap();
@@ -651,7 +647,7 @@ mod tests {
           None,
           Some((-10.0..=10.0, -10.0..=10.0)),
           None,
-          &Path::new("sdss_szp.png"),
+          &temp_dir().join("sdss_szp.png"),
           view,
         )
         .unwrap();
@@ -662,7 +658,7 @@ mod tests {
           None,
           None,
           None,
-          &Path::new("sdss_tan.png"),
+          &temp_dir().join("sdss_tan.png"),
           view,
         )
         .unwrap();
@@ -673,7 +669,7 @@ mod tests {
           None,
           None,
           None,
-          &Path::new("sdss_zea.png"),
+          &temp_dir().join("sdss_zea.png"),
           view,
         )
         .unwrap();
@@ -684,7 +680,7 @@ mod tests {
           None,
           None,
           None,
-          &Path::new("sdss_zpn.png"),
+          &temp_dir().join("sdss_zpn.png"),
           view,
         )
         .unwrap();
@@ -1,16 +1,20 @@
-use std::{error::Error, io::Write};
+use std::{error::Error, io::Write, ops::Range};
 
 use byteorder::WriteBytesExt;
 use serde_json::{self, value::Value::Array, Value};
 
 use crate::{
   elem::cell::Cell,
-  elemset::cell::{Cells, MocCells},
+  elemset::{
+    cell::{Cells, MocCells},
+    range::MocRanges,
+  },
   idx::Idx,
-  moc::{cell::CellMOC, CellMOCIterator, HasMaxDepth},
+  moc::{cell::CellMOC, range::RangeMOC, CellMOCIterator, HasMaxDepth, RangeMOCIterator},
   moc2d::{
     cell::{CellMOC2, CellMOC2Elem},
-    CellMOC2ElemIt, CellMOC2Iterator,
+    range::{RangeMOC2, RangeMOC2Elem},
+    CellMOC2ElemIt, CellMOC2Iterator, RangeMOC2ElemIt, RangeMOC2Iterator,
   },
   qty::MocQty,
 };
@@ -129,27 +133,95 @@ where
   writer.write_all(b"\n]\n")
 }
 
-/*pub fn rangemoc2d_to_json_aladin<T, Q, I, U, R, J, K, L, W>(
+/// Write a JSON following the Aladin JSON format, extended with ranges: each range of the
+/// MOC is written at the MOC depth max, either as a single cell index (if the range contains
+/// a single cell) or as an array containing the first and the last (inclusive) cell indices,
+/// e.g. `{ "12": [[0, 9], 15] }`.
+/// Contrary to [to_json_aladin], ranges are not decomposed into cells of various depths and are
+/// written in streaming mode (without buffering).
+///
+/// # Warning
+/// The output can be read by [range_moc_from_json_aladin], but not by [from_json_aladin]
+/// nor by Aladin.
+pub fn ranges_to_json_aladin<T, Q, I, W>(
+  it: I,
+  fold: &Option<usize>,
+  line_prefix: &str,
+  mut writer: W,
+) -> std::io::Result<()>
+where
+  T: Idx,
+  Q: MocQty<T>,
+  I: RangeMOCIterator<T, Qty = Q>,
+  W: Write,
+{
+  let depth_max = it.depth_max();
+  let shift = Q::shift_from_depth_max(depth_max) as u32;
+  let header = format!("{{\n{}  \"{}\": [", line_prefix, depth_max);
+  writer.write_all(header.as_bytes())?;
+  // Length of the current line
+  let mut l = header.len() - 2;
+  let mut first = true;
+  for range in it {
+    let (start, end) = (
+      range.start.unsigned_shr(shift),
+      range.end.unsigned_shr(shift),
+    );
+    let s = if end - start == T::one() {
+      format!("{}", start)
+    } else {
+      format!("[{}, {}]", start, end - T::one())
+    };
+    if first {
+      first = false;
+    } else {
+      writer.write_all(b",")?;
+      l += 1;
+      match fold {
+        Some(n_chars) if l + 1 + s.len() > *n_chars => {
+          write!(writer, "\n    {}", line_prefix)?;
+          l = 4 + line_prefix.len();
+        }
+        _ => {
+          writer.write_all(b" ")?;
+          l += 1;
+        }
+      }
+    }
+    writer.write_all(s.as_bytes())?;
+    l += s.len();
+  }
+  write!(writer, "]\n{}}}", line_prefix)
+}
+
+/// Write a 2D-MOC following the Aladin JSON format, each 1D-MOC being written with
+/// [ranges_to_json_aladin] (i.e. using the range extension).
+///
+/// # Warning
+/// The output can be read by [rangemoc2d_from_json_aladin], but not by
+/// [cellmoc2d_from_json_aladin] nor by Aladin.
+pub fn rangemoc2d_to_json_aladin<T, Q, I, U, R, J, K, L, W>(
   moc2_it: L,
   fold: &Option<usize>,
-  mut writer: W
+  mut writer: W,
 ) -> std::io::Result<()>
-  where
-    T: Idx,
-    Q: MocQty<T>,
-    I: RangeMOCIterator<T, Qty=Q>,
-    U: Idx,
-    R: MocQty<U>,
-    J: RangeMOCIterator<U, Qty=R>,
-    K: RangeMOC2ElemIt<T, Q, U, R, It1=I, It2=J>,
-    L: RangeMOC2Iterator<T, Q, I, U, R, J, K>,
-    W: Write
+where
+  T: Idx,
+  Q: MocQty<T>,
+  I: RangeMOCIterator<T, Qty = Q>,
+  U: Idx,
+  R: MocQty<U>,
+  J: RangeMOCIterator<U, Qty = R>,
+  K: RangeMOC2ElemIt<T, Q, U, R, It1 = I, It2 = J>,
+  L: RangeMOC2Iterator<T, Q, I, U, R, J, K>,
+  W: Write,
 {
+  let d1 = moc2_it.depth_max_1();
+  let d2 = moc2_it.depth_max_2();
   writer.write_all(b"[\n")?;
   let mut is_first = true;
   for e in moc2_it {
     let (moc1_it, moc2_it) = e.range_mocs_it();
-    let (moc1_it, moc2_it) = (moc1_it.cells(), moc2_it.cells());
     if is_first {
       is_first = false;
       writer.write_all(b"{\n  \"")?;
@@ -158,17 +230,27 @@ where
     }
     writer.write_u8(Q::PREFIX as u8)?;
     writer.write_all(b"\": ")?;
-    to_json_aladin(moc1_it, &fold, "  ", &mut writer)?;
+    ranges_to_json_aladin(moc1_it, fold, "  ", &mut writer)?;
 
     writer.write_all(b",\n  \"")?;
     writer.write_u8(R::PREFIX as u8)?;
     writer.write_all(b"\": ")?;
-    to_json_aladin(moc2_it, &fold, "  ", &mut writer)?;
+    ranges_to_json_aladin(moc2_it, fold, "  ", &mut writer)?;
     writer.write_all(b"\n}")?;
   }
-  writer.write_all(b"\n]\n")?;
-  Ok(())
-}*/
+  if !is_first {
+    writer.write_all(b",\n")?;
+  }
+  write!(
+    &mut writer,
+    "{{ \"{}\": {{ \"{}\": [] }}, \"{}\": {{ \"{}\": [] }} }}",
+    Q::PREFIX,
+    d1,
+    R::PREFIX,
+    d2
+  )?;
+  writer.write_all(b"\n]\n")
+}
 
 /// Read a JSON following the Aladin JSON format.
 pub fn from_json_aladin<T, Q>(input: &str) -> Result<CellMOC<T, Q>, Box<dyn Error>>
@@ -214,18 +296,7 @@ where
           depth_max = depth_max.max(depth);
         }
       }
-      // Sort the cell list
-      cells.sort_by(|a, b| a.flat_cmp::<Q>(b));
-      // Check for non-overlapping cells
-      for (e1, e2) in cells.iter().zip(cells.iter().skip(1)) {
-        if e1.overlap::<Q>(e2) {
-          return Err(
-            String::from("The json MOC is not valid (contains overlapping elements").into(),
-          );
-        }
-      }
-
-      Ok(CellMOC::new(depth_max, MocCells::new(Cells::new(cells))))
+      cell_moc_from_unsorted_cells(depth_max, cells).map_err(|e| e.into())
     }
     _ => Err(
       format!(
@@ -237,6 +308,29 @@ where
   }
 }
 
+/// Sorts the given cells and build a `CellMOC` after having checked that the cells
+/// do not overlap.
+pub(crate) fn cell_moc_from_unsorted_cells<T, Q>(
+  depth_max: u8,
+  mut cells: Vec<Cell<T>>,
+) -> Result<CellMOC<T, Q>, String>
+where
+  T: Idx,
+  Q: MocQty<T>,
+{
+  // Sort the cell list
+  cells.sort_by(|a, b| a.flat_cmp::<Q>(b));
+  // Check for non-overlapping cells
+  for (e1, e2) in cells.iter().zip(cells.iter().skip(1)) {
+    if e1.overlap::<Q>(e2) {
+      return Err(String::from(
+        "The json MOC is not valid (contains overlapping elements",
+      ));
+    }
+  }
+  Ok(CellMOC::new(depth_max, MocCells::new(Cells::new(cells))))
+}
+
 /// Read a JSON following the Aladin JSON format.
 pub fn cellmoc2d_from_json_aladin<T, Q, U, R>(
   input: &str,
@@ -296,6 +390,147 @@ where
   Ok(CellMOC2::new(depth_max_l, depth_max_r, elems))
 }
 
+/// Read a JSON following the Aladin JSON format, possibly extended with ranges
+/// (see [ranges_to_json_aladin]), directly into a range MOC (i.e. without building the list of
+/// cells).
+pub fn range_moc_from_json_aladin<T, Q>(input: &str) -> Result<RangeMOC<T, Q>, Box<dyn Error>>
+where
+  T: Idx,
+  Q: MocQty<T>,
+{
+  let root: Value = serde_json::from_str(input)?;
+  let (depth_max, ranges) = ranges_from_json_aladin_internal::<T, Q>(&root)?;
+  Ok(RangeMOC::new(depth_max, ranges))
+}
+
+/// Returns the depth max and the ranges (at the quantity max depth) of the given JSON MOC,
+/// each element of a depth array being either a cell index or an array containing the first and
+/// the last (inclusive) cell indices of a range of cells.
+fn ranges_from_json_aladin_internal<T, Q>(
+  value: &Value,
+) -> Result<(u8, MocRanges<T, Q>), Box<dyn Error>>
+where
+  T: Idx,
+  Q: MocQty<T>,
+{
+  let map = match value {
+    Value::Object(map) => map,
+    _ => {
+      return Err(
+        format!(
+          "Wrong JSON root type. Expected: Object. Actual: {:?}",
+          &value
+        )
+        .into(),
+      )
+    }
+  };
+  let to_idx = |v: &Value| v.as_u64().and_then(num::cast::<u64, T>);
+  let mut ranges: Vec<Range<T>> = Vec::with_capacity(
+    (0..=Q::MAX_DEPTH)
+      .filter_map(|d| match map.get(&d.to_string()) {
+        Some(Array(vec)) => Some(vec.len()),
+        _ => None,
+      })
+      .sum(),
+  );
+  let mut depth_max = 0;
+  for depth in 0..=Q::MAX_DEPTH {
+    if let Some(Array(vec)) = map.get(&depth.to_string()) {
+      let n_cells = Q::n_cells(depth);
+      let shift = Q::shift_from_depth_max(depth) as u32;
+      for v in vec {
+        let (first, last) = match v {
+          Value::Array(bounds) if bounds.len() == 2 => (to_idx(&bounds[0]), to_idx(&bounds[1])),
+          _ => (to_idx(v), to_idx(v)),
+        };
+        match (first, last) {
+          (Some(first), Some(last)) if first <= last && last < n_cells => {
+            ranges.push(first.unsigned_shl(shift)..(last + T::one()).unsigned_shl(shift))
+          }
+          _ => {
+            return Err(
+              format!(
+                "Wrong JSON MOC element at depth {}. Expected: a cell index or a [first, last] range. Actual: {}",
+                depth, v
+              )
+              .into(),
+            )
+          }
+        }
+      }
+      depth_max = depth_max.max(depth);
+    }
+  }
+  ranges.sort_unstable_by_key(|r| r.start);
+  if ranges.windows(2).any(|w| w[0].end > w[1].start) {
+    return Err(String::from("The json MOC is not valid (contains overlapping elements)").into());
+  }
+  Ok((depth_max, MocRanges::new_from_sorted(ranges)))
+}
+
+/// Read a 2D-MOC following the Aladin JSON format, possibly extended with ranges
+/// (see [rangemoc2d_to_json_aladin]), directly into a range 2D-MOC (i.e. without building the
+/// list of cells).
+pub fn rangemoc2d_from_json_aladin<T, Q, U, R>(
+  input: &str,
+) -> Result<RangeMOC2<T, Q, U, R>, Box<dyn Error>>
+where
+  T: Idx,
+  Q: MocQty<T>,
+  U: Idx,
+  R: MocQty<U>,
+{
+  let root: Value = serde_json::from_str(input)?;
+  let entries = match &root {
+    Value::Array(entries) => entries,
+    _ => return Err(format!("Wrong JSON root type. Expected: Array. Actual: {:?}", &root).into()),
+  };
+  let mut depth_max_l = 0_u8;
+  let mut depth_max_r = 0_u8;
+  let mut elems: Vec<(MocRanges<T, Q>, MocRanges<U, R>)> = Vec::with_capacity(entries.len());
+  for entry in entries {
+    if let Value::Object(map) = entry {
+      let moc1 = map.get(&Q::PREFIX.to_string());
+      let moc2 = map.get(&R::PREFIX.to_string());
+      match (moc1, moc2) {
+        (Some(obj1), Some(obj2)) => {
+          let (d1, l) = ranges_from_json_aladin_internal::<T, Q>(obj1)?;
+          let (d2, r) = ranges_from_json_aladin_internal::<U, R>(obj2)?;
+          depth_max_l = depth_max_l.max(d1);
+          depth_max_r = depth_max_r.max(d2);
+          if !l.is_empty() && !r.is_empty() {
+            elems.push((l, r));
+          }
+        }
+        _ => {
+          return Err(
+            format!(
+              "Wrong JSON array object type. Expected: (Object, Object). Actual: ({:?}, {:?})",
+              &moc1, &moc2
+            )
+            .into(),
+          )
+        }
+      }
+    } else {
+      return Err(
+        format!(
+          "Wrong JSON array elem type. Expected: Object. Actual: {:?}",
+          &entry
+        )
+        .into(),
+      );
+    }
+  }
+  // Each 1D-MOC depth is set to the 2D-MOC depth
+  let elems = elems
+    .into_iter()
+    .map(|(l, r)| RangeMOC2Elem::new(RangeMOC::new(depth_max_l, l), RangeMOC::new(depth_max_r, r)))
+    .collect();
+  Ok(RangeMOC2::new(depth_max_l, depth_max_r, elems))
+}
+
 // json_stream :
 // {
 //   "qty": "",
@@ -309,7 +544,11 @@ mod tests {
 
   use crate::moc2d::{HasTwoMaxDepth, RangeMOC2Iterator};
   use crate::{
-    deser::json::{cellmoc2d_from_json_aladin, cellmoc2d_to_json_aladin, from_json_aladin},
+    deser::json::{
+      cellmoc2d_from_json_aladin, cellmoc2d_to_json_aladin, from_json_aladin,
+      range_moc_from_json_aladin, rangemoc2d_from_json_aladin, rangemoc2d_to_json_aladin,
+      ranges_to_json_aladin,
+    },
     elemset::range::{HpxRanges, MocRanges, TimeRanges},
     moc::{
       range::RangeMOC, CellMOCIntoIterator, CellMOCIterator, RangeMOCIntoIterator, RangeMOCIterator,
@@ -413,12 +652,82 @@ mod tests {
     let moc2 = RangeMOC2::new(61, 4, elems);
     //
     let mut sink = Vec::new();
-    // rangemoc2d_to_json_aladin(moc2.into_cell_moc2_iter(), &None, &mut sink);
-    cellmoc2d_to_json_aladin(moc2.into_cell_moc2_iter(), &None, &mut sink).unwrap();
+    cellmoc2d_to_json_aladin(moc2.clone().into_cell_moc2_iter(), &None, &mut sink).unwrap();
     let json = String::from_utf8_lossy(&sink);
     let _cellmoc2 = cellmoc2d_from_json_aladin::<u64, Time<u64>, u64, Hpx<u64>>(&json);
     // cellmoc2.into_cell_moc2_iter().
     println!("{}\n", &json);
+    // The range reader also reads the pure Aladin format
+    let actual = rangemoc2d_from_json_aladin::<u64, Time<u64>, u64, Hpx<u64>>(&json).unwrap();
+    assert_eq!(actual, moc2);
+
+    let mut sink = Vec::new();
+    rangemoc2d_to_json_aladin((&moc2).into_range_moc2_iter(), &None, &mut sink).unwrap();
+    let json = String::from_utf8_lossy(&sink);
+    let expected = r#"[
+{
+  "t": {
+    "61": [1, 3, 5]
+  },
+  "s": {
+    "4": [[4, 15]]
+  }
+},
+{
+  "t": {
+    "61": [50, 52]
+  },
+  "s": {
+    "4": [25]
+  }
+},
+{ "t": { "61": [] }, "s": { "4": [] } }
+]
+"#;
+    assert_eq!(json, expected);
+    let actual = rangemoc2d_from_json_aladin::<u64, Time<u64>, u64, Hpx<u64>>(&json).unwrap();
+    assert_eq!(actual, moc2);
+  }
+
+  #[test]
+  fn test_ranges_tofrom_json() {
+    let rm = RangeMOC::new(
+      29,
+      MocRanges::<u64, Hpx<u64>>::new_unchecked(vec![
+        0..5,
+        6..59,
+        78..6953,
+        12458..55587,
+        55787..65587,
+      ]),
+    );
+    let mut sink = Vec::new();
+    ranges_to_json_aladin((&rm).into_range_moc_iter(), &Some(30), "", &mut sink).unwrap();
+    let json = String::from_utf8_lossy(&sink);
+    let expected = r#"{
+  "29": [[0, 4], [6, 58],
+    [78, 6952], [12458, 55586],
+    [55787, 65586]]
+}"#;
+    assert_eq!(json, expected);
+    assert_eq!(
+      range_moc_from_json_aladin::<u64, Hpx<u64>>(&json).unwrap(),
+      rm
+    );
+    // Mixed cells and ranges, at various depths
+    let json = r#"{ "0": [1], "2": [[0, 1], 3], "3": [] }"#;
+    let moc = range_moc_from_json_aladin::<u64, Hpx<u64>>(json).unwrap();
+    assert_eq!(moc.depth_max(), 3);
+    let cells = from_json_aladin::<u64, Hpx<u64>>(r#"{ "0": [1], "2": [0, 1, 3], "3": [] }"#)
+      .unwrap()
+      .into_cell_moc_iter()
+      .ranges()
+      .into_range_moc();
+    assert_eq!(moc, cells);
+    // Errors
+    assert!(range_moc_from_json_aladin::<u64, Hpx<u64>>(r#"{ "2": [[3, 1]] }"#).is_err());
+    assert!(range_moc_from_json_aladin::<u64, Hpx<u64>>(r#"{ "0": [12] }"#).is_err());
+    assert!(range_moc_from_json_aladin::<u64, Hpx<u64>>(r#"{ "0": [0], "1": [2] }"#).is_err());
   }
 
   #[test]
@@ -8,9 +8,18 @@
 //! The module also contain experimental code for streaming compatible serialization/deserialization.
 //!
 
+#[cfg(feature = "arrow")]
+pub mod arrow;
 pub mod ascii;
+pub mod bin;
+pub mod ds9;
 pub mod fits;
+pub mod geojson;
 pub mod gz;
 pub mod img;
 pub mod json;
+pub mod packed;
+#[cfg(feature = "serde")]
+pub mod serde;
 pub mod stcs;
+pub mod votable;
@@ -0,0 +1,427 @@
+//! Compact, streamable, serialization of range MOCs.
+//!
+//! Each range is stored as two unsigned LEB128 varints:
+//! * the gap between the end of the previous range (or `0` for the first range) and the start
+//!   of the current range;
+//! * the length of the range.
+//!
+//! Both values are expressed in number of cells at the MOC depth max, so that the values are
+//! small even for `u64` indices.
+//! Since the ranges of a MOC are sorted and non-overlapping, gaps and lengths are always
+//! positive: there is no need for a zigzag encoding of the deltas.
+//! The list of ranges ends with a `(0, 0)` pair (a range can't have a zero length).
+//!
+//! # Format (version 1)
+//!
+//! | size | content                                                        |
+//! |------|----------------------------------------------------------------|
+//! |    4 | magic number: `MOCP`                                           |
+//! |    1 | version of the format: `1`                                     |
+//! |    1 | flags: bit 0 set if the ranges are compressed using *deflate*  |
+//! |    1 | prefix of the quantity: `s`, `t` or `f`                        |
+//! |    1 | depth max of the MOC                                           |
+//! |  var | ranges (possibly deflated), ended by `(0, 0)`                  |
+//!
+//! Encoding and decoding are made in streaming mode, without storing the full MOC in memory.
+
+use std::{
+  io::{self, BufRead, Read, Write},
+  marker::PhantomData,
+  ops::Range,
+};
+
+use flate2::{bufread::DeflateDecoder, write::DeflateEncoder, Compression};
+use log::error;
+use quick_error::quick_error;
+
+use crate::{
+  idx::Idx,
+  moc::{range::RangeMOC, HasMaxDepth, MOCProperties, NonOverlapping, RangeMOCIterator, ZSorted},
+  qty::MocQty,
+  ranges::Ranges,
+};
+
+/// Magic number starting each packed MOC.
+pub const MAGIC: &[u8; 4] = b"MOCP";
+/// Current version of the format.
+pub const VERSION: u8 = 1;
+
+const FLAG_DEFLATE: u8 = 0x01;
+
+quick_error! {
+  #[derive(Debug)]
+  pub enum PackedError {
+    /// IO error
+    Io(err: io::Error) {
+      from()
+      display("I/O error: {}", err)
+    }
+    WrongMagicNumber {
+      display("Wrong magic number, not a packed MOC")
+    }
+    UnsupportedVersion(version: u8) {
+      display("Unsupported packed MOC version: {}. Supported version: {}", version, VERSION)
+    }
+    WrongQty(expected: char, actual: char) {
+      display("Wrong MOC quantity. Expected: '{}'. Actual: '{}'", expected, actual)
+    }
+    WrongDepth(depth_max: u8, actual: u8) {
+      display("Wrong depth. Expected: <= {}. Actual: {}", depth_max, actual)
+    }
+    Truncated {
+      display("Truncated packed MOC: missing the ending (0, 0) pair")
+    }
+  }
+}
+
+/// Writes the given range MOC iterator in the packed format.
+/// # Params
+/// * `it`: the iterator on the MOC ranges
+/// * `deflate`: compress the ranges with *deflate*
+/// * `writer`: the writer in which the packed MOC is written
+pub fn to_packed<T, Q, I, W>(it: I, deflate: bool, mut writer: W) -> Result<(), PackedError>
+where
+  T: Idx,
+  Q: MocQty<T>,
+  I: RangeMOCIterator<T, Qty = Q>,
+  W: Write,
+{
+  let depth_max = it.depth_max();
+  let flags = if deflate { FLAG_DEFLATE } else { 0 };
+  writer.write_all(MAGIC)?;
+  writer.write_all(&[VERSION, flags, Q::PREFIX as u8, depth_max])?;
+  if deflate {
+    let mut encoder = DeflateEncoder::new(writer, Compression::default());
+    write_ranges::<T, Q, I, _>(it, depth_max, &mut encoder)?;
+    encoder.finish()?;
+  } else {
+    write_ranges::<T, Q, I, _>(it, depth_max, &mut writer)?;
+  }
+  Ok(())
+}
+
+fn write_ranges<T, Q, I, W>(it: I, depth_max: u8, mut writer: W) -> io::Result<()>
+where
+  T: Idx,
+  Q: MocQty<T>,
+  I: RangeMOCIterator<T, Qty = Q>,
+  W: Write,
+{
+  let shift = Q::shift_from_depth_max(depth_max) as u32;
+  let mut prev_end = T::zero();
+  for Range { start, end } in it {
+    write_varint(start.unsigned_shr(shift) - prev_end, &mut writer)?;
+    write_varint((end - start).unsigned_shr(shift), &mut writer)?;
+    prev_end = end.unsigned_shr(shift);
+  }
+  write_varint(T::zero(), &mut writer)?;
+  write_varint(T::zero(), &mut writer)
+}
+
+/// Reads the header of a packed MOC and returns an iterator decoding the ranges on the fly.
+/// # Params
+/// * `reader`: the reader containing the packed MOC
+pub fn from_packed<T, Q, R>(mut reader: R) -> Result<RangeMocIterFromPacked<T, Q, R>, PackedError>
+where
+  T: Idx,
+  Q: MocQty<T>,
+  R: BufRead,
+{
+  let mut header = [0_u8; 8];
+  reader.read_exact(&mut header)?;
+  if &header[0..4] != MAGIC {
+    return Err(PackedError::WrongMagicNumber);
+  }
+  let [version, flags, qty, depth_max] = [header[4], header[5], header[6], header[7]];
+  if version != VERSION {
+    return Err(PackedError::UnsupportedVersion(version));
+  }
+  if qty != Q::PREFIX as u8 {
+    return Err(PackedError::WrongQty(Q::PREFIX, qty as char));
+  }
+  if depth_max > Q::MAX_DEPTH {
+    return Err(PackedError::WrongDepth(Q::MAX_DEPTH, depth_max));
+  }
+  let reader = if flags & FLAG_DEFLATE != 0 {
+    PackedReader::Deflate(DeflateDecoder::new(reader))
+  } else {
+    PackedReader::Raw(reader)
+  };
+  Ok(RangeMocIterFromPacked {
+    depth_max,
+    shift: Q::shift_from_depth_max(depth_max) as u32,
+    prev_end: T::zero(),
+    reader,
+    done: false,
+    _qty: PhantomData,
+  })
+}
+
+/// The possibly deflated reader of a packed MOC.
+pub enum PackedReader<R: BufRead> {
+  Raw(R),
+  Deflate(DeflateDecoder<R>),
+}
+
+impl<R: BufRead> Read for PackedReader<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    match self {
+      PackedReader::Raw(r) => r.read(buf),
+      PackedReader::Deflate(r) => r.read(buf),
+    }
+  }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Iterator decoding, on the fly, the ranges of a packed MOC.
+///
+/// The iterator stops at the first decoding error (logged), so that a corrupted or truncated
+/// packed MOC leads to a partial MOC: use [RangeMocIterFromPacked::try_next] or
+/// [RangeMocIterFromPacked::try_into_range_moc] to get the decoding errors.
+pub struct RangeMocIterFromPacked<T: Idx, Q: MocQty<T>, R: BufRead> {
+  depth_max: u8,
+  shift: u32,
+  prev_end: T,
+  reader: PackedReader<R>,
+  done: bool,
+  _qty: PhantomData<Q>,
+}
+
+impl<T: Idx, Q: MocQty<T>, R: BufRead> RangeMocIterFromPacked<T, Q, R> {
+  /// Returns the next range, `None` once the ending `(0, 0)` pair has been read, or an error
+  /// if the packed ranges are corrupted or truncated.
+  pub fn try_next(&mut self) -> Result<Option<Range<T>>, PackedError> {
+    if self.done {
+      return Ok(None);
+    }
+    let res = self.next_range().map_err(|e| match e.kind() {
+      io::ErrorKind::UnexpectedEof => PackedError::Truncated,
+      _ => PackedError::Io(e),
+    });
+    if !matches!(res, Ok(Some(_))) {
+      self.done = true;
+    }
+    res
+  }
+
+  /// Decodes all the remaining ranges, failing if the packed ranges are corrupted or
+  /// truncated (e.g. if the ending `(0, 0)` pair is missing).
+  pub fn try_into_range_moc(mut self) -> Result<RangeMOC<T, Q>, PackedError> {
+    let mut ranges = Vec::new();
+    while let Some(range) = self.try_next()? {
+      ranges.push(range);
+    }
+    Ok(RangeMOC::new(
+      self.depth_max,
+      Ranges::new_unchecked(ranges).into(),
+    ))
+  }
+
+  fn next_range(&mut self) -> io::Result<Option<Range<T>>> {
+    let gap: T = read_varint(&mut self.reader)?;
+    let len: T = read_varint(&mut self.reader)?;
+    if len == T::zero() {
+      if gap == T::zero() {
+        Ok(None)
+      } else {
+        Err(invalid_data("Null range length"))
+      }
+    } else if gap == T::zero() && self.prev_end != T::zero() {
+      Err(invalid_data("Range consecutive with the previous range"))
+    } else {
+      let start = self
+        .prev_end
+        .checked_add(&gap)
+        .ok_or_else(|| invalid_data("Range start overflow"))?;
+      let end = start
+        .checked_add(&len)
+        .ok_or_else(|| invalid_data("Range end overflow"))?;
+      // At depth max, the number of cells is `n_cells_max >> shift`
+      if end > Q::n_cells(self.depth_max) {
+        return Err(invalid_data(
+          "Range out of the domain upper bound at the MOC depth",
+        ));
+      }
+      self.prev_end = end;
+      let range = start.unsigned_shl(self.shift)..end.unsigned_shl(self.shift);
+      if range.start >= range.end || range.end > Q::n_cells_max() {
+        return Err(invalid_data("Invalid range"));
+      }
+      Ok(Some(range))
+    }
+  }
+}
+
+impl<T: Idx, Q: MocQty<T>, R: BufRead> HasMaxDepth for RangeMocIterFromPacked<T, Q, R> {
+  fn depth_max(&self) -> u8 {
+    self.depth_max
+  }
+}
+impl<T: Idx, Q: MocQty<T>, R: BufRead> ZSorted for RangeMocIterFromPacked<T, Q, R> {}
+impl<T: Idx, Q: MocQty<T>, R: BufRead> NonOverlapping for RangeMocIterFromPacked<T, Q, R> {}
+impl<T: Idx, Q: MocQty<T>, R: BufRead> MOCProperties for RangeMocIterFromPacked<T, Q, R> {}
+impl<T: Idx, Q: MocQty<T>, R: BufRead> Iterator for RangeMocIterFromPacked<T, Q, R> {
+  type Item = Range<T>;
+  fn next(&mut self) -> Option<Self::Item> {
+    match self.try_next() {
+      Ok(range) => range,
+      Err(e) => {
+        // Early stop due to read error (see `try_next`).
+        error!("Error decoding packed MOC: {}", e);
+        None
+      }
+    }
+  }
+}
+impl<T: Idx, Q: MocQty<T>, R: BufRead> RangeMOCIterator<T> for RangeMocIterFromPacked<T, Q, R> {
+  type Qty = Q;
+
+  fn peek_last(&self) -> Option<&Range<T>> {
+    None
+  }
+}
+
+/// Writes the given value as an unsigned LEB128 varint.
+fn write_varint<T: Idx, W: Write>(mut val: T, writer: &mut W) -> io::Result<()> {
+  let mask: T = 0x7F_u8.into();
+  let mut buf = [0_u8; 19]; // 19 = ceil(128 / 7)
+  let mut i = 0;
+  while val > mask {
+    buf[i] = (val & mask).to_u8().unwrap() | 0x80;
+    val = val.unsigned_shr(7);
+    i += 1;
+  }
+  buf[i] = val.to_u8().unwrap();
+  writer.write_all(&buf[..=i])
+}
+
+/// Reads an unsigned LEB128 varint.
+fn read_varint<T: Idx, R: Read>(reader: &mut R) -> io::Result<T> {
+  let mut val = T::zero();
+  let mut shift = 0_u32;
+  let mut byte = [0_u8; 1];
+  loop {
+    reader.read_exact(&mut byte)?;
+    let bits: T = (byte[0] & 0x7F).into();
+    if shift >= T::N_BITS as u32 || bits.unsigned_shl(shift).unsigned_shr(shift) != bits {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "Varint overflow",
+      ));
+    }
+    val |= bits.unsigned_shl(shift);
+    if byte[0] & 0x80 == 0 {
+      return Ok(val);
+    }
+    shift += 7;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::{
+    moc::{
+      range::{CellSelection, RangeMOC},
+      RangeMOCIntoIterator,
+    },
+    qty::{Hpx, Time},
+  };
+
+  fn smoc() -> RangeMOC<u64, Hpx<u64>> {
+    RangeMOC::from_cone(0.5, 0.2, 0.3, 12, 2, CellSelection::All)
+  }
+
+  #[test]
+  fn test_varint() {
+    for val in [0_u64, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+      let mut buf = Vec::new();
+      write_varint(val, &mut buf).unwrap();
+      assert_eq!(read_varint::<u64, _>(&mut buf.as_slice()).unwrap(), val);
+    }
+    let mut buf = Vec::new();
+    write_varint(u128::MAX, &mut buf).unwrap();
+    assert_eq!(buf.len(), 19);
+    assert_eq!(
+      read_varint::<u128, _>(&mut buf.as_slice()).unwrap(),
+      u128::MAX
+    );
+    // Overflow
+    let mut buf = Vec::new();
+    write_varint(u32::MAX as u64 + 1, &mut buf).unwrap();
+    assert!(read_varint::<u32, _>(&mut buf.as_slice()).is_err());
+  }
+
+  #[test]
+  fn test_packed_smoc() {
+    let moc = smoc();
+    for deflate in [false, true] {
+      let mut buf = Vec::new();
+      to_packed((&moc).into_range_moc_iter(), deflate, &mut buf).unwrap();
+      // Much more compact than the 16 bytes per range of the FITS serialization
+      assert!(buf.len() < moc.len() * 4);
+      let it = from_packed::<u64, Hpx<u64>, _>(buf.as_slice()).unwrap();
+      assert_eq!(it.depth_max(), moc.depth_max());
+      let moc2: RangeMOC<u64, Hpx<u64>> = it.into_range_moc();
+      assert_eq!(moc2, moc);
+      // Wrong qty
+      assert!(matches!(
+        from_packed::<u64, Time<u64>, _>(buf.as_slice()),
+        Err(PackedError::WrongQty('t', 's'))
+      ));
+    }
+  }
+
+  #[test]
+  fn test_packed_u32_tmoc() {
+    let moc = RangeMOC::<u32, Time<u32>>::new(
+      10,
+      crate::ranges::Ranges::new_unchecked(vec![0..(1 << 20), (5 << 20)..(8 << 20)]).into(),
+    );
+    let mut buf = Vec::new();
+    to_packed((&moc).into_range_moc_iter(), false, &mut buf).unwrap();
+    // 8 bytes header + 4 one byte varints + (0, 0)
+    assert_eq!(buf.len(), 8 + 4 + 2);
+    let moc2: RangeMOC<u32, Time<u32>> = from_packed::<u32, Time<u32>, _>(buf.as_slice())
+      .unwrap()
+      .into_range_moc();
+    assert_eq!(moc2, moc);
+    assert_eq!(
+      from_packed::<u32, Time<u32>, _>(buf.as_slice())
+        .unwrap()
+        .try_into_range_moc()
+        .unwrap(),
+      moc
+    );
+    // Truncated, possibly just missing the (0, 0) ending pair
+    for len in [buf.len() - 3, buf.len() - 2] {
+      assert!(matches!(
+        from_packed::<u32, Time<u32>, _>(&buf[..len])
+          .unwrap()
+          .try_into_range_moc(),
+        Err(PackedError::Truncated)
+      ));
+    }
+    // Corrupted: overflowing, out of bounds and consecutive ranges
+    let header = &buf[..8];
+    for ranges in [vec![u32::MAX, 1], vec![1 << 20, 1], vec![0, 1, 0, 1]] {
+      let mut corrupted = header.to_vec();
+      for v in ranges {
+        write_varint(v, &mut corrupted).unwrap();
+      }
+      let mut it = from_packed::<u32, Time<u32>, _>(corrupted.as_slice()).unwrap();
+      let err = loop {
+        match it.next_range() {
+          Ok(Some(_)) => continue,
+          Ok(None) => panic!("Corrupted MOC not detected"),
+          Err(e) => break e,
+        }
+      };
+      assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+  }
+}
@@ -0,0 +1,558 @@
+//! Implementation of the serde `Serialize` and `Deserialize` traits for the core MOC types
+//! (`RangeMOC`, `CellMOC`, `RangeMOC2` and `Ranges`), available with the `serde` feature.
+//!
+//! The layout depends on the kind of format (see `Serializer::is_human_readable`):
+//! * for human readable formats (JSON, YAML, ...), MOCs follow the Aladin JSON layout, i.e.
+//!   a map of `"depth": [idx, ...]` (the `depth_max` key being always present) for 1D MOCs
+//!   and an array of `{"t": {...}, "s": {...}}` objects for 2D MOCs, the last element of the
+//!   array being used to store the depths max;
+//! * for binary formats (bincode, MessagePack, ...), MOCs are serialized as compact ranges:
+//!   the MOC depth max followed by the flat list of range bounds (`start1, end1, start2, ...`).
+//!
+//! Ranges are always checked when deserializing a MOC.
+
+use std::{fmt, marker::PhantomData, ops::Range};
+
+use ::serde::{
+  de::{self, MapAccess, SeqAccess, Visitor},
+  ser::{SerializeMap, SerializeSeq},
+  Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{
+  deser::{bin::check_ranges, json::cell_moc_from_unsorted_cells},
+  elem::cell::Cell,
+  elemset::{
+    cell::{Cells, MocCells},
+    range::MocRanges,
+  },
+  idx::Idx,
+  moc::{
+    cell::CellMOC, range::RangeMOC, CellMOCIntoIterator, CellMOCIterator, RangeMOCIntoIterator,
+    RangeMOCIterator,
+  },
+  moc2d::{
+    range::{RangeMOC2, RangeMOC2Elem},
+    HasTwoMaxDepth,
+  },
+  qty::MocQty,
+  ranges::Ranges,
+};
+
+/// Wrapper used to (de)serialize an index as a `u64` (or as a `u128` for 128 bits indices).
+struct SerdeIdx<T: Idx>(T);
+
+impl<T: Idx> Serialize for SerdeIdx<T> {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    if T::N_BYTES <= 8 {
+      serializer.serialize_u64(self.0.to_u64())
+    } else {
+      // Unwrap ok since all Idx fit in a u128
+      serializer.serialize_u128(self.0.to_u128().unwrap())
+    }
+  }
+}
+
+impl<'de, T: Idx> Deserialize<'de> for SerdeIdx<T> {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let val = if T::N_BYTES <= 8 {
+      let v = u64::deserialize(deserializer)?;
+      num::cast::<u64, T>(v).ok_or_else(|| de::Error::custom(format!("Index {} overflow", v)))?
+    } else {
+      let v = u128::deserialize(deserializer)?;
+      num::cast::<u128, T>(v).ok_or_else(|| de::Error::custom(format!("Index {} overflow", v)))?
+    };
+    Ok(SerdeIdx(val))
+  }
+}
+
+/// Serializes a slice of ranges as a flat sequence of indices.
+struct FlatRanges<'a, T: Idx>(&'a [Range<T>]);
+
+impl<'a, T: Idx> Serialize for FlatRanges<'a, T> {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut seq = serializer.serialize_seq(Some(self.0.len() << 1))?;
+    for range in self.0 {
+      seq.serialize_element(&SerdeIdx(range.start))?;
+      seq.serialize_element(&SerdeIdx(range.end))?;
+    }
+    seq.end()
+  }
+}
+
+/// Transforms a flat sequence of indices into a list of ranges.
+fn ranges_from_flat<T: Idx, E: de::Error>(flat: Vec<SerdeIdx<T>>) -> Result<Vec<Range<T>>, E> {
+  if flat.len() & 1 != 0 {
+    return Err(E::custom("Odd number of range bounds"));
+  }
+  Ok(
+    flat
+      .chunks_exact(2)
+      .map(|bounds| bounds[0].0..bounds[1].0)
+      .collect(),
+  )
+}
+
+/// Builds a `RangeMOC` from a flat sequence of indices, checking the ranges.
+fn range_moc_from_flat<T: Idx, Q: MocQty<T>, E: de::Error>(
+  depth_max: u8,
+  flat: Vec<SerdeIdx<T>>,
+) -> Result<RangeMOC<T, Q>, E> {
+  if depth_max > Q::MAX_DEPTH {
+    return Err(E::custom(format!(
+      "Depth {} larger than the max depth {}",
+      depth_max,
+      Q::MAX_DEPTH
+    )));
+  }
+  let ranges = ranges_from_flat(flat)?;
+  check_ranges::<T, Q>(&ranges, depth_max).map_err(E::custom)?;
+  Ok(RangeMOC::new(depth_max, MocRanges::new_unchecked(ranges)))
+}
+
+/// Serializes the cells of a MOC in the Aladin JSON layout, i.e. a map of
+/// `"depth": [idx, ...]` in which the `depth_max` key is always present.
+fn serialize_cells_by_depth<T, Q, I, S>(it: I, serializer: S) -> Result<S::Ok, S::Error>
+where
+  T: Idx,
+  Q: MocQty<T>,
+  I: CellMOCIterator<T, Qty = Q>,
+  S: Serializer,
+{
+  let depth_max = it.depth_max();
+  let mut idx_by_depth: Vec<Vec<SerdeIdx<T>>> = (0..=depth_max).map(|_| Vec::new()).collect();
+  for c in it {
+    idx_by_depth[c.depth as usize].push(SerdeIdx(c.idx));
+  }
+  let n_entries = idx_by_depth
+    .iter()
+    .enumerate()
+    .filter(|(d, v)| !v.is_empty() || *d == depth_max as usize)
+    .count();
+  let mut map = serializer.serialize_map(Some(n_entries))?;
+  for (depth, idx) in idx_by_depth.iter().enumerate() {
+    if !idx.is_empty() || depth == depth_max as usize {
+      map.serialize_entry(&depth.to_string(), idx)?;
+    }
+  }
+  map.end()
+}
+
+/// Visitor reading a map following the Aladin JSON layout.
+struct CellsByDepthVisitor<T: Idx, Q: MocQty<T>>(PhantomData<(T, Q)>);
+
+impl<'de, T: Idx, Q: MocQty<T>> Visitor<'de> for CellsByDepthVisitor<T, Q> {
+  type Value = CellMOC<T, Q>;
+
+  fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    formatter.write_str("a map of \"depth\": [idx, ...] entries")
+  }
+
+  fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+    let mut depth_max = 0_u8;
+    let mut cells: Vec<Cell<T>> = Vec::new();
+    while let Some(key) = map.next_key::<String>()? {
+      let depth = key
+        .parse::<u8>()
+        .ok()
+        .filter(|d| *d <= Q::MAX_DEPTH)
+        .ok_or_else(|| de::Error::custom(format!("Wrong depth: '{}'", key)))?;
+      let idx: Vec<SerdeIdx<T>> = map.next_value()?;
+      let n_cells_max = Q::n_cells_max() >> Q::shift_from_depth_max(depth) as usize;
+      for SerdeIdx(idx) in idx {
+        if idx >= n_cells_max {
+          return Err(de::Error::custom(format!(
+            "Index {} out of bounds at depth {}",
+            idx, depth
+          )));
+        }
+        cells.push(Cell::new(depth, idx));
+      }
+      depth_max = depth_max.max(depth);
+    }
+    cell_moc_from_unsorted_cells(depth_max, cells).map_err(de::Error::custom)
+  }
+}
+
+impl<T: Idx, Q: MocQty<T>> Serialize for RangeMOC<T, Q> {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+      serialize_cells_by_depth(self.into_range_moc_iter().cells(), serializer)
+    } else {
+      (self.depth_max(), FlatRanges(&self.moc_ranges().0 .0)).serialize(serializer)
+    }
+  }
+}
+
+impl<'de, T: Idx, Q: MocQty<T>> Deserialize<'de> for RangeMOC<T, Q> {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    if deserializer.is_human_readable() {
+      CellMOC::<T, Q>::deserialize(deserializer)
+        .map(|cell_moc| cell_moc.into_cell_moc_iter().ranges().into_range_moc())
+    } else {
+      let (depth_max, flat) = <(u8, Vec<SerdeIdx<T>>)>::deserialize(deserializer)?;
+      range_moc_from_flat(depth_max, flat)
+    }
+  }
+}
+
+impl<T: Idx, Q: MocQty<T>> Serialize for CellMOC<T, Q> {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+      serialize_cells_by_depth(self.into_cell_moc_iter(), serializer)
+    } else {
+      self
+        .into_cell_moc_iter()
+        .ranges()
+        .into_range_moc()
+        .serialize(serializer)
+    }
+  }
+}
+
+impl<'de, T: Idx, Q: MocQty<T>> Deserialize<'de> for CellMOC<T, Q> {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    if deserializer.is_human_readable() {
+      deserializer.deserialize_map(CellsByDepthVisitor(PhantomData))
+    } else {
+      let moc = RangeMOC::<T, Q>::deserialize(deserializer)?;
+      let depth_max = moc.depth_max();
+      let cells = moc.into_range_moc_iter().cells().collect();
+      Ok(CellMOC::new(depth_max, MocCells::new(Cells::new(cells))))
+    }
+  }
+}
+
+impl<T: Idx> Serialize for Ranges<T> {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+      serializer.collect_seq(
+        self
+          .0
+          .iter()
+          .map(|range| (SerdeIdx(range.start), SerdeIdx(range.end))),
+      )
+    } else {
+      FlatRanges(&self.0).serialize(serializer)
+    }
+  }
+}
+
+impl<'de, T: Idx> Deserialize<'de> for Ranges<T> {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let ranges: Vec<Range<T>> = if deserializer.is_human_readable() {
+      <Vec<(SerdeIdx<T>, SerdeIdx<T>)>>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(start, end)| start.0..end.0)
+        .collect()
+    } else {
+      ranges_from_flat(<Vec<SerdeIdx<T>>>::deserialize(deserializer)?)?
+    };
+    if let Some(range) = ranges.iter().find(|range| range.start >= range.end) {
+      return Err(de::Error::custom(format!("Empty range {:?}", range)));
+    }
+    Ok(Ranges::new_from(ranges))
+  }
+}
+
+/// Serializes an element of a 2D MOC as a `{"t": {...}, "s": {...}}` object.
+struct JsonElem2<'a, T: Idx, Q: MocQty<T>, U: Idx, R: MocQty<U>>(
+  &'a RangeMOC<T, Q>,
+  &'a RangeMOC<U, R>,
+);
+
+impl<'a, T: Idx, Q: MocQty<T>, U: Idx, R: MocQty<U>> Serialize for JsonElem2<'a, T, Q, U, R> {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(2))?;
+    map.serialize_entry(&Q::PREFIX.to_string(), self.0)?;
+    map.serialize_entry(&R::PREFIX.to_string(), self.1)?;
+    map.end()
+  }
+}
+
+/// Visitor reading a `{"t": {...}, "s": {...}}` object.
+struct JsonElem2Visitor<T: Idx, Q: MocQty<T>, U: Idx, R: MocQty<U>>(PhantomData<(T, Q, U, R)>);
+
+impl<'de, T: Idx, Q: MocQty<T>, U: Idx, R: MocQty<U>> Visitor<'de>
+  for JsonElem2Visitor<T, Q, U, R>
+{
+  type Value = (RangeMOC<T, Q>, RangeMOC<U, R>);
+
+  fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      formatter,
+      "an object with keys \"{}\" and \"{}\"",
+      Q::PREFIX,
+      R::PREFIX
+    )
+  }
+
+  fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+    let mut moc_l: Option<RangeMOC<T, Q>> = None;
+    let mut moc_r: Option<RangeMOC<U, R>> = None;
+    while let Some(key) = map.next_key::<String>()? {
+      if key == Q::PREFIX.to_string() && moc_l.is_none() {
+        moc_l = Some(map.next_value()?);
+      } else if key == R::PREFIX.to_string() && moc_r.is_none() {
+        moc_r = Some(map.next_value()?);
+      } else {
+        return Err(de::Error::custom(format!("Unexpected key: '{}'", key)));
+      }
+    }
+    match (moc_l, moc_r) {
+      (Some(moc_l), Some(moc_r)) => Ok((moc_l, moc_r)),
+      (None, _) => Err(de::Error::missing_field("first dimension MOC")),
+      (_, None) => Err(de::Error::missing_field("second dimension MOC")),
+    }
+  }
+}
+
+/// Deserializes a `{"t": {...}, "s": {...}}` object.
+struct JsonElem2Owned<T: Idx, Q: MocQty<T>, U: Idx, R: MocQty<U>>(RangeMOC<T, Q>, RangeMOC<U, R>);
+
+impl<'de, T: Idx, Q: MocQty<T>, U: Idx, R: MocQty<U>> Deserialize<'de>
+  for JsonElem2Owned<T, Q, U, R>
+{
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    deserializer
+      .deserialize_map(JsonElem2Visitor(PhantomData))
+      .map(|(moc_l, moc_r)| JsonElem2Owned(moc_l, moc_r))
+  }
+}
+
+/// Visitor reading a 2D MOC following the Aladin JSON layout.
+struct JsonMoc2Visitor<T: Idx, Q: MocQty<T>, U: Idx, R: MocQty<U>>(PhantomData<(T, Q, U, R)>);
+
+impl<'de, T: Idx, Q: MocQty<T>, U: Idx, R: MocQty<U>> Visitor<'de> for JsonMoc2Visitor<T, Q, U, R> {
+  type Value = RangeMOC2<T, Q, U, R>;
+
+  fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    formatter.write_str("an array of 2D-MOC elements")
+  }
+
+  fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+    let mut depth_max_l = 0_u8;
+    let mut depth_max_r = 0_u8;
+    let mut elems: Vec<RangeMOC2Elem<T, Q, U, R>> =
+      Vec::with_capacity(seq.size_hint().unwrap_or(0));
+    while let Some(JsonElem2Owned(moc_l, moc_r)) = seq.next_element()? {
+      depth_max_l = depth_max_l.max(moc_l.depth_max());
+      depth_max_r = depth_max_r.max(moc_r.depth_max());
+      if !moc_l.is_empty() && !moc_r.is_empty() {
+        elems.push(RangeMOC2Elem::new(moc_l, moc_r));
+      }
+    }
+    check_moc2_elems(&elems)?;
+    Ok(RangeMOC2::new(depth_max_l, depth_max_r, elems))
+  }
+}
+
+/// Checks that the first dimension ranges of the 2D-MOC elements are sorted and do not overlap
+/// from one element to the next.
+fn check_moc2_elems<T: Idx, Q: MocQty<T>, U: Idx, R: MocQty<U>, E: de::Error>(
+  elems: &[RangeMOC2Elem<T, Q, U, R>],
+) -> Result<(), E> {
+  let mut prev_end: Option<T> = None;
+  for elem in elems {
+    let ranges = &elem.moc_l().moc_ranges().0 .0;
+    if let (Some(prev_end), Some(first)) = (prev_end, ranges.first()) {
+      if prev_end > first.start {
+        return Err(de::Error::custom(format!(
+          "first dimension range {:?} not sorted or overlapping with previous element",
+          first
+        )));
+      }
+    }
+    if let Some(last) = ranges.last() {
+      prev_end = Some(last.end);
+    }
+  }
+  Ok(())
+}
+
+impl<T: Idx, Q: MocQty<T>, U: Idx, R: MocQty<U>> Serialize for RangeMOC2<T, Q, U, R> {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+      let mut seq = serializer.serialize_seq(Some(self.elems().len() + 1))?;
+      for elem in self.elems() {
+        seq.serialize_element(&JsonElem2(elem.moc_l(), elem.moc_r()))?;
+      }
+      // Last element used to store the depths max
+      let empty_l: RangeMOC<T, Q> = RangeMOC::new(self.depth_max_1(), Default::default());
+      let empty_r: RangeMOC<U, R> = RangeMOC::new(self.depth_max_2(), Default::default());
+      seq.serialize_element(&JsonElem2(&empty_l, &empty_r))?;
+      seq.end()
+    } else {
+      let elems: Vec<(FlatRanges<T>, FlatRanges<U>)> = self
+        .elems()
+        .iter()
+        .map(|elem| {
+          (
+            FlatRanges(&elem.moc_l().moc_ranges().0 .0),
+            FlatRanges(&elem.moc_r().moc_ranges().0 .0),
+          )
+        })
+        .collect();
+      (self.depth_max_1(), self.depth_max_2(), elems).serialize(serializer)
+    }
+  }
+}
+
+impl<'de, T: Idx, Q: MocQty<T>, U: Idx, R: MocQty<U>> Deserialize<'de> for RangeMOC2<T, Q, U, R> {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    if deserializer.is_human_readable() {
+      deserializer.deserialize_seq(JsonMoc2Visitor(PhantomData))
+    } else {
+      let (depth_max_l, depth_max_r, flat_elems) =
+        <(u8, u8, Vec<(Vec<SerdeIdx<T>>, Vec<SerdeIdx<U>>)>)>::deserialize(deserializer)?;
+      let elems = flat_elems
+        .into_iter()
+        .map(|(flat_l, flat_r)| {
+          Ok(RangeMOC2Elem::new(
+            range_moc_from_flat(depth_max_l, flat_l)?,
+            range_moc_from_flat(depth_max_r, flat_r)?,
+          ))
+        })
+        .collect::<Result<Vec<_>, D::Error>>()?;
+      check_moc2_elems(&elems)?;
+      Ok(RangeMOC2::new(depth_max_l, depth_max_r, elems))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::str::from_utf8;
+
+  use crate::{
+    deser::json::{from_json_aladin, to_json_aladin},
+    elemset::range::MocRanges,
+    moc::{
+      cell::CellMOC, range::RangeMOC, CellMOCIntoIterator, CellMOCIterator, RangeMOCIntoIterator,
+      RangeMOCIterator,
+    },
+    moc2d::{
+      range::{RangeMOC2, RangeMOC2Elem},
+      HasTwoMaxDepth,
+    },
+    qty::{Hpx, Time},
+    ranges::Ranges,
+  };
+
+  fn hpx_moc() -> RangeMOC<u64, Hpx<u64>> {
+    RangeMOC::new(
+      29,
+      MocRanges::<u64, Hpx<u64>>::new_unchecked(vec![
+        0..5,
+        6..59,
+        78..6953,
+        12458..55587,
+        55787..65587,
+      ]),
+    )
+  }
+
+  #[allow(clippy::single_range_in_vec_init)]
+  fn st_moc() -> RangeMOC2<u64, Time<u64>, u64, Hpx<u64>> {
+    RangeMOC2::new(
+      61,
+      29,
+      vec![
+        RangeMOC2Elem::new(
+          RangeMOC::new(61, MocRanges::new_unchecked(vec![0..1, 2..3])),
+          hpx_moc(),
+        ),
+        RangeMOC2Elem::new(
+          RangeMOC::new(61, MocRanges::new_unchecked(vec![10..20])),
+          RangeMOC::new(29, MocRanges::new_unchecked(vec![0..4])),
+        ),
+      ],
+    )
+  }
+
+  #[test]
+  fn test_serde_json_range_moc() {
+    let moc = hpx_moc();
+    let json = serde_json::to_string(&moc).unwrap();
+    // Same content as the Aladin JSON writer
+    let mut aladin = Vec::new();
+    to_json_aladin((&moc).into_range_moc_iter().cells(), &None, "", &mut aladin).unwrap();
+    let aladin: serde_json::Value = serde_json::from_slice(&aladin).unwrap();
+    assert_eq!(
+      serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+      aladin
+    );
+    // Round trip
+    let moc2: RangeMOC<u64, Hpx<u64>> = serde_json::from_str(&json).unwrap();
+    assert_eq!(moc, moc2);
+    // Cell MOC
+    let cell_moc: CellMOC<u64, Hpx<u64>> = serde_json::from_str(&json).unwrap();
+    let cell_moc_ref: CellMOC<u64, Hpx<u64>> = from_json_aladin(&json).unwrap();
+    assert_eq!(
+      serde_json::to_string(&cell_moc).unwrap(),
+      serde_json::to_string(&cell_moc_ref).unwrap()
+    );
+    // Overlapping cells
+    assert!(serde_json::from_str::<RangeMOC<u64, Hpx<u64>>>(r#"{"0": [1], "1": [4]}"#).is_err());
+    // Empty MOC
+    let empty: RangeMOC<u64, Hpx<u64>> = serde_json::from_str(r#"{"8": []}"#).unwrap();
+    assert!(empty.is_empty());
+    assert_eq!(empty.depth_max(), 8);
+  }
+
+  #[test]
+  fn test_serde_bincode_range_moc() {
+    let moc = hpx_moc();
+    let bytes = bincode::serialize(&moc).unwrap();
+    // depth (1 byte) + seq len (8 bytes) + 2 * n_ranges * 8 bytes
+    assert_eq!(bytes.len(), 1 + 8 + 2 * 5 * 8);
+    let moc2: RangeMOC<u64, Hpx<u64>> = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(moc, moc2);
+    let cell_moc: CellMOC<u64, Hpx<u64>> = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(cell_moc.into_cell_moc_iter().ranges().into_range_moc(), moc);
+    // Invalid (overlapping) ranges
+    let bytes = bincode::serialize(&(29_u8, vec![0_u64, 5, 4, 10])).unwrap();
+    assert!(bincode::deserialize::<RangeMOC<u64, Hpx<u64>>>(&bytes).is_err());
+  }
+
+  #[test]
+  fn test_serde_range_moc2() {
+    let moc = st_moc();
+    // JSON
+    let json = serde_json::to_string(&moc).unwrap();
+    let moc2: RangeMOC2<u64, Time<u64>, u64, Hpx<u64>> = serde_json::from_str(&json).unwrap();
+    assert_eq!(moc2.depth_max_1(), 61);
+    assert_eq!(moc2.depth_max_2(), 29);
+    assert!(moc.eq(&moc2));
+    // Bincode
+    let bytes = bincode::serialize(&moc).unwrap();
+    let moc2: RangeMOC2<u64, Time<u64>, u64, Hpx<u64>> = bincode::deserialize(&bytes).unwrap();
+    assert!(moc.eq(&moc2));
+    // Unsorted or overlapping first dimension elements
+    let json = r#"[{"t": {"61": [10]}, "s": {"0": [1]}}, {"t": {"61": [2]}, "s": {"0": [2]}}]"#;
+    assert!(serde_json::from_str::<RangeMOC2<u64, Time<u64>, u64, Hpx<u64>>>(json).is_err());
+    let json = r#"[{"t": {"61": [1, 2]}, "s": {"0": [1]}}, {"t": {"61": [2]}, "s": {"0": [2]}}]"#;
+    assert!(serde_json::from_str::<RangeMOC2<u64, Time<u64>, u64, Hpx<u64>>>(json).is_err());
+    let bytes = bincode::serialize(&(
+      61_u8,
+      29_u8,
+      vec![
+        (vec![10_u64, 20], vec![0_u64, 4]),
+        (vec![0_u64, 11], vec![0_u64, 4]),
+      ],
+    ))
+    .unwrap();
+    assert!(bincode::deserialize::<RangeMOC2<u64, Time<u64>, u64, Hpx<u64>>>(&bytes).is_err());
+  }
+
+  #[test]
+  fn test_serde_ranges() {
+    let ranges: Ranges<u32> = Ranges::new_unchecked(vec![0..5, 8..12]);
+    let json = serde_json::to_string(&ranges).unwrap();
+    assert_eq!(from_utf8(json.as_bytes()).unwrap(), "[[0,5],[8,12]]");
+    assert_eq!(serde_json::from_str::<Ranges<u32>>(&json).unwrap(), ranges);
+    let bytes = bincode::serialize(&ranges).unwrap();
+    assert_eq!(bincode::deserialize::<Ranges<u32>>(&bytes).unwrap(), ranges);
+    assert!(serde_json::from_str::<Ranges<u32>>("[[5,5]]").is_err());
+  }
+}
@@ -1,4 +1,6 @@
-//! MOC creation from an STC-S string.
+//! MOC creation from an STC-S string, and S-MOC/T-MOC serialization into an STC-S string.
+
+use std::{iter::once, ops::Range};
 
 use thiserror::Error;
 
@@ -17,33 +19,68 @@ use stc_s::{
     position::Position,
     positioninterval::PositionInterval,
   },
-  visitor::{impls::donothing::VoidVisitor, CompoundVisitor, SpaceVisitor, StcVisitResult},
+  spectral::{common::SpectralUnit, SpecInterval, SpecValue},
+  time::{
+    common::{DateTime, TimeScale},
+    TimeElem, TimeIntervalArgs, TimeSimple, TimeStartArgs, TimeStopArgs,
+  },
+  visitor::{
+    impls::donothing::VoidVisitor, CompoundVisitor, SpaceVisitor, SpectralVisitor, StcVisitResult,
+    TimeVisitor,
+  },
   Stc,
 };
 
 use healpix::nested::{
   bmoc::BMOC, box_coverage, cone_coverage_approx_custom, elliptical_cone_coverage_custom,
-  polygon_coverage, zone_coverage,
+  map::img::PosConversion, polygon_coverage, zone_coverage,
 };
 
-use crate::{moc::range::RangeMOC, qty::Hpx};
+use crate::{
+  deser::ascii::to_ascii_ivoa,
+  idx::Idx,
+  moc::{
+    range::{
+      polygons::{off_zero_meridian, MocPolygon},
+      RangeMOC,
+    },
+    CellMOCIterator, RangeMOCIntoIterator, RangeMOCIterator,
+  },
+  moc2d::range::{RangeMOC2, RangeMOC2Elem},
+  qty::{Frequency, Hpx, MocQty, Time},
+};
 
 const HALF_PI: f64 = 0.5 * std::f64::consts::PI;
 const PI: f64 = std::f64::consts::PI;
 const TWICE_PI: f64 = 2.0 * std::f64::consts::PI;
+/// Distance, in degrees, from a pole of the vertices replacing a polygon vertex located on a pole.
+const POLE_DIST_DEG: f64 = 1e-9;
+/// Number of microseconds in a day.
+const MICROSEC_PER_DAY: u64 = 86_400_000_000;
+/// Number of microseconds from JD=0 to MJD=0 (i.e. to JD=2400000.5).
+const MJD0_MICROSEC: u64 = 2_400_000 * MICROSEC_PER_DAY + MICROSEC_PER_DAY / 2;
+/// Number of microseconds from JD=0 to 1970-01-01T00:00:00 (i.e. to JD=2440587.5).
+const UNIX_EPOCH_MICROSEC: u64 = 2_440_587 * MICROSEC_PER_DAY + MICROSEC_PER_DAY / 2;
+/// Obliquity of the ecliptic at J2000 (IAU 2006), in radians.
+const OBLIQUITY_J2000: f64 = 84381.406 / 3600.0 * PI / 180.0;
+/// Frequency range, in Hz, supported by F-MOCs.
+const FREQ_MIN_HZ: f64 = 1e-18;
+const FREQ_MAX_HZ: f64 = 1e38;
+/// Speed of light, in m/s.
+const SPEED_OF_LIGHT: f64 = 299_792_458.0;
+/// Frequency, in Hz, of a photon of 1 eV (i.e. `e / h`).
+const EV_IN_HZ: f64 = 2.417_989_242_084_918e14;
 
 #[derive(Error, Debug)]
 pub enum Stc2MocError {
-  #[error("Frame other than ICRS not supported (yet). Found: {found:?}")]
+  #[error(
+    "Frame not supported. Expected: ICRS, FK5, J2000, GALACTIC or ECLIPTIC. Found: {found:?}"
+  )]
   FrameIsNotICRS { found: Frame },
-  #[error("Flavor other than Spher2 not supported (yet). Found: {found:?}")]
+  #[error("Flavor other than Spher2 (or UnitSpher for Convex) not supported. Found: {found:?}")]
   FlavorIsNotSpher2 { found: Flavor },
-  #[error("Units ther than 'deg' not (yet?!) supported. Found: {found:?}")]
+  #[error("Units other than 'deg', 'arcmin' or 'arcsec' not supported. Found: {found:?}")]
   UnitsNotSupported { found: Vec<SpaceUnit> },
-  #[error("Convex shape not (yet?!) supported.")]
-  ConvexNotSupported,
-  #[error("Simple position not supported.")]
-  SimplePositionNotSupported,
   #[error("Position interval not supported.")]
   PositionIntervalNotSupported,
   #[error("invalid header (expected {expected:?}, found {found:?})")]
@@ -52,6 +89,10 @@ pub enum Stc2MocError {
   WrongLongitude { value: f64 },
   #[error("Latitude value out of bounds. Expected: [-90, 90[. Actual: {value:?}")]
   WrongLatitude { value: f64 },
+  #[error("Wrong time '{value}': {msg}")]
+  WrongTime { value: String, msg: String },
+  #[error("Frequency value out of bounds. Expected: [1e-18, 1e38] Hz. Actual: {value:?} Hz")]
+  WrongFrequency { value: f64 },
   #[error("STC-S string parsing not complete. Remaining: {rem:?}")]
   ParseHasRemaining { rem: String },
   #[error("STC-S string parsing incomplete: {msg:?}")]
@@ -62,21 +103,171 @@ pub enum Stc2MocError {
   ParseError { msg: String },
   #[error("No space sub-phrase found in STC-S string")]
   NoSpaceFound,
+  #[error("No time, space or spectral sub-phrase found in STC-S string")]
+  NoTimeSpaceOrSpectralFound,
+  #[error("Time, space and spectral sub-phrases found: 3D MOCs not supported")]
+  TimeSpaceAndSpectralFound,
   #[error("Custom error: {msg:?}")]
   Custom { msg: String },
 }
 
+/// Supported STC-S frames, i.e. frames we are able to convert into ICRS.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StcFrame {
+  /// ICRS, or frames we consider equal to ICRS (FK5, J2000)
+  Icrs,
+  Galactic,
+  /// Ecliptic J2000
+  Ecliptic,
+}
+impl StcFrame {
+  fn from_frame(frame: Frame) -> Result<Self, Stc2MocError> {
+    match frame {
+      Frame::UnknownFrame => {
+        warn!("No frame or frame 'UnknownFrame' found in STC-S: ICRS frame is assumed.");
+        Ok(Self::Icrs)
+      }
+      Frame::ICRS | Frame::FK5 | Frame::J2000 => Ok(Self::Icrs),
+      Frame::Galactic | Frame::Galactic2 => Ok(Self::Galactic),
+      Frame::Ecliptic => Ok(Self::Ecliptic),
+      _ => Err(Stc2MocError::FrameIsNotICRS { found: frame }),
+    }
+  }
+
+  /// Converts the given coordinates, in radians, from this frame to ICRS.
+  fn lonlat_to_icrs(&self, lon: f64, lat: f64) -> (f64, f64) {
+    let (lon, lat) = match self {
+      Self::Icrs => return (lon, lat),
+      Self::Galactic => PosConversion::gal2eq()(lon, lat),
+      Self::Ecliptic => {
+        let (x, y, z) = self.xyz_to_icrs(lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin());
+        (y.atan2(x), z.clamp(-1.0, 1.0).asin())
+      }
+    };
+    let lon = lon.rem_euclid(TWICE_PI);
+    (if lon == TWICE_PI { 0.0 } else { lon }, lat)
+  }
+
+  /// Converts the given unit vector from this frame to ICRS.
+  fn xyz_to_icrs(&self, x: f64, y: f64, z: f64) -> (f64, f64, f64) {
+    match self {
+      Self::Icrs => (x, y, z),
+      Self::Galactic => {
+        let (lon, lat) = PosConversion::gal2eq()(y.atan2(x), z.clamp(-1.0, 1.0).asin());
+        (lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin())
+      }
+      Self::Ecliptic => {
+        let (sin_e, cos_e) = OBLIQUITY_J2000.sin_cos();
+        (x, y * cos_e - z * sin_e, y * sin_e + z * cos_e)
+      }
+    }
+  }
+
+  /// Converts the given position angle, in radians, of an object located at the given position
+  /// (in this frame, in radians) into an ICRS position angle in `[0, pi[`.
+  fn pa_to_icrs(&self, lon: f64, lat: f64, pa: f64) -> f64 {
+    if *self == Self::Icrs {
+      return pa;
+    }
+    // We compute the ICRS position angle of a point at a small distance in the given direction
+    const D: f64 = 1e-6;
+    let (sin_d, cos_d) = D.sin_cos();
+    let lat2 = (lat.sin() * cos_d + lat.cos() * sin_d * pa.cos()).asin();
+    let lon2 = lon + (pa.sin() * sin_d * lat.cos()).atan2(cos_d - lat.sin() * lat2.sin());
+    let (lon1, lat1) = self.lonlat_to_icrs(lon, lat);
+    let (lon2, lat2) = self.lonlat_to_icrs(lon2.rem_euclid(TWICE_PI), lat2);
+    let dlon = lon2 - lon1;
+    (dlon.sin() * lat2.cos())
+      .atan2(lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * dlon.cos())
+      .rem_euclid(PI)
+  }
+}
+
 #[derive(Debug, Clone)]
 struct Stc2Moc {
   depth: u8,
   delta_depth: u8,
+  frame: StcFrame,
+  flavor: Option<Flavor>,
+  /// Factor to convert the STC-S position and size values into degrees
+  unit_to_deg: f64,
 }
 impl Stc2Moc {
   fn new(depth: u8, delta_depth: Option<u8>) -> Self {
     Self {
       depth,
       delta_depth: delta_depth.unwrap_or(2),
+      frame: StcFrame::Icrs,
+      flavor: None,
+      unit_to_deg: 1.0,
+    }
+  }
+
+  /// Returns a copy of this visitor for the given STC-S frame, flavor and units.
+  fn configured(
+    &self,
+    frame: Frame,
+    flavor: Option<Flavor>,
+    units: Option<&Vec<SpaceUnit>>,
+  ) -> Result<Self, Stc2MocError> {
+    let frame = StcFrame::from_frame(frame)?;
+    // Check SPHER2 (or UNITSPHER for Convex) flavor
+    if let Some(flavor) = flavor {
+      if flavor != Flavor::Spher2 && flavor != Flavor::UnitSpher {
+        return Err(Stc2MocError::FlavorIsNotSpher2 { found: flavor });
+      }
+    }
+    // Check units: a same angular unit for all quantities
+    let unit_to_deg = match units.map(|units| units.as_slice()) {
+      None | Some([]) => 1.0,
+      Some([unit, others @ ..]) if others.iter().all(|other| other == unit) => match unit {
+        SpaceUnit::Deg => 1.0,
+        SpaceUnit::Arcmin => 1.0 / 60.0,
+        SpaceUnit::Arcsec => 1.0 / 3600.0,
+        _ => 0.0,
+      },
+      _ => 0.0,
+    };
+    if unit_to_deg == 0.0 {
+      return Err(Stc2MocError::UnitsNotSupported {
+        found: units.cloned().unwrap_or_default(),
+      });
+    }
+    Ok(Self {
+      depth: self.depth,
+      delta_depth: self.delta_depth,
+      frame,
+      flavor,
+      unit_to_deg,
+    })
+  }
+
+  /// Converts the given position, in the STC-S units, into a position in radians (still in the
+  /// STC-S frame).
+  fn lonlat_to_rad(&self, lon: f64, lat: f64) -> Result<(f64, f64), Stc2MocError> {
+    if let Some(flavor @ Flavor::UnitSpher) = self.flavor {
+      return Err(Stc2MocError::FlavorIsNotSpher2 { found: flavor });
     }
+    Ok((
+      lon_deg2rad(lon * self.unit_to_deg)?,
+      lat_deg2rad(lat * self.unit_to_deg)?,
+    ))
+  }
+
+  /// Returns the given shape center in radians, in the STC-S frame.
+  fn center_to_rad(&self, center: &[f64], shape: &str) -> Result<(f64, f64), Stc2MocError> {
+    let lon = center.first().ok_or_else(|| Stc2MocError::Custom {
+      msg: format!("Empty {} longitude", shape),
+    })?;
+    let lat = center.get(1).ok_or_else(|| Stc2MocError::Custom {
+      msg: format!("Empty {} latitude", shape),
+    })?;
+    self.lonlat_to_rad(*lon, *lat)
+  }
+
+  /// Converts a size value, in the STC-S units, into degrees.
+  fn size_to_deg(&self, size: f64) -> f64 {
+    size * self.unit_to_deg
   }
 }
 impl CompoundVisitor for Stc2Moc {
@@ -88,24 +279,17 @@ impl CompoundVisitor for Stc2Moc {
   }
 
   fn visit_circle(&mut self, circle: &CircleParams) -> Result<Self::Value, Self::Error> {
-    // Get params
-    let lon_deg = circle
-      .center()
-      .first()
-      .ok_or_else(|| Stc2MocError::Custom {
-        msg: String::from("Empty circle longitude"),
-      })?;
-    let lat_deg = circle.center().get(1).ok_or_else(|| Stc2MocError::Custom {
-      msg: String::from("Empty circle latitude"),
-    })?;
-    let radius_deg = circle.radius();
-    // Convert params
-    let lon = lon_deg2rad(*lon_deg)?;
-    let lat = lat_deg2rad(*lat_deg)?;
+    // Get and convert params
+    let (lon, lat) = self.center_to_rad(circle.center(), "circle")?;
+    let (lon, lat) = self.frame.lonlat_to_icrs(lon, lat);
+    let radius_deg = self.size_to_deg(circle.radius());
     let r = radius_deg.to_radians();
     if r <= 0.0 || PI <= r {
       Err(Stc2MocError::Custom {
-        msg: format!("Radius out of bounds. Expected: ]0, 180[. Actual: {}.", r),
+        msg: format!(
+          "Radius out of bounds. Expected: ]0, 180[. Actual: {}.",
+          radius_deg
+        ),
       })
     } else {
       Ok(cone_coverage_approx_custom(
@@ -120,24 +304,11 @@ impl CompoundVisitor for Stc2Moc {
 
   fn visit_ellipse(&mut self, ellipse: &EllipseParams) -> Result<Self::Value, Self::Error> {
     // Get params
-    let lon_deg = ellipse
-      .center()
-      .first()
-      .ok_or_else(|| Stc2MocError::Custom {
-        msg: String::from("Empty ellipse longitude"),
-      })?;
-    let lat_deg = ellipse
-      .center()
-      .get(1)
-      .ok_or_else(|| Stc2MocError::Custom {
-        msg: String::from("Empty ellipse latitude"),
-      })?;
-    let a_deg = ellipse.radius_a();
-    let b_deg = ellipse.radius_b();
+    let (lon, lat) = self.center_to_rad(ellipse.center(), "ellipse")?;
+    let a_deg = self.size_to_deg(ellipse.radius_a());
+    let b_deg = self.size_to_deg(ellipse.radius_b());
     let pa_deg = ellipse.pos_angle();
     // Convert params
-    let lon = lon_deg2rad(*lon_deg)?;
-    let lat = lat_deg2rad(*lat_deg)?;
     let a = a_deg.to_radians();
     let b = b_deg.to_radians();
     let pa = pa_deg.to_radians();
@@ -163,6 +334,8 @@ impl CompoundVisitor for Stc2Moc {
         ),
       })
     } else {
+      let pa = self.frame.pa_to_icrs(lon, lat, pa);
+      let (lon, lat) = self.frame.lonlat_to_icrs(lon, lat);
       Ok(elliptical_cone_coverage_custom(
         self.depth,
         self.delta_depth,
@@ -176,33 +349,24 @@ impl CompoundVisitor for Stc2Moc {
   }
 
   fn visit_box(&mut self, skybox: &BoxParams) -> Result<Self::Value, Self::Error> {
-    // Get params
-    let lon_deg = skybox
-      .center()
-      .first()
-      .ok_or_else(|| Stc2MocError::Custom {
-        msg: String::from("Empty ellipse longitude"),
-      })?;
-    let lat_deg = skybox.center().get(1).ok_or_else(|| Stc2MocError::Custom {
-      msg: String::from("Empty ellipse latitude"),
+    // Get params: the box sizes are the full widths along the longitude and the latitude
+    let (lon, lat) = self.center_to_rad(skybox.center(), "box")?;
+    let lon_size = skybox.bsize().first().ok_or_else(|| Stc2MocError::Custom {
+      msg: String::from("Empty bsize on longitude"),
     })?;
-    let mut a_deg = skybox.bsize().first().ok_or_else(|| Stc2MocError::Custom {
+    let lat_size = skybox.bsize().get(1).ok_or_else(|| Stc2MocError::Custom {
       msg: String::from("Empty bsize on latitude"),
     })?;
-    let mut b_deg = skybox.bsize().first().ok_or_else(|| Stc2MocError::Custom {
-      msg: String::from("Empty bsize on longitude"),
-    })?;
-    let mut pa_deg = skybox.bsize().first().copied().unwrap_or(90.0);
+    let mut a_deg = 0.5 * self.size_to_deg(*lon_size);
+    let mut b_deg = 0.5 * self.size_to_deg(*lat_size);
+    let mut pa_deg: f64 = 90.0;
     if a_deg < b_deg {
       std::mem::swap(&mut b_deg, &mut a_deg);
-      pa_deg = 90.0 - pa_deg;
+      pa_deg = 0.0;
     }
     // Convert params
-    let lon = lon_deg2rad(*lon_deg)?;
-    let lat = lat_deg2rad(*lat_deg)?;
     let a = a_deg.to_radians();
     let b = b_deg.to_radians();
-    let pa = pa_deg.to_radians();
     if a <= 0.0 || HALF_PI <= a {
       Err(Stc2MocError::Custom {
         msg: format!(
@@ -210,21 +374,16 @@ impl CompoundVisitor for Stc2Moc {
           a_deg
         ),
       })
-    } else if b <= 0.0 || a <= b {
+    } else if b <= 0.0 {
       Err(Stc2MocError::Custom {
         msg: format!(
-          "Box semi-minor axis out of bounds. Expected: ]0, {}[. Actual: {}.",
+          "Box semi-minor axis out of bounds. Expected: ]0, {}]. Actual: {}.",
           a_deg, b_deg
         ),
       })
-    } else if !(0.0..PI).contains(&pa) {
-      Err(Stc2MocError::Custom {
-        msg: format!(
-          "Position angle out of bounds. Expected: [0, 180[. Actual: {}.",
-          pa_deg
-        ),
-      })
     } else {
+      let pa = self.frame.pa_to_icrs(lon, lat, pa_deg.to_radians());
+      let (lon, lat) = self.frame.lonlat_to_icrs(lon, lat);
       Ok(box_coverage(self.depth, lon, lat, a, b, pa))
     }
   }
@@ -236,16 +395,52 @@ impl CompoundVisitor for Stc2Moc {
       .step_by(2)
       .zip(vertices_deg.iter().skip(1).step_by(2))
       .map(|(lon_deg, lat_deg)| {
-        let lon = lon_deg2rad(*lon_deg)?;
-        let lat = lat_deg2rad(*lat_deg)?;
-        Ok((lon, lat))
+        let (lon, lat) = self.lonlat_to_rad(*lon_deg, *lat_deg)?;
+        let (lon, lat) = self.frame.lonlat_to_icrs(lon, lat);
+        Ok((off_zero_meridian(lon), lat))
       })
       .collect::<Result<Vec<(f64, f64)>, Stc2MocError>>()?;
     Ok(polygon_coverage(self.depth, vertices.as_slice(), true))
   }
 
-  fn visit_convex(&mut self, _convex: &ConvexParams) -> Result<Self::Value, Self::Error> {
-    Err(Stc2MocError::ConvexNotSupported)
+  fn visit_convex(&mut self, convex: &ConvexParams) -> Result<Self::Value, Self::Error> {
+    if let Some(flavor @ Flavor::Spher2) = self.flavor {
+      return Err(Stc2MocError::FlavorIsNotSpher2 { found: flavor });
+    }
+    let hspace = convex.hspace();
+    let xyzc_it = hspace.chunks_exact(4);
+    if !xyzc_it.remainder().is_empty() {
+      return Err(Stc2MocError::Custom {
+        msg: format!(
+          "Wrong number of Convex parameters. Expected: a multiple of 4 (x y z c). Actual: {}",
+          hspace.len()
+        ),
+      });
+    }
+    // Each half-space `{ p | p . (x, y, z) >= c }` is a cone of radius `acos(c / |(x, y, z)|)`
+    let mut bmoc = BMOC::new_allsky(self.depth);
+    for xyzc in xyzc_it {
+      let norm = (xyzc[0] * xyzc[0] + xyzc[1] * xyzc[1] + xyzc[2] * xyzc[2]).sqrt();
+      if norm == 0.0 {
+        return Err(Stc2MocError::Custom {
+          msg: String::from("Null Convex half-space vector"),
+        });
+      }
+      let c = xyzc[3] / norm;
+      if c >= 1.0 {
+        return Ok(BMOC::new_empty(self.depth));
+      } else if c > -1.0 {
+        let (x, y, z) = self
+          .frame
+          .xyz_to_icrs(xyzc[0] / norm, xyzc[1] / norm, xyzc[2] / norm);
+        let lon = y.atan2(x).rem_euclid(TWICE_PI);
+        let lat = z.clamp(-1.0, 1.0).asin();
+        let lon = if lon == TWICE_PI { 0.0 } else { lon };
+        let cone = cone_coverage_approx_custom(self.depth, self.delta_depth, lon, lat, c.acos());
+        bmoc = bmoc.and(&cone);
+      }
+    }
+    Ok(bmoc)
   }
 
   fn visit_not(&mut self, bmoc: Self::Value) -> Result<Self::Value, Self::Error> {
@@ -298,53 +493,46 @@ impl SpaceVisitor for Stc2Moc {
     fill_frame_refpos_flavor: &FillFrameRefposFlavor,
     from_pos_to_velocity: &FromPosToVelocity,
   ) -> Result<Self, Self::Error> {
-    // Check ICRS frame
-    let frame = fill_frame_refpos_flavor.frame();
-    if frame == Frame::UnknownFrame {
-      warn!("No frame or frame 'UnknownFrame' found in STC-S: ICRS frame is assumed.")
-    } else if frame != Frame::ICRS {
-      return Err(Stc2MocError::FrameIsNotICRS { found: frame });
-    }
-    // Check SPHER2 flavor
-    let flavor = fill_frame_refpos_flavor.flavor();
-    if let Some(flavor) = flavor {
-      if flavor != Flavor::Spher2 {
-        return Err(Stc2MocError::FlavorIsNotSpher2 { found: flavor });
-      }
-    }
-    // Check units
-    let opt_units = from_pos_to_velocity.unit().cloned();
-    if let Some(units) = opt_units {
-      for unit in units.iter().cloned() {
-        if unit != SpaceUnit::Deg {
-          return Err(Stc2MocError::UnitsNotSupported { found: units });
-        }
-      }
-    }
-    Ok(self.clone())
+    self.configured(
+      fill_frame_refpos_flavor.frame(),
+      fill_frame_refpos_flavor.flavor(),
+      from_pos_to_velocity.unit(),
+    )
   }
 
-  fn visit_position_simple(self, _: &Position) -> Result<Self::Value, Self::Error> {
-    Err(Stc2MocError::SimplePositionNotSupported)
+  fn visit_position_simple(self, position: &Position) -> Result<Self::Value, Self::Error> {
+    let visitor = self.configured(
+      position.pre.frame(),
+      position.pre.flavor(),
+      position.post.unit(),
+    )?;
+    let (lon, lat) = visitor.center_to_rad(&position.pos, "position")?;
+    let (lon, lat) = visitor.frame.lonlat_to_icrs(lon, lat);
+    Ok(RangeMOC::from_coos(
+      visitor.depth,
+      once((lon, lat)),
+      Some(1),
+    ))
   }
 
   fn visit_position_interval(
     self,
     interval: &PositionInterval,
   ) -> Result<Self::Value, Self::Error> {
-    // We use compound visitor only to check interval parameters
-    self.new_compound_visitor(&interval.pre, &interval.post)?;
+    let visitor = self.new_compound_visitor(&interval.pre, &interval.post)?;
+    // A zone in a frame is not a zone in an other frame
+    if visitor.frame != StcFrame::Icrs {
+      return Err(Stc2MocError::FrameIsNotICRS {
+        found: interval.pre.frame(),
+      });
+    }
     let depth = self.depth;
     let corners = interval
       .lo_hi_limits
       .iter()
       .step_by(2)
       .zip(interval.lo_hi_limits.iter().skip(1).step_by(2))
-      .map(|(lon_deg, lat_deg)| {
-        let lon = lon_deg2rad(*lon_deg)?;
-        let lat = lat_deg2rad(*lat_deg)?;
-        Ok((lon, lat))
-      })
+      .map(|(lon, lat)| visitor.lonlat_to_rad(*lon, *lat))
       .collect::<Result<Vec<(f64, f64)>, Stc2MocError>>()?;
     let mut corners_it = corners
       .iter()
@@ -382,8 +570,8 @@ impl SpaceVisitor for Stc2Moc {
     Ok(bmoc.into())
   }
 
-  fn visit_convex(self, _: BMOC) -> Result<Self::Value, Self::Error> {
-    unreachable!() // because an error is raised before calling this
+  fn visit_convex(self, bmoc: BMOC) -> Result<Self::Value, Self::Error> {
+    Ok(bmoc.into())
   }
 
   fn visit_not(self, bmoc: BMOC) -> Result<Self::Value, Self::Error> {
@@ -403,6 +591,219 @@ impl SpaceVisitor for Stc2Moc {
   }
 }
 
+/// Visitor building a T-MOC from a STC-S time sub-phrase.
+struct Stc2TMoc {
+  depth: u8,
+}
+impl Stc2TMoc {
+  fn check_timescale(timescale: Option<TimeScale>) {
+    match timescale {
+      None | Some(TimeScale::TCB) | Some(TimeScale::Nil) => (),
+      Some(timescale) => warn!(
+        "Time scale {:?} found in STC-S: no conversion to TCB is performed.",
+        timescale
+      ),
+    }
+  }
+
+  fn to_tmoc<I: Iterator<Item = Range<u64>>>(&self, ranges: I) -> RangeMOC<u64, Time<u64>> {
+    RangeMOC::from_microsec_ranges_since_jd0(self.depth, ranges, None)
+  }
+}
+impl TimeVisitor for Stc2TMoc {
+  type Value = RangeMOC<u64, Time<u64>>;
+  type Error = Stc2MocError;
+
+  fn visit_time_simple(self, time: &TimeSimple) -> Result<Self::Value, Self::Error> {
+    Self::check_timescale(time.timescale());
+    let datetime = time.time().ok_or_else(|| Stc2MocError::Custom {
+      msg: String::from("Empty time value"),
+    })?;
+    let t = datetime_to_microsec(datetime)?;
+    Ok(RangeMOC::from_microsec_since_jd0(
+      self.depth,
+      once(t),
+      Some(1),
+    ))
+  }
+
+  fn visit_time_interval(
+    self,
+    time_interval: &TimeElem<TimeIntervalArgs>,
+  ) -> Result<Self::Value, Self::Error> {
+    Self::check_timescale(time_interval.timescale());
+    let ranges = time_interval
+      .start()
+      .iter()
+      .zip(time_interval.stop())
+      .map(|(start, stop)| {
+        let (start, stop) = (datetime_to_microsec(start)?, datetime_to_microsec(stop)?);
+        if start < stop {
+          Ok(start..stop)
+        } else {
+          Err(Stc2MocError::Custom {
+            msg: format!(
+              "Empty time interval: start >= stop ({} >= {} microsec)",
+              start, stop
+            ),
+          })
+        }
+      })
+      .collect::<Result<Vec<_>, Stc2MocError>>()?;
+    Ok(self.to_tmoc(ranges.into_iter()))
+  }
+
+  fn visit_time_start(
+    self,
+    time_start: &TimeElem<TimeStartArgs>,
+  ) -> Result<Self::Value, Self::Error> {
+    Self::check_timescale(time_start.timescale());
+    let start = datetime_to_microsec(time_start.start())?;
+    Ok(self.to_tmoc(once(start..Time::<u64>::n_cells_max())))
+  }
+
+  fn visit_time_stop(self, time_stop: &TimeElem<TimeStopArgs>) -> Result<Self::Value, Self::Error> {
+    Self::check_timescale(time_stop.timescale());
+    let stop = datetime_to_microsec(time_stop.stop())?;
+    Ok(self.to_tmoc(once(0..stop)))
+  }
+}
+
+/// Visitor building a F-MOC from a STC-S spectral sub-phrase.
+struct Stc2FMoc {
+  depth: u8,
+}
+impl SpectralVisitor for Stc2FMoc {
+  type Value = RangeMOC<u64, Frequency<u64>>;
+  type Error = Stc2MocError;
+
+  fn visit_spectral_simple(self, value: &SpecValue) -> Result<Self::Value, Self::Error> {
+    let unit = value.unit_or_default();
+    let value = value.value().ok_or_else(|| Stc2MocError::Custom {
+      msg: String::from("Empty spectral value"),
+    })?;
+    let hz = check_freq(spectral_to_hz(value, unit))?;
+    Ok(RangeMOC::from_freq_in_hz(self.depth, once(hz), Some(1)))
+  }
+
+  fn visit_spectral_interval(self, interval: &SpecInterval) -> Result<Self::Value, Self::Error> {
+    let unit = interval.unit_or_default();
+    let ranges = interval
+      .lo_limits()
+      .iter()
+      .zip(interval.hi_limits())
+      .map(|(lo, hi)| {
+        // Wavelengths are in reverse order with respect to frequencies
+        let (f1, f2) = (spectral_to_hz(*lo, unit), spectral_to_hz(*hi, unit));
+        Ok(check_freq(f1.min(f2))?..check_freq(f1.max(f2))?)
+      })
+      .collect::<Result<Vec<_>, Stc2MocError>>()?;
+    Ok(RangeMOC::from_freq_ranges_in_hz(
+      self.depth,
+      ranges.into_iter(),
+      None,
+    ))
+  }
+}
+
+/// Converts the given spectral value, in the given unit, into a frequency in Hz.
+fn spectral_to_hz(value: f64, unit: SpectralUnit) -> f64 {
+  match unit {
+    SpectralUnit::Hz => value,
+    SpectralUnit::MHz => value * 1e6,
+    SpectralUnit::GHz => value * 1e9,
+    SpectralUnit::EV => value * EV_IN_HZ,
+    SpectralUnit::KeV => value * 1e3 * EV_IN_HZ,
+    SpectralUnit::MeV => value * 1e6 * EV_IN_HZ,
+    SpectralUnit::M => SPEED_OF_LIGHT / value,
+    SpectralUnit::Mm => SPEED_OF_LIGHT / (value * 1e-3),
+    SpectralUnit::Um => SPEED_OF_LIGHT / (value * 1e-6),
+    SpectralUnit::Nm => SPEED_OF_LIGHT / (value * 1e-9),
+    SpectralUnit::Angstrom => SPEED_OF_LIGHT / (value * 1e-10),
+  }
+}
+
+fn check_freq(hz: f64) -> Result<f64, Stc2MocError> {
+  if (FREQ_MIN_HZ..=FREQ_MAX_HZ).contains(&hz) {
+    Ok(hz)
+  } else {
+    Err(Stc2MocError::WrongFrequency { value: hz })
+  }
+}
+
+/// Converts the given STC-S date into a number of microseconds since JD=0 (no time scale
+/// conversion, no leap second).
+fn datetime_to_microsec(datetime: &DateTime) -> Result<u64, Stc2MocError> {
+  let value = datetime.to_string();
+  let res = match datetime {
+    DateTime::JD(day) => decimal_day_to_microsec(&day.to_string(), 0),
+    DateTime::MJD(day) => decimal_day_to_microsec(&day.to_string(), MJD0_MICROSEC),
+    DateTime::Iso(iso) => iso_to_microsec(&iso.to_string()),
+  };
+  res.map_err(|msg| Stc2MocError::WrongTime { value, msg })
+}
+
+/// Exact conversion of a (possibly signed) decimal number of days into microseconds, adding the
+/// given offset.
+fn decimal_day_to_microsec(day: &str, offset: u64) -> Result<u64, String> {
+  let (is_neg, day) = match day.strip_prefix('-') {
+    Some(day) => (true, day),
+    None => (false, day.trim_start_matches('+')),
+  };
+  let (int, frac) = day.split_once('.').unwrap_or((day, ""));
+  let int: i128 = if int.is_empty() {
+    0
+  } else {
+    int.parse().map_err(|e| format!("{}", e))?
+  };
+  // Fractional part, rounded to the microsecond
+  let frac = &frac[..frac.len().min(18)];
+  let frac_microsec: i128 = if frac.is_empty() {
+    0
+  } else {
+    let pow = 10_i128.pow(frac.len() as u32);
+    let frac: i128 = frac.parse().map_err(|e| format!("{}", e))?;
+    (frac * MICROSEC_PER_DAY as i128 + (pow >> 1)) / pow
+  };
+  let microsec = int * MICROSEC_PER_DAY as i128 + frac_microsec;
+  let microsec = offset as i128 + if is_neg { -microsec } else { microsec };
+  u64::try_from(microsec).map_err(|_| String::from("time before JD=0 not supported"))
+}
+
+/// Conversion of a `YYYY-MM-DD[Thh:mm:ss[.s]Z]` date in the proleptic Gregorian calendar into
+/// microseconds since JD=0.
+fn iso_to_microsec(iso: &str) -> Result<u64, String> {
+  let parse = |s: &str| s.parse::<i64>().map_err(|e| format!("{}", e));
+  let (ymd, hms) = iso
+    .trim_end_matches('Z')
+    .split_once('T')
+    .unwrap_or((iso, ""));
+  let (y, m, d) = match ymd.split('-').collect::<Vec<&str>>().as_slice() {
+    [y, m, d] => (parse(y)?, parse(m)?, parse(d)?),
+    _ => return Err(String::from("wrong date format")),
+  };
+  let microsec_in_day = if hms.is_empty() {
+    0
+  } else {
+    match hms.split(':').collect::<Vec<&str>>().as_slice() {
+      [h, m, s] => {
+        let sec_microsec = decimal_day_to_microsec(s, 0)? as i128 / 86_400;
+        ((parse(h)? * 60 + parse(m)?) * 60_000_000) as i128 + sec_microsec
+      }
+      _ => return Err(String::from("wrong time format")),
+    }
+  };
+  // Number of days since 1970-01-01 (H. Hinnant's `days_from_civil` algorithm)
+  let y = if m <= 2 { y - 1 } else { y };
+  let era = y.div_euclid(400);
+  let yoe = y - era * 400;
+  let doy = (153 * (m + if m > 2 { -3 } else { 9 }) + 2) / 5 + d - 1;
+  let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+  let days = (era * 146_097 + doe - 719_468) as i128;
+  let microsec = UNIX_EPOCH_MICROSEC as i128 + days * MICROSEC_PER_DAY as i128 + microsec_in_day;
+  u64::try_from(microsec).map_err(|_| String::from("time before JD=0 not supported"))
+}
+
 fn lon_deg2rad(lon_deg: f64) -> Result<f64, Stc2MocError> {
   let mut lon = lon_deg.to_radians();
   if lon == TWICE_PI {
@@ -424,21 +825,48 @@ fn lat_deg2rad(lat_deg: f64) -> Result<f64, Stc2MocError> {
   }
 }
 
+fn parse_stcs(stcs_ascii: &str) -> Result<Stc, Stc2MocError> {
+  match Stc::parse::<VerboseError<&str>>(stcs_ascii.trim()) {
+    Ok((rem, stcs)) => {
+      if !rem.is_empty() {
+        return Err(Stc2MocError::ParseHasRemaining {
+          rem: rem.to_string(),
+        });
+      }
+      Ok(stcs)
+    }
+    Err(err) => Err(match err {
+      Err::Incomplete(_) => Stc2MocError::ParseIncomplete {
+        msg: String::from("Incomplete parsing."),
+      },
+      Err::Error(e) => Stc2MocError::ParseIncomplete {
+        msg: convert_error(stcs_ascii, e),
+      },
+      Err::Failure(e) => Stc2MocError::ParseIncomplete {
+        msg: convert_error(stcs_ascii, e),
+      },
+    }),
+  }
+}
+
 /// Create new S-MOC from the given STC-S string.
 ///
 /// # WARNING
 /// * `DIFFERENCE` is interpreted as a symmetrical difference (it is a `MINUS` in the STC standard)
 /// * `Polygon` do not follow the STC-S standard: here self-intersecting polygons are supported
-/// * No implicit conversion: the STC-S will be rejected if
-///     + the frame is different from `ICRS`
-///     + the flavor is different from `Spher2`
-///     + the units are different from `degrees`
-/// * Time, Spectral and Redshift sub-phrases are ignored
+/// * Supported frames are `ICRS` (`FK5` and `J2000` being considered as `ICRS`), `GALACTIC` and
+///   `ECLIPTIC` (J2000), the two last being rotated into `ICRS`; a `PositionInterval` must be in
+///   `ICRS`
+/// * Supported units are `deg`, `arcmin` and `arcsec` (the STC-S parser does not support `rad`)
+/// * The flavor must be `Spher2`, except for `Convex` whose half-spaces (`x y z c` tuples) are
+///   in the `UnitSpher` flavor
+/// * A `Position` leads to the single cell of depth `depth` containing the position
+/// * Time, Spectral and Redshift sub-phrases are ignored (see [stcs2mocs])
 ///
 /// # Params
 /// * `depth`: MOC maximum depth in `[0, 29]`
 /// * `delta_depth` the difference between the MOC depth and the depth at which the computations
-///   are made (should remain quite small).  
+///   are made (should remain quite small).
 /// * `ascii_stcs`: lthe STC-S string
 ///
 /// # Output
@@ -448,43 +876,292 @@ pub fn stcs2moc(
   delta_depth: Option<u8>,
   stcs_ascii: &str,
 ) -> Result<RangeMOC<u64, Hpx<u64>>, Stc2MocError> {
-  match Stc::parse::<VerboseError<&str>>(stcs_ascii.trim()) {
-    Ok((rem, stcs)) => {
-      if !rem.is_empty() {
-        return Err(Stc2MocError::ParseHasRemaining {
-          rem: rem.to_string(),
-        });
+  let stcs = parse_stcs(stcs_ascii)?;
+  let stc2moc_visitor = Stc2Moc::new(depth, delta_depth);
+  let StcVisitResult { space, .. } =
+    stcs.accept(VoidVisitor, stc2moc_visitor, VoidVisitor, VoidVisitor);
+  match space {
+    None => Err(Stc2MocError::NoSpaceFound),
+    Some(space_res) => space_res,
+  }
+}
+
+/// MOC built from an STC-S string, its type depending on the STC-S sub-phrases.
+#[derive(Debug)]
+pub enum StcMoc {
+  /// S-MOC, from a space sub-phrase only
+  Space(RangeMOC<u64, Hpx<u64>>),
+  /// T-MOC, from a time sub-phrase only
+  Time(RangeMOC<u64, Time<u64>>),
+  /// F-MOC, from a spectral sub-phrase only
+  Frequency(RangeMOC<u64, Frequency<u64>>),
+  /// ST-MOC, from time and space sub-phrases
+  TimeSpace(RangeMOC2<u64, Time<u64>, u64, Hpx<u64>>),
+  /// SF-MOC, from spectral and space sub-phrases
+  FrequencySpace(RangeMOC2<u64, Frequency<u64>, u64, Hpx<u64>>),
+  /// TF-MOC, from time and spectral sub-phrases
+  TimeFrequency(RangeMOC2<u64, Time<u64>, u64, Frequency<u64>>),
+}
+
+/// Create a new S-MOC, T-MOC, F-MOC, ST-MOC, SF-MOC or TF-MOC from the given STC-S string,
+/// according to the time, space and spectral sub-phrases it contains.
+///
+/// # WARNING
+/// * See [stcs2moc] for the space sub-phrase
+/// * Times are considered to be in `TCB` (no time scale conversion is performed), ISO dates
+///   in the proleptic Gregorian calendar, without leap seconds
+/// * Wavelengths and energies are converted into frequencies
+/// * Redshift sub-phrases are ignored
+///
+/// # Params
+/// * `sdepth`: S-MOC maximum depth in `[0, 29]`
+/// * `delta_depth` the difference between the S-MOC depth and the depth at which the
+///   computations are made (should remain quite small).
+/// * `tdepth`: T-MOC maximum depth in `[0, 61]`
+/// * `fdepth`: F-MOC maximum depth in `[0, 59]`
+/// * `ascii_stcs`: the STC-S string
+pub fn stcs2mocs(
+  sdepth: u8,
+  delta_depth: Option<u8>,
+  tdepth: u8,
+  fdepth: u8,
+  stcs_ascii: &str,
+) -> Result<StcMoc, Stc2MocError> {
+  let stcs = parse_stcs(stcs_ascii)?;
+  let StcVisitResult {
+    time,
+    space,
+    spectral,
+    ..
+  } = stcs.accept(
+    Stc2TMoc { depth: tdepth },
+    Stc2Moc::new(sdepth, delta_depth),
+    Stc2FMoc { depth: fdepth },
+    VoidVisitor,
+  );
+  let (time, space, spectral) = (time.transpose()?, space.transpose()?, spectral.transpose()?);
+  Ok(match (time, space, spectral) {
+    (None, None, None) => return Err(Stc2MocError::NoTimeSpaceOrSpectralFound),
+    (Some(_), Some(_), Some(_)) => return Err(Stc2MocError::TimeSpaceAndSpectralFound),
+    (None, Some(smoc), None) => StcMoc::Space(smoc),
+    (Some(tmoc), None, None) => StcMoc::Time(tmoc),
+    (None, None, Some(fmoc)) => StcMoc::Frequency(fmoc),
+    (Some(tmoc), Some(smoc), None) => StcMoc::TimeSpace(to_moc2(tmoc, smoc)),
+    (None, Some(smoc), Some(fmoc)) => StcMoc::FrequencySpace(to_moc2(fmoc, smoc)),
+    (Some(tmoc), None, Some(fmoc)) => StcMoc::TimeFrequency(to_moc2(tmoc, fmoc)),
+  })
+}
+
+/// Builds the 2D-MOC made of the cartesian product of the two given MOCs.
+fn to_moc2<Q: MocQty<u64>, R: MocQty<u64>>(
+  moc_l: RangeMOC<u64, Q>,
+  moc_r: RangeMOC<u64, R>,
+) -> RangeMOC2<u64, Q, u64, R> {
+  let (depth_l, depth_r) = (moc_l.depth_max(), moc_r.depth_max());
+  let elems = if moc_l.is_empty() || moc_r.is_empty() {
+    vec![]
+  } else {
+    vec![RangeMOC2Elem::new(moc_l, moc_r)]
+  };
+  RangeMOC2::new(depth_l, depth_r, elems)
+}
+
+#[derive(Error, Debug)]
+pub enum Moc2StcError {
+  #[error("An empty T-MOC can't be expressed as an STC-S TimeInterval.")]
+  EmptyTimeMoc,
+}
+
+/// Serialize the given S-MOC into an STC-S string made of the union of its polygons (see
+/// [RangeMOC::to_polygons]), a polygon having holes being written as a `Difference` between
+/// its exterior and its holes, e.g.:
+/// ```text
+/// Union ICRS ( Polygon 10 10 11 10 11 11 Difference ( Polygon 20 20 ... Polygon 21 21 ... ) )
+/// ```
+///
+/// # Remarks
+/// * A polygon going around a pole is written without the pole, and possibly inside a `Not`
+///   so that its interior is the expected one when reading it with [stcs2moc]
+/// * To follow the MOC contours, the number of vertices is large: you may want to degrade the MOC
+///   first
+pub fn moc2stcs<T: Idx>(moc: &RangeMOC<T, Hpx<T>>) -> String {
+  let full_sky = MocPolygon::full_sky();
+  let elems: Vec<(&str, String)> = moc
+    .to_polygons()
+    .iter()
+    .map(|polygon| {
+      let exterior = if polygon.exterior == full_sky.exterior {
+        ("AllSky", String::new())
+      } else {
+        ring_to_stcs(&polygon.exterior)
+      };
+      match polygon.holes.as_slice() {
+        [] => exterior,
+        [hole] => {
+          let hole = ring_to_stcs(hole);
+          (
+            "Difference",
+            format!(" ( {}{} {}{} )", exterior.0, exterior.1, hole.0, hole.1),
+          )
+        }
+        holes => (
+          "Difference",
+          format!(
+            " ( {}{} Union ( {} ) )",
+            exterior.0,
+            exterior.1,
+            holes
+              .iter()
+              .map(|hole| {
+                let (name, params) = ring_to_stcs(hole);
+                format!("{}{}", name, params)
+              })
+              .collect::<Vec<String>>()
+              .join(" ")
+          ),
+        ),
       }
-      let stc2moc_visitor = Stc2Moc::new(depth, delta_depth);
-      let StcVisitResult { space, .. } =
-        stcs.accept(VoidVisitor, stc2moc_visitor, VoidVisitor, VoidVisitor);
-      match space {
-        None => Err(Stc2MocError::NoSpaceFound),
-        Some(space_res) => space_res,
+    })
+    .collect();
+  match elems.as_slice() {
+    [] => String::from("Not ICRS ( AllSky )"),
+    [(name, params)] => format!("{} ICRS{}", name, params),
+    _ => format!(
+      "Union ICRS ( {} )",
+      elems
+        .iter()
+        .map(|(name, params)| format!("{}{}", name, params))
+        .collect::<Vec<String>>()
+        .join(" ")
+    ),
+  }
+}
+
+/// Returns the STC-S region name (`Polygon` or `Not`) and parameters (starting with a space)
+/// of the region delimited by the given polygon ring (vertices in radians, with continuous
+/// longitudes, the region being on the left of the ring, see [MocPolygon]).
+fn ring_to_stcs(ring: &[(f64, f64)]) -> (&'static str, String) {
+  let is_pole = |lat: f64| HALF_PI - lat.abs() < 1e-12;
+  // Latitude of the pole the ring goes around, if any
+  let mut enclosed_pole_lat: Option<f64> = None;
+  let mut vertices_deg: Vec<(f64, f64)> = Vec::with_capacity(ring.len() + 4);
+  let mut i = 0;
+  while i < ring.len() {
+    let (lon, lat) = ring[i];
+    if is_pole(lat) {
+      let (lon_from, lon_to) = match ring.get(i + 1) {
+        Some((next_lon, next_lat)) if is_pole(*next_lat) && next_lat * lat > 0.0 => {
+          i += 1;
+          (lon.to_degrees(), next_lon.to_degrees())
+        }
+        _ => (lon.to_degrees(), lon.to_degrees()),
+      };
+      if (lon_to - lon_from).abs() >= 360.0 - 1e-6 {
+        // The ring goes around the pole: the pole vertices are removed
+        enclosed_pole_lat = Some(lat);
+      } else {
+        // The ring passes by the pole: the pole vertex is replaced by a path close to the pole,
+        // with steps lower than 180 degrees not to pass by the pole
+        let lat_deg = lat.signum() * (90.0 - POLE_DIST_DEG);
+        let n_steps = ((lon_to - lon_from).abs() / 45.0).ceil().max(1.0) as usize;
+        let step = (lon_to - lon_from) / n_steps as f64;
+        vertices_deg.extend((0..=n_steps).map(|k| (lon_from + k as f64 * step, lat_deg)));
       }
+    } else {
+      vertices_deg.push((lon.to_degrees(), lat.to_degrees()));
     }
-    Err(err) => Err(match err {
-      Err::Incomplete(_) => Stc2MocError::ParseIncomplete {
-        msg: String::from("Incomplete parsing."),
-      },
-      Err::Error(e) => Stc2MocError::ParseIncomplete {
-        msg: convert_error(stcs_ascii, e),
-      },
-      Err::Failure(e) => Stc2MocError::ParseIncomplete {
-        msg: convert_error(stcs_ascii, e),
-      },
-    }),
+    i += 1;
+  }
+  vertices_deg.dedup();
+  let params: String = vertices_deg
+    .iter()
+    .map(|(lon, lat)| format!(" {} {}", lon.rem_euclid(360.0), lat))
+    .collect();
+  // For a ring going around a pole, the polygon read by `stcs2moc` contains the south pole if
+  // the gravity center of its vertices is in the south hemisphere
+  let is_complement = enclosed_pole_lat.is_some_and(|pole_lat| {
+    let gravity_center_z: f64 = vertices_deg
+      .iter()
+      .map(|(_, lat)| lat.to_radians().sin())
+      .sum();
+    (gravity_center_z < 0.0) != (pole_lat < 0.0)
+  });
+  if is_complement {
+    ("Not", format!(" ( Polygon{} )", params))
+  } else {
+    ("Polygon", params)
+  }
+}
+
+/// Serialize the given S-MOC into an STC-S `MOC` expression, i.e. the `MOC` keyword followed
+/// by the frame and the IVOA ASCII serialization of the MOC, e.g.:
+/// ```text
+/// MOC ICRS 3/1-5 4/28 29
+/// ```
+pub fn moc2stcs_moc<T: Idx>(moc: &RangeMOC<T, Hpx<T>>) -> String {
+  let mut ascii = Vec::new();
+  // Writing in a Vec<u8> can't fail
+  to_ascii_ivoa(
+    moc.into_range_moc_iter().cells().cellranges(),
+    &None,
+    false,
+    &mut ascii,
+  )
+  .unwrap();
+  format!("MOC ICRS {}", String::from_utf8_lossy(&ascii).trim())
+}
+
+/// Serialize the given T-MOC into an STC-S `TimeInterval` made of the union of the MOC time
+/// ranges, the times being expressed in Julian Days in the `TCB` timescale at the `BARYCENTER`,
+/// e.g.:
+/// ```text
+/// TimeInterval TCB BARYCENTER JD 2451545 JD 2451545.5 JD 2451546 JD 2451546.5
+/// ```
+pub fn tmoc2stcs<T: Idx>(moc: &RangeMOC<T, Time<T>>) -> Result<String, Moc2StcError> {
+  if moc.is_empty() {
+    return Err(Moc2StcError::EmptyTimeMoc);
+  }
+  let mut stcs = String::from("TimeInterval TCB BARYCENTER");
+  for range in moc.into_range_moc_iter() {
+    stcs.push_str(" JD ");
+    stcs.push_str(&microsec_to_jd_str(range.start.to_u64()));
+    stcs.push_str(" JD ");
+    stcs.push_str(&microsec_to_jd_str(range.end.to_u64()));
+  }
+  Ok(stcs)
+}
+
+/// Exact (up to 1e-12 day) decimal representation of the Julian Day corresponding to the given
+/// number of microseconds since JD=0.
+fn microsec_to_jd_str(microsec: u64) -> String {
+  const SCALE: u128 = 1_000_000_000_000;
+  let day_e12 =
+    (microsec as u128 * SCALE + (MICROSEC_PER_DAY as u128 >> 1)) / MICROSEC_PER_DAY as u128;
+  let frac = format!("{:012}", day_e12 % SCALE);
+  let frac = frac.trim_end_matches('0');
+  if frac.is_empty() {
+    format!("{}", day_e12 / SCALE)
+  } else {
+    format!("{}.{}", day_e12 / SCALE, frac)
   }
 }
 
 #[cfg(test)]
 mod tests {
   use crate::{
-    moc::range::{CellSelection, RangeMOC},
-    qty::Hpx,
+    deser::ascii::from_ascii_ivoa,
+    elemset::range::MocRanges,
+    moc::{
+      range::{CellSelection, RangeMOC},
+      CellOrCellRangeMOCIntoIterator, CellOrCellRangeMOCIterator, RangeMOCIterator,
+    },
+    qty::{Frequency, Hpx, MocQty, Time},
   };
 
-  use super::stcs2moc;
+  use super::{
+    moc2stcs, moc2stcs_moc, stcs2moc, stcs2mocs, tmoc2stcs, PosConversion, StcMoc, HALF_PI,
+    OBLIQUITY_J2000, SPEED_OF_LIGHT,
+  };
 
   #[test]
   fn test_from_stcs_circle() {
@@ -615,4 +1292,218 @@ Intersection ICRS (
     .unwrap();
     assert_eq!(moc.len(), 1383);
   }
+
+  #[test]
+  fn test_from_stcs_frames_and_units() {
+    let cone = |lon: f64, lat: f64| {
+      RangeMOC::<u64, Hpx<u64>>::from_cone(
+        lon,
+        lat,
+        0.4_f64.to_radians(),
+        10,
+        2,
+        CellSelection::All,
+      )
+    };
+    // Units
+    let moc = stcs2moc(10, Some(2), "Circle ICRS 8856 4194 24 unit arcmin").unwrap();
+    assert_eq!(moc, cone(147.6_f64.to_radians(), 69.9_f64.to_radians()));
+    let moc = stcs2moc(10, Some(2), "Circle ICRS 531360 251640 1440 unit arcsec").unwrap();
+    assert_eq!(moc, cone(147.6_f64.to_radians(), 69.9_f64.to_radians()));
+    // Galactic
+    let moc = stcs2moc(10, Some(2), "Circle GALACTIC 120 30 0.4").unwrap();
+    let (lon, lat) = PosConversion::gal2eq()(120_f64.to_radians(), 30_f64.to_radians());
+    assert_eq!(moc, cone(lon, lat));
+    // Ecliptic: the ecliptic north pole is at RA = 270 deg, Dec = 90 deg - obliquity
+    let moc = stcs2moc(10, Some(2), "Circle ECLIPTIC 0 90 0.4").unwrap();
+    let expected = cone(1.5 * std::f64::consts::PI, HALF_PI - OBLIQUITY_J2000);
+    assert!(moc.and(&expected).coverage_percentage() > 0.99 * expected.coverage_percentage());
+    assert!(moc.minus(&expected).coverage_percentage() < 0.01 * expected.coverage_percentage());
+    // Box, whose sizes are full widths
+    let moc = stcs2moc(10, Some(2), "Box GALACTIC 120 30 2 1").unwrap();
+    assert!(moc.is_in(lon, lat) && !moc.is_empty());
+    // Non-supported frame and units
+    assert!(stcs2moc(10, Some(2), "Circle FK4 10 10 1").is_err());
+    assert!(stcs2moc(10, Some(2), "Circle ICRS 10 10 1 unit pc").is_err());
+    assert!(stcs2moc(10, Some(2), "PositionInterval GALACTIC 170 -20 190 10").is_err());
+  }
+
+  #[test]
+  fn test_from_stcs_convex_and_position() {
+    // Intersection of 2 cones of radius 60 deg centred on (0, 0) and (90, 0)
+    let moc = stcs2moc(6, Some(2), "Convex ICRS 1 0 0 0.5 0 2 0 1").unwrap();
+    let r = 60_f64.to_radians();
+    let expected =
+      RangeMOC::<u64, Hpx<u64>>::from_cone(0.0, 0.0, r, 6, 2, CellSelection::All).and(&RangeMOC::<
+        u64,
+        Hpx<u64>,
+      >::from_cone(
+        HALF_PI,
+        0.0,
+        r,
+        6,
+        2,
+        CellSelection::All,
+      ));
+    assert_eq!(moc, expected);
+    assert!(stcs2moc(6, Some(2), "Convex ICRS 1 0 0 1.5")
+      .unwrap()
+      .is_empty());
+    assert!(stcs2moc(6, Some(2), "Convex ICRS 1 0 0 0.5 0 1").is_err());
+
+    let moc = stcs2moc(10, None, "Position ICRS 10 20").unwrap();
+    let expected = RangeMOC::<u64, Hpx<u64>>::from_coos(
+      10,
+      std::iter::once((10_f64.to_radians(), 20_f64.to_radians())),
+      None,
+    );
+    assert_eq!(moc, expected);
+  }
+
+  #[test]
+  fn test_from_stcs_time_and_spectral() {
+    let depth = Time::<u64>::MAX_DEPTH;
+    let expected = "TimeInterval TCB BARYCENTER JD 2451545 JD 2451546.25";
+    for stcs in [
+      "TimeInterval TCB JD 2451545 JD 2451546.25",
+      "TimeInterval TCB MJD 51544.5 MJD 51545.75",
+      "TimeInterval TCB 2000-01-01T12:00:00Z 2000-01-02T18:00:00",
+    ] {
+      match stcs2mocs(10, None, depth, 10, stcs).unwrap() {
+        StcMoc::Time(tmoc) => assert_eq!(tmoc2stcs(&tmoc).unwrap(), expected),
+        moc => panic!("T-MOC expected. Actual: {:?}", moc),
+      }
+    }
+    match stcs2mocs(10, None, depth, 10, "StartTime TCB JD 2451545").unwrap() {
+      StcMoc::Time(tmoc) => assert_eq!(tmoc.moc_ranges().0 .0.len(), 1),
+      moc => panic!("T-MOC expected. Actual: {:?}", moc),
+    }
+
+    // Wavelength are converted into frequencies
+    let fdepth = 20;
+    let expected = RangeMOC::<u64, Frequency<u64>>::from_freq_ranges_in_hz(
+      fdepth,
+      std::iter::once(SPEED_OF_LIGHT / 600e-9..SPEED_OF_LIGHT / 500e-9),
+      None,
+    );
+    match stcs2mocs(10, None, 40, fdepth, "SpectralInterval 500 600 unit nm").unwrap() {
+      StcMoc::Frequency(fmoc) => assert_eq!(fmoc, expected),
+      moc => panic!("F-MOC expected. Actual: {:?}", moc),
+    }
+
+    let stcs = "TimeInterval TCB JD 2451545 JD 2451546 Circle ICRS 10 20 1";
+    match stcs2mocs(8, None, 40, fdepth, stcs).unwrap() {
+      StcMoc::TimeSpace(stmoc) => {
+        let elems = stmoc.elems();
+        assert_eq!(elems.len(), 1);
+        assert_eq!(elems[0].n_ranges_1(), 1);
+        assert_eq!(
+          elems[0].n_ranges_2(),
+          stcs2moc(8, None, stcs).unwrap().len()
+        );
+      }
+      moc => panic!("ST-MOC expected. Actual: {:?}", moc),
+    }
+    let stcs =
+      "TimeInterval TCB JD 2451545 JD 2451546 Circle ICRS 10 20 1 SpectralInterval 1e9 2e9";
+    assert!(stcs2mocs(8, None, 40, fdepth, stcs).is_err());
+  }
+
+  #[test]
+  fn test_polygon_vertex_on_zero_meridian() {
+    // (STC-S, lat, lon inside, lon outside)
+    for (stcs, lat, lon_in, lon_out) in [
+      ("Polygon ICRS 0 10 20 10 20 20 0 20", 15.0, 10.0, 350.0),
+      ("Polygon ICRS 360 10 20 10 20 20 360 20", 15.0, 10.0, 350.0),
+      ("Polygon ICRS 340 10 0 10 0 20 340 20", 15.0, 350.0, 10.0),
+      // In the polar caps
+      ("Polygon ICRS 340 50 0 50 0 60 340 60", 55.0, 350.0, 10.0),
+      (
+        "Polygon ICRS 340 -60 0 -60 0 -50 340 -50",
+        -55.0,
+        350.0,
+        10.0,
+      ),
+    ] {
+      let moc = stcs2moc(8, Some(2), stcs).unwrap();
+      let lat = f64::to_radians(lat);
+      assert!(moc.is_in(f64::to_radians(lon_in), lat));
+      assert!(!moc.is_in(f64::to_radians(lon_out), lat));
+    }
+  }
+
+  fn check_polygons_round_trip(moc: &RangeMOC<u64, Hpx<u64>>) -> String {
+    let stcs = moc2stcs(moc);
+    let depth = moc.depth_max();
+    let moc2 = stcs2moc(depth, Some(2), &stcs).unwrap();
+    // Great-circle edges slightly differ from the HEALPix cell edges
+    assert!(moc.minus(&moc2).is_empty());
+    assert!(moc2.minus(&moc.expanded()).is_empty());
+    stcs
+  }
+
+  #[test]
+  fn test_to_stcs_polygons() {
+    let depth = 6;
+    let cone = RangeMOC::from_cone(0.5, 0.3, 0.2, depth, 2, CellSelection::All);
+    let stcs = check_polygons_round_trip(&cone);
+    assert!(stcs.starts_with("Polygon ICRS "));
+    let hole = RangeMOC::from_cone(0.5, 0.3, 0.12, depth, 2, CellSelection::All);
+    let island = RangeMOC::from_cone(0.5, 0.3, 0.03, depth, 2, CellSelection::All);
+    let other = RangeMOC::from_cone(3.0, -0.5, 0.1, depth, 2, CellSelection::All);
+    let stcs = check_polygons_round_trip(&cone.minus(&hole).or(&island).or(&other));
+    assert!(stcs.starts_with("Union ICRS ( Difference ( Polygon "));
+    // Polar caps, their complement and a band
+    let north = RangeMOC::from_cone(0.0, HALF_PI, 0.3, depth, 2, CellSelection::All);
+    let south = RangeMOC::from_cone(1.0, -HALF_PI, 0.5, depth, 2, CellSelection::All);
+    assert!(check_polygons_round_trip(&north).starts_with("Polygon ICRS "));
+    assert!(check_polygons_round_trip(&north.not()).starts_with("Not ICRS ( Polygon "));
+    check_polygons_round_trip(&north.or(&south).not());
+    check_polygons_round_trip(&north.or(&south).or(&island));
+    // Base cells touching the north pole
+    check_polygons_round_trip(&RangeMOC::new(
+      3,
+      MocRanges::new_unchecked(vec![0..(1_u64 << 58), (2_u64 << 58)..(3_u64 << 58)]),
+    ));
+    // Empty and full MOCs
+    assert_eq!(
+      moc2stcs(&RangeMOC::<u64, Hpx<u64>>::new_empty(5)),
+      "Not ICRS ( AllSky )"
+    );
+    let full = RangeMOC::<u64, Hpx<u64>>::new_full_domain(5);
+    assert_eq!(moc2stcs(&full), "AllSky ICRS");
+    let stcs = check_polygons_round_trip(&full.minus(&island.degraded(5)));
+    assert!(stcs.starts_with("Difference ICRS ( AllSky Polygon "));
+  }
+
+  #[test]
+  fn test_to_stcs_moc() {
+    let moc = RangeMOC::<u64, Hpx<u64>>::from_cone(0.5, 0.3, 0.1, 6, 2, CellSelection::All);
+    let stcs = moc2stcs_moc(&moc);
+    let ascii = stcs.strip_prefix("MOC ICRS ").unwrap();
+    let moc2 = from_ascii_ivoa::<u64, Hpx<u64>>(ascii)
+      .unwrap()
+      .into_cellcellrange_moc_iter()
+      .ranges()
+      .into_range_moc();
+    assert_eq!(moc, moc2);
+  }
+
+  #[test]
+  fn test_to_stcs_time() {
+    let jd2000_microsec = 2451545 * 86_400_000_000;
+    let moc = RangeMOC::<u64, Time<u64>>::new(
+      Time::<u64>::MAX_DEPTH,
+      MocRanges::new_unchecked(vec![
+        jd2000_microsec..jd2000_microsec + 43_200_000_000,
+        jd2000_microsec + 86_400_000_000..jd2000_microsec + 86_400_000_001,
+      ]),
+    );
+    assert_eq!(
+      tmoc2stcs(&moc).unwrap(),
+      "TimeInterval TCB BARYCENTER JD 2451545 JD 2451545.5 JD 2451546 JD 2451546.000000000012"
+    );
+    assert!(stc_s::Stc::parse::<nom::error::VerboseError<&str>>(&tmoc2stcs(&moc).unwrap()).is_ok());
+    assert!(tmoc2stcs(&RangeMOC::<u64, Time<u64>>::new_empty(10)).is_err());
+  }
 }
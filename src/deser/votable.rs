@@ -0,0 +1,1156 @@
+//! Minimal VOTable support: reading tables and writing tables or MOCs.
+//!
+//! Only the first `TABLE` of a VOTable is read, whatever the `RESOURCE` it belongs to.
+//! Its rows are read one by one, in streaming mode, by a [VOTableReader]. They can be serialized
+//! in `TABLEDATA`, `BINARY` or `BINARY2` (base64 encoded `STREAM` only); cell values are all
+//! returned as strings (arrays elements being separated by spaces), `None` being a null value.
+//! A MOC is embedded in a VOTable either as an ASCII MOC (DALI `xtype="moc"`) in a `PARAM`,
+//! or as a FITS MOC in the base64 encoded `STREAM` of a `FITS` element.
+//! The XML parser is deliberately minimal: it does not validate the document and does not
+//! resolve external entities.
+
+use std::{
+  borrow::Cow,
+  collections::VecDeque,
+  io::{self, BufRead, BufReader, Read, Write},
+  str,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum VOTableError {
+  #[error("I/O error: {0}")]
+  Io(#[from] std::io::Error),
+  #[error("XML error at byte {pos}: {msg}")]
+  Xml { pos: usize, msg: String },
+  #[error("Base64 decoding error: {0}")]
+  Base64(#[from] base64::DecodeError),
+  #[error("Wrong binary data: {0}")]
+  Binary(String),
+  #[error("Not supported: {0}")]
+  Unsupported(String),
+}
+
+/// A VOTable `PARAM`.
+#[derive(Debug, Clone, Default)]
+pub struct Param {
+  pub name: String,
+  pub datatype: String,
+  pub xtype: Option<String>,
+  pub ucd: Option<String>,
+  pub value: String,
+}
+
+/// A VOTable `FIELD`.
+#[derive(Debug, Clone, Default)]
+pub struct Field {
+  pub name: String,
+  pub id: Option<String>,
+  pub datatype: String,
+  pub arraysize: Option<String>,
+  pub ucd: Option<String>,
+  pub unit: Option<String>,
+  /// Value of the `null` attribute of the `VALUES` element, if any.
+  pub null: Option<String>,
+  /// Original XML of the `FIELD` element, used when writing the table back.
+  xml: String,
+}
+
+/// MOC embedded in a VOTable.
+#[derive(Debug, Clone, Copy)]
+pub enum VOTableMoc<'a> {
+  /// IVOA ASCII serialization, stored in a `PARAM`.
+  Ascii(&'a str),
+  /// IVOA FITS serialization, stored in a `STREAM`.
+  Fits(&'a [u8]),
+}
+
+/// Metadata of the first `TABLE` of a VOTable, plus the `PARAM`s of the whole document.
+/// The rows of the table are read, one by one, by a [VOTableReader].
+#[derive(Debug, Default, Clone)]
+pub struct VOTable {
+  pub params: Vec<Param>,
+  pub fields: Vec<Field>,
+  /// Content of a base64 encoded FITS `STREAM`, if any.
+  pub fits: Option<Vec<u8>>,
+  /// Original XML of the `COOSYS` and `TIMESYS` elements (possibly referenced by the fields).
+  systems: Vec<String>,
+}
+
+/// Returns `true` if the given bytes (typically the first bytes of a file) look like the
+/// beginning of a VOTable (or at least of an XML document).
+pub fn is_votable(bytes: &[u8]) -> bool {
+  let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+  let start = bytes
+    .iter()
+    .position(|b| !b.is_ascii_whitespace())
+    .unwrap_or(bytes.len());
+  let bytes = &bytes[start..];
+  bytes.starts_with(b"<?xml") || bytes.starts_with(b"<VOTABLE")
+}
+
+impl VOTable {
+  /// Reads a whole VOTable from the given reader, skipping the table rows.
+  pub fn from_reader<R: Read>(reader: R) -> Result<Self, VOTableError> {
+    let mut vot_reader = VOTableReader::new(BufReader::new(reader))?;
+    for row in &mut vot_reader {
+      row?;
+    }
+    Ok(vot_reader.votable)
+  }
+
+  /// Parses the given VOTable document, skipping the table rows.
+  pub fn parse(xml: &str) -> Result<Self, VOTableError> {
+    Self::from_reader(xml.as_bytes())
+  }
+
+  /// Returns the index of the column having the given name (case-insensitive) or ID.
+  pub fn column_index(&self, name: &str) -> Option<usize> {
+    self
+      .fields
+      .iter()
+      .position(|f| f.name.eq_ignore_ascii_case(name))
+      .or_else(|| {
+        self
+          .fields
+          .iter()
+          .position(|f| f.id.as_deref() == Some(name))
+      })
+  }
+
+  /// Returns the index of the column having the given UCD (e.g. `pos.eq.ra`), preferring the
+  /// column also having the `meta.main` UCD word.
+  pub fn column_index_by_ucd(&self, ucd: &str) -> Option<usize> {
+    let has_ucd = |f: &Field, word: &str| {
+      f.ucd
+        .as_deref()
+        .map(|u| u.split(';').any(|w| w.trim().eq_ignore_ascii_case(word)))
+        .unwrap_or(false)
+    };
+    self
+      .fields
+      .iter()
+      .position(|f| has_ucd(f, ucd) && has_ucd(f, "meta.main"))
+      .or_else(|| self.fields.iter().position(|f| has_ucd(f, ucd)))
+  }
+
+  /// Returns the first `PARAM` having the `moc` xtype.
+  pub fn moc_param(&self) -> Option<&Param> {
+    self.params.iter().find(|p| {
+      p.xtype
+        .as_deref()
+        .map(|x| x.eq_ignore_ascii_case("moc"))
+        .unwrap_or(false)
+    })
+  }
+
+  /// Returns the MOC embedded in the VOTable, looking first for a `PARAM` having the `moc`
+  /// xtype, then for a FITS `STREAM`.
+  pub fn moc(&self) -> Option<VOTableMoc<'_>> {
+    self
+      .moc_param()
+      .map(|p| VOTableMoc::Ascii(p.value.as_str()))
+      .or_else(|| self.fits.as_deref().map(VOTableMoc::Fits))
+  }
+
+  /// Writes the fields of this VOTable, with the given rows, as a `TABLEDATA` VOTable.
+  /// The first row error, if any, stops the writing and is returned.
+  pub fn write<W, I, T>(&self, rows: I, mut writer: W) -> Result<(), VOTableError>
+  where
+    W: Write,
+    I: IntoIterator<Item = Result<T, VOTableError>>,
+    T: AsRef<[Option<String>]>,
+  {
+    write_header(&mut writer)?;
+    for system in &self.systems {
+      writeln!(writer, "{}", system)?;
+    }
+    writeln!(writer, "<TABLE>")?;
+    for field in &self.fields {
+      writeln!(writer, "{}", field.xml)?;
+    }
+    writeln!(writer, "<DATA>\n<TABLEDATA>")?;
+    for row in rows {
+      write!(writer, "<TR>")?;
+      for value in row?.as_ref() {
+        match value {
+          Some(v) => write!(writer, "<TD>{}</TD>", escape(v))?,
+          None => write!(writer, "<TD/>")?,
+        }
+      }
+      writeln!(writer, "</TR>")?;
+    }
+    writeln!(writer, "</TABLEDATA>\n</DATA>\n</TABLE>")?;
+    write_footer(writer)
+  }
+}
+
+/// Streaming VOTable reader: reads the metadata (`PARAM`s, `FIELD`s, ...) of a VOTable and then
+/// returns, one by one, the rows of its first `TABLE` without loading the whole document.
+pub struct VOTableReader<R: BufRead> {
+  reader: R,
+  /// Metadata read so far
+  votable: VOTable,
+  /// XML read and not yet consumed (starting at `pos`)
+  xml: String,
+  pos: usize,
+  /// Bytes of an incomplete UTF-8 character at the end of the last read chunk
+  utf8_tail: Vec<u8>,
+  eof: bool,
+  state: ParserState,
+  /// Rows read and not yet returned (a chunk of a `BINARY` stream may contain several rows)
+  rows: VecDeque<Vec<Option<String>>>,
+}
+
+/// State of the parser between two XML events.
+#[derive(Default)]
+struct ParserState {
+  /// Names of the elements being read
+  stack: Vec<String>,
+  /// First TABLE: not reached yet, being read, or already read
+  in_table: bool,
+  table_done: bool,
+  /// Start position of the element whose raw XML is being stored
+  raw: Option<usize>,
+  field: Option<Field>,
+  /// Text content of the TD being read
+  text: Option<String>,
+  row: Vec<Option<String>>,
+  /// Base64 encoded STREAM being read
+  stream: Option<StreamDecoder>,
+}
+
+impl<R: BufRead> VOTableReader<R> {
+  /// Reads the VOTable metadata, up to the first row of the first `TABLE` (or up to the end of
+  /// the document if the table has no row).
+  pub fn new(reader: R) -> Result<Self, VOTableError> {
+    let mut vot_reader = Self {
+      reader,
+      votable: VOTable::default(),
+      xml: String::new(),
+      pos: 0,
+      utf8_tail: Vec::new(),
+      eof: false,
+      state: ParserState::default(),
+      rows: VecDeque::new(),
+    };
+    while vot_reader.rows.is_empty() && vot_reader.read_event()? {}
+    Ok(vot_reader)
+  }
+
+  /// Metadata of the VOTable.
+  /// `PARAM`s located after the table are available only once all rows have been read.
+  pub fn votable(&self) -> &VOTable {
+    &self.votable
+  }
+
+  /// Reads and processes the next XML event, returning `false` at the end of the document.
+  fn read_event(&mut self) -> Result<bool, VOTableError> {
+    loop {
+      let mut cursor = XmlCursor {
+        xml: &self.xml,
+        pos: self.pos,
+      };
+      let event = match cursor.next_event() {
+        Ok(Some(Event::Text(text))) if !self.eof && cursor.pos == self.xml.len() => {
+          // The text may continue in the next chunk: keep a possibly truncated entity
+          let len = text.rfind('&').unwrap_or(text.len());
+          cursor.pos = self.pos + len;
+          (len > 0).then(|| Event::Text(&text[..len]))
+        }
+        // Possibly incomplete event
+        Ok(None) | Err(_) if !self.eof => None,
+        Ok(Some(event)) => Some(event),
+        Ok(None) => {
+          return match self.state.stack.last() {
+            Some(open) => Err(VOTableError::Xml {
+              pos: self.pos,
+              msg: format!("end of document reached, tag '{}' not closed", open),
+            }),
+            None => Ok(false),
+          }
+        }
+        Err(e) => return Err(e),
+      };
+      match event {
+        Some(event) => {
+          let pos = cursor.pos;
+          self
+            .state
+            .handle(event, &self.xml, &mut self.votable, &mut self.rows)?;
+          self.pos = pos;
+          return Ok(true);
+        }
+        None => self.read_chunk()?,
+      }
+    }
+  }
+
+  /// Appends the next chunk of the input to the XML not yet consumed.
+  fn read_chunk(&mut self) -> Result<(), VOTableError> {
+    // Remove the consumed XML, unless the raw XML of an element is being stored
+    if self.state.raw.is_none() {
+      self.xml.drain(..self.pos);
+      self.pos = 0;
+    }
+    let chunk = self.reader.fill_buf()?;
+    if chunk.is_empty() {
+      self.eof = true;
+    }
+    self.utf8_tail.extend_from_slice(chunk);
+    let n = chunk.len();
+    self.reader.consume(n);
+    let valid_len = match str::from_utf8(&self.utf8_tail) {
+      Ok(_) => self.utf8_tail.len(),
+      Err(e) if e.error_len().is_none() && !self.eof => e.valid_up_to(),
+      Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e).into()),
+    };
+    // Safe unwrap since checked just before
+    self
+      .xml
+      .push_str(str::from_utf8(&self.utf8_tail[..valid_len]).unwrap());
+    self.utf8_tail.drain(..valid_len);
+    Ok(())
+  }
+}
+
+impl<R: BufRead> Iterator for VOTableReader<R> {
+  type Item = Result<Vec<Option<String>>, VOTableError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      if let Some(row) = self.rows.pop_front() {
+        return Some(Ok(row));
+      }
+      match self.read_event() {
+        Ok(true) => {}
+        Ok(false) => return None,
+        Err(e) => {
+          // Stop at the first error
+          self.eof = true;
+          self.xml.clear();
+          self.pos = 0;
+          self.state = ParserState::default();
+          return Some(Err(e));
+        }
+      }
+    }
+  }
+}
+
+impl ParserState {
+  /// Processes the given XML event, `xml` being the XML the event positions refer to.
+  fn handle(
+    &mut self,
+    event: Event,
+    xml: &str,
+    vot: &mut VOTable,
+    rows: &mut VecDeque<Vec<Option<String>>>,
+  ) -> Result<(), VOTableError> {
+    match event {
+      Event::Start(tag) => {
+        match tag.name {
+          "TABLE" if !self.table_done => self.in_table = true,
+          "FIELD" if self.in_table => {
+            let f = Field {
+              name: tag.attr("name").unwrap_or_default().to_string(),
+              id: tag.attr("ID").map(str::to_string),
+              datatype: tag.attr("datatype").unwrap_or("char").to_string(),
+              arraysize: tag.attr("arraysize").map(str::to_string),
+              ucd: tag.attr("ucd").map(str::to_string),
+              unit: tag.attr("unit").map(str::to_string),
+              null: None,
+              xml: String::new(),
+            };
+            if tag.empty {
+              vot.fields.push(Field {
+                xml: xml[tag.start..tag.end].to_string(),
+                ..f
+              });
+            } else {
+              self.field = Some(f);
+              self.raw = Some(tag.start);
+            }
+          }
+          "VALUES" => {
+            if let (Some(f), Some(null)) = (self.field.as_mut(), tag.attr("null")) {
+              f.null = Some(null.to_string());
+            }
+          }
+          "PARAM" => vot.params.push(Param {
+            name: tag.attr("name").unwrap_or_default().to_string(),
+            datatype: tag.attr("datatype").unwrap_or("char").to_string(),
+            xtype: tag.attr("xtype").map(str::to_string),
+            ucd: tag.attr("ucd").map(str::to_string),
+            value: tag.attr("value").unwrap_or_default().to_string(),
+          }),
+          "COOSYS" | "TIMESYS" if !self.table_done => {
+            if tag.empty {
+              vot.systems.push(xml[tag.start..tag.end].to_string());
+            } else {
+              self.raw = Some(tag.start);
+            }
+          }
+          "TR" if self.in_table => self.row = Vec::with_capacity(vot.fields.len()),
+          "TD" if self.in_table => {
+            if tag.empty {
+              self.row.push(None);
+            } else {
+              self.text = Some(String::new());
+            }
+          }
+          "STREAM" if self.in_table => {
+            if tag.attr("href").is_some() {
+              return Err(VOTableError::Unsupported(String::from(
+                "remote STREAM (href attribute)",
+              )));
+            }
+            match tag.attr("encoding") {
+              Some("base64") => {
+                self.stream = Some(StreamDecoder {
+                  parent: self.stack.last().cloned().unwrap_or_default(),
+                  b64: Vec::new(),
+                  bytes: Vec::new(),
+                })
+              }
+              e => {
+                return Err(VOTableError::Unsupported(format!(
+                  "STREAM encoding '{}' (only 'base64' is supported)",
+                  e.unwrap_or_default()
+                )))
+              }
+            }
+          }
+          _ => {}
+        }
+        if !tag.empty {
+          self.stack.push(tag.name.to_string());
+        }
+      }
+      Event::End(name, end) => {
+        match self.stack.pop() {
+          Some(open) if open == name => {}
+          open => {
+            return Err(VOTableError::Xml {
+              pos: end,
+              msg: format!(
+                "closing tag '{}' does not match opening tag '{}'",
+                name,
+                open.unwrap_or_default()
+              ),
+            })
+          }
+        }
+        match name {
+          "TABLE" if self.in_table => {
+            self.in_table = false;
+            self.table_done = true;
+          }
+          "FIELD" => {
+            if let (Some(mut f), Some(start)) = (self.field.take(), self.raw.take()) {
+              f.xml = xml[start..end].to_string();
+              vot.fields.push(f);
+            }
+          }
+          "COOSYS" | "TIMESYS" => {
+            if let Some(start) = self.raw.take() {
+              vot.systems.push(xml[start..end].to_string());
+            }
+          }
+          "TD" if self.in_table => {
+            let value = self.text.take().unwrap_or_default();
+            let value = value.trim();
+            let null = vot
+              .fields
+              .get(self.row.len())
+              .and_then(|f| f.null.as_deref());
+            self.row.push(if value.is_empty() || Some(value) == null {
+              None
+            } else {
+              Some(value.to_string())
+            });
+          }
+          "TR" if self.in_table => rows.push_back(std::mem::take(&mut self.row)),
+          "STREAM" if self.in_table => {
+            if let Some(mut stream) = self.stream.take() {
+              stream.finish()?;
+              stream.read_rows(&vot.fields, rows, true)?;
+              if stream.parent == "FITS" {
+                vot.fits = Some(stream.bytes);
+              }
+            }
+          }
+          _ => {}
+        }
+      }
+      Event::Text(t) => {
+        if let Some(stream) = self.stream.as_mut() {
+          stream.push(t)?;
+          stream.read_rows(&vot.fields, rows, false)?;
+        } else if let Some(text) = self.text.as_mut() {
+          text.push_str(&unescape(t));
+        }
+      }
+      Event::CData(t) => {
+        if let Some(stream) = self.stream.as_mut() {
+          stream.push(t)?;
+          stream.read_rows(&vot.fields, rows, false)?;
+        } else if let Some(text) = self.text.as_mut() {
+          text.push_str(t);
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Incremental decoder of a base64 encoded `STREAM`.
+struct StreamDecoder {
+  /// Parent element: `FITS`, `BINARY` or `BINARY2`
+  parent: String,
+  /// Base64 characters not decoded yet
+  b64: Vec<u8>,
+  /// Decoded bytes not consumed yet
+  bytes: Vec<u8>,
+}
+
+impl StreamDecoder {
+  /// Decodes the given base64 text, except its trailing incomplete group of 4 characters.
+  fn push(&mut self, text: &str) -> Result<(), VOTableError> {
+    self
+      .b64
+      .extend(text.bytes().filter(|b| !b.is_ascii_whitespace()));
+    let len = self.b64.len() - self.b64.len() % 4;
+    STANDARD.decode_vec(&self.b64[..len], &mut self.bytes)?;
+    self.b64.drain(..len);
+    Ok(())
+  }
+
+  /// Decodes the remaining base64 characters, at the end of the stream.
+  fn finish(&mut self) -> Result<(), VOTableError> {
+    if !self.b64.is_empty() {
+      STANDARD.decode_vec(&self.b64, &mut self.bytes)?;
+      self.b64.clear();
+    }
+    Ok(())
+  }
+
+  /// Moves the `BINARY` or `BINARY2` rows decoded so far in `rows`. The last bytes, possibly
+  /// making an incomplete row, are kept until the `end` of the stream.
+  fn read_rows(
+    &mut self,
+    fields: &[Field],
+    rows: &mut VecDeque<Vec<Option<String>>>,
+    end: bool,
+  ) -> Result<(), VOTableError> {
+    let binary2 = match self.parent.as_str() {
+      "BINARY" => false,
+      "BINARY2" => true,
+      _ => return Ok(()),
+    };
+    let mut buf = &self.bytes[..];
+    while !buf.is_empty() {
+      match read_binary_row(fields, buf, binary2) {
+        Ok((row, rest)) => {
+          rows.push_back(row);
+          buf = rest;
+        }
+        Err(VOTableError::Binary(_)) if !end => break,
+        Err(e) => return Err(e),
+      }
+    }
+    let len = self.bytes.len() - buf.len();
+    self.bytes.drain(..len);
+    Ok(())
+  }
+}
+
+/// Writes a VOTable containing only the given MOC.
+/// An ASCII MOC is stored in the `value` of a `PARAM` having the given name and the `moc` xtype;
+/// a FITS MOC is stored in the base64 encoded `STREAM` of a `TABLE` having the given name.
+pub fn moc_to_votable<W: Write>(
+  name: &str,
+  moc: VOTableMoc,
+  mut writer: W,
+) -> Result<(), VOTableError> {
+  write_header(&mut writer)?;
+  match moc {
+    VOTableMoc::Ascii(ascii) => writeln!(
+      writer,
+      r#"<PARAM name="{}" datatype="char" arraysize="*" xtype="moc" value="{}"/>"#,
+      escape(name),
+      escape(ascii.trim())
+    )?,
+    VOTableMoc::Fits(fits) => {
+      let (ttype, tform) = fits_first_column(fits);
+      let datatype = match tform.trim_start_matches(|c: char| c.is_ascii_digit()) {
+        "B" => "unsignedByte",
+        "I" => "short",
+        "J" => "int",
+        _ => "long",
+      };
+      writeln!(writer, r#"<TABLE name="{}">"#, escape(name))?;
+      writeln!(
+        writer,
+        r#"<FIELD name="{}" datatype="{}"/>"#,
+        escape(&ttype),
+        datatype
+      )?;
+      writeln!(
+        writer,
+        "<DATA>\n<FITS extnum=\"1\">\n<STREAM encoding=\"base64\">"
+      )?;
+      let b64 = STANDARD.encode(fits);
+      for line in b64.as_bytes().chunks(76) {
+        writer.write_all(line)?;
+        writer.write_all(b"\n")?;
+      }
+      writeln!(writer, "</STREAM>\n</FITS>\n</DATA>\n</TABLE>")?;
+    }
+  }
+  write_footer(writer)
+}
+
+fn write_header<W: Write>(mut writer: W) -> Result<(), VOTableError> {
+  writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+  writeln!(
+    writer,
+    r#"<VOTABLE version="1.4" xmlns="http://www.ivoa.net/xml/VOTable/v1.3">"#
+  )?;
+  writeln!(writer, r#"<RESOURCE type="results">"#).map_err(|e| e.into())
+}
+
+fn write_footer<W: Write>(mut writer: W) -> Result<(), VOTableError> {
+  writeln!(writer, "</RESOURCE>\n</VOTABLE>").map_err(|e| e.into())
+}
+
+/// Returns the `TTYPE1` and `TFORM1` values of the given FITS file.
+fn fits_first_column(fits: &[u8]) -> (String, String) {
+  let mut ttype = None;
+  let mut tform = None;
+  for card in fits.chunks_exact(80) {
+    let value = || {
+      String::from_utf8_lossy(&card[10..])
+        .split('/')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .trim_matches('\'')
+        .trim()
+        .to_string()
+    };
+    if card.starts_with(b"TTYPE1  = ") {
+      ttype = Some(value());
+    } else if card.starts_with(b"TFORM1  = ") {
+      tform = Some(value());
+    }
+    if ttype.is_some() && tform.is_some() {
+      break;
+    }
+  }
+  (
+    ttype.unwrap_or_else(|| String::from("RANGE")),
+    tform.unwrap_or_else(|| String::from("1K")),
+  )
+}
+
+fn read_binary_row<'a>(
+  fields: &[Field],
+  buf: &'a [u8],
+  binary2: bool,
+) -> Result<(Vec<Option<String>>, &'a [u8]), VOTableError> {
+  if fields.is_empty() {
+    return Err(VOTableError::Binary(String::from("no FIELD in the table")));
+  }
+  let n_mask_bytes = if binary2 { fields.len().div_ceil(8) } else { 0 };
+  let (mask, mut buf) = take(buf, n_mask_bytes)?;
+  let mut row = Vec::with_capacity(fields.len());
+  for (i, field) in fields.iter().enumerate() {
+    let (value, rest) = read_binary_value(field, buf)?;
+    buf = rest;
+    let is_null = binary2 && mask[i >> 3] & (0x80 >> (i & 7)) != 0;
+    row.push(if is_null {
+      None
+    } else {
+      value.filter(|v| field.null.as_deref() != Some(v.as_str()))
+    });
+  }
+  Ok((row, buf))
+}
+
+fn take(buf: &[u8], n: usize) -> Result<(&[u8], &[u8]), VOTableError> {
+  if buf.len() < n {
+    Err(VOTableError::Binary(format!(
+      "truncated stream: {} bytes expected, {} remaining",
+      n,
+      buf.len()
+    )))
+  } else {
+    Ok(buf.split_at(n))
+  }
+}
+
+/// Returns the number of elements of a fixed size array, or the number of elements per
+/// variable last dimension element plus `true` for variable size arrays.
+fn parse_arraysize(arraysize: &str) -> Result<(usize, bool), VOTableError> {
+  let mut n = 1;
+  let mut variable = false;
+  for dim in arraysize.split('x') {
+    let dim = dim.trim();
+    if dim.ends_with('*') {
+      variable = true;
+    } else {
+      n *= dim
+        .parse::<usize>()
+        .map_err(|_| VOTableError::Binary(format!("wrong arraysize '{}'", arraysize)))?;
+    }
+  }
+  Ok((n, variable))
+}
+
+fn read_binary_value<'a>(
+  field: &Field,
+  buf: &'a [u8],
+) -> Result<(Option<String>, &'a [u8]), VOTableError> {
+  let (n_elems, buf) = match field.arraysize.as_deref() {
+    None => (1, buf),
+    Some(arraysize) => match parse_arraysize(arraysize)? {
+      (n, false) => (n, buf),
+      (n, true) => {
+        let (count, rest) = take(buf, 4)?;
+        let count = u32::from_be_bytes([count[0], count[1], count[2], count[3]]) as usize;
+        (n * count, rest)
+      }
+    },
+  };
+  let n_bytes = match field.datatype.as_str() {
+    "bit" => n_elems.div_ceil(8),
+    "boolean" | "unsignedByte" | "char" => n_elems,
+    "short" | "unicodeChar" => 2 * n_elems,
+    "int" | "float" => 4 * n_elems,
+    "long" | "double" | "floatComplex" => 8 * n_elems,
+    "doubleComplex" => 16 * n_elems,
+    dt => return Err(VOTableError::Unsupported(format!("datatype '{}'", dt))),
+  };
+  let (bytes, rest) = take(buf, n_bytes)?;
+  let value = match field.datatype.as_str() {
+    "char" => Some(
+      bytes
+        .iter()
+        .take_while(|b| **b != 0)
+        .map(|b| *b as char)
+        .collect::<String>(),
+    ),
+    "unicodeChar" => {
+      let utf16: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_be_bytes([c[0], c[1]]))
+        .take_while(|c| *c != 0)
+        .collect();
+      Some(String::from_utf16_lossy(&utf16))
+    }
+    "bit" => Some(
+      (0..n_elems)
+        .map(|i| {
+          if bytes[i >> 3] & (0x80 >> (i & 7)) != 0 {
+            '1'
+          } else {
+            '0'
+          }
+        })
+        .collect::<String>(),
+    ),
+    _ => {
+      let elems = binary_elems(&field.datatype, bytes);
+      if field.arraysize.is_none() {
+        elems.into_iter().next().flatten()
+      } else {
+        Some(
+          elems
+            .into_iter()
+            .map(|e| e.unwrap_or_else(|| String::from("NaN")))
+            .collect::<Vec<String>>()
+            .join(" "),
+        )
+      }
+    }
+  };
+  Ok((
+    value
+      .map(|v| v.trim_end().to_string())
+      .filter(|v| !v.is_empty()),
+    rest,
+  ))
+}
+
+/// Decodes the elements of a numeric or boolean array (`None` for NaN or null boolean values).
+fn binary_elems(datatype: &str, bytes: &[u8]) -> Vec<Option<String>> {
+  let float = |v: f32| (!v.is_nan()).then(|| v.to_string());
+  let double = |v: f64| (!v.is_nan()).then(|| v.to_string());
+  let f32_at = |c: &[u8]| f32::from_be_bytes([c[0], c[1], c[2], c[3]]);
+  let f64_at = |c: &[u8]| f64::from_be_bytes([c[0], c[1], c[2], c[3], c[4], c[5], c[6], c[7]]);
+  match datatype {
+    "boolean" => bytes
+      .iter()
+      .map(|b| match b {
+        b'T' | b't' | b'1' => Some(String::from("T")),
+        b'F' | b'f' | b'0' => Some(String::from("F")),
+        _ => None,
+      })
+      .collect(),
+    "unsignedByte" => bytes.iter().map(|b| Some(b.to_string())).collect(),
+    "short" => bytes
+      .chunks_exact(2)
+      .map(|c| Some(i16::from_be_bytes([c[0], c[1]]).to_string()))
+      .collect(),
+    "int" => bytes
+      .chunks_exact(4)
+      .map(|c| Some(i32::from_be_bytes([c[0], c[1], c[2], c[3]]).to_string()))
+      .collect(),
+    "long" => bytes
+      .chunks_exact(8)
+      .map(|c| {
+        Some(i64::from_be_bytes([c[0], c[1], c[2], c[3], c[4], c[5], c[6], c[7]]).to_string())
+      })
+      .collect(),
+    "float" => bytes.chunks_exact(4).map(|c| float(f32_at(c))).collect(),
+    "double" => bytes.chunks_exact(8).map(|c| double(f64_at(c))).collect(),
+    "floatComplex" => bytes
+      .chunks_exact(8)
+      .map(|c| Some(format!("{} {}", f32_at(&c[..4]), f32_at(&c[4..]))))
+      .collect(),
+    "doubleComplex" => bytes
+      .chunks_exact(16)
+      .map(|c| Some(format!("{} {}", f64_at(&c[..8]), f64_at(&c[8..]))))
+      .collect(),
+    _ => Vec::new(),
+  }
+}
+
+fn escape(s: &str) -> Cow<'_, str> {
+  if s.contains(['&', '<', '>', '"']) {
+    Cow::Owned(
+      s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;"),
+    )
+  } else {
+    Cow::Borrowed(s)
+  }
+}
+
+fn unescape(s: &str) -> Cow<'_, str> {
+  if !s.contains('&') {
+    return Cow::Borrowed(s);
+  }
+  let mut res = String::with_capacity(s.len());
+  let mut rest = s;
+  while let Some(i) = rest.find('&') {
+    res.push_str(&rest[..i]);
+    rest = &rest[i..];
+    let entity = rest.find(';').map(|j| (&rest[1..j], j));
+    let c = entity.and_then(|(e, _)| match e {
+      "lt" => Some('<'),
+      "gt" => Some('>'),
+      "amp" => Some('&'),
+      "quot" => Some('"'),
+      "apos" => Some('\''),
+      _ => e
+        .strip_prefix("#x")
+        .map(|h| u32::from_str_radix(h, 16))
+        .or_else(|| e.strip_prefix('#').map(|d| d.parse::<u32>()))
+        .and_then(|n| n.ok())
+        .and_then(char::from_u32),
+    });
+    match (c, entity) {
+      (Some(c), Some((_, j))) => {
+        res.push(c);
+        rest = &rest[j + 1..];
+      }
+      _ => {
+        res.push('&');
+        rest = &rest[1..];
+      }
+    }
+  }
+  res.push_str(rest);
+  Cow::Owned(res)
+}
+
+/// An XML start (or empty) element tag.
+struct Tag<'a> {
+  /// Local name (namespace prefix removed).
+  name: &'a str,
+  attrs: Vec<(&'a str, Cow<'a, str>)>,
+  empty: bool,
+  start: usize,
+  end: usize,
+}
+
+impl Tag<'_> {
+  fn attr(&self, name: &str) -> Option<&str> {
+    self
+      .attrs
+      .iter()
+      .find(|(k, _)| *k == name)
+      .map(|(_, v)| v.as_ref())
+  }
+}
+
+enum Event<'a> {
+  Start(Tag<'a>),
+  /// Local name and position following the closing tag.
+  End(&'a str, usize),
+  /// Raw (still escaped) text.
+  Text(&'a str),
+  CData(&'a str),
+}
+
+struct XmlCursor<'a> {
+  xml: &'a str,
+  pos: usize,
+}
+
+fn local_name(name: &str) -> &str {
+  name.rsplit(':').next().unwrap_or(name)
+}
+
+impl<'a> XmlCursor<'a> {
+  fn err(&self, msg: &str) -> VOTableError {
+    VOTableError::Xml {
+      pos: self.pos,
+      msg: msg.to_string(),
+    }
+  }
+
+  /// Moves the cursor after the given pattern, returning the content before the pattern.
+  fn skip_after(&mut self, pattern: &str) -> Result<&'a str, VOTableError> {
+    let xml = self.xml;
+    match xml[self.pos..].find(pattern) {
+      Some(i) => {
+        let content = &xml[self.pos..self.pos + i];
+        self.pos += i + pattern.len();
+        Ok(content)
+      }
+      None => Err(self.err(&format!("'{}' not found", pattern))),
+    }
+  }
+
+  fn next_event(&mut self) -> Result<Option<Event<'a>>, VOTableError> {
+    let xml = self.xml;
+    loop {
+      let rest = &xml[self.pos..];
+      if rest.is_empty() {
+        return Ok(None);
+      }
+      if !rest.starts_with('<') {
+        let len = rest.find('<').unwrap_or(rest.len());
+        self.pos += len;
+        return Ok(Some(Event::Text(&rest[..len])));
+      }
+      if rest.starts_with("<!--") {
+        self.skip_after("-->")?;
+      } else if rest.starts_with("<![CDATA[") {
+        self.pos += 9;
+        return self.skip_after("]]>").map(|t| Some(Event::CData(t)));
+      } else if rest.starts_with("<?") {
+        self.skip_after("?>")?;
+      } else if rest.starts_with("<!") {
+        self.skip_after(">")?;
+      } else if rest.starts_with("</") {
+        self.pos += 2;
+        let name = self.skip_after(">")?.trim();
+        if name.is_empty() {
+          return Err(self.err("empty closing tag"));
+        }
+        return Ok(Some(Event::End(local_name(name), self.pos)));
+      } else {
+        return self.parse_tag().map(|t| Some(Event::Start(t)));
+      }
+    }
+  }
+
+  fn parse_tag(&mut self) -> Result<Tag<'a>, VOTableError> {
+    let xml = self.xml;
+    let start = self.pos;
+    let is_name_end = |c: char| c.is_ascii_whitespace() || c == '/' || c == '>' || c == '=';
+    let mut pos = start + 1;
+    let name_len = xml[pos..].find(is_name_end).unwrap_or(xml.len() - pos);
+    let name = local_name(&xml[pos..pos + name_len]);
+    pos += name_len;
+    let mut attrs = Vec::new();
+    loop {
+      pos += xml[pos..].len() - xml[pos..].trim_start().len();
+      let rest = &xml[pos..];
+      if rest.starts_with("/>") || rest.starts_with('>') {
+        let empty = rest.starts_with('/');
+        self.pos = pos + if empty { 2 } else { 1 };
+        return Ok(Tag {
+          name,
+          attrs,
+          empty,
+          start,
+          end: self.pos,
+        });
+      }
+      let key_len = rest.find(is_name_end).unwrap_or(rest.len());
+      let key = local_name(&rest[..key_len]);
+      let rest = rest[key_len..].trim_start();
+      let rest = rest.strip_prefix('=').map(str::trim_start);
+      let quote = rest
+        .and_then(|r| r.chars().next())
+        .filter(|c| *c == '"' || *c == '\'');
+      match (rest, quote) {
+        (Some(rest), Some(quote)) if key_len > 0 => {
+          let value_start = xml.len() - rest.len() + 1;
+          match xml[value_start..].find(quote) {
+            Some(len) => {
+              attrs.push((key, unescape(&xml[value_start..value_start + len])));
+              pos = value_start + len + 1;
+            }
+            None => {
+              self.pos = value_start;
+              return Err(self.err("unterminated attribute value"));
+            }
+          }
+        }
+        _ => {
+          self.pos = pos;
+          return Err(self.err(&format!("wrong attribute in tag '{}'", name)));
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_votable_tabledata() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- A comment -->
+<VOTABLE version="1.4" xmlns="http://www.ivoa.net/xml/VOTable/v1.3">
+<RESOURCE type='results'>
+<COOSYS ID="icrs" system="ICRS"/>
+<PARAM name="survey" datatype="char" arraysize="*" value="A &amp; B"/>
+<TABLE>
+<FIELD name="ra" datatype="double" ucd="pos.eq.ra" ref="icrs"/>
+<FIELD name="dec" datatype="double" ucd="pos.eq.dec;meta.main" ref="icrs"/>
+<FIELD name="mag" datatype="int">
+  <DESCRIPTION>Magnitude</DESCRIPTION>
+  <VALUES null="-99"/>
+</FIELD>
+<DATA><TABLEDATA>
+<TR><TD>10.5</TD><TD> -20.25 </TD><TD>-99</TD></TR>
+<TR><TD>11</TD><TD/><TD><![CDATA[12]]></TD></TR>
+</TABLEDATA></DATA>
+</TABLE>
+</RESOURCE>
+</VOTABLE>"#;
+    let vot = VOTable::parse(xml).unwrap();
+    assert_eq!(vot.params.len(), 1);
+    assert_eq!(vot.params[0].value, "A & B");
+    assert_eq!(vot.fields.len(), 3);
+    assert_eq!(vot.fields[2].null.as_deref(), Some("-99"));
+    assert_eq!(vot.column_index("MAG"), Some(2));
+    assert_eq!(vot.column_index_by_ucd("pos.eq.dec"), Some(1));
+    let expected = vec![
+      vec![Some("10.5".to_string()), Some("-20.25".to_string()), None],
+      vec![Some("11".to_string()), None, Some("12".to_string())],
+    ];
+    // Small buffers to test events split between chunks
+    for capacity in [1, 7, 64, 8192] {
+      let reader = VOTableReader::new(BufReader::with_capacity(capacity, xml.as_bytes())).unwrap();
+      assert_eq!(reader.votable().fields.len(), 3);
+      assert_eq!(reader.votable().systems.len(), 1);
+      let rows: Vec<Vec<Option<String>>> = reader.map(|row| row.unwrap()).collect();
+      assert_eq!(rows, expected);
+    }
+    // Write the last row only and read it back
+    let mut buf = Vec::new();
+    let reader = VOTableReader::new(xml.as_bytes()).unwrap();
+    let header = reader.votable().clone();
+    header.write(reader.skip(1), &mut buf).unwrap();
+    let reader = VOTableReader::new(&buf[..]).unwrap();
+    let vot2 = reader.votable().clone();
+    assert_eq!(vot2.fields.len(), 3);
+    assert_eq!(vot2.systems.len(), 1);
+    assert_eq!(vot2.fields[2].null.as_deref(), Some("-99"));
+    let rows: Vec<Vec<Option<String>>> = reader.map(|row| row.unwrap()).collect();
+    assert_eq!(rows, expected[1..].to_vec());
+    // Errors
+    let truncated = &xml[..xml.find("</TABLEDATA>").unwrap()];
+    assert!(VOTableReader::new(truncated.as_bytes())
+      .unwrap()
+      .any(|row| row.is_err()));
+    assert!(VOTable::parse("<VOTABLE><TABLE></VOTABLE>").is_err());
+  }
+
+  #[test]
+  fn test_votable_binary2() {
+    // Row 1: id=1, ra=10.0, name="ab"; row 2: id=2, ra=null (mask), name="" (empty variable array)
+    let mut bytes = Vec::new();
+    bytes.push(0u8);
+    bytes.extend_from_slice(&1_i64.to_be_bytes());
+    bytes.extend_from_slice(&10.0_f64.to_be_bytes());
+    bytes.extend_from_slice(&2_u32.to_be_bytes());
+    bytes.extend_from_slice(b"ab");
+    bytes.push(0b0100_0000);
+    bytes.extend_from_slice(&2_i64.to_be_bytes());
+    bytes.extend_from_slice(&0.0_f64.to_be_bytes());
+    bytes.extend_from_slice(&0_u32.to_be_bytes());
+    let xml = format!(
+      r#"<VOTABLE><RESOURCE><TABLE>
+<FIELD name="id" datatype="long"/>
+<FIELD name="ra" datatype="double"/>
+<FIELD name="name" datatype="char" arraysize="*"/>
+<DATA><BINARY2><STREAM encoding="base64">{}</STREAM></BINARY2></DATA>
+</TABLE></RESOURCE></VOTABLE>"#,
+      STANDARD.encode(&bytes)
+    );
+    for capacity in [1, 5, 8192] {
+      let reader = VOTableReader::new(BufReader::with_capacity(capacity, xml.as_bytes())).unwrap();
+      let rows: Vec<Vec<Option<String>>> = reader.map(|row| row.unwrap()).collect();
+      assert_eq!(
+        rows,
+        vec![
+          vec![
+            Some("1".to_string()),
+            Some("10".to_string()),
+            Some("ab".to_string())
+          ],
+          vec![Some("2".to_string()), None, None],
+        ]
+      );
+    }
+    // Truncated binary stream
+    let xml = xml.replace(
+      &STANDARD.encode(&bytes),
+      &STANDARD.encode(&bytes[..bytes.len() - 1]),
+    );
+    assert!(VOTableReader::new(xml.as_bytes())
+      .unwrap()
+      .any(|row| row.is_err()));
+  }
+
+  #[test]
+  fn test_votable_moc() {
+    let mut buf = Vec::new();
+    moc_to_votable("smoc", VOTableMoc::Ascii("3/1-3 4/\n"), &mut buf).unwrap();
+    let vot = VOTable::from_reader(&buf[..]).unwrap();
+    assert_eq!(vot.moc_param().map(|p| p.name.as_str()), Some("smoc"));
+    assert!(matches!(vot.moc(), Some(VOTableMoc::Ascii("3/1-3 4/"))));
+
+    let fits: Vec<u8> = (0..=255_u8).cycle().take(2 * 2880).collect();
+    let mut buf = Vec::new();
+    moc_to_votable("smoc", VOTableMoc::Fits(&fits), &mut buf).unwrap();
+    assert!(is_votable(&buf));
+    let vot = VOTable::from_reader(&buf[..]).unwrap();
+    assert!(matches!(vot.moc(), Some(VOTableMoc::Fits(f)) if f == &fits[..]));
+  }
+}
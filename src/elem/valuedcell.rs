@@ -1,9 +1,12 @@
 use std::cmp::Ordering::Equal;
+use std::f64::consts::PI;
 use std::ops::Range;
 
 use num::{Num, One};
 
 use crate::idx::Idx;
+use crate::moc::range::RangeMOC;
+use crate::mom::{HpxMOMIterator, HpxMomIter};
 use crate::qty::Hpx;
 
 use super::super::elemset::range::HpxRanges;
@@ -270,6 +273,133 @@ where
   HpxRanges::new_from(result)
 }
 
+/// Area of the whole sky, in square degrees.
+const SKY_AREA_DEG2: f64 = 4.0 * PI * (180.0 / PI) * (180.0 / PI);
+
+/// Credible region, i.e. region containing a given cumulative value (e.g. the 90% credible
+/// region of a gravitational-wave probability map), see [valued_cells_to_credible_regions].
+#[derive(Debug, Clone)]
+pub struct CredibleRegion {
+  /// Requested cumulative value (e.g. `0.9`)
+  pub level: f64,
+  /// MOC of the region
+  pub moc: RangeMOC<u64, Hpx<u64>>,
+  /// Area of the MOC, in square degrees
+  pub area_deg2: f64,
+  /// Cumulative value actually contained in the MOC (differs from `level` because of the
+  /// MOC resolution)
+  pub value: f64,
+}
+
+/// Searched area and value at a given position, i.e. the area and the cumulative value of the
+/// smallest region, built from the highest to the lowest densities, containing the position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchedRegion {
+  /// Searched area, in square degrees
+  pub area_deg2: f64,
+  /// Searched value (e.g. the searched probability)
+  pub value: f64,
+}
+
+/// Same as [valued_cells_to_moc_with_opt] with `cumul_from = 0` and descending densities, but
+/// computing the credible regions of all the given levels in one pass (the cells being sorted
+/// once), and optionally the searched region at a given position.
+///
+/// # Args
+/// * `max_depth`: the largest depth of the output MOCs, which must be larger or equals to the
+///   largest depth in the `uniq` values
+/// * `uniq_val_dens`: vector of `(uniq cells, value proportional to area, surface density value)`
+/// * `levels`: the cumulative values of the credible regions (e.g. `[0.5, 0.9]`)
+/// * `strict`: (sub-)cells overlapping the level values are not added
+/// * `no_split`: cells overlapping the level values are not recursively split
+/// * `reverse_decent`: perform the recursive decent from the highest cell number to the lowest (to be compatible with Aladin)
+/// * `position`: optional `(lon, lat)` position, in radians, at which the searched region is computed
+///
+/// # Output
+/// * the credible regions, in the same order as the input `levels`
+/// * the searched region, `None` if no position is provided or if no cell contains the position
+pub fn valued_cells_to_credible_regions(
+  max_depth: u8,
+  mut uniq_val_dens: Vec<(u64, f64, f64)>,
+  levels: &[f64],
+  strict: bool,
+  no_split: bool,
+  reverse_decent: bool,
+  position: Option<(f64, f64)>,
+) -> (Vec<CredibleRegion>, Option<SearchedRegion>) {
+  let actual_max_depth = uniq_val_dens
+    .iter()
+    .map(|(uniq, _, _)| Hpx::<u64>::from_uniq_hpx(*uniq).0)
+    .max()
+    .unwrap_or(0);
+  let max_depth = max_depth.max(actual_max_depth);
+  uniq_val_dens.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(Equal));
+  // Levels are processed in ascending order, each level starting from the previous one
+  let mut sorted_levels: Vec<(usize, f64)> = levels.iter().cloned().enumerate().collect();
+  sorted_levels.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Equal));
+  let mut regions: Vec<Option<CredibleRegion>> = vec![None; levels.len()];
+  // Ranges of the cells fully included in the current level
+  let mut full: Vec<Range<u64>> = Vec::with_capacity(uniq_val_dens.len());
+  let mut i = 0_usize;
+  let mut acc = 0_f64;
+  for (ilevel, level) in sorted_levels {
+    while i < uniq_val_dens.len() && acc + uniq_val_dens[i].1 <= level {
+      acc += uniq_val_dens[i].1;
+      full.push(Hpx::<u64>::uniq_hpx_to_range(uniq_val_dens[i].0));
+      i += 1;
+    }
+    let mut result = full.clone();
+    if i < uniq_val_dens.len() && acc < level {
+      let (uniq, val, _) = uniq_val_dens[i];
+      if no_split {
+        if !strict {
+          result.push(Hpx::<u64>::uniq_hpx_to_range(uniq));
+        }
+      } else {
+        let (depth, icell) = Hpx::<u64>::from_uniq_hpx(uniq);
+        result = if reverse_decent {
+          reverse_recursive_descent(depth, icell, max_depth, val, strict, level - acc, result)
+        } else {
+          recursive_descent(depth, icell, max_depth, val, strict, level - acc, result)
+        };
+      }
+    }
+    let moc = RangeMOC::new(max_depth, HpxRanges::new_from(result));
+    let value = HpxMomIter::<u64, Hpx<u64>, f64, _>::new(
+      uniq_val_dens.iter().map(|(uniq, val, _)| (*uniq, *val)),
+    )
+    .sum_values_in_hpxmoc(&moc);
+    regions[ilevel] = Some(CredibleRegion {
+      level,
+      area_deg2: moc.coverage_percentage() * SKY_AREA_DEG2,
+      moc,
+      value,
+    });
+  }
+  let searched = position.and_then(|(lon, lat)| searched_region(&uniq_val_dens, lon, lat));
+  (regions.into_iter().flatten().collect(), searched)
+}
+
+/// Returns the searched region at the given position, the input cells being sorted by
+/// descending densities.
+fn searched_region(
+  sorted_uniq_val_dens: &[(u64, f64, f64)],
+  lon: f64,
+  lat: f64,
+) -> Option<SearchedRegion> {
+  let mut area_deg2 = 0_f64;
+  let mut value = 0_f64;
+  for (uniq, val, _) in sorted_uniq_val_dens {
+    let (depth, icell) = Hpx::<u64>::from_uniq_hpx(*uniq);
+    area_deg2 += SKY_AREA_DEG2 / (12_u64 << (depth << 1) as u32) as f64;
+    value += val;
+    if healpix::nested::hash(depth, lon, lat) == icell {
+      return Some(SearchedRegion { area_deg2, value });
+    }
+  }
+  None
+}
+
 // Recursively add cells to the result till the target_val is reached.
 fn recursive_descent<T, V>(
   depth: u8,
@@ -488,7 +618,7 @@ where
 mod tests {
   use std::u64;
 
-  use crate::elem::valuedcell::valued_cells_to_moc;
+  use crate::elem::valuedcell::{valued_cells_to_credible_regions, valued_cells_to_moc};
   use crate::elemset::range::HpxRanges;
   use crate::qty::{Hpx, MocQty};
 
@@ -539,4 +669,64 @@ mod tests {
 
     assert_eq!(nested_ranges, expect_nested_ranges);
   }
+
+  #[test]
+  fn test_credible_regions() {
+    // Base cells 0 to 3 containing 40%, 30%, 20% and 10% of the probability
+    let uniq_val_dens = vec![(4, 0.4, 4.0), (5, 0.3, 3.0), (6, 0.2, 2.0), (7, 0.1, 1.0)];
+    let (regions, searched) = valued_cells_to_credible_regions(
+      1,
+      uniq_val_dens,
+      &[0.9, 0.5],
+      true,
+      false,
+      false,
+      // (lon, lat) = (45, -60) deg, in base cell 8 (i.e. outside the 4 first base cells)
+      Some((45_f64.to_radians(), -60_f64.to_radians())),
+    );
+    assert_eq!(regions.len(), 2);
+    // Order of the input levels preserved
+    assert_eq!(regions[0].level, 0.9);
+    assert_eq!(regions[1].level, 0.5);
+    let shift = Hpx::<u64>::shift_from_depth_max(1);
+    // 0.5 = 0.4 + 0.1 => base cell 0 + 1 sub-cell (of value 0.075) of base cell 1 (strict)
+    assert_eq!(
+      regions[1]
+        .moc
+        .moc_ranges()
+        .iter()
+        .cloned()
+        .collect::<Vec<_>>(),
+      vec![0..5 << shift]
+    );
+    assert!((regions[1].value - 0.475).abs() < 1e-12);
+    assert!((regions[1].area_deg2 - 41252.96124941928 * 5.0 / 48.0).abs() < 1e-6);
+    // 0.9 = 0.4 + 0.3 + 0.2 => 3 base cells
+    assert_eq!(
+      regions[0]
+        .moc
+        .moc_ranges()
+        .iter()
+        .cloned()
+        .collect::<Vec<_>>(),
+      vec![0..12 << shift]
+    );
+    assert!((regions[0].value - 0.9).abs() < 1e-12);
+    assert!((regions[0].area_deg2 - 41252.96124941928 / 4.0).abs() < 1e-6);
+    // Position outside the map
+    assert_eq!(searched, None);
+    // Position in base cell 1
+    let (_, searched) = valued_cells_to_credible_regions(
+      1,
+      vec![(4, 0.4, 4.0), (5, 0.3, 3.0), (6, 0.2, 2.0), (7, 0.1, 1.0)],
+      &[],
+      true,
+      false,
+      false,
+      Some((135_f64.to_radians(), 45_f64.to_radians())),
+    );
+    let searched = searched.unwrap();
+    assert!((searched.value - 0.7).abs() < 1e-12);
+    assert!((searched.area_deg2 - 41252.96124941928 / 6.0).abs() < 1e-6);
+  }
 }
@@ -6,6 +6,7 @@ use std::ops::{Index, Range};
 use std::slice::Iter;
 
 use crate::idx::Idx;
+use crate::moc::range::{op::check::RangeIssue, RangeMOC};
 use crate::qty::{Bounded, Hpx, MocQty, Time};
 use crate::ranges::{BorrowedRanges, MergeOverlappingRangesIter, Ranges, SNORanges};
 
@@ -121,6 +122,12 @@ impl<T: Idx, Q: MocQty<T>> MocRanges<T, Q> {
     MocRanges(Ranges::new_unchecked(data), PhantomData)
   }
 
+  /// Checked version of [MocRanges::new_unchecked]: fails if the input ranges are empty,
+  /// unsorted, overlapping, contiguous or out of the quantity bounds.
+  pub fn try_new(data: Vec<Range<T>>) -> Result<Self, RangeIssue<T>> {
+    RangeMOC::try_new(Q::MAX_DEPTH, Self::new_unchecked(data)).map(RangeMOC::into_moc_ranges)
+  }
+
   /// Assumes (without checking!) that the input vector of range is already sorted **BUT**
   /// may contains overlapping (or consecutive) ranges.
   pub fn new_from_sorted(data: Vec<Range<T>>) -> Self {
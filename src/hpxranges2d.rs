@@ -40,13 +40,7 @@ impl<TT: Idx> TimeSpaceMoc<TT, TT> {
     depth_max_t: u8,
     depth_max_s: u8,
   ) -> RangeMOC2IteratorAdaptor<'_, TT, Time<TT>> {
-    RangeMOC2IteratorAdaptor {
-      depth_max_f: depth_max_t,
-      depth_max_s,
-      it_t: self.0.ranges2d.x.iter().peekable(),
-      it_s: self.0.ranges2d.y.iter().peekable(),
-      _phantom: PhantomData,
-    }
+    self.0.range_moc2_iter(depth_max_t, depth_max_s)
   }
 
   /// Returns the smallest time value in the 2DMOC
@@ -130,13 +124,7 @@ impl<TT: Idx> FreqSpaceMoc<TT, TT> {
     depth_max_f: u8,
     depth_max_s: u8,
   ) -> RangeMOC2IteratorAdaptor<'_, TT, Frequency<TT>> {
-    RangeMOC2IteratorAdaptor {
-      depth_max_f,
-      depth_max_s,
-      it_t: self.0.ranges2d.x.iter().peekable(),
-      it_s: self.0.ranges2d.y.iter().peekable(),
-      _phantom: PhantomData,
-    }
+    self.0.range_moc2_iter(depth_max_f, depth_max_s)
   }
 
   /// Returns the smallest frequency in the 2DMOC
@@ -855,14 +843,32 @@ impl From<CellMoc2Iter<u64, Time<u64>, u64, Hpx<u64>>> for HpxRanges2D<u64, Time
 }
 
 // Adaptor to write FITs
-pub struct RangeMOC2IteratorAdaptor<'a, T: Idx, Q1: MocQty<T>> {
+pub struct RangeMOC2IteratorAdaptor<'a, T: Idx, Q1: MocQty<T>, Q2: MocQty<T> = Hpx<T>> {
   depth_max_f: u8,
   depth_max_s: u8,
   it_t: Peekable<slice::Iter<'a, Range<T>>>,
   it_s: Peekable<slice::Iter<'a, Ranges<T>>>,
-  _phantom: PhantomData<Q1>,
+  _phantom: PhantomData<(Q1, Q2)>,
+}
+impl<'a, T: Idx, Q1: MocQty<T>, Q2: MocQty<T>> RangeMOC2IteratorAdaptor<'a, T, Q1, Q2> {
+  pub(crate) fn new(
+    depth_max_f: u8,
+    depth_max_s: u8,
+    it_t: slice::Iter<'a, Range<T>>,
+    it_s: slice::Iter<'a, Ranges<T>>,
+  ) -> Self {
+    Self {
+      depth_max_f,
+      depth_max_s,
+      it_t: it_t.peekable(),
+      it_s: it_s.peekable(),
+      _phantom: PhantomData,
+    }
+  }
 }
-impl<'a, T: Idx, Q1: MocQty<T>> HasTwoMaxDepth for RangeMOC2IteratorAdaptor<'a, T, Q1> {
+impl<'a, T: Idx, Q1: MocQty<T>, Q2: MocQty<T>> HasTwoMaxDepth
+  for RangeMOC2IteratorAdaptor<'a, T, Q1, Q2>
+{
   fn depth_max_1(&self) -> u8 {
     self.depth_max_f
   }
@@ -870,11 +876,19 @@ impl<'a, T: Idx, Q1: MocQty<T>> HasTwoMaxDepth for RangeMOC2IteratorAdaptor<'a,
     self.depth_max_s
   }
 }
-impl<'a, T: Idx, Q1: MocQty<T>> ZSorted for RangeMOC2IteratorAdaptor<'a, T, Q1> {}
-impl<'a, T: Idx, Q1: MocQty<T>> NonOverlapping for RangeMOC2IteratorAdaptor<'a, T, Q1> {}
-impl<'a, T: Idx, Q1: MocQty<T>> MOC2Properties for RangeMOC2IteratorAdaptor<'a, T, Q1> {}
-impl<'a, T: Idx, Q1: MocQty<T>> Iterator for RangeMOC2IteratorAdaptor<'a, T, Q1> {
-  type Item = RangeMOC2Elem<T, Q1, T, Hpx<T>>;
+impl<'a, T: Idx, Q1: MocQty<T>, Q2: MocQty<T>> ZSorted for RangeMOC2IteratorAdaptor<'a, T, Q1, Q2> {}
+impl<'a, T: Idx, Q1: MocQty<T>, Q2: MocQty<T>> NonOverlapping
+  for RangeMOC2IteratorAdaptor<'a, T, Q1, Q2>
+{
+}
+impl<'a, T: Idx, Q1: MocQty<T>, Q2: MocQty<T>> MOC2Properties
+  for RangeMOC2IteratorAdaptor<'a, T, Q1, Q2>
+{
+}
+impl<'a, T: Idx, Q1: MocQty<T>, Q2: MocQty<T>> Iterator
+  for RangeMOC2IteratorAdaptor<'a, T, Q1, Q2>
+{
+  type Item = RangeMOC2Elem<T, Q1, T, Q2>;
   fn next(&mut self) -> Option<Self::Item> {
     if let (Some(t_range), Some(s_ranges)) = (self.it_t.next(), self.it_s.next()) {
       let mut t = vec![t_range.clone()];
@@ -895,16 +909,16 @@ impl<'a, T: Idx, Q1: MocQty<T>> Iterator for RangeMOC2IteratorAdaptor<'a, T, Q1>
     }
   }
 }
-impl<'a, T: Idx, Q1: MocQty<T>>
+impl<'a, T: Idx, Q1: MocQty<T>, Q2: MocQty<T>>
   RangeMOC2Iterator<
     T,
     Q1,
     RangeMocIter<T, Q1>,
     T,
-    Hpx<T>,
-    RangeMocIter<T, Hpx<T>>,
-    RangeMOC2Elem<T, Q1, T, Hpx<T>>,
-  > for RangeMOC2IteratorAdaptor<'a, T, Q1>
+    Q2,
+    RangeMocIter<T, Q2>,
+    RangeMOC2Elem<T, Q1, T, Q2>,
+  > for RangeMOC2IteratorAdaptor<'a, T, Q1, Q2>
 {
 }
 
@@ -15,7 +15,10 @@ use healpix::{
 use crate::{
   deser::{
     self,
-    fits::{error::FitsError, hpx_cells_to_fits_ivoa, keywords, ranges_to_fits_ivoa},
+    fits::{
+      error::FitsError, hpx_cells_to_fits_ivoa, keywords, metadata::MocMetadata,
+      ranges_to_fits_ivoa, ranges_to_fits_ivoa_with_metadata,
+    },
   },
   elem::{cell::Cell, cellcellrange::CellOrCellRange, range::MocRange},
   idx::Idx,
@@ -27,7 +30,7 @@ use crate::{
     range::{
       op::{
         and::{and, AndRangeIter},
-        check::{check, CheckedIterator},
+        check::{check, try_check, CheckedIterator, RangeIssue, TryCheckedIterator},
         convert::{convert, ConvertIterator},
         degrade::{degrade, DegradeRangeIter},
         minus::{minus, MinusRangeIter},
@@ -241,6 +244,26 @@ pub trait RangeMOCIterator<T: Idx>: Sized + MOCProperties + Iterator<Item = Rang
     for _ in self.into_checked() {}
   }
 
+  /// Non-panicking version of `into_checked`: transform this iterator into an iterator returning
+  /// an error (and then stopping) at the first range which is not valid.
+  fn into_try_checked(self) -> TryCheckedIterator<T, Self::Qty, Self> {
+    try_check(self)
+  }
+
+  /// Non-panicking version of `check`: returns the first issue found in the iterator, if any.
+  fn try_check(self) -> Result<(), RangeIssue<T>> {
+    self.into_try_checked().try_for_each(|res| res.map(|_| ()))
+  }
+
+  /// Same as `into_range_moc`, but fails at the first range which is not valid (i.e. empty,
+  /// unsorted, overlapping or not merged, not aligned on the depth max or out of bounds).
+  /// To be used on iterators on untrusted data.
+  fn try_into_range_moc(self) -> Result<RangeMOC<T, Self::Qty>, RangeIssue<T>> {
+    let depth_max = self.depth_max();
+    let ranges = self.into_try_checked().collect::<Result<_, _>>()?;
+    Ok(RangeMOC::new(depth_max, ranges))
+  }
+
   fn to_fits_ivoa<W: Write>(
     self,
     moc_id: Option<String>,
@@ -250,6 +273,15 @@ pub trait RangeMOCIterator<T: Idx>: Sized + MOCProperties + Iterator<Item = Rang
     ranges_to_fits_ivoa(self, moc_id, moc_type, writer)
   }
 
+  /// Same as `to_fits_ivoa`, but writing the given metadata in the header.
+  fn to_fits_ivoa_with_metadata<W: Write>(
+    self,
+    metadata: &MocMetadata,
+    writer: W,
+  ) -> Result<(), FitsError> {
+    ranges_to_fits_ivoa_with_metadata(self, metadata, writer)
+  }
+
   fn into_range_moc(self) -> RangeMOC<T, Self::Qty> {
     RangeMOC::new(self.depth_max(), self.collect())
   }
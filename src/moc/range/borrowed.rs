@@ -1,10 +1,14 @@
 use std::{marker::PhantomData, ops::Range};
 
 use crate::{
-  elemset::range::BorrowedMocRanges,
+  elemset::range::{BorrowedMocRanges, MocRanges},
   idx::Idx,
-  moc::{range::RangeRefMocIter, HasMaxDepth, NonOverlapping, RangeMOCIntoIterator, ZSorted},
+  moc::{
+    range::{RangeMOC, RangeRefMocIter},
+    HasMaxDepth, NonOverlapping, RangeMOCIntoIterator, ZSorted,
+  },
   qty::MocQty,
+  ranges::Ranges,
 };
 
 pub struct BorrowedRangeMOC<'a, T: Idx, Q: MocQty<T>> {
@@ -16,6 +20,27 @@ impl<'a, T: Idx, Q: MocQty<T>> BorrowedRangeMOC<'a, T, Q> {
   pub fn new(depth_max: u8, ranges: BorrowedMocRanges<'a, T, Q>) -> Self {
     Self { depth_max, ranges }
   }
+
+  pub fn moc_ranges(&self) -> &BorrowedMocRanges<'a, T, Q> {
+    &self.ranges
+  }
+
+  /// Returns the number of ranges the MOC contains
+  pub fn len(&self) -> usize {
+    self.ranges.0 .0.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Copies the borrowed ranges into a new, owned, `RangeMOC`.
+  pub fn to_range_moc(&self) -> RangeMOC<T, Q> {
+    RangeMOC::new(
+      self.depth_max,
+      MocRanges::from(Ranges::new_unchecked(self.ranges.0 .0.to_vec())),
+    )
+  }
 }
 
 impl<'a, T: Idx, Q: MocQty<T>> HasMaxDepth for BorrowedRangeMOC<'a, T, Q> {
@@ -29,7 +29,10 @@ use healpix::{
 use crate::{
   deser::{
     ascii::AsciiError,
-    fits::{error::FitsError, from_fits_ivoa, keywords, MocIdxType, MocQtyType, MocType},
+    fits::{
+      error::FitsError, from_fits_ivoa, keywords, metadata::MocMetadata, MocIdxType, MocQtyType,
+      MocType,
+    },
   },
   elem::{cell::Cell, range::MocRange},
   elemset::{
@@ -48,6 +51,7 @@ use crate::{
       borrowed::BorrowedRangeMOC,
       op::{
         and::{and, AndRangeIter},
+        check::RangeIssue,
         merge::merge_sorted,
         minus::{minus, MinusRangeIter},
         multi_op::kway_or,
@@ -64,6 +68,7 @@ use crate::{
 };
 pub mod borrowed;
 pub mod op;
+pub mod polygons;
 
 /// Structure made to draw MOCs in AladinLite.
 /// It contains an HEALPix cell and the list of edges to be drawn.
@@ -141,6 +146,12 @@ impl<T: Idx, Q: MocQty<T>> RangeMOC<T, Q> {
       ranges,
     }
   }
+  /// Same as [RangeMOC::new], but fails if the given ranges are not valid, see
+  /// [RangeMOC::validate]. To be used with untrusted data.
+  pub fn try_new(depth_max: u8, ranges: MocRanges<T, Q>) -> Result<Self, RangeIssue<T>> {
+    let moc = Self::new(depth_max, ranges);
+    moc.validate().map(|()| moc)
+  }
   pub fn new_empty(depth_max: u8) -> Self {
     Self::new(depth_max, MocRanges::default())
   }
@@ -181,6 +192,12 @@ impl<T: Idx, Q: MocQty<T>> RangeMOC<T, Q> {
     self.ranges.0.iter().last().map(|r| r.end)
   }
 
+  /// Returns the first issue found in the MOC ranges (empty, unsorted, overlapping or contiguous
+  /// ranges, ranges not aligned on the MOC depth or out of bounds), if any.
+  pub fn validate(&self) -> Result<(), RangeIssue<T>> {
+    self.into_range_moc_iter().try_check()
+  }
+
   pub fn moc_ranges(&self) -> &MocRanges<T, Q> {
     &self.ranges
   }
@@ -309,6 +326,12 @@ impl<T: Idx, Q: MocQty<T>> RangeMOC<T, Q> {
     let ranges = self.ranges.intersection(&rhs.ranges);
     RangeMOC::new(depth_max, ranges)
   }
+  /// Same as [RangeMOC::and], but first validates both MOCs (see [RangeMOC::validate]).
+  pub fn try_and(&self, rhs: &RangeMOC<T, Q>) -> Result<RangeMOC<T, Q>, RangeIssue<T>> {
+    self.validate()?;
+    rhs.validate()?;
+    Ok(self.and(rhs))
+  }
   pub fn intersection(&self, rhs: &RangeMOC<T, Q>) -> RangeMOC<T, Q> {
     self.and(rhs)
   }
@@ -318,6 +341,12 @@ impl<T: Idx, Q: MocQty<T>> RangeMOC<T, Q> {
     let ranges = self.ranges.union(&rhs.ranges);
     RangeMOC::new(depth_max, ranges)
   }
+  /// Same as [RangeMOC::or], but first validates both MOCs (see [RangeMOC::validate]).
+  pub fn try_or(&self, rhs: &RangeMOC<T, Q>) -> Result<RangeMOC<T, Q>, RangeIssue<T>> {
+    self.validate()?;
+    rhs.validate()?;
+    Ok(self.or(rhs))
+  }
   pub fn union(&self, rhs: &RangeMOC<T, Q>) -> RangeMOC<T, Q> {
     self.or(rhs)
   }
@@ -334,12 +363,24 @@ impl<T: Idx, Q: MocQty<T>> RangeMOC<T, Q> {
     let ranges = xor(self.into_range_moc_iter(), rhs.into_range_moc_iter()).collect();
     RangeMOC::new(depth_max, ranges)
   }
+  /// Same as [RangeMOC::xor], but first validates both MOCs (see [RangeMOC::validate]).
+  pub fn try_xor(&self, rhs: &RangeMOC<T, Q>) -> Result<RangeMOC<T, Q>, RangeIssue<T>> {
+    self.validate()?;
+    rhs.validate()?;
+    Ok(self.xor(rhs))
+  }
 
   pub fn minus(&self, rhs: &RangeMOC<T, Q>) -> RangeMOC<T, Q> {
     let depth_max = self.depth_max.max(rhs.depth_max);
     let ranges = minus(self.into_range_moc_iter(), rhs.into_range_moc_iter()).collect();
     RangeMOC::new(depth_max, ranges)
   }
+  /// Same as [RangeMOC::minus], but first validates both MOCs (see [RangeMOC::validate]).
+  pub fn try_minus(&self, rhs: &RangeMOC<T, Q>) -> Result<RangeMOC<T, Q>, RangeIssue<T>> {
+    self.validate()?;
+    rhs.validate()?;
+    Ok(self.minus(rhs))
+  }
 
   pub fn degraded(&self, new_depth: u8) -> RangeMOC<T, Q> {
     let depth_max = self.depth_max.min(new_depth);
@@ -422,6 +463,21 @@ impl<T: Idx, Q: MocQty<T>> RangeMOC<T, Q> {
           .to_fits_ivoa(moc_id, moc_type, BufWriter::new(file))
       })
   }
+
+  /// Same as [RangeMOC::to_fits_file_ivoa], but writing the given metadata in the header.
+  pub fn to_fits_file_ivoa_with_metadata<P: AsRef<Path>>(
+    &self,
+    metadata: &MocMetadata,
+    path: P,
+  ) -> Result<(), FitsError> {
+    File::create(path.as_ref())
+      .map_err(FitsError::Io)
+      .and_then(|file| {
+        self
+          .into_range_moc_iter()
+          .to_fits_ivoa_with_metadata(metadata, BufWriter::new(file))
+      })
+  }
 }
 impl<T: Idx, Q: MocQty<T>> HasMaxDepth for RangeMOC<T, Q> {
   fn depth_max(&self) -> u8 {
@@ -1,4 +1,9 @@
-use std::ops::Range;
+use std::{
+  error::Error,
+  fmt::{self, Display},
+  marker::PhantomData,
+  ops::Range,
+};
 
 use crate::idx::Idx;
 use crate::moc::{HasMaxDepth, MOCProperties, NonOverlapping, RangeMOCIterator, ZSorted};
@@ -15,6 +20,256 @@ where
   CheckedIterator::new(it)
 }
 
+/// Issue found by [check_issues] or [try_check] in a range MOC iterator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RangeIssue<T: Idx> {
+  /// Range of given index which is empty or reversed
+  Empty { index: usize, range: Range<T> },
+  /// Range of given index starting before the end of the previous range
+  Unsorted {
+    index: usize,
+    prev: Range<T>,
+    curr: Range<T>,
+  },
+  /// Range of given index starting at the end of the previous range (both should be merged)
+  NotMerged {
+    index: usize,
+    prev: Range<T>,
+    curr: Range<T>,
+  },
+  /// Range of given index with bounds not aligned on cells at the MOC depth
+  NotAtDepth {
+    index: usize,
+    range: Range<T>,
+    depth: u8,
+  },
+  /// Range of given index exceeding the upper bound of the MOC quantity
+  OutOfBounds { index: usize, range: Range<T> },
+}
+
+impl<T: Idx> Display for RangeIssue<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      RangeIssue::Empty { index, range } => {
+        write!(f, "Range {} is empty: {:?}", index, range)
+      }
+      RangeIssue::Unsorted { index, prev, curr } => write!(
+        f,
+        "Range {} not sorted or overlapping the previous one: {:?} then {:?}",
+        index, prev, curr
+      ),
+      RangeIssue::NotMerged { index, prev, curr } => write!(
+        f,
+        "Range {} contiguous to the previous one (not merged): {:?} then {:?}",
+        index, prev, curr
+      ),
+      RangeIssue::NotAtDepth {
+        index,
+        range,
+        depth,
+      } => write!(
+        f,
+        "Range {} bounds not aligned on depth {} cells: {:?}",
+        index, depth, range
+      ),
+      RangeIssue::OutOfBounds { index, range } => {
+        write!(f, "Range {} out of the quantity bounds: {:?}", index, range)
+      }
+    }
+  }
+}
+
+impl<T: Idx> Error for RangeIssue<T> {}
+
+/// Non-panicking version of [check]: returns all the issues found in the given iterator, i.e.
+/// empty, unsorted, overlapping or non-merged ranges, ranges not aligned on the iterator depth
+/// and ranges exceeding the quantity upper bound.
+pub fn check_issues<T, Q, I>(it: I) -> Vec<RangeIssue<T>>
+where
+  T: Idx,
+  Q: MocQty<T>,
+  I: RangeMOCIterator<T, Qty = Q>,
+{
+  let checker = RangeChecker::<T, Q>::new(it.depth_max());
+  let mut issues = Vec::new();
+  let mut prev: Option<Range<T>> = None;
+  for (index, range) in it.enumerate() {
+    checker.issues(index, prev.as_ref(), &range, |issue| issues.push(issue));
+    prev = Some(range);
+  }
+  issues
+}
+
+/// Decorates the given iterator with an iterator returning an error (and then stopping) instead
+/// of panicking (see [check]) at the first range which is empty, not sorted, overlapping or
+/// contiguous to the previous one, not aligned on the iterator depth or out of the quantity
+/// bounds.
+pub fn try_check<T, Q, I>(it: I) -> TryCheckedIterator<T, Q, I>
+where
+  T: Idx,
+  Q: MocQty<T>,
+  I: RangeMOCIterator<T, Qty = Q>,
+{
+  TryCheckedIterator::new(it)
+}
+
+/// Performs the checks of a range with respect to the previous one, at a given depth.
+struct RangeChecker<T: Idx, Q: MocQty<T>> {
+  depth: u8,
+  mask: T,
+  upper_bound: T,
+  _q_type: PhantomData<Q>,
+}
+
+impl<T: Idx, Q: MocQty<T>> RangeChecker<T, Q> {
+  fn new(depth: u8) -> Self {
+    let depth = depth.min(Q::MAX_DEPTH);
+    let shift = Q::shift_from_depth_max(depth) as u32;
+    Self {
+      depth,
+      mask: !(!T::zero()).unsigned_shl(shift),
+      upper_bound: Q::n_cells_max(),
+      _q_type: PhantomData,
+    }
+  }
+
+  /// Calls `push` on each issue found for the range of given index.
+  fn issues<F>(&self, index: usize, prev: Option<&Range<T>>, range: &Range<T>, mut push: F)
+  where
+    F: FnMut(RangeIssue<T>),
+  {
+    if range.start >= range.end {
+      push(RangeIssue::Empty {
+        index,
+        range: range.clone(),
+      });
+    }
+    if range.end > self.upper_bound {
+      push(RangeIssue::OutOfBounds {
+        index,
+        range: range.clone(),
+      });
+    }
+    if (range.start & self.mask) != T::zero() || (range.end & self.mask) != T::zero() {
+      push(RangeIssue::NotAtDepth {
+        index,
+        range: range.clone(),
+        depth: self.depth,
+      });
+    }
+    if let Some(prev) = prev {
+      if range.start < prev.end {
+        push(RangeIssue::Unsorted {
+          index,
+          prev: prev.clone(),
+          curr: range.clone(),
+        });
+      } else if range.start == prev.end {
+        push(RangeIssue::NotMerged {
+          index,
+          prev: prev.clone(),
+          curr: range.clone(),
+        });
+      }
+    }
+  }
+
+  /// Returns the first issue found for the range of given index, if any.
+  fn first_issue(
+    &self,
+    index: usize,
+    prev: Option<&Range<T>>,
+    range: &Range<T>,
+  ) -> Option<RangeIssue<T>> {
+    let mut first = None;
+    self.issues(index, prev, range, |issue| {
+      first.get_or_insert(issue);
+    });
+    first
+  }
+}
+
+/// Iterator decorator returning the ranges of the decorated iterator as long as they are valid,
+/// see [try_check].
+/// Once an issue has been returned (as an `Err`), the iterator returns `None`.
+pub struct TryCheckedIterator<T, Q, I>
+where
+  T: Idx,
+  Q: MocQty<T>,
+  I: RangeMOCIterator<T, Qty = Q>,
+{
+  it: I,
+  checker: RangeChecker<T, Q>,
+  prev: Option<Range<T>>,
+  index: usize,
+  failed: bool,
+}
+
+impl<T, Q, I> TryCheckedIterator<T, Q, I>
+where
+  T: Idx,
+  Q: MocQty<T>,
+  I: RangeMOCIterator<T, Qty = Q>,
+{
+  pub fn new(it: I) -> TryCheckedIterator<T, Q, I> {
+    let checker = RangeChecker::new(it.depth_max());
+    TryCheckedIterator {
+      it,
+      checker,
+      prev: None,
+      index: 0,
+      failed: false,
+    }
+  }
+}
+
+impl<T, Q, I> HasMaxDepth for TryCheckedIterator<T, Q, I>
+where
+  T: Idx,
+  Q: MocQty<T>,
+  I: RangeMOCIterator<T, Qty = Q>,
+{
+  fn depth_max(&self) -> u8 {
+    self.it.depth_max()
+  }
+}
+
+impl<T, Q, I> Iterator for TryCheckedIterator<T, Q, I>
+where
+  T: Idx,
+  Q: MocQty<T>,
+  I: RangeMOCIterator<T, Qty = Q>,
+{
+  type Item = Result<Range<T>, RangeIssue<T>>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.failed {
+      return None;
+    }
+    let range = self.it.next()?;
+    let index = self.index;
+    self.index += 1;
+    match self.checker.first_issue(index, self.prev.as_ref(), &range) {
+      Some(issue) => {
+        self.failed = true;
+        Some(Err(issue))
+      }
+      None => {
+        self.prev = Some(range.clone());
+        Some(Ok(range))
+      }
+    }
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    if self.failed {
+      (0, Some(0))
+    } else {
+      (0, self.it.size_hint().1)
+    }
+  }
+}
+
 /// Iterator decorator made to ensure that the decorated iterator returns sorted non-overlapping
 /// ranges.
 /// If it is not the case, a call to the `next` method `panics`!
@@ -109,3 +364,105 @@ where
     self.it.peek_last()
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::{
+    elemset::range::MocRanges,
+    moc::{range::RangeMOC, RangeMOCIntoIterator},
+    qty::Hpx,
+  };
+
+  #[test]
+  fn test_check_issues() {
+    let shift = Hpx::<u64>::shift_from_depth_max(2);
+    let ranges = vec![
+      0..1 << shift,
+      1 << shift..2 << shift,
+      3 << shift..3 << shift,
+      5 << shift..(7 << shift) + 1,
+      4 << shift..6 << shift,
+    ];
+    let moc = RangeMOC::<u64, Hpx<u64>>::new(2, MocRanges::new_unchecked(ranges));
+    let issues = check_issues((&moc).into_range_moc_iter());
+    assert_eq!(
+      issues,
+      vec![
+        RangeIssue::NotMerged {
+          index: 1,
+          prev: 0..1 << shift,
+          curr: 1 << shift..2 << shift
+        },
+        RangeIssue::Empty {
+          index: 2,
+          range: 3 << shift..3 << shift
+        },
+        RangeIssue::NotAtDepth {
+          index: 3,
+          range: 5 << shift..(7 << shift) + 1,
+          depth: 2
+        },
+        RangeIssue::Unsorted {
+          index: 4,
+          prev: 5 << shift..(7 << shift) + 1,
+          curr: 4 << shift..6 << shift
+        },
+      ]
+    );
+    let valid = RangeMOC::<u64, Hpx<u64>>::new(2, MocRanges::new_unchecked(vec![0..1 << shift]));
+    assert!(check_issues(valid.into_range_moc_iter()).is_empty());
+  }
+
+  #[test]
+  fn test_try_check() {
+    let shift = Hpx::<u64>::shift_from_depth_max(2);
+    let ranges = vec![
+      0..1 << shift,
+      3 << shift..4 << shift,
+      2 << shift..3 << shift,
+      6 << shift..7 << shift,
+    ];
+    let moc = RangeMOC::<u64, Hpx<u64>>::new(2, MocRanges::new_unchecked(ranges));
+    let res: Vec<_> = try_check((&moc).into_range_moc_iter()).collect();
+    assert_eq!(
+      res,
+      vec![
+        Ok(0..1 << shift),
+        Ok(3 << shift..4 << shift),
+        Err(RangeIssue::Unsorted {
+          index: 2,
+          prev: 3 << shift..4 << shift,
+          curr: 2 << shift..3 << shift
+        }),
+      ]
+    );
+    assert!((&moc).into_range_moc_iter().try_check().is_err());
+    assert!((&moc).into_range_moc_iter().try_into_range_moc().is_err());
+    assert!(moc.validate().is_err());
+    let valid = RangeMOC::<u64, Hpx<u64>>::try_new(
+      2,
+      MocRanges::new_unchecked(vec![0..1 << shift, 3 << shift..4 << shift]),
+    )
+    .unwrap();
+    assert_eq!(
+      (&valid).into_range_moc_iter().try_into_range_moc(),
+      Ok(valid.clone())
+    );
+    assert!(valid.try_or(&moc).is_err());
+    assert_eq!(valid.try_and(&valid), Ok(valid.clone()));
+    // Not aligned on depth 2
+    assert_eq!(
+      RangeMOC::<u64, Hpx<u64>>::try_new(2, MocRanges::new_unchecked(vec![0..1])).unwrap_err(),
+      RangeIssue::NotAtDepth {
+        index: 0,
+        range: 0..1,
+        depth: 2
+      }
+    );
+    // Out of bounds
+    assert!(MocRanges::<u64, Hpx<u64>>::try_new(vec![0..u64::MAX]).is_err());
+    assert!(MocRanges::<u64, Hpx<u64>>::try_new(vec![0..1, 2..3]).is_ok());
+  }
+}
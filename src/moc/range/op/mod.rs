@@ -3,6 +3,7 @@
 pub mod check;
 pub mod convert;
 pub mod merge;
+pub mod repair;
 
 pub mod degrade;
 pub mod not; // <=> complement
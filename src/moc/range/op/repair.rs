@@ -0,0 +1,290 @@
+//! Tolerant normalisation of possibly malformed MOCs (e.g. MOCs written by third-party tools):
+//! elements are sorted, overlapping and contiguous elements are merged, bounds are clipped and
+//! cells deeper than the MOC depth are degraded.
+//! Contrary to [check](super::check::check), the repair functions never panic: they return a
+//! valid MOC together with a report of what has been fixed.
+
+use std::{
+  fmt::{self, Display},
+  ops::Range,
+};
+
+use crate::{
+  elem::{cell::Cell, range::MocRange},
+  elemset::range::MocRanges,
+  idx::Idx,
+  moc::range::RangeMOC,
+  qty::MocQty,
+};
+
+/// Summary of the fixes made by [repair_ranges] or [repair_cells].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepairReport {
+  /// Input depth, larger than the quantity maximum depth, which has been clipped
+  pub clipped_depth: Option<u8>,
+  /// Number of cells deeper than the MOC depth, degraded to the MOC depth
+  pub n_too_deep: usize,
+  /// Number of empty or reversed ranges, removed
+  pub n_empty: usize,
+  /// Number of ranges exceeding the quantity upper bound, clipped (or removed)
+  pub n_out_of_bounds: usize,
+  /// Number of ranges with bounds not aligned on the MOC depth cells, enlarged
+  pub n_not_at_depth: usize,
+  /// Number of elements starting before the previous element, sorted
+  pub n_unsorted: usize,
+  /// Number of elements overlapping (or included in) other elements, merged
+  pub n_overlapping: usize,
+  /// Number of elements contiguous to the previous element, merged
+  pub n_contiguous: usize,
+}
+
+impl RepairReport {
+  /// Returns `true` if nothing had to be fixed.
+  pub fn is_clean(&self) -> bool {
+    *self == Self::default()
+  }
+}
+
+impl Display for RepairReport {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if self.is_clean() {
+      return write!(f, "Nothing to repair.");
+    }
+    let mut lines = Vec::new();
+    if let Some(depth) = self.clipped_depth {
+      lines.push(format!(
+        "depth {} larger than the max depth: clipped",
+        depth
+      ));
+    }
+    for (n, msg) in [
+      (
+        self.n_too_deep,
+        "cell(s) deeper than the MOC depth: degraded",
+      ),
+      (self.n_empty, "empty or reversed range(s): removed"),
+      (
+        self.n_out_of_bounds,
+        "range(s) out of the quantity bounds: clipped",
+      ),
+      (
+        self.n_not_at_depth,
+        "range(s) not aligned on the MOC depth: enlarged",
+      ),
+      (self.n_unsorted, "unsorted element(s): sorted"),
+      (
+        self.n_overlapping,
+        "overlapping or redundant element(s): merged",
+      ),
+      (self.n_contiguous, "contiguous element(s): merged"),
+    ] {
+      if n > 0 {
+        lines.push(format!("{} {}", n, msg));
+      }
+    }
+    write!(f, "{}", lines.join("\n"))
+  }
+}
+
+/// Builds a valid range MOC from a list of ranges (at the quantity max depth) possibly empty,
+/// unsorted, overlapping, not aligned on the given depth or exceeding the quantity upper bound.
+///
+/// # Params
+/// * `depth_max`: the MOC depth (clipped to `Q::MAX_DEPTH` if larger)
+/// * `ranges`: the possibly malformed ranges
+pub fn repair_ranges<T, Q, I>(depth_max: u8, ranges: I) -> (RangeMOC<T, Q>, RepairReport)
+where
+  T: Idx,
+  Q: MocQty<T>,
+  I: IntoIterator<Item = Range<T>>,
+{
+  let mut report = RepairReport::default();
+  let depth_max = clip_depth::<T, Q>(depth_max, &mut report);
+  let moc = repair(depth_max, ranges, true, &mut report);
+  (moc, report)
+}
+
+/// Builds a valid range MOC from a list of cells possibly unsorted, overlapping (redundant
+/// sub-cells) or deeper than the given depth (such cells being degraded to the given depth).
+/// Since a list of cells has no canonical order (e.g. NUNIQ vs Z-order), unsorted and
+/// contiguous cells are not reported.
+///
+/// # Params
+/// * `depth_max`: the MOC depth (clipped to `Q::MAX_DEPTH` if larger)
+/// * `cells`: the possibly malformed cells
+pub fn repair_cells<T, Q, I>(depth_max: u8, cells: I) -> (RangeMOC<T, Q>, RepairReport)
+where
+  T: Idx,
+  Q: MocQty<T>,
+  I: IntoIterator<Item = Cell<T>>,
+{
+  let mut report = RepairReport::default();
+  let depth_max = clip_depth::<T, Q>(depth_max, &mut report);
+  let mut n_too_deep = 0;
+  let ranges: Vec<Range<T>> = cells
+    .into_iter()
+    .map(|cell| {
+      let cell = if cell.depth > depth_max {
+        n_too_deep += 1;
+        let shift = (Q::DIM * (cell.depth - depth_max)) as u32;
+        Cell::new(depth_max, cell.idx.unsigned_shr(shift))
+      } else {
+        cell
+      };
+      MocRange::<T, Q>::from(cell).0
+    })
+    .collect();
+  report.n_too_deep = n_too_deep;
+  // Cells have no canonical order and contiguous cells are legit: both are not reported
+  let moc = repair(depth_max, ranges, false, &mut report);
+  (moc, report)
+}
+
+fn clip_depth<T: Idx, Q: MocQty<T>>(depth_max: u8, report: &mut RepairReport) -> u8 {
+  if depth_max > Q::MAX_DEPTH {
+    report.clipped_depth = Some(depth_max);
+    Q::MAX_DEPTH
+  } else {
+    depth_max
+  }
+}
+
+fn repair<T, Q, I>(
+  depth_max: u8,
+  ranges: I,
+  report_order: bool,
+  report: &mut RepairReport,
+) -> RangeMOC<T, Q>
+where
+  T: Idx,
+  Q: MocQty<T>,
+  I: IntoIterator<Item = Range<T>>,
+{
+  let shift = Q::shift_from_depth_max(depth_max) as u32;
+  let mask = !(!T::zero()).unsigned_shl(shift);
+  let upper_bound = Q::n_cells_max();
+  // Filter, clip and align
+  let mut ranges: Vec<Range<T>> = ranges
+    .into_iter()
+    .filter_map(|mut range| {
+      if range.start >= range.end {
+        report.n_empty += 1;
+        return None;
+      }
+      if range.end > upper_bound {
+        report.n_out_of_bounds += 1;
+        if range.start >= upper_bound {
+          return None;
+        }
+        range.end = upper_bound;
+      }
+      if (range.start & mask) != T::zero() || (range.end & mask) != T::zero() {
+        report.n_not_at_depth += 1;
+        // The upper bound being a multiple of the cell size at any depth, no overflow possible
+        range.start &= !mask;
+        range.end = ((range.end - T::one()) | mask) + T::one();
+      }
+      Some(range)
+    })
+    .collect();
+  // Sort
+  let n_unsorted = ranges
+    .windows(2)
+    .filter(|w| w[1].start < w[0].start)
+    .count();
+  if n_unsorted > 0 {
+    ranges.sort_unstable_by_key(|r| r.start);
+  }
+  if report_order {
+    report.n_unsorted = n_unsorted;
+  }
+  // Merge
+  let mut merged: Vec<Range<T>> = Vec::with_capacity(ranges.len());
+  for range in ranges {
+    match merged.last_mut() {
+      Some(last) if range.start < last.end => {
+        report.n_overlapping += 1;
+        if range.end > last.end {
+          last.end = range.end;
+        }
+      }
+      Some(last) if range.start == last.end => {
+        if report_order {
+          report.n_contiguous += 1;
+        }
+        last.end = range.end;
+      }
+      _ => merged.push(range),
+    }
+  }
+  RangeMOC::new(depth_max, MocRanges::new_unchecked(merged))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::{
+    moc::{range::op::check::check_issues, RangeMOCIntoIterator},
+    qty::{Hpx, Time},
+  };
+
+  #[test]
+  fn test_repair_ranges() {
+    let shift = Hpx::<u64>::shift_from_depth_max(2);
+    let ranges = vec![
+      5 << shift..(7 << shift) + 1,
+      3 << shift..3 << shift,
+      0..1 << shift,
+      1 << shift..2 << shift,
+      4 << shift..6 << shift,
+      (191 << shift) + 3..u64::MAX,
+    ];
+    let (moc, report) = repair_ranges::<u64, Hpx<u64>, _>(2, ranges);
+    assert_eq!(
+      moc.moc_ranges().iter().cloned().collect::<Vec<_>>(),
+      vec![
+        0..2 << shift,
+        4 << shift..8 << shift,
+        191 << shift..192 << shift
+      ]
+    );
+    assert_eq!(
+      report,
+      RepairReport {
+        n_empty: 1,
+        n_out_of_bounds: 1,
+        n_not_at_depth: 2,
+        n_unsorted: 1,
+        n_overlapping: 1,
+        n_contiguous: 1,
+        ..Default::default()
+      }
+    );
+    assert!(check_issues((&moc).into_range_moc_iter()).is_empty());
+    // A valid MOC is left untouched
+    let (moc2, report) = repair_ranges::<u64, Hpx<u64>, _>(2, moc.moc_ranges().iter().cloned());
+    assert!(report.is_clean());
+    assert_eq!(moc2, moc);
+  }
+
+  #[test]
+  fn test_repair_cells() {
+    let cells = vec![
+      Cell::new(3, 17_u64),
+      Cell::new(1, 3),
+      Cell::new(5, 50 * 16),
+      Cell::new(4, 17 * 4 + 1),
+    ];
+    let (moc, report) = repair_cells::<u64, Hpx<u64>, _>(3, cells);
+    let expected = repair_cells::<u64, Hpx<u64>, _>(3, vec![Cell::new(1, 3), Cell::new(3, 17)]).0;
+    assert_eq!(moc, expected);
+    assert_eq!(report.n_too_deep, 2);
+    assert_eq!(report.n_unsorted, 0);
+    assert_eq!(report.n_overlapping, 2);
+    // Depth larger than the quantity max depth
+    let (moc, report) = repair_cells::<u64, Time<u64>, _>(70, vec![Cell::new(61, 1)]);
+    assert_eq!(moc.depth_max(), Time::<u64>::MAX_DEPTH);
+    assert_eq!(report.clipped_depth, Some(70));
+  }
+}
@@ -0,0 +1,806 @@
+//! Computation of the polygons (exterior ring + holes) delimiting a S-MOC.
+
+use std::{
+  collections::HashMap,
+  f64::consts::{FRAC_PI_2, PI},
+};
+
+use healpix::{
+  compass_point::MainWind,
+  nested::{self, custom_polygon_coverage},
+  sph_geom::ContainsSouthPoleMethod,
+};
+
+use crate::{
+  idx::Idx,
+  moc::{
+    range::{CellSelection, RangeMOC},
+    CellMOCIntoIterator, CellMOCIterator, RangeMOCIterator,
+  },
+  qty::Hpx,
+};
+
+const TWICE_PI: f64 = 2.0 * PI;
+
+/// Scale factor used to snap the vertices (unit vector coordinates) on a grid, so that a same
+/// vertex computed from different cells gets the same identifier.
+/// The grid step (~6e-11 rad) is much larger than the vertices computation errors (~1e-15 rad)
+/// and much smaller than the smallest distance between two vertices at depth 29 (~1e-9 rad).
+const SNAP_FACTOR: f64 = (1_u64 << 34) as f64;
+/// Shift, in degrees, applied to the latitude of the polygon vertices located on a pole.
+const POLE_SHIFT_DEG: f64 = 1e-9;
+/// Latitude, in degrees, of the transition between the HEALPix equatorial region and polar caps.
+const TRANSITION_LAT_DEG: f64 = 41.810_314_895_778_6;
+
+/// Polygon delimiting a connected component of a S-MOC.
+///
+/// Vertices coordinates are `(lon, lat)` tuples, in radians. In the `(lon, lat)` plane, the
+/// exterior ring is counterclockwise and the holes are clockwise (the MOC is always on the left
+/// of the rings), the rings are not explicitly closed (i.e. the last vertex differs from the first
+/// one).
+/// Longitudes are kept continuous along a ring: they may be negative or larger than `2pi` when
+/// the ring crosses the `lon = 0` meridian.
+/// Rings going around a pole are closed passing by that pole (with two vertices of latitude
+/// `+-pi/2`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct MocPolygon {
+  /// The exterior ring of the polygon
+  pub exterior: Vec<(f64, f64)>,
+  /// The (possibly empty) list of holes of the polygon
+  pub holes: Vec<Vec<(f64, f64)>>,
+}
+
+impl MocPolygon {
+  /// Polygon covering the full sky.
+  pub fn full_sky() -> Self {
+    Self {
+      exterior: vec![
+        (0.0, -FRAC_PI_2),
+        (TWICE_PI, -FRAC_PI_2),
+        (TWICE_PI, FRAC_PI_2),
+        (0.0, FRAC_PI_2),
+      ],
+      holes: Default::default(),
+    }
+  }
+}
+
+impl MocPolygon {
+  /// Splits the polygon along the given meridians (longitudes in radians, in the continuous
+  /// longitude range of the polygon), returning the polygons located between two consecutive
+  /// meridians. A hole crossed by a meridian becomes part of the resulting exterior rings.
+  pub fn split_at_meridians(&self, lons: &[f64]) -> Vec<MocPolygon> {
+    let mut bounds: Vec<f64> = lons.to_vec();
+    bounds.sort_by(|a, b| a.total_cmp(b));
+    bounds.dedup();
+    let mut polygons = Vec::new();
+    for i in 0..=bounds.len() {
+      let west = if i == 0 {
+        f64::NEG_INFINITY
+      } else {
+        bounds[i - 1]
+      };
+      let east = bounds.get(i).copied().unwrap_or(f64::INFINITY);
+      polygons.extend(self.clip_to_strip(west, east));
+    }
+    polygons
+  }
+
+  /// Returns the polygons resulting from the intersection of the polygon with the strip
+  /// `west <= lon <= east`.
+  /// The parts of the rings inside the strip are chains starting and ending on the strip borders.
+  /// The polygon being on the left of its rings, the chains are joined going north along the east
+  /// border, and south along the west border.
+  fn clip_to_strip(&self, west: f64, east: f64) -> Vec<MocPolygon> {
+    let mut chains: Vec<Vec<(f64, f64)>> = Vec::new();
+    let mut exteriors: Vec<Vec<(f64, f64)>> = Vec::new();
+    let mut holes: Vec<Vec<(f64, f64)>> = Vec::new();
+    for (i, ring) in std::iter::once(&self.exterior)
+      .chain(self.holes.iter())
+      .enumerate()
+    {
+      let is_in = |lon: f64| west <= lon && lon <= east;
+      match ring.iter().position(|(lon, _)| !is_in(*lon)) {
+        None if i == 0 => exteriors.push(ring.clone()),
+        None => holes.push(ring.clone()),
+        Some(first_out) => chains.extend(clip_ring_to_strip(ring, first_out, west, east)),
+      }
+    }
+    // Join the chains
+    let mut used = vec![false; chains.len()];
+    for start in 0..chains.len() {
+      if used[start] {
+        continue;
+      }
+      let mut ring = Vec::new();
+      let mut curr = start;
+      loop {
+        used[curr] = true;
+        ring.extend_from_slice(&chains[curr]);
+        let (end_lon, end_lat) = chains[curr][chains[curr].len() - 1];
+        let going_north = end_lon == east;
+        let next = (0..chains.len())
+          .filter(|j| {
+            let (lon, lat) = chains[*j][0];
+            lon == end_lon
+              && if going_north {
+                lat > end_lat || (lat == end_lat && *j != curr)
+              } else {
+                lat < end_lat || (lat == end_lat && *j != curr)
+              }
+          })
+          .min_by(|a, b| {
+            let (lat_a, lat_b) = (chains[*a][0].1, chains[*b][0].1);
+            if going_north {
+              lat_a.total_cmp(&lat_b)
+            } else {
+              lat_b.total_cmp(&lat_a)
+            }
+          });
+        match next {
+          Some(next) if !used[next] => curr = next,
+          _ => break,
+        }
+      }
+      let ring = remove_spikes(ring);
+      if ring.len() >= 3 {
+        exteriors.push(ring);
+      }
+    }
+    let mut polygons: Vec<MocPolygon> = exteriors
+      .into_iter()
+      .map(|exterior| MocPolygon {
+        exterior,
+        holes: Default::default(),
+      })
+      .collect();
+    for hole in holes {
+      if let Some(polygon) = polygons
+        .iter_mut()
+        .find(|polygon| contains(&polygon.exterior, hole[0]))
+      {
+        polygon.holes.push(hole);
+      }
+    }
+    polygons
+  }
+}
+
+/// Returns the parts of the ring located in the strip `west <= lon <= east`, the vertex of
+/// index `first_out` being outside the strip.
+fn clip_ring_to_strip(
+  ring: &[(f64, f64)],
+  first_out: usize,
+  west: f64,
+  east: f64,
+) -> Vec<Vec<(f64, f64)>> {
+  let n = ring.len();
+  let push = |chain: &mut Vec<(f64, f64)>, vertex: (f64, f64)| {
+    if chain.last() != Some(&vertex) {
+      chain.push(vertex);
+    }
+  };
+  let mut chains = Vec::new();
+  let mut chain: Vec<(f64, f64)> = Vec::new();
+  for k in 0..n {
+    let (lon1, lat1) = ring[(first_out + k) % n];
+    let (lon2, lat2) = ring[(first_out + k + 1) % n];
+    let crossing = |lon: f64| (lon, lat1 + (lon - lon1) / (lon2 - lon1) * (lat2 - lat1));
+    // Border crossed when going out of the strip (or when coming into the strip)
+    let border = |lon: f64| if lon > east { east } else { west };
+    let (in1, in2) = (west <= lon1 && lon1 <= east, west <= lon2 && lon2 <= east);
+    match (in1, in2) {
+      (true, true) => push(&mut chain, (lon2, lat2)),
+      (false, true) => {
+        chain.clear();
+        push(&mut chain, crossing(border(lon1)));
+        push(&mut chain, (lon2, lat2));
+      }
+      (true, false) => {
+        push(&mut chain, crossing(border(lon2)));
+        if chain.len() >= 2 {
+          chains.push(std::mem::take(&mut chain));
+        } else {
+          chain.clear();
+        }
+      }
+      (false, false) => {
+        // Edge going through the strip
+        if (lon1 < west && lon2 > east) || (lon1 > east && lon2 < west) {
+          chains.push(vec![crossing(border(lon1)), crossing(border(lon2))]);
+        }
+      }
+    }
+  }
+  chains
+}
+
+impl<T: Idx> RangeMOC<T, Hpx<T>> {
+  /// Returns the polygons delimiting the MOC, one polygon per connected component (considering
+  /// only direct neighbours, i.e. cells sharing an edge) with its possible holes.
+  /// The vertices are the vertices of the MOC border cells at the MOC depth max, so you may want
+  /// to degrade the MOC before calling this method to reduce the number of vertices.
+  ///
+  /// # Remark
+  /// A component both containing the north and the south poles and having a hole going all around
+  /// the sky (like a band) leads to two polygons (e.g. the full sky with a hole plus a polygon
+  /// inside the hole).
+  /// Polygons are sorted by decreasing exterior ring area (in the `(lon, lat)` plane), so that
+  /// a polygon located in the hole of an other polygon comes after it.
+  pub fn to_polygons(&self) -> Vec<MocPolygon> {
+    if self.is_empty() {
+      return vec![];
+    }
+    let mut polygons: Vec<(f64, MocPolygon)> = self
+      .split_into_joint_mocs(false)
+      .into_iter()
+      .flat_map(|cell_moc| {
+        let component: RangeMOC<T, Hpx<T>> =
+          cell_moc.into_cell_moc_iter().ranges().into_range_moc();
+        component_polygons(&component)
+      })
+      .map(|polygon| (signed_area(&polygon.exterior), polygon))
+      .collect();
+    // A polygon located in the hole of an other polygon has a smaller area
+    polygons.sort_by(|(a1, _), (a2, _)| a2.total_cmp(a1));
+    polygons.into_iter().map(|(_, polygon)| polygon).collect()
+  }
+}
+
+/// Oriented elementary edge, the MOC being on its left.
+struct Edge {
+  from: usize,
+  to: usize,
+  /// Cell, at the MOC depth max, the edge belongs to
+  hash: u64,
+}
+
+/// Assigns a same identifier to vertices equal up to rounding errors.
+#[derive(Default)]
+struct Vertices {
+  ids: HashMap<(i64, i64, i64), usize>,
+  coos: Vec<(f64, f64)>,
+}
+
+impl Vertices {
+  fn id(&mut self, (lon, lat): (f64, f64)) -> usize {
+    let (sin_lon, cos_lon) = lon.sin_cos();
+    let (sin_lat, cos_lat) = lat.sin_cos();
+    let key = (
+      (cos_lat * cos_lon * SNAP_FACTOR).round() as i64,
+      (cos_lat * sin_lon * SNAP_FACTOR).round() as i64,
+      (sin_lat * SNAP_FACTOR).round() as i64,
+    );
+    for dx in -1..=1 {
+      for dy in -1..=1 {
+        for dz in -1..=1 {
+          if let Some(id) = self.ids.get(&(key.0 + dx, key.1 + dy, key.2 + dz)) {
+            return *id;
+          }
+        }
+      }
+    }
+    let id = self.coos.len();
+    self.coos.push((lon, lat));
+    self.ids.insert(key, id);
+    id
+  }
+}
+
+/// Compute the polygon(s) of a single connected component.
+fn component_polygons<T: Idx>(moc: &RangeMOC<T, Hpx<T>>) -> Vec<MocPolygon> {
+  let layer = nested::get(moc.depth_max());
+  // Build the list of elementary edges separating a MOC cell from a non-MOC cell
+  let mut vertices = Vertices::default();
+  let mut edges: Vec<Edge> = Vec::new();
+  for idx in moc.internal_border_iter().flatten_to_fixed_depth_cells() {
+    let hash = idx.to_u64();
+    let neigs = layer.neighbours(hash, false);
+    let [s, e, n, w] = layer.vertices(hash);
+    // Counterclockwise order, the cell being on the left of each edge
+    for (side, from, to) in [
+      (MainWind::SE, s, e),
+      (MainWind::NE, e, n),
+      (MainWind::NW, n, w),
+      (MainWind::SW, w, s),
+    ] {
+      if let Some(nh) = neigs.get(side) {
+        if !moc.contains_depth_max_val(&T::from_u64(*nh)) {
+          let from = vertices.id(from);
+          let to = vertices.id(to);
+          edges.push(Edge { from, to, hash });
+        }
+      }
+    }
+  }
+  if edges.is_empty() {
+    // No border: full sky
+    return vec![MocPolygon::full_sky()];
+  }
+  // Chain the edges to form closed rings
+  let rings = chain_edges(&edges);
+  // Rings going around a pole are closed passing by the pole the component contains (if any).
+  let closing_pole_lat = if moc.is_in(0.0, FRAC_PI_2) {
+    FRAC_PI_2
+  } else {
+    -FRAC_PI_2
+  };
+  let mut exteriors: Vec<(Vec<(f64, f64)>, f64)> = Vec::new();
+  let mut holes: Vec<Vec<(f64, f64)>> = Vec::new();
+  for ring in rings {
+    let ring = unwrap_ring(&ring, &vertices.coos, closing_pole_lat);
+    let area = signed_area(&ring);
+    if area > 0.0 {
+      exteriors.push((ring, area));
+    } else {
+      holes.push(ring);
+    }
+  }
+  // Assign each hole to the smallest exterior containing it
+  let mut polygons: Vec<MocPolygon> = exteriors
+    .iter()
+    .map(|(exterior, _)| MocPolygon {
+      exterior: exterior.clone(),
+      holes: Default::default(),
+    })
+    .collect();
+  let mut full_sky: Option<MocPolygon> = None;
+  for hole in holes {
+    // Middle of the first edge, not on any other ring
+    let (lon_a, lat_a) = hole[0];
+    let (lon_b, lat_b) = hole[1];
+    let point = (0.5 * (lon_a + lon_b), 0.5 * (lat_a + lat_b));
+    let container = exteriors
+      .iter()
+      .enumerate()
+      .filter(|(_, (exterior, _))| {
+        [0.0, -TWICE_PI, TWICE_PI]
+          .iter()
+          .any(|shift| contains(exterior, (point.0 + shift, point.1)))
+      })
+      .min_by(|(_, (_, a1)), (_, (_, a2))| a1.total_cmp(a2))
+      .map(|(i, _)| i);
+    match container {
+      Some(i) => polygons[i].holes.push(hole),
+      None => full_sky
+        .get_or_insert_with(MocPolygon::full_sky)
+        .holes
+        .push(hole),
+    }
+  }
+  polygons.extend(full_sky);
+  polygons
+}
+
+/// Chain the edges to build closed rings (of vertex identifiers).
+/// When several edges start from a same vertex (i.e. when two cells only share a vertex), we
+/// follow the edge of the current cell so that the rings stay simple.
+fn chain_edges(edges: &[Edge]) -> Vec<Vec<usize>> {
+  let mut outgoing: HashMap<usize, Vec<usize>> = HashMap::with_capacity(edges.len());
+  for (i, edge) in edges.iter().enumerate() {
+    outgoing.entry(edge.from).or_default().push(i);
+  }
+  let mut used = vec![false; edges.len()];
+  let mut rings = Vec::new();
+  for start in 0..edges.len() {
+    if used[start] {
+      continue;
+    }
+    used[start] = true;
+    let mut ring = vec![edges[start].from];
+    let mut curr = start;
+    loop {
+      let vertex = edges[curr].to;
+      let next = outgoing
+        .get(&vertex)
+        .into_iter()
+        .flatten()
+        .copied()
+        .filter(|i| !used[*i] || *i == start)
+        .min_by_key(|i| edges[*i].hash != edges[curr].hash);
+      match next {
+        Some(next) if next != start => {
+          used[next] = true;
+          ring.push(vertex);
+          curr = next;
+        }
+        _ => break,
+      }
+    }
+    rings.push(ring);
+  }
+  rings
+}
+
+fn is_pole(lat: f64) -> bool {
+  FRAC_PI_2 - lat.abs() < 1e-12
+}
+
+/// Transforms a ring of vertex identifiers into a list of coordinates having continuous
+/// longitudes. A vertex located on a pole is replaced by two vertices (at the pole latitude) having
+/// the longitudes of the previous and next vertices.
+/// If the ring goes around a pole, it is closed passing by the pole of given latitude.
+fn unwrap_ring(ring: &[usize], coos: &[(f64, f64)], closing_pole_lat: f64) -> Vec<(f64, f64)> {
+  // A cell has 4 distinct vertices, only one of them possibly being a pole
+  let first = ring
+    .iter()
+    .position(|id| !is_pole(coos[*id].1))
+    .unwrap_or(0);
+  let (lon_start, lat_start) = coos[ring[first]];
+  let mut unwrapped = Vec::with_capacity(ring.len() + 4);
+  unwrapped.push((lon_start, lat_start));
+  let mut prev_lon = lon_start;
+  let mut curr_lon = lon_start;
+  let mut pending_pole: Option<f64> = None;
+  for k in 1..=ring.len() {
+    let (lon, lat) = coos[ring[(first + k) % ring.len()]];
+    if is_pole(lat) {
+      pending_pole = Some(lat);
+      continue;
+    }
+    let delta = lon - prev_lon;
+    let delta = match pending_pole.take() {
+      // MOC on the left: going west around the north pole
+      Some(pole_lat) if pole_lat > 0.0 => {
+        let d = delta.rem_euclid(TWICE_PI);
+        let d = if d > 0.0 { d - TWICE_PI } else { d };
+        unwrapped.push((curr_lon, pole_lat));
+        unwrapped.push((curr_lon + d, pole_lat));
+        d
+      }
+      // MOC on the left: going east around the south pole
+      Some(pole_lat) => {
+        let d = delta.rem_euclid(TWICE_PI);
+        unwrapped.push((curr_lon, pole_lat));
+        unwrapped.push((curr_lon + d, pole_lat));
+        d
+      }
+      None => {
+        let d = delta.rem_euclid(TWICE_PI);
+        if d > PI {
+          d - TWICE_PI
+        } else {
+          d
+        }
+      }
+    };
+    curr_lon += delta;
+    prev_lon = lon;
+    if k < ring.len() {
+      unwrapped.push((curr_lon, lat));
+    }
+  }
+  if (curr_lon - lon_start).abs() > PI {
+    // The ring goes around a pole
+    unwrapped.push((curr_lon, lat_start));
+    unwrapped.push((curr_lon, closing_pole_lat));
+    unwrapped.push((lon_start, closing_pole_lat));
+  }
+  unwrapped
+}
+
+/// Signed area of the ring in the `(lon, lat)` plane (positive if counterclockwise).
+fn signed_area(ring: &[(f64, f64)]) -> f64 {
+  let n = ring.len();
+  0.5
+    * (0..n)
+      .map(|i| {
+        let (x1, y1) = ring[i];
+        let (x2, y2) = ring[(i + 1) % n];
+        x1 * y2 - x2 * y1
+      })
+      .sum::<f64>()
+}
+
+/// Tells whether the given point is inside the ring, in the `(lon, lat)` plane.
+pub(crate) fn contains(ring: &[(f64, f64)], (x, y): (f64, f64)) -> bool {
+  let n = ring.len();
+  let mut inside = false;
+  let mut j = n - 1;
+  for i in 0..n {
+    let (xi, yi) = ring[i];
+    let (xj, yj) = ring[j];
+    if (yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+      inside = !inside;
+    }
+    j = i;
+  }
+  inside
+}
+
+/// Removes the duplicated vertices and the zero-width spikes (vertices at which the ring goes
+/// back on its previous edge) of a ring, e.g. resulting from joining ring parts along a common
+/// line.
+pub(crate) fn remove_spikes(mut ring: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+  let mut i = 0;
+  while ring.len() >= 3 && i < ring.len() {
+    let n = ring.len();
+    let (xa, ya) = ring[(i + n - 1) % n];
+    let (xb, yb) = ring[i];
+    let (xc, yc) = ring[(i + 1) % n];
+    let (dx1, dy1, dx2, dy2) = (xb - xa, yb - ya, xc - xb, yc - yb);
+    let cross = dx1 * dy2 - dy1 * dx2;
+    let dot = dx1 * dx2 + dy1 * dy2;
+    let norms = dx1.hypot(dy1) * dx2.hypot(dy2);
+    if (xa, ya) == (xb, yb) || (cross.abs() <= 1e-12 * norms && dot < 0.0) {
+      ring.remove(i);
+      // Removing a spike may create a new one at the previous vertex
+      i = i.saturating_sub(1);
+    } else {
+      i += 1;
+    }
+  }
+  ring
+}
+
+/// Build the S-MOC of given depth covering the given polygon, the inverse of
+/// [RangeMOC::to_polygons].
+///
+/// # Input
+/// - `exterior`: the exterior ring vertices, `(lon, lat)` in degrees, not explicitly closed
+/// - `holes`: the holes vertices, `(lon, lat)` in degrees, not explicitly closed
+/// - `depth`: the MOC depth
+/// - `selection`: select BMOC cells to keep in the MOC
+///
+/// # Remark
+/// Edges are great-circle arcs, except edges along a pole latitude (`+-90`) which are considered
+/// to go around the pole when their longitude difference is at least 360 degrees.
+pub(crate) fn polygon_coverage_deg(
+  exterior: &[(f64, f64)],
+  holes: &[Vec<(f64, f64)>],
+  depth: u8,
+  selection: CellSelection,
+) -> Result<RangeMOC<u64, Hpx<u64>>, String> {
+  // Cells partially overlapping a hole must be kept when selecting all cells, and removed when
+  // selecting only the cells fully inside the polygon.
+  let hole_selection = match selection {
+    CellSelection::All => CellSelection::Inside,
+    CellSelection::Inside => CellSelection::All,
+    CellSelection::Border => {
+      return Ok(
+        polygon_coverage_deg(exterior, holes, depth, CellSelection::All)?.minus(
+          &polygon_coverage_deg(exterior, holes, depth, CellSelection::Inside)?,
+        ),
+      )
+    }
+  };
+  let mut moc = ring_coverage_deg(exterior, depth, selection)?;
+  for hole in holes {
+    moc = moc.minus(&ring_coverage_deg(hole, depth, hole_selection)?);
+  }
+  Ok(moc)
+}
+
+fn is_pole_deg(lat_deg: f64) -> bool {
+  90.0 - lat_deg.abs() < 1e-9
+}
+
+/// A polygon vertex exactly on the `lon = 0` meridian is a degenerate case when looking for the
+/// polygon special points (edges crossing the meridian), so we move it by a negligible amount.
+/// The given longitude, in radians, is in `[0, 2pi[`, rounding errors on both sides of the
+/// meridian being also taken into account.
+pub(crate) fn off_zero_meridian(lon: f64) -> f64 {
+  if lon < 1e-14 || TWICE_PI - lon < 1e-14 {
+    1e-15
+  } else {
+    lon
+  }
+}
+
+/// Middle of the great-circle arc linking two non-antipodal vertices, `(lon, lat)` in degrees.
+fn great_circle_middle_deg((lon1, lat1): (f64, f64), (lon2, lat2): (f64, f64)) -> (f64, f64) {
+  let to_xyz = |lon: f64, lat: f64| {
+    let (lon, lat) = (lon.to_radians(), lat.to_radians());
+    (lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin())
+  };
+  let (x1, y1, z1) = to_xyz(lon1, lat1);
+  let (x2, y2, z2) = to_xyz(lon2, lat2);
+  let (x, y, z) = (x1 + x2, y1 + y2, z1 + z2);
+  (
+    y.atan2(x).to_degrees(),
+    z.atan2((x * x + y * y).sqrt()).to_degrees(),
+  )
+}
+
+/// Tells whether both vertices, in radians, are equal up to rounding errors.
+fn same_vertex((lon1, lat1): &(f64, f64), (lon2, lat2): &(f64, f64)) -> bool {
+  let dlon = (lon1 - lon2).rem_euclid(TWICE_PI);
+  (lat1 - lat2).abs() < 1e-12 && (dlon < 1e-12 || TWICE_PI - dlon < 1e-12)
+}
+
+/// Build the S-MOC of given depth covering the polygon defined by the given ring, `(lon, lat)`
+/// in degrees, not explicitly closed.
+fn ring_coverage_deg(
+  vertices: &[(f64, f64)],
+  depth: u8,
+  selection: CellSelection,
+) -> Result<RangeMOC<u64, Hpx<u64>>, String> {
+  if vertices.len() < 3 {
+    return Err(String::from(
+      "a ring must have at least 3 distinct vertices",
+    ));
+  }
+  let n = vertices.len();
+  let edges = || (0..n).map(|i| (vertices[i], vertices[(i + 1) % n]));
+  // Full sky polygon, like [[0, -90], [360, -90], [360, 90], [0, 90], [0, -90]]
+  let area = 0.5
+    * edges()
+      .map(|((x1, y1), (x2, y2))| x1 * y2 - x2 * y1)
+      .sum::<f64>();
+  if area.abs() >= 360.0 * 180.0 - 1e-6 {
+    return Ok(RangeMOC::new_full_domain(depth));
+  }
+  // Look for an edge along a pole going all around the sky
+  let enclosed_pole = edges()
+    .find(|((lon1, lat1), (lon2, lat2))| {
+      is_pole_deg(*lat1)
+        && is_pole_deg(*lat2)
+        && lat1 * lat2 > 0.0
+        && (lon2 - lon1).abs() >= 360.0 - 1e-9
+    })
+    .map(|((_, lat), _)| lat);
+  // Add a vertex on edges linking both poles (antipodal vertices), vertices every 45 degrees
+  // on edges along a pole (not to pass by the pole once the vertices moved away from the pole),
+  // and a vertex in the middle of the edges linking both polar caps (the polygon special points
+  // finder failing on such edges, e.g. long meridian edges resulting from an antimeridian split)
+  let mut vertices_deg: Vec<(f64, f64)> = Vec::with_capacity(n + 2);
+  for ((lon1, lat1), (lon2, lat2)) in edges() {
+    vertices_deg.push((lon1, lat1));
+    if lat1 * lat2 < 0.0
+      && lat1.abs() > TRANSITION_LAT_DEG
+      && lat2.abs() > TRANSITION_LAT_DEG
+      && !(is_pole_deg(lat1) && is_pole_deg(lat2))
+    {
+      vertices_deg.push(great_circle_middle_deg((lon1, lat1), (lon2, lat2)));
+    } else if is_pole_deg(lat1) && is_pole_deg(lat2) {
+      if lat1 * lat2 < 0.0 {
+        vertices_deg.push((0.5 * (lon1 + lon2), 0.0));
+      } else {
+        let n_steps = ((lon2 - lon1).abs() / 45.0).ceil() as usize;
+        let step = (lon2 - lon1) / n_steps.max(1) as f64;
+        vertices_deg.extend((1..n_steps).map(|i| (lon1 + i as f64 * step, lat1)));
+      }
+    }
+  }
+  // Convert in radians:
+  // * removing the vertices located on an enclosed pole (the pole being then selected by the
+  //   polygon method),
+  // * slightly moving away from the pole the other vertices located on a pole (the longitude of a
+  //   pole being meaningless, the edges from or to a pole are then along meridians),
+  // * removing duplicated consecutive vertices.
+  let mut vertices_rad: Vec<(f64, f64)> = Vec::with_capacity(vertices_deg.len());
+  for (lon, lat) in vertices_deg {
+    let lat = if is_pole_deg(lat) {
+      if enclosed_pole.is_some_and(|pole_lat| pole_lat * lat > 0.0) {
+        continue;
+      }
+      lat.signum() * (90.0 - POLE_SHIFT_DEG)
+    } else {
+      lat
+    };
+    let lon = off_zero_meridian(lon.to_radians().rem_euclid(TWICE_PI));
+    let vertex = (lon, lat.to_radians());
+    if vertices_rad
+      .last()
+      .is_none_or(|prev| !same_vertex(prev, &vertex))
+    {
+      vertices_rad.push(vertex);
+    }
+  }
+  while vertices_rad.len() > 1
+    && same_vertex(&vertices_rad[0], &vertices_rad[vertices_rad.len() - 1])
+  {
+    vertices_rad.pop();
+  }
+  if vertices_rad.len() < 3 {
+    return Err(String::from(
+      "a ring must have at least 3 distinct vertices",
+    ));
+  }
+  let method = match enclosed_pole {
+    Some(lat) if lat > 0.0 => ContainsSouthPoleMethod::DoNotContainsSouthPole,
+    Some(_) => ContainsSouthPoleMethod::ContainsSouthPole,
+    None => ContainsSouthPoleMethod::Default,
+  };
+  let bmoc = custom_polygon_coverage(depth, &vertices_rad, &method, true);
+  Ok(RangeMOC::from((bmoc, selection)))
+}
+
+#[cfg(test)]
+mod tests {
+  use std::f64::consts::FRAC_PI_2;
+
+  use super::{signed_area, MocPolygon};
+  use crate::{
+    moc::range::{CellSelection, RangeMOC},
+    qty::Hpx,
+  };
+
+  #[test]
+  fn test_polygons_cone() {
+    let moc: RangeMOC<u64, Hpx<u64>> = RangeMOC::from_cone(1.0, 0.5, 0.1, 8, 2, CellSelection::All);
+    let polygons = moc.to_polygons();
+    assert_eq!(polygons.len(), 1);
+    assert!(polygons[0].holes.is_empty());
+    assert!(polygons[0].exterior.len() > 4);
+    assert!(super::contains(&polygons[0].exterior, (1.0, 0.5)));
+  }
+
+  #[test]
+  fn test_polygons_with_hole_and_disjoint() {
+    let outer: RangeMOC<u64, Hpx<u64>> =
+      RangeMOC::from_cone(1.0, 0.5, 0.1, 8, 2, CellSelection::All);
+    let inner: RangeMOC<u64, Hpx<u64>> =
+      RangeMOC::from_cone(1.0, 0.5, 0.03, 8, 2, CellSelection::All);
+    let other: RangeMOC<u64, Hpx<u64>> =
+      RangeMOC::from_cone(4.0, -0.5, 0.05, 8, 2, CellSelection::All);
+    let moc = outer.minus(&inner).or(&other);
+    let mut polygons = moc.to_polygons();
+    assert_eq!(polygons.len(), 2);
+    polygons.sort_by_key(|p| p.holes.len());
+    assert_eq!(polygons[0].holes.len(), 0);
+    assert_eq!(polygons[1].holes.len(), 1);
+  }
+
+  #[test]
+  fn test_polygons_polar_cap() {
+    let moc: RangeMOC<u64, Hpx<u64>> =
+      RangeMOC::from_cone(0.0, FRAC_PI_2, 0.2, 6, 2, CellSelection::All);
+    let polygons = moc.to_polygons();
+    assert_eq!(polygons.len(), 1);
+    assert!(polygons[0].holes.is_empty());
+    assert!(polygons[0]
+      .exterior
+      .iter()
+      .any(|(_, lat)| *lat == FRAC_PI_2));
+    // Complement: the region south of the cap border
+    let polygons = moc.not().to_polygons();
+    assert_eq!(polygons.len(), 1);
+    assert!(polygons[0].holes.is_empty());
+    assert!(polygons[0]
+      .exterior
+      .iter()
+      .any(|(_, lat)| *lat == -FRAC_PI_2));
+  }
+
+  #[test]
+  fn test_polygons_band() {
+    let north: RangeMOC<u64, Hpx<u64>> =
+      RangeMOC::from_cone(0.0, FRAC_PI_2, 1.0, 5, 2, CellSelection::All);
+    let south: RangeMOC<u64, Hpx<u64>> =
+      RangeMOC::from_cone(0.0, -FRAC_PI_2, 1.0, 5, 2, CellSelection::All);
+    let moc = north.or(&south).not();
+    let polygons = moc.to_polygons();
+    assert_eq!(polygons.len(), 1);
+    assert_eq!(polygons[0].holes.len(), 1);
+  }
+
+  #[test]
+  fn test_polygons_split_at_meridians() {
+    let cone: RangeMOC<u64, Hpx<u64>> =
+      RangeMOC::from_cone(1.0, 0.5, 0.1, 8, 2, CellSelection::All);
+    let hole = RangeMOC::from_cone(1.0, 0.5, 0.03, 8, 2, CellSelection::All);
+    let polygons = cone.minus(&hole).to_polygons();
+    assert_eq!(polygons.len(), 1);
+    let polygon = &polygons[0];
+    let area = |p: &MocPolygon| {
+      signed_area(&p.exterior) + p.holes.iter().map(|h| signed_area(h)).sum::<f64>()
+    };
+    // Meridian crossing the hole: two polygons without hole
+    let split = polygon.split_at_meridians(&[1.0]);
+    assert_eq!(split.len(), 2);
+    assert!(split.iter().all(|p| p.holes.is_empty()));
+    assert!((split.iter().map(area).sum::<f64>() - area(polygon)).abs() < 1e-12);
+    // Meridian not crossing the hole
+    let split = polygon.split_at_meridians(&[1.08]);
+    assert_eq!(split.len(), 2);
+    assert_eq!(split.iter().map(|p| p.holes.len()).sum::<usize>(), 1);
+    assert!((split.iter().map(area).sum::<f64>() - area(polygon)).abs() < 1e-12);
+  }
+
+  #[test]
+  fn test_polygons_full_sky() {
+    let moc: RangeMOC<u64, Hpx<u64>> = RangeMOC::new_full_domain(3);
+    assert_eq!(moc.to_polygons(), vec![super::MocPolygon::full_sky()]);
+  }
+}
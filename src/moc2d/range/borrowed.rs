@@ -0,0 +1,126 @@
+use std::{marker::PhantomData, ops::Range};
+
+use crate::{
+  idx::Idx,
+  moc::range::borrowed::BorrowedRangeMOC,
+  moc2d::{
+    range::{RangeMOC2, RangeMOC2Elem},
+    HasTwoMaxDepth,
+  },
+  qty::MocQty,
+  ranges::{BorrowedRanges, SNORanges},
+};
+
+/// A MOC2 made of Range elements borrowing its data, e.g. from a memory mapped file.
+/// All the ranges (of both dimensions) are stored in a single slice, and the elements are
+/// defined by a slice of cumulative offsets: the ranges of the `i`-th element are
+/// `ranges[offsets[2i]..offsets[2i + 1]]` in the first dimension and
+/// `ranges[offsets[2i + 1]..offsets[2i + 2]]` in the second dimension.
+pub struct BorrowedRangeMOC2<'a, T: Idx, Q: MocQty<T>, R: MocQty<T>> {
+  depth_max_l: u8,
+  depth_max_r: u8,
+  offsets: &'a [u64],
+  ranges: &'a [Range<T>],
+  _qties: PhantomData<(Q, R)>,
+}
+
+impl<'a, T: Idx, Q: MocQty<T>, R: MocQty<T>> BorrowedRangeMOC2<'a, T, Q, R> {
+  /// # Warning
+  /// We do not check that:
+  /// * `offsets` is made of `2 * n_elems + 1` increasing values, starting at 0 and
+  ///   ending at `ranges.len()`
+  /// * the ranges of each element are sorted and non-overlapping
+  /// * the first dimension ranges of the elements are sorted and non-overlapping
+  pub fn new(depth_max_l: u8, depth_max_r: u8, offsets: &'a [u64], ranges: &'a [Range<T>]) -> Self {
+    Self {
+      depth_max_l,
+      depth_max_r,
+      offsets,
+      ranges,
+      _qties: PhantomData,
+    }
+  }
+
+  /// Returns the number of elements, i.e. of couples of (dim 1 MOC, dim 2 MOC).
+  pub fn n_elems(&self) -> usize {
+    self.offsets.len() >> 1
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.n_elems() == 0
+  }
+
+  /// The total number of ranges in both dimensions
+  pub fn n_ranges(&self) -> u64 {
+    self.ranges.len() as u64
+  }
+
+  /// Returns the `i`-th element.
+  /// # Panics
+  /// If `i >= self.n_elems()`.
+  pub fn elem(&self, i: usize) -> (BorrowedRangeMOC<'a, T, Q>, BorrowedRangeMOC<'a, T, R>) {
+    let from = self.offsets[i << 1] as usize;
+    let mid = self.offsets[(i << 1) + 1] as usize;
+    let to = self.offsets[(i << 1) + 2] as usize;
+    (
+      BorrowedRangeMOC::new(
+        self.depth_max_l,
+        BorrowedRanges(&self.ranges[from..mid]).into(),
+      ),
+      BorrowedRangeMOC::new(
+        self.depth_max_r,
+        BorrowedRanges(&self.ranges[mid..to]).into(),
+      ),
+    )
+  }
+
+  /// Iterates over the elements.
+  pub fn elems(
+    &self,
+  ) -> impl Iterator<Item = (BorrowedRangeMOC<'a, T, Q>, BorrowedRangeMOC<'a, T, R>)> + '_ {
+    (0..self.n_elems()).map(move |i| self.elem(i))
+  }
+
+  /// The values are the values at Q::MAX_DEPTH and R::MAX_DEPTH respectively
+  pub fn contains_val(&self, val_left: &T, val_right: &T) -> bool {
+    let mut lo = 0;
+    let mut hi = self.n_elems();
+    while lo < hi {
+      let mid = (lo + hi) >> 1;
+      let (moc_l, moc_r) = self.elem(mid);
+      let ranges_l = moc_l.moc_ranges().0 .0;
+      match (ranges_l.first(), ranges_l.last()) {
+        (Some(first), Some(last)) => {
+          if *val_left < first.start {
+            hi = mid;
+          } else if *val_left >= last.end {
+            lo = mid + 1;
+          } else {
+            return moc_l.moc_ranges().contains_val(val_left)
+              && moc_r.moc_ranges().contains_val(val_right);
+          }
+        }
+        _ => return false,
+      }
+    }
+    false
+  }
+
+  /// Copies the borrowed data into a new, owned, `RangeMOC2`.
+  pub fn to_range_moc2(&self) -> RangeMOC2<T, Q, T, R> {
+    let elems = self
+      .elems()
+      .map(|(moc_l, moc_r)| RangeMOC2Elem::new(moc_l.to_range_moc(), moc_r.to_range_moc()))
+      .collect();
+    RangeMOC2::new(self.depth_max_l, self.depth_max_r, elems)
+  }
+}
+
+impl<'a, T: Idx, Q: MocQty<T>, R: MocQty<T>> HasTwoMaxDepth for BorrowedRangeMOC2<'a, T, Q, R> {
+  fn depth_max_1(&self) -> u8 {
+    self.depth_max_l
+  }
+  fn depth_max_2(&self) -> u8 {
+    self.depth_max_r
+  }
+}
@@ -19,6 +19,7 @@ use crate::{
   qty::{Hpx, MocQty, Time},
 };
 
+pub mod borrowed;
 pub mod op;
 
 /// One element of a MOC2 made of Range elements
@@ -55,6 +56,15 @@ impl<T: Idx, Q: MocQty<T>, U: Idx, R: MocQty<U>> RangeMOC2Elem<T, Q, U, R> {
     (self.moc_l, self.moc_r)
   }
 
+  /// Returns the MOC on the first dimension
+  pub fn moc_l(&self) -> &RangeMOC<T, Q> {
+    &self.moc_l
+  }
+  /// Returns the MOC on the second dimension
+  pub fn moc_r(&self) -> &RangeMOC<U, R> {
+    &self.moc_r
+  }
+
   pub fn eq_without_depth(&self, rhs: &Self) -> bool {
     self.moc_l.eq_without_depth(&rhs.moc_l) && self.moc_r.eq_without_depth(&rhs.moc_r)
   }
@@ -171,6 +181,10 @@ impl<T: Idx, Q: MocQty<T>, U: Idx, R: MocQty<U>> RangeMOC2<T, Q, U, R> {
     self.elems.is_empty()
   }
 
+  pub fn elems(&self) -> &[RangeMOC2Elem<T, Q, U, R>] {
+    &self.elems
+  }
+
   pub fn min_index_left(&self) -> Option<T> {
     self.elems.first().and_then(|elem| elem.first_index_left())
   }
@@ -342,6 +356,35 @@ impl RangeMOC2<u64, Frequency<u64>, u64, Hpx<u64>> {
   }
 }
 
+impl RangeMOC2<u64, Time<u64>, u64, Frequency<u64>> {
+  pub fn new_empty(depth_time: u8, depth_freq: u8) -> Self {
+    Self::new(depth_time, depth_freq, Default::default())
+  }
+
+  /// Time in microseconds since JD=0
+  /// Frequency in Hz
+  pub fn from_time_and_freq_in_hz<I: Iterator<Item = (u64, f64)>>(
+    depth_time: u8,
+    depth_freq: u8,
+    val_it: I,
+    buf_capacity: Option<usize>,
+  ) -> Self {
+    let shift_time = Time::<u64>::shift_from_depth_max(depth_time);
+    let shift_freq = Frequency::<u64>::shift_from_depth_max(depth_freq);
+    Self::from_fixed_depth_cells(
+      depth_time,
+      depth_freq,
+      val_it.map(move |(us_since_jd0, freq_hz)| {
+        (
+          us_since_jd0 >> shift_time,
+          Frequency::<u64>::freq2hash(freq_hz) >> shift_freq,
+        )
+      }),
+      buf_capacity,
+    )
+  }
+}
+
 impl<T, Q, U, R> HasTwoMaxDepth for RangeMOC2<T, Q, U, R>
 where
   T: Idx,
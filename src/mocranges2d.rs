@@ -5,6 +5,7 @@ use rayon::prelude::*;
 
 use crate::{
   elemset::range::MocRanges,
+  hpxranges2d::RangeMOC2IteratorAdaptor,
   idx::Idx,
   moc::{range::RangeMocIter, RangeMOCIntoIterator, RangeMOCIterator},
   moc2d::{range::RangeMOC2Elem, RangeMOC2ElemIt, RangeMOC2Iterator},
@@ -270,6 +271,33 @@ where
   }
 }
 
+impl<T, Q1, Q2> Moc2DRanges<T, Q1, T, Q2>
+where
+  T: Idx,
+  Q1: MocQty<T>,
+  Q2: MocQty<T>,
+{
+  /// Returns an iterator on the 2D-MOC elements of the coverage (i.e. the first dimension
+  /// ranges sharing the same second dimension ranges).
+  ///
+  /// # Arguments
+  ///
+  /// * `depth_max_1` - The depth of the first dimension MOCs
+  /// * `depth_max_2` - The depth of the second dimension MOCs
+  pub fn range_moc2_iter(
+    &self,
+    depth_max_1: u8,
+    depth_max_2: u8,
+  ) -> RangeMOC2IteratorAdaptor<'_, T, Q1, Q2> {
+    RangeMOC2IteratorAdaptor::new(
+      depth_max_1,
+      depth_max_2,
+      self.ranges2d.x.iter(),
+      self.ranges2d.y.iter(),
+    )
+  }
+}
+
 impl<TT, T, ST, S> PartialEq for Moc2DRanges<TT, T, ST, S>
 where
   TT: Idx,
@@ -288,8 +316,9 @@ mod tests {
 
   use crate::hpxranges2d::{HpxRanges2D, TimeSpaceMoc};
   use crate::idx::Idx;
+  use crate::moc::range::RangeMOC;
   use crate::mocranges2d::Moc2DRanges;
-  use crate::qty::{Hpx, Time};
+  use crate::qty::{Frequency, Hpx, MocQty, Time};
   use crate::ranges::ranges2d::SNORanges2D;
   use crate::ranges::Ranges;
 
@@ -456,4 +485,34 @@ mod tests {
     let coverage_expect = new_time_space_moc(t_expect, s_expect);
     assert_eq!(coverage, coverage_expect);
   }
+
+  #[test]
+  #[allow(clippy::single_range_in_vec_init)]
+  fn time_freq_intersection_iter() {
+    type TimeFreqRanges = Moc2DRanges<u64, Time<u64>, u64, Frequency<u64>>;
+    let (depth_t, depth_f) = (Time::<u64>::MAX_DEPTH, Frequency::<u64>::MAX_DEPTH);
+    let left = TimeFreqRanges::new(
+      vec![0..10, 10..20],
+      vec![
+        Ranges::<u64>::new_unchecked(vec![0..4]),
+        Ranges::<u64>::new_unchecked(vec![0..8]),
+      ],
+    );
+    let right = TimeFreqRanges::new(vec![5..15], vec![Ranges::<u64>::new_unchecked(vec![2..6])]);
+    let elems: Vec<_> = left
+      .intersection(&right)
+      .range_moc2_iter(depth_t, depth_f)
+      .map(|elem| elem.mocs())
+      .collect();
+    let expected = vec![(5..10, 2..4), (10..15, 2..6)]
+      .into_iter()
+      .map(|(t, f)| {
+        (
+          RangeMOC::new(depth_t, Ranges::new_unchecked(vec![t]).into()),
+          RangeMOC::new(depth_f, Ranges::new_unchecked(vec![f]).into()),
+        )
+      })
+      .collect::<Vec<(RangeMOC<u64, Time<u64>>, RangeMOC<u64, Frequency<u64>>)>>();
+    assert_eq!(elems, expected);
+  }
 }
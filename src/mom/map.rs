@@ -0,0 +1,300 @@
+//! Owned HEALPix multi-order map (MOM), providing resampling operations: to a fixed depth
+//! (skymap), to a coarser depth, onto the cells of a MOC, or merging uniform regions.
+//! As in the rest of the [mom](super) module, values are proportional to the cell area:
+//! splitting a cell distributes its value evenly among its sub-cells while merging cells sums
+//! their values, so that the sum of the values is conserved.
+
+use std::{iter::Copied, ops::Range, slice::Iter, vec::IntoIter};
+
+use crate::{
+  idx::Idx,
+  moc::{range::RangeMOC, RangeMOCIntoIterator, RangeMOCIterator},
+  mom::{HpxMOMIterator, HpxMomIter, Value},
+  qty::{Hpx, MocQty},
+};
+
+/// A HEALPix multi-order map, i.e. a set of non-overlapping `(uniq, value)` pairs, the value
+/// being proportional to the cell area.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultiOrderMap<T: Idx, V: Value<T>> {
+  depth_max: u8,
+  elems: Vec<(T, V)>,
+}
+
+impl<T: Idx, V: Value<T>> MultiOrderMap<T, V> {
+  /// # Params
+  /// * `depth_max`: the MOM depth, larger or equal to the depth of the deepest cell
+  /// * `elems`: the non-overlapping `(uniq, value)` pairs, in any order
+  pub fn new(depth_max: u8, elems: Vec<(T, V)>) -> Self {
+    Self { depth_max, elems }
+  }
+
+  /// Builds a MOM from the values of an implicit, `NESTED` ordered, all-sky skymap of the
+  /// given depth (one cell per pixel).
+  ///
+  /// # Panics
+  /// If the number of values does not equal the number of HEALPix cells at the given depth.
+  pub fn from_skymap(depth: u8, values: Vec<V>) -> Self {
+    assert_eq!(
+      num::cast::<usize, T>(values.len()),
+      Some(Hpx::<T>::n_cells(depth)),
+      "Wrong number of skymap values"
+    );
+    let elems = values
+      .into_iter()
+      .enumerate()
+      .map(|(ipix, value)| (Hpx::<T>::uniq_hpx(depth, num::cast(ipix).unwrap()), value))
+      .collect();
+    Self::new(depth, elems)
+  }
+
+  pub fn depth_max(&self) -> u8 {
+    self.depth_max
+  }
+
+  pub fn len(&self) -> usize {
+    self.elems.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.elems.is_empty()
+  }
+
+  pub fn elems(&self) -> &[(T, V)] {
+    &self.elems
+  }
+
+  pub fn into_elems(self) -> Vec<(T, V)> {
+    self.elems
+  }
+
+  #[allow(clippy::type_complexity)]
+  pub fn iter(&self) -> HpxMomIter<T, Hpx<T>, V, Copied<Iter<'_, (T, V)>>> {
+    HpxMomIter::new(self.elems.iter().copied())
+  }
+
+  pub fn into_hpx_mom_iter(self) -> HpxMomIter<T, Hpx<T>, V, IntoIter<(T, V)>> {
+    HpxMomIter::new(self.elems.into_iter())
+  }
+
+  /// Resamples the MOM at the given fixed depth, see [HpxMOMIterator::to_skymap].
+  pub fn to_skymap(&self, depth: u8) -> Result<Vec<V>, String> {
+    self.iter().to_skymap(depth)
+  }
+
+  /// Degrades the MOM to the given depth: cells deeper than `new_depth` are merged into their
+  /// super-cell at `new_depth`, the value of the super-cell being the sum of the values of its
+  /// sub-cells. Other cells are left unchanged.
+  /// The output cells are sorted by `uniq` index.
+  pub fn degrade(self, new_depth: u8) -> Self {
+    if new_depth >= self.depth_max {
+      return self;
+    }
+    let mut elems: Vec<(T, V)> = self
+      .elems
+      .into_iter()
+      .map(|(uniq, value)| {
+        let (depth, ipix) = Hpx::<T>::from_uniq_hpx(uniq);
+        if depth > new_depth {
+          let twice_dd = ((depth - new_depth) << 1) as u32;
+          (
+            Hpx::<T>::uniq_hpx(new_depth, ipix.unsigned_shr(twice_dd)),
+            value,
+          )
+        } else {
+          (uniq, value)
+        }
+      })
+      .collect();
+    elems.sort_unstable_by_key(|(uniq, _)| *uniq);
+    Self::new(new_depth, sum_consecutive_duplicates(elems))
+  }
+
+  /// Resamples the MOM onto the cells of the given MOC: the value of each MOC cell is the sum of
+  /// the values of the MOM cells it overlaps, weighted by the fraction of the MOM cell area
+  /// it covers. MOM values outside the MOC are lost.
+  /// The output cells are sorted in Z-order and the output depth is the MOC depth.
+  pub fn resample_on_moc(&self, moc: &RangeMOC<T, Hpx<T>>) -> Self {
+    let mut mom: Vec<(Range<T>, V)> = self
+      .elems
+      .iter()
+      .map(|(uniq, value)| (Hpx::<T>::uniq_hpx_to_range(*uniq), *value))
+      .collect();
+    mom.sort_unstable_by_key(|(range, _)| range.start);
+    let mut elems = Vec::with_capacity(moc.moc_ranges().0 .0.len());
+    // Index of the first MOM cell possibly overlapping the current MOC cell
+    let mut i = 0;
+    for cell in moc.into_range_moc_iter().cells() {
+      let range = Hpx::<T>::uniq_hpx_to_range(Hpx::<T>::uniq_hpx(cell.depth, cell.idx));
+      while i < mom.len() && mom[i].0.end <= range.start {
+        i += 1;
+      }
+      let mut value = V::zero();
+      for (mom_range, mom_value) in mom[i..].iter() {
+        if mom_range.start >= range.end {
+          break;
+        }
+        let overlap = mom_range.end.min(range.end) - mom_range.start.max(range.start);
+        value +=
+          *mom_value * (overlap.cast_to_f64() / (mom_range.end - mom_range.start).cast_to_f64());
+      }
+      elems.push((Hpx::<T>::uniq_hpx(cell.depth, cell.idx), value));
+    }
+    Self::new(moc.depth_max(), elems)
+  }
+
+  /// Adaptively coarsens the MOM, recursively merging 4 sibling cells into their parent cell
+  /// when their values are uniform, i.e. when `max - min <= rel_tol * max` (values are supposed
+  /// to be positive). A null tolerance merges only siblings having the exact same value.
+  /// The value of a parent cell is the sum of the values of its sub-cells.
+  pub fn coarsen(self, rel_tol: f64) -> Self {
+    let mut elems = self.elems;
+    for depth in (1..=self.depth_max).rev() {
+      let (mut at_depth, mut others): (Vec<_>, Vec<_>) = elems
+        .into_iter()
+        .partition(|(uniq, _)| Hpx::<T>::from_uniq_hpx(*uniq).0 == depth);
+      // At a given depth, the uniq order is the NESTED order
+      at_depth.sort_unstable_by_key(|(uniq, _)| *uniq);
+      let three = <T as From<u8>>::from(3);
+      let mut i = 0;
+      while i < at_depth.len() {
+        let (uniq, _) = at_depth[i];
+        let siblings = &at_depth[i..at_depth.len().min(i + 4)];
+        if (uniq - Hpx::<T>::uniq_hpx(depth, T::zero())) & three == T::zero()
+          && siblings.len() == 4
+          && siblings[3].0 == uniq + three
+          && is_uniform(siblings, rel_tol)
+        {
+          let ipix = Hpx::<T>::from_uniq_hpx(uniq).1;
+          let mut value = V::zero();
+          for (_, v) in siblings {
+            value += *v;
+          }
+          others.push((Hpx::<T>::uniq_hpx(depth - 1, ipix.unsigned_shr(2)), value));
+          i += 4;
+        } else {
+          others.push(at_depth[i]);
+          i += 1;
+        }
+      }
+      elems = others;
+    }
+    Self::new(self.depth_max, elems)
+  }
+}
+
+impl<T: Idx, V: Value<T>> IntoIterator for MultiOrderMap<T, V> {
+  type Item = (T, V);
+  type IntoIter = HpxMomIter<T, Hpx<T>, V, IntoIter<(T, V)>>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.into_hpx_mom_iter()
+  }
+}
+
+/// Sums the values of consecutive elements having the same `uniq`.
+fn sum_consecutive_duplicates<T: Idx, V: Value<T>>(elems: Vec<(T, V)>) -> Vec<(T, V)> {
+  let mut res: Vec<(T, V)> = Vec::with_capacity(elems.len());
+  for (uniq, value) in elems {
+    match res.last_mut() {
+      Some((prev_uniq, prev_value)) if *prev_uniq == uniq => *prev_value += value,
+      _ => res.push((uniq, value)),
+    }
+  }
+  res
+}
+
+fn is_uniform<T: Idx, V: Value<T>>(elems: &[(T, V)], rel_tol: f64) -> bool {
+  let (mut min, mut max) = (elems[0].1, elems[0].1);
+  for (_, value) in &elems[1..] {
+    if *value < min {
+      min = *value;
+    }
+    if *value > max {
+      max = *value;
+    }
+  }
+  max - min <= max * rel_tol
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::{elemset::range::HpxRanges, mom::SKYMAP_DEPTH_MAX};
+
+  fn uniq(depth: u8, ipix: u64) -> u64 {
+    Hpx::<u64>::uniq_hpx(depth, ipix)
+  }
+
+  #[test]
+  fn test_mom_to_skymap_and_degrade() {
+    let mom = MultiOrderMap::new(
+      2,
+      vec![
+        (uniq(0, 0), 0.4),
+        (uniq(1, 4), 0.2),
+        (uniq(2, 20), 0.1),
+        (uniq(2, 23), 0.3),
+      ],
+    );
+    let skymap = mom.to_skymap(1).unwrap();
+    assert_eq!(skymap.len(), 48);
+    assert_eq!(&skymap[0..6], &[0.1, 0.1, 0.1, 0.1, 0.2, 0.4]);
+    assert!(skymap[6..].iter().all(|v| *v == 0.0));
+    assert!(mom.to_skymap(SKYMAP_DEPTH_MAX + 1).is_err());
+    let degraded = mom.degrade(1);
+    assert_eq!(degraded.depth_max(), 1);
+    assert_eq!(
+      degraded.elems(),
+      &[(uniq(0, 0), 0.4), (uniq(1, 4), 0.2), (uniq(1, 5), 0.4)]
+    );
+  }
+
+  #[test]
+  fn test_mom_resample_on_moc() {
+    let mom = MultiOrderMap::new(
+      1,
+      vec![(uniq(0, 0), 0.4), (uniq(1, 4), 0.2), (uniq(1, 5), 0.4)],
+    );
+    // MOC made of the depth 1 cell 0 and of the depth 0 cell 1
+    let shift = Hpx::<u64>::shift_from_depth_max(1);
+    let moc = RangeMOC::new(
+      1,
+      HpxRanges::new_unchecked(vec![0..1 << shift, 4 << shift..8 << shift]),
+    );
+    let resampled = mom.resample_on_moc(&moc);
+    assert_eq!(resampled.depth_max(), 1);
+    assert_eq!(resampled.len(), 2);
+    assert_eq!(resampled.elems()[0], (uniq(1, 0), 0.1));
+    assert_eq!(resampled.elems()[1].0, uniq(0, 1));
+    assert!((resampled.elems()[1].1 - 0.6).abs() < 1e-12);
+  }
+
+  #[test]
+  fn test_mom_coarsen() {
+    let mut values = vec![1.0; 48];
+    values[5] = 1.05;
+    values[8] = 2.0;
+    let mom = MultiOrderMap::<u64, f64>::from_skymap(1, values);
+    let mut coarsened = mom.clone().coarsen(0.0).into_elems();
+    coarsened.sort_by_key(|(uniq, _)| *uniq);
+    let mut expected: Vec<(u64, f64)> = (0..12)
+      .filter(|ipix| *ipix != 1 && *ipix != 2)
+      .map(|ipix| (uniq(0, ipix), 4.0))
+      .collect();
+    expected.extend((4..12).map(|ipix| (uniq(1, ipix), 1.0)));
+    expected[11].1 = 1.05;
+    expected[14].1 = 2.0;
+    expected.sort_by_key(|(uniq, _)| *uniq);
+    assert_eq!(coarsened, expected);
+    // Tolerance of 10%: 1.05 merged but not 2.0
+    let coarsened = mom.clone().coarsen(0.1);
+    assert_eq!(coarsened.len(), 15);
+    // Tolerance of 50%: all sky at depth 0
+    let coarsened = mom.coarsen(0.5);
+    assert_eq!(coarsened.len(), 12);
+    let sum: f64 = coarsened.iter().map(|(_, v)| v).sum();
+    assert!((sum - 49.05).abs() < 1e-12);
+  }
+}
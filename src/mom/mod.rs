@@ -14,6 +14,7 @@ use std::{
   f64::{self, consts::FRAC_PI_3},
   marker::PhantomData,
   ops::{AddAssign, Mul},
+  vec::IntoIter,
 };
 
 use num::Num;
@@ -24,6 +25,21 @@ use crate::{
   qty::{Hpx, MocQty},
 };
 
+pub mod map;
+pub mod op;
+pub mod stats;
+
+use self::op::{HpxMomBinOp, ValueKind};
+
+/// Maximum depth of the skymaps built by [HpxMOMIterator::to_skymap], i.e. `12 * 4^13` cells
+/// (about 6.4 GB of `f64` values).
+pub const SKYMAP_DEPTH_MAX: u8 = 13;
+
+/// Returns the area, in steradians, of a HEALPix cell at the given depth.
+pub fn cell_area(depth: u8) -> f64 {
+  FRAC_PI_3 / (1_u64 << (depth << 1) as u32) as f64 // = 4pi / (12*4^depth)
+}
+
 // 'static mean that Idx does not contains any reference
 pub trait Value<T: Idx>:
   'static
@@ -74,6 +90,155 @@ pub trait HpxMOMIterator<T: Idx, V: Value<T>>: MOMIterator<T, Hpx<T>, V> {
   ) -> HpxMOMFilter<'_, T, V, Self> {
     HpxMOMFilter::new(self, moc)
   }
+
+  /// Returns the `(uniq, value)` pair of the MOM cell containing the given position
+  /// (in radians), or `None` if no cell contains the position.
+  fn value_at(mut self, lon: f64, lat: f64) -> Option<(T, V)> {
+    self.find(|(uniq, _)| {
+      let (depth, ipix) = Hpx::<T>::from_uniq_hpx(*uniq);
+      T::from_u64(healpix::nested::hash(depth, lon, lat)) == ipix
+    })
+  }
+
+  /// Resamples the MOM at the given fixed depth, returning the values of the
+  /// `12 * 4^depth` HEALPix cells in the `NESTED` order (i.e. a skymap).
+  /// The value of a MOM cell of depth lower than `depth` is evenly distributed among its
+  /// sub-cells while the values of MOM cells of depth larger than `depth` are summed in their
+  /// super-cell. Cells not in the MOM are set to 0.
+  ///
+  /// # Errors
+  /// If `depth` is larger than [SKYMAP_DEPTH_MAX].
+  fn to_skymap(self, depth: u8) -> Result<Vec<V>, String> {
+    if depth > SKYMAP_DEPTH_MAX {
+      return Err(format!(
+        "Skymap depth {} larger than the maximum depth {}.",
+        depth, SKYMAP_DEPTH_MAX
+      ));
+    }
+    let n_cells = Hpx::<T>::n_cells(depth).to_usize().unwrap();
+    let mut values = vec![V::zero(); n_cells];
+    for (uniq, value) in self {
+      let (cdepth, ipix) = Hpx::<T>::from_uniq_hpx(uniq);
+      if cdepth <= depth {
+        let twice_dd = ((depth - cdepth) << 1) as u32;
+        let n_sub_cells = 1_usize << twice_dd;
+        let sub_value = value * (1.0 / n_sub_cells as f64);
+        let from = ipix.unsigned_shl(twice_dd).to_usize().unwrap();
+        for v in &mut values[from..from + n_sub_cells] {
+          *v += sub_value;
+        }
+      } else {
+        let twice_dd = ((cdepth - depth) << 1) as u32;
+        values[ipix.unsigned_shr(twice_dd).to_usize().unwrap()] += value;
+      }
+    }
+    Ok(values)
+  }
+
+  /// Collects the MOM and sorts it in Z-order (i.e. according to the first deepest sub-cell of
+  /// each cell), the order required by the binary operations.
+  fn into_zsorted(self) -> HpxMomIter<T, Hpx<T>, V, IntoIter<(T, V)>> {
+    let mut elems: Vec<(T, V)> = self.collect();
+    elems.sort_by_key(|(uniq, _)| Hpx::<T>::uniq_hpx_to_range(*uniq).start);
+    HpxMomIter::new(elems.into_iter())
+  }
+
+  /// Performs, in streaming mode, a binary operation between this MOM and the given MOM, both
+  /// being sorted in Z-order (see [HpxMOMIterator::into_zsorted]).
+  /// Cells of different depths are aligned by splitting the largest cell according to the
+  /// given value `kind`, and a cell present in a single MOM is associated with a zero value
+  /// in the other MOM. The result is also sorted in Z-order.
+  ///
+  /// # Params
+  /// * `rhs`: the right hand side MOM
+  /// * `kind`: the nature of the MOMs values
+  /// * `op`: the operation, taking the cell depth, the left and the right values
+  ///
+  /// # Panics
+  /// While iterating, if a MOM is not sorted in Z-order or contains overlapping cells.
+  fn binary_op<I, F>(self, rhs: I, kind: ValueKind, op: F) -> HpxMomBinOp<T, V, Self, I, F>
+  where
+    I: HpxMOMIterator<T, V>,
+    F: FnMut(u8, V, V) -> V,
+  {
+    HpxMomBinOp::new(self, rhs, kind, op)
+  }
+
+  /// Sums the values of this MOM and of the given MOM, see [HpxMOMIterator::binary_op].
+  #[allow(clippy::type_complexity)]
+  fn add<I>(self, rhs: I, kind: ValueKind) -> HpxMomBinOp<T, V, Self, I, fn(u8, V, V) -> V>
+  where
+    I: HpxMOMIterator<T, V>,
+  {
+    self.binary_op(rhs, kind, |_, l, r| l + r)
+  }
+
+  /// Multiplies the densities of this MOM by the densities of the given MOM, see
+  /// [HpxMOMIterator::binary_op] (e.g. to combine the probability maps of several detectors).
+  /// In the `AreaProportional` case, the result is the product of the densities multiplied by the
+  /// cell area (so that the result values are also proportional to the area).
+  #[allow(clippy::type_complexity)]
+  fn multiply<I>(self, rhs: I, kind: ValueKind) -> HpxMomBinOp<T, V, Self, I, fn(u8, V, V) -> V>
+  where
+    I: HpxMOMIterator<T, V>,
+  {
+    match kind {
+      ValueKind::AreaProportional => {
+        self.binary_op(rhs, kind, |depth, l, r| l * r * (1.0 / cell_area(depth)))
+      }
+      ValueKind::Density => self.binary_op(rhs, kind, |_, l, r| l * r),
+    }
+  }
+
+  /// Multiplies all the values by the given factor.
+  fn scale(self, factor: f64) -> HpxMomIter<T, Hpx<T>, V, impl Iterator<Item = (T, V)>> {
+    HpxMomIter::new(self.map(move |(uniq, value)| (uniq, value * factor)))
+  }
+
+  /// Collects the MOM and divides its values by its integral (i.e. the sum of the values in the
+  /// `AreaProportional` case, or the sum of the densities multiplied by the cell areas in the
+  /// `Density` case) so that the integral of the result equals 1.
+  /// The MOM is left unchanged if its integral is null.
+  fn normalized(self, kind: ValueKind) -> HpxMomIter<T, Hpx<T>, V, IntoIter<(T, V)>> {
+    let mut elems: Vec<(T, V)> = self.collect();
+    let mut integral = V::zero();
+    for (uniq, value) in &elems {
+      integral += match kind {
+        ValueKind::AreaProportional => *value,
+        ValueKind::Density => *value * cell_area(Hpx::<T>::from_uniq_hpx(*uniq).0),
+      };
+    }
+    if integral != V::zero() {
+      for (_, value) in elems.iter_mut() {
+        *value = *value / integral;
+      }
+    }
+    HpxMomIter::new(elems.into_iter())
+  }
+
+  /// Transforms values proportional to the cell area into densities, dividing them by the cell
+  /// area (in steradians).
+  fn to_densities(self) -> HpxMomIter<T, Hpx<T>, V, impl Iterator<Item = (T, V)>> {
+    HpxMomIter::new(self.map(|(uniq, value)| {
+      let depth = Hpx::<T>::from_uniq_hpx(uniq).0;
+      (uniq, value * (1.0 / cell_area(depth)))
+    }))
+  }
+
+  /// Transforms densities into values proportional to the cell area, multiplying them by the
+  /// cell area (in steradians).
+  fn to_area_proportional(self) -> HpxMomIter<T, Hpx<T>, V, impl Iterator<Item = (T, V)>> {
+    HpxMomIter::new(self.map(|(uniq, value)| {
+      let depth = Hpx::<T>::from_uniq_hpx(uniq).0;
+      (uniq, value * cell_area(depth))
+    }))
+  }
+
+  /// Keeps only the cells having a value larger than or equal to the given threshold
+  /// (to be applied on densities to get a threshold independent of the cells depth).
+  fn threshold(self, min: V) -> HpxMomIter<T, Hpx<T>, V, impl Iterator<Item = (T, V)>> {
+    HpxMomIter::new(self.filter(move |(_, value)| *value >= min))
+  }
 }
 
 pub struct HpxMomIter<T: Idx, Q: MocQty<T>, V: Value<T>, I: Sized + Iterator<Item = (T, V)>> {
@@ -150,8 +315,7 @@ where
       let (depth, ipix) = Hpx::<T>::from_uniq_hpx(uniq);
       let cell_fraction = self.moc.cell_fraction(depth, ipix);
       if cell_fraction > 0.0 {
-        let cell_area = FRAC_PI_3 / (1_u64 << (depth << 1) as u32) as f64;
-        return Some((val, cell_area * cell_fraction));
+        return Some((val, cell_area(depth) * cell_fraction));
       }
     }
     None
@@ -161,3 +325,17 @@ where
     (0, self.it.size_hint().1)
   }
 }
+
+/// Builds a MOM from `(depth, ipix, value)` cells, for the tests of the `mom` sub-modules.
+#[cfg(test)]
+pub(crate) fn mom_from_cells(
+  elems: Vec<(u8, u64, f64)>,
+) -> HpxMomIter<u64, Hpx<u64>, f64, IntoIter<(u64, f64)>> {
+  HpxMomIter::new(
+    elems
+      .into_iter()
+      .map(|(depth, ipix, val)| (Hpx::<u64>::uniq_hpx(depth, ipix), val))
+      .collect::<Vec<_>>()
+      .into_iter(),
+  )
+}
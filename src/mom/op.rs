@@ -0,0 +1,303 @@
+//! Operations on HEALPix multi-order maps (MOMs).
+//! Binary operations are performed in streaming mode on MOMs sorted in Z-order (i.e. according
+//! to the first deepest sub-cell of each cell, see [HpxMOMIterator::into_zsorted]), cells of
+//! different depths being aligned by splitting the largest cell into its sub-cells.
+
+use std::{marker::PhantomData, ops::Range};
+
+use crate::{
+  idx::Idx,
+  mom::{HpxMOMIterator, MOMIterator, Value},
+  qty::Hpx,
+};
+
+/// Nature of the values of a multi-order map, telling how the value of a cell is split
+/// into the values of its 4 sub-cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+  /// Values proportional to the cell area (e.g. probabilities), divided by 4 when splitting
+  AreaProportional,
+  /// Values independent of the cell area (e.g. probability densities), copied when splitting
+  Density,
+}
+
+impl ValueKind {
+  fn split<T: Idx, V: Value<T>>(&self, value: V) -> V {
+    match self {
+      ValueKind::AreaProportional => value * 0.25,
+      ValueKind::Density => value,
+    }
+  }
+}
+
+/// Returns the range, at the HEALPix max depth, covered by the cell of given UNIQ index.
+fn zrange<T: Idx>(uniq: T) -> Range<T> {
+  Hpx::<T>::uniq_hpx_to_range(uniq)
+}
+
+/// Performs, in streaming mode, a binary operation on two multi-order maps sorted in Z-order,
+/// see [HpxMOMIterator::binary_op].
+///
+/// # Panics
+/// If an input MOM is not sorted in Z-order or contains overlapping cells.
+pub struct HpxMomBinOp<T, V, I1, I2, F>
+where
+  T: Idx,
+  V: Value<T>,
+  I1: HpxMOMIterator<T, V>,
+  I2: HpxMOMIterator<T, V>,
+  F: FnMut(u8, V, V) -> V,
+{
+  left_it: I1,
+  right_it: I2,
+  /// Sub-cells waiting to be processed, the next one being at the top of the stack
+  left_stack: Vec<(T, V)>,
+  right_stack: Vec<(T, V)>,
+  /// End of the range covered by the last cell read from each input MOM (to check the Z-order)
+  left_end: T,
+  right_end: T,
+  kind: ValueKind,
+  op: F,
+  _phantom: PhantomData<V>,
+}
+
+impl<T, V, I1, I2, F> HpxMomBinOp<T, V, I1, I2, F>
+where
+  T: Idx,
+  V: Value<T>,
+  I1: HpxMOMIterator<T, V>,
+  I2: HpxMOMIterator<T, V>,
+  F: FnMut(u8, V, V) -> V,
+{
+  pub fn new(left_it: I1, right_it: I2, kind: ValueKind, op: F) -> Self {
+    Self {
+      left_it,
+      right_it,
+      left_stack: Vec::new(),
+      right_stack: Vec::new(),
+      left_end: T::zero(),
+      right_end: T::zero(),
+      kind,
+      op,
+      _phantom: PhantomData,
+    }
+  }
+
+  /// Returns the next cell of the given MOM, checking that it is sorted in Z-order and that it
+  /// does not overlap the previous cell.
+  fn next_zsorted<I: HpxMOMIterator<T, V>>(
+    it: &mut I,
+    prev_end: &mut T,
+    side: &str,
+  ) -> Option<(T, V)> {
+    let (uniq, value) = it.next()?;
+    let range = zrange(uniq);
+    if range.start < *prev_end {
+      panic!(
+        "The {} MOM is not sorted in Z-order or contains overlapping cells (UNIQ {}). {}",
+        side, uniq, "Use `into_zsorted` to sort it."
+      );
+    }
+    *prev_end = range.end;
+    Some((uniq, value))
+  }
+
+  /// Splits the given cell and push its sub-cells on the given stack.
+  fn split(stack: &mut Vec<(T, V)>, kind: ValueKind, (uniq, value): (T, V)) {
+    let value = kind.split::<T, V>(value);
+    let (depth, ipix) = Hpx::<T>::from_uniq_hpx(uniq);
+    let first_child = ipix.unsigned_shl(2);
+    for k in (0..4_u8).rev() {
+      let child = Hpx::<T>::uniq_hpx(depth + 1, first_child + <T as From<u8>>::from(k));
+      stack.push((child, value));
+    }
+  }
+}
+
+impl<T, V, I1, I2, F> Iterator for HpxMomBinOp<T, V, I1, I2, F>
+where
+  T: Idx,
+  V: Value<T>,
+  I1: HpxMOMIterator<T, V>,
+  I2: HpxMOMIterator<T, V>,
+  F: FnMut(u8, V, V) -> V,
+{
+  type Item = (T, V);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      let left = self
+        .left_stack
+        .pop()
+        .or_else(|| Self::next_zsorted(&mut self.left_it, &mut self.left_end, "left"));
+      let right = self
+        .right_stack
+        .pop()
+        .or_else(|| Self::next_zsorted(&mut self.right_it, &mut self.right_end, "right"));
+      match (left, right) {
+        (None, None) => return None,
+        (Some((uniq, l)), None) => {
+          let depth = Hpx::<T>::from_uniq_hpx(uniq).0;
+          return Some((uniq, (self.op)(depth, l, V::zero())));
+        }
+        (None, Some((uniq, r))) => {
+          let depth = Hpx::<T>::from_uniq_hpx(uniq).0;
+          return Some((uniq, (self.op)(depth, V::zero(), r)));
+        }
+        (Some((luniq, l)), Some((runiq, r))) => {
+          let (lrange, rrange) = (zrange(luniq), zrange(runiq));
+          if lrange.end <= rrange.start {
+            self.right_stack.push((runiq, r));
+            let depth = Hpx::<T>::from_uniq_hpx(luniq).0;
+            return Some((luniq, (self.op)(depth, l, V::zero())));
+          } else if rrange.end <= lrange.start {
+            self.left_stack.push((luniq, l));
+            let depth = Hpx::<T>::from_uniq_hpx(runiq).0;
+            return Some((runiq, (self.op)(depth, V::zero(), r)));
+          } else if luniq == runiq {
+            let depth = Hpx::<T>::from_uniq_hpx(luniq).0;
+            return Some((luniq, (self.op)(depth, l, r)));
+          } else if lrange.end - lrange.start > rrange.end - rrange.start {
+            // The left cell contains the right cell
+            self.right_stack.push((runiq, r));
+            Self::split(&mut self.left_stack, self.kind, (luniq, l));
+          } else {
+            // The right cell contains the left cell
+            self.left_stack.push((luniq, l));
+            Self::split(&mut self.right_stack, self.kind, (runiq, r));
+          }
+        }
+      }
+    }
+  }
+}
+
+impl<T, V, I1, I2, F> MOMIterator<T, Hpx<T>, V> for HpxMomBinOp<T, V, I1, I2, F>
+where
+  T: Idx,
+  V: Value<T>,
+  I1: HpxMOMIterator<T, V>,
+  I2: HpxMOMIterator<T, V>,
+  F: FnMut(u8, V, V) -> V,
+{
+}
+
+impl<T, V, I1, I2, F> HpxMOMIterator<T, V> for HpxMomBinOp<T, V, I1, I2, F>
+where
+  T: Idx,
+  V: Value<T>,
+  I1: HpxMOMIterator<T, V>,
+  I2: HpxMOMIterator<T, V>,
+  F: FnMut(u8, V, V) -> V,
+{
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::mom::{cell_area, mom_from_cells};
+
+  fn cells(it: impl Iterator<Item = (u64, f64)>) -> Vec<(u8, u64, f64)> {
+    it.map(|(uniq, val)| {
+      let (depth, ipix) = Hpx::<u64>::from_uniq_hpx(uniq);
+      (depth, ipix, val)
+    })
+    .collect()
+  }
+
+  #[test]
+  fn test_mom_add() {
+    let left = mom_from_cells(vec![(0, 0, 4.0), (1, 4, 1.0)]);
+    let right = mom_from_cells(vec![(1, 1, 2.0), (0, 1, 8.0), (2, 50, 1.0)]);
+    let actual = cells(left.add(right, ValueKind::AreaProportional));
+    assert_eq!(
+      actual,
+      vec![
+        (1, 0, 1.0),
+        (1, 1, 3.0),
+        (1, 2, 1.0),
+        (1, 3, 1.0),
+        (1, 4, 3.0),
+        (1, 5, 2.0),
+        (1, 6, 2.0),
+        (1, 7, 2.0),
+        (2, 50, 1.0),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_mom_multiply() {
+    // Uniform densities of 2 and 3 => product equals 6 everywhere both maps are defined
+    let left = mom_from_cells(vec![(0, 0, 2.0 * cell_area(0))]);
+    let right = mom_from_cells(vec![(1, 2, 3.0 * cell_area(1)), (1, 4, 3.0 * cell_area(1))]);
+    let actual = cells(
+      left
+        .multiply(right, ValueKind::AreaProportional)
+        .to_densities(),
+    );
+    let expected = [
+      (1, 0, 0.0),
+      (1, 1, 0.0),
+      (1, 2, 6.0),
+      (1, 3, 0.0),
+      (1, 4, 0.0),
+    ];
+    assert_eq!(actual.len(), expected.len());
+    for ((d1, i1, v1), (d2, i2, v2)) in actual.into_iter().zip(expected) {
+      assert_eq!((d1, i1), (d2, i2));
+      assert!((v1 - v2).abs() < 1e-12);
+    }
+    let left = mom_from_cells(vec![(0, 0, 2.0)]);
+    let right = mom_from_cells(vec![(1, 2, 3.0)]);
+    let actual = cells(left.multiply(right, ValueKind::Density));
+    assert_eq!(
+      actual,
+      vec![(1, 0, 0.0), (1, 1, 0.0), (1, 2, 6.0), (1, 3, 0.0)]
+    );
+  }
+
+  #[test]
+  #[should_panic(expected = "not sorted in Z-order")]
+  fn test_mom_add_not_zsorted() {
+    let left = mom_from_cells(vec![(0, 0, 4.0)]);
+    // Cell 1/3 is inside cell 0/0 which comes first in Z-order
+    let right = mom_from_cells(vec![(1, 3, 1.0), (0, 0, 8.0)]);
+    let _ = cells(left.add(right, ValueKind::AreaProportional));
+  }
+
+  #[test]
+  #[should_panic(expected = "overlapping cells")]
+  fn test_mom_add_overlapping() {
+    let left = mom_from_cells(vec![(0, 0, 4.0), (1, 3, 1.0)]);
+    let right = mom_from_cells(vec![(0, 1, 8.0)]);
+    let _ = cells(left.add(right, ValueKind::AreaProportional));
+  }
+
+  #[test]
+  fn test_mom_unary_ops() {
+    let m = mom_from_cells(vec![(1, 3, 1.0), (0, 1, 3.0)]);
+    let actual = cells(
+      m.into_zsorted()
+        .scale(2.0)
+        .normalized(ValueKind::AreaProportional),
+    );
+    assert_eq!(actual, vec![(1, 3, 0.25), (0, 1, 0.75)]);
+    let m = mom_from_cells(vec![(1, 3, 1.0), (0, 1, 3.0)]);
+    assert_eq!(cells(m.threshold(2.0)), vec![(0, 1, 3.0)]);
+    let m = mom_from_cells(vec![(0, 1, 3.0), (1, 3, 1.0)]);
+    let densities = cells(m.normalized(ValueKind::Density));
+    let integral: f64 = densities
+      .iter()
+      .map(|(depth, _, dens)| dens * cell_area(*depth))
+      .sum();
+    assert!((integral - 1.0).abs() < 1e-12);
+    let m = mom_from_cells(vec![(0, 1, 3.0), (1, 3, 1.0)]);
+    let back = cells(m.to_densities().to_area_proportional());
+    assert!(back
+      .iter()
+      .zip([3.0, 1.0])
+      .all(|((_, _, actual), expected)| (actual - expected).abs() < 1e-12));
+  }
+}
@@ -0,0 +1,217 @@
+//! Statistics of the densities of a HEALPix multi-order map (MOM) inside a MOC.
+//! The MOM values being proportional to the cell area (see [crate::mom]), statistics are
+//! computed on the densities (i.e. the values divided by the cell area), each cell being
+//! weighted by its area covered by the MOC.
+
+use std::cmp::Ordering::Equal;
+
+use crate::{
+  idx::Idx,
+  moc::{range::RangeMOC, CellMOCIntoIterator, CellMOCIterator, RangeMOCIterator},
+  mom::{cell_area, HpxMOMIterator, HpxMomIter},
+  qty::Hpx,
+};
+
+/// Area weighted statistics of the densities of a MOM inside a MOC,
+/// see [MomStats::from_mom_in_moc].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MomStats {
+  /// Number of MOM cells overlapping the MOC
+  pub n_cells: usize,
+  /// Area, in steradians, of the MOM cells parts covered by the MOC
+  pub area: f64,
+  /// Sum of the MOM values, weighted by the fraction of each cell covered by the MOC
+  /// (see [HpxMOMIterator::sum_values_in_hpxmoc])
+  pub sum: f64,
+  /// Area weighted mean density, i.e. `sum / area`
+  pub mean: f64,
+  /// Minimum density
+  pub min: f64,
+  /// Maximum density
+  pub max: f64,
+  /// Area weighted median density
+  pub median: f64,
+  /// Requested area weighted quantiles, as `(quantile, density)` pairs
+  pub quantiles: Vec<(f64, f64)>,
+}
+
+impl MomStats {
+  /// Computes the statistics of the MOM densities inside the given MOC.
+  /// Returns `None` if no MOM cell overlaps the MOC.
+  ///
+  /// # Params
+  /// * `mom`: the multi-order map, values being proportional to the cell area
+  /// * `moc`: the MOC the MOM is restricted to
+  /// * `quantiles`: the area weighted quantiles to be computed, each one in `[0, 1]`
+  pub fn from_mom_in_moc<T, I>(mom: I, moc: &RangeMOC<T, Hpx<T>>, quantiles: &[f64]) -> Option<Self>
+  where
+    T: Idx,
+    I: HpxMOMIterator<T, f64>,
+  {
+    // (density, weight) pairs
+    let mut dens_weights: Vec<(f64, f64)> = Vec::new();
+    let mut sum = 0_f64;
+    for (uniq, value) in mom {
+      let (depth, ipix) = Hpx::<T>::from_uniq_hpx(uniq);
+      let cell_fraction = moc.cell_fraction(depth, ipix);
+      if cell_fraction > 0.0 {
+        let area = cell_area(depth);
+        sum += value * cell_fraction;
+        dens_weights.push((value / area, area * cell_fraction));
+      }
+    }
+    if dens_weights.is_empty() {
+      return None;
+    }
+    dens_weights.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Equal));
+    let area: f64 = dens_weights.iter().map(|(_, weight)| weight).sum();
+    Some(Self {
+      n_cells: dens_weights.len(),
+      area,
+      sum,
+      mean: sum / area,
+      min: dens_weights[0].0,
+      max: dens_weights[dens_weights.len() - 1].0,
+      median: weighted_quantile(&dens_weights, area, 0.5),
+      quantiles: quantiles
+        .iter()
+        .map(|q| (*q, weighted_quantile(&dens_weights, area, *q)))
+        .collect(),
+    })
+  }
+}
+
+/// Computes the statistics of the MOM densities inside each connected component of the given
+/// MOC (see [RangeMOC::split_into_joint_mocs]).
+/// Returns the components, together with their statistics (`None` if no MOM cell overlaps
+/// the component).
+///
+/// # Params
+/// * `mom`: the multi-order map, values being proportional to the cell area
+/// * `moc`: the MOC to be split into connected components
+/// * `include_indirect_neighbours`: connect cells sharing only a vertex (8-neighbours)
+/// * `quantiles`: the area weighted quantiles to be computed, each one in `[0, 1]`
+#[allow(clippy::type_complexity)]
+pub fn mom_stats_per_component<T, I>(
+  mom: I,
+  moc: &RangeMOC<T, Hpx<T>>,
+  include_indirect_neighbours: bool,
+  quantiles: &[f64],
+) -> Vec<(RangeMOC<T, Hpx<T>>, Option<MomStats>)>
+where
+  T: Idx,
+  I: HpxMOMIterator<T, f64>,
+{
+  let elems: Vec<(T, f64)> = mom.collect();
+  moc
+    .split_into_joint_mocs(include_indirect_neighbours)
+    .into_iter()
+    .map(|cell_moc| {
+      let component = cell_moc.into_cell_moc_iter().ranges().into_range_moc();
+      let stats = MomStats::from_mom_in_moc(
+        HpxMomIter::<T, Hpx<T>, f64, _>::new(elems.iter().copied()),
+        &component,
+        quantiles,
+      );
+      (component, stats)
+    })
+    .collect()
+}
+
+/// Returns the smallest density for which the cumulative weight reaches `q` times the total
+/// weight, the input `(density, weight)` pairs being sorted by ascending density.
+fn weighted_quantile(sorted_dens_weights: &[(f64, f64)], total_weight: f64, q: f64) -> f64 {
+  let target = q * total_weight;
+  let mut cumul = 0_f64;
+  for (dens, weight) in sorted_dens_weights {
+    cumul += weight;
+    if cumul >= target {
+      return *dens;
+    }
+  }
+  sorted_dens_weights[sorted_dens_weights.len() - 1].0
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::{elemset::range::HpxRanges, mom::mom_from_cells, qty::MocQty};
+
+  #[test]
+  #[allow(clippy::single_range_in_vec_init)]
+  fn test_mom_stats() {
+    let shift = Hpx::<u64>::shift_from_depth_max(1);
+    // Base cell 0 (densities 1, 2, 3 and 4 at depth 1) and a quarter of base cell 1 (density 10)
+    let elems = vec![
+      (1, 0, 1.0),
+      (1, 1, 2.0),
+      (1, 2, 3.0),
+      (1, 3, 4.0),
+      (0, 1, 10.0),
+      (0, 2, 100.0),
+    ];
+    let moc = RangeMOC::new(1, HpxRanges::new_unchecked(vec![0..5 << shift]));
+    let stats = MomStats::from_mom_in_moc(
+      mom_from_cells(elems.clone()).to_area_proportional(),
+      &moc,
+      &[0.25, 1.0],
+    )
+    .unwrap();
+    let a1 = cell_area(1);
+    assert_eq!(stats.n_cells, 5);
+    assert!((stats.area - 5.0 * a1).abs() < 1e-12);
+    assert!((stats.sum - 20.0 * a1).abs() < 1e-12);
+    assert!((stats.mean - 4.0).abs() < 1e-12);
+    assert_eq!(stats.min, 1.0);
+    assert_eq!(stats.max, 10.0);
+    assert_eq!(stats.median, 3.0);
+    assert_eq!(stats.quantiles, vec![(0.25, 2.0), (1.0, 10.0)]);
+    // No overlap
+    let moc = RangeMOC::new(1, HpxRanges::new_unchecked(vec![40 << shift..41 << shift]));
+    assert_eq!(
+      MomStats::from_mom_in_moc(
+        mom_from_cells(elems.clone()).to_area_proportional(),
+        &moc,
+        &[]
+      ),
+      None
+    );
+    // Value at a position in base cell 1
+    let (uniq, value) = mom_from_cells(elems)
+      .to_area_proportional()
+      .value_at(135_f64.to_radians(), 45_f64.to_radians())
+      .unwrap();
+    assert_eq!(uniq, Hpx::<u64>::uniq_hpx(0, 1));
+    assert!((value / cell_area(0) - 10.0).abs() < 1e-12);
+  }
+
+  #[test]
+  fn test_mom_stats_per_component() {
+    let shift = Hpx::<u64>::shift_from_depth_max(1);
+    let elems = vec![(0, 0, 1.0), (0, 1, 2.0)];
+    // 3 disjoint cells in base cells 0, 1 and 10 (the first sub-cells are not neighbours)
+    let moc = RangeMOC::new(
+      1,
+      HpxRanges::new_unchecked(vec![
+        0..1 << shift,
+        4 << shift..5 << shift,
+        40 << shift..41 << shift,
+      ]),
+    );
+    let res = mom_stats_per_component(
+      mom_from_cells(elems).to_area_proportional(),
+      &moc,
+      false,
+      &[],
+    );
+    assert_eq!(res.len(), 3);
+    let means: Vec<Option<f64>> = res
+      .iter()
+      .map(|(_, stats)| stats.as_ref().map(|s| s.mean))
+      .collect();
+    assert!(means.contains(&Some(1.0)));
+    assert!(means.contains(&Some(2.0)));
+    assert!(means.contains(&None));
+  }
+}
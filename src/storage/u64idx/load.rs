@@ -24,6 +24,7 @@ pub(crate) fn from_fits_gen<T: Idx, R: BufRead>(
     MocQtyType::Freq(moc) => from_fits_freq(moc),
     MocQtyType::TimeHpx(_) => Err(String::from("Only u64 ST-MOCs supported").into()),
     MocQtyType::FreqHpx(_) => Err(String::from("Only u64 SF-MOCs supported").into()),
+    MocQtyType::TimeFreq(_) => Err(String::from("TF-MOCs not supported in the MOC store").into()),
   }
 }
 
@@ -44,6 +45,9 @@ pub(crate) fn smoc_from_fits_gen<T: Idx, R: BufRead>(
     MocQtyType::FreqHpx(_) => {
       Err(String::from("Wrong MOC type. Expected: S-MOCs. Actual: SF-MOC").into())
     }
+    MocQtyType::TimeFreq(_) => {
+      Err(String::from("Wrong MOC type. Expected: S-MOCs. Actual: TF-MOC").into())
+    }
   }
 }
 
@@ -64,6 +68,9 @@ pub(crate) fn tmoc_from_fits_gen<T: Idx, R: BufRead>(
     MocQtyType::FreqHpx(_) => {
       Err(String::from("Wrong MOC type. Expected: T-MOCs. Actual: SF-MOC").into())
     }
+    MocQtyType::TimeFreq(_) => {
+      Err(String::from("Wrong MOC type. Expected: T-MOCs. Actual: TF-MOC").into())
+    }
   }
 }
 
@@ -84,6 +91,9 @@ pub(crate) fn fmoc_from_fits_gen<T: Idx, R: BufRead>(
     MocQtyType::FreqHpx(_) => {
       Err(String::from("Wrong MOC type. Expected: F-MOCs. Actual: ST-MOC").into())
     }
+    MocQtyType::TimeFreq(_) => {
+      Err(String::from("Wrong MOC type. Expected: F-MOCs. Actual: TF-MOC").into())
+    }
   }
 }
 
@@ -104,6 +114,9 @@ pub(crate) fn stmoc_from_fits_u64<R: BufRead>(
     MocQtyType::FreqHpx(_) => {
       Err(String::from("Wrong MOC type. Expected: ST-MOCs. Actual: SF-MOC").into())
     }
+    MocQtyType::TimeFreq(_) => {
+      Err(String::from("Wrong MOC type. Expected: ST-MOCs. Actual: TF-MOC").into())
+    }
   }
 }
 
@@ -124,6 +137,9 @@ pub(crate) fn sfmoc_from_fits_u64<R: BufRead>(
       Err(String::from("Wrong MOC type. Expected: SF-MOCs. Actual: ST-MOC").into())
     }
     MocQtyType::FreqHpx(moc2) => from_fits_spacefreq(moc2),
+    MocQtyType::TimeFreq(_) => {
+      Err(String::from("Wrong MOC type. Expected: SF-MOCs. Actual: TF-MOC").into())
+    }
   }
 }
 
@@ -138,6 +154,7 @@ pub(crate) fn from_fits_u64<R: BufRead>(
     MocQtyType::Freq(moc) => from_fits_freq(moc),
     MocQtyType::TimeHpx(moc2) => from_fits_spacetime(moc2),
     MocQtyType::FreqHpx(moc2) => from_fits_spacefreq(moc2),
+    MocQtyType::TimeFreq(_) => Err(String::from("TF-MOCs not supported in the MOC store").into()),
   }
 }
 
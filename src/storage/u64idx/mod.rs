@@ -44,6 +44,7 @@ use crate::{
     range::RangeMOC2, CellMOC2IntoIterator, CellOrCellRangeMOC2IntoIterator, RangeMOC2IntoIterator,
     RangeMOC2Iterator,
   },
+  mom::cell_area,
   qty::{Frequency, Hpx, MocQty, Time},
   storage::u64idx::op1::{
     op1_mom_filter, op1_mom_filter_mask, op1_mom_sum, op1_mom_sum_from_data, op1_mom_sum_from_path,
@@ -493,6 +494,7 @@ impl U64MocStore {
     let reader = BufReader::new(file);
     from_fits_multiordermap(
       reader,
+      None,
       from_threshold,
       to_threshold,
       asc,
@@ -525,6 +527,7 @@ impl U64MocStore {
   ) -> Result<usize, String> {
     from_fits_multiordermap(
       BufReader::new(Cursor::new(data)),
+      None,
       from_threshold,
       to_threshold,
       asc,
@@ -561,6 +564,7 @@ impl U64MocStore {
     let reader = BufReader::new(file);
     from_fits_skymap(
       reader,
+      None,
       skip_values_le,
       from_threshold,
       to_threshold,
@@ -596,6 +600,7 @@ impl U64MocStore {
   ) -> Result<usize, String> {
     from_fits_skymap(
       BufReader::new(Cursor::new(data)),
+      None,
       skip_values_le,
       from_threshold,
       to_threshold,
@@ -1495,7 +1500,7 @@ impl U64MocStore {
     if to_threshold < from_threshold {
       return Err(String::from("`cumul_from` has to be < to `cumul_to`."));
     }
-    let area_per_cell = (PI / 3.0) / (1_u64 << (depth << 1) as u32) as f64; // = 4pi / (12*4^depth)
+    let area_per_cell = cell_area(depth);
     let ranges: HpxRanges<u64> = if density {
       valued_cells_to_moc_with_opt::<u64, f64>(
         depth,